@@ -0,0 +1,236 @@
+//! Native reference client built on Bevy.
+//!
+//! Consumes the `shared` protocol types directly, interpolates between
+//! server snapshots, and provides level-up and scoreboard screens.
+
+mod net;
+mod snapshot;
+mod ui;
+mod world;
+
+#[cfg(test)]
+mod tests;
+
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use shared::{
+    ClientMessage, Position, RunStats, ScoreEntry, ServerMessage, UpgradeText, UpgradeType,
+};
+use uuid::Uuid;
+
+use net::ServerConnection;
+use snapshot::{Snapshot, SnapshotBuffer};
+
+/// Minimum delay between Move messages (one server tick)
+const MOVE_INTERVAL: f64 = 0.05;
+/// How far ahead of the player a keyboard move targets
+const KEYBOARD_MOVE_DISTANCE: f32 = 100.0;
+
+/// Final stats shown on the death screen
+pub struct DeathSummary {
+    pub max_ring: u32,
+    pub survival_time: f32,
+    pub enemies_defeated: u32,
+    pub score_recorded: bool,
+    pub stats: RunStats,
+    pub run_id: Option<Uuid>, // the summary at GET /runs/{id}
+}
+
+/// Non-spatial state received from the server
+#[derive(Resource, Default)]
+pub struct Session {
+    pub player_id: Option<Uuid>,
+    pub scores: Vec<ScoreEntry>,
+    pub upgrade_choices: Vec<UpgradeType>,
+    pub upgrade_text: Vec<UpgradeText>, // `upgrade_choices` in our locale
+    pub death: Option<DeathSummary>,
+    last_move: f64,
+    input_seq: u32, // numbers Move inputs for the server to acknowledge
+}
+
+fn main() {
+    let url = std::env::args()
+        .nth(1)
+        .or_else(|| std::env::var("SERVER_URL").ok())
+        .unwrap_or_else(|| "ws://localhost:3000/ws".to_string());
+
+    App::new()
+        .add_plugins(DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                title: "Game Client".into(),
+                ..default()
+            }),
+            ..default()
+        }))
+        .insert_resource(ClearColor(Color::srgb(0.04, 0.04, 0.04)))
+        .insert_resource(ServerConnection::spawn(url))
+        .init_resource::<Session>()
+        .init_resource::<SnapshotBuffer>()
+        .init_resource::<world::EntityMap>()
+        .init_resource::<ui::ScoreboardVisible>()
+        .add_systems(Startup, (world::setup_world, ui::setup_ui))
+        .add_systems(
+            Update,
+            (
+                receive_messages,
+                handle_input,
+                world::sync_entities,
+                ui::update_hud,
+                ui::update_center_text,
+                ui::update_scoreboard,
+            )
+                .chain(),
+        )
+        .run();
+}
+
+fn receive_messages(
+    time: Res<Time>,
+    mut connection: ResMut<ServerConnection>,
+    mut session: ResMut<Session>,
+    mut buffer: ResMut<SnapshotBuffer>,
+) {
+    for msg in connection.poll() {
+        match msg {
+            ServerMessage::Welcome { player_id } => {
+                info!("Joined game as {}", player_id);
+                session.player_id = Some(player_id);
+            }
+            ServerMessage::GameState {
+                own_player,
+                players,
+                enemies,
+                projectiles,
+                ..
+            } => buffer.push(Snapshot {
+                received_at: time.elapsed_secs_f64(),
+                own_player,
+                players,
+                enemies,
+                projectiles,
+            }),
+            ServerMessage::PlayerDied {
+                player_id,
+                max_ring,
+                survival_time,
+                enemies_defeated,
+                score_recorded,
+                stats,
+                run_id,
+            } if Some(player_id) == session.player_id => {
+                session.upgrade_choices.clear();
+                session.death = Some(DeathSummary {
+                    max_ring,
+                    survival_time,
+                    enemies_defeated,
+                    score_recorded,
+                    stats,
+                    run_id,
+                });
+            }
+            ServerMessage::PlayerDied { .. } => {}
+            ServerMessage::Scoreboard { scores, .. } => session.scores = scores,
+            ServerMessage::LevelUp {
+                player_id,
+                upgrade_choices,
+                choice_text,
+                ..
+            } if Some(player_id) == session.player_id => {
+                session.upgrade_text = UpgradeText::for_choices(&upgrade_choices, choice_text);
+                session.upgrade_choices = upgrade_choices;
+            }
+            ServerMessage::LevelUp { .. } => {}
+            ServerMessage::LevelUpExpired { player_id, .. }
+                if Some(player_id) == session.player_id =>
+            {
+                session.upgrade_choices.clear();
+            }
+            ServerMessage::Error { message, .. } => warn!("Server error: {}", message),
+            ServerMessage::Announcement { text } => info!("Announcement: {}", text),
+            // Events this client doesn't visualize yet
+            _ => {}
+        }
+    }
+}
+
+fn handle_input(
+    time: Res<Time>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    connection: Res<ServerConnection>,
+    mut buffer: ResMut<SnapshotBuffer>,
+    mut session: ResMut<Session>,
+) {
+    if session.death.is_some() {
+        if keys.just_pressed(KeyCode::Enter) {
+            session.death = None;
+            session.player_id = None;
+            buffer.clear();
+            connection.send(net::join_message());
+        }
+        return;
+    }
+
+    let choice_keys = [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3];
+    if let Some(index) = choice_keys.iter().position(|k| keys.just_pressed(*k)) {
+        if let Some(&upgrade) = session.upgrade_choices.get(index) {
+            connection.send(ClientMessage::ChooseUpgrade { upgrade });
+            session.upgrade_choices.clear();
+        }
+    }
+
+    let now = time.elapsed_secs_f64();
+    if now - session.last_move < MOVE_INTERVAL {
+        return;
+    }
+    let Some(me) = buffer.latest().and_then(|s| s.own_player.as_deref()) else {
+        return;
+    };
+
+    let mut direction = Vec2::ZERO;
+    if keys.pressed(KeyCode::KeyW) {
+        direction.y -= 1.0;
+    }
+    if keys.pressed(KeyCode::KeyS) {
+        direction.y += 1.0;
+    }
+    if keys.pressed(KeyCode::KeyA) {
+        direction.x -= 1.0;
+    }
+    if keys.pressed(KeyCode::KeyD) {
+        direction.x += 1.0;
+    }
+
+    let target = if direction != Vec2::ZERO {
+        let step = direction.normalize() * KEYBOARD_MOVE_DISTANCE;
+        Some(Position::new(
+            me.position.x + step.x,
+            me.position.y + step.y,
+        ))
+    } else if mouse.pressed(MouseButton::Left) {
+        cursor_world_position(&windows, &cameras)
+    } else {
+        None
+    };
+
+    if let Some(target) = target {
+        session.input_seq += 1;
+        connection.send(ClientMessage::Move {
+            target,
+            seq: session.input_seq,
+        });
+        session.last_move = now;
+    }
+}
+
+fn cursor_world_position(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    cameras: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Position> {
+    let cursor = windows.get_single().ok()?.cursor_position()?;
+    let (camera, transform) = cameras.get_single().ok()?;
+    let world = camera.viewport_to_world_2d(transform, cursor).ok()?;
+    Some(world::from_world(world))
+}
@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use shared::{ClientMessage, Locale, ServerMessage};
+use std::io::ErrorKind;
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+/// How long a blocking socket read waits before checking for outgoing messages
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Connection status and message channels to the network thread
+#[derive(Resource)]
+pub struct ServerConnection {
+    outgoing: Sender<ClientMessage>,
+    incoming: Receiver<NetEvent>,
+    pub connected: bool,
+}
+
+/// Events produced by the network thread
+pub enum NetEvent {
+    Connected,
+    Message(ServerMessage),
+    Disconnected(String),
+}
+
+impl ServerConnection {
+    /// Connect to `url` on a background thread. Join is sent automatically.
+    pub fn spawn(url: String) -> Self {
+        let (out_tx, out_rx) = crossbeam_channel::unbounded();
+        let (in_tx, in_rx) = crossbeam_channel::unbounded();
+
+        thread::spawn(move || {
+            let reason = match run_connection(&url, &out_rx, &in_tx) {
+                Ok(()) => "connection closed".to_string(),
+                Err(e) => e.to_string(),
+            };
+            let _ = in_tx.send(NetEvent::Disconnected(reason));
+        });
+
+        Self {
+            outgoing: out_tx,
+            incoming: in_rx,
+            connected: false,
+        }
+    }
+
+    pub fn send(&self, msg: ClientMessage) {
+        let _ = self.outgoing.send(msg);
+    }
+
+    /// Drain everything received since the last frame
+    pub fn poll(&mut self) -> Vec<ServerMessage> {
+        let mut messages = Vec::new();
+        loop {
+            match self.incoming.try_recv() {
+                Ok(NetEvent::Connected) => self.connected = true,
+                Ok(NetEvent::Message(msg)) => messages.push(msg),
+                Ok(NetEvent::Disconnected(reason)) => {
+                    warn!("Disconnected from server: {}", reason);
+                    self.connected = false;
+                }
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        messages
+    }
+}
+
+fn run_connection(
+    url: &str,
+    outgoing: &Receiver<ClientMessage>,
+    incoming: &Sender<NetEvent>,
+) -> tungstenite::Result<()> {
+    let (mut socket, _) = tungstenite::connect(url)?;
+    if let MaybeTlsStream::Plain(stream) = socket.get_ref() {
+        stream.set_read_timeout(Some(POLL_INTERVAL))?;
+    }
+    let _ = incoming.send(NetEvent::Connected);
+    send_message(&mut socket, &join_message())?;
+
+    loop {
+        while let Ok(msg) = outgoing.try_recv() {
+            send_message(&mut socket, &msg)?;
+        }
+
+        match socket.read() {
+            Ok(Message::Text(text)) => match serde_json::from_str::<ServerMessage>(&text) {
+                Ok(msg) => {
+                    if incoming.send(NetEvent::Message(msg)).is_err() {
+                        return Ok(());
+                    }
+                }
+                Err(e) => warn!("Failed to parse server message: {}", e),
+            },
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn send_message(
+    socket: &mut WebSocket<MaybeTlsStream<TcpStream>>,
+    msg: &ClientMessage,
+) -> tungstenite::Result<()> {
+    let json = serde_json::to_string(msg).expect("client messages always serialize");
+    socket.send(Message::text(json))
+}
+
+/// Our `Join`, with `SERVER_PASSWORD` for a password-protected server and
+/// `SERVER_ACCOUNT` (`name:key`) for a whitelisted one
+pub fn join_message() -> ClientMessage {
+    let password = std::env::var("SERVER_PASSWORD").ok();
+    let account = std::env::var("SERVER_ACCOUNT").ok();
+    ClientMessage::join(Locale::from_env())
+        .with_password(password)
+        .with_account(account)
+}
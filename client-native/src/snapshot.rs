@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+use shared::{Enemy, OwnPlayerState, Position, Projectile, RemotePlayerView};
+use std::collections::VecDeque;
+use uuid::Uuid;
+
+/// Render this far behind the newest snapshot so there is usually a pair to
+/// interpolate between (two server updates at 20 Hz)
+pub const INTERPOLATION_DELAY: f64 = 0.1;
+
+const MAX_BUFFERED: usize = 16;
+
+/// One `GameState` message, stamped with the local receive time
+pub struct Snapshot {
+    pub received_at: f64,
+    pub own_player: Option<Box<OwnPlayerState>>,
+    pub players: Vec<RemotePlayerView>,
+    pub enemies: Vec<Enemy>,
+    pub projectiles: Vec<Projectile>,
+}
+
+/// Recent snapshots, oldest first
+#[derive(Resource, Default)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl SnapshotBuffer {
+    pub fn push(&mut self, snapshot: Snapshot) {
+        self.snapshots.push_back(snapshot);
+        while self.snapshots.len() > MAX_BUFFERED {
+            self.snapshots.pop_front();
+        }
+    }
+
+    pub fn latest(&self) -> Option<&Snapshot> {
+        self.snapshots.back()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    /// The two snapshots surrounding `render_time` and the blend factor
+    /// between them. Falls back to the newest snapshot when the buffer has
+    /// run dry.
+    pub fn bracket(&self, render_time: f64) -> Option<(&Snapshot, &Snapshot, f32)> {
+        let latest = self.snapshots.back()?;
+        for (from, to) in self.snapshots.iter().zip(self.snapshots.iter().skip(1)) {
+            if from.received_at <= render_time && render_time <= to.received_at {
+                let span = to.received_at - from.received_at;
+                let t = if span > 0.0 {
+                    ((render_time - from.received_at) / span) as f32
+                } else {
+                    1.0
+                };
+                return Some((from, to, t));
+            }
+        }
+        Some((latest, latest, 1.0))
+    }
+}
+
+/// Interpolated position of an entity present in `to`, using its position
+/// in `from` if it existed there too
+pub fn interpolate<T>(
+    from: &[T],
+    id: Uuid,
+    target: Position,
+    t: f32,
+    key: impl Fn(&T) -> (Uuid, Position),
+) -> Position {
+    match from.iter().map(&key).find(|(other, _)| *other == id) {
+        Some((_, start)) => start + (target - start) * t,
+        None => target,
+    }
+}
@@ -0,0 +1,91 @@
+use bevy::prelude::Vec2;
+use shared::{ClientMessage, Enemy, EnemyType, Position};
+use uuid::Uuid;
+
+use crate::net::join_message;
+use crate::snapshot::{interpolate, Snapshot, SnapshotBuffer};
+use crate::world::{from_world, to_world};
+
+fn snapshot_at(received_at: f64, enemies: Vec<Enemy>) -> Snapshot {
+    Snapshot {
+        received_at,
+        own_player: None,
+        players: Vec::new(),
+        enemies,
+        projectiles: Vec::new(),
+    }
+}
+
+#[test]
+fn test_bracket_blends_between_the_snapshots_around_render_time() {
+    let mut buffer = SnapshotBuffer::default();
+    assert!(buffer.bracket(1.0).is_none());
+
+    for at in [1.0, 1.05, 1.1] {
+        buffer.push(snapshot_at(at, Vec::new()));
+    }
+    let (from, to, t) = buffer.bracket(1.075).unwrap();
+    assert_eq!((from.received_at, to.received_at), (1.05, 1.1));
+    assert!((t - 0.5).abs() < 1e-4);
+
+    // Past the newest snapshot the buffer has run dry; hold the latest
+    let (from, to, t) = buffer.bracket(2.0).unwrap();
+    assert_eq!((from.received_at, to.received_at, t), (1.1, 1.1, 1.0));
+}
+
+#[test]
+fn test_buffer_keeps_only_the_newest_snapshots() {
+    let mut buffer = SnapshotBuffer::default();
+    for i in 0..40 {
+        buffer.push(snapshot_at(i as f64, Vec::new()));
+    }
+    assert_eq!(buffer.latest().map(|s| s.received_at), Some(39.0));
+    // The oldest ones are gone, so an early render time falls back to the newest
+    let (from, _, _) = buffer.bracket(5.5).unwrap();
+    assert_eq!(from.received_at, 39.0);
+
+    buffer.clear();
+    assert!(buffer.latest().is_none());
+}
+
+#[test]
+fn test_entities_slide_from_their_old_position_and_newcomers_snap_in() {
+    let id = Uuid::new_v4();
+    let old = Enemy::new(id, EnemyType::Goblin, Position::new(0.0, 0.0), 1);
+    let from = vec![old];
+
+    let key = |e: &Enemy| (e.id, e.position);
+    let halfway = interpolate(&from, id, Position::new(10.0, -20.0), 0.5, key);
+    assert_eq!((halfway.x, halfway.y), (5.0, -10.0));
+
+    let fresh = interpolate(&from, Uuid::new_v4(), Position::new(10.0, -20.0), 0.5, key);
+    assert_eq!((fresh.x, fresh.y), (10.0, -20.0));
+}
+
+#[test]
+fn test_world_coordinates_flip_y_and_round_trip() {
+    let pos = Position::new(30.0, 40.0);
+    let world = to_world(pos, 2.0);
+    assert_eq!((world.x, world.y, world.z), (30.0, -40.0, 2.0));
+
+    let back = from_world(Vec2::new(world.x, world.y));
+    assert_eq!((back.x, back.y), (pos.x, pos.y));
+}
+
+#[test]
+fn test_join_message_carries_the_password_and_account_from_the_environment() {
+    std::env::set_var("SERVER_PASSWORD", "hunter2");
+    std::env::set_var("SERVER_ACCOUNT", "ada:key");
+    let ClientMessage::Join {
+        protocol_version,
+        password,
+        account,
+        ..
+    } = join_message()
+    else {
+        panic!("the client opens with a Join");
+    };
+    assert_eq!(protocol_version, Some(shared::PROTOCOL_VERSION));
+    assert_eq!(password.as_deref(), Some("hunter2"));
+    assert_eq!(account.as_deref(), Some("ada:key"));
+}
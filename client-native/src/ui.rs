@@ -0,0 +1,173 @@
+use bevy::prelude::*;
+
+use crate::net::ServerConnection;
+use crate::snapshot::SnapshotBuffer;
+use crate::Session;
+
+#[derive(Component)]
+pub struct HudText;
+
+#[derive(Component)]
+pub struct CenterText;
+
+#[derive(Component)]
+pub struct ScoreboardText;
+
+/// Whether the scoreboard panel is open (toggled with Tab, forced on death)
+#[derive(Resource, Default)]
+pub struct ScoreboardVisible(pub bool);
+
+pub fn setup_ui(mut commands: Commands) {
+    commands.spawn((
+        HudText,
+        Text::new(""),
+        TextFont::from_font_size(16.0),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            left: Val::Px(10.0),
+            ..default()
+        },
+    ));
+
+    commands.spawn((
+        CenterText,
+        Text::new(""),
+        TextFont::from_font_size(20.0),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(35.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+    ));
+
+    commands.spawn((
+        ScoreboardText,
+        Text::new(""),
+        TextFont::from_font_size(14.0),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            right: Val::Px(10.0),
+            ..default()
+        },
+    ));
+}
+
+pub fn update_hud(
+    buffer: Res<SnapshotBuffer>,
+    connection: Res<ServerConnection>,
+    mut hud: Query<&mut Text, With<HudText>>,
+) {
+    let Ok(mut text) = hud.get_single_mut() else {
+        return;
+    };
+
+    let me = buffer.latest().and_then(|s| s.own_player.as_deref());
+
+    text.0 = match me {
+        Some(p) => format!(
+            "HP: {:.0}/{:.0}\nLevel: {} ({}/{} XP)\nRing: {}\nEnemies: {}",
+            p.health,
+            p.max_health,
+            p.level,
+            p.current_xp,
+            p.xp_to_next_level,
+            p.max_ring_reached,
+            p.enemies_defeated
+        ),
+        None if connection.connected => "Joining...".to_string(),
+        None => "Connecting...".to_string(),
+    };
+}
+
+/// Level-up choices and the death summary share the center of the screen
+pub fn update_center_text(session: Res<Session>, mut center: Query<&mut Text, With<CenterText>>) {
+    let Ok(mut text) = center.get_single_mut() else {
+        return;
+    };
+
+    text.0 = if let Some(death) = &session.death {
+        let stats = &death.stats;
+        let mut weapons: Vec<String> = stats
+            .damage_by_weapon
+            .iter()
+            .map(|(weapon, damage)| format!("\n{}: {:.0}", weapon.name(), damage))
+            .collect();
+        weapons.sort();
+        let share = death
+            .run_id
+            .map(|id| format!("\n\nShare this run: /runs/{}", id))
+            .unwrap_or_default();
+        format!(
+            "GAME OVER\nRing {} - {:.1}s - {} kills{}\n\n\
+             Damage dealt {:.0} - taken {:.0}\n{} XP - {:.0} distance{}{}\n\n\
+             Press Enter to play again",
+            death.max_ring,
+            death.survival_time,
+            death.enemies_defeated,
+            if death.score_recorded {
+                "\nScore recorded!"
+            } else {
+                ""
+            },
+            stats.damage_dealt,
+            stats.damage_taken,
+            stats.xp_collected,
+            stats.distance_traveled,
+            weapons.concat(),
+            share
+        )
+    } else if !session.upgrade_choices.is_empty() {
+        let mut lines = vec!["LEVEL UP! Choose an upgrade:".to_string()];
+        for (i, text) in session.upgrade_text.iter().enumerate() {
+            lines.push(format!(
+                "[{}] {} ({}) - {}",
+                i + 1,
+                text.name,
+                text.rarity,
+                text.description
+            ));
+        }
+        lines.join("\n")
+    } else {
+        String::new()
+    };
+}
+
+pub fn update_scoreboard(
+    keys: Res<ButtonInput<KeyCode>>,
+    session: Res<Session>,
+    mut visible: ResMut<ScoreboardVisible>,
+    mut scoreboard: Query<&mut Text, With<ScoreboardText>>,
+) {
+    if keys.just_pressed(KeyCode::Tab) {
+        visible.0 = !visible.0;
+    }
+    let Ok(mut text) = scoreboard.get_single_mut() else {
+        return;
+    };
+
+    if !visible.0 && session.death.is_none() {
+        text.0 = "[Tab] Scoreboard".to_string();
+        return;
+    }
+
+    let mut lines = vec!["TOP SCORES".to_string()];
+    if session.scores.is_empty() {
+        lines.push("No qualifying runs yet".to_string());
+    }
+    for (i, score) in session.scores.iter().take(10).enumerate() {
+        lines.push(format!(
+            "{}. Ring {} - {:.0}s - {} kills",
+            i + 1,
+            score.max_ring_reached,
+            score.survival_time_seconds,
+            score.enemies_defeated
+        ));
+    }
+    text.0 = lines.join("\n");
+}
@@ -0,0 +1,188 @@
+use bevy::prelude::*;
+use shared::{EnemyType, Position};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::snapshot::{interpolate, SnapshotBuffer, INTERPOLATION_DELAY};
+use crate::Session;
+
+/// World layout mirrored from the server's default `GameConfig`
+const SAFE_ZONE_RADIUS: f32 = 100.0;
+const RING_RADIUS: f32 = 200.0;
+const MAX_RINGS: u32 = 10;
+
+/// Marks a Bevy entity mirroring a server entity
+#[derive(Component)]
+pub struct NetEntity;
+
+/// Server ID -> Bevy entity
+#[derive(Resource, Default)]
+pub struct EntityMap(HashMap<Uuid, Entity>);
+
+#[derive(Resource)]
+pub struct WorldAssets {
+    circle: Handle<Mesh>,
+    own_player: Handle<ColorMaterial>,
+    other_player: Handle<ColorMaterial>,
+    projectile: Handle<ColorMaterial>,
+    enemies: HashMap<EnemyType, Handle<ColorMaterial>>,
+}
+
+/// Server coordinates grow downward, Bevy's grow upward
+pub fn to_world(pos: Position, z: f32) -> Vec3 {
+    Vec3::new(pos.x, -pos.y, z)
+}
+
+pub fn from_world(pos: Vec2) -> Position {
+    Position::new(pos.x, -pos.y)
+}
+
+pub fn setup_world(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.spawn(Camera2d);
+
+    let safe_zone = meshes.add(Circle::new(SAFE_ZONE_RADIUS));
+    commands.spawn((
+        Mesh2d(safe_zone),
+        MeshMaterial2d(materials.add(Color::srgba(0.0, 0.8, 0.0, 0.08))),
+        Transform::from_xyz(0.0, 0.0, -2.0),
+    ));
+
+    let ring_material = materials.add(Color::srgba(0.6, 0.4, 1.0, 0.25));
+    for ring in 1..=MAX_RINGS {
+        let radius = ring as f32 * RING_RADIUS;
+        commands.spawn((
+            Mesh2d(meshes.add(Annulus::new(radius - 1.0, radius + 1.0))),
+            MeshMaterial2d(ring_material.clone()),
+            Transform::from_xyz(0.0, 0.0, -1.0),
+        ));
+    }
+
+    let enemies = EnemyType::all()
+        .into_iter()
+        .map(|t| (t, materials.add(enemy_color(t))))
+        .collect();
+
+    commands.insert_resource(WorldAssets {
+        circle: meshes.add(Circle::new(1.0)),
+        own_player: materials.add(Color::srgb(0.0, 1.0, 0.0)),
+        other_player: materials.add(Color::srgb(0.0, 0.6, 1.0)),
+        projectile: materials.add(Color::srgb(1.0, 1.0, 0.0)),
+        enemies,
+    });
+}
+
+/// Interpolate buffered snapshots and mirror them onto Bevy entities
+pub fn sync_entities(
+    mut commands: Commands,
+    time: Res<Time>,
+    buffer: Res<SnapshotBuffer>,
+    session: Res<Session>,
+    assets: Res<WorldAssets>,
+    mut map: ResMut<EntityMap>,
+    mut transforms: Query<&mut Transform, With<NetEntity>>,
+    mut camera: Query<&mut Transform, (With<Camera2d>, Without<NetEntity>)>,
+) {
+    let render_time = time.elapsed_secs_f64() - INTERPOLATION_DELAY;
+    let Some((from, to, t)) = buffer.bracket(render_time) else {
+        return;
+    };
+
+    // (id, interpolated position, radius, material, z)
+    let mut visible = Vec::new();
+    for player in &to.players {
+        let pos = interpolate(&from.players, player.id, player.position, t, |p| {
+            (p.id, p.position)
+        });
+        let own = Some(player.id) == session.player_id;
+        let material = if own {
+            &assets.own_player
+        } else {
+            &assets.other_player
+        };
+        visible.push((player.id, pos, 8.0, material.clone(), 2.0));
+
+        if own {
+            if let Ok(mut cam) = camera.get_single_mut() {
+                cam.translation = to_world(pos, cam.translation.z);
+            }
+        }
+    }
+    for enemy in &to.enemies {
+        let pos = interpolate(&from.enemies, enemy.id, enemy.position, t, |e| {
+            (e.id, e.position)
+        });
+        let material = assets.enemies[&enemy.enemy_type].clone();
+        visible.push((enemy.id, pos, enemy_radius(enemy.enemy_type), material, 1.0));
+    }
+    for projectile in &to.projectiles {
+        let pos = interpolate(
+            &from.projectiles,
+            projectile.id,
+            projectile.position,
+            t,
+            |p| (p.id, p.position),
+        );
+        visible.push((projectile.id, pos, 4.0, assets.projectile.clone(), 3.0));
+    }
+
+    let mut seen = HashSet::with_capacity(visible.len());
+    for (id, pos, radius, material, z) in visible {
+        seen.insert(id);
+        let transform =
+            Transform::from_translation(to_world(pos, z)).with_scale(Vec3::splat(radius));
+        match map.0.get(&id).and_then(|e| transforms.get_mut(*e).ok()) {
+            Some(mut existing) => *existing = transform,
+            None => {
+                let entity = commands
+                    .spawn((
+                        NetEntity,
+                        Mesh2d(assets.circle.clone()),
+                        MeshMaterial2d(material),
+                        transform,
+                    ))
+                    .id();
+                map.0.insert(id, entity);
+            }
+        }
+    }
+
+    map.0.retain(|id, entity| {
+        let keep = seen.contains(id);
+        if !keep {
+            commands.entity(*entity).despawn();
+        }
+        keep
+    });
+}
+
+fn enemy_radius(enemy_type: EnemyType) -> f32 {
+    match enemy_type {
+        EnemyType::Goblin | EnemyType::Wraith => 5.0,
+        EnemyType::Wolf | EnemyType::Skeleton => 6.0,
+        EnemyType::Orc => 7.0,
+        EnemyType::Zombie => 8.0,
+        EnemyType::Demon => 9.0,
+        EnemyType::Lich => 10.0,
+        EnemyType::Troll => 12.0,
+        EnemyType::Dragon => 15.0,
+    }
+}
+
+fn enemy_color(enemy_type: EnemyType) -> Color {
+    match enemy_type {
+        EnemyType::Goblin => Color::srgb(1.0, 1.0, 0.0),
+        EnemyType::Orc => Color::srgb(1.0, 0.4, 0.0),
+        EnemyType::Wolf => Color::srgb(1.0, 0.2, 0.2),
+        EnemyType::Skeleton => Color::srgb(0.8, 0.8, 0.8),
+        EnemyType::Zombie => Color::srgb(0.0, 1.0, 0.0),
+        EnemyType::Wraith => Color::srgb(0.8, 0.6, 1.0),
+        EnemyType::Demon => Color::srgb(1.0, 0.0, 0.0),
+        EnemyType::Troll => Color::srgb(0.0, 0.8, 1.0),
+        EnemyType::Dragon => Color::srgb(1.0, 0.0, 1.0),
+        EnemyType::Lich => Color::srgb(0.0, 1.0, 1.0),
+    }
+}
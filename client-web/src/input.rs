@@ -0,0 +1,109 @@
+use shared::EmoteKind;
+use std::collections::HashSet;
+
+/// Keyboard and mouse state collected from DOM events
+#[derive(Debug, Default)]
+pub struct InputState {
+    keys: HashSet<String>,
+    clicked_at: Option<(f64, f64)>,
+    pending_choice: Option<usize>,
+    interact: bool,
+    dash: bool,
+    reroll: bool,
+    emote: Option<EmoteKind>,
+    pinged_at: Option<(f64, f64)>,
+}
+
+impl InputState {
+    pub fn key_down(&mut self, key: &str) {
+        let key = key.to_uppercase();
+        match key.as_str() {
+            "1" | "2" | "3" | "4" => self.pending_choice = key.parse::<usize>().ok().map(|n| n - 1),
+            "E" => self.interact = true,
+            " " => self.dash = true,
+            "R" => self.reroll = true,
+            "Z" => self.emote = Some(EmoteKind::Wave),
+            "X" => self.emote = Some(EmoteKind::Thanks),
+            "C" => self.emote = Some(EmoteKind::Help),
+            "V" => self.emote = Some(EmoteKind::FollowMe),
+            "B" => self.emote = Some(EmoteKind::Attack),
+            "N" => self.emote = Some(EmoteKind::Retreat),
+            _ => {
+                self.keys.insert(key);
+            }
+        }
+    }
+
+    pub fn key_up(&mut self, key: &str) {
+        self.keys.remove(&key.to_uppercase());
+    }
+
+    pub fn click(&mut self, x: f64, y: f64) {
+        self.clicked_at = Some((x, y));
+    }
+
+    /// An Alt+click, pinging the spot rather than walking there
+    pub fn ping(&mut self, x: f64, y: f64) {
+        self.pinged_at = Some((x, y));
+    }
+
+    /// Normalized WASD direction, or None if no movement keys are held
+    pub fn movement_direction(&self) -> Option<(f32, f32)> {
+        let mut x: f32 = 0.0;
+        let mut y: f32 = 0.0;
+        if self.keys.contains("W") {
+            y -= 1.0;
+        }
+        if self.keys.contains("S") {
+            y += 1.0;
+        }
+        if self.keys.contains("A") {
+            x -= 1.0;
+        }
+        if self.keys.contains("D") {
+            x += 1.0;
+        }
+
+        let length = (x * x + y * y).sqrt();
+        if length > 0.0 {
+            Some((x / length, y / length))
+        } else {
+            None
+        }
+    }
+
+    pub fn take_click(&mut self) -> Option<(f64, f64)> {
+        self.clicked_at.take()
+    }
+
+    /// Upgrade slot (or shop item) selected with the number keys since the
+    /// last call
+    pub fn take_upgrade_choice(&mut self) -> Option<usize> {
+        self.pending_choice.take()
+    }
+
+    /// Whether the interact key was pressed since the last call
+    pub fn take_interact(&mut self) -> bool {
+        std::mem::take(&mut self.interact)
+    }
+
+    /// Whether the dash key was pressed since the last call
+    pub fn take_dash(&mut self) -> bool {
+        std::mem::take(&mut self.dash)
+    }
+
+    /// Whether the reroll key was pressed since the last call
+    pub fn take_reroll(&mut self) -> bool {
+        std::mem::take(&mut self.reroll)
+    }
+
+    /// The emote picked with Z/X/C/V/B/N since the last call
+    pub fn take_emote(&mut self) -> Option<EmoteKind> {
+        self.emote.take()
+    }
+
+    /// Where the screen was Alt+clicked since the last call
+    pub fn take_ping(&mut self) -> Option<(f64, f64)> {
+        self.pinged_at.take()
+    }
+}
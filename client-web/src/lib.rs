@@ -0,0 +1,321 @@
+//! Reference browser client for the game server.
+//!
+//! Connects to `/ws`, renders the world onto a `<canvas id="canvas">`, and
+//! drives the Join/Move/ChooseUpgrade side of the protocol.
+
+mod input;
+mod render;
+mod state;
+
+#[cfg(test)]
+mod tests;
+
+pub use input::InputState;
+pub use state::{ClientState, DeathSummary};
+
+use shared::{ClientMessage, Envelope, Locale, Position, Vec2};
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    CanvasRenderingContext2d, HtmlCanvasElement, KeyboardEvent, MessageEvent, MouseEvent, Url,
+    UrlSearchParams, WebSocket, Window,
+};
+
+use render::Renderer;
+
+/// Minimum delay between Move messages (one server tick)
+const MOVE_INTERVAL_MS: f64 = 50.0;
+/// How far ahead of the player a keyboard move targets
+const KEYBOARD_MOVE_DISTANCE: f32 = 100.0;
+
+struct App {
+    socket: WebSocket,
+    canvas: HtmlCanvasElement,
+    renderer: Renderer,
+    state: ClientState,
+    input: InputState,
+    last_move_ms: f64,
+    reported_view_radius: f32,
+    input_seq: u32,           // numbers Move/Dash inputs for the server to acknowledge
+    locale: Locale,           // sent with Join for localized upgrade offers
+    password: Option<String>, // `?password=`, for a password-protected server
+    account: Option<String>,  // `?account=name:key`, for a whitelisted server
+}
+
+impl App {
+    fn send(&self, msg: &ClientMessage) {
+        if self.socket.ready_state() != WebSocket::OPEN {
+            return;
+        }
+        let _ = self.socket.send_with_str(&msg.to_json());
+    }
+
+    fn frame(&mut self, now_ms: f64) {
+        if let Some(window) = web_sys::window() {
+            let width = window
+                .inner_width()
+                .ok()
+                .and_then(|w| w.as_f64())
+                .unwrap_or(800.0);
+            let height = window
+                .inner_height()
+                .ok()
+                .and_then(|h| h.as_f64())
+                .unwrap_or(600.0);
+            if self.canvas.width() != width as u32 || self.canvas.height() != height as u32 {
+                self.canvas.set_width(width as u32);
+                self.canvas.set_height(height as u32);
+            }
+            self.renderer.resize(width, height);
+
+            // Half the screen diagonal: the server culls snapshots to it
+            // and can spawn out of sight
+            let view_radius = (width.hypot(height) / 2.0) as f32;
+            if self.state.player_id.is_some() && view_radius != self.reported_view_radius {
+                self.send(&ClientMessage::SetViewport {
+                    radius: view_radius,
+                });
+                self.reported_view_radius = view_radius;
+            }
+        }
+
+        if let Some(index) = self.input.take_upgrade_choice() {
+            if let Some(upgrade) = self.state.take_upgrade_choice(index) {
+                self.send(&ClientMessage::ChooseUpgrade { upgrade });
+            } else if let Some(item) = self.state.shop_item(index) {
+                self.send(&ClientMessage::BuyItem { item });
+            }
+        }
+
+        if self.input.take_reroll() && !self.state.upgrade_choices.is_empty() {
+            self.send(&ClientMessage::RerollUpgrades);
+        }
+
+        if self.input.take_interact() {
+            if let Some(waypoint) = self.state.next_waypoint() {
+                let waypoint = waypoint.id;
+                self.send(&ClientMessage::TeleportTo { waypoint });
+            } else if let Some(landmark) = self.state.usable_landmark_nearby() {
+                let landmark_id = landmark.id;
+                self.send(&ClientMessage::Interact { landmark_id });
+            }
+        }
+
+        if let Some(kind) = self.input.take_emote() {
+            self.send(&ClientMessage::Emote { kind });
+        }
+        if let Some((x, y)) = self.input.take_ping() {
+            let position = self.renderer.screen_to_world(x, y);
+            self.send(&ClientMessage::PingLocation { position });
+        }
+
+        // Dash along the held WASD direction
+        if self.input.take_dash() {
+            if let Some((dx, dy)) = self.input.movement_direction() {
+                self.input_seq += 1;
+                self.send(&ClientMessage::Dash {
+                    direction: Vec2::new(dx, dy),
+                    seq: self.input_seq,
+                });
+            }
+        }
+
+        self.update_movement(now_ms);
+        self.renderer.draw(&self.state);
+    }
+
+    fn update_movement(&mut self, now_ms: f64) {
+        if now_ms - self.last_move_ms < MOVE_INTERVAL_MS {
+            return;
+        }
+        let Some(me) = self.state.own_player() else {
+            return;
+        };
+
+        let target = if let Some((dx, dy)) = self.input.movement_direction() {
+            Position::new(
+                me.position.x + dx * KEYBOARD_MOVE_DISTANCE,
+                me.position.y + dy * KEYBOARD_MOVE_DISTANCE,
+            )
+        } else if let Some((x, y)) = self.input.take_click() {
+            self.renderer.screen_to_world(x, y)
+        } else {
+            return;
+        };
+
+        self.input_seq += 1;
+        self.send(&ClientMessage::Move {
+            target,
+            seq: self.input_seq,
+        });
+        self.last_move_ms = now_ms;
+    }
+}
+
+/// Entry point invoked by the generated JS glue once the module loads
+#[wasm_bindgen(start)]
+pub fn start() -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or("no global window")?;
+    let document = window.document().ok_or("no document")?;
+    let canvas: HtmlCanvasElement = document
+        .get_element_by_id("canvas")
+        .ok_or("missing <canvas id=\"canvas\">")?
+        .dyn_into()?;
+    let ctx: CanvasRenderingContext2d = canvas
+        .get_context("2d")?
+        .ok_or("canvas has no 2d context")?
+        .dyn_into()?;
+
+    let socket = WebSocket::new(&server_url(&window)?)?;
+    let query = UrlSearchParams::new_with_str(&window.location().search()?)?;
+
+    let app = Rc::new(RefCell::new(App {
+        socket,
+        canvas,
+        renderer: Renderer::new(ctx),
+        state: ClientState::default(),
+        input: InputState::default(),
+        last_move_ms: 0.0,
+        reported_view_radius: 0.0,
+        input_seq: 0,
+        locale: locale(&window),
+        password: query.get("password"),
+        account: query.get("account"),
+    }));
+
+    install_socket_handlers(&app);
+    install_input_handlers(&window, &app)?;
+    start_animation_loop(app)?;
+    Ok(())
+}
+
+/// Server address from `?server=`, defaulting to `ws` one directory up
+/// from the page: `/ws` for a page at the root or the server's `/play/`,
+/// `/game/ws` under a `/game` base path. `?room=` is passed on to pick a
+/// room other than the main game, with `?code=` for a private one's invite.
+fn server_url(window: &Window) -> Result<String, JsValue> {
+    let location = window.location();
+    let params = UrlSearchParams::new_with_str(&location.search()?)?;
+    if let Some(url) = params.get("server") {
+        return Ok(url);
+    }
+    let url = Url::new_with_base("../ws", &location.href()?)?;
+    let scheme = if location.protocol()? == "https:" {
+        "wss:"
+    } else {
+        "ws:"
+    };
+    url.set_protocol(scheme);
+    for key in ["room", "code"] {
+        if let Some(value) = params.get(key) {
+            url.search_params().set(key, &value);
+        }
+    }
+    Ok(url.href())
+}
+
+/// Language for server text: `?lang=`, else the browser's
+fn locale(window: &Window) -> Locale {
+    let requested = window
+        .location()
+        .search()
+        .ok()
+        .and_then(|search| UrlSearchParams::new_with_str(&search).ok())
+        .and_then(|params| params.get("lang"));
+    let tag = requested.or_else(|| window.navigator().language());
+    tag.map_or(Locale::En, |tag| Locale::from_tag(&tag))
+}
+
+fn install_socket_handlers(app: &Rc<RefCell<App>>) {
+    let socket = app.borrow().socket.clone();
+
+    let on_open = {
+        let app = app.clone();
+        Closure::<dyn FnMut()>::new(move || {
+            let app = app.borrow();
+            let join = ClientMessage::join(app.locale)
+                .with_password(app.password.clone())
+                .with_account(app.account.clone());
+            app.send(&join);
+        })
+    };
+    socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+    on_open.forget();
+
+    let on_message = {
+        let app = app.clone();
+        Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            let Some(text) = event.data().as_string() else {
+                return;
+            };
+            match Envelope::from_json(&text) {
+                Ok(envelope) => app.borrow_mut().state.receive(envelope),
+                Err(e) => web_sys::console::warn_1(&format!("Bad server message: {}", e).into()),
+            }
+        })
+    };
+    socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    on_message.forget();
+}
+
+fn install_input_handlers(window: &Window, app: &Rc<RefCell<App>>) -> Result<(), JsValue> {
+    let on_key_down = {
+        let app = app.clone();
+        Closure::<dyn FnMut(KeyboardEvent)>::new(move |event: KeyboardEvent| {
+            app.borrow_mut().input.key_down(&event.key());
+        })
+    };
+    window.add_event_listener_with_callback("keydown", on_key_down.as_ref().unchecked_ref())?;
+    on_key_down.forget();
+
+    let on_key_up = {
+        let app = app.clone();
+        Closure::<dyn FnMut(KeyboardEvent)>::new(move |event: KeyboardEvent| {
+            app.borrow_mut().input.key_up(&event.key());
+        })
+    };
+    window.add_event_listener_with_callback("keyup", on_key_up.as_ref().unchecked_ref())?;
+    on_key_up.forget();
+
+    let on_click = {
+        let app = app.clone();
+        Closure::<dyn FnMut(MouseEvent)>::new(move |event: MouseEvent| {
+            let (x, y) = (event.client_x() as f64, event.client_y() as f64);
+            let input = &mut app.borrow_mut().input;
+            if event.alt_key() {
+                input.ping(x, y);
+            } else {
+                input.click(x, y);
+            }
+        })
+    };
+    window.add_event_listener_with_callback("click", on_click.as_ref().unchecked_ref())?;
+    on_click.forget();
+
+    Ok(())
+}
+
+fn start_animation_loop(app: Rc<RefCell<App>>) -> Result<(), JsValue> {
+    type FrameCallback = Closure<dyn FnMut(f64)>;
+
+    let callback: Rc<RefCell<Option<FrameCallback>>> = Rc::new(RefCell::new(None));
+    let next = callback.clone();
+
+    *callback.borrow_mut() = Some(Closure::new(move |now: f64| {
+        app.borrow_mut().frame(now);
+        if let Some(cb) = next.borrow().as_ref() {
+            let _ = request_animation_frame(cb);
+        }
+    }));
+
+    let first = callback.borrow();
+    request_animation_frame(first.as_ref().ok_or("animation callback missing")?)
+}
+
+fn request_animation_frame(callback: &Closure<dyn FnMut(f64)>) -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or("no global window")?;
+    window.request_animation_frame(callback.as_ref().unchecked_ref())?;
+    Ok(())
+}
@@ -0,0 +1,491 @@
+use shared::{
+    CombatEventKind, EmoteKind, EnemyBehavior, EnemyType, LandmarkType, MinimapMarker, PickupType,
+    Player, Position, SummonType,
+};
+use std::f64::consts::TAU;
+use uuid::Uuid;
+use web_sys::CanvasRenderingContext2d;
+
+use crate::state::{
+    ClientState, DAMAGE_NUMBER_LIFETIME, EMOTE_LIFETIME, EXPLOSION_LIFETIME, MAX_RINGS,
+    PING_LIFETIME, RING_RADIUS, SAFE_ZONE_RADIUS,
+};
+
+/// Screen radius of the minimap in pixels
+const MINIMAP_RADIUS: f64 = 70.0;
+
+/// Canvas renderer with the camera centered on the local player
+pub struct Renderer {
+    ctx: CanvasRenderingContext2d,
+    width: f64,
+    height: f64,
+    camera: Position,
+}
+
+impl Renderer {
+    pub fn new(ctx: CanvasRenderingContext2d) -> Self {
+        Self {
+            ctx,
+            width: 0.0,
+            height: 0.0,
+            camera: Position::new(0.0, 0.0),
+        }
+    }
+
+    pub fn resize(&mut self, width: f64, height: f64) {
+        self.width = width;
+        self.height = height;
+    }
+
+    /// Convert a screen-space point to world coordinates
+    pub fn screen_to_world(&self, x: f64, y: f64) -> Position {
+        Position::new(
+            self.camera.x + (x - self.width / 2.0) as f32,
+            self.camera.y + (y - self.height / 2.0) as f32,
+        )
+    }
+
+    fn to_screen(&self, pos: &Position) -> (f64, f64) {
+        (
+            (pos.x - self.camera.x) as f64 + self.width / 2.0,
+            (pos.y - self.camera.y) as f64 + self.height / 2.0,
+        )
+    }
+
+    pub fn draw(&mut self, state: &ClientState) {
+        self.ctx.set_fill_style_str("#0a0a0a");
+        self.ctx.fill_rect(0.0, 0.0, self.width, self.height);
+
+        let Some(me) = state.own_player() else {
+            self.draw_text_center("Connecting...", self.height / 2.0);
+            return;
+        };
+        self.camera = me.position;
+
+        self.draw_zones();
+        if let Some(radius) = state.map_radius {
+            self.draw_world_edge(radius);
+        }
+
+        for enemy in &state.enemies {
+            let (x, y) = self.to_screen(&enemy.position);
+            let radius = enemy.enemy_type.radius() as f64;
+            // Flash white while telegraphing a charge; phased wraiths fade out
+            let color = match enemy.behavior {
+                EnemyBehavior::WindUp => "#ffffff",
+                EnemyBehavior::Phased => "#333333",
+                _ => enemy_color(enemy.enemy_type),
+            };
+            self.circle(x, y, radius, color);
+            self.health_bar(x, y - radius - 12.0, 18.0, enemy.health / enemy.max_health);
+        }
+
+        for landmark in &state.landmarks {
+            let (x, y) = self.to_screen(&landmark.position);
+            let color = if landmark.is_usable(state.game_time) {
+                landmark_color(landmark.landmark_type)
+            } else {
+                "#444444"
+            };
+            self.ctx.set_fill_style_str(color);
+            self.ctx.fill_rect(x - 10.0, y - 10.0, 20.0, 20.0);
+        }
+
+        for pickup in &state.pickups {
+            let (x, y) = self.to_screen(&pickup.position);
+            self.circle(x, y, 6.0, pickup_color(pickup.pickup_type));
+        }
+
+        for summon in &state.summons {
+            let (x, y) = self.to_screen(&summon.position);
+            let color = match summon.summon_type {
+                SummonType::AttackDrone => "#99ffff",
+                SummonType::WolfPet => "#bb8855",
+            };
+            self.circle(x, y, 5.0, color);
+        }
+
+        for projectile in &state.projectiles {
+            let (x, y) = self.to_screen(&projectile.position);
+            let color = if projectile.hostile {
+                "#ff4444"
+            } else {
+                "#ffff00"
+            };
+            self.circle(x, y, 4.0, color);
+        }
+
+        for player in &state.players {
+            let (x, y) = self.to_screen(&player.position);
+            let color = if player.dashing {
+                "#ffffff"
+            } else if player.id == me.id {
+                "#00ff00"
+            } else if player.party_id.is_some() && player.party_id == me.party_id {
+                "#ffaa00"
+            } else {
+                "#0099ff"
+            };
+            self.circle(x, y, Player::RADIUS as f64, color);
+            self.health_bar(x, y - 23.0, 20.0, player.health_ratio);
+            if player.shield_ratio > 0.0 {
+                self.shield_bar(x, y - 27.0, 20.0, player.shield_ratio);
+            }
+        }
+
+        self.draw_blasts(state);
+        self.draw_pings(state);
+        self.draw_emotes(state);
+        self.draw_damage_numbers(state);
+        self.draw_hud(state);
+        self.draw_minimap(state, me.id);
+    }
+
+    /// The whole world scaled into a circle in the bottom right corner
+    fn draw_minimap(&self, state: &ClientState, own_id: Uuid) {
+        let Some(minimap) = &state.minimap else {
+            return;
+        };
+        let (cx, cy) = (
+            self.width - MINIMAP_RADIUS - 10.0,
+            self.height - MINIMAP_RADIUS - 10.0,
+        );
+        let scale = MINIMAP_RADIUS / minimap.map_radius.max(1.0) as f64;
+        let to_minimap = |marker: &MinimapMarker| {
+            let pos = marker.position();
+            (cx + pos.x as f64 * scale, cy + pos.y as f64 * scale)
+        };
+
+        self.ctx.set_fill_style_str("rgba(0, 0, 0, 0.6)");
+        self.ctx.set_stroke_style_str("rgba(255, 255, 255, 0.4)");
+        self.ctx.begin_path();
+        let _ = self.ctx.arc(cx, cy, MINIMAP_RADIUS, 0.0, TAU);
+        self.ctx.fill();
+        self.ctx.stroke();
+
+        for marker in &minimap.landmarks {
+            let (x, y) = to_minimap(marker);
+            let color = state
+                .landmarks
+                .iter()
+                .find(|l| l.id == marker.id)
+                .map_or("#444444", |l| landmark_color(l.landmark_type));
+            self.ctx.set_fill_style_str(color);
+            self.ctx.fill_rect(x - 1.5, y - 1.5, 3.0, 3.0);
+        }
+        for marker in &minimap.bosses {
+            let (x, y) = to_minimap(marker);
+            self.circle(x, y, 3.0, "#ff0000");
+        }
+        for marker in &minimap.players {
+            let (x, y) = to_minimap(marker);
+            let color = if marker.id == own_id {
+                "#00ff00"
+            } else {
+                "#0099ff"
+            };
+            self.circle(x, y, 2.5, color);
+        }
+    }
+
+    fn draw_zones(&self) {
+        let (cx, cy) = self.to_screen(&Position::new(0.0, 0.0));
+
+        self.ctx.set_fill_style_str("rgba(0, 200, 0, 0.05)");
+        self.ctx.begin_path();
+        let _ = self.ctx.arc(cx, cy, SAFE_ZONE_RADIUS as f64, 0.0, TAU);
+        self.ctx.fill();
+        self.ctx.set_stroke_style_str("rgba(0, 200, 0, 0.3)");
+        self.ctx.stroke();
+
+        self.ctx.set_stroke_style_str("rgba(150, 100, 255, 0.2)");
+        self.ctx.set_fill_style_str("rgba(150, 100, 255, 0.3)");
+        self.ctx.set_font("10px Arial");
+        for ring in 1..=MAX_RINGS {
+            let radius = (ring as f32 * RING_RADIUS) as f64;
+            self.ctx.begin_path();
+            let _ = self.ctx.arc(cx, cy, radius, 0.0, TAU);
+            self.ctx.stroke();
+            let _ = self
+                .ctx
+                .fill_text(&format!("Ring {}", ring), cx + radius, cy);
+        }
+    }
+
+    fn draw_world_edge(&self, radius: f32) {
+        let (cx, cy) = self.to_screen(&Position::new(0.0, 0.0));
+        self.ctx.set_stroke_style_str("rgba(255, 60, 60, 0.8)");
+        self.ctx.set_line_width(3.0);
+        self.ctx.begin_path();
+        let _ = self.ctx.arc(cx, cy, radius as f64, 0.0, TAU);
+        self.ctx.stroke();
+        self.ctx.set_line_width(1.0);
+    }
+
+    fn draw_hud(&self, state: &ClientState) {
+        let Some(me) = state.own_player() else {
+            return;
+        };
+
+        self.ctx.set_fill_style_str("#ffffff");
+        self.ctx.set_font("14px Arial");
+        let lines = [
+            format!("HP: {:.0}/{:.0}", me.health, me.max_health),
+            format!(
+                "Level: {} ({}/{} XP)",
+                me.level, me.current_xp, me.xp_to_next_level
+            ),
+            format!("Ring: {}", me.max_ring_reached),
+            format!("Enemies: {}", me.enemies_defeated),
+            format!("Gold: {}", me.gold),
+            format!("Difficulty: {}", state.difficulty_tier),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            let _ = self.ctx.fill_text(line, 10.0, 20.0 + i as f64 * 18.0);
+        }
+        let gear_top = 20.0 + lines.len() as f64 * 18.0;
+        for (i, item) in me.equipment.iter().enumerate() {
+            let line = format!("{} (ring {})", item.slot.name(), item.level);
+            let _ = self.ctx.fill_text(&line, 10.0, gear_top + i as f64 * 18.0);
+        }
+
+        for (i, score) in state.scores.iter().take(10).enumerate() {
+            let line = format!(
+                "{}. Ring {} - {:.0}s - {} kills",
+                i + 1,
+                score.max_ring_reached,
+                score.survival_time_seconds,
+                score.enemies_defeated
+            );
+            let _ = self
+                .ctx
+                .fill_text(&line, self.width - 220.0, 20.0 + i as f64 * 16.0);
+        }
+
+        if let Some(line) = &state.boss_line {
+            let line = format!("{}: \"{}\"", line.enemy_type.name(), line.text);
+            self.draw_text_center(&line, 40.0);
+        }
+        if let Some(announcement) = &state.announcement {
+            self.draw_text_center(&announcement.text, 60.0);
+        }
+
+        if !state.upgrade_choices.is_empty() {
+            self.draw_text_center("LEVEL UP! Choose an upgrade:", self.height / 2.0 - 40.0);
+            for (i, text) in state.upgrade_text.iter().enumerate() {
+                let line = format!(
+                    "[{}] {} ({}) - {}",
+                    i + 1,
+                    text.name,
+                    text.rarity,
+                    text.description
+                );
+                self.draw_text_center(&line, self.height / 2.0 + i as f64 * 20.0);
+            }
+            let line = format!("[R] Reroll ({} left)", me.rerolls_left);
+            self.draw_text_center(&line, self.height / 2.0 + 70.0);
+        } else if let Some(teleport) = me.teleport {
+            let line = format!(
+                "Teleporting... {:.1}s",
+                (teleport.arrives_at - state.game_time).max(0.0)
+            );
+            self.draw_text_center(&line, self.height / 2.0 - 40.0);
+        } else if state.shop_open() && !state.shop.is_empty() {
+            self.draw_text_center("MERCHANT", self.height / 2.0 + 60.0);
+            for (i, listing) in state.shop.iter().enumerate() {
+                let line = format!(
+                    "[{}] {} - {} ({} gold)",
+                    i + 1,
+                    listing.item.name(),
+                    listing.item.description(),
+                    listing.price
+                );
+                self.draw_text_center(&line, self.height / 2.0 + 80.0 + i as f64 * 20.0);
+            }
+        }
+
+        if let Some(death) = &state.death {
+            let line = format!(
+                "GAME OVER - Ring {}, {:.1}s, {} kills",
+                death.max_ring, death.survival_time, death.enemies_defeated
+            );
+            self.draw_text_center(&line, self.height / 2.0 - 80.0);
+            if let Some(id) = death.run_id {
+                let line = format!("Share this run: /runs/{}", id);
+                self.draw_text_center(&line, self.height / 2.0 - 100.0);
+            }
+
+            let stats = &death.stats;
+            let line = format!(
+                "Damage dealt {:.0}, taken {:.0} - {} XP - {:.0} distance",
+                stats.damage_dealt, stats.damage_taken, stats.xp_collected, stats.distance_traveled
+            );
+            self.draw_text_center(&line, self.height / 2.0 - 60.0);
+            for (i, (weapon, damage)) in stats.damage_by_weapon.iter().enumerate() {
+                let line = format!("{}: {:.0}", weapon.name(), damage);
+                self.draw_text_center(&line, self.height / 2.0 - 40.0 + i as f64 * 16.0);
+            }
+        }
+    }
+
+    /// Blast rings grow out to the explosion's radius and fade
+    fn draw_blasts(&self, state: &ClientState) {
+        for blast in &state.blasts {
+            let age = ((state.game_time - blast.received_at) / EXPLOSION_LIFETIME).clamp(0.0, 1.0);
+            let (x, y) = self.to_screen(&blast.position);
+            self.ctx.set_global_alpha(1.0 - age);
+            self.ctx.set_stroke_style_str("#ff8800");
+            self.ctx.set_line_width(3.0);
+            self.ctx.begin_path();
+            let _ = self
+                .ctx
+                .arc(x, y, blast.radius as f64 * (0.3 + 0.7 * age), 0.0, TAU);
+            self.ctx.stroke();
+        }
+        self.ctx.set_global_alpha(1.0);
+        self.ctx.set_line_width(1.0);
+    }
+
+    /// Pings pulse in place and fade over their lifetime
+    fn draw_pings(&self, state: &ClientState) {
+        for ping in &state.pings {
+            let age = ((state.game_time - ping.received_at) / PING_LIFETIME).clamp(0.0, 1.0);
+            let pulse = (state.game_time - ping.received_at).fract();
+            let (x, y) = self.to_screen(&ping.position);
+            self.ctx.set_global_alpha(1.0 - age);
+            self.ctx.set_stroke_style_str("#ffee55");
+            self.ctx.set_line_width(2.0);
+            self.ctx.begin_path();
+            let _ = self.ctx.arc(x, y, 6.0 + 18.0 * pulse, 0.0, TAU);
+            self.ctx.stroke();
+            self.circle(x, y, 3.0, "#ffee55");
+        }
+        self.ctx.set_global_alpha(1.0);
+        self.ctx.set_line_width(1.0);
+    }
+
+    /// Each player's latest emote, over their health bar
+    fn draw_emotes(&self, state: &ClientState) {
+        self.ctx.set_text_align("center");
+        self.ctx.set_font("bold 13px Arial");
+        for emote in &state.emotes {
+            let Some(player) = state.players.iter().find(|p| p.id == emote.player_id) else {
+                continue;
+            };
+            let age = (state.game_time - emote.received_at) / EMOTE_LIFETIME;
+            let (x, y) = self.to_screen(&player.position);
+            self.ctx.set_global_alpha((1.0 - age).clamp(0.0, 1.0));
+            self.ctx.set_fill_style_str("#ffffff");
+            let _ = self.ctx.fill_text(emote_text(emote.kind), x, y - 34.0);
+        }
+        self.ctx.set_global_alpha(1.0);
+        self.ctx.set_text_align("start");
+    }
+
+    /// Numbers float up and fade over their lifetime
+    fn draw_damage_numbers(&self, state: &ClientState) {
+        self.ctx.set_text_align("center");
+        for number in &state.damage_numbers {
+            let age = (state.game_time - number.received_at) / DAMAGE_NUMBER_LIFETIME;
+            let (x, y) = self.to_screen(&number.event.position);
+            let amount = format!("{:.0}", number.event.amount);
+            let (text, color, font) = match number.event.kind {
+                CombatEventKind::Hit => (amount, "#ffffff", "14px Arial"),
+                CombatEventKind::Crit => (amount + "!", "#ffcc00", "bold 18px Arial"),
+                CombatEventKind::Blocked => ("Blocked".to_string(), "#88ccff", "12px Arial"),
+                CombatEventKind::Kill => (amount, "#ff4444", "bold 16px Arial"),
+                CombatEventKind::SecondWind => {
+                    ("Second Wind!".to_string(), "#66ff99", "bold 16px Arial")
+                }
+            };
+            self.ctx.set_global_alpha(1.0 - age.clamp(0.0, 1.0));
+            self.ctx.set_fill_style_str(color);
+            self.ctx.set_font(font);
+            let _ = self.ctx.fill_text(&text, x, y - 20.0 - age * 30.0);
+        }
+        self.ctx.set_global_alpha(1.0);
+        self.ctx.set_text_align("start");
+    }
+
+    fn draw_text_center(&self, text: &str, y: f64) {
+        self.ctx.set_fill_style_str("#ffffff");
+        self.ctx.set_font("16px Arial");
+        self.ctx.set_text_align("center");
+        let _ = self.ctx.fill_text(text, self.width / 2.0, y);
+        self.ctx.set_text_align("start");
+    }
+
+    fn circle(&self, x: f64, y: f64, radius: f64, color: &str) {
+        self.ctx.set_fill_style_str(color);
+        self.ctx.begin_path();
+        let _ = self.ctx.arc(x, y, radius, 0.0, TAU);
+        self.ctx.fill();
+    }
+
+    fn health_bar(&self, x: f64, y: f64, width: f64, ratio: f32) {
+        let ratio = ratio.clamp(0.0, 1.0) as f64;
+        let color = if ratio > 0.5 {
+            "#00ff00"
+        } else if ratio > 0.25 {
+            "#ffff00"
+        } else {
+            "#ff0000"
+        };
+        self.ctx.set_fill_style_str("#333333");
+        self.ctx.fill_rect(x - width / 2.0, y, width, 3.0);
+        self.ctx.set_fill_style_str(color);
+        self.ctx.fill_rect(x - width / 2.0, y, width * ratio, 3.0);
+    }
+
+    /// A thin bar over the health bar, for the shield left
+    fn shield_bar(&self, x: f64, y: f64, width: f64, ratio: f32) {
+        let ratio = ratio.clamp(0.0, 1.0) as f64;
+        self.ctx.set_fill_style_str("#66ccff");
+        self.ctx.fill_rect(x - width / 2.0, y, width * ratio, 2.0);
+    }
+}
+
+fn emote_text(kind: EmoteKind) -> &'static str {
+    match kind {
+        EmoteKind::Wave => "Hi!",
+        EmoteKind::Thanks => "Thanks!",
+        EmoteKind::Help => "Help!",
+        EmoteKind::FollowMe => "Follow me!",
+        EmoteKind::Attack => "Attack!",
+        EmoteKind::Retreat => "Fall back!",
+    }
+}
+
+fn landmark_color(landmark_type: LandmarkType) -> &'static str {
+    match landmark_type {
+        LandmarkType::Chest => "#b8860b",
+        LandmarkType::Shrine => "#9966ff",
+        LandmarkType::Obelisk => "#888888",
+        LandmarkType::Waypoint => "#33cccc",
+    }
+}
+
+fn pickup_color(pickup_type: PickupType) -> &'static str {
+    match pickup_type {
+        PickupType::HealthPotion => "#ff3366",
+        PickupType::DoubleDamage => "#ff8800",
+        PickupType::SpeedBoost => "#33ccff",
+        PickupType::Gold => "#ffd700",
+        PickupType::Equipment => "#66ff99",
+    }
+}
+
+fn enemy_color(enemy_type: EnemyType) -> &'static str {
+    match enemy_type {
+        EnemyType::Goblin => "#ffff00",
+        EnemyType::Orc => "#ff6600",
+        EnemyType::Wolf => "#ff3333",
+        EnemyType::Skeleton => "#cccccc",
+        EnemyType::Zombie => "#00ff00",
+        EnemyType::Wraith => "#cc99ff",
+        EnemyType::Demon => "#ff0000",
+        EnemyType::Troll => "#00ccff",
+        EnemyType::Dragon => "#ff00ff",
+        EnemyType::Lich => "#00ffff",
+    }
+}
@@ -0,0 +1,360 @@
+use shared::{
+    CombatEvent, EmoteKind, Enemy, EnemyType, Envelope, ErrorCode, Landmark, MinimapMarker,
+    OwnPlayerState, Pickup, Player, Position, Projectile, RemotePlayerView, RunStats, ScoreEntry,
+    ServerMessage, ShopItem, ShopListing, Summon, UpgradeText, UpgradeType,
+};
+use uuid::Uuid;
+
+/// World layout mirrored from the server's default `GameConfig`
+pub const SAFE_ZONE_RADIUS: f32 = 100.0;
+pub const RING_RADIUS: f32 = 200.0;
+pub const MAX_RINGS: u32 = 10;
+pub const INTERACT_RANGE: f32 = 40.0;
+/// Seconds a floating damage number stays on screen
+pub const DAMAGE_NUMBER_LIFETIME: f64 = 0.8;
+/// Seconds an explosion's blast ring stays on screen
+pub const EXPLOSION_LIFETIME: f64 = 0.4;
+/// Seconds a boss's line of dialogue stays on screen
+pub const BOSS_LINE_LIFETIME: f64 = 4.0;
+/// Seconds an operator announcement stays on screen
+pub const ANNOUNCEMENT_LIFETIME: f64 = 8.0;
+/// Seconds an emote stays over a player's head
+pub const EMOTE_LIFETIME: f64 = 2.5;
+/// Seconds a ping marker stays on the map
+pub const PING_LIFETIME: f64 = 4.0;
+
+/// Final stats shown on the death screen
+#[derive(Debug, Clone)]
+pub struct DeathSummary {
+    pub max_ring: u32,
+    pub survival_time: f32,
+    pub enemies_defeated: u32,
+    pub score_recorded: bool,
+    pub stats: RunStats,
+    pub run_id: Option<Uuid>, // the summary at GET /runs/{id}
+}
+
+/// The latest `MinimapUpdate`: the whole world at low detail
+#[derive(Debug, Clone)]
+pub struct Minimap {
+    pub map_radius: f32,
+    pub players: Vec<MinimapMarker>,
+    pub bosses: Vec<MinimapMarker>,
+    pub landmarks: Vec<MinimapMarker>,
+}
+
+/// A recent combat event and the game time it arrived
+#[derive(Debug, Clone)]
+pub struct DamageNumber {
+    pub event: CombatEvent,
+    pub received_at: f64,
+}
+
+/// A recent explosion and the game time it arrived
+#[derive(Debug, Clone)]
+pub struct Blast {
+    pub position: Position,
+    pub radius: f32,
+    pub received_at: f64,
+}
+
+/// The latest line a boss said, and the game time it arrived
+#[derive(Debug, Clone)]
+pub struct BossLine {
+    pub enemy_type: EnemyType,
+    pub text: String,
+    pub received_at: f64,
+}
+
+/// A player's latest emote, and the game time it arrived
+#[derive(Debug, Clone)]
+pub struct EmoteBubble {
+    pub player_id: Uuid,
+    pub kind: EmoteKind,
+    pub received_at: f64,
+}
+
+/// A spot a player pinged, and the game time it arrived
+#[derive(Debug, Clone)]
+pub struct Ping {
+    pub player_id: Uuid,
+    pub position: Position,
+    pub received_at: f64,
+}
+
+/// The latest operator announcement, and the game time it arrived
+#[derive(Debug, Clone)]
+pub struct Announcement {
+    pub text: String,
+    pub received_at: f64,
+}
+
+/// Client-side view of the world, rebuilt from server messages.
+///
+/// Kept free of any browser types so the protocol handling can be
+/// exercised in native tests.
+#[derive(Debug, Default)]
+pub struct ClientState {
+    pub player_id: Option<Uuid>,
+    /// Our own player in full, from the latest snapshot
+    pub own: Option<OwnPlayerState>,
+    /// Everyone in the room, us included, as the others see us
+    pub players: Vec<RemotePlayerView>,
+    pub enemies: Vec<Enemy>,
+    pub projectiles: Vec<Projectile>,
+    pub pickups: Vec<Pickup>,
+    pub summons: Vec<Summon>,
+    pub landmarks: Vec<Landmark>,
+    /// The merchant's stock, sent on join
+    pub shop: Vec<ShopListing>,
+    pub damage_numbers: Vec<DamageNumber>,
+    pub blasts: Vec<Blast>,
+    pub emotes: Vec<EmoteBubble>, // one per player at most
+    pub pings: Vec<Ping>,
+    pub boss_line: Option<BossLine>,
+    pub announcement: Option<Announcement>,
+    pub game_time: f64,
+    pub difficulty_tier: u32,
+    /// Server wall clock of the latest snapshot, for skew detection
+    pub server_time_ms: i64,
+    pub scores: Vec<ScoreEntry>,
+    pub upgrade_choices: Vec<UpgradeType>,
+    pub upgrade_text: Vec<UpgradeText>, // `upgrade_choices` in our locale
+    pub death: Option<DeathSummary>,
+    pub last_error: Option<String>,
+    /// Known once the server has warned us about the world edge
+    pub map_radius: Option<f32>,
+    pub minimap: Option<Minimap>,
+    /// `seq` of the latest envelope, and how many before it never arrived
+    pub last_seq: u64,
+    pub missed_messages: u64,
+}
+
+impl ClientState {
+    /// Note where an envelope sits in the stream, then apply its message
+    pub fn receive(&mut self, envelope: Envelope) {
+        self.missed_messages += envelope.seq.saturating_sub(self.last_seq + 1);
+        self.last_seq = self.last_seq.max(envelope.seq);
+        self.apply(envelope.message);
+    }
+
+    /// Apply a message received from the server
+    pub fn apply(&mut self, msg: ServerMessage) {
+        match msg {
+            ServerMessage::Welcome { player_id } => {
+                self.player_id = Some(player_id);
+            }
+            ServerMessage::GameState {
+                own_player,
+                players,
+                enemies,
+                projectiles,
+                pickups,
+                summons,
+                game_time,
+                difficulty_tier,
+                server_time_ms,
+                ..
+            } => {
+                self.own = own_player.map(|p| *p);
+                self.players = players;
+                self.enemies = enemies;
+                self.projectiles = projectiles;
+                self.pickups = pickups;
+                self.summons = summons;
+                self.game_time = game_time;
+                self.difficulty_tier = difficulty_tier;
+                self.server_time_ms = server_time_ms;
+                self.damage_numbers
+                    .retain(|n| game_time - n.received_at < DAMAGE_NUMBER_LIFETIME);
+                self.blasts
+                    .retain(|b| game_time - b.received_at < EXPLOSION_LIFETIME);
+                self.emotes
+                    .retain(|e| game_time - e.received_at < EMOTE_LIFETIME);
+                self.pings
+                    .retain(|p| game_time - p.received_at < PING_LIFETIME);
+                self.boss_line
+                    .take_if(|line| game_time - line.received_at >= BOSS_LINE_LIFETIME);
+                self.announcement
+                    .take_if(|a| game_time - a.received_at >= ANNOUNCEMENT_LIFETIME);
+            }
+            ServerMessage::PlayerDied {
+                player_id,
+                max_ring,
+                survival_time,
+                enemies_defeated,
+                score_recorded,
+                stats,
+                run_id,
+            } if Some(player_id) == self.player_id => {
+                self.death = Some(DeathSummary {
+                    max_ring,
+                    survival_time,
+                    enemies_defeated,
+                    score_recorded,
+                    stats,
+                    run_id,
+                });
+                self.upgrade_choices.clear();
+            }
+            ServerMessage::Scoreboard { scores, .. } => {
+                self.scores = scores;
+            }
+            ServerMessage::LevelUp {
+                player_id,
+                upgrade_choices,
+                choice_text,
+                ..
+            } if Some(player_id) == self.player_id => {
+                self.upgrade_text = UpgradeText::for_choices(&upgrade_choices, choice_text);
+                self.upgrade_choices = upgrade_choices;
+            }
+            ServerMessage::LevelUpExpired { player_id, .. }
+                if Some(player_id) == self.player_id =>
+            {
+                self.upgrade_choices.clear();
+            }
+            ServerMessage::BoundaryWarning {
+                player_id,
+                map_radius,
+            } if Some(player_id) == self.player_id => {
+                self.map_radius = Some(map_radius);
+            }
+            ServerMessage::CombatEvents { events } => {
+                let received_at = self.game_time;
+                self.damage_numbers.extend(
+                    events
+                        .into_iter()
+                        .map(|event| DamageNumber { event, received_at }),
+                );
+            }
+            ServerMessage::Explosion {
+                position, radius, ..
+            } => {
+                self.blasts.push(Blast {
+                    position,
+                    radius,
+                    received_at: self.game_time,
+                });
+            }
+            ServerMessage::Landmarks { landmarks } => {
+                self.landmarks = landmarks;
+            }
+            ServerMessage::MinimapUpdate {
+                map_radius,
+                players,
+                bosses,
+                landmarks,
+            } => {
+                self.minimap = Some(Minimap {
+                    map_radius,
+                    players,
+                    bosses,
+                    landmarks,
+                });
+            }
+            ServerMessage::BossDialogue {
+                enemy_type, text, ..
+            } => {
+                self.boss_line = Some(BossLine {
+                    enemy_type,
+                    text,
+                    received_at: self.game_time,
+                });
+            }
+            ServerMessage::Announcement { text } => {
+                self.announcement = Some(Announcement {
+                    text,
+                    received_at: self.game_time,
+                });
+            }
+            ServerMessage::Shop { listings } => {
+                self.shop = listings;
+            }
+            ServerMessage::Emoted { player_id, kind } => {
+                self.emotes.retain(|e| e.player_id != player_id);
+                self.emotes.push(EmoteBubble {
+                    player_id,
+                    kind,
+                    received_at: self.game_time,
+                });
+            }
+            ServerMessage::LocationPinged {
+                player_id,
+                position,
+            } => {
+                self.pings.push(Ping {
+                    player_id,
+                    position,
+                    received_at: self.game_time,
+                });
+            }
+            // Only the oldest queued moves were dropped; nothing to show
+            ServerMessage::Error {
+                code: ErrorCode::RateLimited,
+                ..
+            } => {}
+            ServerMessage::Error { message, .. } => {
+                self.last_error = Some(message);
+            }
+            // Events this client doesn't visualize yet
+            _ => {}
+        }
+    }
+
+    /// The player controlled by this client, if present in the last snapshot
+    pub fn own_player(&self) -> Option<&Player> {
+        self.own.as_ref()
+    }
+
+    /// Closest landmark the player could use right now, if within reach
+    pub fn usable_landmark_nearby(&self) -> Option<&Landmark> {
+        let me = self.own_player()?;
+        self.landmarks
+            .iter()
+            .filter(|l| l.is_usable(self.game_time) && !me.is_attuned(l))
+            .filter(|l| l.position.distance_to(&me.position) <= INTERACT_RANGE)
+            .min_by(|a, b| {
+                let dist_a = a.position.distance_to(&me.position);
+                let dist_b = b.position.distance_to(&me.position);
+                dist_a.total_cmp(&dist_b)
+            })
+    }
+
+    /// Where `E` at an attuned waypoint takes us: the next attuned waypoint
+    /// outwards, wrapping back to the safe zone after the furthest
+    pub fn next_waypoint(&self) -> Option<&Landmark> {
+        let me = self.own_player()?;
+        let mut attuned: Vec<&Landmark> =
+            self.landmarks.iter().filter(|l| me.is_attuned(l)).collect();
+        attuned.sort_by_key(|l| l.ring);
+        let here = attuned
+            .iter()
+            .position(|l| l.position.distance_to(&me.position) <= INTERACT_RANGE)?;
+        (attuned.len() > 1).then(|| attuned[(here + 1) % attuned.len()])
+    }
+
+    /// Whether the merchant is open to us: in the safe zone with no
+    /// upgrade offer using the number keys
+    pub fn shop_open(&self) -> bool {
+        self.upgrade_choices.is_empty()
+            && self
+                .own_player()
+                .is_some_and(|me| me.is_alive() && me.in_safe_zone)
+    }
+
+    /// The merchant's item at `index`, if the shop is open
+    pub fn shop_item(&self, index: usize) -> Option<ShopItem> {
+        if !self.shop_open() {
+            return None;
+        }
+        self.shop.get(index).map(|listing| listing.item)
+    }
+
+    /// Take the upgrade at `index` from the pending choices, clearing them
+    pub fn take_upgrade_choice(&mut self, index: usize) -> Option<UpgradeType> {
+        let upgrade = *self.upgrade_choices.get(index)?;
+        self.upgrade_choices.clear();
+        Some(upgrade)
+    }
+}
@@ -0,0 +1,151 @@
+use shared::{
+    EmoteKind, Envelope, Player, Position, RemotePlayerView, ServerMessage, ShopItem, ShopListing,
+    UpgradeType,
+};
+use uuid::Uuid;
+
+use crate::{ClientState, InputState};
+
+#[test]
+fn test_state_tracks_own_player() {
+    let mut state = ClientState::default();
+    let id = Uuid::new_v4();
+
+    state.apply(ServerMessage::Welcome { player_id: id });
+    let (me, other) = (Player::new(id), Player::new(Uuid::new_v4()));
+    state.apply(ServerMessage::GameState {
+        players: vec![
+            RemotePlayerView::of(&other, 1.5),
+            RemotePlayerView::of(&me, 1.5),
+        ],
+        own_player: Some(Box::new(me)),
+        enemies: vec![],
+        projectiles: vec![],
+        pickups: vec![],
+        summons: vec![],
+        game_time: 1.5,
+        difficulty_tier: 2,
+        tick: 30,
+        server_time_ms: 1_700_000_000_000,
+    });
+
+    assert_eq!(state.own_player().map(|p| p.id), Some(id));
+    assert!((state.game_time - 1.5).abs() < f64::EPSILON);
+    assert_eq!(state.difficulty_tier, 2);
+}
+
+#[test]
+fn test_gaps_in_envelope_sequence_count_as_missed() {
+    let mut state = ClientState::default();
+    let id = Uuid::new_v4();
+    for seq in [1, 2, 5] {
+        state.receive(Envelope {
+            server_tick: seq,
+            seq,
+            message: ServerMessage::Welcome { player_id: id },
+        });
+    }
+
+    assert_eq!(state.last_seq, 5);
+    assert_eq!(state.missed_messages, 2);
+    assert_eq!(state.player_id, Some(id));
+}
+
+#[test]
+fn test_level_up_choices_for_other_players_ignored() {
+    let mut state = ClientState::default();
+    let id = Uuid::new_v4();
+    state.apply(ServerMessage::Welcome { player_id: id });
+
+    state.apply(ServerMessage::LevelUp {
+        player_id: Uuid::new_v4(),
+        new_level: 2,
+        upgrade_choices: vec![UpgradeType::Armor],
+        choice_rarities: Vec::new(),
+        choice_text: Vec::new(),
+    });
+    assert!(state.upgrade_choices.is_empty());
+
+    state.apply(ServerMessage::LevelUp {
+        player_id: id,
+        new_level: 2,
+        upgrade_choices: vec![UpgradeType::Armor, UpgradeType::Luck],
+        choice_rarities: Vec::new(),
+        choice_text: Vec::new(),
+    });
+    assert_eq!(state.take_upgrade_choice(1), Some(UpgradeType::Luck));
+    assert!(state.upgrade_choices.is_empty());
+}
+
+#[test]
+fn test_input_diagonal_is_normalized() {
+    let mut input = InputState::default();
+    input.key_down("w");
+    input.key_down("d");
+
+    let (x, y) = input.movement_direction().unwrap();
+    assert!(((x * x + y * y).sqrt() - 1.0).abs() < 0.001);
+
+    input.key_up("w");
+    input.key_up("d");
+    assert!(input.movement_direction().is_none());
+}
+
+#[test]
+fn test_shop_only_takes_number_keys_in_the_safe_zone() {
+    let mut state = ClientState::default();
+    let id = Uuid::new_v4();
+    state.apply(ServerMessage::Welcome { player_id: id });
+    state.apply(ServerMessage::Shop {
+        listings: vec![ShopListing {
+            item: ShopItem::Heal,
+            price: 20,
+        }],
+    });
+    let mut me = Player::new(id);
+    me.in_safe_zone = false;
+    state.own = Some(me.clone());
+    assert_eq!(state.shop_item(0), None);
+
+    me.in_safe_zone = true;
+    state.own = Some(me);
+    assert_eq!(state.shop_item(0), Some(ShopItem::Heal));
+    assert_eq!(state.shop_item(1), None);
+
+    // An open upgrade offer has the number keys
+    state.apply(ServerMessage::LevelUp {
+        player_id: id,
+        new_level: 2,
+        upgrade_choices: vec![UpgradeType::Armor],
+        choice_rarities: Vec::new(),
+        choice_text: Vec::new(),
+    });
+    assert_eq!(state.shop_item(0), None);
+}
+
+#[test]
+fn test_emotes_replace_each_other_and_pings_pile_up() {
+    let mut state = ClientState::default();
+    let friend = Uuid::new_v4();
+    for kind in [EmoteKind::Wave, EmoteKind::Help] {
+        state.apply(ServerMessage::Emoted {
+            player_id: friend,
+            kind,
+        });
+    }
+    assert_eq!(state.emotes.len(), 1);
+    assert_eq!(state.emotes[0].kind, EmoteKind::Help);
+
+    for x in [10.0, 20.0] {
+        state.apply(ServerMessage::LocationPinged {
+            player_id: friend,
+            position: Position::new(x, 0.0),
+        });
+    }
+    assert_eq!(state.pings.len(), 2);
+
+    let mut input = InputState::default();
+    input.key_down("v");
+    assert_eq!(input.take_emote(), Some(EmoteKind::FollowMe));
+    assert_eq!(input.take_emote(), None);
+}
@@ -1,7 +1,7 @@
 import { GameClient } from "./network";
 import { GameRenderer } from "./renderer";
 import { InputHandler } from "./input";
-import { ServerMessage, Player, Enemy, Projectile, ScoreEntry, Position } from "./protocol";
+import { ServerMessage, Player, RemotePlayerView, Enemy, Projectile, ScoreEntry, Position } from "./protocol";
 import { logger } from "./logger";
 
 export class GameEngine {
@@ -10,7 +10,8 @@ export class GameEngine {
   private input: InputHandler;
 
   private playerId: string | null = null;
-  private players: Map<string, Player> = new Map();
+  private ownPlayer: Player | null = null;
+  private players: Map<string, RemotePlayerView> = new Map();
   private enemies: Map<string, Enemy> = new Map();
   private projectiles: Map<string, Projectile> = new Map();
   private scores: ScoreEntry[] = [];
@@ -43,11 +44,12 @@ export class GameEngine {
           break;
 
         case "GameState":
+          this.ownPlayer = message.own_player;
           this.players = new Map(message.players.map((p) => [p.id, p]));
           this.enemies = new Map(message.enemies.map((e) => [e.id, e]));
           this.projectiles = new Map(message.projectiles.map((p) => [p.id, p]));
           if (this.playerId) {
-            const myPlayer = this.players.get(this.playerId);
+            const myPlayer = this.ownPlayer;
             if (myPlayer) {
               logger.update(`Player position: (${myPlayer.position.x.toFixed(2)}, ${myPlayer.position.y.toFixed(2)}), Health: ${myPlayer.health}/${myPlayer.max_health}, Ring: ${myPlayer.current_ring}`);
             }
@@ -58,7 +60,7 @@ export class GameEngine {
           console.log("Player died:", message);
           if (message.player_id === this.playerId) {
             console.log("YOU DIED!");
-            alert(`Game Over! Final Stats:\nRing: ${message.max_ring}\nTime: ${message.survival_time.toFixed(1)}s\nKills: ${message.enemies_defeated}`);
+            alert(`Game Over! Final Stats:\nRing: ${message.max_ring}\nTime: ${message.survival_time.toFixed(1)}s\nKills: ${message.enemies_defeated}\nDamage dealt: ${message.stats.damage_dealt.toFixed(0)}\nDamage taken: ${message.stats.damage_taken.toFixed(0)}\nXP: ${message.stats.xp_collected}\nDistance: ${message.stats.distance_traveled.toFixed(0)}${message.run_id ? `\nShare this run: /runs/${message.run_id}` : ""}`);
             this.reset();
           }
           break;
@@ -69,7 +71,11 @@ export class GameEngine {
           break;
 
         case "Error":
-          console.error("Server error:", message.message);
+          console.error(`Server error (${message.code}):`, message.message);
+          break;
+
+        case "Announcement":
+          console.log("Announcement:", message.text);
           break;
       }
 
@@ -105,7 +111,7 @@ export class GameEngine {
   private update() {
     if (!this.playerId) return;
 
-    const currentPlayer = this.players.get(this.playerId);
+    const currentPlayer = this.ownPlayer;
     if (!currentPlayer) return;
 
     // Handle movement input
@@ -157,7 +163,7 @@ export class GameEngine {
   }
 
   private render() {
-    const currentPlayer = this.players.get(this.playerId || "");
+    const currentPlayer = this.ownPlayer;
     if (!currentPlayer) {
       this.renderer.clear();
       return;
@@ -179,7 +185,7 @@ export class GameEngine {
   }
 
   private updateUI() {
-    const currentPlayer = this.players.get(this.playerId || "");
+    const currentPlayer = this.ownPlayer;
     if (!currentPlayer) return;
 
     // Update stats
@@ -230,6 +236,7 @@ export class GameEngine {
 
   private reset() {
     this.playerId = null;
+    this.ownPlayer = null;
     this.players.clear();
     this.enemies.clear();
 
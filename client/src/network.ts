@@ -1,7 +1,9 @@
 import {
   ClientMessage,
+  Envelope,
   ServerMessage,
   Position,
+  PROTOCOL_VERSION,
 } from "./protocol";
 import { logger } from "./logger";
 import { logger } from "./logger";
@@ -15,6 +17,7 @@ export class GameClient {
   private reconnectAttempts = 0;
   private maxReconnectAttempts = 5;
   private reconnectDelay = 2000;
+  private inputSeq = 0; // numbers Move inputs for the server to acknowledge
 
   constructor(url: string = "ws://localhost:3000/ws") {
     this.url = url;
@@ -35,7 +38,7 @@ export class GameClient {
 
         this.ws.onmessage = (event) => {
           try {
-            const message = JSON.parse(event.data) as ServerMessage;
+            const message = JSON.parse(event.data) as Envelope;
                         logger.network(`Received message: ${message.type}`);
             this.callbacks.forEach((cb) => cb(message));
           } catch (e) {
@@ -97,14 +100,16 @@ export class GameClient {
     }
   }
 
-  join() {
+  /** `password` is only needed on a password-protected server */
+  join(password?: string) {
       logger.network("Sending Join message");
-    this.send({ type: "Join" });
+    this.send({ type: "Join", locale: navigator.language, protocol_version: PROTOCOL_VERSION, password });
   }
 
   move(target: Position) {
       logger.network(`Sending Move to (${target.x.toFixed(2)}, ${target.y.toFixed(2)})`);
-    this.send({ type: "Move", target });
+    this.inputSeq += 1;
+    this.send({ type: "Move", target, seq: this.inputSeq });
   }
 
   disconnect() {
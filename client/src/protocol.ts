@@ -2,6 +2,13 @@
 // Shared Protocol Types
 // ============================================================================
 
+/** WebSocket subprotocol for deflated binary frames. Not offered by this
+ * client, so the server keeps sending it plain JSON text. */
+export const DEFLATE_PROTOCOL = "deflate-json";
+
+/** The protocol version these types describe, sent with Join */
+export const PROTOCOL_VERSION = 2;
+
 export interface Position {
   x: number;
   y: number;
@@ -25,16 +32,73 @@ export interface Player {
   position: Position;
   health: number;
   max_health: number;
+  shield: number; // absorbs damage before health, wearing off over time
+  max_shield: number; // full width of the shield bar
+  second_wind: number; // share of max health a lethal hit leaves instead; 0 if none
+  second_wind_used: boolean;
   damage: number;
   attack_speed: number;
   movement_speed: number;
+  crit_chance: number;
+  crit_multiplier: number;
   last_attack_time: number;
   max_ring_reached: number;
   enemies_defeated: number;
+  bosses_defeated: number;
+  players_defeated: number;
   spawn_time: string;
   level: number;
   current_xp: number;
   xp_to_next_level: number;
+  rerolls_left: number;
+  banishes_left: number;
+  banished: UpgradeType[];
+  in_safe_zone: boolean;
+  safe_zone_time: number;
+  party_id: string | null;
+  gold: number;
+  damage_boost_until: number;
+  speed_boost_until: number;
+  dash_ready_at: number;
+  dashing_until: number;
+  dash_direction: Position;
+  invulnerable_until: number;
+  stats: RunStats;
+  equipment: Equipment[];
+  inventory: Equipment[];
+  waypoints: string[];
+  teleport: Teleport | null;
+  velocity: Position; // units/sec over the last tick
+  facing: number; // radians
+  last_processed_input: number; // seq of the latest Move/Dash applied
+}
+
+// Another player as the server shows them: no upgrades, gear or XP
+export interface RemotePlayerView {
+  id: string;
+  position: Position;
+  velocity: Position;
+  facing: number;
+  health_ratio: number; // 0-1 of max health
+  shield_ratio: number;
+  level: number;
+  party_id: string | null;
+  dashing: boolean;
+}
+
+export enum Weapon {
+  AutoAttack = "AutoAttack",
+  AttackDrone = "AttackDrone",
+  WolfPet = "WolfPet",
+  Aura = "Aura",
+}
+
+export interface RunStats {
+  damage_dealt: number;
+  damage_taken: number;
+  xp_collected: number;
+  distance_traveled: number;
+  damage_by_weapon: Partial<Record<Weapon, number>>;
 }
 
 export interface Enemy {
@@ -50,6 +114,22 @@ export interface Enemy {
   xp_reward: number;
   last_attack_time: number;
   target_player_id: string | null;
+  behavior: EnemyBehavior;
+  behavior_started: number;
+  charge_direction: Position;
+  velocity: Position; // units/sec over the last tick
+  facing: number; // radians
+}
+
+export enum EnemyBehavior {
+  Chase = "Chase",
+  Circle = "Circle",
+  Phased = "Phased",
+  Kite = "Kite",
+  WindUp = "WindUp",
+  Charge = "Charge",
+  Recover = "Recover",
+  Return = "Return",
 }
 
 export interface ScoreEntry {
@@ -57,9 +137,19 @@ export interface ScoreEntry {
   max_ring_reached: number;
   survival_time_seconds: number;
   enemies_defeated: number;
+  players_defeated: number;
+  party_members: string[];
   timestamp: string;
 }
 
+// How an entry moved on the scoreboard; ranks count from 1, null for a new
+// entry (from) or one pushed off the end (to)
+export interface RankChange {
+  player_id: string;
+  from: number | null;
+  to: number | null;
+}
+
 export interface Projectile {
   id: string;
   owner_id: string;
@@ -68,6 +158,102 @@ export interface Projectile {
   damage: number;
   lifetime: number;
   max_lifetime: number;
+  hostile: boolean;
+  weapon: Weapon;
+  turn_rate: number; // radians/sec it steers toward enemies; 0 flies straight
+  explosion: number; // fraction of damage dealt around where it lands; 0 doesn't explode
+}
+
+export enum CombatEventKind {
+  Hit = "Hit",
+  Crit = "Crit",
+  Blocked = "Blocked",
+  Kill = "Kill",
+  SecondWind = "SecondWind",
+}
+
+export interface CombatEvent {
+  attacker: string;
+  target: string;
+  amount: number;
+  kind: CombatEventKind;
+  position: Position;
+}
+
+export enum PickupType {
+  HealthPotion = "HealthPotion",
+  DoubleDamage = "DoubleDamage",
+  SpeedBoost = "SpeedBoost",
+  Gold = "Gold",
+  Equipment = "Equipment",
+}
+
+export interface Pickup {
+  id: string;
+  pickup_type: PickupType;
+  position: Position;
+  amount: number;
+  lifetime: number;
+  item: Equipment | null;
+}
+
+export enum EquipmentSlot {
+  Ring = "Ring",
+  Amulet = "Amulet",
+  Boots = "Boots",
+}
+
+export interface StatModifiers {
+  damage: number;
+  max_health: number;
+  movement_speed: number;
+  crit_chance: number;
+}
+
+export interface Equipment {
+  id: string;
+  slot: EquipmentSlot;
+  level: number;
+  modifiers: StatModifiers;
+}
+
+export enum LandmarkType {
+  Chest = "Chest",
+  Shrine = "Shrine",
+  Obelisk = "Obelisk",
+  Waypoint = "Waypoint",
+}
+
+export interface Landmark {
+  id: string;
+  landmark_type: LandmarkType;
+  position: Position;
+  ring: number;
+  opened: boolean;
+  ready_at: number;
+}
+
+export interface Teleport {
+  waypoint: string;
+  arrives_at: number;
+}
+
+export interface MinimapMarker {
+  id: string;
+  x: number; // whole units
+  y: number;
+}
+
+export enum ShopItem {
+  Heal = "Heal",
+  Reroll = "Reroll",
+  DamageBoost = "DamageBoost",
+  SpeedBoost = "SpeedBoost",
+}
+
+export interface ShopListing {
+  item: ShopItem;
+  price: number;
 }
 
 export enum UpgradeType {
@@ -76,25 +262,71 @@ export enum UpgradeType {
   IncreaseProjectileSpeed = "IncreaseProjectileSpeed",
   MultiShot = "MultiShot",
   PiercingShots = "PiercingShots",
+  HomingShots = "HomingShots",
+  ExplosiveShots = "ExplosiveShots",
+  CritChance = "CritChance",
   IncreaseMaxHealth = "IncreaseMaxHealth",
   IncreaseMovementSpeed = "IncreaseMovementSpeed",
   HealthRegeneration = "HealthRegeneration",
+  Overshield = "Overshield",
   PickupRadius = "PickupRadius",
   Magnet = "Magnet",
   Armor = "Armor",
   Luck = "Luck",
+  SecondWind = "SecondWind",
+  SummonDrone = "SummonDrone",
+  SummonWolf = "SummonWolf",
+  SummonDamage = "SummonDamage",
+  DamageAura = "DamageAura",
+  AuraRadius = "AuraRadius",
+}
+
+export enum SummonType {
+  AttackDrone = "AttackDrone",
+  WolfPet = "WolfPet",
+}
+
+export type Rarity = "Common" | "Rare" | "Epic";
+
+// Upgrade name, description and rarity in the locale sent with Join
+export interface UpgradeText {
+  name: string;
+  description: string;
+  rarity: string;
+}
+
+export interface Summon {
+  id: string;
+  owner_id: string;
+  summon_type: SummonType;
+  position: Position;
+  last_attack_time: number;
 }
 
 // Client to Server
 export type ClientMessage =
-  | { type: "Join" }
-  | { type: "Move"; target: Position }
-  | { type: "ChooseUpgrade"; upgrade: UpgradeType };
+  | { type: "Join"; locale?: string; protocol_version?: number; password?: string }
+  | { type: "Move"; target: Position; seq: number }
+  | { type: "ChooseUpgrade"; upgrade: UpgradeType }
+  | { type: "RerollUpgrades" }
+  | { type: "BanishUpgrade"; upgrade: UpgradeType }
+  | { type: "CreateParty" }
+  | { type: "JoinParty"; party_id: string }
+  | { type: "Interact"; landmark_id: string }
+  | { type: "Dash"; direction: Position; seq: number }
+  | { type: "SetViewport"; radius: number }
+  | { type: "BuyItem"; item: ShopItem }
+  | { type: "EquipItem"; item_id: string }
+  | { type: "DropItem"; item_id: string }
+  | { type: "TeleportTo"; waypoint: string };
+
+// Server to Client. Every message arrives with the tick it was produced in
+// and its place in the connection's stream alongside its own fields.
+export type Envelope = ServerMessage & { server_tick: number; seq: number };
 
-// Server to Client
 export type ServerMessage =
   | { type: "Welcome"; player_id: string }
-  | { type: "GameState"; players: Player[]; enemies: Enemy[]; projectiles: Projectile[]; game_time: number }
+  | { type: "GameState"; own_player: Player | null; players: RemotePlayerView[]; enemies: Enemy[]; projectiles: Projectile[]; pickups: Pickup[]; summons: Summon[]; game_time: number; difficulty_tier: number; tick: number; server_time_ms: number }
   | {
       type: "PlayerDied";
       player_id: string;
@@ -102,7 +334,56 @@ export type ServerMessage =
       survival_time: number;
       enemies_defeated: number;
       score_recorded: boolean;
+      stats: RunStats;
+      run_id: string | null; // shareable as GET /runs/{id}
     }
-  | { type: "Scoreboard"; scores: ScoreEntry[] }
-  | { type: "LevelUp"; player_id: string; new_level: number; upgrade_choices: UpgradeType[] }
-  | { type: "Error"; message: string };
+  | { type: "Scoreboard"; scores: ScoreEntry[]; changes: RankChange[] }
+  | { type: "LevelUp"; player_id: string; new_level: number; upgrade_choices: UpgradeType[]; choice_rarities: Rarity[]; choice_text: UpgradeText[] }
+  | { type: "LevelUpExpired"; player_id: string; auto_picked: UpgradeType | null }
+  | { type: "SafeZoneEntered"; player_id: string }
+  | { type: "SafeZoneExited"; player_id: string }
+  | { type: "ZoneUpdate"; closed_radius: number; next_closed_radius: number; next_shrink_time: number }
+  | { type: "MatchEnded"; reason: string; winners: string[] }
+  | { type: "BoundaryWarning"; player_id: string; map_radius: number }
+  | { type: "Landmarks"; landmarks: Landmark[] }
+  | { type: "LandmarkUsed"; player_id: string; landmark_id: string; reward: string }
+  | { type: "Shop"; listings: ShopListing[] }
+  | { type: "ItemBought"; player_id: string; item: ShopItem; gold_left: number }
+  | { type: "Teleported"; player_id: string; waypoint: string }
+  | { type: "MinimapUpdate"; map_radius: number; players: MinimapMarker[]; bosses: MinimapMarker[]; landmarks: MinimapMarker[] }
+  | { type: "TeleportInterrupted"; player_id: string }
+  | { type: "CombatEvents"; events: CombatEvent[] }
+  | { type: "Explosion"; owner_id: string; position: Position; radius: number }
+  | { type: "PickupCollected"; player_id: string; pickup_type: PickupType }
+  | { type: "PartyUpdate"; party_id: string; members: string[] }
+  | { type: "UdpOffer"; port: number; token: string }
+  | { type: "AfkWarning"; seconds_left: number | null }
+  | { type: "QueuePosition"; position: number; queue_length: number }
+  | { type: "Kicked"; reason: string }
+  | { type: "Announcement"; text: string }
+  | { type: "BossDialogue"; enemy_id: string; enemy_type: EnemyType; text: string }
+  | { type: "Error"; code: ErrorCode; message: string };
+
+// Why the server refused a message
+export type ErrorCode =
+  | "Refused"
+  | "Malformed"
+  | "TooLarge"
+  | "InvalidField"
+  | "InvalidUpgradeChoice"
+  | "NotInGame"
+  | "RateLimited"
+  | "RoomFull"
+  | "ProtocolMismatch"
+  | "WrongPassword";
+
+// GET /info
+export interface ServerInfo {
+  name: string;
+  motd: string;
+  players: number;
+  max_players: number | null;
+  mode: string;
+  uptime_secs: number;
+  protocol_version: number;
+}
@@ -46,7 +46,7 @@ export class GameRenderer {
 
   drawGameState(
     playerPos: Position,
-    players: { id: string; position: Position; health: number; max_health: number }[],
+    players: { id: string; position: Position; health_ratio: number }[],
     enemies: Enemy[],
     projectiles: Projectile[],
     safeZoneRadius: number,
@@ -159,7 +159,7 @@ export class GameRenderer {
 
   private drawPlayers(
     ctx: DrawContext,
-    players: { id: string; position: Position; health: number; max_health: number }[],
+    players: { id: string; position: Position; health_ratio: number }[],
     playerPos: Position
   ) {
     for (const player of players) {
@@ -179,7 +179,7 @@ export class GameRenderer {
       ctx.ctx.fill();
 
       // Draw health bar above player
-      this.drawHealthBar(ctx, screenX, screenY - radius - 15, 20, 4, player.health, player.max_health);
+      this.drawHealthBar(ctx, screenX, screenY - radius - 15, 20, 4, player.health_ratio, 1);
     }
   }
 
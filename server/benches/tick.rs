@@ -0,0 +1,91 @@
+//! Tick pipeline benchmarks. Run with `cargo bench -p server`.
+//!
+//! Each case builds a fresh world of `n` enemies, `n` projectiles and one
+//! player per ten enemies, laid out from a fixed seed so runs compare.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use server::config::GameConfig;
+use server::game_loop;
+use server::game_state::GameState;
+use shared::{Enemy, EnemyType, Position, Projectile};
+use uuid::Uuid;
+
+const SIZES: [usize; 3] = [10, 100, 1000];
+const DT: f32 = 0.05;
+
+fn random_position(rng: &mut StdRng, radius: f32) -> Position {
+    let r = rng.gen_range(0.0..radius);
+    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+    Position::new(r * angle.cos(), r * angle.sin())
+}
+
+fn world(n: usize) -> GameState {
+    let mut rng = StdRng::seed_from_u64(42);
+    // No spawning, to keep the pipeline from adding entities of its own
+    // mid-measurement
+    let mut game = GameState::new(GameConfig {
+        seed: Some(42),
+        enemy_spawn_rate: 0.0,
+        ..GameConfig::default()
+    });
+
+    let types = EnemyType::all();
+    let mut player_ids = Vec::new();
+    for _ in 0..(n / 10).max(1) {
+        let id = Uuid::new_v4();
+        game.add_player(id);
+        game.player_mut(id).unwrap().position = random_position(&mut rng, 1500.0);
+        player_ids.push(id);
+    }
+    for i in 0..n {
+        let enemy_type = types[i % types.len()];
+        let position = random_position(&mut rng, 1500.0);
+        let enemy = Enemy::new(Uuid::new_v4(), enemy_type, position, 1);
+        game.insert_enemy(enemy);
+    }
+    for i in 0..n {
+        let owner = player_ids[i % player_ids.len()];
+        let position = random_position(&mut rng, 1500.0);
+        let direction = random_position(&mut rng, 1.0) - Position::default();
+        let projectile = Projectile::new(owner, position, direction, 300.0, 10.0, 2.0);
+        game.insert_projectile(projectile);
+    }
+    game
+}
+
+/// Benchmark one stage of the pipeline against fresh worlds of each size
+fn bench_stage(c: &mut Criterion, name: &str, stage: fn(&mut GameState)) {
+    let mut group = c.benchmark_group(name);
+    for n in SIZES {
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, &n| {
+            b.iter_batched_ref(|| world(n), stage, BatchSize::LargeInput);
+        });
+    }
+    group.finish();
+}
+
+fn update_enemies(c: &mut Criterion) {
+    bench_stage(c, "update_enemies", |game| game.update_enemies(DT));
+}
+
+fn process_combat(c: &mut Criterion) {
+    bench_stage(c, "process_combat", |game| game.process_combat());
+}
+
+fn update_projectiles(c: &mut Criterion) {
+    bench_stage(c, "update_projectiles", |game| game.update_projectiles(DT));
+}
+
+fn full_tick(c: &mut Criterion) {
+    bench_stage(c, "full_tick", |game| game_loop::step(game, DT));
+}
+
+criterion_group!(
+    benches,
+    update_enemies,
+    process_combat,
+    update_projectiles,
+    full_tick
+);
+criterion_main!(benches);
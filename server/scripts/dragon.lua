@@ -0,0 +1,49 @@
+-- Example boss fight. Load every script in this directory with
+--   cargo run -p server --features lua -- --boss-scripts server/scripts
+-- A file is picked up by the boss it's named after; see src/boss_script.rs
+-- for what `boss` offers. Edits apply from the next Dragon that spawns.
+return {
+  phases = {
+    { name = "grounded" },
+    { name = "airborne", below = 0.6 },
+    { name = "enraged", below = 0.25 },
+  },
+
+  on_spawn = function(boss)
+    boss:say("Who dares wake me?")
+  end,
+
+  on_phase = function(boss, phase)
+    if phase == "airborne" then
+      boss:say("Burn!")
+      boss:radial(16, 8, 0.5)
+    elseif phase == "enraged" then
+      boss:say("I will not fall to the likes of you!")
+      boss:summon("Demon", 3)
+    end
+  end,
+
+  on_tick = function(boss, dt)
+    local memory = boss.memory
+    memory.cooldown = (memory.cooldown or 2) - dt
+    if memory.cooldown > 0 then
+      return
+    end
+
+    if boss.phase == "grounded" then
+      boss:aimed(3, 30, 10, 0.5)
+      memory.cooldown = 2
+    elseif boss.phase == "airborne" then
+      boss:radial(12, 7, 0.4)
+      memory.cooldown = 3
+    else
+      boss:aimed(5, 60, 12, 0.6)
+      boss:radial(8, 6, 0.4)
+      memory.cooldown = 1.5
+    end
+  end,
+
+  on_defeat = function(boss)
+    boss:say("The sky... grows dark...")
+  end,
+}
@@ -0,0 +1,29 @@
+;; Example rule script. Load it with
+;;   cargo run -p server --features scripting -- --script server/scripts/example.wat
+;; Scripts can be text (.wat) or compiled (.wasm); see src/scripting.rs for
+;; the exports the server looks for. All of them are optional.
+(module
+  ;; Trolls replace every Wolf from ring 3 outwards
+  ;; (indexes follow EnemyType::all(): Wolf is 2, Troll is 7)
+  (func (export "spawn_type") (param $ring i32) (param $default i32) (result i32)
+    (if (result i32)
+      (i32.and
+        (i32.ge_s (local.get $ring) (i32.const 3))
+        (i32.eq (local.get $default) (i32.const 2)))
+      (then (i32.const 7))
+      (else (local.get $default))))
+
+  ;; Damage+ (upgrade 0) gives 25% a level instead of the catalog's value
+  (func (export "upgrade_per_level") (param $upgrade i32) (param $default f32) (result f32)
+    (if (result f32)
+      (i32.eqz (local.get $upgrade))
+      (then (f32.const 0.25))
+      (else (local.get $default))))
+
+  ;; New players start with 20 gold
+  (func (export "on_player_join") (result i32)
+    (i32.const 20))
+
+  ;; A gold bounty of 2 per ring on every kill
+  (func (export "on_enemy_killed") (param $enemy i32) (param $ring i32) (result i32)
+    (i32.mul (local.get $ring) (i32.const 2))))
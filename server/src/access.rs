@@ -0,0 +1,199 @@
+//! Who may connect: the persistent ban list and per-IP connection counts,
+//! checked before a WebSocket upgrade or a QUIC session starts, and the
+//! `server_password` and `allowed_accounts` whitelist.
+//!
+//! The password and account are checked once the client says who it is:
+//! in the WebSocket handshake if it sends `PASSWORD_HEADER` or
+//! `ACCOUNT_HEADER`, otherwise in `Join`. Both are compared in constant
+//! time. Player IDs are handed out per connection for now, so a player ban
+//! only ends the session it's aimed at; IP bans keep the address out for
+//! good, and dropping an account from the whitelist keeps it out.
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+
+use crate::game_state::GameState;
+
+/// What a ban applies to; serialized as `{"ip": "..."}` or `{"player": "..."}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BanTarget {
+    Ip(IpAddr),
+    Player(Uuid),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ban {
+    pub target: BanTarget,
+    pub reason: Option<String>,
+    pub banned_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BanList {
+    bans: HashMap<BanTarget, Ban>,
+}
+
+impl BanList {
+    /// Read the list written by `save`; a missing file is an empty list
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let json = match std::fs::read(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+        };
+        let bans: Vec<Ban> =
+            serde_json::from_slice(&json).with_context(|| format!("parsing {}", path.display()))?;
+        Ok(bans.into_iter().collect())
+    }
+
+    /// Write the list to `path`, oldest ban first, through a temporary file
+    pub async fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let json = serde_json::to_vec_pretty(&self.to_vec())?;
+        let tmp_path = path.with_extension("tmp");
+        tokio::fs::write(&tmp_path, json)
+            .await
+            .with_context(|| format!("writing {}", tmp_path.display()))?;
+        tokio::fs::rename(&tmp_path, path)
+            .await
+            .with_context(|| format!("replacing {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn get(&self, target: &BanTarget) -> Option<&Ban> {
+        self.bans.get(target)
+    }
+
+    /// Add a ban, replacing any earlier one on the same target
+    pub fn insert(&mut self, ban: Ban) -> Option<Ban> {
+        self.bans.insert(ban.target, ban)
+    }
+
+    pub fn remove(&mut self, target: &BanTarget) -> Option<Ban> {
+        self.bans.remove(target)
+    }
+
+    /// Every ban, oldest first
+    pub fn to_vec(&self) -> Vec<Ban> {
+        let mut bans: Vec<Ban> = self.bans.values().cloned().collect();
+        bans.sort_by_key(|ban| ban.banned_at);
+        bans
+    }
+}
+
+impl FromIterator<Ban> for BanList {
+    fn from_iter<I: IntoIterator<Item = Ban>>(iter: I) -> Self {
+        Self {
+            bans: iter.into_iter().map(|ban| (ban.target, ban)).collect(),
+        }
+    }
+}
+
+/// Open connections per client IP. Shared with the connection tasks, which
+/// hold a `ConnectionGuard` for as long as they run.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionCounts(Arc<Mutex<HashMap<IpAddr, usize>>>);
+
+impl ConnectionCounts {
+    /// Count a new connection from `ip`, or `None` if it already has `max`
+    /// open. A `max` of 0 means no limit.
+    pub fn try_acquire(&self, ip: IpAddr, max: usize) -> Option<ConnectionGuard> {
+        let mut counts = self.0.lock().unwrap();
+        let count = counts.entry(ip).or_default();
+        if max > 0 && *count >= max {
+            return None;
+        }
+        *count += 1;
+        Some(ConnectionGuard {
+            counts: self.clone(),
+            ip,
+        })
+    }
+
+    pub fn get(&self, ip: IpAddr) -> usize {
+        self.0.lock().unwrap().get(&ip).copied().unwrap_or(0)
+    }
+
+    /// Open connections across every address
+    pub fn total(&self) -> usize {
+        self.0.lock().unwrap().values().sum()
+    }
+}
+
+/// Whether `given` is the server's password, or no password is needed
+pub fn password_matches(game: &GameState, given: Option<&str>) -> bool {
+    match &game.config().server_password {
+        Some(password) => given.is_some_and(|given| secret_matches(password, given)),
+        None => true,
+    }
+}
+
+/// Whether `given` (`name:key`) is a whitelisted account, or the server
+/// takes anyone. Every entry is compared, so the time taken doesn't tell
+/// which names are on the list.
+pub fn account_allowed(game: &GameState, given: Option<&str>) -> bool {
+    let allowed = &game.config().allowed_accounts;
+    if allowed.is_empty() {
+        return true;
+    }
+    let Some(given) = given else {
+        return false;
+    };
+    allowed.iter().fold(false, |found, account| {
+        found | secret_matches(account, given)
+    })
+}
+
+fn secret_matches(expected: &str, given: &str) -> bool {
+    expected.as_bytes().ct_eq(given.as_bytes()).into()
+}
+
+/// What the transport's handshake already proved, so `Join` needn't
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Handshake {
+    pub password_checked: bool,
+    pub account_checked: bool,
+}
+
+/// One counted connection; dropping it frees the slot
+#[derive(Debug)]
+pub struct ConnectionGuard {
+    counts: ConnectionCounts,
+    ip: IpAddr,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut counts = self.counts.0.lock().unwrap();
+        if let Some(count) = counts.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&self.ip);
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Rejection {
+    #[error("banned{}", .0.as_deref().map(|r| format!(": {}", r)).unwrap_or_default())]
+    Banned(Option<String>),
+    #[error("too many connections from this address")]
+    TooManyConnections,
+}
+
+/// Let `ip` open another connection, unless it's banned or at its limit
+pub fn admit(game: &GameState, ip: IpAddr) -> Result<ConnectionGuard, Rejection> {
+    if let Some(ban) = game.bans().get(&BanTarget::Ip(ip)) {
+        return Err(Rejection::Banned(ban.reason.clone()));
+    }
+    game.connections()
+        .try_acquire(ip, game.config().max_connections_per_ip)
+        .ok_or(Rejection::TooManyConnections)
+}
@@ -0,0 +1,384 @@
+//! Operator endpoints, all behind `Authorization: Bearer <admin_token>`.
+//! Not mounted at all unless `admin_token` is set.
+//!
+//! - `GET /admin/bans`: every ban, oldest first
+//! - `POST /admin/bans` with `{"target": {"ip": "..."}, "reason": "..."}`
+//!   (or `{"player": "<id>"}`): ban and kick whoever it covers
+//! - `DELETE /admin/bans/ip/{ip}`, `DELETE /admin/bans/player/{id}`: lift a ban
+//! - `GET /admin/status`: entity counts, tick timings and connected players
+//! - `POST /admin/players/{id}/kick`: kick a player or queued joiner
+//! - `GET /admin/logs?after=<seq>`: recent log lines, see `log_tail`
+//! - `GET /admin/reviews`: scores held back by the integrity checks, with
+//!   why, oldest first; see `integrity`
+//! - `POST /admin/reviews/{id}/approve`, `POST /admin/reviews/{id}/reject`:
+//!   put a held score on the scoreboard, or drop it
+//! - `POST /admin/announce` with `{"text": "..."}`: show a message to
+//!   everyone connected, on every server in the cluster if there is one
+//! - `GET /admin/rooms/{token}/sim`: whether a room's simulation is paused,
+//!   and how fast it runs; `main` is the main game
+//! - `POST /admin/rooms/{token}/sim` with `{"paused": true}` and/or
+//!   `{"speed": 2.0}` (0.25 to 4 times `tick_rate`): pause, resume or
+//!   change the speed
+//! - `POST /admin/rooms/{token}/sim/step` with `{"ticks": n}` (default 1):
+//!   run that many steps of a paused simulation
+//! - `GET /debug/tasks`: task counts and lock wait times, see `diagnostics`
+//!
+//! `GET /admin/ui` is a dashboard over these. The page itself holds no
+//! data and is served without the token, since a browser can't send one
+//! when navigating; it asks for the token and sends it with each request.
+
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::access::{Ban, BanList, BanTarget};
+use crate::diagnostics;
+use crate::game_loop::{SimControl, TickStats};
+use crate::game_mode::GameMode;
+use crate::game_state::{EntityCounts, SharedGameState};
+use crate::integrity::HeldScore;
+use crate::log_tail::LogLine;
+use crate::rooms::MAIN_ROOM;
+
+type AdminError = (StatusCode, String);
+
+pub fn router(token: String) -> Router<SharedGameState> {
+    let token = Arc::new(token);
+    Router::new()
+        .route("/admin/bans", get(list_bans).post(add_ban))
+        .route("/admin/bans/{kind}/{value}", delete(remove_ban))
+        .route("/admin/status", get(status))
+        .route("/admin/players/{id}/kick", post(kick_player))
+        .route("/admin/logs", get(logs))
+        .route("/admin/reviews", get(list_reviews))
+        .route("/admin/reviews/{id}/{verdict}", post(review))
+        .route("/admin/announce", post(announce))
+        .route("/admin/rooms/{token}/sim", get(sim).post(control_sim))
+        .route("/admin/rooms/{token}/sim/step", post(step_sim))
+        .route("/debug/tasks", get(diagnostics::tasks))
+        .layer(middleware::from_fn(move |request, next| {
+            require_token(token.clone(), request, next)
+        }))
+        .route("/admin/ui", get(dashboard))
+}
+
+async fn require_token(token: Arc<String>, request: Request, next: Next) -> Response {
+    let presented = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if presented != Some(token.as_str()) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    next.run(request).await
+}
+
+async fn list_bans(State(state): State<SharedGameState>) -> Json<Vec<Ban>> {
+    Json(state.read().await.bans().to_vec())
+}
+
+#[derive(Debug, Deserialize)]
+struct BanRequest {
+    target: BanTarget,
+    reason: Option<String>,
+}
+
+async fn add_ban(
+    State(state): State<SharedGameState>,
+    Json(request): Json<BanRequest>,
+) -> Result<(StatusCode, Json<Ban>), AdminError> {
+    let ban = Ban {
+        target: request.target,
+        reason: request.reason,
+        banned_at: chrono::Utc::now(),
+    };
+    let (bans, path) = {
+        let mut game = state.write().await;
+        let kicked = game.ban(ban.clone());
+        tracing::info!("Banned {:?}, kicked {:?}", ban.target, kicked);
+        (
+            game.bans().clone(),
+            PathBuf::from(&game.config().ban_list_path),
+        )
+    };
+    save(&bans, &path).await?;
+    Ok((StatusCode::CREATED, Json(ban)))
+}
+
+async fn remove_ban(
+    State(state): State<SharedGameState>,
+    Path((kind, value)): Path<(String, String)>,
+) -> Result<StatusCode, AdminError> {
+    let bad_target = || {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("bad ban target {}/{}", kind, value),
+        )
+    };
+    let target = match kind.as_str() {
+        "ip" => BanTarget::Ip(value.parse().map_err(|_| bad_target())?),
+        "player" => BanTarget::Player(value.parse::<Uuid>().map_err(|_| bad_target())?),
+        _ => return Err(bad_target()),
+    };
+
+    let (bans, path) = {
+        let mut game = state.write().await;
+        if game.unban(&target).is_none() {
+            return Err((StatusCode::NOT_FOUND, format!("{:?} isn't banned", target)));
+        }
+        tracing::info!("Lifted ban on {:?}", target);
+        (
+            game.bans().clone(),
+            PathBuf::from(&game.config().ban_list_path),
+        )
+    };
+    save(&bans, &path).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn dashboard() -> Html<&'static str> {
+    Html(include_str!("admin_ui.html"))
+}
+
+#[derive(Debug, Serialize)]
+struct AdminStatus {
+    tick: u64,
+    game_time: f64,
+    uptime_secs: u64,
+    mode: GameMode,
+    counts: Counts,
+    tick_rate: f64,
+    timings: TickStats,
+    sim: SimControl,
+    players: Vec<PlayerSummary>,
+}
+
+#[derive(Debug, Serialize)]
+struct Counts {
+    #[serde(flatten)]
+    entities: EntityCounts,
+    connections: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct PlayerSummary {
+    id: Uuid,
+    ip: Option<IpAddr>,
+    level: u32,
+    health: f32,
+    max_health: f32,
+    ring: u32, // furthest reached
+    kills: u32,
+    udp: bool, // snapshots over UDP instead of the WebSocket
+}
+
+async fn status(State(state): State<SharedGameState>) -> Json<AdminStatus> {
+    let game = state.read().await;
+    let mut players: Vec<PlayerSummary> = game
+        .players()
+        .map(|p| PlayerSummary {
+            id: p.id,
+            ip: game.player_ip(p.id),
+            level: p.level,
+            health: p.health,
+            max_health: p.max_health,
+            ring: p.max_ring_reached,
+            kills: p.enemies_defeated,
+            udp: game.uses_udp(p.id),
+        })
+        .collect();
+    players.sort_by_key(|p| std::cmp::Reverse(p.level));
+
+    Json(AdminStatus {
+        tick: game.tick(),
+        game_time: game.game_time(),
+        uptime_secs: game.uptime().as_secs(),
+        mode: game.config().mode,
+        counts: Counts {
+            entities: game.entity_counts(),
+            connections: game.connections().total(),
+        },
+        tick_rate: game.config().tick_rate,
+        timings: game.tick_stats().clone(),
+        sim: game.sim_control(),
+        players,
+    })
+}
+
+async fn kick_player(
+    State(state): State<SharedGameState>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AdminError> {
+    let mut game = state.write().await;
+    if !game.knows_player(id) {
+        return Err((StatusCode::NOT_FOUND, format!("no player {}", id)));
+    }
+    game.kick(id, "Kicked by an admin".to_string());
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+struct LogQuery {
+    after: Option<u64>, // the last `seq` already shown
+}
+
+async fn logs(
+    State(state): State<SharedGameState>,
+    Query(query): Query<LogQuery>,
+) -> Json<Vec<LogLine>> {
+    let tail = state.read().await.log_tail().clone();
+    Json(tail.since(query.after))
+}
+
+async fn list_reviews(State(state): State<SharedGameState>) -> Json<Vec<HeldScore>> {
+    Json(state.read().await.held_scores().to_vec())
+}
+
+async fn review(
+    State(state): State<SharedGameState>,
+    Path((id, verdict)): Path<(Uuid, String)>,
+) -> Result<StatusCode, AdminError> {
+    let approve = match verdict.as_str() {
+        "approve" => true,
+        "reject" => false,
+        _ => return Err((StatusCode::NOT_FOUND, format!("no verdict {}", verdict))),
+    };
+    let mut game = state.write().await;
+    let Some(held) = game.review_score(id, approve) else {
+        return Err((StatusCode::NOT_FOUND, format!("no held score {}", id)));
+    };
+    tracing::info!(
+        "Score of {} {}",
+        held.score.player_id,
+        if approve { "approved" } else { "rejected" }
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+struct AnnounceRequest {
+    text: String,
+}
+
+async fn announce(
+    State(state): State<SharedGameState>,
+    Json(request): Json<AnnounceRequest>,
+) -> Result<StatusCode, AdminError> {
+    let text = request.text.trim();
+    if text.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "empty announcement".to_string()));
+    }
+    state.write().await.announce(text.to_string());
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The game behind a room token, `main` for the main one
+async fn room(state: &SharedGameState, token: &str) -> Result<SharedGameState, AdminError> {
+    if token == MAIN_ROOM {
+        return Ok(state.clone());
+    }
+    let rooms = state.read().await.rooms().clone();
+    rooms
+        .get(token)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("no room {}", token)))
+}
+
+async fn sim(
+    State(state): State<SharedGameState>,
+    Path(token): Path<String>,
+) -> Result<Json<SimControl>, AdminError> {
+    let room = room(&state, &token).await?;
+    let control = room.read().await.sim_control();
+    Ok(Json(control))
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SimRequest {
+    paused: Option<bool>,
+    speed: Option<f64>,
+}
+
+async fn control_sim(
+    State(state): State<SharedGameState>,
+    Path(token): Path<String>,
+    Json(request): Json<SimRequest>,
+) -> Result<Json<SimControl>, AdminError> {
+    let room = room(&state, &token).await?;
+    let mut game = room.write().await;
+    let mut control = game.sim_control();
+    if let Some(speed) = request.speed {
+        control
+            .set_speed(speed)
+            .map_err(|reason| (StatusCode::BAD_REQUEST, reason))?;
+    }
+    if let Some(paused) = request.paused {
+        control.set_paused(paused);
+    }
+    if control != game.sim_control() {
+        tracing::info!(
+            "Simulation of {} {} at {}x",
+            token,
+            if control.paused { "paused" } else { "running" },
+            control.speed
+        );
+    }
+    game.set_sim_control(control);
+    Ok(Json(control))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct StepRequest {
+    ticks: u32,
+}
+
+impl Default for StepRequest {
+    fn default() -> Self {
+        Self { ticks: 1 }
+    }
+}
+
+async fn step_sim(
+    State(state): State<SharedGameState>,
+    Path(token): Path<String>,
+    request: Option<Json<StepRequest>>,
+) -> Result<Json<SimControl>, AdminError> {
+    let Json(request) = request.unwrap_or_default();
+    let room = room(&state, &token).await?;
+    let mut game = room.write().await;
+    let mut control = game.sim_control();
+    if let Err(reason) = control.queue_steps(request.ticks) {
+        let status = if control.paused {
+            StatusCode::BAD_REQUEST
+        } else {
+            StatusCode::CONFLICT
+        };
+        return Err((status, reason));
+    }
+    game.set_sim_control(control);
+    Ok(Json(control))
+}
+
+/// The change already applies in memory; a failed write only means it
+/// won't survive a restart, which the operator needs to hear about
+async fn save(bans: &BanList, path: &std::path::Path) -> Result<(), AdminError> {
+    bans.save(path).await.map_err(|e| {
+        tracing::error!("Failed to save ban list: {:#}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("applied but not saved: {:#}", e),
+        )
+    })
+}
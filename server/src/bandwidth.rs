@@ -0,0 +1,100 @@
+use shared::{cmp_distance, Position};
+use std::time::{Duration, Instant};
+
+use crate::config::GameConfig;
+
+/// How often a connection's usage is compared against its budget
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Per-connection send accounting. Each second over budget halves the
+/// snapshot rate (20 -> 10 -> 5 Hz with the defaults) and each second with
+/// room to spare doubles it back. Events are counted but never held back.
+#[derive(Debug)]
+pub struct BandwidthBudget {
+    budget: usize, // bytes per window
+    max_level: u32,
+    level: u32, // snapshots go out on every 2^level-th tick
+    tick: u64,
+    window_start: Instant,
+    window_bytes: usize,
+}
+
+impl BandwidthBudget {
+    pub fn new(config: &GameConfig, now: Instant) -> Self {
+        let mut max_level = 0;
+        while config.snapshot_rate / 2f32.powi(max_level as i32 + 1) >= config.min_snapshot_rate {
+            max_level += 1;
+        }
+
+        Self {
+            budget: config.bandwidth_budget,
+            max_level,
+            level: 0,
+            tick: 0,
+            window_start: now,
+            window_bytes: 0,
+        }
+    }
+
+    pub fn record(&mut self, bytes: usize) {
+        self.window_bytes += bytes;
+    }
+
+    /// Called once per snapshot tick; true if a snapshot should go out now
+    pub fn snapshot_due(&mut self, now: Instant) -> bool {
+        if now.duration_since(self.window_start) >= WINDOW {
+            self.adjust();
+            self.window_start = now;
+            self.window_bytes = 0;
+        }
+
+        let due = self.tick.is_multiple_of(1 << self.level);
+        self.tick += 1;
+        due
+    }
+
+    /// Fraction of the full snapshot rate currently being sent
+    pub fn rate_scale(&self) -> f32 {
+        1.0 / (1 << self.level) as f32
+    }
+
+    /// Over budget: snapshots should carry only the most relevant entities
+    pub fn under_pressure(&self) -> bool {
+        self.level > 0
+    }
+
+    fn adjust(&mut self) {
+        if self.window_bytes > self.budget && self.level < self.max_level {
+            self.level += 1;
+        } else if self.window_bytes < self.budget / 2 && self.level > 0 {
+            self.level -= 1;
+        } else {
+            return;
+        }
+        tracing::debug!(
+            "Snapshot rate now {:.0}% ({} bytes last second, budget {})",
+            self.rate_scale() * 100.0,
+            self.window_bytes,
+            self.budget
+        );
+    }
+}
+
+/// Keep the `limit` entities closest to `center`
+pub fn nearest<T>(
+    mut entities: Vec<T>,
+    center: &Position,
+    limit: usize,
+    position: impl Fn(&T) -> Position,
+) -> Vec<T> {
+    if entities.len() > limit {
+        entities.sort_unstable_by(|a, b| {
+            cmp_distance(
+                &position(a).distance_to(center),
+                &position(b).distance_to(center),
+            )
+        });
+        entities.truncate(limit);
+    }
+    entities
+}
@@ -0,0 +1,509 @@
+//! Boss encounters choreographed in Lua, so designers can rework a fight
+//! without recompiling the server.
+//!
+//! Each boss type may have a script in the `boss_scripts` directory, named
+//! after it (`dragon.lua`, `lich.lua`), that returns a table:
+//!
+//! ```lua
+//! return {
+//!   phases = { { name = "grounded" }, { name = "enraged", below = 0.5 } },
+//!   on_spawn = function(boss) boss:say("Who dares?") end,
+//!   on_phase = function(boss, phase) end,
+//!   on_tick = function(boss, dt) end,
+//!   on_defeat = function(boss) end,
+//! }
+//! ```
+//!
+//! A phase starts once the boss's health fraction falls to its `below`.
+//! `boss` has `x`, `y`, `health`, `max_health`, `health_fraction`, `phase`,
+//! `phase_time`, `target_x`/`target_y` (nil without a target) and
+//! `memory`, a table kept for the whole fight. Its methods queue what the
+//! boss does: `say(text)`, `radial(count, speed, damage_scale)`,
+//! `aimed(count, spread_degrees, speed, damage_scale)` and
+//! `summon(enemy_type, count)`. The boss's usual AI keeps running too.
+//!
+//! Scripts only get Lua's `math`, `string` and `table` libraries and an
+//! instruction budget per call. A changed script is picked up at the next
+//! fight against that boss; errors are logged and the call skipped.
+
+use anyhow::Context;
+use mlua::{Function, HookTriggers, IntoLua, Lua, LuaOptions, RegistryKey, StdLib, Table, Value};
+use shared::{Enemy, EnemyType, Position, Projectile, ServerMessage, Vec2};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use crate::entity_map::EntityId;
+use crate::game_state::GameState;
+use crate::plugin::GamePlugin;
+
+const INSTRUCTION_BUDGET: u32 = 100_000; // per script call
+const PROJECTILE_LIFETIME: f32 = 3.0;
+const MAX_PROJECTILES: u32 = 64; // per radial or aimed volley
+const MAX_SUMMONS: u32 = 8; // per summon
+const SUMMON_DISTANCE: f32 = 40.0; // from the boss
+
+/// Something a script told its boss to do
+#[derive(Debug, Clone, PartialEq)]
+enum BossAction {
+    Say(String),
+    Radial {
+        count: u32,
+        speed: f32,
+        damage_scale: f32,
+    },
+    Aimed {
+        count: u32,
+        spread: f32, // radians
+        speed: f32,
+        damage_scale: f32,
+    },
+    Summon {
+        enemy_type: EnemyType,
+        count: u32,
+    },
+}
+
+struct Phase {
+    name: String,
+    below: f32, // health fraction the phase starts at
+}
+
+/// One boss type's loaded script
+struct Encounter {
+    lua: Lua,
+    script: RegistryKey, // the table the script returned
+    phases: Vec<Phase>,
+    modified: Option<SystemTime>,
+}
+
+/// What a script sees of its boss
+struct BossView {
+    enemy: Enemy,
+    phase: String,
+    phase_time: f64,
+    target: Option<Position>,
+}
+
+impl Encounter {
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let source =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let libs = StdLib::MATH | StdLib::STRING | StdLib::TABLE;
+        let lua = Lua::new_with(libs, LuaOptions::default())?;
+        register_api(&lua)?;
+
+        let (script, phases) = {
+            limit(&lua);
+            let script: Table = lua
+                .load(&source)
+                .set_name(path.display().to_string())
+                .eval()
+                .with_context(|| format!("running {}", path.display()))?;
+            let mut phases = Vec::new();
+            if let Some(list) = script.get::<_, Option<Table>>("phases")? {
+                for phase in list.sequence_values::<Table>() {
+                    let phase = phase?;
+                    phases.push(Phase {
+                        name: phase.get("name")?,
+                        below: phase.get::<_, Option<f32>>("below")?.unwrap_or(1.0),
+                    });
+                }
+            }
+            (lua.create_registry_value(script)?, phases)
+        };
+        Ok(Self {
+            lua,
+            script,
+            phases,
+            modified,
+        })
+    }
+
+    /// Run the script's `hook`, if it has one, and collect what it told
+    /// the boss to do
+    fn call<'lua>(
+        &'lua self,
+        hook: &str,
+        view: &BossView,
+        memory: &RegistryKey,
+        arg: impl IntoLua<'lua>,
+    ) -> Vec<BossAction> {
+        let result = (|| -> mlua::Result<()> {
+            let script: Table = self.lua.registry_value(&self.script)?;
+            let Some(function) = script.get::<_, Option<Function>>(hook)? else {
+                return Ok(());
+            };
+            let boss = view.to_table(&self.lua, memory)?;
+            limit(&self.lua);
+            function.call::<_, ()>((boss, arg))
+        })();
+
+        let actions = self
+            .lua
+            .app_data_mut::<Vec<BossAction>>()
+            .map(|mut actions| std::mem::take(&mut *actions))
+            .unwrap_or_default();
+        match result {
+            Ok(()) => actions,
+            Err(e) => {
+                tracing::warn!("Boss script {} failed: {}", hook, e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Start a fresh instruction budget for the next call into the script
+fn limit(lua: &Lua) {
+    let triggers = HookTriggers::new().every_nth_instruction(INSTRUCTION_BUDGET);
+    lua.set_hook(triggers, |_, _| {
+        Err(mlua::Error::RuntimeError(
+            "instruction budget exceeded".to_string(),
+        ))
+    });
+}
+
+fn queue(lua: &Lua, action: BossAction) {
+    if let Some(mut actions) = lua.app_data_mut::<Vec<BossAction>>() {
+        actions.push(action);
+    }
+}
+
+/// The `boss` methods, behind a metatable every view shares
+fn register_api(lua: &Lua) -> mlua::Result<()> {
+    lua.set_app_data(Vec::<BossAction>::new());
+    let api = lua.create_table()?;
+    api.set(
+        "say",
+        lua.create_function(|lua, (_, text): (Table, String)| {
+            queue(lua, BossAction::Say(text));
+            Ok(())
+        })?,
+    )?;
+    api.set(
+        "radial",
+        lua.create_function(
+            |lua, (_, count, speed, damage_scale): (Table, u32, f32, Option<f32>)| {
+                let action = BossAction::Radial {
+                    count,
+                    speed,
+                    damage_scale: damage_scale.unwrap_or(1.0),
+                };
+                queue(lua, action);
+                Ok(())
+            },
+        )?,
+    )?;
+    api.set(
+        "aimed",
+        lua.create_function(
+            |lua, (_, count, spread, speed, damage_scale): (Table, u32, f32, f32, Option<f32>)| {
+                let action = BossAction::Aimed {
+                    count,
+                    spread: spread.to_radians(),
+                    speed,
+                    damage_scale: damage_scale.unwrap_or(1.0),
+                };
+                queue(lua, action);
+                Ok(())
+            },
+        )?,
+    )?;
+    api.set(
+        "summon",
+        lua.create_function(|lua, (_, name, count): (Table, String, Option<u32>)| {
+            let enemy_type = EnemyType::all()
+                .into_iter()
+                .find(|t| t.name().eq_ignore_ascii_case(&name))
+                .ok_or_else(|| mlua::Error::RuntimeError(format!("no enemy type {name}")))?;
+            let count = count.unwrap_or(1);
+            queue(lua, BossAction::Summon { enemy_type, count });
+            Ok(())
+        })?,
+    )?;
+    let meta = lua.create_table()?;
+    meta.set("__index", api)?;
+    lua.set_named_registry_value("boss_meta", meta)
+}
+
+impl BossView {
+    fn to_table<'lua>(&self, lua: &'lua Lua, memory: &RegistryKey) -> mlua::Result<Table<'lua>> {
+        let boss = lua.create_table()?;
+        let enemy = &self.enemy;
+        boss.set("x", enemy.position.x)?;
+        boss.set("y", enemy.position.y)?;
+        boss.set("health", enemy.health)?;
+        boss.set("max_health", enemy.max_health)?;
+        boss.set("health_fraction", enemy.health / enemy.max_health)?;
+        boss.set("phase", self.phase.as_str())?;
+        boss.set("phase_time", self.phase_time)?;
+        if let Some(target) = self.target {
+            boss.set("target_x", target.x)?;
+            boss.set("target_y", target.y)?;
+        }
+        boss.set("memory", lua.registry_value::<Table>(memory)?)?;
+        boss.set_metatable(Some(lua.named_registry_value("boss_meta")?));
+        Ok(boss)
+    }
+}
+
+/// A boss in the world and how far its fight has got
+struct Fight {
+    enemy_type: EnemyType,
+    enemy_id: Uuid,
+    phase: usize,
+    phase_started: f64,
+    memory: Option<RegistryKey>, // None when the boss has no script
+}
+
+/// Runs the scripted encounter of every boss in the world
+pub struct BossDirector {
+    dir: PathBuf,
+    encounters: Mutex<HashMap<EnemyType, Encounter>>, // Lua states aren't Sync
+    fights: HashMap<EntityId, Fight>,
+}
+
+impl BossDirector {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            encounters: Mutex::default(),
+            fights: HashMap::new(),
+        }
+    }
+
+    /// Load the boss's script, or reload it if it changed since. Left alone
+    /// while a fight with that boss is on.
+    fn refresh(&mut self, enemy_type: EnemyType) {
+        if self.fights.values().any(|f| f.enemy_type == enemy_type) {
+            return;
+        }
+        let encounters = self.encounters.get_mut().unwrap_or_else(|e| e.into_inner());
+        let path = self
+            .dir
+            .join(format!("{}.lua", enemy_type.name().to_lowercase()));
+        let Ok(modified) = std::fs::metadata(&path).and_then(|m| m.modified()) else {
+            encounters.remove(&enemy_type);
+            return;
+        };
+        if encounters
+            .get(&enemy_type)
+            .is_some_and(|e| e.modified == Some(modified))
+        {
+            return;
+        }
+        match Encounter::load(&path) {
+            Ok(encounter) => {
+                tracing::info!("Loaded boss script {}", path.display());
+                encounters.insert(enemy_type, encounter);
+            }
+            Err(e) => tracing::warn!("Boss script {} not loaded: {:#}", path.display(), e),
+        }
+    }
+
+    /// Run `hook` for the fight against `id` and carry out what it says
+    fn run(&mut self, game: &mut GameState, id: EntityId, hook: &str, arg: HookArg) {
+        let (Some(fight), Some(enemy)) = (self.fights.get(&id), game.enemies().get(id)) else {
+            return;
+        };
+        let encounters = self.encounters.get_mut().unwrap_or_else(|e| e.into_inner());
+        let (Some(encounter), Some(memory)) = (encounters.get(&fight.enemy_type), &fight.memory)
+        else {
+            return;
+        };
+        let view = BossView {
+            enemy: enemy.clone(),
+            phase: encounter
+                .phases
+                .get(fight.phase)
+                .map(|p| p.name.clone())
+                .unwrap_or_default(),
+            phase_time: game.game_time() - fight.phase_started,
+            target: enemy
+                .target_player_id
+                .and_then(|p| game.player(p))
+                .map(|p| p.position),
+        };
+        let actions = match arg {
+            HookArg::None => encounter.call(hook, &view, memory, Value::Nil),
+            HookArg::Phase(name) => encounter.call(hook, &view, memory, name),
+            HookArg::Delta(delta_time) => encounter.call(hook, &view, memory, delta_time),
+        };
+        for action in actions {
+            perform(game, &view.enemy, action);
+        }
+    }
+
+    fn start_fight(&mut self, game: &mut GameState, id: EntityId) {
+        let Some(enemy) = game.enemies().get(id) else {
+            return;
+        };
+        let (enemy_type, enemy_id) = (enemy.enemy_type, enemy.id);
+        self.refresh(enemy_type);
+        let encounters = self.encounters.get_mut().unwrap_or_else(|e| e.into_inner());
+        let memory = encounters.get(&enemy_type).and_then(|e| {
+            let table = e.lua.create_table().ok()?;
+            e.lua.create_registry_value(table).ok()
+        });
+        let fight = Fight {
+            enemy_type,
+            enemy_id,
+            phase: 0,
+            phase_started: game.game_time(),
+            memory,
+        };
+        self.fights.insert(id, fight);
+        self.run(game, id, "on_spawn", HookArg::None);
+    }
+
+    /// Move the fight on to every phase the boss's health has reached
+    fn advance_phases(&mut self, game: &mut GameState, id: EntityId) {
+        loop {
+            let (Some(fight), Some(enemy)) = (self.fights.get(&id), game.enemies().get(id)) else {
+                return;
+            };
+            let encounters = self.encounters.get_mut().unwrap_or_else(|e| e.into_inner());
+            let Some(next) = encounters
+                .get(&fight.enemy_type)
+                .and_then(|e| e.phases.get(fight.phase + 1))
+            else {
+                return;
+            };
+            if enemy.health / enemy.max_health > next.below {
+                return;
+            }
+            let name = next.name.clone();
+            if let Some(fight) = self.fights.get_mut(&id) {
+                fight.phase += 1;
+                fight.phase_started = game.game_time();
+            }
+            self.run(game, id, "on_phase", HookArg::Phase(name));
+        }
+    }
+}
+
+enum HookArg {
+    None,
+    Phase(String),
+    Delta(f32),
+}
+
+/// Carry out one of a boss's scripted actions
+fn perform(game: &mut GameState, boss: &Enemy, action: BossAction) {
+    let volley = |game: &mut GameState, angles: Vec<f32>, speed: f32, damage_scale: f32| {
+        for angle in angles {
+            let mut projectile = Projectile::new(
+                boss.id,
+                boss.position,
+                Vec2::from_angle(angle),
+                speed,
+                boss.damage * damage_scale,
+                PROJECTILE_LIFETIME,
+            );
+            projectile.hostile = true;
+            game.insert_projectile(projectile);
+        }
+    };
+
+    match action {
+        BossAction::Say(text) => game.broadcast(ServerMessage::BossDialogue {
+            enemy_id: boss.id,
+            enemy_type: boss.enemy_type,
+            text,
+        }),
+        BossAction::Radial {
+            count,
+            speed,
+            damage_scale,
+        } => {
+            let count = count.min(MAX_PROJECTILES);
+            let angles = (0..count)
+                .map(|i| std::f32::consts::TAU * i as f32 / count as f32)
+                .collect();
+            volley(game, angles, speed, damage_scale);
+        }
+        BossAction::Aimed {
+            count,
+            spread,
+            speed,
+            damage_scale,
+        } => {
+            let Some(target) = boss
+                .target_player_id
+                .and_then(|p| game.player(p))
+                .map(|p| p.position)
+            else {
+                return;
+            };
+            let count = count.min(MAX_PROJECTILES);
+            let aim = (target - boss.position).angle();
+            let angles = (0..count)
+                .map(|i| match count {
+                    1 => aim,
+                    _ => aim - spread / 2.0 + spread * i as f32 / (count - 1) as f32,
+                })
+                .collect();
+            volley(game, angles, speed, damage_scale);
+        }
+        BossAction::Summon { enemy_type, count } => {
+            let count = count.min(MAX_SUMMONS);
+            for i in 0..count {
+                let angle = std::f32::consts::TAU * i as f32 / count as f32;
+                let position = Position::new(
+                    boss.position.x + SUMMON_DISTANCE * angle.cos(),
+                    boss.position.y + SUMMON_DISTANCE * angle.sin(),
+                );
+                let mut add = game.config().enemies.spawn(
+                    Uuid::new_v4(),
+                    enemy_type,
+                    position,
+                    boss.spawn_ring,
+                );
+                add.scale_stats(game.difficulty_multiplier());
+                game.insert_enemy(add);
+            }
+        }
+    }
+}
+
+impl GamePlugin for BossDirector {
+    fn name(&self) -> &str {
+        "boss scripts"
+    }
+
+    fn on_tick(&mut self, game: &mut GameState, delta_time: f32) {
+        // Bosses can also leave without dying, e.g. when the match resets
+        self.fights.retain(|id, _| game.enemies().contains(*id));
+
+        let bosses: Vec<EntityId> = game
+            .enemies()
+            .iter()
+            .filter(|(_, e)| e.is_alive() && game.config().enemies.is_boss(e.enemy_type))
+            .map(|(id, _)| id)
+            .collect();
+        for id in bosses {
+            if !self.fights.contains_key(&id) {
+                self.start_fight(game, id);
+            }
+            self.advance_phases(game, id);
+            self.run(game, id, "on_tick", HookArg::Delta(delta_time));
+        }
+    }
+
+    fn on_enemy_killed(&mut self, game: &mut GameState, enemy: &Enemy, _killer: Uuid) {
+        let Some(id) = self
+            .fights
+            .iter()
+            .find(|(_, f)| f.enemy_id == enemy.id)
+            .map(|(id, _)| *id)
+        else {
+            return;
+        };
+        self.run(game, id, "on_defeat", HookArg::None);
+        self.fights.remove(&id);
+    }
+}
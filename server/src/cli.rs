@@ -0,0 +1,251 @@
+//! Command line for the `server` binary. Running it without a subcommand
+//! is `serve`. Most options can also be set through the environment
+//! variables named in `--help`.
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use server::config::GameConfig;
+use server::game_mode::GameMode;
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(name = "server", version, about = "Multiplayer game server")]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub serve: ServeArgs,
+
+    /// Log output: human-readable lines, or one JSON object per event with
+    /// the enclosing spans (`tick`, `connection`, ...) and their fields
+    #[arg(long, value_enum, default_value_t = LogFormat::Text, global = true)]
+    pub log_format: LogFormat,
+}
+
+impl Cli {
+    /// The subcommand to run, `serve` when none was given
+    pub fn into_command(self) -> Command {
+        self.command.unwrap_or(Command::Serve(self.serve))
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the game server (the default)
+    Serve(ServeArgs),
+    /// Check the configuration the server would run with, then exit
+    ValidateConfig(ConfigArgs),
+    /// Run the simulation offline on a saved world, e.g. a crash snapshot
+    Replay(ReplayArgs),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Where the configuration comes from: defaults, then `--config`, then the
+/// options here
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    /// JSON file with the `GameConfig` fields to change from the defaults
+    #[arg(long, env = "GAME_CONFIG", value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// TOML file with the enemy stats, XP and ring spawns to use instead of
+    /// the built-in ones
+    #[arg(long, env = "ENEMIES_FILE", value_name = "FILE")]
+    pub enemies: Option<PathBuf>,
+
+    /// TOML file with what each upgrade level is worth, instead of the
+    /// built-in magnitudes
+    #[arg(long, env = "UPGRADES_FILE", value_name = "FILE")]
+    pub upgrades: Option<PathBuf>,
+
+    /// WebAssembly rule script to run; repeat for several. Needs a build
+    /// with the scripting feature
+    #[arg(long = "script", value_name = "FILE")]
+    pub scripts: Vec<String>,
+
+    /// Directory of Lua boss fight scripts, one per boss type
+    /// (`dragon.lua`). Needs a build with the lua feature
+    #[arg(long, env = "BOSS_SCRIPTS", value_name = "DIR")]
+    pub boss_scripts: Option<String>,
+
+    /// Fixed world seed; random per match when unset
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// endless, timed, wave_defense, battle_royale or daily
+    #[arg(long, env = "GAME_MODE")]
+    pub mode: Option<GameMode>,
+
+    /// Players in the game at once; 0 for no cap
+    #[arg(long)]
+    pub max_players: Option<usize>,
+
+    /// Offer clients a UDP channel for snapshots and inputs on this port
+    #[arg(long, env = "UDP_PORT")]
+    pub udp_port: Option<u16>,
+
+    /// PEM certificate chain; with --tls-key, serve wss:// directly
+    #[arg(long, env = "TLS_CERT", value_name = "FILE")]
+    pub tls_cert: Option<String>,
+
+    /// PEM private key for --tls-cert
+    #[arg(long, env = "TLS_KEY", value_name = "FILE")]
+    pub tls_key: Option<String>,
+
+    /// Browser origins allowed to call the HTTP endpoints, comma separated
+    #[arg(long, env = "CORS_ORIGINS", value_delimiter = ',')]
+    pub cors_origins: Vec<String>,
+
+    /// Take client IPs from X-Forwarded-For; only behind a proxy
+    #[arg(long, env = "TRUST_FORWARDED_FOR")]
+    pub trust_forwarded_for: bool,
+
+    /// Prefix for every route, e.g. /game
+    #[arg(long, env = "BASE_PATH")]
+    pub base_path: Option<String>,
+
+    /// Built web client to serve at /play
+    #[arg(long, env = "CLIENT_DIR", value_name = "DIR")]
+    pub client_dir: Option<String>,
+
+    /// Bearer token for the /admin and /debug routes; unset disables them
+    #[arg(long, env = "ADMIN_TOKEN", hide_env_values = true)]
+    pub admin_token: Option<String>,
+
+    /// Players must give this to join; see PASSWORD_HEADER in shared
+    #[arg(long, env = "SERVER_PASSWORD", hide_env_values = true)]
+    pub server_password: Option<String>,
+
+    /// Shown by GET /info
+    #[arg(long, env = "SERVER_NAME")]
+    pub server_name: Option<String>,
+
+    /// Message of the day, shown by GET /info
+    #[arg(long, env = "MOTD")]
+    pub motd: Option<String>,
+
+    /// Share the scoreboard and announcements through this Redis server;
+    /// needs the redis feature
+    #[arg(long, env = "REDIS_URL", hide_env_values = true)]
+    pub redis_url: Option<String>,
+
+    /// Servers sharing a Redis server and this name share a scoreboard
+    #[arg(long, env = "CLUSTER_NAME")]
+    pub cluster_name: Option<String>,
+}
+
+impl ConfigArgs {
+    pub fn load(&self) -> anyhow::Result<GameConfig> {
+        let mut config = match &self.config {
+            Some(path) => GameConfig::load(path)?,
+            None => GameConfig::default(),
+        };
+        if let Some(path) = &self.enemies {
+            config.load_enemies(path)?;
+        }
+        if let Some(path) = &self.upgrades {
+            config.load_upgrades(path)?;
+        }
+        config.scripts.extend(self.scripts.iter().cloned());
+        if let Some(dir) = &self.boss_scripts {
+            config.boss_scripts = Some(dir.clone());
+        }
+        if let Some(seed) = self.seed {
+            config.seed = Some(seed);
+        }
+        if let Some(mode) = self.mode {
+            config.mode = mode;
+        }
+        if let Some(max_players) = self.max_players {
+            config.max_players = max_players;
+        }
+        if let Some(port) = self.udp_port {
+            config.udp_port = Some(port);
+        }
+        if self.tls_cert.is_some() {
+            config.tls_cert = self.tls_cert.clone();
+        }
+        if self.tls_key.is_some() {
+            config.tls_key = self.tls_key.clone();
+        }
+        if !self.cors_origins.is_empty() {
+            config.cors_origins = self
+                .cors_origins
+                .iter()
+                .map(|o| o.trim().to_string())
+                .collect();
+        }
+        config.trust_forwarded_for |= self.trust_forwarded_for;
+        if let Some(base_path) = &self.base_path {
+            config.base_path = base_path.clone();
+        }
+        if self.client_dir.is_some() {
+            config.client_dir = self.client_dir.clone();
+        }
+        if self.admin_token.is_some() {
+            config.admin_token = self.admin_token.clone();
+        }
+        if self.server_password.is_some() {
+            config.server_password = self.server_password.clone();
+        }
+        if let Some(name) = &self.server_name {
+            config.server_name = name.clone();
+        }
+        if let Some(motd) = &self.motd {
+            config.motd = motd.clone();
+        }
+        if self.redis_url.is_some() {
+            config.redis_url = self.redis_url.clone();
+        }
+        if let Some(name) = &self.cluster_name {
+            config.cluster_name = name.clone();
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+
+    /// Address to listen on
+    #[arg(long, env = "SERVER_ADDR", default_value = "0.0.0.0:3000")]
+    pub bind: String,
+
+    /// Pick up a saved world: this snapshot, or `snapshot_path` if no file
+    /// is given
+    #[arg(long, value_name = "SNAPSHOT", num_args = 0..=1)]
+    pub resume: Option<Option<PathBuf>>,
+
+    /// Also accept raw QUIC clients on this address
+    #[cfg(feature = "quic")]
+    #[arg(long, env = "QUIC_ADDR")]
+    pub quic_addr: Option<std::net::SocketAddr>,
+
+    /// PEM certificate for QUIC; self-signed when unset
+    #[cfg(feature = "quic")]
+    #[arg(long, env = "QUIC_CERT", value_name = "FILE", requires = "quic_key")]
+    pub quic_cert: Option<PathBuf>,
+
+    /// PEM private key for --quic-cert
+    #[cfg(feature = "quic")]
+    #[arg(long, env = "QUIC_KEY", value_name = "FILE", requires = "quic_cert")]
+    pub quic_key: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    /// World snapshot, e.g. world_snapshot.crash-<timestamp>.json
+    pub file: PathBuf,
+
+    /// Simulation steps to run
+    #[arg(long, default_value_t = 200)]
+    pub ticks: u64,
+}
@@ -0,0 +1,176 @@
+//! Several servers presenting one meta layer through Redis, with the
+//! `redis` feature and `redis_url` set. Servers sharing a `cluster_name`
+//! share two things:
+//!
+//! - The scoreboard: each server adds its standings to a Redis sorted set
+//!   every `SYNC_INTERVAL` and merges the top entries back, so every
+//!   server lists the same best runs. Daily boards get a set per day.
+//! - Announcements: one made on any server (`POST /admin/announce`) goes
+//!   out on a pub/sub channel and is shown on all of them.
+//!
+//! Losing Redis costs only the sharing: each server keeps its own board
+//! and announcements, and reconnects every `RETRY_INTERVAL`.
+
+use anyhow::Context;
+use futures_util::StreamExt;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use shared::{ScoreEntry, ServerMessage};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+use crate::events::GameEvent;
+use crate::game_mode::GameMode;
+use crate::game_state::{GameState, SharedGameState};
+
+/// How often the scoreboard is pushed to and merged back from Redis
+const SYNC_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long to wait before reconnecting after Redis went away
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What goes over the announcement channel. Redis delivers a server's own
+/// messages back to it; `origin` lets it skip those.
+#[derive(Debug, Serialize, Deserialize)]
+struct Announcement {
+    origin: Uuid,
+    text: String,
+}
+
+/// Share the scoreboard and announcements through `url` for as long as the
+/// server runs
+pub async fn run(state: SharedGameState, url: String) {
+    let origin = Uuid::new_v4();
+    loop {
+        if let Err(e) = connect(&state, &url, origin).await {
+            tracing::error!("Lost the cluster connection: {:#}", e);
+        }
+        tokio::time::sleep(RETRY_INTERVAL).await;
+    }
+}
+
+async fn connect(state: &SharedGameState, url: &str, origin: Uuid) -> anyhow::Result<()> {
+    let client = redis::Client::open(url).context("parsing redis_url")?;
+    let conn = client
+        .get_multiplexed_tokio_connection()
+        .await
+        .context("connecting to Redis")?;
+    let (cluster, events) = {
+        let game = state.read().await;
+        (
+            game.config().cluster_name.clone(),
+            game.subscribe_game_events(),
+        )
+    };
+    let channel = format!("{}:announcements", cluster);
+    tracing::info!("Joined cluster {}", cluster);
+
+    tokio::select! {
+        result = sync_scores(state, conn.clone(), &cluster) => result,
+        result = send_announcements(events, conn, &channel, origin) => result,
+        result = receive_announcements(state, &client, &channel, origin) => result,
+    }
+}
+
+/// Push this server's standings and merge in everyone's best, forever
+async fn sync_scores(
+    state: &SharedGameState,
+    mut conn: redis::aio::MultiplexedConnection,
+    cluster: &str,
+) -> anyhow::Result<()> {
+    let mut timer = tokio::time::interval(SYNC_INTERVAL);
+    loop {
+        timer.tick().await;
+        let (key, local, mode, max_entries) = {
+            let game = state.read().await;
+            let mode = game.config().mode;
+            let max_entries = game.config().max_scoreboard_entries;
+            (
+                board_key(&game, cluster),
+                game.standings().to_vec(),
+                mode,
+                max_entries,
+            )
+        };
+
+        // Runs are the members, so pushing the same one again changes nothing
+        let mut members = Vec::with_capacity(local.len());
+        for entry in &local {
+            members.push((mode.score(entry), serde_json::to_string(entry)?));
+        }
+        if !members.is_empty() {
+            let _: () = conn.zadd_multiple(&key, &members).await?;
+        }
+        let keep = max_entries as isize;
+        let _: () = conn.zremrangebyrank(&key, 0, -keep - 1).await?;
+
+        let best: Vec<String> = conn.zrevrange(&key, 0, keep - 1).await?;
+        let remote: Vec<ScoreEntry> = best
+            .iter()
+            .filter_map(|member| serde_json::from_str(member).ok())
+            .collect();
+        state.write().await.add_scores(remote);
+    }
+}
+
+/// The sorted set this server's board is shared through
+fn board_key(game: &GameState, cluster: &str) -> String {
+    match game.config().mode {
+        GameMode::Daily => format!("{}:scores:Daily:{}", cluster, game.daily().date),
+        mode => format!("{}:scores:{:?}", cluster, mode),
+    }
+}
+
+/// Pass this server's announcements on to the others
+async fn send_announcements(
+    mut events: broadcast::Receiver<GameEvent>,
+    mut conn: redis::aio::MultiplexedConnection,
+    channel: &str,
+    origin: Uuid,
+) -> anyhow::Result<()> {
+    loop {
+        let text = match events.recv().await {
+            Ok(GameEvent::Announcement { text }) => text,
+            Ok(_) => continue,
+            Err(RecvError::Lagged(missed)) => {
+                tracing::warn!("Cluster relay fell behind, {} events skipped", missed);
+                continue;
+            }
+            Err(RecvError::Closed) => return Ok(()),
+        };
+        let payload = serde_json::to_string(&Announcement { origin, text })?;
+        let _: () = conn.publish(channel, payload).await?;
+    }
+}
+
+/// Show other servers' announcements here. They're broadcast directly
+/// rather than published as events, which would send them back out.
+async fn receive_announcements(
+    state: &SharedGameState,
+    client: &redis::Client,
+    channel: &str,
+    origin: Uuid,
+) -> anyhow::Result<()> {
+    let mut pubsub = client.get_async_pubsub().await?;
+    pubsub.subscribe(channel).await?;
+    let mut messages = pubsub.on_message();
+    while let Some(message) = messages.next().await {
+        let payload: String = message.get_payload()?;
+        let announcement: Announcement = match serde_json::from_str(&payload) {
+            Ok(announcement) => announcement,
+            Err(e) => {
+                tracing::debug!("Ignoring a malformed announcement: {}", e);
+                continue;
+            }
+        };
+        if announcement.origin != origin {
+            let text = announcement.text;
+            state
+                .write()
+                .await
+                .broadcast(ServerMessage::Announcement { text });
+        }
+    }
+    anyhow::bail!("the announcement channel closed")
+}
@@ -0,0 +1,80 @@
+use shared::{Position, Vec2};
+use std::collections::HashMap;
+
+/// A circle taking part in separation. Pinned bodies push others out but
+/// never move themselves (players, whose movement the client drives).
+#[derive(Debug, Clone, Copy)]
+pub struct Body {
+    pub position: Position,
+    pub radius: f32,
+    pub pinned: bool,
+}
+
+/// Push overlapping bodies apart so crowds spread out instead of collapsing
+/// onto one point. `strength` is the fraction of each overlap resolved per
+/// call; repeated every tick it settles smoothly.
+pub fn separate(bodies: &mut [Body], strength: f32) {
+    let Some(max_radius) = bodies.iter().map(|b| b.radius).reduce(f32::max) else {
+        return;
+    };
+
+    // Bucket into cells two radii wide, so only neighbouring cells can overlap
+    let cell_size = (max_radius * 2.0).max(1.0);
+    let cell_of = |p: &Position| {
+        (
+            (p.x / cell_size).floor() as i32,
+            (p.y / cell_size).floor() as i32,
+        )
+    };
+    let cells: Vec<(i32, i32)> = bodies.iter().map(|b| cell_of(&b.position)).collect();
+    let mut grid: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+    for (i, cell) in cells.iter().enumerate() {
+        grid.entry(*cell).or_default().push(i);
+    }
+
+    for (i, &(cx, cy)) in cells.iter().enumerate() {
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                // Saturating: a far-off (or NaN) body lands in the edge cell
+                let neighbour = (cx.saturating_add(dx), cy.saturating_add(dy));
+                let Some(cell) = grid.get(&neighbour) else {
+                    continue;
+                };
+                for &j in cell.iter().filter(|&&j| j > i) {
+                    push_apart(bodies, i, j, strength);
+                }
+            }
+        }
+    }
+}
+
+fn push_apart(bodies: &mut [Body], i: usize, j: usize, strength: f32) {
+    let (a, b) = (bodies[i], bodies[j]);
+    if a.pinned && b.pinned {
+        return;
+    }
+
+    let offset = b.position - a.position;
+    let distance = offset.length();
+    let overlap = a.radius + b.radius - distance;
+    if overlap <= 0.0 {
+        return;
+    }
+
+    // Exactly stacked: split along an arbitrary but stable axis
+    let normal = if distance > 0.001 {
+        offset / distance
+    } else {
+        Vec2::from_angle((i * 7 + j * 13) as f32)
+    };
+
+    // Movable bodies share the push; against a pinned one they take all of it
+    let push = overlap * strength;
+    let (share_a, share_b) = match (a.pinned, b.pinned) {
+        (true, _) => (0.0, push),
+        (_, true) => (push, 0.0),
+        _ => (push / 2.0, push / 2.0),
+    };
+    bodies[i].position -= normal * share_a;
+    bodies[j].position += normal * share_b;
+}
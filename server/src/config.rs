@@ -1,8 +1,22 @@
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
+use shared::{EnemyRoster, ShopItem, ShopListing, UpgradeCatalog, UpgradeTable};
+use std::path::Path;
 
+use crate::game_mode::GameMode;
+use crate::integrity::IntegrityLimits;
+use crate::kill_credit::KillCredit;
+
+/// Most rings a map can have; each one gets its own landmarks
+pub const MAX_RINGS: u32 = 100;
+
+/// Server settings. A config file (JSON) only needs the fields it changes;
+/// everything else keeps its default.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GameConfig {
     pub tick_rate: f64,        // ticks per second
+    pub ai_decision_rate: f32, // enemy AI decisions per second; movement runs every tick
     pub safe_zone_radius: f32, // radius of safe zone
     pub ring_radius: f32,      // radius of each ring
     pub max_rings: u32,        // number of rings
@@ -10,12 +24,144 @@ pub struct GameConfig {
     pub map_size: f32,         // total map radius
     pub score_min_ring: u32,   // minimum ring to qualify for scoreboard
     pub max_scoreboard_entries: usize,
+    pub safe_zone_regen: f32,      // HP per second while in the safe zone
+    pub safe_zone_max_stay: f32,   // seconds before a camping player is pushed out
+    pub safe_zone_push_speed: f32, // units per second when pushing a camper out
+    pub mode: GameMode,
+    pub zone_shrink_interval: f32, // battle royale: seconds between ring closures
+    pub zone_damage: f32,          // battle royale: HP per second inside the closed area
+    pub timed_duration: f32,       // timed: match length in seconds
+    pub wave_interval: f32,        // wave defense: seconds between waves
+    pub wave_base_size: u32,       // wave defense: enemies in the first wave
+    pub wave_spawn_radius: f32,    // wave defense: distance from center waves appear at
+    pub wave_aggro_range: f32,     // wave defense: enemies divert to players this close
+    pub core_health: f32,          // wave defense: damage the center absorbs before falling
+    pub pvp_enabled: bool,         // player projectiles also hit other players
+    pub pvp_damage_scale: f32,     // multiplier on projectile damage against players
+    pub contact_damage: bool,      // enemies hurt players they touch every tick instead of swinging
+    pub party_max_size: usize,     // players per party
+    pub party_xp_share: f32,       // fraction of kill XP given to nearby party members
+    pub party_share_range: f32,    // how close to a kill a member must be to get shared XP
+    pub party_full_range: f32,     // closer than this a member gets a full share, not a tapered one
+    pub party_active_window: f32,  // secs since last hurting an enemy to get a share; 0 disables
+    pub kill_credit: KillCredit,   // split kill XP by damage dealt, or give it all to the last hit
+    pub pickup_drop_chance: f32,   // chance an enemy drops something on death
+    pub pickup_lifetime: f32,      // seconds a drop stays on the ground
+    pub pickup_radius: f32,        // base collection distance
+    pub aura_radius: f32,          // base reach of the DamageAura weapon
+    pub aura_interval: f32,        // seconds between aura damage ticks
+    pub homing_range: f32,         // how far a homing shot looks for an enemy to turn toward
+    pub explosion_radius: f32,     // reach of an ExplosiveShots blast
+    pub potion_heal: u32,          // HP restored by a health potion
+    pub power_up_duration: f32,    // seconds double damage / speed boost last
+    pub seed: Option<u64>,         // fixed world layout and rolls; random per match when unset
+    pub interact_range: f32,       // how close a player must be to use a landmark
+    pub shrine_cooldown: f32,      // seconds before a shrine can be used again
+    pub shrine_buff_duration: f32, // seconds a shrine buff lasts
+    pub shrine_aura_radius: f32,   // reach of a shrine's healing aura around the blessed player
+    pub shrine_aura_heal: f32,     // HP per second the aura restores to players in reach
+    pub shrine_shield: f32,        // shield points a shrine's warding blessing grants
+    pub shield_recharge: f32,      // seconds between Overshield recharges
+    pub shield_decay: f32,         // shield points lost per second
+    pub second_wind_invulnerability: f32, // seconds a second wind protects the player
+    pub burn_damage: f32,          // HP per second a burning player loses
+    pub burn_duration: f32,        // seconds a burn lasts after the last fiery hit
+    pub chest_rare_chance: f32,    // chance a chest holds a rare item instead of a choice
+    pub threat_per_damage: f32,    // threat gained per point of damage dealt
+    pub threat_range: f32,         // players inside this range build proximity threat
+    pub threat_proximity_rate: f32, // threat per second when standing on top of an enemy
+    pub threat_decay_per_unit: f32, // threat lost per second per unit of distance
+    pub threat_switch_margin: f32, // a new target needs this multiple of the current's threat
+    pub base_crit_chance: f32,     // crit chance every player starts with
+    pub base_crit_multiplier: f32, // damage multiplier on a crit
+    pub damage_variance: f32,      // hits roll within +/- this fraction of base damage
+    pub dash_distance: f32,
+    pub dash_duration: f32, // seconds the burst of movement takes
+    pub dash_cooldown: f32,
+    pub dash_iframes: f32, // seconds of invulnerability from the start of a dash
+    pub base_rerolls: u32, // upgrade rerolls per run; each Luck level adds one
+    pub base_banishes: u32, // upgrade banishes per run; each Luck level adds one
+    pub level_up_timeout: f32, // seconds an upgrade offer waits for an answer
+    pub level_up_resend_interval: f32, // seconds between reminders of an open offer
+    pub level_up_auto_pick: bool, // on timeout pick for the player instead of dropping it
+    pub difficulty_tier_interval: f32, // seconds of match time per difficulty tier
+    pub difficulty_stat_growth: f32, // enemy health/damage added per tier (0.1 = +10%)
+    pub difficulty_spawn_growth: f32, // spawn rate added per tier
+    pub max_difficulty_tier: u32,
+    pub spawn_bubble_radius: f32, // players this close to a spawn cap the enemy's level
+    pub levels_per_enemy_level: u32, // player levels per ring of enemy level allowed
+    pub leash_range: f32,         // distance from its spawn point before an enemy gives up
+    pub min_spawn_distance: f32,  // enemies never appear closer than this to a player
+    pub spawn_attempts: u32,      // tries to find a clear spawn point before giving up
+    pub offscreen_spawns: bool,   // also keep spawns outside each client's reported view
+    pub max_view_radius: f32,     // cap on what a client may report
+    pub min_view_radius: f32,     // floor on what a client may report
+    pub aoi_margin: f32,          // snapshots reach this far past a client's view
+    pub separation_strength: f32, // share of entity overlap resolved per tick; 0 disables
+    pub boundary_warning_distance: f32, // warn players this close to the map edge
+    pub max_catch_up_steps: u32,  // simulation steps allowed per loop iteration when behind
+    pub slow_tick_threshold_ms: f64, // log a stage breakdown of steps slower than this; 0 disables
+    pub snapshot_path: String,    // world snapshot written on a timer and at shutdown
+    pub snapshot_interval: f32,   // seconds between autosaves; 0 disables the timer
+    pub snapshot_rate: f32,       // state snapshots per second to each client
+    pub min_snapshot_rate: f32,   // floor the rate halves down to on a congested connection
+    pub bandwidth_budget: usize,  // bytes per second per connection before snapshots slow down
+    pub congested_entity_limit: usize, // per kind, nearest first, while over budget
+    pub compression_threshold: usize, // deflate messages at least this many bytes, if negotiated
+    pub max_message_size: usize,  // bytes a client message may have; see validation.rs
+    pub udp_port: Option<u16>,    // offer snapshots and inputs over UDP on this port
+    pub udp_timeout: f32,         // seconds of silence before a UDP client goes back to WebSocket
+    pub tls_cert: Option<String>, // PEM certificate chain; with tls_key, serve wss:// directly
+    pub tls_key: Option<String>,  // PEM private key for tls_cert
+    pub cors_origins: Vec<String>, // origins allowed cross-origin access; "*" for any, empty for none
+    pub trust_forwarded_for: bool, // take client IPs from X-Forwarded-For; only behind a proxy
+    pub base_path: String,         // prefix for every route, e.g. "/game"; empty serves at the root
+    pub client_dir: Option<String>, // built web client (index.html + pkg/) to serve at /play
+    pub afk_warning_after: f32,    // seconds without input before a player is warned; 0 disables
+    pub afk_timeout: f32,          // seconds without input before a player is removed; 0 disables
+    pub max_queued_inputs: usize,  // Move/Dash inputs held per player for the next ticks
+    pub max_players: usize,        // players in the game at once; 0 for no cap
+    pub max_queue_length: usize,   // joiners waiting for a slot beyond max_players; 0 rejects them
+    pub max_connections_per_ip: usize, // concurrent connections one address may hold; 0 for no limit
+    pub ban_list_path: String,         // IP and player bans, kept across restarts
+    pub server_password: Option<String>, // players must give it to join; unset lets anyone in
+    pub allowed_accounts: Vec<String>, // "name:key" of each account that may join; empty for any
+    pub admin_token: Option<String>,   // bearer token for /admin routes; unset disables them
+    pub server_name: String,           // shown by GET /info
+    pub motd: String,                  // message of the day, shown by GET /info
+    pub match_history_path: String,    // finished matches, appended one JSON record per line
+    pub max_match_history: usize,      // newest matches kept in memory for GET /matches
+    pub max_run_summaries: usize,      // newest death summaries kept for GET /runs/{id}
+    pub shop_inventory: Vec<ShopListing>, // what the safe zone merchant sells, in menu order
+    pub shop_heal: u32,                // HP restored by a Heal bought from the merchant
+    pub equipment_drop_chance: f32,    // chance an enemy drops gear on death, before luck
+    pub inventory_size: usize,         // gear a player can carry besides what they wear
+    pub teleport_channel_time: f32,    // seconds standing still before a teleport goes through
+    pub teleport_combat_lockout: f32,  // seconds after taking a hit before a teleport can start
+    pub minimap_interval: f32,         // seconds between MinimapUpdate broadcasts
+    pub upgrade_table: UpgradeTable,   // upgrade and rarity weights for level-up offers
+    pub upgrade_catalog: UpgradeCatalog, // what each upgrade level is worth
+    pub enemies: EnemyRoster,          // enemy stats, XP and which rings they spawn in
+    pub scripts: Vec<String>,          // WebAssembly rule scripts; needs the scripting feature
+    pub script_fuel: u64,              // instructions, roughly, a script call may run
+    pub script_memory_limit: usize,    // bytes of linear memory a script may use
+    pub boss_scripts: Option<String>,  // directory of Lua boss fights; needs the lua feature
+    pub integrity_limits: IntegrityLimits, // runs past these are held for admin review
+    pub redis_url: Option<String>, // shared leaderboard and announcements; needs the redis feature
+    pub cluster_name: String,      // servers with the same name and redis_url share them
+    pub max_rooms: usize,          // rooms POST /rooms may open next to the main game
+    pub room_idle_timeout: f32,    // seconds a room may sit empty before it's closed
+    pub invite_code_lifetime: f32, // seconds a private room's invite code lets players in
+    pub signal_range: f32,         // emotes and pings reach players this close to the sender
+    pub signal_burst: u32,         // emotes and pings a player may send at once
+    pub signal_rate: f32,          // per second, refilling the burst
 }
 
 impl Default for GameConfig {
     fn default() -> Self {
         Self {
             tick_rate: 20.0,         // 20 ticks per second (50ms per tick)
+            ai_decision_rate: 5.0,   // targets and behaviors 5 times a second
             safe_zone_radius: 100.0, // 100 units
             ring_radius: 200.0,      // 200 units per ring
             max_rings: 10,
@@ -23,6 +169,265 @@ impl Default for GameConfig {
             map_size: 2500.0,      // 2500 units total (beyond ring 10)
             score_min_ring: 10,
             max_scoreboard_entries: 100,
+            safe_zone_regen: 5.0,
+            safe_zone_max_stay: 30.0,
+            safe_zone_push_speed: 80.0,
+            mode: GameMode::Endless,
+            zone_shrink_interval: 60.0,
+            zone_damage: 10.0,
+            timed_duration: 900.0, // 15 minutes
+            wave_interval: 30.0,
+            wave_base_size: 5,
+            wave_spawn_radius: 500.0,
+            wave_aggro_range: 300.0,
+            core_health: 100.0,
+            pvp_enabled: false,
+            pvp_damage_scale: 0.5, // half damage so fights last longer than one volley
+            contact_damage: false,
+            party_max_size: 4,
+            party_xp_share: 0.5,
+            party_share_range: 600.0,
+            party_full_range: 300.0,
+            party_active_window: 15.0,
+            kill_credit: KillCredit::Proportional,
+            pickup_drop_chance: 0.15,
+            pickup_lifetime: 30.0,
+            pickup_radius: 25.0,
+            aura_radius: 60.0,
+            aura_interval: 0.5,
+            homing_range: 200.0,
+            explosion_radius: 50.0,
+            potion_heal: 25,
+            power_up_duration: 10.0,
+            seed: None,
+            interact_range: 40.0,
+            shrine_cooldown: 60.0,
+            shrine_buff_duration: 20.0,
+            shrine_aura_radius: 150.0,
+            shrine_aura_heal: 3.0,
+            shrine_shield: 50.0,
+            shield_recharge: 10.0,
+            shield_decay: 2.0,
+            second_wind_invulnerability: 2.0,
+            burn_damage: 4.0,
+            burn_duration: 3.0,
+            chest_rare_chance: 0.25,
+            threat_per_damage: 1.0,
+            threat_range: 400.0,
+            threat_proximity_rate: 5.0,
+            threat_decay_per_unit: 0.01,
+            threat_switch_margin: 1.1, // 110%: the classic MMO taunt threshold
+            base_crit_chance: 0.05,
+            base_crit_multiplier: 1.5,
+            damage_variance: 0.1,
+            dash_distance: 150.0,
+            dash_duration: 0.15,
+            dash_cooldown: 3.0,
+            dash_iframes: 0.3,
+            base_rerolls: 2,
+            base_banishes: 1,
+            level_up_timeout: 30.0,
+            level_up_resend_interval: 5.0,
+            level_up_auto_pick: true,
+            difficulty_tier_interval: 60.0,
+            difficulty_stat_growth: 0.1,
+            difficulty_spawn_growth: 0.15,
+            max_difficulty_tier: 20,
+            spawn_bubble_radius: 600.0,
+            levels_per_enemy_level: 2,
+            leash_range: 800.0,
+            min_spawn_distance: 200.0,
+            spawn_attempts: 8,
+            offscreen_spawns: false,
+            max_view_radius: 1200.0,
+            min_view_radius: 200.0,
+            aoi_margin: 100.0,
+            separation_strength: 0.5,
+            boundary_warning_distance: 200.0,
+            max_catch_up_steps: 5,
+            slow_tick_threshold_ms: 0.0,
+            snapshot_path: "world_snapshot.json".to_string(),
+            snapshot_interval: 300.0,
+            snapshot_rate: 20.0,
+            min_snapshot_rate: 5.0,
+            bandwidth_budget: 256 * 1024,
+            congested_entity_limit: 150,
+            compression_threshold: 1024,
+            max_message_size: 4096,
+            udp_port: None,
+            udp_timeout: 5.0,
+            tls_cert: None,
+            tls_key: None,
+            cors_origins: Vec::new(),
+            trust_forwarded_for: false,
+            base_path: String::new(),
+            client_dir: None,
+            afk_warning_after: 240.0,
+            afk_timeout: 300.0,
+            max_queued_inputs: 8,
+            max_players: 64,
+            max_queue_length: 32,
+            max_connections_per_ip: 8,
+            server_password: None,
+            allowed_accounts: Vec::new(),
+            ban_list_path: "bans.json".to_string(),
+            admin_token: None,
+            server_name: "Game Server".to_string(),
+            motd: String::new(),
+            match_history_path: "matches.jsonl".to_string(),
+            max_match_history: 100,
+            max_run_summaries: 1000,
+            shop_inventory: vec![
+                ShopListing {
+                    item: ShopItem::Heal,
+                    price: 20,
+                },
+                ShopListing {
+                    item: ShopItem::Reroll,
+                    price: 30,
+                },
+                ShopListing {
+                    item: ShopItem::DamageBoost,
+                    price: 40,
+                },
+                ShopListing {
+                    item: ShopItem::SpeedBoost,
+                    price: 25,
+                },
+            ],
+            shop_heal: 50,
+            equipment_drop_chance: 0.02,
+            inventory_size: 6,
+            teleport_channel_time: 3.0,
+            teleport_combat_lockout: 5.0,
+            minimap_interval: 1.0,
+            upgrade_table: UpgradeTable::default(),
+            upgrade_catalog: UpgradeCatalog::default(),
+            enemies: EnemyRoster::default(),
+            scripts: Vec::new(),
+            script_fuel: 1_000_000,
+            script_memory_limit: 16 << 20,
+            boss_scripts: None,
+            integrity_limits: IntegrityLimits::default(),
+            redis_url: None,
+            cluster_name: "game".to_string(),
+            max_rooms: 8,
+            room_idle_timeout: 300.0,
+            invite_code_lifetime: 3600.0,
+            signal_range: 1200.0, // about a screen
+            signal_burst: 3,
+            signal_rate: 0.5,
         }
     }
 }
+
+impl GameConfig {
+    /// Read a JSON config file on top of the defaults
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        let config =
+            serde_json::from_slice(&json).with_context(|| format!("parsing {}", path.display()))?;
+        Ok(config)
+    }
+
+    /// Replace the enemy roster with one read from a TOML data file, laid
+    /// out like `data/enemies.toml`
+    pub fn load_enemies(&mut self, path: &Path) -> anyhow::Result<()> {
+        let text =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        self.enemies =
+            toml::from_str(&text).with_context(|| format!("parsing {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Replace the upgrade magnitudes with ones read from a TOML data file,
+    /// laid out like `shared/data/upgrades.toml`
+    pub fn load_upgrades(&mut self, path: &Path) -> anyhow::Result<()> {
+        let text =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        self.upgrade_catalog =
+            toml::from_str(&text).with_context(|| format!("parsing {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Every setting the server can't run with, one line each
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_string());
+            }
+        };
+        check(self.tick_rate > 0.0, "tick_rate must be positive");
+        check(
+            self.ai_decision_rate > 0.0,
+            "ai_decision_rate must be positive",
+        );
+        check(self.snapshot_rate > 0.0, "snapshot_rate must be positive");
+        check(
+            self.min_snapshot_rate <= self.snapshot_rate,
+            "min_snapshot_rate can't exceed snapshot_rate",
+        );
+        check(
+            self.max_catch_up_steps > 0,
+            "max_catch_up_steps must be at least 1",
+        );
+        check(
+            self.slow_tick_threshold_ms >= 0.0,
+            "slow_tick_threshold_ms can't be negative",
+        );
+        check(self.ring_radius > 0.0, "ring_radius must be positive");
+        check(
+            (1..=MAX_RINGS).contains(&self.max_rings),
+            &format!("max_rings must be between 1 and {}", MAX_RINGS),
+        );
+        check(
+            self.enemy_spawn_rate.is_finite() && self.enemy_spawn_rate > 0.0,
+            "enemy_spawn_rate must be positive",
+        );
+        check(
+            self.timed_duration.is_finite() && self.timed_duration > 0.0,
+            "timed_duration must be a positive number of seconds",
+        );
+        check(
+            self.safe_zone_radius < self.map_size,
+            "safe_zone_radius must be inside map_size",
+        );
+        check(
+            self.min_view_radius <= self.max_view_radius,
+            "min_view_radius can't exceed max_view_radius",
+        );
+        check(
+            self.party_full_range <= self.party_share_range,
+            "party_full_range can't exceed party_share_range",
+        );
+        check(self.signal_rate >= 0.0, "signal_rate can't be negative");
+        check(
+            self.udp_timeout.is_finite() && self.udp_timeout > 0.0,
+            "udp_timeout must be a positive number of seconds",
+        );
+        check(
+            self.room_idle_timeout.is_finite() && self.room_idle_timeout > 0.0,
+            "room_idle_timeout must be a positive number of seconds",
+        );
+        check(
+            self.invite_code_lifetime.is_finite() && self.invite_code_lifetime > 0.0,
+            "invite_code_lifetime must be a positive number of seconds",
+        );
+        check(
+            self.tls_cert.is_some() == self.tls_key.is_some(),
+            "tls_cert and tls_key must be set together",
+        );
+        check(
+            self.upgrade_table.upgrades.iter().any(|u| u.weight > 0.0),
+            "upgrade_table needs an upgrade with a positive weight",
+        );
+        check(
+            self.upgrade_table.rarities.iter().all(|t| t.weight >= 0.0),
+            "upgrade_table rarity weights can't be negative",
+        );
+        problems.extend(self.upgrade_catalog.problems());
+        problems.extend(self.enemies.problems());
+        problems
+    }
+}
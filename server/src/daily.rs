@@ -0,0 +1,78 @@
+//! Daily runs: `GameMode::Daily` plays on one seed per UTC day, the same
+//! on every server, and keeps a leaderboard of its own for that day.
+//! `GET /daily` serves today's seed and standings.
+
+use axum::{extract::State, routing::get, Json, Router};
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use shared::ScoreEntry;
+
+use crate::game_mode::GameMode;
+use crate::game_state::SharedGameState;
+
+/// Mix `parts` into `seed` (splitmix64 per step), for deriving independent
+/// random streams from one seed
+pub fn derive_seed(seed: u64, parts: &[u64]) -> u64 {
+    parts
+        .iter()
+        .fold(splitmix64(seed), |acc, part| splitmix64(acc ^ part))
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// The seed every daily run on `date` plays with
+pub fn seed_for(date: NaiveDate) -> u64 {
+    derive_seed(date.num_days_from_ce() as u64, &[])
+}
+
+pub fn today() -> NaiveDate {
+    chrono::Utc::now().date_naive()
+}
+
+/// One day's seed and leaderboard, best run first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyBoard {
+    pub date: NaiveDate,
+    pub seed: u64,
+    pub standings: Vec<ScoreEntry>,
+}
+
+impl DailyBoard {
+    pub fn new(date: NaiveDate) -> Self {
+        Self {
+            date,
+            seed: seed_for(date),
+            standings: Vec::new(),
+        }
+    }
+
+    /// Rank a finished run, keeping the best `max_entries`
+    pub fn add(&mut self, entry: ScoreEntry, max_entries: usize) {
+        self.standings.push(entry);
+        self.standings
+            .sort_by_key(|s| std::cmp::Reverse(GameMode::Daily.score(s)));
+        self.standings.truncate(max_entries);
+    }
+}
+
+/// `GET /daily`
+pub fn router() -> Router<SharedGameState> {
+    Router::new().route("/daily", get(todays_board))
+}
+
+async fn todays_board(State(state): State<SharedGameState>) -> Json<DailyBoard> {
+    let game = state.read().await;
+    // Until the match rolls over (or outside daily mode) the board may be
+    // an earlier day's, which has no standings for today yet
+    let today = today();
+    if game.daily().date == today {
+        Json(game.daily().clone())
+    } else {
+        Json(DailyBoard::new(today))
+    }
+}
@@ -0,0 +1,137 @@
+//! Runtime diagnostics: how long tasks wait for the game state lock, and
+//! the `/debug/tasks` summary served next to the admin endpoints.
+//!
+//! For a live view of every task, build with the `console` feature and
+//! `RUSTFLAGS="--cfg tokio_unstable"` and attach `tokio-console`.
+
+use axum::{extract::State, Json};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{RwLockReadGuard, RwLockWriteGuard};
+
+use crate::game_state::{GameState, SharedGameState};
+
+/// Who was waiting for the game state lock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockSite {
+    GameLoop, // the simulation step, writing
+    Snapshot, // a connection building its snapshot, reading
+    Message,  // a connection applying a client message, writing
+}
+
+/// Waits for the game state lock, per `LockSite`. Atomic so that readers
+/// can record theirs too.
+#[derive(Debug, Default)]
+pub struct LockWaits {
+    game_loop: WaitStats,
+    snapshot: WaitStats,
+    message: WaitStats,
+}
+
+impl LockWaits {
+    pub fn record(&self, site: LockSite, wait: Duration) {
+        let stats = match site {
+            LockSite::GameLoop => &self.game_loop,
+            LockSite::Snapshot => &self.snapshot,
+            LockSite::Message => &self.message,
+        };
+        let micros = wait.as_micros() as u64;
+        stats.count.fetch_add(1, Ordering::Relaxed);
+        stats.total_us.fetch_add(micros, Ordering::Relaxed);
+        stats.max_us.fetch_max(micros, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Default)]
+struct WaitStats {
+    count: AtomicU64,
+    total_us: AtomicU64,
+    max_us: AtomicU64,
+}
+
+impl WaitStats {
+    fn summary(&self) -> WaitSummary {
+        let count = self.count.load(Ordering::Relaxed);
+        let total_ms = self.total_us.load(Ordering::Relaxed) as f64 / 1000.0;
+        WaitSummary {
+            count,
+            mean_ms: total_ms / count.max(1) as f64,
+            max_ms: self.max_us.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+}
+
+/// `state.write()`, recording the wait under `site`
+pub async fn write(state: &SharedGameState, site: LockSite) -> RwLockWriteGuard<'_, GameState> {
+    let start = Instant::now();
+    let game = state.write().await;
+    game.lock_waits().record(site, start.elapsed());
+    game
+}
+
+/// `state.read()`, recording the wait under `site`
+pub async fn read(state: &SharedGameState, site: LockSite) -> RwLockReadGuard<'_, GameState> {
+    let start = Instant::now();
+    let game = state.read().await;
+    game.lock_waits().record(site, start.elapsed());
+    game
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaskReport {
+    pub runtime: RuntimeSummary,
+    pub sessions: usize,    // connection tasks subscribed to game events
+    pub connections: usize, // counted against the per-IP limit
+    pub players: usize,
+    pub queued: usize,
+    pub lock_waits: LockWaitSummary,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RuntimeSummary {
+    pub workers: usize,
+    pub alive_tasks: usize,
+    pub global_queue_depth: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LockWaitSummary {
+    pub game_loop: WaitSummary,
+    pub snapshot: WaitSummary,
+    pub message: WaitSummary,
+}
+
+/// Since startup
+#[derive(Debug, Serialize)]
+pub struct WaitSummary {
+    pub count: u64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+}
+
+/// `GET /debug/tasks`
+pub async fn tasks(State(state): State<SharedGameState>) -> Json<TaskReport> {
+    let metrics = tokio::runtime::Handle::current().metrics();
+    let runtime = RuntimeSummary {
+        workers: metrics.num_workers(),
+        alive_tasks: metrics.num_alive_tasks(),
+        global_queue_depth: metrics.global_queue_depth(),
+    };
+
+    let game = state.read().await;
+    let waits = game.lock_waits();
+    let counts = game.entity_counts();
+    Json(TaskReport {
+        runtime,
+        sessions: game.session_count(),
+        connections: game.connections().total(),
+        players: counts.players,
+        queued: counts.queued,
+        lock_waits: LockWaitSummary {
+            game_loop: waits.game_loop.summary(),
+            snapshot: waits.snapshot.summary(),
+            message: waits.message.summary(),
+        },
+    })
+}
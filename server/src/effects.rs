@@ -0,0 +1,117 @@
+//! Timed effects: things that happen to a player every so often for a
+//! while, like regeneration, burns, shields, auras and shrine blessings.
+//! They're all scheduled here instead of each keeping its own timer, and
+//! `GameState::update_effects` applies the ones that are due.
+
+use std::mem::discriminant;
+use uuid::Uuid;
+
+/// What an effect does each time it applies
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EffectKind {
+    /// Restore HP to the player
+    Heal(f32),
+    /// Restore HP while the player is in the safe zone and hasn't
+    /// overstayed it, kept apart from `Heal` so the two don't replace each
+    /// other
+    ZoneRegen(f32),
+    /// Damage the player, credited to the effect's source
+    Burn(f32),
+    /// Raise the player's shield to this many points
+    Shield(f32),
+    /// The same, as the Overshield upgrade's recharge, so a one-off shield
+    /// doesn't replace it
+    ShieldRecharge(f32),
+    /// Heal every living player within `radius` of this one, itself included
+    HealingAura { radius: f32, heal: f32 },
+    /// Damage every enemy within `radius` of the player, on their behalf
+    DamageAura { radius: f32, damage: f32 },
+    /// Double damage until the effect ends
+    DamageBoost,
+    /// Faster movement until the effect ends
+    SpeedBoost,
+}
+
+/// An effect on one player
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedEffect {
+    pub player_id: Uuid,
+    pub kind: EffectKind,
+    pub interval: f32,        // seconds between applications
+    pub next_at: f64,         // game time it next applies
+    pub ends_at: f64,         // game time it wears off; infinite if it lasts
+    pub source: Option<Uuid>, // who caused it, credited with its damage
+}
+
+impl TimedEffect {
+    /// Applies every `interval` seconds, the first time one interval from
+    /// `now`, until `duration` is up
+    pub fn periodic(
+        player_id: Uuid,
+        kind: EffectKind,
+        interval: f32,
+        duration: f32,
+        now: f64,
+    ) -> Self {
+        Self {
+            player_id,
+            kind,
+            interval,
+            next_at: now + interval as f64,
+            ends_at: now + duration as f64,
+            source: None,
+        }
+    }
+
+    /// Applies once, right away; what it grants lasts `duration`
+    pub fn buff(player_id: Uuid, kind: EffectKind, duration: f32, now: f64) -> Self {
+        Self {
+            interval: f32::INFINITY,
+            next_at: now,
+            ..Self::periodic(player_id, kind, duration, duration, now)
+        }
+    }
+
+    pub fn from(self, source: Uuid) -> Self {
+        Self {
+            source: Some(source),
+            ..self
+        }
+    }
+
+    fn same_slot(&self, other: &TimedEffect) -> bool {
+        self.player_id == other.player_id && discriminant(&self.kind) == discriminant(&other.kind)
+    }
+}
+
+/// Every effect in play. A player has at most one of each kind: adding
+/// another replaces it, so reapplying refreshes instead of stacking.
+#[derive(Debug, Default)]
+pub struct Effects(Vec<TimedEffect>);
+
+impl Effects {
+    pub fn add(&mut self, effect: TimedEffect) {
+        self.0.retain(|e| !e.same_slot(&effect));
+        self.0.push(effect);
+    }
+
+    pub fn on(&self, player_id: Uuid) -> impl Iterator<Item = &TimedEffect> {
+        self.0.iter().filter(move |e| e.player_id == player_id)
+    }
+
+    pub fn retain(&mut self, keep: impl FnMut(&TimedEffect) -> bool) {
+        self.0.retain(keep);
+    }
+
+    /// The effects due by `now`, each scheduled for its next application;
+    /// those with none left before they end are dropped
+    pub(crate) fn take_due(&mut self, now: f64) -> Vec<TimedEffect> {
+        let mut due = Vec::new();
+        for effect in self.0.iter_mut().filter(|e| e.next_at <= now) {
+            due.push(effect.clone());
+            effect.next_at += effect.interval as f64;
+        }
+        self.0.retain(|e| e.next_at <= e.ends_at);
+        due
+    }
+}
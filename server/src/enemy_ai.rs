@@ -0,0 +1,174 @@
+use shared::{Enemy, EnemyBehavior, EnemyType, Player, Position, Projectile, Steering, Vec2};
+
+// Wolves: orbit alone, rush once the pack gathers
+const WOLF_CIRCLE_RADIUS: f32 = 120.0;
+const WOLF_CIRCLE_STEP: f32 = 0.5; // radians ahead on the orbit to steer toward
+const WOLF_PACK_RADIUS: f32 = 200.0;
+const WOLF_PACK_SIZE: usize = 3;
+const WOLF_CIRCLE_TIME: f64 = 3.0; // give up waiting for the pack after this
+const WOLF_LUNGE_TIME: f64 = 2.0;
+
+// Wraiths: fade out, reappear next to the target
+const WRAITH_TELEPORT_INTERVAL: f64 = 5.0;
+const WRAITH_TELEPORT_MIN_DISTANCE: f32 = 150.0;
+const WRAITH_PHASE_TIME: f64 = 1.0;
+const WRAITH_REAPPEAR_DISTANCE: f32 = 60.0;
+
+// Skeletons: hold a band of range and shoot
+const SKELETON_MIN_RANGE: f32 = 180.0;
+const SKELETON_MAX_RANGE: f32 = 260.0;
+const SKELETON_ARROW_SPEED: f32 = 220.0;
+const SKELETON_ARROW_LIFETIME: f32 = 2.0;
+
+// Trolls: telegraphed charge
+const TROLL_CHARGE_RANGE: f32 = 250.0;
+const TROLL_WINDUP_TIME: f64 = 1.0;
+const TROLL_CHARGE_TIME: f64 = 0.8;
+const TROLL_CHARGE_SPEED: f32 = 3.0; // multiplier on movement speed
+const TROLL_RECOVER_TIME: f64 = 1.0;
+
+/// What an enemy can see of the world when deciding its next step
+pub struct AiContext<'a> {
+    pub game_time: f64,
+    pub wolves: &'a [Position], // every living wolf, for pack checks
+}
+
+/// Advance one enemy's behavior state machine against its target and
+/// leave it a steering to move by until the next decision. Returns a
+/// projectile when a ranged enemy fires.
+pub fn decide(enemy: &mut Enemy, target: &Player, ctx: &AiContext) -> Option<Projectile> {
+    match enemy.enemy_type {
+        EnemyType::Wolf => update_wolf(enemy, target, ctx),
+        EnemyType::Wraith => update_wraith(enemy, target, ctx),
+        EnemyType::Skeleton => return update_skeleton(enemy, target, ctx),
+        EnemyType::Troll => update_troll(enemy, target, ctx),
+        _ => enemy.steering = Steering::Chase,
+    }
+    None
+}
+
+fn set_behavior(enemy: &mut Enemy, behavior: EnemyBehavior, game_time: f64) {
+    enemy.behavior = behavior;
+    enemy.behavior_started = game_time;
+}
+
+fn update_wolf(enemy: &mut Enemy, target: &Player, ctx: &AiContext) {
+    let elapsed = ctx.game_time - enemy.behavior_started;
+
+    if enemy.behavior != EnemyBehavior::Circle {
+        if elapsed >= WOLF_LUNGE_TIME {
+            set_behavior(enemy, EnemyBehavior::Circle, ctx.game_time);
+        }
+        enemy.steering = Steering::Chase;
+        return;
+    }
+
+    // The count includes this wolf
+    let pack = ctx
+        .wolves
+        .iter()
+        .filter(|p| p.distance_to(&enemy.position) <= WOLF_PACK_RADIUS)
+        .count();
+    if pack >= WOLF_PACK_SIZE || elapsed >= WOLF_CIRCLE_TIME {
+        set_behavior(enemy, EnemyBehavior::Chase, ctx.game_time);
+        enemy.steering = Steering::Chase;
+        return;
+    }
+
+    // Steer toward a point a little further around the orbit
+    let angle = (enemy.position - target.position).angle() + WOLF_CIRCLE_STEP;
+    let waypoint = target.position + Vec2::from_angle(angle) * WOLF_CIRCLE_RADIUS;
+    enemy.steering = Steering::Toward(waypoint);
+}
+
+fn update_wraith(enemy: &mut Enemy, target: &Player, ctx: &AiContext) {
+    let elapsed = ctx.game_time - enemy.behavior_started;
+
+    if enemy.behavior == EnemyBehavior::Phased {
+        if elapsed >= WRAITH_PHASE_TIME {
+            // Reappear on the side we came from, just out of melee
+            let offset = enemy.position - target.position;
+            let distance = offset.length().max(0.01);
+            enemy.position = target.position + offset / distance * WRAITH_REAPPEAR_DISTANCE;
+            set_behavior(enemy, EnemyBehavior::Chase, ctx.game_time);
+            enemy.steering = Steering::Chase;
+        }
+        return;
+    }
+
+    let distance = enemy.position.distance_to(&target.position);
+    if elapsed >= WRAITH_TELEPORT_INTERVAL && distance > WRAITH_TELEPORT_MIN_DISTANCE {
+        set_behavior(enemy, EnemyBehavior::Phased, ctx.game_time);
+        enemy.steering = Steering::Hold;
+        return;
+    }
+    enemy.steering = Steering::Chase;
+}
+
+fn update_skeleton(enemy: &mut Enemy, target: &Player, ctx: &AiContext) -> Option<Projectile> {
+    let distance = enemy.position.distance_to(&target.position);
+    if distance < SKELETON_MIN_RANGE {
+        let away = enemy.position + (enemy.position - target.position);
+        enemy.steering = Steering::Toward(away);
+    } else if distance > SKELETON_MAX_RANGE {
+        enemy.steering = Steering::Chase;
+    } else {
+        enemy.steering = Steering::Hold;
+    }
+
+    // Arrows can reach a little past the preferred band
+    let arrow_range = SKELETON_ARROW_SPEED * SKELETON_ARROW_LIFETIME;
+    if distance > arrow_range || !enemy.can_attack(ctx.game_time) {
+        return None;
+    }
+
+    enemy.last_attack_time = ctx.game_time;
+    let mut arrow = Projectile::new(
+        enemy.id,
+        enemy.position,
+        target.position - enemy.position,
+        SKELETON_ARROW_SPEED,
+        enemy.damage,
+        SKELETON_ARROW_LIFETIME,
+    );
+    arrow.hostile = true;
+    enemy.facing = arrow.facing; // aims while holding its ground
+    Some(arrow)
+}
+
+fn update_troll(enemy: &mut Enemy, target: &Player, ctx: &AiContext) {
+    let elapsed = ctx.game_time - enemy.behavior_started;
+
+    match enemy.behavior {
+        EnemyBehavior::WindUp => {
+            // Track the target while telegraphing, then commit
+            enemy.charge_direction = target.position - enemy.position;
+            enemy.facing = enemy.charge_direction.angle();
+            enemy.steering = Steering::Hold;
+            if elapsed >= TROLL_WINDUP_TIME {
+                set_behavior(enemy, EnemyBehavior::Charge, ctx.game_time);
+                enemy.steering = Steering::Heading(enemy.charge_direction, TROLL_CHARGE_SPEED);
+            }
+        }
+        EnemyBehavior::Charge => {
+            if elapsed >= TROLL_CHARGE_TIME {
+                set_behavior(enemy, EnemyBehavior::Recover, ctx.game_time);
+                enemy.steering = Steering::Hold;
+            }
+        }
+        EnemyBehavior::Recover => {
+            if elapsed >= TROLL_RECOVER_TIME {
+                set_behavior(enemy, EnemyBehavior::Chase, ctx.game_time);
+                enemy.steering = Steering::Chase;
+            }
+        }
+        _ => {
+            if enemy.position.distance_to(&target.position) <= TROLL_CHARGE_RANGE {
+                set_behavior(enemy, EnemyBehavior::WindUp, ctx.game_time);
+                enemy.steering = Steering::Hold;
+            } else {
+                enemy.steering = Steering::Chase;
+            }
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::VecDeque;
+use std::ops::Index;
+use uuid::Uuid;
+
+/// Low bits of an [`EntityId`] pick the slot, the high 32 count how often
+/// the slot has been reused so a stale ID never finds the slot's next
+/// occupant. That many reuses outlasts any server at any spawn rate, so in
+/// practice slots never run out of generations.
+const INDEX_BITS: u32 = 32;
+const INDEX_MASK: u64 = (1 << INDEX_BITS) - 1;
+const MAX_GENERATION: u32 = u32::MAX;
+
+/// Versions of changes kept for `changed_since`; a reader further behind
+/// than this has to start over from everything
+pub const CHANGE_HISTORY: u64 = 64;
+
+/// Compact server-side handle for a pooled entity (enemy, projectile,
+/// pickup). Never sent to clients; those keep identifying entities by UUID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityId(u64);
+
+impl EntityId {
+    fn new(index: u32, generation: u32) -> Self {
+        Self((generation as u64) << INDEX_BITS | index as u64)
+    }
+
+    fn index(self) -> usize {
+        (self.0 & INDEX_MASK) as usize
+    }
+
+    fn generation(self) -> u32 {
+        (self.0 >> INDEX_BITS) as u32
+    }
+
+    /// A wire ID for whatever holds this slot in this generation, so pooled
+    /// entities don't each draw on the random UUID generator. `namespace`
+    /// keeps apart maps whose slots would otherwise give the same IDs.
+    pub fn to_uuid(self, namespace: u64) -> Uuid {
+        Uuid::from_u64_pair(namespace, self.0)
+    }
+}
+
+impl std::fmt::Display for EntityId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}v{}", self.index(), self.generation())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Slot<T> {
+    generation: u32,
+    changed: u64, // version of the latest change to this slot
+    value: Option<T>,
+}
+
+/// Generational arena: entities live in a flat `Vec` and freed slots are
+/// reused, so spawning and despawning in bulk doesn't rehash or reallocate.
+///
+/// The map also tracks what changed in each version (the game starts one
+/// per step and one per snapshot refresh with `set_version`), so whoever serializes entities can pick out
+/// the changed ones without comparing every entity. Inserts and mutable
+/// access count as changes; `update` counts only the entities its closure
+/// says it changed.
+#[derive(Debug, Clone)]
+pub struct EntityMap<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    len: usize,
+    version: u64,
+    history_start: u64, // oldest version whose changes are all still logged
+    changes: VecDeque<(u64, u32)>, // version, slot index; once per slot per version
+    removals: VecDeque<(u64, EntityId)>,
+}
+
+impl<T> Default for EntityMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> EntityMap<T> {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+            version: 0,
+            history_start: 0,
+            changes: VecDeque::new(),
+            removals: VecDeque::new(),
+        }
+    }
+
+    pub fn insert(&mut self, value: T) -> EntityId {
+        self.len += 1;
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.value = Some(value);
+            let id = EntityId::new(index, slot.generation);
+            self.mark(index as usize);
+            return id;
+        }
+
+        let index = u32::try_from(self.slots.len()).expect("entity map is full");
+        self.slots.push(Slot {
+            generation: 0,
+            changed: self.version,
+            value: Some(value),
+        });
+        self.changes.push_back((self.version, index));
+        EntityId::new(index, 0)
+    }
+
+    /// Insert a value made knowing the ID it will get, for entities whose
+    /// wire ID comes from their slot
+    pub fn insert_with(&mut self, make: impl FnOnce(EntityId) -> T) -> EntityId {
+        let id = match self.free.last() {
+            Some(&index) => EntityId::new(index, self.slots[index as usize].generation),
+            None => EntityId::new(self.slots.len() as u32, 0),
+        };
+        self.insert(make(id))
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&T> {
+        self.slots
+            .get(id.index())
+            .filter(|slot| slot.generation == id.generation())
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T> {
+        if !self.contains(id) {
+            return None;
+        }
+        self.mark(id.index());
+        self.slots[id.index()].value.as_mut()
+    }
+
+    pub fn contains(&self, id: EntityId) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<T> {
+        let slot = self.slots.get_mut(id.index())?;
+        if slot.generation != id.generation() {
+            return None;
+        }
+        let value = slot.value.take()?;
+        self.release(id.index());
+        Some(value)
+    }
+
+    /// Keep only the entities the predicate accepts
+    pub fn retain(&mut self, mut keep: impl FnMut(EntityId, &T) -> bool) {
+        for index in 0..self.slots.len() {
+            let slot = &mut self.slots[index];
+            let id = EntityId::new(index as u32, slot.generation);
+            if let Some(value) = slot.value.as_ref() {
+                if !keep(id, value) {
+                    slot.value = None;
+                    self.release(index);
+                }
+            }
+        }
+    }
+
+    /// Run `change` on every entity, counting as changed only those it
+    /// returns true for. Bulk passes that leave most entities alone use
+    /// this instead of `values_mut`.
+    pub fn update(&mut self, mut change: impl FnMut(EntityId, &mut T) -> bool) {
+        for index in 0..self.slots.len() {
+            let slot = &mut self.slots[index];
+            let id = EntityId::new(index as u32, slot.generation);
+            if let Some(value) = slot.value.as_mut() {
+                if change(id, value) {
+                    self.mark(index);
+                }
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.retain(|_, _| false);
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let id = EntityId::new(index as u32, slot.generation);
+            slot.value.as_ref().map(|value| (id, value))
+        })
+    }
+
+    /// Every entity, mutably; all of them count as changed
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut T)> {
+        self.mark_all();
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let id = EntityId::new(index as u32, slot.generation);
+                slot.value.as_mut().map(|value| (id, value))
+            })
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = EntityId> + '_ {
+        self.iter().map(|(id, _)| id)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.slots.iter().filter_map(|slot| slot.value.as_ref())
+    }
+
+    /// Every entity, mutably; all of them count as changed
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.mark_all();
+        self.slots.iter_mut().filter_map(|slot| slot.value.as_mut())
+    }
+
+    /// Start a new version of changes, forgetting those older than
+    /// `CHANGE_HISTORY` versions. Versions must only go up.
+    pub fn set_version(&mut self, version: u64) {
+        self.version = version;
+        self.history_start = self
+            .history_start
+            .max(version.saturating_sub(CHANGE_HISTORY));
+        while self
+            .changes
+            .front()
+            .is_some_and(|(v, _)| *v < self.history_start)
+        {
+            self.changes.pop_front();
+        }
+        while self
+            .removals
+            .front()
+            .is_some_and(|(v, _)| *v < self.history_start)
+        {
+            self.removals.pop_front();
+        }
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// The live entities changed in version `since` or later, each once,
+    /// in time proportional to the number of changes. A reader passes the
+    /// version it last read at, so changes made later in that same version
+    /// aren't missed (at the cost of seeing the rest again). `None` when
+    /// `since` is older than the history kept.
+    pub fn changed_since(&self, since: u64) -> Option<impl Iterator<Item = EntityId> + '_> {
+        if since < self.history_start {
+            return None;
+        }
+        let changed = self
+            .changes
+            .iter()
+            .rev()
+            .take_while(move |(version, _)| *version >= since)
+            .filter_map(|&(version, index)| {
+                let slot = &self.slots[index as usize];
+                // Only the slot's latest entry, and only while it's occupied
+                (slot.changed == version && slot.value.is_some())
+                    .then(|| EntityId::new(index, slot.generation))
+            });
+        Some(changed)
+    }
+
+    /// Entities removed in version `since` or later; see `changed_since`
+    pub fn removed_since(&self, since: u64) -> Option<impl Iterator<Item = EntityId> + '_> {
+        if since < self.history_start {
+            return None;
+        }
+        let removed = self
+            .removals
+            .iter()
+            .rev()
+            .take_while(move |(version, _)| *version >= since)
+            .map(|&(_, id)| id);
+        Some(removed)
+    }
+
+    fn mark(&mut self, index: usize) {
+        let slot = &mut self.slots[index];
+        if slot.changed != self.version {
+            slot.changed = self.version;
+            self.changes.push_back((self.version, index as u32));
+        }
+    }
+
+    fn mark_all(&mut self) {
+        for index in 0..self.slots.len() {
+            if self.slots[index].value.is_some() {
+                self.mark(index);
+            }
+        }
+    }
+
+    /// Bump the slot's generation and put it back in the pool. A slot that
+    /// has used up its generations is retired instead of wrapping around,
+    /// as an ID still held somewhere would find the wrapped slot's occupant.
+    fn release(&mut self, index: usize) {
+        self.len -= 1;
+        let slot = &mut self.slots[index];
+        let removed = EntityId::new(index as u32, slot.generation);
+        self.removals.push_back((self.version, removed));
+        if slot.generation < MAX_GENERATION {
+            slot.generation += 1;
+            self.free.push(index as u32);
+        }
+    }
+}
+
+impl<T> Index<EntityId> for EntityMap<T> {
+    type Output = T;
+
+    fn index(&self, id: EntityId) -> &T {
+        self.get(id).expect("no entity with this ID")
+    }
+}
+
+impl<T> FromIterator<T> for EntityMap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut map = Self::new();
+        for value in iter {
+            map.insert(value);
+        }
+        map
+    }
+}
@@ -0,0 +1,76 @@
+//! Typed events the simulation publishes as things happen. Systems only
+//! say what happened; what follows from it (rewards, client messages,
+//! plugin hooks) lives in the subscribers `GameState::dispatch_events`
+//! runs. Anything outside the tick, like webhooks or achievements, can
+//! listen through `EventBus::subscribe`.
+
+use shared::{Enemy, RankChange, Weapon};
+use std::collections::VecDeque;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Events kept for slow external subscribers before the oldest are dropped
+const SUBSCRIBER_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub enum GameEvent {
+    /// A player entered the game, with their starting stats set
+    PlayerJoined { player_id: Uuid },
+    /// A player's attack finished off an enemy
+    EnemyKilled {
+        enemy: Enemy,
+        killer: Uuid,
+        weapon: Weapon,
+    },
+    /// A player reached `level`; one event per level gained
+    PlayerLevelUp { player_id: Uuid, level: u32 },
+    /// A player died; once per run
+    PlayerDied { player_id: Uuid },
+    /// A boss entered the world
+    BossSpawned { enemy: Enemy },
+    /// A run made the scoreboard, moving the entries listed
+    ScoreboardChanged { changes: Vec<RankChange> },
+    /// An operator's message for everyone connected
+    Announcement { text: String },
+}
+
+/// Events published but not yet dispatched, in order, and the channel
+/// they're passed on to once they have been
+#[derive(Debug)]
+pub struct EventBus {
+    queue: VecDeque<GameEvent>,
+    tx: broadcast::Sender<GameEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(SUBSCRIBER_CAPACITY);
+        Self {
+            queue: VecDeque::new(),
+            tx,
+        }
+    }
+}
+
+impl EventBus {
+    pub fn publish(&mut self, event: GameEvent) {
+        self.queue.push_back(event);
+    }
+
+    /// The oldest event still waiting for dispatch
+    pub(crate) fn next(&mut self) -> Option<GameEvent> {
+        self.queue.pop_front()
+    }
+
+    /// Hand a dispatched event to external subscribers
+    pub(crate) fn forward(&self, event: GameEvent) {
+        // Err only means nobody is listening
+        let _ = self.tx.send(event);
+    }
+
+    /// Every event from now on, after the game's own subscribers have
+    /// handled it
+    pub fn subscribe(&self) -> broadcast::Receiver<GameEvent> {
+        self.tx.subscribe()
+    }
+}
@@ -1,41 +1,448 @@
-use crate::game_state::SharedGameState;
-use std::time::Duration;
-use tokio::time;
+use crate::diagnostics::{self, LockSite};
+use crate::game_state::{GameState, SharedGameState};
+use crate::persistence;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::time::{self, MissedTickBehavior};
 
-pub async fn run_game_loop(state: SharedGameState) {
-    let tick_rate = {
+/// Pause before restarting a crashed loop, so a panic that repeats every
+/// tick doesn't spin the CPU
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// Slowest and fastest the simulation may be run, relative to `tick_rate`
+pub const MIN_SIM_SPEED: f64 = 0.25;
+pub const MAX_SIM_SPEED: f64 = 4.0;
+
+/// Most single steps that can be waiting to run at once
+pub const MAX_QUEUED_STEPS: u32 = 1200;
+
+/// How fast the loop runs the simulation, set through the admin API for
+/// debugging. Speed changes how many fixed steps run per second, never
+/// their length, so a sped-up run plays out exactly like a normal one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SimControl {
+    pub paused: bool,
+    pub speed: f64,        // multiple of tick_rate, MIN_SIM_SPEED..=MAX_SIM_SPEED
+    pub queued_steps: u32, // single steps to run while paused
+}
+
+impl Default for SimControl {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            speed: 1.0,
+            queued_steps: 0,
+        }
+    }
+}
+
+impl SimControl {
+    /// Pause or resume. Resuming drops single steps not yet run.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        if !paused {
+            self.queued_steps = 0;
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: f64) -> Result<(), String> {
+        if !(MIN_SIM_SPEED..=MAX_SIM_SPEED).contains(&speed) {
+            return Err(format!(
+                "speed must be between {} and {}",
+                MIN_SIM_SPEED, MAX_SIM_SPEED
+            ));
+        }
+        self.speed = speed;
+        Ok(())
+    }
+
+    /// Run `steps` more single steps, which only works while paused
+    pub fn queue_steps(&mut self, steps: u32) -> Result<(), String> {
+        if !self.paused {
+            return Err("single steps need the simulation paused".to_string());
+        }
+        let queued = self.queued_steps.saturating_add(steps);
+        if queued > MAX_QUEUED_STEPS {
+            return Err(format!(
+                "at most {} steps can wait to run",
+                MAX_QUEUED_STEPS
+            ));
+        }
+        self.queued_steps = queued;
+        Ok(())
+    }
+}
+
+/// Timing and health of the simulation loop
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TickStats {
+    pub crashes: u64, // panics caught by the supervisor
+    pub ticks: u64,
+    pub overruns: u64,        // loop iterations whose work took longer than a tick
+    pub catch_up_steps: u64,  // extra steps run to make up for slow iterations
+    pub dropped_seconds: f64, // simulation time skipped once the catch-up cap was hit
+    pub last_tick_ms: f64,
+    pub max_tick_ms: f64,
+    pub slowest_stage: &'static str, // of the latest step
+    pub slowest_stage_ms: f64,
+    pub breakdown: StageBreakdown, // of the latest step
+    pub slow_ticks: u64,           // steps over slow_tick_threshold_ms
+    pub changed_entities: usize,   // entities the latest step changed
+}
+
+/// Wall-clock time the simulation still owes, paid off in fixed steps
+#[derive(Debug)]
+pub struct Accumulator {
+    tick_duration: Duration,
+    owed: Duration,
+}
+
+impl Accumulator {
+    pub fn new(tick_duration: Duration) -> Self {
+        Self {
+            tick_duration,
+            owed: Duration::ZERO,
+        }
+    }
+
+    pub fn add(&mut self, elapsed: Duration) {
+        self.owed += elapsed;
+    }
+
+    /// Forget the time owed, e.g. while paused
+    pub fn clear(&mut self) {
+        self.owed = Duration::ZERO;
+    }
+
+    /// Steps to run now so the simulation catches up with the wall clock,
+    /// with `done` already run this wake and at most `max_steps` in all.
+    /// Whole steps still owed past the cap can't realistically be made up,
+    /// so they're dropped and returned in seconds.
+    pub fn take_steps(&mut self, done: u32, max_steps: u32) -> (u32, Option<f64>) {
+        let mut steps = 0;
+        while self.owed >= self.tick_duration && done + steps < max_steps {
+            self.owed -= self.tick_duration;
+            steps += 1;
+        }
+        if self.owed < self.tick_duration {
+            return (steps, None);
+        }
+        let dropped = self.owed.as_secs_f64();
+        self.owed = Duration::ZERO;
+        (steps, Some(dropped))
+    }
+}
+
+/// Where a step's time went, by area of the simulation
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct StageBreakdown {
+    pub spawning_ms: f64,
+    pub ai_ms: f64,
+    pub projectiles_ms: f64,
+    pub combat_ms: f64,
+    pub broadcast_ms: f64, // events, minimaps and snapshot serialization
+    pub other_ms: f64,
+}
+
+impl StageBreakdown {
+    /// Count `ms` spent in stage `name` toward its area
+    fn add(&mut self, name: &str, ms: f64) {
+        let area = match name {
+            "spawn_enemies" => &mut self.spawning_ms,
+            "decide_enemies" | "update_enemies" | "update_summons" => &mut self.ai_ms,
+            "update_projectiles" => &mut self.projectiles_ms,
+            "process_combat"
+            | "update_contact_damage"
+            | "update_effects"
+            | "update_shields"
+            | "update_second_winds"
+            | "update_deaths" => &mut self.combat_ms,
+            "update_minimap" | "flush_events" | "serialize_snapshots" => &mut self.broadcast_ms,
+            _ => &mut self.other_ms,
+        };
+        *area += ms;
+    }
+}
+
+/// Run the game loop, restarting it if it panics. The crashed state is
+/// saved next to the world snapshot for the bug report before carrying on.
+pub async fn supervise(state: SharedGameState) {
+    loop {
+        let handle = tokio::spawn(run_game_loop(state.clone()));
+        let error = match handle.await {
+            Ok(()) => return,
+            Err(e) if e.is_panic() => e.into_panic(),
+            Err(e) => {
+                tracing::error!("Game loop task failed: {}", e);
+                return;
+            }
+        };
+
+        let reason = error
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| error.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+
+        let path = {
+            let mut game = state.write().await;
+            game.tick_stats_mut().crashes += 1;
+            tracing::error!(
+                "Game loop panicked at tick {} ({} crashes so far): {}",
+                game.tick(),
+                game.tick_stats().crashes,
+                reason
+            );
+            let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%S");
+            PathBuf::from(&game.config().snapshot_path)
+                .with_extension(format!("crash-{stamp}.json"))
+        };
+        if let Err(e) = persistence::save(&state, &path).await {
+            tracing::error!("Failed to save crash snapshot: {:#}", e);
+        }
+
+        time::sleep(RESTART_DELAY).await;
+        tracing::info!("Restarting game loop");
+    }
+}
+
+async fn run_game_loop(state: SharedGameState) {
+    let (tick_rate, max_catch_up_steps) = {
         let s = state.read().await;
-        s.config.tick_rate
+        (s.config().tick_rate, s.config().max_catch_up_steps)
     };
 
     let tick_duration = Duration::from_secs_f64(1.0 / tick_rate);
     let mut interval = time::interval(tick_duration);
+    // Lateness is made up by the accumulator below, not by bursting the timer
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
     tracing::info!("Game loop started at {} ticks/sec", tick_rate);
 
+    let delta_time = 1.0 / tick_rate as f32;
+    let mut last_wake = Instant::now();
+    let mut accumulator = Accumulator::new(tick_duration);
+
     loop {
         interval.tick().await;
 
-        let delta_time = 1.0 / tick_rate as f32;
+        let now = Instant::now();
+        let elapsed = now - last_wake;
+        last_wake = now;
+
+        let mut game = diagnostics::write(&state, LockSite::GameLoop).await;
+        let mut control = game.sim_control();
+        let SimControl { paused, speed, .. } = control;
+        let per_wake = speed.ceil() as u32;
+        let max_steps = max_catch_up_steps * per_wake;
+
+        // Paused, only the single steps asked for run, as many per wake as
+        // catching up would, and the paused time is never made up
+        let mut steps = 0;
+        if paused {
+            accumulator.clear();
+            steps = control.queued_steps.min(max_steps);
+            control.queued_steps -= steps;
+            game.set_sim_control(control);
+            for _ in 0..steps {
+                step(&mut game, delta_time);
+            }
+        } else {
+            accumulator.add(elapsed.mul_f64(speed));
+        }
+
+        // Step in fixed increments until the simulation catches up with the
+        // wall clock, but give up on time it can't realistically make up
+        let (due, dropped) = accumulator.take_steps(steps, max_steps);
+        for _ in 0..due {
+            step(&mut game, delta_time);
+        }
+        steps += due;
+        if let Some(dropped) = dropped {
+            game.tick_stats_mut().dropped_seconds += dropped;
+            tracing::warn!(
+                "Game loop fell behind, dropping {:.3}s of simulation",
+                dropped
+            );
+        }
+
+        let work = now.elapsed();
+        let stats = game.tick_stats_mut();
+        stats.ticks += steps as u64;
+        if !paused {
+            stats.catch_up_steps += steps.saturating_sub(per_wake) as u64;
+        }
+        stats.last_tick_ms = work.as_secs_f64() * 1000.0;
+        stats.max_tick_ms = stats.max_tick_ms.max(stats.last_tick_ms);
+        if work > tick_duration {
+            stats.overruns += 1;
+            tracing::debug!(
+                "Tick overrun: {:.1}ms (budget {:.1}ms, {} overruns so far), slowest stage {} at {:.1}ms",
+                stats.last_tick_ms,
+                tick_duration.as_secs_f64() * 1000.0,
+                stats.overruns,
+                stats.slowest_stage,
+                stats.slowest_stage_ms
+            );
+        }
+    }
+}
+
+/// Advance the simulation by one fixed step. Runs inside a `tick` span,
+/// with a `stage` span around each part of the pipeline. A step slower
+/// than `slow_tick_threshold_ms` is logged with its `StageBreakdown`.
+pub fn step(game: &mut GameState, delta_time: f32) {
+    // Update game time
+    game.advance_clock(delta_time);
+    let _span = tracing::info_span!("tick", tick = game.tick()).entered();
+    let started = Instant::now();
+    let stats = game.tick_stats_mut();
+    stats.slowest_stage = "";
+    stats.slowest_stage_ms = 0.0;
+    stats.breakdown = StageBreakdown::default();
+    let version = game.next_entity_version();
+
+    // Moves and dashes received since the last tick
+    stage(game, "apply_inputs", |game| game.apply_inputs(delta_time));
+
+    // Advance active dashes
+    stage(game, "update_dashes", |game| game.update_dashes(delta_time));
+
+    // Spawn enemies
+    stage(game, "spawn_enemies", |game| game.spawn_enemies(delta_time));
+
+    // Enemy AI decisions, at their own lower rate
+    stage(game, "decide_enemies", |game| {
+        game.decide_enemies(delta_time)
+    });
+
+    // Move enemies along their steering
+    stage(game, "update_enemies", |game| {
+        game.update_enemies(delta_time)
+    });
+
+    // Companions follow their owners and attack
+    stage(game, "update_summons", |game| {
+        game.update_summons(delta_time)
+    });
+
+    // Update projectiles and collisions
+    stage(game, "update_projectiles", |game| {
+        game.update_projectiles(delta_time)
+    });
 
-        let mut game = state.write().await;
+    // Expire drops and collect the ones players walk over
+    stage(game, "update_pickups", |game| {
+        game.update_pickups(delta_time)
+    });
 
-        // Update game time
-        game.game_time += delta_time as f64;
+    // Keep everything inside the map and warn players near the edge
+    stage(game, "update_boundary", |game| game.update_boundary());
 
-        // Spawn enemies
-        game.spawn_enemies(delta_time);
+    // Process combat (spawn projectiles)
+    stage(game, "process_combat", |game| game.process_combat());
 
-        // Update enemy AI
-        game.update_enemies(delta_time);
+    // Contact damage mode: enemies touching players hurt them
+    stage(game, "update_contact_damage", |game| {
+        game.update_contact_damage(delta_time)
+    });
 
-        // Update projectiles and collisions
-        game.update_projectiles(delta_time);
+    // Safe zone healing and camping limit
+    stage(game, "update_safe_zone", |game| {
+        game.update_safe_zone(delta_time)
+    });
+
+    // Regeneration, burns, auras and timed buffs
+    stage(game, "update_effects", |game| game.update_effects());
+
+    // Shields wear off
+    stage(game, "update_shields", |game| {
+        game.update_shields(delta_time)
+    });
+
+    // Battle royale zone closure
+    stage(game, "update_zone", |game| game.update_zone(delta_time));
+
+    // Invulnerability for players who survived a lethal hit this tick
+    stage(game, "update_second_winds", |game| {
+        game.update_second_winds()
+    });
+
+    // Note hits for the teleport lockout and finish teleport channels
+    stage(game, "update_teleports", |game| game.update_teleports());
+
+    // Death summaries for players who died this tick
+    stage(game, "update_deaths", |game| game.update_deaths());
+
+    // Score parties whose members have all died
+    stage(game, "update_parties", |game| game.update_parties());
+
+    // Remind players of open upgrade offers and time out stale ones
+    stage(game, "update_level_ups", |game| game.update_level_ups());
+
+    // Warn idle players, then remove them to free their slot
+    stage(game, "update_afk", |game| game.update_afk());
+
+    // Mode end conditions (timer, core, last standing)
+    stage(game, "update_match", |game| game.update_match());
+
+    // Velocity and facing for snapshots, from this tick's movement
+    stage(game, "update_motion", |game| game.update_motion(delta_time));
+
+    // Coarse world overview for minimaps, about once a second
+    stage(game, "update_minimap", |game| game.update_minimap());
+
+    // Operators' custom mechanics
+    stage(game, "update_plugins", |game| {
+        game.update_plugins(delta_time)
+    });
+
+    // Deliver events raised this tick; snapshots are still sent by
+    // each connection's own timer
+    stage(game, "flush_events", |game| game.flush_events());
+    game.tick_stats_mut().changed_entities = game.changed_entities(version);
+
+    // Serialize what the step changed once, for every connection's snapshots
+    stage(game, "serialize_snapshots", |game| {
+        game.refresh_snapshot_cache()
+    });
+
+    let ms = started.elapsed().as_secs_f64() * 1000.0;
+    let threshold = game.config().slow_tick_threshold_ms;
+    if threshold > 0.0 && ms > threshold {
+        let stats = game.tick_stats_mut();
+        stats.slow_ticks += 1;
+        let b = &stats.breakdown;
+        tracing::warn!(
+            tick_ms = ms,
+            spawning_ms = b.spawning_ms,
+            ai_ms = b.ai_ms,
+            projectiles_ms = b.projectiles_ms,
+            combat_ms = b.combat_ms,
+            broadcast_ms = b.broadcast_ms,
+            other_ms = b.other_ms,
+            slowest_stage = stats.slowest_stage,
+            "Slow tick: {:.1}ms over the {:.1}ms threshold",
+            ms,
+            threshold
+        );
+    }
+}
 
-        // Process combat (spawn projectiles)
-        game.process_combat();
+/// Run one stage of `step` in its own span, then dispatch the events it
+/// published, noting it if it's the slowest so far this step
+fn stage(game: &mut GameState, name: &'static str, run: impl FnOnce(&mut GameState)) {
+    let _span = tracing::debug_span!("stage", stage = name).entered();
+    let start = Instant::now();
+    run(game);
+    game.dispatch_events();
 
-        // Could add state broadcasting here if needed
-        // For now, clients request state via WebSocket
+    let ms = start.elapsed().as_secs_f64() * 1000.0;
+    let stats = game.tick_stats_mut();
+    stats.breakdown.add(name, ms);
+    if ms > stats.slowest_stage_ms {
+        stats.slowest_stage = name;
+        stats.slowest_stage_ms = ms;
     }
 }
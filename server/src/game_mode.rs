@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use shared::{Player, ScoreEntry};
+
+use crate::config::GameConfig;
+
+/// Rule set for a match: how enemies spawn, how it ends, and how runs score
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    /// Survive as long as possible; the match never ends
+    #[default]
+    Endless,
+    /// Fixed-length run (`timed_duration`); survivors at the buzzer win
+    Timed,
+    /// Waves march on the center; the match is lost when the core falls
+    WaveDefense,
+    /// Rings close from the center outward; last player standing wins
+    BattleRoyale,
+    /// Endless rules on the day's shared seed, ranked on a daily
+    /// leaderboard; the match rolls over at midnight UTC. See `daily`.
+    Daily,
+}
+
+/// Why a match ended and who (if anyone) won it
+#[derive(Debug, Clone)]
+pub struct MatchOutcome {
+    pub reason: String,
+    pub winners: Vec<uuid::Uuid>,
+}
+
+impl std::str::FromStr for GameMode {
+    type Err = anyhow::Error;
+
+    /// `endless`, `timed`, `wave_defense`, `battle_royale` or `daily`
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "endless" => Ok(GameMode::Endless),
+            "timed" => Ok(GameMode::Timed),
+            "wave_defense" => Ok(GameMode::WaveDefense),
+            "battle_royale" => Ok(GameMode::BattleRoyale),
+            "daily" => Ok(GameMode::Daily),
+            _ => anyhow::bail!("unknown game mode {:?}", s),
+        }
+    }
+}
+
+impl GameMode {
+    pub fn name(&self) -> &str {
+        match self {
+            GameMode::Endless => "Endless",
+            GameMode::Timed => "Timed",
+            GameMode::WaveDefense => "Wave Defense",
+            GameMode::BattleRoyale => "Battle Royale",
+            GameMode::Daily => "Daily Run",
+        }
+    }
+
+    /// Enemies spawn around players by ring (as opposed to in waves)
+    pub fn spawns_by_ring(&self) -> bool {
+        !matches!(self, GameMode::WaveDefense)
+    }
+
+    /// Whether the mode has matches that end, as opposed to open-ended runs
+    pub fn has_matches(&self) -> bool {
+        !matches!(self, GameMode::Endless)
+    }
+
+    /// Inner rings close over time
+    pub fn closes_zone(&self) -> bool {
+        matches!(self, GameMode::BattleRoyale)
+    }
+
+    /// Whether a finished run earns a scoreboard entry
+    pub fn qualifies_for_scoreboard(&self, player: &Player, config: &GameConfig) -> bool {
+        match self {
+            GameMode::Endless => player.max_ring_reached >= config.score_min_ring,
+            // Every run in a bounded mode is comparable, so all of them count
+            GameMode::Timed | GameMode::WaveDefense | GameMode::BattleRoyale | GameMode::Daily => {
+                player.enemies_defeated > 0
+            }
+        }
+    }
+
+    /// Sort key for the scoreboard (higher is better)
+    pub fn score(&self, entry: &ScoreEntry) -> u32 {
+        match self {
+            GameMode::Endless | GameMode::BattleRoyale | GameMode::Daily => entry.total_score(),
+            // Fixed duration: kills matter most, depth breaks ties
+            GameMode::Timed => entry.enemies_defeated * 100 + entry.max_ring_reached,
+            // Holding out longer is the achievement
+            GameMode::WaveDefense => {
+                (entry.survival_time_seconds as u32) * 10 + entry.enemies_defeated
+            }
+        }
+    }
+}
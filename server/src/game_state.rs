@@ -1,384 +1,3839 @@
-use rand::Rng;
-use shared::{Enemy, EnemyType, Player, Position, Projectile, ScoreEntry, UpgradeType};
-use std::collections::HashMap;
+//! The simulation's state and everything that changes it.
+//!
+//! Everything outside the simulation (connection sessions, transports,
+//! HTTP routes, the binary, integration tests) goes through `GameState`'s
+//! methods: queries such as `player`, `entity_counts` and `snapshot`,
+//! `handle_command` for what clients send, and `subscribe_events` /
+//! `subscribe_game_events` for what happens. The fields are private; even
+//! the simulation's own stages (`game_loop`, `persistence`, `snapshot`,
+//! `replay`) read and change them through methods, so how entities are
+//! stored can change without the rest of the server noticing.
+
+use bytes::Bytes;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Serialize;
+use shared::{
+    cmp_distance, ClientMessage, CombatEvent, CombatEventKind, DespawnReason, Enemy, EnemyBehavior,
+    EnemyType, EntityEvent, EntityKind, Equipment, ErrorCode, Landmark, LandmarkType, Locale,
+    MinimapMarker, Pickup, PickupType, Player, Position, Projectile, RankChange, ScoreEntry,
+    ServerMessage, ShopItem, Steering, Summon, SummonType, Teleport, UpgradeChoice, UpgradeType,
+    Vec2, Weapon,
+};
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, RwLock};
 use uuid::Uuid;
 
+use crate::access::{Ban, BanList, BanTarget, ConnectionCounts};
+use crate::collision::{self, Body};
 use crate::config::GameConfig;
+use crate::daily::{self, DailyBoard};
+use crate::diagnostics::LockWaits;
+use crate::effects::{EffectKind, Effects, TimedEffect};
+use crate::enemy_ai::{self, AiContext};
+use crate::entity_map::{EntityId, EntityMap};
+use crate::events::{EventBus, GameEvent};
+use crate::game_loop::{SimControl, TickStats};
+use crate::game_mode::{GameMode, MatchOutcome};
+use crate::input::{InputKind, InputQueue, QueuedInput};
+use crate::integrity::{HeldScore, InputRate, IntegrityRecord, MAX_HELD_SCORES};
+use crate::kill_credit;
+use crate::level_up::PendingLevelUp;
+use crate::log_tail::LogTail;
+use crate::match_history::{MatchHistory, MatchRecord, Participant};
+use crate::party::Party;
+use crate::persistence::WorldSnapshot;
+use crate::plugin::{GamePlugin, Plugins};
+use crate::rooms::Rooms;
+use crate::runs::{RunLog, RunSummary};
+use crate::signals::SignalAllowance;
+use crate::snapshot::{SnapshotBuffer, SnapshotCache};
+use crate::threat;
+use crate::transport::udp;
+use crate::validation::MessageError;
+
+pub type SharedGameState = Arc<RwLock<GameState>>;
+
+/// How many undelivered events a connection may fall behind before it lags
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// How far past touching an enemy still counts as in contact; separation
+/// keeps crowds from ever quite overlapping a player
+const CONTACT_SLACK: f32 = 2.0;
+
+/// Upgrades offered per level-up
+const UPGRADE_CHOICES: usize = 3;
+
+/// First element of each `run_rng` stream, keeping their rolls apart
+const SPAWN_RNG: u64 = 1;
+const WAVE_RNG: u64 = 2;
+const UPGRADE_RNG: u64 = 3;
+const DAMAGE_RNG: u64 = 4;
+const DROP_RNG: u64 = 5;
+const LANDMARK_RNG: u64 = 6;
+const AUTO_PICK_RNG: u64 = 7;
+
+/// Mixed into the match seed to give each pooled kind its own ID namespace
+const PROJECTILE_IDS: u64 = 0x7072_6f6a;
+const PICKUP_IDS: u64 = 0x7069_636b;
+
+/// When a player last sent input, for AFK detection, and last hurt an
+/// enemy, for a share of their party's XP
+#[derive(Debug, Clone, Copy)]
+pub struct Activity {
+    pub last_input: f64, // game time
+    pub warned: bool,
+    pub last_hit: Option<f64>, // game time
+}
+
+/// What became of a join request, given `max_players`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinOutcome {
+    Joined,
+    Queued(usize), // 1-based position
+    Rejected,      // queue full too; the connection was sent `Kicked`
+}
+
+/// What a projectile struck directly, spared by its own explosion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Nothing,
+    Enemy(EntityId),
+    Player(Uuid),
+}
+
+/// A message produced by the simulation, delivered by the connection tasks
+#[derive(Debug, Clone)]
+pub struct OutboundMessage {
+    /// `None` delivers to every connection
+    pub recipient: Option<Uuid>,
+    pub message: ServerMessage,
+    pub tick: u64, // when it was queued, for its envelope
+}
+
+/// How many of each kind of entity there are
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct EntityCounts {
+    pub players: usize,
+    pub queued: usize, // waiting for a slot under max_players
+    pub enemies: usize,
+    pub projectiles: usize,
+    pub pickups: usize,
+    pub summons: usize,
+}
+
+#[derive(Debug)]
+pub struct GameState {
+    config: GameConfig,
+    players: HashMap<Uuid, Player>,
+    enemies: EntityMap<Enemy>,
+    projectiles: EntityMap<Projectile>,
+    pickups: EntityMap<Pickup>,
+    summons: EntityMap<Summon>, // players' companions; not saved in snapshots
+    landmarks: HashMap<Uuid, Landmark>,
+    scores: Vec<ScoreEntry>,
+    held_scores: Vec<HeldScore>,
+    daily: DailyBoard, // today's seed and standings in daily mode
+    game_time: f64,
+    tick: u64,              // fixed simulation steps run so far
+    tick_wall_time_ms: i64, // wall clock (Unix millis) when `tick` ran
+    started_at: Instant,    // server start, for uptime; not saved in snapshots
+    last_spawn_time: f64,
+    next_minimap_time: f64,
+    pending_level_ups: HashMap<Uuid, PendingLevelUp>, // Player ID -> open upgrade offer
+    closed_rings: u32,     // battle royale: inner rings no longer playable
+    next_zone_shrink: f64, // game time of the next ring closure
+    match_start_time: f64,
+    match_seed: u64,     // drives the landmark layout
+    wave_number: u32,    // wave defense: waves spawned so far
+    next_wave_time: f64, // wave defense: game time of the next wave
+    core_health: f32,    // wave defense: the match is lost at zero
+    match_id: Uuid,      // names the match's record in match history
+    match_started_at: chrono::DateTime<chrono::Utc>,
+    match_departed: Vec<Participant>, // left before the match ended, for its record
+    parties: HashMap<Uuid, Party>,
+    udp_peers: HashMap<Uuid, SocketAddr>, // players getting snapshots over UDP instead
+    player_ips: HashMap<Uuid, IpAddr>,    // address each player connected from
+    locales: HashMap<Uuid, Locale>,       // language of the text each player is sent
+    join_queue: VecDeque<Uuid>,           // waiting for a slot under max_players
+    activity: HashMap<Uuid, Activity>,    // Player ID -> last input, for AFK removal
+    input_queues: HashMap<Uuid, InputQueue>, // Move/Dash inputs waiting for a tick
+    input_rates: HashMap<Uuid, InputRate>, // how fast each player sends them
+    signal_allowances: HashMap<Uuid, SignalAllowance>, // emotes and pings left
+    bans: BanList,
+    match_history: MatchHistory,
+    runs: RunLog,                  // how recent runs ended, for GET /runs/{id}
+    rooms: Rooms,                  // games opened next to this one by POST /rooms
+    connections: ConnectionCounts, // open connections per IP
+    tick_stats: TickStats,
+    sim_control: SimControl,
+    lock_waits: LockWaits,
+    snapshot_cache: SnapshotCache,
+    log_tail: LogTail,               // recent log lines for the admin dashboard
+    plugins: Plugins,                // custom mechanics called from the simulation
+    game_events: EventBus,           // what happened this stage, for dispatch_events
+    effects: Effects,                // regeneration, burns, auras and buffs on players
+    entity_version: u64,             // current version of entity changes; see EntityMap
+    combat_events: Vec<CombatEvent>, // batched into one message per tick
+    entity_events: Vec<EntityEvent>, // batched per tick, for the players in view
+    scratch_ids: Vec<EntityId>,      // reused by update_projectiles each tick
+    pending_events: Vec<OutboundMessage>, // queued during the tick, sent by flush_events
+    events_tx: broadcast::Sender<OutboundMessage>,
+}
+
+impl GameState {
+    pub fn new(config: GameConfig) -> Self {
+        let (events_tx, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let next_zone_shrink = config.zone_shrink_interval as f64;
+        let core_health = config.core_health;
+        let daily = DailyBoard::new(daily::today());
+        let match_seed = match config.mode {
+            GameMode::Daily => daily.seed,
+            _ => config.seed.unwrap_or_else(rand::random),
+        };
+        let match_history = MatchHistory::new(config.max_match_history);
+        let runs = RunLog::new(config.max_run_summaries);
+        let mut state = Self {
+            config,
+            players: HashMap::new(),
+            enemies: EntityMap::new(),
+            projectiles: EntityMap::new(),
+            pickups: EntityMap::new(),
+            summons: EntityMap::new(),
+            landmarks: HashMap::new(),
+            scores: Vec::new(),
+            held_scores: Vec::new(),
+            daily,
+            game_time: 0.0,
+            tick: 0,
+            tick_wall_time_ms: 0,
+            started_at: Instant::now(),
+            last_spawn_time: 0.0,
+            pending_level_ups: HashMap::new(),
+            closed_rings: 0,
+            next_zone_shrink,
+            match_start_time: 0.0,
+            match_seed,
+            wave_number: 0,
+            next_wave_time: 0.0,
+            core_health,
+            next_minimap_time: 0.0,
+            match_id: Uuid::new_v4(),
+            match_started_at: chrono::Utc::now(),
+            match_departed: Vec::new(),
+            parties: HashMap::new(),
+            udp_peers: HashMap::new(),
+            player_ips: HashMap::new(),
+            locales: HashMap::new(),
+            join_queue: VecDeque::new(),
+            activity: HashMap::new(),
+            input_queues: HashMap::new(),
+            input_rates: HashMap::new(),
+            signal_allowances: HashMap::new(),
+            bans: BanList::default(),
+            match_history,
+            runs,
+            rooms: Rooms::default(),
+            connections: ConnectionCounts::default(),
+            tick_stats: TickStats::default(),
+            sim_control: SimControl::default(),
+            lock_waits: LockWaits::default(),
+            log_tail: LogTail::default(),
+            plugins: Plugins::default(),
+            game_events: EventBus::default(),
+            effects: Effects::default(),
+            snapshot_cache: SnapshotCache::default(),
+            entity_version: 0,
+            combat_events: Vec::new(),
+            entity_events: Vec::new(),
+            scratch_ids: Vec::new(),
+            pending_events: Vec::new(),
+            events_tx,
+        };
+        state.generate_landmarks();
+        state
+    }
+
+    /// Queue a message for every connected client
+    pub fn broadcast(&mut self, message: ServerMessage) {
+        self.pending_events.push(OutboundMessage {
+            recipient: None,
+            message,
+            tick: self.tick,
+        });
+    }
+
+    /// Queue a message for a single player's connection
+    pub fn send_to(&mut self, player_id: Uuid, message: ServerMessage) {
+        self.pending_events.push(OutboundMessage {
+            recipient: Some(player_id),
+            message,
+            tick: self.tick,
+        });
+    }
+
+    /// Deliver everything queued during the tick
+    pub fn flush_events(&mut self) {
+        if !self.combat_events.is_empty() {
+            let events = std::mem::take(&mut self.combat_events);
+            self.broadcast(ServerMessage::CombatEvents { events });
+        }
+        if !self.entity_events.is_empty() {
+            self.send_entity_events();
+        }
+
+        for event in self.pending_events.drain(..) {
+            // Err only means nobody is connected right now
+            let _ = self.events_tx.send(event);
+        }
+    }
+
+    /// Send each player the entity events that happened within their view,
+    /// the same area their snapshots cover
+    fn send_entity_events(&mut self) {
+        let events = std::mem::take(&mut self.entity_events);
+        let recipients: Vec<_> = self
+            .players
+            .values()
+            .map(|p| (p.id, p.position, self.aoi_radius(p)))
+            .collect();
+        for (player_id, center, radius) in recipients {
+            let nearby: Vec<_> = events
+                .iter()
+                .filter(|e| e.position().distance_to(&center) <= radius)
+                .cloned()
+                .collect();
+            if !nearby.is_empty() {
+                self.send_to(player_id, ServerMessage::EntityEvents { events: nearby });
+            }
+        }
+    }
+
+    pub fn subscribe_events(&self) -> broadcast::Receiver<OutboundMessage> {
+        self.events_tx.subscribe()
+    }
+
+    /// Connections currently subscribed to events, one per session task
+    pub fn session_count(&self) -> usize {
+        self.events_tx.receiver_count()
+    }
+
+    /// Everything the simulation publishes, as it's dispatched
+    pub fn subscribe_game_events(&self) -> broadcast::Receiver<GameEvent> {
+        self.game_events.subscribe()
+    }
+
+    /// `player_id`'s next snapshot, written into their connection's
+    /// `buffer`; see `SnapshotBuffer::write`
+    pub fn snapshot(
+        &self,
+        buffer: &mut SnapshotBuffer,
+        player_id: Uuid,
+        limit: Option<usize>,
+        seq: u64,
+    ) -> Bytes {
+        buffer.write(self, player_id, limit, seq)
+    }
+
+    /// Carry out a client message from a player already given an ID. Move
+    /// and Dash wait in the player's input queue for the next tick; the
+    /// rest take effect at once. Anything but a viewport report counts as
+    /// activity. A connection's first Join is up to the connection, which
+    /// knows the password and protocol checks it passed; any after that
+    /// end up here and are refused.
+    pub fn handle_command(
+        &mut self,
+        player_id: Uuid,
+        command: ClientMessage,
+    ) -> Result<(), MessageError> {
+        if !matches!(command, ClientMessage::SetViewport { .. }) {
+            self.record_input(player_id);
+        }
+        let refused = |message| MessageError::new(ErrorCode::Refused, message);
+        let bad_choice = |message| MessageError::new(ErrorCode::InvalidUpgradeChoice, message);
+        match command {
+            ClientMessage::Join { .. } => Err(refused("Already joined".to_string())),
+            ClientMessage::Move { target, seq } => {
+                self.queue_input(player_id, seq, InputKind::Move { target });
+                Ok(())
+            }
+            ClientMessage::Dash { direction, seq } => {
+                self.queue_input(player_id, seq, InputKind::Dash { direction });
+                Ok(())
+            }
+            ClientMessage::SetViewport { radius } => {
+                self.set_view_radius(player_id, radius);
+                Ok(())
+            }
+            ClientMessage::ChooseUpgrade { upgrade } => {
+                self.apply_upgrade(player_id, upgrade).map_err(bad_choice)
+            }
+            ClientMessage::RerollUpgrades => self.reroll_upgrades(player_id).map_err(bad_choice),
+            ClientMessage::BanishUpgrade { upgrade } => {
+                self.banish_upgrade(player_id, upgrade).map_err(bad_choice)
+            }
+            ClientMessage::CreateParty => self.create_party(player_id).map(|_| ()).map_err(refused),
+            ClientMessage::JoinParty { party_id } => {
+                self.join_party(player_id, party_id).map_err(refused)
+            }
+            ClientMessage::Interact { landmark_id } => {
+                self.interact(player_id, landmark_id).map_err(refused)
+            }
+            ClientMessage::EquipItem { item_id } => {
+                self.equip_item(player_id, item_id).map_err(refused)
+            }
+            ClientMessage::DropItem { item_id } => {
+                self.drop_item(player_id, item_id).map_err(refused)
+            }
+            ClientMessage::TeleportTo { waypoint } => {
+                self.teleport_to(player_id, waypoint).map_err(refused)
+            }
+            ClientMessage::BuyItem { item } => self.buy_item(player_id, item).map_err(refused),
+            ClientMessage::Emote { kind } => {
+                self.signal(player_id, ServerMessage::Emoted { player_id, kind })
+            }
+            ClientMessage::PingLocation { position } => {
+                let ping = ServerMessage::LocationPinged {
+                    player_id,
+                    position,
+                };
+                self.signal(player_id, ping)
+            }
+        }
+    }
+
+    /// Relay an emote or ping from `player_id` to the players around them
+    /// and their party, them included; see `signals`
+    fn signal(&mut self, player_id: Uuid, message: ServerMessage) -> Result<(), MessageError> {
+        let Some(sender) = self.players.get(&player_id) else {
+            return Err(MessageError::new(ErrorCode::NotInGame, "Not in the game"));
+        };
+        let (position, party_id) = (sender.position, sender.party_id);
+        let (burst, rate) = (self.config.signal_burst, self.config.signal_rate);
+        let allowance = self.signal_allowances.entry(player_id).or_default();
+        if !allowance.take(self.game_time, burst, rate) {
+            let message = "Too many emotes and pings; wait a moment";
+            return Err(MessageError::new(ErrorCode::RateLimited, message));
+        }
+
+        let range = self.config.signal_range;
+        let recipients: Vec<Uuid> = self
+            .players
+            .values()
+            .filter(|p| {
+                p.position.distance_to(&position) <= range
+                    || (party_id.is_some() && p.party_id == party_id)
+            })
+            .map(|p| p.id)
+            .collect();
+        for recipient in recipients {
+            self.send_to(recipient, message.clone());
+        }
+        Ok(())
+    }
+
+    /// Remember where a newly joined (or queued) player connected from and
+    /// the language they read
+    pub fn connect_client(&mut self, player_id: Uuid, ip: IpAddr, locale: Locale) {
+        self.player_ips.insert(player_id, ip);
+        self.locales.insert(player_id, locale);
+    }
+
+    /// Send `player_id`'s snapshots to `addr` over UDP from now on. Returns
+    /// whether that's a change.
+    pub fn bind_udp(&mut self, player_id: Uuid, addr: SocketAddr) -> bool {
+        self.udp_peers.insert(player_id, addr) != Some(addr)
+    }
+
+    /// Hand `player_id`'s snapshots back to their connection, unless
+    /// they've moved on from `addr` already
+    pub fn unbind_udp(&mut self, player_id: Uuid, addr: SocketAddr) {
+        if self.udp_peers.get(&player_id) == Some(&addr) {
+            self.udp_peers.remove(&player_id);
+        }
+    }
+
+    /// Whether `player_id`'s snapshots go out over UDP
+    pub fn uses_udp(&self, player_id: Uuid) -> bool {
+        self.udp_peers.contains_key(&player_id)
+    }
+
+    pub fn config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    /// Fixed simulation steps run so far
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Seconds simulated so far
+    pub fn game_time(&self) -> f64 {
+        self.game_time
+    }
+
+    /// Wall clock (Unix millis) when the latest tick ran
+    pub fn tick_wall_time_ms(&self) -> i64 {
+        self.tick_wall_time_ms
+    }
+
+    /// Start the next fixed step, `delta_time` seconds on from the last
+    pub fn advance_clock(&mut self, delta_time: f32) {
+        self.game_time += delta_time as f64;
+        self.tick += 1;
+        self.tick_wall_time_ms = chrono::Utc::now().timestamp_millis();
+    }
+
+    /// Time since the server started
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    pub fn player(&self, player_id: Uuid) -> Option<&Player> {
+        self.players.get(&player_id)
+    }
+
+    pub fn player_mut(&mut self, player_id: Uuid) -> Option<&mut Player> {
+        self.players.get_mut(&player_id)
+    }
+
+    /// Everyone in the game, in no particular order
+    pub fn players(&self) -> impl ExactSizeIterator<Item = &Player> {
+        self.players.values()
+    }
+
+    /// Whether `player_id` is in the game or waiting in the join queue
+    pub fn knows_player(&self, player_id: Uuid) -> bool {
+        self.players.contains_key(&player_id) || self.join_queue.contains(&player_id)
+    }
+
+    /// The address `player_id` connected from
+    pub fn player_ip(&self, player_id: Uuid) -> Option<IpAddr> {
+        self.player_ips.get(&player_id).copied()
+    }
+
+    pub fn entity_counts(&self) -> EntityCounts {
+        EntityCounts {
+            players: self.players.len(),
+            queued: self.join_queue.len(),
+            enemies: self.enemies.len(),
+            projectiles: self.projectiles.len(),
+            pickups: self.pickups.len(),
+            summons: self.summons.len(),
+        }
+    }
+
+    pub fn enemies(&self) -> &EntityMap<Enemy> {
+        &self.enemies
+    }
+
+    pub fn projectiles(&self) -> &EntityMap<Projectile> {
+        &self.projectiles
+    }
+
+    pub fn pickups(&self) -> &EntityMap<Pickup> {
+        &self.pickups
+    }
+
+    pub fn summons(&self) -> &EntityMap<Summon> {
+        &self.summons
+    }
+
+    /// Put an enemy into the world as it is, without the spawn hooks
+    pub fn insert_enemy(&mut self, enemy: Enemy) -> EntityId {
+        self.entity_events
+            .push(spawned(EntityKind::Enemy, enemy.id, enemy.position));
+        self.enemies.insert(enemy)
+    }
+
+    /// The upgrades `player_id` is choosing between, if any
+    pub fn upgrade_offer(&self, player_id: Uuid) -> Option<&PendingLevelUp> {
+        self.pending_level_ups.get(&player_id)
+    }
+
+    /// Offer `player_id` these upgrades in place of whatever they had on
+    /// offer, without telling them
+    pub fn set_upgrade_offer(&mut self, player_id: Uuid, choices: Vec<UpgradeChoice>) {
+        let offer = PendingLevelUp::new(choices, self.game_time);
+        self.pending_level_ups.insert(player_id, offer);
+    }
+
+    pub fn register_plugin(&mut self, plugin: Box<dyn GamePlugin>) {
+        self.plugins.register(plugin);
+    }
+
+    pub fn tick_stats(&self) -> &TickStats {
+        &self.tick_stats
+    }
+
+    pub fn tick_stats_mut(&mut self) -> &mut TickStats {
+        &mut self.tick_stats
+    }
+
+    pub fn sim_control(&self) -> SimControl {
+        self.sim_control
+    }
+
+    pub fn set_sim_control(&mut self, control: SimControl) {
+        self.sim_control = control;
+    }
+
+    pub fn bans(&self) -> &BanList {
+        &self.bans
+    }
+
+    pub fn set_bans(&mut self, bans: BanList) {
+        self.bans = bans;
+    }
+
+    /// Lift the ban on `target`, returning it if there was one
+    pub fn unban(&mut self, target: &BanTarget) -> Option<Ban> {
+        self.bans.remove(target)
+    }
+
+    pub fn rooms(&self) -> &Rooms {
+        &self.rooms
+    }
+
+    pub fn connections(&self) -> &ConnectionCounts {
+        &self.connections
+    }
+
+    pub fn lock_waits(&self) -> &LockWaits {
+        &self.lock_waits
+    }
+
+    pub fn log_tail(&self) -> &LogTail {
+        &self.log_tail
+    }
+
+    pub fn set_log_tail(&mut self, log_tail: LogTail) {
+        self.log_tail = log_tail;
+    }
+
+    /// Scores held back for review, oldest first
+    pub fn held_scores(&self) -> &[HeldScore] {
+        &self.held_scores
+    }
+
+    pub fn daily(&self) -> &DailyBoard {
+        &self.daily
+    }
+
+    pub fn match_history(&self) -> &MatchHistory {
+        &self.match_history
+    }
+
+    pub fn set_match_history(&mut self, match_history: MatchHistory) {
+        self.match_history = match_history;
+    }
+
+    pub fn match_history_mut(&mut self) -> &mut MatchHistory {
+        &mut self.match_history
+    }
+
+    pub fn runs(&self) -> &RunLog {
+        &self.runs
+    }
+
+    /// Every connection's shared snapshot fragments, as of the last tick
+    pub fn snapshot_cache(&self) -> &SnapshotCache {
+        &self.snapshot_cache
+    }
+
+    /// The world as it stands, to be brought back after a restart
+    pub fn capture_world(&self) -> WorldSnapshot {
+        WorldSnapshot {
+            saved_at: chrono::Utc::now(),
+            config: self.config.clone(),
+            players: self.players.values().cloned().collect(),
+            enemies: self.enemies.values().cloned().collect(),
+            projectiles: self.projectiles.values().cloned().collect(),
+            pickups: self.pickups.values().cloned().collect(),
+            landmarks: self.landmarks.values().cloned().collect(),
+            scores: self.scores.clone(),
+            daily: Some(self.daily.clone()),
+            held_scores: self.held_scores.clone(),
+            runs: self.runs.clone(),
+            game_time: self.game_time,
+            tick: self.tick,
+            last_spawn_time: self.last_spawn_time,
+            closed_rings: self.closed_rings,
+            next_zone_shrink: self.next_zone_shrink,
+            match_start_time: self.match_start_time,
+            match_seed: self.match_seed,
+            wave_number: self.wave_number,
+            next_wave_time: self.next_wave_time,
+            core_health: self.core_health,
+        }
+    }
+
+    /// Load a saved world into this freshly created state
+    pub fn restore_world(&mut self, snapshot: WorldSnapshot) {
+        self.enemies = snapshot
+            .enemies
+            .into_iter()
+            .map(|mut e| {
+                // Server-only fields aren't saved; leash from where it stands
+                e.home = e.position;
+                e
+            })
+            .collect();
+        self.landmarks = snapshot.landmarks.into_iter().map(|l| (l.id, l)).collect();
+        self.scores = snapshot.scores;
+        // An earlier day's board is replaced when the daily match rolls over
+        if let Some(daily) = snapshot.daily {
+            self.daily = daily;
+        }
+        self.held_scores = snapshot.held_scores;
+        self.runs.restore(snapshot.runs);
+        self.game_time = snapshot.game_time;
+        self.tick = snapshot.tick;
+        self.last_spawn_time = snapshot.last_spawn_time;
+        self.closed_rings = snapshot.closed_rings;
+        self.next_zone_shrink = snapshot.next_zone_shrink;
+        self.match_start_time = snapshot.match_start_time;
+        self.match_seed = snapshot.match_seed;
+        self.wave_number = snapshot.wave_number;
+        self.next_wave_time = snapshot.next_wave_time;
+        self.core_health = snapshot.core_health;
+
+        // Pooled IDs are handed out afresh, so nothing new takes a saved one
+        for projectile in snapshot.projectiles {
+            self.insert_projectile(projectile);
+        }
+        for pickup in snapshot.pickups {
+            self.insert_pickup(pickup);
+        }
+    }
+
+    /// Call `hook` on each registered plugin. They're taken out of the
+    /// state for the call so each can be handed all of it.
+    fn run_plugins(&mut self, mut hook: impl FnMut(&mut dyn GamePlugin, &mut GameState)) {
+        if self.plugins.is_empty() {
+            return;
+        }
+        let mut plugins = std::mem::take(&mut self.plugins);
+        for plugin in plugins.iter_mut() {
+            hook(plugin.as_mut(), self);
+        }
+        self.plugins.restore(plugins);
+    }
+
+    /// Plugins' once-a-tick hook
+    pub fn update_plugins(&mut self, delta_time: f32) {
+        self.run_plugins(|plugin, game| plugin.on_tick(game, delta_time));
+    }
+
+    /// Run the game's subscribers on every event published so far,
+    /// including those they publish in turn, then pass each on to
+    /// external subscribers. `game_loop::step` calls this after every
+    /// stage.
+    pub fn dispatch_events(&mut self) {
+        while let Some(event) = self.game_events.next() {
+            match &event {
+                GameEvent::PlayerJoined { player_id } => {
+                    let player_id = *player_id;
+                    self.run_plugins(|plugin, game| plugin.on_player_join(game, player_id));
+                }
+                GameEvent::EnemyKilled { enemy, killer, .. } => {
+                    self.credit_kill(enemy, *killer);
+                    self.run_plugins(|plugin, game| plugin.on_enemy_killed(game, enemy, *killer));
+                }
+                GameEvent::PlayerLevelUp { player_id, level } => {
+                    tracing::info!("Player {} leveled up to {}", player_id, level);
+                    self.queue_level_up(*player_id);
+                }
+                GameEvent::PlayerDied { player_id } => self.send_death_summary(*player_id),
+                GameEvent::BossSpawned { enemy } => {
+                    let name = enemy.enemy_type.name();
+                    tracing::info!("{} spawned in ring {}", name, enemy.spawn_ring);
+                }
+                GameEvent::Announcement { text } => {
+                    let text = text.clone();
+                    self.broadcast(ServerMessage::Announcement { text });
+                }
+                GameEvent::ScoreboardChanged { changes } => {
+                    let scores = self.standings().to_vec();
+                    let changes = changes.clone();
+                    self.broadcast(ServerMessage::Scoreboard { scores, changes });
+                }
+            }
+            self.run_plugins(|plugin, game| plugin.on_event(game, &event));
+            self.game_events.forward(event);
+        }
+    }
+
+    /// Add a new player to the game
+    pub fn add_player(&mut self, player_id: Uuid) -> Player {
+        let player = self.spawn_player(player_id);
+        self.players.insert(player_id, player.clone());
+        let activity = Activity {
+            last_input: self.game_time,
+            warned: false,
+            last_hit: None,
+        };
+        self.activity.insert(player_id, activity);
+        self.input_rates.insert(player_id, InputRate::default());
+        self.refresh_snapshot_players();
+        tracing::info!("Player {} joined the game", player_id);
+
+        if self.config.mode.closes_zone() {
+            let update = self.zone_update();
+            self.send_to(player_id, update);
+        }
+        let landmarks = self.landmarks_message();
+        self.send_to(player_id, landmarks);
+        let listings = self.config.shop_inventory.clone();
+        self.send_to(player_id, ServerMessage::Shop { listings });
+        if let Some(port) = self.config.udp_port {
+            let token = format!("{:016x}", udp::session_token(player_id));
+            self.send_to(player_id, ServerMessage::UdpOffer { port, token });
+        }
+
+        // Joins come from connections, outside the game loop's stages
+        let joined = GameEvent::PlayerJoined { player_id };
+        self.game_events.publish(joined);
+        self.dispatch_events();
+        self.players.get(&player_id).cloned().unwrap_or(player)
+    }
+
+    /// A fresh player with config-driven base stats
+    fn spawn_player(&self, player_id: Uuid) -> Player {
+        let mut player = Player::new(player_id);
+        player.crit_chance = self.config.base_crit_chance;
+        player.crit_multiplier = self.config.base_crit_multiplier;
+        player.rerolls_left = self.config.base_rerolls;
+        player.banishes_left = self.config.base_banishes;
+        player
+    }
+
+    /// Add a player if there's a free slot, otherwise queue them (or turn
+    /// them away once the queue is full too)
+    pub fn request_join(&mut self, player_id: Uuid) -> JoinOutcome {
+        if self.has_free_slot() && self.join_queue.is_empty() {
+            self.add_player(player_id);
+            return JoinOutcome::Joined;
+        }
+        if self.join_queue.len() >= self.config.max_queue_length {
+            let reason = "Server is full".to_string();
+            self.turn_away(player_id, ErrorCode::RoomFull, reason);
+            return JoinOutcome::Rejected;
+        }
+        self.join_queue.push_back(player_id);
+        self.send_queue_positions();
+        JoinOutcome::Queued(self.join_queue.len())
+    }
+
+    /// Refuse a join with an error saying why, then close the connection
+    pub fn turn_away(&mut self, player_id: Uuid, code: ErrorCode, reason: String) {
+        let message = reason.clone();
+        self.send_to(player_id, ServerMessage::Error { code, message });
+        self.send_to(player_id, ServerMessage::Kicked { reason });
+    }
+
+    fn has_free_slot(&self) -> bool {
+        self.config.max_players == 0 || self.players.len() < self.config.max_players
+    }
+
+    /// Drop a queued player; false if they weren't queued
+    pub fn leave_queue(&mut self, player_id: Uuid) -> bool {
+        let Some(index) = self.join_queue.iter().position(|id| *id == player_id) else {
+            return false;
+        };
+        self.join_queue.remove(index);
+        self.player_ips.remove(&player_id);
+        self.locales.remove(&player_id);
+        self.send_queue_positions();
+        true
+    }
+
+    /// Let queued players in, oldest first, while there's room
+    fn admit_queued(&mut self) {
+        let mut admitted = false;
+        while self.has_free_slot() {
+            let Some(player_id) = self.join_queue.pop_front() else {
+                break;
+            };
+            self.add_player(player_id);
+            admitted = true;
+        }
+        if admitted {
+            self.send_queue_positions();
+        }
+    }
+
+    fn send_queue_positions(&mut self) {
+        let queue_length = self.join_queue.len();
+        let queued: Vec<Uuid> = self.join_queue.iter().copied().collect();
+        for (index, player_id) in queued.into_iter().enumerate() {
+            let position = index + 1;
+            let update = ServerMessage::QueuePosition {
+                position,
+                queue_length,
+            };
+            self.send_to(player_id, update);
+        }
+    }
+
+    /// The player did something; restarts their AFK timer
+    pub fn record_input(&mut self, player_id: Uuid) {
+        if let Some(activity) = self.activity.get_mut(&player_id) {
+            activity.last_input = self.game_time;
+            activity.warned = false;
+        }
+    }
+
+    /// Warn players idle for `afk_warning_after` seconds, and remove those
+    /// idle for `afk_timeout` to free their slot. Either at 0 is disabled.
+    pub fn update_afk(&mut self) {
+        let warning_after = self.config.afk_warning_after as f64;
+        let timeout = self.config.afk_timeout as f64;
+        let mut warnings = Vec::new();
+        let mut expired = Vec::new();
+        for (player_id, activity) in &mut self.activity {
+            let idle = self.game_time - activity.last_input;
+            if timeout > 0.0 && idle >= timeout {
+                expired.push(*player_id);
+            } else if warning_after > 0.0 && idle >= warning_after && !activity.warned {
+                activity.warned = true;
+                warnings.push(*player_id);
+            }
+        }
+
+        for player_id in warnings {
+            let seconds_left = if timeout > 0.0 {
+                Some(self.config.afk_timeout - self.config.afk_warning_after)
+            } else {
+                None
+            };
+            self.send_to(player_id, ServerMessage::AfkWarning { seconds_left });
+        }
+        for player_id in expired {
+            self.kick(player_id, "Removed for inactivity".to_string());
+        }
+    }
+
+    /// Remove a player (or queued joiner) and tell their connection why; it
+    /// closes once the `Kicked` message is delivered
+    pub fn kick(&mut self, player_id: Uuid, reason: String) -> Option<Player> {
+        let queued = self.join_queue.contains(&player_id);
+        if !queued && !self.players.contains_key(&player_id) {
+            return None;
+        }
+        tracing::info!("Kicking player {}: {}", player_id, reason);
+        self.send_to(player_id, ServerMessage::Kicked { reason });
+        if queued {
+            self.leave_queue(player_id);
+            return None;
+        }
+        self.remove_player(player_id)
+    }
+
+    /// Show an operator's message to everyone connected
+    pub fn announce(&mut self, text: String) {
+        tracing::info!("Announcement: {}", text);
+        self.game_events.publish(GameEvent::Announcement { text });
+    }
+
+    /// Ban `target` and kick every player it covers; returns the kicked IDs
+    pub fn ban(&mut self, ban: Ban) -> Vec<Uuid> {
+        let covered: Vec<Uuid> = match ban.target {
+            BanTarget::Player(id) => vec![id],
+            BanTarget::Ip(ip) => self
+                .player_ips
+                .iter()
+                .filter(|(_, player_ip)| **player_ip == ip)
+                .map(|(id, _)| *id)
+                .collect(),
+        };
+        let reason = match &ban.reason {
+            Some(reason) => format!("Banned: {}", reason),
+            None => "Banned".to_string(),
+        };
+        self.bans.insert(ban);
+
+        covered
+            .into_iter()
+            .filter(|id| self.kick(*id, reason.clone()).is_some())
+            .collect()
+    }
+
+    /// Remove a player (death or disconnect)
+    pub fn remove_player(&mut self, player_id: Uuid) -> Option<Player> {
+        let player = self.players.remove(&player_id)?;
+        self.udp_peers.remove(&player_id);
+        self.player_ips.remove(&player_id);
+        self.locales.remove(&player_id);
+        self.activity.remove(&player_id);
+        self.input_queues.remove(&player_id);
+
+        self.record_score(&player);
+        self.record_departure(&player);
+        self.input_rates.remove(&player_id);
+        self.signal_allowances.remove(&player_id);
+        self.leave_party(player_id);
+        self.admit_queued();
+        self.refresh_snapshot_players();
+
+        Some(player)
+    }
+
+    /// Start a new party led by `player_id`, leaving any current one
+    pub fn create_party(&mut self, player_id: Uuid) -> Result<Uuid, String> {
+        if !self.players.contains_key(&player_id) {
+            return Err("Player not found".to_string());
+        }
+
+        self.leave_party(player_id);
+        let party = Party::new(player_id);
+        let party_id = party.id;
+        self.parties.insert(party_id, party);
+        if let Some(player) = self.players.get_mut(&player_id) {
+            player.party_id = Some(party_id);
+        }
+
+        tracing::info!("Player {} created party {}", player_id, party_id);
+        self.notify_party(party_id);
+        Ok(party_id)
+    }
+
+    /// Join an existing party, leaving any current one
+    pub fn join_party(&mut self, player_id: Uuid, party_id: Uuid) -> Result<(), String> {
+        if !self.players.contains_key(&player_id) {
+            return Err("Player not found".to_string());
+        }
+
+        let party = self
+            .parties
+            .get(&party_id)
+            .ok_or_else(|| "Party not found".to_string())?;
+        if party.members.contains(&player_id) {
+            return Ok(());
+        }
+        if party.members.len() >= self.config.party_max_size {
+            return Err("Party is full".to_string());
+        }
+
+        self.leave_party(player_id);
+        if let Some(party) = self.parties.get_mut(&party_id) {
+            party.members.push(player_id);
+        }
+        if let Some(player) = self.players.get_mut(&player_id) {
+            player.party_id = Some(party_id);
+        }
+
+        tracing::info!("Player {} joined party {}", player_id, party_id);
+        self.notify_party(party_id);
+        Ok(())
+    }
+
+    /// Drop a player from whatever party they are in; empty parties disband
+    fn leave_party(&mut self, player_id: Uuid) {
+        let Some(party_id) = self
+            .parties
+            .values()
+            .find(|p| p.members.contains(&player_id))
+            .map(|p| p.id)
+        else {
+            return;
+        };
+
+        if let Some(player) = self.players.get_mut(&player_id) {
+            player.party_id = None;
+        }
+
+        let Some(party) = self.parties.get_mut(&party_id) else {
+            return;
+        };
+        party.remove_member(player_id);
+        if party.is_empty() {
+            self.parties.remove(&party_id);
+        } else {
+            self.notify_party(party_id);
+        }
+    }
+
+    /// Send the current member list to everyone in the party
+    fn notify_party(&mut self, party_id: Uuid) {
+        let Some(members) = self.parties.get(&party_id).map(|p| p.members.clone()) else {
+            return;
+        };
+        for member in &members {
+            self.send_to(
+                *member,
+                ServerMessage::PartyUpdate {
+                    party_id,
+                    members: members.clone(),
+                },
+            );
+        }
+    }
+
+    /// Publish a death for each player who died this tick, once per run
+    pub fn update_deaths(&mut self) {
+        for player in self.players.values_mut() {
+            if player.is_alive() || player.death_reported {
+                continue;
+            }
+            player.death_reported = true;
+            let died = GameEvent::PlayerDied {
+                player_id: player.id,
+            };
+            self.game_events.publish(died);
+        }
+    }
+
+    /// Tell everyone how a player's run went
+    fn send_death_summary(&mut self, player_id: Uuid) {
+        let Some(player) = self.players.get(&player_id) else {
+            return;
+        };
+        let score_recorded = self
+            .config
+            .mode
+            .qualifies_for_scoreboard(player, &self.config);
+        let run = RunSummary::new(player, self.config.mode.name(), score_recorded);
+        let summary = ServerMessage::PlayerDied {
+            player_id,
+            max_ring: run.max_ring_reached,
+            survival_time: run.survival_time,
+            enemies_defeated: run.enemies_defeated,
+            score_recorded,
+            stats: run.stats.clone(),
+            run_id: Some(run.id),
+        };
+        self.runs.push(run);
+        self.broadcast(summary);
+    }
+
+    /// Record a combined scoreboard entry for each party whose members all died
+    pub fn update_parties(&mut self) {
+        let mut wiped = Vec::new();
+        for party in self.parties.values().filter(|p| !p.wiped) {
+            let members: Vec<&Player> = party
+                .members
+                .iter()
+                .filter_map(|id| self.players.get(id))
+                .collect();
+            if members.is_empty() || members.iter().any(|p| p.is_alive()) {
+                continue;
+            }
+
+            let qualifies = members
+                .iter()
+                .any(|p| self.config.mode.qualifies_for_scoreboard(p, &self.config));
+            let now = chrono::Utc::now();
+            let entry = ScoreEntry {
+                player_id: party.id,
+                max_ring_reached: members
+                    .iter()
+                    .map(|p| p.max_ring_reached)
+                    .max()
+                    .unwrap_or(0),
+                survival_time_seconds: members
+                    .iter()
+                    .map(|p| (now - p.spawn_time).num_seconds() as f32)
+                    .fold(0.0, f32::max),
+                enemies_defeated: members.iter().map(|p| p.enemies_defeated).sum(),
+                players_defeated: members.iter().map(|p| p.players_defeated).sum(),
+                party_members: party.members.clone(),
+                timestamp: now,
+            };
+            let records = members.iter().map(|p| self.integrity_record(p)).collect();
+            wiped.push((party.id, qualifies.then_some((entry, records))));
+        }
+
+        for (party_id, entry) in wiped {
+            if let Some(party) = self.parties.get_mut(&party_id) {
+                party.wiped = true;
+            }
+            tracing::info!("Party {} wiped", party_id);
+            if let Some((entry, records)) = entry {
+                self.submit_score(entry, records);
+            }
+        }
+    }
+
+    /// Add a finished run to the scoreboard if the game mode says it counts
+    fn record_score(&mut self, player: &Player) {
+        if !self
+            .config
+            .mode
+            .qualifies_for_scoreboard(player, &self.config)
+        {
+            return;
+        }
+
+        let survival_time = (chrono::Utc::now() - player.spawn_time).num_seconds() as f32;
+        let score = ScoreEntry {
+            player_id: player.id,
+            max_ring_reached: player.max_ring_reached,
+            survival_time_seconds: survival_time,
+            enemies_defeated: player.enemies_defeated,
+            players_defeated: player.players_defeated,
+            party_members: Vec::new(),
+            timestamp: chrono::Utc::now(),
+        };
+
+        let record = self.integrity_record(player);
+        self.submit_score(score, vec![record]);
+        tracing::info!(
+            "Player {} qualified for scoreboard: Ring {}, Time: {:.1}s, Kills: {}",
+            player.id,
+            player.max_ring_reached,
+            survival_time,
+            player.enemies_defeated
+        );
+    }
+
+    /// How the player's run so far measures up to `integrity_limits`
+    fn integrity_record(&self, player: &Player) -> IntegrityRecord {
+        let run_time = chrono::Utc::now() - player.spawn_time;
+        let run_seconds = run_time.num_milliseconds() as f32 / 1000.0;
+        let inputs = self.input_rates.get(&player.id);
+        IntegrityRecord::check(player, inputs, run_seconds, &self.config.integrity_limits)
+    }
+
+    /// A player's line in a match record, with their run's integrity record
+    fn participant(&self, player: &Player, left_early: bool) -> Participant {
+        Participant {
+            integrity: Some(self.integrity_record(player)),
+            ..Participant::from_player(player, left_early)
+        }
+    }
+
+    /// Add a score to the scoreboard, or hold it for review if a run in it
+    /// broke an integrity limit
+    fn submit_score(&mut self, score: ScoreEntry, records: Vec<IntegrityRecord>) {
+        if !records.iter().any(IntegrityRecord::is_suspicious) {
+            self.add_score(score);
+            return;
+        }
+
+        let flags: Vec<&str> = records
+            .iter()
+            .flat_map(|r| r.flags.iter().map(String::as_str))
+            .collect();
+        tracing::warn!(
+            "Holding score of {} for review: {}",
+            score.player_id,
+            flags.join("; ")
+        );
+        if self.held_scores.len() >= MAX_HELD_SCORES {
+            self.held_scores.remove(0);
+        }
+        self.held_scores.push(HeldScore {
+            id: Uuid::new_v4(),
+            score,
+            records,
+            held_at: chrono::Utc::now(),
+        });
+    }
+
+    /// Settle a held score: approved goes on the scoreboard after all,
+    /// rejected is dropped. None if there's no held score `id`.
+    pub fn review_score(&mut self, id: Uuid, approve: bool) -> Option<HeldScore> {
+        let index = self.held_scores.iter().position(|h| h.id == id)?;
+        let held = self.held_scores.remove(index);
+        if approve {
+            self.add_score(held.score.clone());
+        }
+        Some(held)
+    }
+
+    /// A player left. In Endless that ends their run, which gets a record
+    /// of its own; otherwise they're listed when the match ends.
+    fn record_departure(&mut self, player: &Player) {
+        if self.config.mode.has_matches() {
+            self.match_departed.push(self.participant(player, true));
+            return;
+        }
+
+        let ended_at = chrono::Utc::now();
+        let outcome = if player.is_alive() { "Left" } else { "Died" };
+        self.match_history.push(MatchRecord {
+            id: Uuid::new_v4(),
+            mode: self.config.mode.name().to_string(),
+            seed: self.match_seed,
+            started_at: player.spawn_time,
+            ended_at,
+            duration_seconds: (ended_at - player.spawn_time).num_milliseconds() as f32 / 1000.0,
+            outcome: outcome.to_string(),
+            winners: Vec::new(),
+            bosses_killed: player.bosses_defeated,
+            participants: vec![self.participant(player, false)],
+        });
+    }
+
+    /// Add the finished match to the history, unless nobody played in it
+    fn record_match(&mut self, outcome: &MatchOutcome) {
+        let mut participants = std::mem::take(&mut self.match_departed);
+        participants.extend(self.players.values().map(|p| self.participant(p, false)));
+        if participants.is_empty() {
+            return;
+        }
+
+        self.match_history.push(MatchRecord {
+            id: self.match_id,
+            mode: self.config.mode.name().to_string(),
+            seed: self.match_seed,
+            started_at: self.match_started_at,
+            ended_at: chrono::Utc::now(),
+            duration_seconds: (self.game_time - self.match_start_time) as f32,
+            outcome: outcome.reason.clone(),
+            winners: outcome.winners.clone(),
+            bosses_killed: participants.iter().map(|p| p.bosses_defeated).sum(),
+            participants,
+        });
+    }
+
+    /// Check the mode's end condition and roll over to a fresh match if met
+    pub fn update_match(&mut self) {
+        let Some(outcome) = self.check_match_end() else {
+            return;
+        };
+
+        tracing::info!(
+            "{} match ended: {} ({} winners)",
+            self.config.mode.name(),
+            outcome.reason,
+            outcome.winners.len()
+        );
+
+        let finished: Vec<Player> = self.players.values().cloned().collect();
+        for player in &finished {
+            self.record_score(player);
+        }
+        self.record_match(&outcome);
+
+        self.broadcast(ServerMessage::MatchEnded {
+            reason: outcome.reason,
+            winners: outcome.winners,
+        });
+        self.restart_match();
+    }
+
+    fn check_match_end(&self) -> Option<MatchOutcome> {
+        let alive: Vec<Uuid> = self
+            .players
+            .values()
+            .filter(|p| p.is_alive())
+            .map(|p| p.id)
+            .collect();
+
+        match self.config.mode {
+            GameMode::Endless => None,
+            GameMode::Timed => {
+                let elapsed = self.game_time - self.match_start_time;
+                (elapsed >= self.config.timed_duration as f64).then(|| MatchOutcome {
+                    reason: "Time's up".to_string(),
+                    winners: alive,
+                })
+            }
+            GameMode::WaveDefense => (self.core_health <= 0.0).then(|| MatchOutcome {
+                reason: "The core has fallen".to_string(),
+                winners: Vec::new(),
+            }),
+            // Needs at least two contestants, otherwise a solo player wins instantly
+            GameMode::BattleRoyale => {
+                (self.players.len() >= 2 && alive.len() <= 1).then(|| MatchOutcome {
+                    reason: "Last player standing".to_string(),
+                    winners: alive,
+                })
+            }
+            // Also catches a world resumed from another mode's snapshot
+            GameMode::Daily => {
+                let stale = daily::today() != self.daily.date || self.match_seed != self.daily.seed;
+                stale.then(|| MatchOutcome {
+                    reason: "A new daily run begins".to_string(),
+                    winners: Vec::new(),
+                })
+            }
+        }
+    }
+
+    /// Clear the world and respawn everyone for a new match
+    fn restart_match(&mut self) {
+        self.enemies.clear();
+        self.projectiles.clear();
+        self.pickups.clear();
+        self.summons.clear();
+        // One message for the lot, and nothing queued about what's gone
+        self.entity_events.clear();
+        self.broadcast(ServerMessage::WorldCleared);
+        self.pending_level_ups.clear();
+        self.input_queues.clear();
+        self.effects = Effects::default();
+        let ids: Vec<Uuid> = self.players.keys().copied().collect();
+        for id in ids {
+            let mut player = self.spawn_player(id);
+            player.party_id = self.players.get(&id).and_then(|p| p.party_id);
+            self.players.insert(id, player);
+        }
+        self.update_effects();
+
+        self.match_start_time = self.game_time;
+        self.match_id = Uuid::new_v4();
+        self.match_started_at = chrono::Utc::now();
+        self.match_departed.clear();
+        self.last_spawn_time = self.game_time;
+        self.closed_rings = 0;
+        self.next_zone_shrink = self.game_time + self.config.zone_shrink_interval as f64;
+        self.wave_number = 0;
+        self.next_wave_time = self.game_time;
+        self.core_health = self.config.core_health;
+        self.match_seed = match self.config.mode {
+            GameMode::Daily => {
+                let today = daily::today();
+                if self.daily.date != today {
+                    self.daily = DailyBoard::new(today);
+                }
+                self.daily.seed
+            }
+            _ => self.config.seed.unwrap_or_else(rand::random),
+        };
+        self.generate_landmarks();
+        let landmarks = self.landmarks_message();
+        self.broadcast(landmarks);
+        for party in self.parties.values_mut() {
+            party.wiped = false;
+        }
+
+        if self.config.mode.closes_zone() {
+            let update = self.zone_update();
+            self.broadcast(update);
+        }
+    }
+
+    /// Place an obelisk, a chest and a shrine in every ring, laid out from the match seed
+    fn generate_landmarks(&mut self) {
+        let mut rng = StdRng::seed_from_u64(self.match_seed);
+        self.landmarks.clear();
+
+        // The safe zone's waypoint, which everyone starts attuned to
+        let id = Uuid::from_u128(rng.gen());
+        self.landmarks.insert(
+            id,
+            Landmark::new(id, LandmarkType::Waypoint, Position::new(0.0, 0.0), 0),
+        );
+
+        let types = [
+            LandmarkType::Obelisk,
+            LandmarkType::Chest,
+            LandmarkType::Shrine,
+            LandmarkType::Waypoint,
+        ];
+        for ring in 1..=self.config.max_rings {
+            for landmark_type in types {
+                // Ring 1 starts at the ring boundary so nothing sits in the safe zone
+                let inner = ring as f32 * self.config.ring_radius;
+                let radius = rng.gen_range(inner..inner + self.config.ring_radius);
+                let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                let position = Position::new(radius * angle.cos(), radius * angle.sin());
+                let id = Uuid::from_u128(rng.gen());
+                self.landmarks
+                    .insert(id, Landmark::new(id, landmark_type, position, ring));
+            }
+        }
+    }
+
+    fn landmarks_message(&self) -> ServerMessage {
+        ServerMessage::Landmarks {
+            landmarks: self.landmarks.values().cloned().collect(),
+        }
+    }
+
+    /// Use a chest, shrine or waypoint the player is standing next to
+    pub fn interact(&mut self, player_id: Uuid, landmark_id: Uuid) -> Result<(), String> {
+        let player = self
+            .players
+            .get(&player_id)
+            .filter(|p| p.is_alive())
+            .ok_or_else(|| "Player not found".to_string())?;
+        let landmark = self
+            .landmarks
+            .get(&landmark_id)
+            .ok_or_else(|| "Landmark not found".to_string())?;
+        if landmark.position.distance_to(&player.position) > self.config.interact_range {
+            return Err("Too far away".to_string());
+        }
+        if !landmark.is_usable(self.game_time) {
+            return Err(format!(
+                "{} can't be used right now",
+                landmark.landmark_type.name()
+            ));
+        }
+
+        let mut rng = self.run_rng(&[
+            LANDMARK_RNG,
+            self.tick,
+            landmark_id.as_u64_pair().1,
+            player_id.as_u64_pair().1,
+        ]);
+        let reward = match landmark.landmark_type {
+            LandmarkType::Chest => {
+                if rng.gen_bool(self.config.chest_rare_chance as f64) {
+                    // Rare item: a free upgrade, applied on the spot
+                    let luck = player.upgrades.luck_level;
+                    let table = &self.config.upgrade_table;
+                    let Some(choice) = table.roll(&player.banished, luck, 1, &mut rng).pop() else {
+                        return Err("Nothing left to find".to_string());
+                    };
+                    self.apply_choice(player_id, choice)?;
+                    let rarity = choice.rarity.name();
+                    format!("found a relic ({} {})", rarity, choice.upgrade.name())
+                } else {
+                    self.queue_level_up(player_id);
+                    "found an upgrade".to_string()
+                }
+            }
+            LandmarkType::Shrine => {
+                let (now, duration) = (self.game_time, self.config.shrine_buff_duration);
+                let (blessing, reward) = match rng.gen_range(0..4) {
+                    0 => (
+                        TimedEffect::buff(player_id, EffectKind::DamageBoost, duration, now),
+                        "was blessed with strength",
+                    ),
+                    1 => (
+                        TimedEffect::buff(player_id, EffectKind::SpeedBoost, duration, now),
+                        "was blessed with swiftness",
+                    ),
+                    2 => {
+                        let ward = EffectKind::Shield(self.config.shrine_shield);
+                        let effect = TimedEffect::buff(player_id, ward, duration, now);
+                        (effect, "was blessed with warding")
+                    }
+                    _ => {
+                        let aura = EffectKind::HealingAura {
+                            radius: self.config.shrine_aura_radius,
+                            heal: self.config.shrine_aura_heal,
+                        };
+                        let effect = TimedEffect::periodic(player_id, aura, 1.0, duration, now);
+                        (effect, "was blessed with renewal")
+                    }
+                };
+                self.add_effect(blessing);
+                reward.to_string()
+            }
+            LandmarkType::Waypoint => {
+                if player.is_attuned(landmark) {
+                    return Err("Already attuned to this waypoint".to_string());
+                }
+                // Attunement is per player, so the waypoint itself is unchanged
+                if let Some(player) = self.players.get_mut(&player_id) {
+                    player.waypoints.push(landmark_id);
+                }
+                "attuned to a waypoint".to_string()
+            }
+            LandmarkType::Obelisk => unreachable!("obelisks are never usable"),
+        };
+
+        if let Some(landmark) = self
+            .landmarks
+            .get_mut(&landmark_id)
+            .filter(|l| l.landmark_type != LandmarkType::Waypoint)
+        {
+            landmark.opened = true;
+            landmark.ready_at = self.game_time + self.config.shrine_cooldown as f64;
+        }
+
+        tracing::info!("Player {} {} at {}", player_id, reward, landmark_id);
+        self.broadcast(ServerMessage::LandmarkUsed {
+            player_id,
+            landmark_id,
+            reward,
+        });
+        let landmarks = self.landmarks_message();
+        self.broadcast(landmarks);
+        Ok(())
+    }
+
+    /// Start channeling a teleport from the attuned waypoint the player is
+    /// standing at to another one
+    pub fn teleport_to(&mut self, player_id: Uuid, waypoint: Uuid) -> Result<(), String> {
+        let player = self
+            .players
+            .get(&player_id)
+            .filter(|p| p.is_alive())
+            .ok_or_else(|| "Player not found".to_string())?;
+        let destination = self
+            .landmarks
+            .get(&waypoint)
+            .filter(|l| player.is_attuned(l))
+            .ok_or_else(|| "Not attuned to that waypoint".to_string())?;
+        let here = self.landmarks.values().find(|l| {
+            player.is_attuned(l)
+                && l.position.distance_to(&player.position) <= self.config.interact_range
+        });
+        match here {
+            None => return Err("Stand at an attuned waypoint to teleport".to_string()),
+            Some(here) if here.id == destination.id => {
+                return Err("Already at that waypoint".to_string());
+            }
+            Some(_) => {}
+        }
+        let lockout = self.config.teleport_combat_lockout as f64;
+        if player
+            .last_hurt_at
+            .is_some_and(|hurt_at| self.game_time < hurt_at + lockout)
+        {
+            return Err("Can't teleport while in combat".to_string());
+        }
+
+        let arrives_at = self.game_time + self.config.teleport_channel_time as f64;
+        if let Some(player) = self.players.get_mut(&player_id) {
+            player.teleport = Some(Teleport {
+                waypoint,
+                arrives_at,
+            });
+        }
+        Ok(())
+    }
+
+    /// Drop a player's teleport channel, telling them if there was one
+    fn interrupt_teleport(&mut self, player_id: Uuid) {
+        let channeling = self
+            .players
+            .get_mut(&player_id)
+            .and_then(|p| p.teleport.take())
+            .is_some();
+        if channeling {
+            self.send_to(player_id, ServerMessage::TeleportInterrupted { player_id });
+        }
+    }
+
+    /// Note who was hit this tick for the combat lockout, cancel their
+    /// channels, and move players whose channel has finished
+    pub fn update_teleports(&mut self) {
+        let hurt: Vec<Uuid> = self
+            .combat_events
+            .iter()
+            .filter(|e| e.amount > 0.0)
+            .map(|e| e.target)
+            .collect();
+        for player_id in hurt {
+            if let Some(player) = self.players.get_mut(&player_id) {
+                player.last_hurt_at = Some(self.game_time);
+                self.interrupt_teleport(player_id);
+            }
+        }
+
+        let mut arrived = Vec::new();
+        for player in self.players.values_mut() {
+            if !player.is_alive() {
+                player.teleport = None;
+                continue;
+            }
+            let Some(teleport) = player.teleport else {
+                continue;
+            };
+            if self.game_time < teleport.arrives_at {
+                continue;
+            }
+            player.teleport = None;
+            // The waypoint is gone if the match restarted mid-channel
+            if let Some(waypoint) = self.landmarks.get(&teleport.waypoint) {
+                player.position = waypoint.position;
+                player.last_position = None; // a jump, not movement
+                arrived.push((player.id, waypoint.id));
+            }
+        }
+        for (player_id, waypoint) in arrived {
+            tracing::info!("Player {} teleported to {}", player_id, waypoint);
+            self.broadcast(ServerMessage::Teleported {
+                player_id,
+                waypoint,
+            });
+        }
+    }
+
+    /// Broadcast where everyone, every boss and every landmark is, on a
+    /// slower timer than snapshots
+    pub fn update_minimap(&mut self) {
+        if self.game_time < self.next_minimap_time {
+            return;
+        }
+        self.next_minimap_time = self.game_time + self.config.minimap_interval as f64;
+
+        let players = self
+            .players
+            .values()
+            .filter(|p| p.is_alive())
+            .map(|p| MinimapMarker::new(p.id, p.position))
+            .collect();
+        let bosses = self
+            .enemies
+            .values()
+            .filter(|e| self.config.enemies.is_boss(e.enemy_type))
+            .map(|e| MinimapMarker::new(e.id, e.position))
+            .collect();
+        let landmarks = self
+            .landmarks
+            .values()
+            .map(|l| MinimapMarker::new(l.id, l.position))
+            .collect();
+        self.broadcast(ServerMessage::MinimapUpdate {
+            map_radius: self.config.map_size,
+            players,
+            bosses,
+            landmarks,
+        });
+    }
+
+    /// Spend gold at the merchant, who only trades inside the safe zone
+    pub fn buy_item(&mut self, player_id: Uuid, item: ShopItem) -> Result<(), String> {
+        let player = self
+            .players
+            .get(&player_id)
+            .filter(|p| p.is_alive())
+            .ok_or_else(|| "Player not found".to_string())?;
+        if !self.is_safe(&player.position) {
+            return Err("The merchant only trades in the safe zone".to_string());
+        }
+        let price = self
+            .config
+            .shop_inventory
+            .iter()
+            .find(|listing| listing.item == item)
+            .map(|listing| listing.price)
+            .ok_or_else(|| format!("{} is not for sale", item.name()))?;
+        if player.gold < price {
+            return Err(format!("{} costs {} gold", item.name(), price));
+        }
+        if item == ShopItem::Heal && player.health >= player.max_health {
+            return Err("Already at full health".to_string());
+        }
+
+        let player = self
+            .players
+            .get_mut(&player_id)
+            .ok_or_else(|| "Player not found".to_string())?;
+        player.gold -= price;
+        let boost = match item {
+            ShopItem::Heal => {
+                player.heal(self.config.shop_heal as f32);
+                None
+            }
+            ShopItem::Reroll => {
+                player.rerolls_left += 1;
+                None
+            }
+            ShopItem::DamageBoost => Some(EffectKind::DamageBoost),
+            ShopItem::SpeedBoost => Some(EffectKind::SpeedBoost),
+        };
+        let gold_left = player.gold;
+        if let Some(boost) = boost {
+            self.add_power_up(player_id, boost);
+        }
+
+        tracing::info!(
+            "Player {} bought {} for {} gold",
+            player_id,
+            item.name(),
+            price
+        );
+        self.send_to(
+            player_id,
+            ServerMessage::ItemBought {
+                player_id,
+                item,
+                gold_left,
+            },
+        );
+        Ok(())
+    }
+
+    /// Wear an item from the inventory; whatever was in its slot goes back
+    /// into the inventory
+    pub fn equip_item(&mut self, player_id: Uuid, item_id: Uuid) -> Result<(), String> {
+        let player = self
+            .players
+            .get_mut(&player_id)
+            .filter(|p| p.is_alive())
+            .ok_or_else(|| "Player not found".to_string())?;
+        let index = player
+            .inventory
+            .iter()
+            .position(|item| item.id == item_id)
+            .ok_or_else(|| "Item not in inventory".to_string())?;
+
+        let item = player.inventory.remove(index);
+        let slot = item.slot;
+        if let Some(replaced) = player.equip(item) {
+            player.inventory.push(replaced);
+        }
+        tracing::debug!("Player {} equipped {:?} {}", player_id, slot, item_id);
+        Ok(())
+    }
+
+    /// Drop a carried or worn item where the player stands, for anyone
+    /// else to pick up
+    pub fn drop_item(&mut self, player_id: Uuid, item_id: Uuid) -> Result<(), String> {
+        let player = self
+            .players
+            .get_mut(&player_id)
+            .filter(|p| p.is_alive())
+            .ok_or_else(|| "Player not found".to_string())?;
+
+        let carried = player.inventory.iter().position(|item| item.id == item_id);
+        let worn = player
+            .equipment
+            .iter()
+            .find(|item| item.id == item_id)
+            .map(|item| item.slot);
+        let item = match (carried, worn) {
+            (Some(index), _) => player.inventory.remove(index),
+            (None, Some(slot)) => player
+                .unequip(slot)
+                .ok_or_else(|| "Item not found".to_string())?,
+            (None, None) => return Err("Item not found".to_string()),
+        };
+
+        let mut pickup = Pickup::equipment(item, player.position, self.config.pickup_lifetime);
+        pickup.dropped_by = Some(player_id);
+        self.insert_pickup(pickup);
+        Ok(())
+    }
+
+    /// Update player movement
+    pub fn move_player(&mut self, player_id: Uuid, target: Position, delta_time: f32) {
+        if !target.is_finite() {
+            return;
+        }
+        if let Some(player) = self.players.get_mut(&player_id) {
+            let start = player.position;
+            player.position.move_towards(
+                &target,
+                player.effective_speed(self.game_time),
+                delta_time,
+            );
+            player.position.clamp_to_radius(self.config.map_size);
+            player.stats.distance_traveled += start.distance_to(&player.position);
+
+            // Update max ring reached
+            let current_ring = player.position.ring(self.config.ring_radius);
+            if current_ring > player.max_ring_reached {
+                player.max_ring_reached = current_ring;
+            }
+
+            if player.position != start {
+                self.interrupt_teleport(player_id);
+            }
+        }
+    }
+
+    /// Hold a Move or Dash until the next tick applies it
+    pub fn queue_input(&mut self, player_id: Uuid, seq: u32, kind: InputKind) {
+        if !self.players.contains_key(&player_id) {
+            return;
+        }
+        if let Some(rate) = self.input_rates.get_mut(&player_id) {
+            rate.record(self.game_time);
+        }
+        let capacity = self.config.max_queued_inputs;
+        let dropped = self
+            .input_queues
+            .entry(player_id)
+            .or_default()
+            .push(QueuedInput { seq, kind }, capacity);
+        if dropped {
+            let code = ErrorCode::RateLimited;
+            let message = "Inputs are arriving faster than the tick rate".to_string();
+            self.send_to(player_id, ServerMessage::Error { code, message });
+        }
+    }
+
+    /// Apply each player's queued inputs for this tick, noting the last one
+    /// so snapshots can acknowledge it
+    pub fn apply_inputs(&mut self, delta_time: f32) {
+        let ticks: Vec<(Uuid, Vec<QueuedInput>)> = self
+            .input_queues
+            .iter_mut()
+            .map(|(id, queue)| (*id, queue.take_tick()))
+            .collect();
+        self.input_queues.retain(|_, queue| !queue.is_empty());
+
+        for (player_id, inputs) in ticks {
+            for input in inputs {
+                match input.kind {
+                    InputKind::Move { target } => self.move_player(player_id, target, delta_time),
+                    InputKind::Dash { direction } => {
+                        if let Err(e) = self.dash(player_id, direction) {
+                            tracing::debug!("Rejected dash from player {}: {}", player_id, e);
+                        }
+                    }
+                }
+                if let Some(player) = self.players.get_mut(&player_id) {
+                    player.last_processed_input = input.seq;
+                }
+            }
+        }
+    }
+
+    /// Record how far the player's client can see
+    pub fn set_view_radius(&mut self, player_id: Uuid, radius: f32) {
+        if !radius.is_finite() {
+            return;
+        }
+        if let Some(player) = self.players.get_mut(&player_id) {
+            player.view_radius =
+                radius.clamp(self.config.min_view_radius, self.config.max_view_radius);
+        }
+    }
+
+    /// How far from the player their snapshots reach. Until the client
+    /// reports its view, assume the largest one allowed.
+    pub fn aoi_radius(&self, player: &Player) -> f32 {
+        let view = if player.view_radius > 0.0 {
+            player.view_radius
+        } else {
+            self.config.max_view_radius
+        };
+        view + self.config.aoi_margin
+    }
+
+    /// Start a dash if the player is alive and off cooldown
+    pub fn dash(&mut self, player_id: Uuid, direction: Vec2) -> Result<(), String> {
+        let length = direction.length();
+        if !length.is_finite() || length < 0.001 {
+            return Err("Invalid dash direction".to_string());
+        }
+
+        let player = self
+            .players
+            .get_mut(&player_id)
+            .filter(|p| p.is_alive())
+            .ok_or_else(|| "Player not found".to_string())?;
+        if self.game_time < player.dash_ready_at {
+            return Err("Dash is on cooldown".to_string());
+        }
+
+        player.dash_direction = direction.normalize();
+        player.dashing_until = self.game_time + self.config.dash_duration as f64;
+        player.invulnerable_until = self.game_time + self.config.dash_iframes as f64;
+        player.dash_ready_at = self.game_time + self.config.dash_cooldown as f64;
+        self.interrupt_teleport(player_id);
+        Ok(())
+    }
+
+    /// Move dashing players along their dash
+    pub fn update_dashes(&mut self, delta_time: f32) {
+        let speed = self.config.dash_distance / self.config.dash_duration.max(0.001);
+        for player in self.players.values_mut() {
+            if !player.is_dashing(self.game_time) || !player.is_alive() {
+                continue;
+            }
+
+            let start = player.position;
+            player.position += player.dash_direction * (speed * delta_time);
+            player.position.clamp_to_radius(self.config.map_size);
+            player.stats.distance_traveled += start.distance_to(&player.position);
+
+            let current_ring = player.position.ring(self.config.ring_radius);
+            if current_ring > player.max_ring_reached {
+                player.max_ring_reached = current_ring;
+            }
+        }
+    }
+
+    /// Start a new version of entity changes. Each step starts one, and
+    /// refreshing the snapshot cache another for changes between steps.
+    pub fn next_entity_version(&mut self) -> u64 {
+        self.entity_version += 1;
+        let version = self.entity_version;
+        self.enemies.set_version(version);
+        self.projectiles.set_version(version);
+        self.pickups.set_version(version);
+        self.summons.set_version(version);
+        version
+    }
+
+    pub fn entity_version(&self) -> u64 {
+        self.entity_version
+    }
+
+    /// Bring the shared snapshot pieces up to date. Anything changed after
+    /// this gets a new entity version, to be picked up by the next refresh.
+    pub fn refresh_snapshot_cache(&mut self) {
+        let mut cache = std::mem::take(&mut self.snapshot_cache);
+        cache.refresh(self);
+        self.snapshot_cache = cache;
+        self.next_entity_version();
+    }
+
+    /// Bring just the cached player outlines up to date, so someone who
+    /// joins between steps is in their very first snapshot
+    fn refresh_snapshot_players(&mut self) {
+        let mut cache = std::mem::take(&mut self.snapshot_cache);
+        cache.refresh_players(self);
+        self.snapshot_cache = cache;
+    }
+
+    /// How many entities changed in version `since` or later, counted from
+    /// the change logs rather than by comparing entities. Anything older
+    /// than the kept history counts every entity as changed.
+    pub fn changed_entities(&self, since: u64) -> usize {
+        fn count<T>(map: &EntityMap<T>, since: u64) -> usize {
+            map.changed_since(since).map_or(map.len(), Iterator::count)
+        }
+        count(&self.enemies, since)
+            + count(&self.projectiles, since)
+            + count(&self.pickups, since)
+            + count(&self.summons, since)
+    }
+
+    /// Work out each player's and enemy's velocity and facing from how far
+    /// they moved since the last tick, for clients to extrapolate with
+    pub fn update_motion(&mut self, delta_time: f32) {
+        for player in self.players.values_mut() {
+            (player.velocity, player.facing) = motion(
+                player.last_position,
+                player.position,
+                player.facing,
+                delta_time,
+            );
+            player.last_position = Some(player.position);
+        }
+        self.enemies.update(|_, enemy| {
+            let before = (enemy.velocity, enemy.facing);
+            (enemy.velocity, enemy.facing) = motion(
+                enemy.last_position,
+                enemy.position,
+                enemy.facing,
+                delta_time,
+            );
+            enemy.last_position = Some(enemy.position);
+            (enemy.velocity, enemy.facing) != before
+        });
+    }
+
+    /// Clamp players and enemies to the map radius and warn players as they
+    /// come within `boundary_warning_distance` of the edge
+    pub fn update_boundary(&mut self) {
+        let map_size = self.config.map_size;
+        let warning_radius = map_size - self.config.boundary_warning_distance;
+
+        // A NaN position can't be clamped back; drop the enemy instead
+        self.enemies.retain(|_, enemy| {
+            if !enemy.position.is_finite() {
+                tracing::warn!("Removing enemy {} with corrupt position", enemy.id);
+                let last_seen = enemy.last_position.unwrap_or_default();
+                self.entity_events.push(despawned(
+                    EntityKind::Enemy,
+                    enemy.id,
+                    last_seen,
+                    DespawnReason::Removed,
+                ));
+            }
+            enemy.position.is_finite()
+        });
+        self.enemies.update(|_, enemy| {
+            let before = enemy.position;
+            enemy.position.clamp_to_radius(map_size);
+            enemy.position != before
+        });
+
+        let mut warned = Vec::new();
+        for player in self.players.values_mut() {
+            player.position.clamp_to_radius(map_size);
+
+            let near = player.position.distance_from_center() >= warning_radius;
+            if near && !player.near_boundary {
+                warned.push(player.id);
+            }
+            player.near_boundary = near;
+        }
+
+        for player_id in warned {
+            self.send_to(
+                player_id,
+                ServerMessage::BoundaryWarning {
+                    player_id,
+                    map_radius: map_size,
+                },
+            );
+        }
+    }
+
+    /// Whether a position is protected by the safe zone. In battle royale
+    /// the safe zone disappears once the first ring closes.
+    pub fn is_safe(&self, position: &Position) -> bool {
+        self.closed_rings == 0 && position.distance_from_center() <= self.config.safe_zone_radius
+    }
+
+    /// Radius of the closed (damaging) area for a given number of closed rings
+    fn closed_radius_for(&self, closed_rings: u32) -> f32 {
+        if closed_rings == 0 {
+            0.0
+        } else {
+            closed_rings as f32 * self.config.ring_radius + self.config.safe_zone_radius
+        }
+    }
+
+    fn zone_update(&self) -> ServerMessage {
+        let next = (self.closed_rings + 1).min(self.config.max_rings);
+        ServerMessage::ZoneUpdate {
+            closed_radius: self.closed_radius_for(self.closed_rings),
+            next_closed_radius: self.closed_radius_for(next),
+            next_shrink_time: self.next_zone_shrink,
+        }
+    }
+
+    /// Battle royale: close rings on a timer and damage anyone left inside
+    pub fn update_zone(&mut self, delta_time: f32) {
+        if !self.config.mode.closes_zone() {
+            return;
+        }
+
+        // The outermost ring never closes so there is always somewhere to stand
+        if self.game_time >= self.next_zone_shrink && self.closed_rings + 1 < self.config.max_rings
+        {
+            self.closed_rings += 1;
+            self.next_zone_shrink = self.game_time + self.config.zone_shrink_interval as f64;
+            tracing::info!("Zone closed ring {}", self.closed_rings);
+            let update = self.zone_update();
+            self.broadcast(update);
+        }
+
+        let closed_radius = self.closed_radius_for(self.closed_rings);
+        if closed_radius <= 0.0 {
+            return;
+        }
+        let damage = self.config.zone_damage * delta_time;
+        for player in self.players.values_mut() {
+            if player.is_alive() && player.position.distance_from_center() < closed_radius {
+                player.take_damage(damage);
+                if !player.is_alive() {
+                    tracing::info!("Player {} was consumed by the zone", player.id);
+                }
+            }
+        }
+    }
+
+    /// Safe zone regeneration, the camping limit, and enter/leave announcements
+    pub fn update_safe_zone(&mut self, delta_time: f32) {
+        let radius = self.config.safe_zone_radius;
+        let zone_open = self.closed_rings == 0;
+        let mut transitions = Vec::new();
+        let mut unscheduled = Vec::new();
+
+        for player in self.players.values_mut() {
+            if !player.is_alive() {
+                continue;
+            }
+
+            let inside = zone_open && player.is_in_safe_zone(radius);
+            if inside != player.in_safe_zone {
+                player.in_safe_zone = inside;
+                transitions.push((player.id, inside));
+            }
+
+            if !inside {
+                // Time spent outside earns back camping allowance
+                player.safe_zone_time = (player.safe_zone_time - delta_time).max(0.0);
+                continue;
+            }
+
+            player.safe_zone_time += delta_time;
+            let regenerating = self
+                .effects
+                .on(player.id)
+                .any(|e| matches!(e.kind, EffectKind::ZoneRegen(_)));
+            if !regenerating {
+                unscheduled.push(player.id);
+            }
+            if player.safe_zone_time > self.config.safe_zone_max_stay {
+                // Overstayed: no healing, and drift outward until they leave
+                let distance = player.position.distance_from_center();
+                let exit = if distance > 0.01 {
+                    let scale = (radius + 1.0) / distance;
+                    Position::new(player.position.x * scale, player.position.y * scale)
+                } else {
+                    Position::new(radius + 1.0, 0.0)
+                };
+                player
+                    .position
+                    .move_towards(&exit, self.config.safe_zone_push_speed, delta_time);
+            }
+        }
+
+        // Regeneration heals once a second for as long as they stay
+        let regen = EffectKind::ZoneRegen(self.config.safe_zone_regen);
+        let now = self.game_time;
+        for player_id in unscheduled {
+            let effect = TimedEffect::periodic(player_id, regen, 1.0, f32::INFINITY, now);
+            self.effects.add(effect);
+        }
+        for (player_id, entered) in transitions {
+            let message = if entered {
+                ServerMessage::SafeZoneEntered { player_id }
+            } else {
+                self.effects.retain(|e| {
+                    e.player_id != player_id || !matches!(e.kind, EffectKind::ZoneRegen(_))
+                });
+                ServerMessage::SafeZoneExited { player_id }
+            };
+            self.broadcast(message);
+        }
+    }
+
+    /// Grant a pickup or shop boost for `power_up_duration` seconds
+    fn add_power_up(&mut self, player_id: Uuid, boost: EffectKind) {
+        let (duration, now) = (self.config.power_up_duration, self.game_time);
+        self.add_effect(TimedEffect::buff(player_id, boost, duration, now));
+    }
+
+    /// Schedule an effect, applying it now if it starts now
+    pub fn add_effect(&mut self, effect: TimedEffect) {
+        self.effects.add(effect);
+        self.update_effects();
+    }
+
+    /// Apply every timed effect that's due. Effects end with their
+    /// player's run.
+    pub fn update_effects(&mut self) {
+        let players = &self.players;
+        self.effects
+            .retain(|e| players.get(&e.player_id).is_some_and(|p| p.is_alive()));
+        for effect in self.effects.take_due(self.game_time) {
+            self.apply_effect(&effect);
+        }
+    }
+
+    fn apply_effect(&mut self, effect: &TimedEffect) {
+        let Some(player) = self.players.get(&effect.player_id) else {
+            return;
+        };
+        let (center, safe) = (player.position, self.is_safe(&player.position));
+        let Some(player) = self.players.get_mut(&effect.player_id) else {
+            return;
+        };
+        match effect.kind {
+            EffectKind::Heal(amount) => player.heal(amount),
+            EffectKind::ZoneRegen(amount) => {
+                if player.in_safe_zone && player.safe_zone_time <= self.config.safe_zone_max_stay {
+                    player.heal(amount);
+                }
+            }
+            EffectKind::Shield(amount) | EffectKind::ShieldRecharge(amount) => {
+                player.raise_shield(amount)
+            }
+            EffectKind::Burn(damage) => {
+                if safe || !player.is_alive() {
+                    return;
+                }
+                player.take_damage(damage);
+                let kind = if player.is_alive() {
+                    CombatEventKind::Hit
+                } else {
+                    tracing::info!("Player {} burned to death", player.id);
+                    CombatEventKind::Kill
+                };
+                self.combat_events.push(CombatEvent {
+                    attacker: effect.source.unwrap_or(player.id),
+                    target: player.id,
+                    amount: damage,
+                    kind,
+                    position: player.position,
+                });
+            }
+            EffectKind::DamageAura { radius, damage } => {
+                if safe || !player.is_alive() {
+                    return;
+                }
+                let attacker = player.id;
+                for enemy_id in self.enemies_within(&center, radius) {
+                    self.hit_enemy(attacker, enemy_id, damage, false, Weapon::Aura);
+                }
+            }
+            EffectKind::HealingAura { radius, heal } => {
+                for p in self.players.values_mut() {
+                    if p.is_alive() && p.position.distance_to(&center) <= radius {
+                        p.heal(heal);
+                    }
+                }
+            }
+            EffectKind::DamageBoost => {
+                player.damage_boost_until = player.damage_boost_until.max(effect.ends_at);
+            }
+            EffectKind::SpeedBoost => {
+                player.speed_boost_until = player.speed_boost_until.max(effect.ends_at);
+            }
+        }
+    }
+
+    /// Shields wear off at `shield_decay` points per second
+    pub fn update_shields(&mut self, delta_time: f32) {
+        let decay = self.config.shield_decay * delta_time;
+        for player in self.players.values_mut() {
+            player.drain_shield(decay);
+        }
+    }
+
+    /// Time the invulnerability of players whose second wind went off this
+    /// tick, and announce it
+    pub fn update_second_winds(&mut self) {
+        let until = self.game_time + self.config.second_wind_invulnerability as f64;
+        for player in self.players.values_mut().filter(|p| p.revived) {
+            player.revived = false;
+            player.invulnerable_until = player.invulnerable_until.max(until);
+            tracing::info!("Player {} got a second wind", player.id);
+            self.combat_events.push(CombatEvent {
+                attacker: player.id,
+                target: player.id,
+                amount: player.health,
+                kind: CombatEventKind::SecondWind,
+                position: player.position,
+            });
+        }
+    }
+
+    /// Spawn enemies according to the game mode
+    pub fn spawn_enemies(&mut self, delta_time: f32) {
+        if self.config.mode.spawns_by_ring() {
+            self.spawn_enemies_by_ring(delta_time);
+        } else {
+            self.spawn_wave();
+        }
+    }
+
+    /// Targetable enemies within `radius` of `center`, for area attacks
+    pub fn enemies_within(&self, center: &Position, radius: f32) -> Vec<EntityId> {
+        self.enemies
+            .iter()
+            .filter(|(_, e)| e.is_targetable() && e.position.distance_to(center) <= radius)
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Whether an enemy may appear here: far enough from every living player,
+    /// and outside their view when off-screen spawning is on
+    fn is_clear_spawn(&self, position: &Position) -> bool {
+        self.players.values().filter(|p| p.is_alive()).all(|p| {
+            let mut min_distance = self.config.min_spawn_distance;
+            if self.config.offscreen_spawns {
+                min_distance = min_distance.max(p.view_radius);
+            }
+            p.position.distance_to(position) >= min_distance
+        })
+    }
+
+    /// Level for an enemy spawning in `ring`: the ring, capped by the
+    /// lowest-level player nearby so newcomers aren't met by a veteran's
+    /// high-ring monsters
+    fn spawn_level(&self, ring: u32, position: &Position) -> u32 {
+        self.players
+            .values()
+            .filter(|p| p.is_alive())
+            .filter(|p| p.position.distance_to(position) <= self.config.spawn_bubble_radius)
+            .map(|p| 1 + (p.level - 1) / self.config.levels_per_enemy_level.max(1))
+            .min()
+            .map_or(ring, |cap| ring.min(cap))
+    }
+
+    /// Randomness for everything the simulation rolls. Daily runs and
+    /// servers given a `seed` derive it from the match seed and `stream`,
+    /// so the same situation always gets the same rolls and a recorded
+    /// session replays exactly; otherwise it's fresh entropy.
+    fn run_rng(&self, stream: &[u64]) -> StdRng {
+        if self.config.mode == GameMode::Daily || self.config.seed.is_some() {
+            StdRng::seed_from_u64(daily::derive_seed(self.match_seed, stream))
+        } else {
+            StdRng::from_entropy()
+        }
+    }
+
+    /// Difficulty tier reached by time spent in the current match, so
+    /// camping an easy ring gets harder the longer it goes on
+    pub fn difficulty_tier(&self) -> u32 {
+        let elapsed = self.game_time - self.match_start_time;
+        let tier = (elapsed / self.config.difficulty_tier_interval as f64) as u32;
+        tier.min(self.config.max_difficulty_tier)
+    }
+
+    /// Enemy health/damage multiplier for the current difficulty tier
+    pub fn difficulty_multiplier(&self) -> f32 {
+        1.0 + self.difficulty_tier() as f32 * self.config.difficulty_stat_growth
+    }
+
+    /// Wave defense: a growing group appears at the wave radius on a timer
+    fn spawn_wave(&mut self) {
+        if self.game_time < self.next_wave_time {
+            return;
+        }
+
+        self.wave_number += 1;
+        self.next_wave_time = self.game_time + self.config.wave_interval as f64;
+
+        let mut rng = self.run_rng(&[WAVE_RNG, self.wave_number as u64]);
+        let ring = self.wave_number.min(self.config.max_rings);
+        let enemy_types = self.config.enemies.for_ring(ring).to_vec();
+        let count = self.config.wave_base_size + (self.wave_number - 1) * 3;
+        let multiplier = self.difficulty_multiplier();
+        let radius = self.config.wave_spawn_radius;
+
+        for _ in 0..count {
+            let enemy_type = enemy_types[rng.gen_range(0..enemy_types.len())];
+            let position = (0..self.config.spawn_attempts)
+                .map(|_| {
+                    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                    Position::new(radius * angle.cos(), radius * angle.sin())
+                })
+                .find(|position| self.is_clear_spawn(position));
+            let Some(position) = position else {
+                continue;
+            };
+            let id = Uuid::from_u128(rng.gen());
+            let mut enemy = self.config.enemies.spawn(id, enemy_type, position, ring);
+            enemy.scale_stats(multiplier);
+            self.insert_spawn(enemy);
+        }
+
+        tracing::info!("Wave {} spawned ({} enemies)", self.wave_number, count);
+    }
+
+    /// Ring-based spawning around active players
+    fn spawn_enemies_by_ring(&mut self, _delta_time: f32) {
+        let spawn_rate = self.config.enemy_spawn_rate
+            * (1.0 + self.difficulty_tier() as f32 * self.config.difficulty_spawn_growth);
+        let spawn_interval = 1.0 / spawn_rate as f64;
+
+        if self.game_time - self.last_spawn_time < spawn_interval {
+            return;
+        }
+
+        self.last_spawn_time = self.game_time;
+
+        // Determine active rings based on player positions
+        let active_rings = self.get_active_rings();
+
+        for ring in active_rings {
+            self.spawn_enemy_in_ring(ring);
+        }
+    }
+
+    fn get_active_rings(&self) -> Vec<u32> {
+        // Ordered, so enemies spawn in the same order on every replay
+        let mut rings = std::collections::BTreeSet::new();
+
+        for player in self.players.values() {
+            let player_ring = player.position.ring(self.config.ring_radius);
+            // Spawn in player's ring and adjacent rings
+            for offset in 0..=1 {
+                let ring = (player_ring + offset).min(self.config.max_rings);
+                rings.insert(ring);
+            }
+        }
+
+        rings.into_iter().collect()
+    }
+
+    fn spawn_enemy_in_ring(&mut self, ring: u32) {
+        let match_tick = ((self.game_time - self.match_start_time) * self.config.tick_rate) as u64;
+        let mut rng = self.run_rng(&[SPAWN_RNG, match_tick, ring as u64]);
+
+        // Generate random position in the ring, away from players
+        let inner_radius =
+            (ring - 1) as f32 * self.config.ring_radius + self.config.safe_zone_radius;
+        let outer_radius = ring as f32 * self.config.ring_radius + self.config.safe_zone_radius;
+        let position = (0..self.config.spawn_attempts)
+            .map(|_| {
+                let radius = rng.gen_range(inner_radius..outer_radius);
+                let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                Position::new(radius * angle.cos(), radius * angle.sin())
+            })
+            .find(|position| self.is_clear_spawn(position));
+        let Some(position) = position else {
+            tracing::debug!("No clear spawn point in ring {}", ring);
+            return;
+        };
+
+        // Choose an enemy type appropriate to its level
+        let level = self.spawn_level(ring, &position);
+        let enemy_types = self.config.enemies.for_ring(level);
+        let enemy_type = enemy_types[rng.gen_range(0..enemy_types.len())];
+
+        let id = Uuid::from_u128(rng.gen());
+        let mut enemy = self.config.enemies.spawn(id, enemy_type, position, level);
+        enemy.scale_stats(self.difficulty_multiplier());
+        self.insert_spawn(enemy);
+        tracing::debug!(
+            "Spawned {:?} (level {}) in ring {} at ({:.1}, {:.1})",
+            enemy_type,
+            level,
+            ring,
+            position.x,
+            position.y
+        );
+    }
+
+    /// Put a newly spawned enemy into the world, once plugins have had
+    /// their say on it
+    fn insert_spawn(&mut self, mut enemy: Enemy) {
+        self.run_plugins(|plugin, game| plugin.modify_spawn(game, &mut enemy));
+        if self.config.enemies.is_boss(enemy.enemy_type) {
+            let event = GameEvent::BossSpawned {
+                enemy: enemy.clone(),
+            };
+            self.game_events.publish(event);
+        }
+        self.insert_enemy(enemy);
+    }
+
+    /// Run the enemy AI decisions that are due: threat, targeting, leashes
+    /// and behavior changes. Each enemy decides `ai_decision_rate` times a
+    /// second, timed from its own last decision so the work spreads across
+    /// ticks; in between, `update_enemies` moves it along its steering.
+    pub fn decide_enemies(&mut self, delta_time: f32) {
+        let players: Vec<_> = self.players.values().cloned().collect();
+        let wave_aggro_range =
+            (!self.config.mode.spawns_by_ring()).then_some(self.config.wave_aggro_range);
+        let wolves: Vec<Position> = self
+            .enemies
+            .values()
+            .filter(|e| e.enemy_type == EnemyType::Wolf && e.is_alive())
+            .map(|e| e.position)
+            .collect();
+        let ctx = AiContext {
+            game_time: self.game_time,
+            wolves: &wolves,
+        };
+        // Within half a tick counts as due, so a rate that doesn't divide
+        // the tick rate rounds to the nearest tick rather than the next
+        let interval = 1.0 / self.config.ai_decision_rate as f64 - delta_time as f64 / 2.0;
+        let mut fired = Vec::new();
+
+        // Only the enemies that decided count as changed
+        self.enemies.update(|_, enemy| {
+            let elapsed = match enemy.last_decision {
+                Some(last) if self.game_time - last < interval => return false,
+                Some(last) => (self.game_time - last) as f32,
+                None => delta_time,
+            };
+            enemy.last_decision = Some(self.game_time);
+
+            // Ring modes: enemies pulled too far from home walk back and reset
+            if wave_aggro_range.is_none() && Self::update_leash(enemy, &self.config, ctx.game_time)
+            {
+                return true;
+            }
+
+            // Target whoever holds the most threat
+            threat::update_threat(enemy, &players, &self.config, elapsed);
+            let target = threat::select_target(enemy, &players, &self.config);
+
+            // Wave defense: march on the core unless a player is close
+            let target = target.filter(|p| {
+                wave_aggro_range
+                    .is_none_or(|range| enemy.position.distance_to(&p.position) <= range)
+            });
+
+            enemy.target_player_id = target.map(|p| p.id);
+            if let Some(target_player) = target {
+                fired.extend(enemy_ai::decide(enemy, target_player, &ctx));
+            } else if wave_aggro_range.is_some() {
+                enemy.steering = Steering::Toward(Position::new(0.0, 0.0));
+            } else {
+                enemy.steering = Steering::Hold;
+            }
+            true
+        });
+
+        for projectile in fired {
+            self.insert_projectile(projectile);
+        }
+    }
+
+    /// Move every enemy along the steering its last decision left it
+    pub fn update_enemies(&mut self, delta_time: f32) {
+        let wave_defense = !self.config.mode.spawns_by_ring();
+        let mut reached_core = Vec::new();
+
+        self.enemies.update(|id, enemy| {
+            let (destination, speed) = match enemy.steering {
+                Steering::Hold => (None, enemy.movement_speed),
+                Steering::Chase => {
+                    let target = enemy.target_player_id.and_then(|id| self.players.get(&id));
+                    (target.map(|p| p.position), enemy.movement_speed)
+                }
+                Steering::Toward(point) => (Some(point), enemy.movement_speed),
+                Steering::Heading(direction, multiple) => (
+                    Some(enemy.position + direction),
+                    enemy.movement_speed * multiple,
+                ),
+            };
+            let start = enemy.position;
+            if let Some(destination) = destination {
+                enemy.position.move_towards(&destination, speed, delta_time);
+            }
+            let mut changed = enemy.position != start;
+
+            if enemy.behavior == EnemyBehavior::Return
+                && enemy.position.distance_to(&enemy.home) <= 1.0
+            {
+                // Back home: full health and a clean slate
+                enemy.health = enemy.max_health;
+                enemy.behavior = enemy.enemy_type.initial_behavior();
+                enemy.behavior_started = self.game_time;
+                enemy.steering = Steering::Hold;
+                changed = true;
+            }
+
+            // Wave defense: reaching the core spends the enemy
+            if wave_defense
+                && enemy.target_player_id.is_none()
+                && enemy.position.distance_from_center() <= self.config.safe_zone_radius
+            {
+                self.core_health = (self.core_health - enemy.damage).max(0.0);
+                reached_core.push(id);
+            }
+            changed
+        });
+        for id in reached_core {
+            if let Some(e) = self.enemies.remove(id) {
+                let reason = DespawnReason::ReachedCore;
+                let event = despawned(EntityKind::Enemy, e.id, e.position, reason);
+                self.entity_events.push(event);
+            }
+        }
+
+        self.separate_entities();
+    }
+
+    /// Push enemies out of each other and out of players so hordes surround
+    /// their target instead of stacking on it. Phased wraiths pass through.
+    fn separate_entities(&mut self) {
+        if self.config.separation_strength <= 0.0 {
+            return;
+        }
+
+        let (enemy_ids, mut bodies): (Vec<EntityId>, Vec<Body>) = self
+            .enemies
+            .iter()
+            .filter(|(_, e)| e.is_targetable())
+            .map(|(id, e)| {
+                let body = Body {
+                    position: e.position,
+                    radius: e.enemy_type.radius(),
+                    pinned: false,
+                };
+                (id, body)
+            })
+            .unzip();
+        bodies.extend(
+            self.players
+                .values()
+                .filter(|p| p.is_alive())
+                .map(|p| Body {
+                    position: p.position,
+                    radius: Player::RADIUS,
+                    pinned: true,
+                }),
+        );
+
+        collision::separate(&mut bodies, self.config.separation_strength);
+
+        for (id, body) in enemy_ids.into_iter().zip(&bodies) {
+            // Only touch the ones that moved, so the rest stay unchanged
+            let pushed = self
+                .enemies
+                .get(id)
+                .is_some_and(|e| e.position != body.position);
+            if !pushed {
+                continue;
+            }
+            if let Some(enemy) = self.enemies.get_mut(id) {
+                enemy.position = body.position;
+            }
+        }
+    }
+
+    /// Returns true while the enemy is leashed and should ignore players
+    fn update_leash(enemy: &mut Enemy, config: &GameConfig, game_time: f64) -> bool {
+        if enemy.behavior != EnemyBehavior::Return {
+            if enemy.position.distance_to(&enemy.home) <= config.leash_range {
+                return false;
+            }
+            enemy.behavior = EnemyBehavior::Return;
+            enemy.behavior_started = game_time;
+            enemy.target_player_id = None;
+            enemy.threat.clear();
+        }
+        enemy.steering = Steering::Toward(enemy.home);
+        true
+    }
+
+    /// Process combat between players and enemies
+    pub fn process_combat(&mut self) {
+        let projectile_speed = 300.0; // units per second
+        let projectile_lifetime = 3.0; // seconds
+        let auto_attack_range = 400.0; // auto-aim range for Vampire Survivors style
+
+        // Players spawn projectiles (auto-attack closest enemy)
+        let player_ids: Vec<_> = self.players.keys().cloned().collect();
+        for player_id in player_ids {
+            let player = match self.players.get(&player_id) {
+                Some(p) if p.is_alive() && p.can_attack(self.game_time) => p.clone(),
+                _ => continue,
+            };
+
+            // Can't attack in safe zone
+            if self.is_safe(&player.position) {
+                continue;
+            }
+
+            // Find closest target to auto-aim at; in PvP that includes other players
+            let enemy_targets = self
+                .enemies
+                .values()
+                .filter(|e| e.is_targetable())
+                .map(|e| e.position);
+            let player_targets = self
+                .players
+                .values()
+                .filter(|p| self.config.pvp_enabled && p.id != player_id && p.is_alive())
+                .map(|p| p.position)
+                .filter(|pos| !self.is_safe(pos));
+            let target = enemy_targets.chain(player_targets).min_by(|a, b| {
+                let dist_a = a.distance_to(&player.position);
+                let dist_b = b.distance_to(&player.position);
+                cmp_distance(&dist_a, &dist_b)
+            });
+
+            if let Some(target) = target {
+                let distance = target.distance_to(&player.position);
+                if distance <= auto_attack_range {
+                    // Spawn projectile toward target
+                    let mut projectile = Projectile::new(
+                        player_id,
+                        player.position,
+                        target - player.position,
+                        projectile_speed,
+                        player.effective_damage(self.game_time),
+                        projectile_lifetime,
+                    );
+                    let catalog = &self.config.upgrade_catalog;
+                    projectile.turn_rate = player.upgrades.homing_turn_rate(catalog);
+                    projectile.explosion = player.upgrades.explosion_damage(catalog);
+                    let facing = projectile.facing;
+
+                    self.insert_projectile(projectile);
+
+                    // Update attack cooldown, and face the shot unless
+                    // moving this tick turns them back (see `update_motion`)
+                    if let Some(p) = self.players.get_mut(&player_id) {
+                        p.last_attack_time = self.game_time;
+                        p.facing = facing;
+                    }
+                }
+            }
+        }
+
+        // Enemies attack players, unless touching them does the damage
+        // instead (see `update_contact_damage`)
+        let enemy_ids: Vec<_> = if self.config.contact_damage {
+            Vec::new()
+        } else {
+            self.enemies.ids().collect()
+        };
+        for enemy_id in enemy_ids {
+            let enemy = match self.enemies.get(enemy_id) {
+                Some(e) if e.is_alive() && e.can_melee() && e.can_attack(self.game_time) => {
+                    e.clone()
+                }
+                _ => continue,
+            };
+
+            if let Some(target_id) = enemy.target_player_id {
+                if let Some(target_player) = self.players.get(&target_id) {
+                    // Can't attack players in safe zone or mid-dodge
+                    if self.is_safe(&target_player.position)
+                        || target_player.is_invulnerable(self.game_time)
+                    {
+                        continue;
+                    }
+
+                    let distance = enemy.position.distance_to(&target_player.position);
+                    let melee_range = 50.0;
+                    if distance <= melee_range {
+                        // Apply damage
+                        if let Some(player) = self.players.get_mut(&target_id) {
+                            player.take_damage(enemy.damage);
+
+                            let kind = if player.is_alive() {
+                                CombatEventKind::Hit
+                            } else {
+                                tracing::info!("Player {} died", target_id);
+                                CombatEventKind::Kill
+                            };
+                            self.combat_events.push(CombatEvent {
+                                attacker: enemy.id,
+                                target: target_id,
+                                amount: enemy.damage,
+                                kind,
+                                position: player.position,
+                            });
+                        }
+                        if self.config.enemies.burns(enemy.enemy_type) {
+                            let burn = TimedEffect::periodic(
+                                target_id,
+                                EffectKind::Burn(self.config.burn_damage),
+                                1.0,
+                                self.config.burn_duration,
+                                self.game_time,
+                            );
+                            self.effects.add(burn.from(enemy.id));
+                        }
+
+                        // Update attack cooldown
+                        if let Some(e) = self.enemies.get_mut(enemy_id) {
+                            e.last_attack_time = self.game_time;
+                        }
+                    }
+                }
+            }
+        }
+
+        // Clean up dead enemies
+        self.enemies.retain(|_, e| {
+            if !e.is_alive() {
+                let event = despawned(EntityKind::Enemy, e.id, e.position, DespawnReason::Killed);
+                self.entity_events.push(event);
+            }
+            e.is_alive()
+        });
+
+        // Dead players will be removed when connection drops
+    }
+
+    /// Contact damage mode: every enemy touching a player hurts them
+    /// continuously, its damage per swing × swings per second scaled to
+    /// the tick. The safe zone and invulnerability protect as from melee.
+    pub fn update_contact_damage(&mut self, delta_time: f32) {
+        if !self.config.contact_damage {
+            return;
+        }
+
+        let mut contacts = Vec::new();
+        for enemy in self.enemies.values() {
+            if !enemy.is_alive() || !enemy.can_melee() {
+                continue;
+            }
+            let reach = enemy.enemy_type.radius() + Player::RADIUS + CONTACT_SLACK;
+            let touching = self
+                .players
+                .values()
+                .filter(|p| p.position.distance_to(&enemy.position) <= reach)
+                .filter(|p| !self.is_safe(&p.position));
+            for player in touching {
+                let damage = enemy.damage * enemy.attack_speed * delta_time;
+                contacts.push((enemy.id, enemy.enemy_type, player.id, damage));
+            }
+        }
+
+        for (enemy_id, enemy_type, player_id, damage) in contacts {
+            let Some(player) = self.players.get_mut(&player_id) else {
+                continue;
+            };
+            // Dead or saved by a second wind earlier in the loop
+            if !player.is_alive() || player.is_invulnerable(self.game_time) {
+                continue;
+            }
+            player.take_damage(damage);
+            // A number every tick would be noise; only the end is shown
+            if !player.is_alive() {
+                tracing::info!("Player {} died", player_id);
+                self.combat_events.push(CombatEvent {
+                    attacker: enemy_id,
+                    target: player_id,
+                    amount: damage,
+                    kind: CombatEventKind::Kill,
+                    position: player.position,
+                });
+            }
+
+            // Burns run their course rather than restarting every tick
+            let burning = self
+                .effects
+                .on(player_id)
+                .any(|e| matches!(e.kind, EffectKind::Burn(_)));
+            if self.config.enemies.burns(enemy_type) && !burning {
+                let burn = TimedEffect::periodic(
+                    player_id,
+                    EffectKind::Burn(self.config.burn_damage),
+                    1.0,
+                    self.config.burn_duration,
+                    self.game_time,
+                );
+                self.effects.add(burn.from(enemy_id));
+            }
+        }
+    }
+
+    /// Update projectiles and check collisions
+    pub fn update_projectiles(&mut self, delta_time: f32) {
+        let collision_radius = 20.0; // hit detection radius
+
+        // Update projectile positions, steering homing shots; anything
+        // leaving the map is gone. Lifetimes count down unseen, so only
+        // shots that moved or turned count as changed.
+        let (enemies, homing_range) = (&self.enemies, self.config.homing_range);
+        self.projectiles.update(|_, projectile| {
+            let (position, velocity) = (projectile.position, projectile.velocity);
+            projectile.update(delta_time, |from| {
+                nearest_enemy(enemies, from, homing_range)
+            });
+            projectile.position != position || projectile.velocity != velocity
+        });
+        let map_size = self.config.map_size;
+        self.projectiles.retain(|_, p| {
+            let inside = p.position.distance_from_center() <= map_size;
+            if !inside {
+                let reason = DespawnReason::OutOfBounds;
+                let event = despawned(EntityKind::Projectile, p.id, p.position, reason);
+                self.entity_events.push(event);
+            }
+            inside
+        });
+
+        // Check collisions with enemies. The ID list reuses one buffer from
+        // tick to tick rather than allocating.
+        let mut projectile_ids = std::mem::take(&mut self.scratch_ids);
+        projectile_ids.clear();
+        projectile_ids.extend(self.projectiles.ids());
+        for &proj_id in &projectile_ids {
+            let projectile = match self.projectiles.get(proj_id) {
+                Some(p) => p.clone(),
+                None => continue,
+            };
+
+            if projectile.hostile {
+                self.check_hostile_hit(proj_id, &projectile, collision_radius);
+                continue;
+            }
+
+            // Find hit enemy
+            if let Some((enemy_id, _)) = self
+                .enemies
+                .iter()
+                .filter(|(_, e)| e.is_targetable())
+                .map(|(id, e)| (id, e.position.distance_to(&projectile.position)))
+                .filter(|(_, dist)| *dist <= collision_radius)
+                .min_by(|a, b| cmp_distance(&a.1, &b.1))
+            {
+                let (damage, crit) = self.roll_damage(&projectile);
+                self.hit_enemy(
+                    projectile.owner_id,
+                    enemy_id,
+                    damage,
+                    crit,
+                    projectile.weapon,
+                );
+                self.explode(&projectile, Target::Enemy(enemy_id));
+
+                // Remove projectile on hit
+                self.remove_projectile(proj_id, DespawnReason::Hit);
+                continue;
+            }
+
+            if self.config.pvp_enabled {
+                self.check_pvp_hit(proj_id, &projectile, collision_radius);
+            }
+        }
+
+        // Remove expired projectiles; explosive ones go off where they stop
+        projectile_ids.clear();
+        projectile_ids.extend(
+            self.projectiles
+                .iter()
+                .filter(|(_, p)| !p.is_alive())
+                .map(|(id, _)| id),
+        );
+        for &proj_id in &projectile_ids {
+            let Some(projectile) = self.remove_projectile(proj_id, DespawnReason::Expired) else {
+                continue;
+            };
+            if !projectile.hostile {
+                self.explode(&projectile, Target::Nothing);
+            }
+        }
+        self.scratch_ids = projectile_ids;
+    }
+
+    /// Splash damage from an explosive shot around where it landed, to
+    /// everything within `explosion_radius` but what it struck directly.
+    /// In PvP the blast also catches players, sparing the shooter and
+    /// their party.
+    fn explode(&mut self, projectile: &Projectile, struck: Target) {
+        if projectile.explosion <= 0.0 {
+            return;
+        }
+        let (owner_id, weapon) = (projectile.owner_id, projectile.weapon);
+        let center = projectile.position;
+        let radius = self.config.explosion_radius;
+        let (damage, _) = self.roll_damage(projectile);
+        let damage = damage * projectile.explosion;
+        self.broadcast(ServerMessage::Explosion {
+            owner_id,
+            position: center,
+            radius,
+        });
+
+        for enemy_id in self.enemies_within(&center, radius) {
+            if struck != Target::Enemy(enemy_id) {
+                self.hit_enemy(owner_id, enemy_id, damage, false, weapon);
+            }
+        }
+
+        if !self.config.pvp_enabled {
+            return;
+        }
+        let owner_party = self.players.get(&owner_id).and_then(|p| p.party_id);
+        let victims: Vec<Uuid> = self
+            .players
+            .values()
+            .filter(|p| p.id != owner_id && p.is_alive())
+            .filter(|p| owner_party.is_none() || p.party_id != owner_party)
+            .filter(|p| struck != Target::Player(p.id))
+            .filter(|p| !self.is_safe(&p.position) && !p.is_invulnerable(self.game_time))
+            .filter(|p| p.position.distance_to(&center) <= radius)
+            .map(|p| p.id)
+            .collect();
+        let damage = damage * self.config.pvp_damage_scale;
+        for victim_id in victims {
+            self.hit_player(owner_id, victim_id, damage, false, weapon);
+        }
+    }
+
+    /// Damage an enemy on a player's behalf, crediting them with the kill
+    fn hit_enemy(
+        &mut self,
+        attacker: Uuid,
+        enemy_id: EntityId,
+        damage: f32,
+        crit: bool,
+        weapon: Weapon,
+    ) {
+        let Some(enemy) = self.enemies.get_mut(enemy_id) else {
+            return;
+        };
+        let dealt = damage.min(enemy.health);
+        enemy.take_damage(damage);
+        *enemy.damage_by.entry(attacker).or_insert(0.0) += dealt;
+        threat::add_damage_threat(enemy, attacker, damage, &self.config);
+        if let Some(p) = self.players.get_mut(&attacker) {
+            p.stats.record_damage(weapon, dealt);
+        }
+        if let Some(activity) = self.activity.get_mut(&attacker) {
+            activity.last_hit = Some(self.game_time);
+        }
+
+        let kind = match (enemy.is_alive(), crit) {
+            (false, _) => CombatEventKind::Kill,
+            (true, true) => CombatEventKind::Crit,
+            (true, false) => CombatEventKind::Hit,
+        };
+        self.combat_events.push(CombatEvent {
+            attacker,
+            target: enemy.id,
+            amount: damage,
+            kind,
+            position: enemy.position,
+        });
+
+        if !enemy.is_alive() {
+            tracing::debug!(
+                "{} from player {} killed enemy {}",
+                weapon.name(),
+                attacker,
+                enemy.id
+            );
+            let event = GameEvent::EnemyKilled {
+                enemy: enemy.clone(),
+                killer: attacker,
+                weapon,
+            };
+            self.game_events.publish(event);
+        }
+    }
+
+    /// Hand out a kill `killer` finished: the tally and a drop roll to
+    /// whoever `kill_credit` names, and the XP to everyone it's split
+    /// between, and their parties
+    fn credit_kill(&mut self, enemy: &Enemy, killer: Uuid) {
+        let credit = self.config.kill_credit;
+        let credited = kill_credit::credited(enemy, killer, credit);
+        let boss = self.config.enemies.is_boss(enemy.enemy_type);
+        if let Some(p) = self.players.get_mut(&credited) {
+            p.enemies_defeated += 1;
+            if boss {
+                p.bosses_defeated += 1;
+            }
+        }
+        self.roll_drop(credited, enemy.position, enemy.spawn_ring);
+        for (player_id, xp) in kill_credit::xp_shares(enemy, killer, credit) {
+            self.grant_xp(player_id, xp);
+            self.share_party_xp(player_id, xp, enemy.position);
+        }
+    }
+
+    /// Companions follow their owner, close in on enemies near them and
+    /// attack on their own cooldowns. They vanish when the owner dies or leaves.
+    pub fn update_summons(&mut self, delta_time: f32) {
+        self.summons.retain(|_, summon| {
+            let kept = self
+                .players
+                .get(&summon.owner_id)
+                .is_some_and(|p| p.is_alive());
+            if !kept {
+                self.entity_events.push(despawned(
+                    EntityKind::Summon,
+                    summon.id,
+                    summon.position,
+                    DespawnReason::Removed,
+                ));
+            }
+            kept
+        });
+
+        let summon_ids: Vec<_> = self.summons.ids().collect();
+        for summon_id in summon_ids {
+            let Some(summon) = self.summons.get(summon_id).cloned() else {
+                continue;
+            };
+            let Some(owner) = self.players.get(&summon.owner_id) else {
+                continue;
+            };
+            let stats = summon.summon_type.stats();
+            let damage = stats.damage * owner.upgrades.summon_damage_multiplier(&self.config.upgrade_catalog);
+            let owner_position = owner.position;
+
+            // Nearest enemy its owner could be fighting; the safe zone is off limits
+            let target = self
+                .enemies
+                .iter()
+                .filter(|(_, e)| e.is_targetable() && !self.is_safe(&e.position))
+                .filter(|(_, e)| e.position.distance_to(&owner_position) <= stats.aggro_range)
+                .map(|(id, e)| (id, e.position, e.position.distance_to(&summon.position)))
+                .min_by(|a, b| cmp_distance(&a.2, &b.2));
+
+            let goal = match target {
+                Some((_, position, _)) if summon.summon_type.is_melee() => position,
+                _ => summon.follow_point(&owner_position),
+            };
+            let Some(s) = self.summons.get_mut(summon_id) else {
+                continue;
+            };
+            s.position.move_towards(&goal, stats.speed, delta_time);
+            let position = s.position;
 
-pub type SharedGameState = Arc<RwLock<GameState>>;
+            let Some((enemy_id, enemy_position, _)) = target else {
+                continue;
+            };
+            if !summon.can_attack(self.game_time)
+                || position.distance_to(&enemy_position) > stats.attack_range
+            {
+                continue;
+            }
+            s.last_attack_time = self.game_time;
 
-#[derive(Debug)]
-pub struct GameState {
-    pub config: GameConfig,
-    pub players: HashMap<Uuid, Player>,
-    pub enemies: HashMap<Uuid, Enemy>,
-    pub projectiles: HashMap<Uuid, Projectile>,
-    pub scores: Vec<ScoreEntry>,
-    pub game_time: f64,
-    pub last_spawn_time: f64,
-    pub pending_level_ups: HashMap<Uuid, Vec<UpgradeType>>, // Player ID -> upgrade choices
-}
+            let weapon = summon.summon_type.weapon();
+            if summon.summon_type.is_melee() {
+                self.hit_enemy(summon.owner_id, enemy_id, damage, false, weapon);
+            } else {
+                let direction = enemy_position - position;
+                let mut projectile =
+                    Projectile::new(summon.owner_id, position, direction, 300.0, damage, 1.5);
+                projectile.weapon = weapon;
+                self.insert_projectile(projectile);
+            }
+        }
+    }
 
-impl GameState {
-    pub fn new(config: GameConfig) -> Self {
-        Self {
-            config,
-            players: HashMap::new(),
-            enemies: HashMap::new(),
-            projectiles: HashMap::new(),
-            scores: Vec::new(),
-            game_time: 0.0,
-            last_spawn_time: 0.0,
-            pending_level_ups: HashMap::new(),
+    /// Maybe leave a pickup where an enemy died; the killer's luck raises the odds
+    fn roll_drop(&mut self, killer_id: Uuid, position: Position, ring: u32) {
+        let luck = self
+            .players
+            .get(&killer_id)
+            .map(|p| p.upgrades.luck_multiplier(&self.config.upgrade_catalog))
+            .unwrap_or(1.0);
+        let gear_chance = (self.config.equipment_drop_chance * luck).clamp(0.0, 1.0);
+        let (x, y) = (position.x.to_bits() as u64, position.y.to_bits() as u64);
+        let mut rng = self.run_rng(&[DROP_RNG, self.tick, x, y]);
+        if rng.gen_bool(gear_chance as f64) {
+            let item = Equipment::roll(ring, &mut rng);
+            let pickup = Pickup::equipment(item, position, self.config.pickup_lifetime);
+            self.insert_pickup(pickup);
+            return;
         }
+        let drop_chance = self.config.pickup_drop_chance * luck;
+        let Some(pickup_type) = PickupType::roll_drop(drop_chance, &mut rng) else {
+            return;
+        };
+
+        let amount = match pickup_type {
+            PickupType::Gold => ring * 5,
+            PickupType::HealthPotion => self.config.potion_heal,
+            PickupType::DoubleDamage | PickupType::SpeedBoost | PickupType::Equipment => 0,
+        };
+        let pickup = Pickup::new(pickup_type, position, amount, self.config.pickup_lifetime);
+        self.insert_pickup(pickup);
     }
 
-    /// Add a new player to the game
-    pub fn add_player(&mut self, player_id: Uuid) -> Player {
-        let player = Player::new(player_id);
-        self.players.insert(player_id, player.clone());
-        tracing::info!("Player {} joined the game", player_id);
-        player
+    /// Put a projectile into the world. Its ID comes from the pooled slot it
+    /// lands in, as thousands of short-lived shots would otherwise keep the
+    /// random UUID generator busy.
+    pub fn insert_projectile(&mut self, mut projectile: Projectile) -> EntityId {
+        let namespace = self.match_seed ^ PROJECTILE_IDS;
+        let id = self.projectiles.insert_with(|id| {
+            projectile.id = id.to_uuid(namespace);
+            projectile
+        });
+        let projectile = &self.projectiles[id];
+        self.entity_events.push(spawned(
+            EntityKind::Projectile,
+            projectile.id,
+            projectile.position,
+        ));
+        id
     }
 
-    /// Remove a player (death or disconnect)
-    pub fn remove_player(&mut self, player_id: Uuid) -> Option<Player> {
-        let player = self.players.remove(&player_id)?;
+    /// Take a projectile out of the world, noting why for `EntityEvents`
+    fn remove_projectile(&mut self, id: EntityId, reason: DespawnReason) -> Option<Projectile> {
+        let projectile = self.projectiles.remove(id)?;
+        self.entity_events.push(despawned(
+            EntityKind::Projectile,
+            projectile.id,
+            projectile.position,
+            reason,
+        ));
+        Some(projectile)
+    }
 
-        // Check if eligible for scoreboard (reached ring 10+)
-        if player.max_ring_reached >= self.config.score_min_ring {
-            let survival_time = (chrono::Utc::now() - player.spawn_time).num_seconds() as f32;
+    /// Put a drop into the world, with an ID from its pooled slot
+    pub fn insert_pickup(&mut self, mut pickup: Pickup) -> EntityId {
+        let namespace = self.match_seed ^ PICKUP_IDS;
+        let id = self.pickups.insert_with(|id| {
+            pickup.id = id.to_uuid(namespace);
+            pickup
+        });
+        let pickup = &self.pickups[id];
+        self.entity_events
+            .push(spawned(EntityKind::Pickup, pickup.id, pickup.position));
+        id
+    }
 
-            let score = ScoreEntry {
-                player_id,
-                max_ring_reached: player.max_ring_reached,
-                survival_time_seconds: survival_time,
-                enemies_defeated: player.enemies_defeated,
-                timestamp: chrono::Utc::now(),
+    /// Age out old drops and let players collect the ones they walk over
+    pub fn update_pickups(&mut self, delta_time: f32) {
+        // Neither the countdown nor who dropped a pickup is drawn, so
+        // neither counts as a change
+        self.pickups.update(|_, pickup| {
+            pickup.lifetime -= delta_time;
+            false
+        });
+        self.pickups.retain(|_, p| {
+            if !p.is_alive() {
+                let event = despawned(EntityKind::Pickup, p.id, p.position, DespawnReason::Expired);
+                self.entity_events.push(event);
+            }
+            p.is_alive()
+        });
+
+        let pickup_radius = |player: &Player| {
+            let catalog = &self.config.upgrade_catalog;
+            self.config.pickup_radius * player.upgrades.pickup_radius_multiplier(catalog)
+        };
+        self.pickups.update(|_, pickup| {
+            let Some(dropper) = pickup.dropped_by else {
+                return false;
             };
+            let stepped_away = self
+                .players
+                .get(&dropper)
+                .is_none_or(|p| p.position.distance_to(&pickup.position) > pickup_radius(p));
+            if stepped_away {
+                pickup.dropped_by = None;
+            }
+            false
+        });
 
-            self.add_score(score);
-            tracing::info!(
-                "Player {} qualified for scoreboard: Ring {}, Time: {:.1}s, Kills: {}",
-                player_id,
-                player.max_ring_reached,
-                survival_time,
-                player.enemies_defeated
-            );
+        let mut collected = Vec::new();
+        for player in self.players.values().filter(|p| p.is_alive()) {
+            let radius = pickup_radius(player);
+            for (pickup_id, pickup) in self.pickups.iter() {
+                if pickup.dropped_by != Some(player.id)
+                    && pickup.position.distance_to(&player.position) <= radius
+                {
+                    collected.push((player.id, pickup_id));
+                }
+            }
         }
 
-        Some(player)
-    }
-
-    /// Update player movement
-    pub fn move_player(&mut self, player_id: Uuid, target: Position, delta_time: f32) {
-        if let Some(player) = self.players.get_mut(&player_id) {
-            player
-                .position
-                .move_towards(&target, player.movement_speed, delta_time);
+        for (player_id, pickup_id) in collected {
+            let Some(player) = self.players.get_mut(&player_id) else {
+                continue;
+            };
+            // Gear stays on the ground for someone with room to carry it
+            let is_gear = self
+                .pickups
+                .get(pickup_id)
+                .is_some_and(|p| p.pickup_type == PickupType::Equipment);
+            if is_gear && player.inventory.len() >= self.config.inventory_size {
+                continue;
+            }
+            // Two players in range of the same drop: first one wins
+            let Some(pickup) = self.pickups.remove(pickup_id) else {
+                continue;
+            };
+            self.entity_events.push(despawned(
+                EntityKind::Pickup,
+                pickup.id,
+                pickup.position,
+                DespawnReason::Collected,
+            ));
 
-            // Update max ring reached
-            let current_ring = player.position.ring(self.config.ring_radius);
-            if current_ring > player.max_ring_reached {
-                player.max_ring_reached = current_ring;
+            match pickup.pickup_type {
+                PickupType::HealthPotion => player.heal(pickup.amount as f32),
+                PickupType::DoubleDamage => self.add_power_up(player_id, EffectKind::DamageBoost),
+                PickupType::SpeedBoost => self.add_power_up(player_id, EffectKind::SpeedBoost),
+                PickupType::Gold => player.gold += pickup.amount,
+                PickupType::Equipment => player.inventory.extend(pickup.item),
             }
+
+            self.broadcast(ServerMessage::PickupCollected {
+                player_id,
+                pickup_type: pickup.pickup_type,
+            });
         }
     }
 
-    /// Spawn enemies based on active rings
-    pub fn spawn_enemies(&mut self, _delta_time: f32) {
-        let spawn_interval = 1.0 / self.config.enemy_spawn_rate as f64;
-
-        if self.game_time - self.last_spawn_time < spawn_interval {
+    /// Grant XP to a player, publishing each level they gain
+    fn grant_xp(&mut self, player_id: Uuid, amount: u32) {
+        let Some(p) = self.players.get_mut(&player_id) else {
             return;
+        };
+
+        let levels = p.grant_xp(amount);
+        for level in p.level - levels + 1..=p.level {
+            self.game_events
+                .publish(GameEvent::PlayerLevelUp { player_id, level });
         }
+    }
 
-        self.last_spawn_time = self.game_time;
+    /// Give living party members who took part in the fight at `at` their
+    /// cut of the XP: none for anyone who hasn't hurt an enemy within
+    /// `party_active_window`, all of it within `party_full_range` of
+    /// the fight, tapering to none at `party_share_range`
+    fn share_party_xp(&mut self, earner_id: Uuid, amount: u32, at: Position) {
+        let Some(earner) = self.players.get(&earner_id) else {
+            return;
+        };
+        let Some(party) = earner.party_id.and_then(|id| self.parties.get(&id)) else {
+            return;
+        };
 
-        // Determine active rings based on player positions
-        let active_rings = self.get_active_rings();
+        let shares: Vec<(Uuid, u32)> = party
+            .members
+            .iter()
+            .filter(|id| **id != earner_id)
+            .filter_map(|id| self.players.get(id))
+            .filter(|p| p.is_alive())
+            .map(|p| {
+                let scale = self.participation(p.id, p.position.distance_to(&at));
+                let shared = amount as f32 * self.config.party_xp_share * scale;
+                (p.id, shared as u32)
+            })
+            .filter(|&(_, shared)| shared > 0)
+            .collect();
 
-        for ring in active_rings {
-            self.spawn_enemy_in_ring(ring);
+        for (member_id, shared) in shares {
+            self.grant_xp(member_id, shared);
         }
     }
 
-    fn get_active_rings(&self) -> Vec<u32> {
-        let mut rings = std::collections::HashSet::new();
-
-        for player in self.players.values() {
-            let player_ring = player.position.ring(self.config.ring_radius);
-            // Spawn in player's ring and adjacent rings
-            for offset in 0..=1 {
-                let ring = (player_ring + offset).min(self.config.max_rings);
-                rings.insert(ring);
-            }
+    /// How much of a full party XP share a member `distance` from the
+    /// fight has earned, from 0 to 1
+    fn participation(&self, player_id: Uuid, distance: f32) -> f32 {
+        let window = self.config.party_active_window as f64;
+        let last_hit = self.activity.get(&player_id).and_then(|a| a.last_hit);
+        let active = match last_hit {
+            _ if window <= 0.0 => true,
+            Some(at) => self.game_time - at <= window,
+            None => false,
+        };
+        if !active {
+            return 0.0;
         }
 
-        rings.into_iter().collect()
+        let (full, range) = (self.config.party_full_range, self.config.party_share_range);
+        if distance <= full {
+            1.0
+        } else if distance <= range {
+            (range - distance) / (range - full)
+        } else {
+            0.0
+        }
     }
 
-    fn spawn_enemy_in_ring(&mut self, ring: u32) {
-        let mut rng = rand::thread_rng();
+    /// Final damage for a player projectile: base with some variance, and a
+    /// chance to crit using the shooter's current stats
+    fn roll_damage(&self, projectile: &Projectile) -> (f32, bool) {
+        let (high, low) = projectile.id.as_u64_pair();
+        let mut rng = self.run_rng(&[DAMAGE_RNG, self.tick, high, low]);
+        let variance = self.config.damage_variance.clamp(0.0, 1.0);
+        let damage = projectile.damage * rng.gen_range(1.0 - variance..=1.0 + variance);
 
-        // Choose ring-appropriate enemy type
-        let enemy_types = EnemyType::for_ring(ring);
-        let enemy_type = enemy_types[rng.gen_range(0..enemy_types.len())];
+        let Some(owner) = self.players.get(&projectile.owner_id) else {
+            return (damage, false);
+        };
+        let crit = rng.gen_bool(owner.effective_crit_chance().clamp(0.0, 1.0) as f64);
+        if crit {
+            (damage * owner.crit_multiplier, true)
+        } else {
+            (damage, false)
+        }
+    }
 
-        // Generate random position in the ring
-        let inner_radius =
-            (ring - 1) as f32 * self.config.ring_radius + self.config.safe_zone_radius;
-        let outer_radius = ring as f32 * self.config.ring_radius + self.config.safe_zone_radius;
-        let radius = rng.gen_range(inner_radius..outer_radius);
-        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+    /// Enemy projectiles hit players; the safe zone absorbs them
+    fn check_hostile_hit(
+        &mut self,
+        proj_id: EntityId,
+        projectile: &Projectile,
+        collision_radius: f32,
+    ) {
+        let hit = self
+            .players
+            .values()
+            .filter(|p| p.is_alive() && !p.is_invulnerable(self.game_time))
+            .map(|p| (p.id, p.position.distance_to(&projectile.position)))
+            .filter(|(_, dist)| *dist <= collision_radius)
+            .min_by(|a, b| cmp_distance(&a.1, &b.1));
 
-        let position = Position::new(radius * angle.cos(), radius * angle.sin());
+        let Some((player_id, _)) = hit else {
+            return;
+        };
+        self.remove_projectile(proj_id, DespawnReason::Hit);
 
-        let enemy_id = Uuid::new_v4();
-        let enemy = Enemy::new(enemy_id, enemy_type, position, ring);
+        let safe = self
+            .players
+            .get(&player_id)
+            .is_some_and(|p| self.is_safe(&p.position));
+        let Some(player) = self.players.get_mut(&player_id) else {
+            return;
+        };
 
-        self.enemies.insert(enemy_id, enemy);
-        tracing::debug!(
-            "Spawned {:?} (level {}) in ring {} at ({:.1}, {:.1})",
-            enemy_type,
-            ring,
-            ring,
-            position.x,
-            position.y
-        );
+        let (amount, kind) = if safe {
+            (0.0, CombatEventKind::Blocked)
+        } else {
+            player.take_damage(projectile.damage);
+            if player.is_alive() {
+                (projectile.damage, CombatEventKind::Hit)
+            } else {
+                tracing::info!("Player {} died", player_id);
+                (projectile.damage, CombatEventKind::Kill)
+            }
+        };
+        self.combat_events.push(CombatEvent {
+            attacker: projectile.owner_id,
+            target: player_id,
+            amount,
+            kind,
+            position: player.position,
+        });
     }
 
-    /// Update enemy AI and movement
-    pub fn update_enemies(&mut self, delta_time: f32) {
-        let players: Vec<_> = self.players.values().cloned().collect();
+    /// PvP: a projectile that missed every enemy may still hit another player
+    fn check_pvp_hit(
+        &mut self,
+        proj_id: EntityId,
+        projectile: &Projectile,
+        collision_radius: f32,
+    ) {
+        let hit = self
+            .players
+            .values()
+            .filter(|p| p.id != projectile.owner_id && p.is_alive())
+            .filter(|p| !self.is_safe(&p.position) && !p.is_invulnerable(self.game_time))
+            .map(|p| (p.id, p.position.distance_to(&projectile.position)))
+            .filter(|(_, dist)| *dist <= collision_radius)
+            .min_by(|a, b| cmp_distance(&a.1, &b.1));
 
-        for enemy in self.enemies.values_mut() {
-            // Find closest player
-            let closest_player = players.iter().filter(|p| p.is_alive()).min_by(|a, b| {
-                let dist_a = enemy.position.distance_to(&a.position);
-                let dist_b = enemy.position.distance_to(&b.position);
-                dist_a.partial_cmp(&dist_b).unwrap()
-            });
+        let Some((victim_id, _)) = hit else {
+            return;
+        };
 
-            if let Some(target_player) = closest_player {
-                enemy.target_player_id = Some(target_player.id);
-                enemy.position.move_towards(
-                    &target_player.position,
-                    enemy.movement_speed,
-                    delta_time,
-                );
+        let (damage, crit) = self.roll_damage(projectile);
+        let damage = damage * self.config.pvp_damage_scale;
+        let (owner_id, weapon) = (projectile.owner_id, projectile.weapon);
+        self.hit_player(owner_id, victim_id, damage, crit, weapon);
+        self.explode(projectile, Target::Player(victim_id));
+
+        self.remove_projectile(proj_id, DespawnReason::Hit);
+    }
+
+    /// Damage a player on another's behalf in PvP, crediting the kill
+    fn hit_player(
+        &mut self,
+        attacker: Uuid,
+        victim_id: Uuid,
+        damage: f32,
+        crit: bool,
+        weapon: Weapon,
+    ) {
+        let (dealt, killed) = match self.players.get_mut(&victim_id) {
+            Some(victim) => {
+                let dealt = damage.min(victim.health + victim.shield);
+                victim.take_damage(damage);
+                let killed = !victim.is_alive();
+                let kind = match (killed, crit) {
+                    (true, _) => CombatEventKind::Kill,
+                    (false, true) => CombatEventKind::Crit,
+                    (false, false) => CombatEventKind::Hit,
+                };
+                self.combat_events.push(CombatEvent {
+                    attacker,
+                    target: victim_id,
+                    amount: damage,
+                    kind,
+                    position: victim.position,
+                });
+                (dealt, killed)
+            }
+            None => (0.0, false),
+        };
+
+        if let Some(p) = self.players.get_mut(&attacker) {
+            p.stats.record_damage(weapon, dealt);
+        }
+        if killed {
+            tracing::info!("Player {} killed player {}", attacker, victim_id);
+            if let Some(p) = self.players.get_mut(&attacker) {
+                p.players_defeated += 1;
             }
         }
     }
 
-    /// Process combat between players and enemies
-    pub fn process_combat(&mut self) {
-        let projectile_speed = 300.0; // units per second
-        let projectile_lifetime = 3.0; // seconds
-        let auto_attack_range = 400.0; // auto-aim range for Vampire Survivors style
+    /// Apply a chosen upgrade to a player
+    pub fn apply_upgrade(&mut self, player_id: Uuid, upgrade: UpgradeType) -> Result<(), String> {
+        let pending = self
+            .pending_level_ups
+            .get(&player_id)
+            .ok_or_else(|| "No upgrade to choose".to_string())?;
+        let choice = pending
+            .offered(upgrade)
+            .ok_or_else(|| "That upgrade isn't on offer".to_string())?;
 
-        // Players spawn projectiles (auto-attack closest enemy)
-        let player_ids: Vec<_> = self.players.keys().cloned().collect();
-        for player_id in player_ids {
-            let player = match self.players.get(&player_id) {
-                Some(p) if p.is_alive() && p.can_attack(self.game_time) => p.clone(),
-                _ => continue,
-            };
+        self.apply_choice(player_id, choice)?;
+        self.next_level_up(player_id);
 
-            // Can't attack in safe zone
-            if player.is_in_safe_zone(self.config.safe_zone_radius) {
-                continue;
-            }
+        tracing::info!("Player {} chose upgrade: {:?}", player_id, choice);
+        Ok(())
+    }
 
-            // Find closest enemy to auto-target
-            if let Some((_, enemy)) = self
-                .enemies
-                .iter()
-                .filter(|(_, e)| e.is_alive())
-                .map(|(id, e)| (id, e))
-                .min_by(|a, b| {
-                    let dist_a = a.1.position.distance_to(&player.position);
-                    let dist_b = b.1.position.distance_to(&player.position);
-                    dist_a.partial_cmp(&dist_b).unwrap()
-                })
-            {
-                let distance = enemy.position.distance_to(&player.position);
-                if distance <= auto_attack_range {
-                    // Spawn projectile toward enemy
-                    let direction = Position::new(
-                        enemy.position.x - player.position.x,
-                        enemy.position.y - player.position.y,
-                    );
-                    
-                    let projectile = Projectile::new(
-                        player_id,
-                        player.position,
-                        direction,
-                        projectile_speed,
-                        player.damage,
-                        projectile_lifetime,
-                    );
-                    
-                    self.projectiles.insert(projectile.id, projectile);
-                    
-                    // Update attack cooldown
-                    if let Some(p) = self.players.get_mut(&player_id) {
-                        p.last_attack_time = self.game_time;
-                    }
-                }
-            }
+    /// Apply as many levels of the upgrade as its rarity is worth
+    fn apply_choice(&mut self, player_id: Uuid, choice: UpgradeChoice) -> Result<(), String> {
+        for _ in 0..self.config.upgrade_table.levels(choice.rarity) {
+            self.apply_upgrade_stats(player_id, choice.upgrade)?;
         }
+        Ok(())
+    }
 
-        // Enemies attack players (keep melee)
-        let enemy_ids: Vec<_> = self.enemies.keys().cloned().collect();
-        for enemy_id in enemy_ids {
-            let enemy = match self.enemies.get(&enemy_id) {
-                Some(e) if e.is_alive() && e.can_attack(self.game_time) => e.clone(),
-                _ => continue,
-            };
+    /// Swap the pending upgrade offer for a fresh one
+    pub fn reroll_upgrades(&mut self, player_id: Uuid) -> Result<(), String> {
+        if !self.pending_level_ups.contains_key(&player_id) {
+            return Err("No upgrade to reroll".to_string());
+        }
+        let player = self
+            .players
+            .get_mut(&player_id)
+            .ok_or_else(|| "Player not found".to_string())?;
+        if player.rerolls_left == 0 {
+            return Err("No rerolls left".to_string());
+        }
 
-            if let Some(target_id) = enemy.target_player_id {
-                if let Some(target_player) = self.players.get(&target_id) {
-                    // Can't attack players in safe zone
-                    if target_player.is_in_safe_zone(self.config.safe_zone_radius) {
-                        continue;
-                    }
+        player.rerolls_left -= 1;
+        self.offer_upgrades(player_id);
+        Ok(())
+    }
 
-                    let distance = enemy.position.distance_to(&target_player.position);
-                    let melee_range = 50.0;
-                    if distance <= melee_range {
-                        // Apply damage
-                        if let Some(player) = self.players.get_mut(&target_id) {
-                            player.take_damage(enemy.damage);
+    /// Ban an upgrade from the current offer for the rest of the run and re-offer
+    pub fn banish_upgrade(&mut self, player_id: Uuid, upgrade: UpgradeType) -> Result<(), String> {
+        let offered = self
+            .pending_level_ups
+            .get(&player_id)
+            .is_some_and(|pending| pending.offered(upgrade).is_some());
+        if !offered {
+            return Err("That upgrade isn't on offer".to_string());
+        }
+        let player = self
+            .players
+            .get_mut(&player_id)
+            .ok_or_else(|| "Player not found".to_string())?;
+        if player.banishes_left == 0 {
+            return Err("No banishes left".to_string());
+        }
 
-                            if !player.is_alive() {
-                                tracing::info!("Player {} died", target_id);
-                            }
-                        }
+        player.banishes_left -= 1;
+        player.banished.push(upgrade);
+        self.offer_upgrades(player_id);
+        Ok(())
+    }
 
-                        // Update attack cooldown
-                        if let Some(e) = self.enemies.get_mut(&enemy_id) {
-                            e.last_attack_time = self.game_time;
-                        }
-                    }
-                }
+    /// Re-send open offers the player hasn't answered and resolve the ones
+    /// that have waited past `level_up_timeout`
+    pub fn update_level_ups(&mut self) {
+        let players = &self.players;
+        self.pending_level_ups
+            .retain(|id, _| players.get(id).is_some_and(|p| p.is_alive()));
+
+        let mut reminders = Vec::new();
+        let mut expired = Vec::new();
+        for (player_id, pending) in &self.pending_level_ups {
+            if self.game_time - pending.offered_at >= self.config.level_up_timeout as f64 {
+                expired.push(*player_id);
+            } else if self.game_time - pending.last_sent
+                >= self.config.level_up_resend_interval as f64
+            {
+                reminders.push(*player_id);
             }
         }
 
-        // Clean up dead enemies
-        self.enemies.retain(|_, e| e.is_alive());
-
-        // Dead players will be removed when connection drops
+        for player_id in reminders {
+            self.send_level_up(player_id);
+        }
+        for player_id in expired {
+            self.expire_level_up(player_id);
+        }
     }
 
-    /// Update projectiles and check collisions
-    pub fn update_projectiles(&mut self, delta_time: f32) {
-        let collision_radius = 20.0; // hit detection radius
-
-        // Update projectile positions
-        for projectile in self.projectiles.values_mut() {
-            projectile.update(delta_time);
+    /// Offer a level-up now, or queue it behind the one the player is
+    /// still deciding on
+    fn queue_level_up(&mut self, player_id: Uuid) {
+        match self.pending_level_ups.get_mut(&player_id) {
+            Some(pending) => pending.queued += 1,
+            None => self.offer_upgrades(player_id),
         }
+    }
 
-        // Check collisions with enemies
-        let projectile_ids: Vec<_> = self.projectiles.keys().cloned().collect();
-        for proj_id in projectile_ids {
-            let projectile = match self.projectiles.get(&proj_id) {
-                Some(p) => p.clone(),
-                None => continue,
-            };
+    /// Close the current offer and move on to the next queued one, if any
+    fn next_level_up(&mut self, player_id: Uuid) {
+        match self.pending_level_ups.get_mut(&player_id) {
+            Some(pending) if pending.queued > 0 => {
+                pending.queued -= 1;
+                self.offer_upgrades(player_id);
+            }
+            _ => {
+                self.pending_level_ups.remove(&player_id);
+            }
+        }
+    }
 
-            // Find hit enemy
-            if let Some((enemy_id, _)) = self
-                .enemies
-                .iter()
-                .filter(|(_, e)| e.is_alive())
-                .map(|(id, e)| (id, e.position.distance_to(&projectile.position)))
-                .filter(|(_, dist)| *dist <= collision_radius)
-                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
-            {
-                let enemy_id = *enemy_id;
-
-                // Get XP reward before killing enemy
-                let xp_reward = self.enemies.get(&enemy_id).map(|e| e.xp_reward).unwrap_or(0);
-
-                // Apply damage
-                if let Some(enemy) = self.enemies.get_mut(&enemy_id) {
-                    enemy.take_damage(projectile.damage);
-
-                    if !enemy.is_alive() {
-                        tracing::debug!("Projectile from player {} killed enemy {}", projectile.owner_id, enemy_id);
-                        if let Some(p) = self.players.get_mut(&projectile.owner_id) {
-                            p.enemies_defeated += 1;
-                            // Grant XP to player
-                            let leveled_up = p.grant_xp(xp_reward);
-                            if leveled_up {
-                                tracing::info!("Player {} leveled up to {}", projectile.owner_id, p.level);
-                                // Generate upgrade choices
-                                let choices = UpgradeType::random_choices(&[]);
-                                self.pending_level_ups.insert(projectile.owner_id, choices);
-                            }
-                        }
-                    }
-                }
+    fn expire_level_up(&mut self, player_id: Uuid) {
+        let Some(pending) = self.pending_level_ups.get(&player_id) else {
+            return;
+        };
 
-                // Remove projectile on hit
-                self.projectiles.remove(&proj_id);
+        let mut auto_picked = None;
+        if self.config.level_up_auto_pick {
+            let mut rng = self.run_rng(&[AUTO_PICK_RNG, self.tick, player_id.as_u64_pair().1]);
+            let index = rng.gen_range(0..pending.choices.len());
+            let choice = pending.choices[index];
+            if self.apply_choice(player_id, choice).is_ok() {
+                tracing::info!("Player {} timed out, auto-picked {:?}", player_id, choice);
+                auto_picked = Some(choice.upgrade);
             }
         }
 
-        // Remove expired projectiles
-        self.projectiles.retain(|_, p| p.is_alive());
+        self.send_to(
+            player_id,
+            ServerMessage::LevelUpExpired {
+                player_id,
+                auto_picked,
+            },
+        );
+        self.next_level_up(player_id);
+    }
+
+    /// Roll a new upgrade offer (respecting banishes) and send it to the player
+    fn offer_upgrades(&mut self, player_id: Uuid) {
+        let Some(player) = self.players.get(&player_id) else {
+            return;
+        };
+        let mut rng = self.run_rng(&[
+            UPGRADE_RNG,
+            player.level as u64,
+            player.rerolls_left as u64,
+            player.banished.len() as u64,
+        ]);
+        let luck = player.upgrades.luck_level;
+        let table = &self.config.upgrade_table;
+        let choices = table.roll(&player.banished, luck, UPGRADE_CHOICES, &mut rng);
+        let queued = self
+            .pending_level_ups
+            .get(&player_id)
+            .map_or(0, |pending| pending.queued);
+
+        let mut pending = PendingLevelUp::new(choices, self.game_time);
+        pending.queued = queued;
+        self.pending_level_ups.insert(player_id, pending);
+        self.send_level_up(player_id);
     }
 
-    /// Apply a chosen upgrade to a player
-    pub fn apply_upgrade(&mut self, player_id: Uuid, upgrade: UpgradeType) -> Result<(), String> {
-        // Remove pending level up
-        self.pending_level_ups.remove(&player_id);
+    fn send_level_up(&mut self, player_id: Uuid) {
+        let (Some(player), Some(pending)) = (
+            self.players.get(&player_id),
+            self.pending_level_ups.get_mut(&player_id),
+        ) else {
+            return;
+        };
+        pending.last_sent = self.game_time;
+
+        let locale = self.locales.get(&player_id).copied().unwrap_or_default();
+        let (table, catalog) = (&self.config.upgrade_table, &self.config.upgrade_catalog);
+        let choices = &pending.choices;
+        let message = ServerMessage::LevelUp {
+            player_id,
+            new_level: player.level,
+            upgrade_choices: choices.iter().map(|c| c.upgrade).collect(),
+            choice_rarities: choices.iter().map(|c| c.rarity).collect(),
+            choice_text: choices
+                .iter()
+                .map(|c| locale.choice_text(*c, table.levels(c.rarity), catalog))
+                .collect(),
+        };
+        self.send_to(player_id, message);
+    }
 
+    /// Record an upgrade on the player and update the stats it affects
+    fn apply_upgrade_stats(&mut self, player_id: Uuid, upgrade: UpgradeType) -> Result<(), String> {
         let player = self.players.get_mut(&player_id)
             .ok_or_else(|| "Player not found".to_string())?;
 
         player.upgrades.apply_upgrade(upgrade);
+        let catalog = &self.config.upgrade_catalog;
 
         // Apply stat changes immediately based on upgrade type
         match upgrade {
             UpgradeType::IncreaseDamage => {
-                player.damage = 10.0 * player.upgrades.damage_multiplier();
+                player.damage = 10.0 * player.upgrades.damage_multiplier(catalog);
             },
             UpgradeType::IncreaseAttackSpeed => {
-                player.attack_speed = 1.0 * player.upgrades.attack_speed_multiplier();
+                player.attack_speed = 1.0 * player.upgrades.attack_speed_multiplier(catalog);
             },
             UpgradeType::IncreaseMovementSpeed => {
-                player.movement_speed = 120.0 * player.upgrades.movement_speed_multiplier();
+                player.movement_speed = 120.0 * player.upgrades.movement_speed_multiplier(catalog);
+            },
+            UpgradeType::Luck => {
+                player.rerolls_left += 1;
+                player.banishes_left += 1;
+            },
+            UpgradeType::SecondWind if !player.second_wind_used => {
+                player.second_wind = player.upgrades.second_wind_health(catalog);
+            },
+            UpgradeType::CritChance => {
+                player.crit_chance =
+                    self.config.base_crit_chance + player.upgrades.crit_chance_bonus(catalog);
             },
             UpgradeType::IncreaseMaxHealth => {
                 let old_max = player.max_health;
-                player.max_health = 100.0 * (1.0 + player.upgrades.max_health_level as f32 * 0.25);
+                player.max_health =
+                    100.0 * player.upgrades.max_health_multiplier(catalog) + player.gear().max_health;
                 // Heal the difference
                 player.health += player.max_health - old_max;
             },
+            UpgradeType::HealthRegeneration => {
+                let per_second = catalog.bonus(upgrade, player.upgrades.regen_level);
+                let regen = EffectKind::Heal(per_second);
+                let now = self.game_time;
+                let effect = TimedEffect::periodic(player_id, regen, 1.0, f32::INFINITY, now);
+                self.effects.add(effect);
+            },
+            UpgradeType::Overshield => {
+                let shield = EffectKind::ShieldRecharge(player.upgrades.shield_capacity(catalog));
+                let (now, every) = (self.game_time, self.config.shield_recharge);
+                let recharge = TimedEffect::periodic(player_id, shield, every, f32::INFINITY, now);
+                // The first charge comes right away
+                self.effects.add(TimedEffect {
+                    next_at: now,
+                    ..recharge
+                });
+            },
+            UpgradeType::DamageAura | UpgradeType::AuraRadius if player.upgrades.aura_level > 0 => {
+                let upgrades = &player.upgrades;
+                let interval = self.config.aura_interval;
+                let aura = EffectKind::DamageAura {
+                    radius: self.config.aura_radius * upgrades.aura_radius_multiplier(catalog),
+                    damage: upgrades.aura_damage(catalog) * interval,
+                };
+                let now = self.game_time;
+                let effect = TimedEffect::periodic(player_id, aura, interval, f32::INFINITY, now);
+                self.effects.add(effect);
+            },
+            UpgradeType::SummonDrone | UpgradeType::SummonWolf => {
+                let summon_type = if upgrade == UpgradeType::SummonDrone {
+                    SummonType::AttackDrone
+                } else {
+                    SummonType::WolfPet
+                };
+                let summon = Summon::new(player_id, summon_type, player.position);
+                let event = spawned(EntityKind::Summon, summon.id, summon.position);
+                self.entity_events.push(event);
+                self.summons.insert(summon);
+            },
             _ => {
                 // Other upgrades are passive or handled elsewhere
             }
         }
 
-        tracing::info!("Player {} chose upgrade: {:?}", player_id, upgrade);
         Ok(())
     }
 
     /// Add a score entry to the leaderboard
     fn add_score(&mut self, score: ScoreEntry) {
-        self.scores.push(score);
+        self.add_scores(vec![score]);
+    }
+
+    /// Add score entries to the leaderboard, telling everyone if they
+    /// changed the top N. Runs already listed are skipped, so another
+    /// server's board can be merged in.
+    pub fn add_scores(&mut self, scores: Vec<ScoreEntry>) {
+        let before = self.standings().to_vec();
+        for score in scores {
+            if self.standings().iter().any(|s| s.same_run(&score)) {
+                continue;
+            }
+            if self.config.mode == GameMode::Daily {
+                self.daily.add(score, self.config.max_scoreboard_entries);
+                continue;
+            }
+            self.scores.push(score);
+
+            // Sort by the mode's score, descending
+            let mode = self.config.mode;
+            self.scores
+                .sort_by_key(|s| std::cmp::Reverse(mode.score(s)));
 
-        // Sort by score descending
-        self.scores
-            .sort_by_key(|s| std::cmp::Reverse(s.total_score()));
+            // Keep only top N
+            self.scores.truncate(self.config.max_scoreboard_entries);
+        }
+
+        let changes = RankChange::between(&before, self.standings());
+        if !changes.is_empty() {
+            self.game_events
+                .publish(GameEvent::ScoreboardChanged { changes });
+        }
+    }
 
-        // Keep only top N
-        self.scores.truncate(self.config.max_scoreboard_entries);
+    /// The scoreboard this mode ranks runs on
+    pub fn standings(&self) -> &[ScoreEntry] {
+        if self.config.mode == GameMode::Daily {
+            &self.daily.standings
+        } else {
+            &self.scores
+        }
     }
 
     /// Get top scores
@@ -387,3 +3842,46 @@ impl GameState {
         self.scores.iter().take(limit).cloned().collect()
     }
 }
+
+/// Where the closest targetable enemy within `range` of `from` is
+fn nearest_enemy(enemies: &EntityMap<Enemy>, from: &Position, range: f32) -> Option<Position> {
+    enemies
+        .values()
+        .filter(|e| e.is_targetable())
+        .map(|e| (e.position, e.position.distance_to(from)))
+        .filter(|(_, distance)| *distance <= range)
+        .min_by(|a, b| cmp_distance(&a.1, &b.1))
+        .map(|(position, _)| position)
+}
+
+fn spawned(kind: EntityKind, id: Uuid, position: Position) -> EntityEvent {
+    EntityEvent::Spawned { id, kind, position }
+}
+
+fn despawned(kind: EntityKind, id: Uuid, position: Position, reason: DespawnReason) -> EntityEvent {
+    EntityEvent::Despawned {
+        id,
+        kind,
+        position,
+        reason,
+    }
+}
+
+/// Velocity over one tick from `last` to `now`, and the facing it implies.
+/// Standing still keeps the old facing; with no `last` (just spawned or
+/// teleported) the velocity is zero.
+fn motion(last: Option<Position>, now: Position, facing: f32, delta_time: f32) -> (Vec2, f32) {
+    let Some(last) = last else {
+        return (Vec2::ZERO, facing);
+    };
+    let velocity = (now - last) / delta_time;
+    if now == last {
+        (velocity, facing)
+    } else {
+        (velocity, velocity.angle())
+    }
+}
+
+#[cfg(test)]
+#[path = "tests.rs"]
+mod tests;
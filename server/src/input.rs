@@ -0,0 +1,58 @@
+//! Movement inputs are queued per player as they arrive and applied at the
+//! start of the next tick, at most one move per tick. Each applied input's
+//! sequence number is echoed as `Player::last_processed_input`, so a
+//! predicting client knows which of its inputs a snapshot's position
+//! already reflects and can replay the rest on top of it.
+
+use shared::{Position, Vec2};
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputKind {
+    Move { target: Position },
+    Dash { direction: Vec2 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueuedInput {
+    pub seq: u32,
+    pub kind: InputKind,
+}
+
+/// One player's inputs waiting for a tick, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct InputQueue {
+    inputs: VecDeque<QueuedInput>,
+}
+
+impl InputQueue {
+    /// Add an input, dropping the oldest once `capacity` are waiting so a
+    /// client sending faster than the tick rate can't build up lag. True
+    /// if any were dropped.
+    pub fn push(&mut self, input: QueuedInput, capacity: usize) -> bool {
+        self.inputs.push_back(input);
+        let mut dropped = false;
+        while self.inputs.len() > capacity.max(1) {
+            self.inputs.pop_front();
+            dropped = true;
+        }
+        dropped
+    }
+
+    /// This tick's inputs: everything up to and including the next move.
+    /// Dashes don't use up the tick's move.
+    pub fn take_tick(&mut self) -> Vec<QueuedInput> {
+        let mut taken = Vec::new();
+        while let Some(input) = self.inputs.pop_front() {
+            taken.push(input);
+            if matches!(input.kind, InputKind::Move { .. }) {
+                break;
+            }
+        }
+        taken
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+}
@@ -0,0 +1,129 @@
+//! Run integrity checks. The server simulates every run itself, so a
+//! client can't send a made-up score, but a modified one can still flood
+//! inputs or script its play. Each run that would reach the scoreboard is
+//! checked against `integrity_limits` first; one that breaks a limit is
+//! held for an admin to approve or reject instead of being listed.
+
+use serde::{Deserialize, Serialize};
+use shared::{Player, ScoreEntry};
+use uuid::Uuid;
+
+/// Held scores kept at most; the oldest is dropped to make room
+pub const MAX_HELD_SCORES: usize = 200;
+
+/// Input rates are counted per window of this many seconds
+const WINDOW_SECONDS: f64 = 1.0;
+
+/// Runs shorter than this are judged as if they'd lasted this long, so a
+/// lucky first few seconds aren't extrapolated into an absurd rate
+const MIN_RUN_MINUTES: f32 = 1.0;
+
+/// What a run may plausibly reach. 0 turns a check off.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IntegrityLimits {
+    pub max_inputs_per_second: u32, // Move and Dash inputs, in the busiest second
+    pub max_xp_per_minute: f32,
+    pub max_kills_per_minute: f32,
+}
+
+impl Default for IntegrityLimits {
+    fn default() -> Self {
+        Self {
+            max_inputs_per_second: 40, // the web client sends 20
+            max_xp_per_minute: 20_000.0,
+            max_kills_per_minute: 300.0,
+        }
+    }
+}
+
+/// How fast a player has been sending inputs
+#[derive(Debug, Clone, Default)]
+pub struct InputRate {
+    pub total: u64,
+    pub peak: u32, // most inputs in one window
+    window_start: f64,
+    in_window: u32,
+}
+
+impl InputRate {
+    /// Count an input received at game time `now`
+    pub fn record(&mut self, now: f64) {
+        if now - self.window_start >= WINDOW_SECONDS {
+            self.window_start = now;
+            self.in_window = 0;
+        }
+        self.in_window += 1;
+        self.total += 1;
+        self.peak = self.peak.max(self.in_window);
+    }
+}
+
+/// One run's rates and the limits they broke, kept with its score
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntegrityRecord {
+    pub player_id: Uuid,
+    pub run_seconds: f32,
+    pub inputs: u64,
+    pub peak_inputs_per_second: u32,
+    pub xp_per_minute: f32,
+    pub kills_per_minute: f32,
+    pub flags: Vec<String>, // empty for a clean run
+}
+
+impl IntegrityRecord {
+    pub fn check(
+        player: &Player,
+        inputs: Option<&InputRate>,
+        run_seconds: f32,
+        limits: &IntegrityLimits,
+    ) -> Self {
+        let minutes = (run_seconds / 60.0).max(MIN_RUN_MINUTES);
+        let inputs = inputs.cloned().unwrap_or_default();
+        let mut record = Self {
+            player_id: player.id,
+            run_seconds,
+            inputs: inputs.total,
+            peak_inputs_per_second: inputs.peak,
+            xp_per_minute: player.stats.xp_collected as f32 / minutes,
+            kills_per_minute: player.enemies_defeated as f32 / minutes,
+            flags: Vec::new(),
+        };
+
+        let max_inputs = limits.max_inputs_per_second;
+        if max_inputs > 0 && record.peak_inputs_per_second > max_inputs {
+            let peak = record.peak_inputs_per_second;
+            record
+                .flags
+                .push(format!("{peak} inputs in one second, over {max_inputs}"));
+        }
+        if limits.max_xp_per_minute > 0.0 && record.xp_per_minute > limits.max_xp_per_minute {
+            record.flags.push(format!(
+                "{:.0} XP per minute, over {:.0}",
+                record.xp_per_minute, limits.max_xp_per_minute
+            ));
+        }
+        if limits.max_kills_per_minute > 0.0
+            && record.kills_per_minute > limits.max_kills_per_minute
+        {
+            record.flags.push(format!(
+                "{:.0} kills per minute, over {:.0}",
+                record.kills_per_minute, limits.max_kills_per_minute
+            ));
+        }
+        record
+    }
+
+    pub fn is_suspicious(&self) -> bool {
+        !self.flags.is_empty()
+    }
+}
+
+/// A score kept off the scoreboard until an admin has looked at it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeldScore {
+    pub id: Uuid,
+    pub score: ScoreEntry,
+    pub records: Vec<IntegrityRecord>, // one per player in the run
+    pub held_at: chrono::DateTime<chrono::Utc>,
+}
@@ -0,0 +1,53 @@
+//! Who gets what when several players wear an enemy down. Every hit is
+//! tallied on the enemy (`Enemy::damage_by`); by default the XP is split
+//! in proportion to it and the kill goes to whoever dealt the most, so a
+//! party member tanking with an aura isn't outdone by whoever happened to
+//! land the last shot. `KillCredit::LastHit` keeps the old rule.
+
+use serde::{Deserialize, Serialize};
+use shared::Enemy;
+use uuid::Uuid;
+
+/// How a kill is credited, set by `kill_credit` in the config
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KillCredit {
+    /// XP split by damage dealt; the kill to the top damage dealer
+    #[default]
+    Proportional,
+    /// All of it to the player whose hit finished the enemy off
+    LastHit,
+}
+
+/// The player credited with killing `enemy`, which `killer` finished off.
+/// Ties go to the killer.
+pub fn credited(enemy: &Enemy, killer: Uuid, credit: KillCredit) -> Uuid {
+    if credit == KillCredit::LastHit {
+        return killer;
+    }
+    let dealt_by_killer = enemy.damage_by.get(&killer).copied().unwrap_or(0.0);
+    enemy
+        .damage_by
+        .iter()
+        .filter(|&(_, &dealt)| dealt > dealt_by_killer)
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map_or(killer, |(id, _)| *id)
+}
+
+/// Each player's XP for killing `enemy`, which `killer` finished off,
+/// rounded to whole points. An enemy nobody recorded damage on, like one
+/// killed in a single blow, is all the killer's.
+pub fn xp_shares(enemy: &Enemy, killer: Uuid, credit: KillCredit) -> Vec<(Uuid, u32)> {
+    let total: f32 = enemy.damage_by.values().sum();
+    if credit == KillCredit::LastHit || total <= 0.0 {
+        return vec![(killer, enemy.xp_reward)];
+    }
+    let mut shares: Vec<(Uuid, u32)> = enemy
+        .damage_by
+        .iter()
+        .map(|(id, dealt)| (*id, (enemy.xp_reward as f32 * dealt / total).round() as u32))
+        .filter(|&(_, xp)| xp > 0)
+        .collect();
+    // Grant in a fixed order, so replays publish level-ups alike
+    shares.sort_by_key(|&(id, _)| id);
+    shares
+}
@@ -0,0 +1,28 @@
+use shared::{UpgradeChoice, UpgradeType};
+
+/// A player's unresolved level-ups: the offer currently on screen, plus any
+/// earned while it was waiting. Queued ones are rolled when they come up so
+/// they respect banishes made in the meantime.
+#[derive(Debug, Clone)]
+pub struct PendingLevelUp {
+    pub choices: Vec<UpgradeChoice>,
+    pub queued: u32,
+    pub offered_at: f64, // game time the current offer was made
+    pub last_sent: f64,  // game time the offer was last (re)sent
+}
+
+impl PendingLevelUp {
+    pub fn new(choices: Vec<UpgradeChoice>, now: f64) -> Self {
+        Self {
+            choices,
+            queued: 0,
+            offered_at: now,
+            last_sent: now,
+        }
+    }
+
+    /// The offered choice of `upgrade`, if there is one
+    pub fn offered(&self, upgrade: UpgradeType) -> Option<UpgradeChoice> {
+        self.choices.iter().copied().find(|c| c.upgrade == upgrade)
+    }
+}
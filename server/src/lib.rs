@@ -0,0 +1,46 @@
+//! Game server: the simulation, its WebSocket front end and persistence.
+//!
+//! The `server` binary wires these together; integration tests under
+//! `tests/` drive the router directly.
+
+pub mod access;
+pub mod admin;
+pub mod bandwidth;
+#[cfg(feature = "redis")]
+pub mod cluster;
+#[cfg(feature = "lua")]
+pub mod boss_script;
+pub mod collision;
+pub mod config;
+pub mod daily;
+pub mod diagnostics;
+pub mod effects;
+pub mod enemy_ai;
+pub mod events;
+pub mod entity_map;
+pub mod game_loop;
+pub mod game_mode;
+pub mod game_state;
+pub mod input;
+pub mod integrity;
+pub mod kill_credit;
+pub mod level_up;
+pub mod log_tail;
+pub mod match_history;
+pub mod network;
+pub mod party;
+pub mod persistence;
+pub mod plugin;
+pub mod replay;
+pub mod rooms;
+pub mod runs;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod signals;
+pub mod snapshot;
+pub mod threat;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod transport;
+pub mod validation;
+
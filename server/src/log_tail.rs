@@ -0,0 +1,93 @@
+//! The latest log lines, kept in memory for the admin dashboard. Installed
+//! as a `tracing` layer next to the normal log output; the dashboard polls
+//! `GET /admin/logs?after=<seq>` for lines it hasn't shown yet.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Lines kept; older ones are dropped
+const CAPACITY: usize = 500;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    pub seq: u64, // increases by one per line, for polling
+    pub time: chrono::DateTime<chrono::Utc>,
+    pub level: String,
+    pub target: String,
+    pub message: String, // with the event's other fields appended as key=value
+}
+
+/// Shared handle to the buffer; clones see the same lines
+#[derive(Debug, Clone, Default)]
+pub struct LogTail {
+    inner: Arc<Mutex<Buffer>>,
+}
+
+#[derive(Debug, Default)]
+struct Buffer {
+    lines: VecDeque<LogLine>,
+    next_seq: u64,
+}
+
+impl LogTail {
+    /// Lines with `seq` greater than `after`, oldest first
+    pub fn since(&self, after: Option<u64>) -> Vec<LogLine> {
+        let buffer = self.inner.lock().unwrap();
+        buffer
+            .lines
+            .iter()
+            .filter(|line| after.is_none_or(|after| line.seq > after))
+            .cloned()
+            .collect()
+    }
+
+    fn push(&self, level: String, target: String, message: String) {
+        let mut buffer = self.inner.lock().unwrap();
+        let seq = buffer.next_seq;
+        buffer.next_seq += 1;
+        buffer.lines.push_back(LogLine {
+            seq,
+            time: chrono::Utc::now(),
+            level,
+            target,
+            message,
+        });
+        if buffer.lines.len() > CAPACITY {
+            buffer.lines.pop_front();
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogTail {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        self.push(
+            metadata.level().to_string(),
+            metadata.target().to_string(),
+            visitor.message,
+        );
+    }
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let fields = std::mem::take(&mut self.message);
+            let _ = write!(self.message, "{:?}{}", value, fields);
+        } else {
+            let _ = write!(self.message, " {}={:?}", field.name(), value);
+        }
+    }
+}
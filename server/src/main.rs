@@ -1,57 +1,192 @@
-mod config;
-mod game_loop;
-mod game_state;
-mod network;
+mod cli;
 
-use config::GameConfig;
-use game_state::GameState;
+use clap::Parser;
+use cli::{Cli, Command, LogFormat, ReplayArgs, ServeArgs};
+use server::access::BanList;
+use server::config::GameConfig;
+use server::game_state::GameState;
+use server::log_tail::LogTail;
+use server::match_history::{self, MatchHistory};
+use server::plugin::GamePlugin;
+use server::{game_loop, network, persistence, plugin, transport};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
     // Initialize tracing
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "server=debug,tower_http=debug".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "server=debug,tower_http=debug".into());
+    let fmt = match cli.log_format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+    };
+    // The filter only applies to the log output, so tokio's own task spans
+    // still reach tokio-console
+    let registry = tracing_subscriber::registry().with(fmt.with_filter(filter));
+    // The admin dashboard's log view: our own info and up, anyone's warnings
+    let log_tail = LogTail::default();
+    let tail_filter = Targets::new()
+        .with_default(LevelFilter::WARN)
+        .with_target("server", LevelFilter::INFO);
+    let registry = registry.with(log_tail.clone().with_filter(tail_filter));
+    #[cfg(feature = "console")]
+    let registry = registry.with(console_subscriber::spawn());
+    registry.init();
 
+    match cli.into_command() {
+        Command::Serve(args) => serve(args, log_tail).await,
+        Command::ValidateConfig(args) => {
+            let problems = args.load()?.problems();
+            if problems.is_empty() {
+                println!("Configuration OK");
+                return Ok(());
+            }
+            for problem in &problems {
+                println!("{}", problem);
+            }
+            anyhow::bail!("{} problem(s) in the configuration", problems.len())
+        }
+        Command::Replay(args) => replay(args),
+    }
+}
+
+async fn serve(args: ServeArgs, log_tail: LogTail) -> anyhow::Result<()> {
     tracing::info!("Starting game server...");
 
     // Load configuration
-    let config = GameConfig::default();
+    let mut config = args.config.load()?;
+    if let Some(problem) = config.problems().first() {
+        anyhow::bail!("invalid configuration: {}", problem);
+    }
+    let mut scripts = load_scripts(&mut config)?;
+    scripts.extend(load_boss_scripts(&config)?);
     tracing::info!("Game configuration: {:?}", config);
 
-    // Initialize game state
-    let game_state = Arc::new(RwLock::new(GameState::new(config)));
+    let snapshot_path = PathBuf::from(&config.snapshot_path);
+    let snapshot_interval = config.snapshot_interval;
+    let bans = BanList::load(config.ban_list_path.as_ref())?;
+    let history_path = PathBuf::from(&config.match_history_path);
+    let match_history = MatchHistory::load(&history_path, config.max_match_history)?;
+    let tls_paths = config.tls_cert.clone().zip(config.tls_key.clone());
 
-    // Start game loop
-    let game_loop_handle = {
+    // Initialize game state, picking up the saved world with --resume
+    let mut state = GameState::new(config);
+    state.set_bans(bans);
+    state.set_match_history(match_history);
+    state.set_log_tail(log_tail);
+    for plugin in plugin::installed().into_iter().chain(scripts) {
+        state.register_plugin(plugin);
+    }
+    if let Some(resume) = args.resume {
+        let path = resume.unwrap_or_else(|| snapshot_path.clone());
+        let snapshot = persistence::load(&path)?;
+        tracing::info!("Resuming world saved at {}", snapshot.saved_at);
+        state.restore_world(snapshot);
+    }
+    let game_state = Arc::new(RwLock::new(state));
+
+    // Periodic world snapshots
+    if snapshot_interval > 0.0 {
         let state = game_state.clone();
-        tokio::spawn(async move {
-            game_loop::run_game_loop(state).await;
-        })
-    };
+        let path = snapshot_path.clone();
+        let interval = Duration::from_secs_f32(snapshot_interval);
+        tokio::spawn(persistence::run_autosave(state, path, interval));
+    }
 
-    // Create router
-    let app = network::create_router(game_state);
+    // Append finished matches to the history file
+    tokio::spawn(match_history::run_writer(
+        game_state.clone(),
+        history_path.clone(),
+    ));
 
-    // Configure server address
-    let addr = std::env::var("SERVER_ADDR").unwrap_or_else(|_| "0.0.0.0:3000".to_string());
+    // Close rooms opened by POST /rooms once they've sat empty
+    tokio::spawn(server::rooms::run_sweeper(game_state.clone()));
 
+    // Start game loop; panics inside it are caught and the loop restarted
+    let game_loop_handle = tokio::spawn(game_loop::supervise(game_state.clone()));
+
+    // Create router
+    let app = network::create_router(game_state.clone()).await;
+
+    let addr = args.bind;
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    tracing::info!("Server listening on {}", addr);
 
-    // Run server
-    let server_handle = tokio::spawn(async move {
-        axum::serve(listener, app).await.expect("Server failed");
-    });
+    // Run server, terminating TLS ourselves when a certificate is configured
+    let server_handle = match tls_paths {
+        #[cfg(feature = "tls")]
+        Some((cert, key)) => {
+            let tls = server::tls::load(&cert, &key).await?;
+            tracing::info!("Server listening on {} (TLS)", addr);
+            tokio::spawn(async move {
+                server::tls::serve(listener, tls, app)
+                    .await
+                    .expect("Server failed");
+            })
+        }
+        #[cfg(not(feature = "tls"))]
+        Some(_) => anyhow::bail!(
+            "a TLS certificate is configured but the server was built without the tls feature"
+        ),
+        None => {
+            tracing::info!("Server listening on {}", addr);
+            tokio::spawn(async move {
+                let app = app.into_make_service_with_connect_info::<SocketAddr>();
+                axum::serve(listener, app).await.expect("Server failed");
+            })
+        }
+    };
+
+    // Optional leaderboard and announcements shared with other servers
+    let redis_url = game_state.read().await.config().redis_url.clone();
+    match redis_url {
+        #[cfg(feature = "redis")]
+        Some(url) => {
+            tokio::spawn(server::cluster::run(game_state.clone(), url));
+        }
+        #[cfg(not(feature = "redis"))]
+        Some(_) => anyhow::bail!(
+            "redis_url is configured but the server was built without the redis feature"
+        ),
+        None => {}
+    }
+
+    // Optional UDP channel for snapshots and inputs
+    if let Some(port) = game_state.read().await.config().udp_port {
+        let socket = tokio::net::UdpSocket::bind(("0.0.0.0", port)).await?;
+        tracing::info!("UDP listening on port {}", port);
+        tokio::spawn(transport::udp::serve(socket, game_state.clone()));
+    }
+
+    // Optional QUIC transport next to the WebSocket one
+    #[cfg(feature = "quic")]
+    if let Some(quic_addr) = args.quic_addr {
+        use server::transport::quic;
 
-    // Wait for either task to complete (they shouldn't)
+        let certificate = match (&args.quic_cert, &args.quic_key) {
+            (Some(cert), Some(key)) => quic::load_certificate(cert, key)?,
+            _ => {
+                tracing::warn!("--quic-cert/--quic-key not set, using a self-signed certificate");
+                quic::self_signed()?
+            }
+        };
+        let endpoint = quic::bind(quic_addr, certificate)?;
+        tracing::info!("QUIC listening on {}", quic_addr);
+        tokio::spawn(quic::serve(endpoint, game_state.clone()));
+    }
+
+    // Run until Ctrl+C, or until either task dies (they shouldn't)
     tokio::select! {
         _ = game_loop_handle => {
             tracing::error!("Game loop terminated unexpectedly");
@@ -59,7 +194,97 @@ async fn main() -> anyhow::Result<()> {
         _ = server_handle => {
             tracing::error!("Server terminated unexpectedly");
         }
+        _ = tokio::signal::ctrl_c() => {
+            tracing::info!("Shutting down");
+        }
     }
 
+    if let Err(e) = persistence::save(&game_state, &snapshot_path).await {
+        tracing::error!("Failed to save world snapshot: {:#}", e);
+    }
+    if let Err(e) = match_history::flush(&game_state, &history_path).await {
+        tracing::error!("Failed to save match history: {:#}", e);
+    }
+
+    Ok(())
+}
+
+/// Compile the configured rule scripts, letting each adjust the config
+/// before the game starts
+#[cfg(feature = "scripting")]
+fn load_scripts(config: &mut GameConfig) -> anyhow::Result<Vec<Box<dyn GamePlugin>>> {
+    let mut scripts: Vec<Box<dyn GamePlugin>> = Vec::new();
+    for path in config.scripts.clone() {
+        let mut script = server::scripting::RuleScript::load(path.as_ref(), config)?;
+        script.configure(config);
+        scripts.push(Box::new(script));
+    }
+    Ok(scripts)
+}
+
+#[cfg(not(feature = "scripting"))]
+fn load_scripts(config: &mut GameConfig) -> anyhow::Result<Vec<Box<dyn GamePlugin>>> {
+    if !config.scripts.is_empty() {
+        anyhow::bail!(
+            "rule scripts are configured but the server was built without the scripting feature"
+        );
+    }
+    Ok(Vec::new())
+}
+
+/// The director for the configured Lua boss fights, if any
+#[cfg(feature = "lua")]
+fn load_boss_scripts(config: &GameConfig) -> anyhow::Result<Vec<Box<dyn GamePlugin>>> {
+    let Some(dir) = &config.boss_scripts else {
+        return Ok(Vec::new());
+    };
+    if !std::path::Path::new(dir).is_dir() {
+        anyhow::bail!("boss script directory {} not found", dir);
+    }
+    Ok(vec![Box::new(server::boss_script::BossDirector::new(dir))])
+}
+
+#[cfg(not(feature = "lua"))]
+fn load_boss_scripts(config: &GameConfig) -> anyhow::Result<Vec<Box<dyn GamePlugin>>> {
+    if config.boss_scripts.is_some() {
+        anyhow::bail!(
+            "boss scripts are configured but the server was built without the lua feature"
+        );
+    }
+    Ok(Vec::new())
+}
+
+/// Step a saved world without any network, to reproduce a crash from the
+/// snapshot the supervisor wrote. Runs on the config saved with it.
+fn replay(args: ReplayArgs) -> anyhow::Result<()> {
+    let snapshot = persistence::load(&args.file)?;
+    println!(
+        "Replaying {} (saved at {}) for {} ticks",
+        args.file.display(),
+        snapshot.saved_at,
+        args.ticks
+    );
+    let mut state = GameState::new(snapshot.config.clone());
+    state.restore_world(snapshot);
+
+    let delta_time = 1.0 / state.config().tick_rate as f32;
+    let (mut slowest_stage, mut slowest_ms) = ("", 0.0);
+    for _ in 0..args.ticks {
+        game_loop::step(&mut state, delta_time);
+        let stats = state.tick_stats();
+        if stats.slowest_stage_ms > slowest_ms {
+            (slowest_stage, slowest_ms) = (stats.slowest_stage, stats.slowest_stage_ms);
+        }
+    }
+    let counts = state.entity_counts();
+    println!(
+        "Finished at tick {} with {} enemies, {} projectiles and {} pickups; slowest stage {} at {:.2}ms",
+        state.tick(),
+        counts.enemies,
+        counts.projectiles,
+        counts.pickups,
+        slowest_stage,
+        slowest_ms
+    );
     Ok(())
 }
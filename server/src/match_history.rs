@@ -0,0 +1,245 @@
+//! Finished matches: kept in memory for `GET /matches` and appended to
+//! `match_history_path` (one JSON record per line) so they outlive the
+//! process.
+//!
+//! Endless mode has no match end, so there every run is its own record,
+//! written when the player leaves. The other modes write one record per
+//! match, including players who left before the end.
+
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use shared::Player;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::game_state::SharedGameState;
+use crate::integrity::IntegrityRecord;
+
+/// How often new records are appended to the history file
+const WRITE_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchRecord {
+    pub id: Uuid,
+    pub mode: String,
+    pub seed: u64,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub ended_at: chrono::DateTime<chrono::Utc>,
+    pub duration_seconds: f32,
+    pub outcome: String,
+    pub winners: Vec<Uuid>,
+    pub bosses_killed: u32,
+    pub participants: Vec<Participant>,
+}
+
+/// One player's final stats for the match
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Participant {
+    pub player_id: Uuid,
+    pub level: u32,
+    pub max_ring_reached: u32,
+    pub enemies_defeated: u32,
+    pub bosses_defeated: u32,
+    pub players_defeated: u32,
+    pub gold: u32,
+    pub survived: bool,
+    pub left_early: bool, // disconnected before the match ended
+    #[serde(default)]
+    pub integrity: Option<IntegrityRecord>, // how plausible the run looked
+}
+
+impl Participant {
+    pub fn from_player(player: &Player, left_early: bool) -> Self {
+        Self {
+            player_id: player.id,
+            level: player.level,
+            max_ring_reached: player.max_ring_reached,
+            enemies_defeated: player.enemies_defeated,
+            bosses_defeated: player.bosses_defeated,
+            players_defeated: player.players_defeated,
+            gold: player.gold,
+            survived: player.is_alive(),
+            left_early,
+            integrity: None,
+        }
+    }
+}
+
+/// A `MatchRecord` without its participant list, for `GET /matches`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchSummary {
+    pub id: Uuid,
+    pub mode: String,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub duration_seconds: f32,
+    pub outcome: String,
+    pub participants: usize,
+    pub bosses_killed: u32,
+}
+
+impl From<&MatchRecord> for MatchSummary {
+    fn from(record: &MatchRecord) -> Self {
+        Self {
+            id: record.id,
+            mode: record.mode.clone(),
+            started_at: record.started_at,
+            duration_seconds: record.duration_seconds,
+            outcome: record.outcome.clone(),
+            participants: record.participants.len(),
+            bosses_killed: record.bosses_killed,
+        }
+    }
+}
+
+/// The latest `capacity` matches, plus those not yet written to disk
+#[derive(Debug, Clone, Default)]
+pub struct MatchHistory {
+    records: VecDeque<MatchRecord>,
+    unsaved: Vec<MatchRecord>,
+    capacity: usize,
+}
+
+impl MatchHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Self::default()
+        }
+    }
+
+    /// Read the newest `capacity` records from the file `append` writes;
+    /// a missing file is an empty history
+    pub fn load(path: &std::path::Path, capacity: usize) -> anyhow::Result<Self> {
+        let mut history = Self::new(capacity);
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(history),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+        };
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = serde_json::from_str(line)
+                .with_context(|| format!("parsing {} line {}", path.display(), number + 1))?;
+            history.keep(record);
+        }
+        Ok(history)
+    }
+
+    pub fn push(&mut self, record: MatchRecord) {
+        self.unsaved.push(record.clone());
+        self.keep(record);
+    }
+
+    fn keep(&mut self, record: MatchRecord) {
+        self.records.push_back(record);
+        while self.records.len() > self.capacity {
+            self.records.pop_front();
+        }
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<&MatchRecord> {
+        self.records.iter().find(|record| record.id == id)
+    }
+
+    /// Newest first
+    pub fn summaries(&self) -> Vec<MatchSummary> {
+        self.records.iter().rev().map(MatchSummary::from).collect()
+    }
+
+    /// Records pushed since the last call, oldest first
+    pub fn take_unsaved(&mut self) -> Vec<MatchRecord> {
+        std::mem::take(&mut self.unsaved)
+    }
+
+    /// Put back records from `take_unsaved` that couldn't be written
+    fn return_unsaved(&mut self, mut records: Vec<MatchRecord>) {
+        records.append(&mut self.unsaved);
+        self.unsaved = records;
+    }
+}
+
+/// Append `records` to `path`, one JSON object per line
+pub async fn append(path: &std::path::Path, records: &[MatchRecord]) -> anyhow::Result<()> {
+    let mut lines = Vec::new();
+    for record in records {
+        serde_json::to_writer(&mut lines, record)?;
+        lines.push(b'\n');
+    }
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .with_context(|| format!("opening {}", path.display()))?;
+    // tokio writes on a background thread; flushing waits for it to finish
+    file.write_all(&lines)
+        .await
+        .with_context(|| format!("writing {}", path.display()))?;
+    file.flush()
+        .await
+        .with_context(|| format!("writing {}", path.display()))?;
+    Ok(())
+}
+
+/// Write out records finished since the last call. On failure they're put
+/// back to retry next time.
+pub async fn flush(state: &SharedGameState, path: &std::path::Path) -> anyhow::Result<()> {
+    let records = state.write().await.match_history_mut().take_unsaved();
+    if records.is_empty() {
+        return Ok(());
+    }
+    if let Err(e) = append(path, &records).await {
+        state
+            .write()
+            .await
+            .match_history_mut()
+            .return_unsaved(records);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Append finished matches to `path` for as long as the server runs
+pub async fn run_writer(state: SharedGameState, path: PathBuf) {
+    let mut timer = tokio::time::interval(WRITE_INTERVAL);
+    loop {
+        timer.tick().await;
+        if let Err(e) = flush(&state, &path).await {
+            tracing::error!("Failed to save match history: {:#}", e);
+        }
+    }
+}
+
+/// `GET /matches` (newest first) and `GET /matches/{id}`
+pub fn router() -> Router<SharedGameState> {
+    Router::new()
+        .route("/matches", get(list_matches))
+        .route("/matches/{id}", get(get_match))
+}
+
+async fn list_matches(State(state): State<SharedGameState>) -> Json<Vec<MatchSummary>> {
+    Json(state.read().await.match_history().summaries())
+}
+
+async fn get_match(
+    State(state): State<SharedGameState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<MatchRecord>, StatusCode> {
+    let game = state.read().await;
+    game.match_history()
+        .get(id)
+        .cloned()
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
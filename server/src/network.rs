@@ -1,108 +1,398 @@
 use axum::{
-    extract::{
-        ws::{Message, WebSocket},
-        State, WebSocketUpgrade,
-    },
-    response::Response,
+    extract::{ConnectInfo, Query, State, WebSocketUpgrade},
+    http::{HeaderMap, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Redirect, Response},
     routing::get,
-    Router,
+    Json, Router,
 };
-use futures_util::{stream::StreamExt, SinkExt};
-use shared::{ClientMessage, ServerMessage};
+use bytes::Bytes;
+use serde::Deserialize;
+use shared::{
+    ClientMessage, Envelope, ErrorCode, ServerInfo, ServerMessage, ACCOUNT_HEADER,
+    DEFLATE_PROTOCOL, PASSWORD_HEADER, PROTOCOL_VERSION,
+};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, RwLock};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::services::ServeDir;
+use tracing::Instrument;
 use uuid::Uuid;
 
-use crate::game_state::SharedGameState;
+use crate::access::{self, Handshake, Rejection};
+use crate::admin;
+use crate::bandwidth::BandwidthBudget;
+use crate::daily;
+use crate::diagnostics::{self, LockSite};
+use crate::game_state::{GameState, JoinOutcome, SharedGameState};
+use crate::match_history;
+use crate::rooms::{self, RoomRejection, MAIN_ROOM};
+use crate::runs;
+use crate::snapshot::SnapshotBuffer;
+use crate::transport::{websocket, Delivery, MessageSink, MessageSource};
+use crate::validation::{self, MessageError};
+
+/// The HTTP routes, plus the web client at `/play` if `client_dir` is set
+/// and `/admin` and `/debug` if `admin_token` is, under `base_path` and
+/// behind CORS as configured. Serve it with connect info (`into_make_service_with_connect_info::<SocketAddr>`)
+/// so sessions know the client's address.
+pub async fn create_router(state: SharedGameState) -> Router {
+    let (base_path, cors, client_dir, admin_token) = {
+        let game = state.read().await;
+        (
+            normalize_base_path(&game.config().base_path),
+            cors_layer(&game.config().cors_origins),
+            game.config().client_dir.clone(),
+            game.config().admin_token.clone(),
+        )
+    };
 
-pub fn create_router(state: SharedGameState) -> Router {
-    Router::new()
+    let mut routes = Router::new()
         .route("/ws", get(ws_handler))
         .route("/health", get(health_check))
-        .with_state(state)
+        .route("/info", get(server_info))
+        .merge(match_history::router())
+        .merge(runs::router())
+        .merge(rooms::router())
+        .merge(daily::router());
+    if let Some(dir) = client_dir {
+        // The page loads `pkg/...` relative to itself, so it needs the slash
+        let index = format!("{}/play/", base_path);
+        routes = routes
+            .route(
+                "/play",
+                get(move || async move { Redirect::permanent(&index) }),
+            )
+            .nest_service("/play/", ServeDir::new(dir));
+    }
+    if let Some(token) = admin_token {
+        routes = routes.merge(admin::router(token));
+    }
+    let mut router = if base_path.is_empty() {
+        routes
+    } else {
+        Router::new().nest(&base_path, routes)
+    };
+    if let Some(cors) = cors {
+        router = router.layer(cors);
+    }
+    router.with_state(state)
+}
+
+/// `"game/"` -> `"/game"`; empty for the root
+pub(crate) fn normalize_base_path(path: &str) -> String {
+    let trimmed = path.trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
+/// `None` when no origins are configured, which leaves browsers enforcing
+/// same-origin as before
+fn cors_layer(origins: &[String]) -> Option<CorsLayer> {
+    if origins.is_empty() {
+        return None;
+    }
+    let allow = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(origins.iter().filter_map(|origin| {
+            let value = HeaderValue::from_str(origin).ok();
+            if value.is_none() {
+                tracing::warn!("Ignoring invalid CORS origin {:?}", origin);
+            }
+            value
+        }))
+    };
+    Some(
+        CorsLayer::new()
+            .allow_origin(allow)
+            .allow_methods([Method::GET]),
+    )
+}
+
+/// The address a client connected from. Behind a reverse proxy that's the
+/// proxy, so with `trust_forwarded_for` the last `X-Forwarded-For` entry
+/// (the one our proxy appended; earlier ones are client-supplied) is used.
+pub(crate) fn client_ip(
+    peer: SocketAddr,
+    headers: &HeaderMap,
+    trust_forwarded_for: bool,
+) -> IpAddr {
+    if !trust_forwarded_for {
+        return peer.ip();
+    }
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .last()
+        .and_then(|ip| ip.trim().parse().ok())
+        .unwrap_or(peer.ip())
 }
 
 async fn health_check() -> &'static str {
     "OK"
 }
 
-async fn ws_handler(ws: WebSocketUpgrade, State(state): State<SharedGameState>) -> Response {
-    ws.on_upgrade(move |socket| handle_socket(socket, state))
+async fn server_info(State(state): State<SharedGameState>) -> Json<ServerInfo> {
+    let game = state.read().await;
+    Json(ServerInfo {
+        name: game.config().server_name.clone(),
+        motd: game.config().motd.clone(),
+        players: game.entity_counts().players,
+        max_players: (game.config().max_players > 0).then_some(game.config().max_players),
+        mode: game.config().mode.name().to_string(),
+        uptime_secs: game.uptime().as_secs(),
+        protocol_version: PROTOCOL_VERSION,
+    })
 }
 
-async fn handle_socket(socket: WebSocket, state: SharedGameState) {
-    let (mut sender, mut receiver) = socket.split();
+/// `/ws` query: the room to play in, the main game when left out, and
+/// the invite code a private room needs
+#[derive(Debug, Deserialize)]
+struct WsParams {
+    room: Option<String>,
+    code: Option<String>,
+}
+
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(params): Query<WsParams>,
+    State(state): State<SharedGameState>,
+) -> Response {
+    let (threshold, trust_forwarded_for, max_message_size) = {
+        let game = state.read().await;
+        (
+            game.config().compression_threshold,
+            game.config().trust_forwarded_for,
+            game.config().max_message_size,
+        )
+    };
+    let ip = client_ip(peer, &headers, trust_forwarded_for);
+    let guard = match access::admit(&*state.read().await, ip) {
+        Ok(guard) => guard,
+        Err(rejection) => {
+            tracing::info!("Refused WebSocket connection from {}: {}", ip, rejection);
+            let status = match rejection {
+                Rejection::Banned(_) => StatusCode::FORBIDDEN,
+                Rejection::TooManyConnections => StatusCode::TOO_MANY_REQUESTS,
+            };
+            return (status, rejection.to_string()).into_response();
+        }
+    };
+    tracing::info!("WebSocket connection from {}", ip);
+
+    // A password or account in the handshake has to be right; without
+    // one it's asked for in Join
+    let header = |name| {
+        headers
+            .get(name)
+            .map(|value: &HeaderValue| value.to_str().unwrap_or_default())
+    };
+    let password_checked = match header(PASSWORD_HEADER) {
+        Some(password) if !access::password_matches(&*state.read().await, Some(password)) => {
+            tracing::info!("Refused {}: wrong server password", ip);
+            return (StatusCode::UNAUTHORIZED, "wrong server password").into_response();
+        }
+        Some(_) => true,
+        None => false,
+    };
+    let account_checked = match header(ACCOUNT_HEADER) {
+        Some(account) if !access::account_allowed(&*state.read().await, Some(account)) => {
+            tracing::info!("Refused {}: account not on the whitelist", ip);
+            return (StatusCode::FORBIDDEN, "account not on the whitelist").into_response();
+        }
+        Some(_) => true,
+        None => false,
+    };
+    let handshake = Handshake {
+        password_checked,
+        account_checked,
+    };
+
+    // Bans and connection limits are the main game's, whichever room it is
+    let room = match params.room.as_deref() {
+        None | Some(MAIN_ROOM) => state,
+        Some(token) => match state
+            .read()
+            .await
+            .rooms()
+            .admit(token, params.code.as_deref())
+        {
+            Ok(room) => room,
+            Err(rejection) => {
+                tracing::info!("Refused {} a place in room {}: {}", ip, token, rejection);
+                let status = match rejection {
+                    RoomRejection::NoSuchRoom => StatusCode::NOT_FOUND,
+                    RoomRejection::BadInviteCode => StatusCode::FORBIDDEN,
+                };
+                return (status, rejection.to_string()).into_response();
+            }
+        },
+    };
+
+    ws.protocols([DEFLATE_PROTOCOL])
+        .max_message_size(max_message_size)
+        .max_frame_size(max_message_size)
+        .on_upgrade(move |socket| async move {
+            let (sink, source) = websocket::split(socket, threshold);
+            run_session(sink, source, room, ip, handshake).await;
+            drop(guard);
+        })
+}
 
+/// Play one client's session over any transport: forward snapshots and
+/// events out, apply client messages in, and remove the player when the
+/// connection ends. Everything logged for the session sits in a
+/// `connection` span carrying the client address and, once joined, the
+/// player ID; each client message gets a `message` span inside it.
+/// `handshake` is what the transport already checked, the server password
+/// or whitelisted account, so `Join` needn't carry it.
+#[tracing::instrument(
+    name = "connection",
+    skip_all,
+    fields(client = %ip, player_id = tracing::field::Empty)
+)]
+pub async fn run_session(
+    sink: impl MessageSink,
+    mut source: impl MessageSource,
+    state: SharedGameState,
+    ip: IpAddr,
+    handshake: Handshake,
+) {
     let player_id = Arc::new(RwLock::new(None::<Uuid>));
     let player_id_clone = player_id.clone();
 
-    // Spawn task to send game state updates
+    // Spawn task to send game state updates and simulation events
     let state_clone = state.clone();
-    let mut send_task = tokio::spawn(async move {
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(50)); // 20 updates/sec
+    let (mut events, mut outbox, snapshot_period) = {
+        let game = state.read().await;
+        let outbox = Outbox {
+            sink,
+            budget: BandwidthBudget::new(game.config(), Instant::now()),
+            seq: 0,
+            snapshot: SnapshotBuffer::default(),
+        };
+        let period = Duration::from_secs_f32(1.0 / game.config().snapshot_rate);
+        (game.subscribe_events(), outbox, period)
+    };
+    let send = async move {
+        let mut interval = tokio::time::interval(snapshot_period);
         let mut welcome_sent = false;
 
         loop {
-            interval.tick().await;
+            tokio::select! {
+                _ = interval.tick() => {
+                    let pid = *player_id_clone.read().await;
+                    let Some(pid) = pid else {
+                        continue;
+                    };
+                    // Queued players wait for a slot, and kicked ones only
+                    // for their notice
+                    let tick = {
+                        let game = state_clone.read().await;
+                        if game.player(pid).is_none() {
+                            continue;
+                        }
+                        game.tick()
+                    };
 
-            let pid = *player_id_clone.read().await;
-            if pid.is_none() {
-                continue;
-            }
-            let pid_unwrapped = pid.unwrap();
+                    // Send Welcome once per connection, on entering the game
+                    if !welcome_sent {
+                        let welcome = ServerMessage::Welcome { player_id: pid };
+                        if !outbox.send(&welcome, tick, Delivery::Reliable).await {
+                            break;
+                        }
+                        welcome_sent = true;
+                    }
 
-            // Send Welcome once per connection
-            if !welcome_sent {
-                let welcome = ServerMessage::Welcome { player_id: pid_unwrapped };
-                if let Ok(json) = serde_json::to_string(&welcome) {
-                    if sender.send(Message::Text(json.into())).await.is_err() {
+                    // Congested connections get fewer, smaller snapshots
+                    if !outbox.budget.snapshot_due(Instant::now()) {
+                        continue;
+                    }
+                    let game = diagnostics::read(&state_clone, LockSite::Snapshot).await;
+                    // Players bound over UDP get their snapshots there
+                    if game.uses_udp(pid) || game.player(pid).is_none() {
+                        continue;
+                    }
+                    let limit = outbox
+                        .budget
+                        .under_pressure()
+                        .then_some(game.config().congested_entity_limit);
+                    outbox.seq += 1;
+                    let json = game.snapshot(&mut outbox.snapshot, pid, limit, outbox.seq);
+                    drop(game);
+                    if !outbox.send_json(json, Delivery::Latest).await {
                         break;
                     }
-                    welcome_sent = true;
                 }
+                event = events.recv() => match event {
+                    Ok(event) => {
+                        let pid = *player_id_clone.read().await;
+                        if pid.is_none() || event.recipient.is_some_and(|r| Some(r) != pid) {
+                            continue;
+                        }
+                        if !outbox.send(&event.message, event.tick, Delivery::Reliable).await {
+                            break;
+                        }
+                        if matches!(event.message, ServerMessage::Kicked { .. }) {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!("Connection fell behind, dropped {} events", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
             }
+        }
+    };
+    let mut send_task = tokio::spawn(send.in_current_span());
 
-            let game = state_clone.read().await;
-
-            // Send game state
-            let msg = ServerMessage::GameState {
-                players: game.players.values().cloned().collect(),
-                enemies: game.enemies.values().cloned().collect(),
-                projectiles: game.projectiles.values().cloned().collect(),
-                game_time: game.game_time,
+    // Handle incoming messages
+    let state_clone = state.clone();
+    let player_id_recv = player_id.clone();
+    let connection = tracing::Span::current();
+    let recv = async move {
+        let mut recorded = None;
+        while let Some(received) = source.recv().await {
+            let checked = match received {
+                Ok(msg) => {
+                    let game = state_clone.read().await;
+                    validation::check(&msg, game.config()).map(|()| msg)
+                }
+                Err(e) => Err(e),
             };
-
-            let json = match serde_json::to_string(&msg) {
-                Ok(j) => j,
+            let client_msg = match checked {
+                Ok(msg) => msg,
                 Err(e) => {
-                    tracing::error!("Failed to serialize message: {}", e);
+                    reject(&state_clone, &player_id_recv, e).await;
                     continue;
                 }
             };
+            let span = tracing::debug_span!("message", msg = ?client_msg);
+            handle_client_message(client_msg, &state_clone, &player_id_recv, ip, handshake)
+                .instrument(span)
+                .await;
 
-            if sender.send(Message::Text(json.into())).await.is_err() {
-                break;
-            }
-        }
-    });
-
-    // Handle incoming messages
-    let state_clone = state.clone();
-    let player_id_recv = player_id.clone();
-    let mut recv_task = tokio::spawn(async move {
-        while let Some(Ok(msg)) = receiver.next().await {
-            if let Message::Text(text) = msg {
-                match serde_json::from_str::<ClientMessage>(&text) {
-                    Ok(client_msg) => {
-                        handle_client_message(client_msg, &state_clone, &player_id_recv).await;
-                    }
-                    Err(e) => {
-                        tracing::warn!("Failed to parse client message: {}", e);
-                    }
+            // Tag the connection span with the player once joined
+            let pid = *player_id_recv.read().await;
+            if pid != recorded {
+                if let Some(pid) = pid {
+                    connection.record("player_id", tracing::field::display(pid));
                 }
+                recorded = pid;
             }
         }
-    });
+    };
+    let mut recv_task = tokio::spawn(recv.in_current_span());
 
     // Wait for either task to finish
     tokio::select! {
@@ -118,43 +408,148 @@ async fn handle_socket(socket: WebSocket, state: SharedGameState) {
     let pid = *player_id.read().await;
     if let Some(pid) = pid {
         let mut game = state.write().await;
-        game.remove_player(pid);
+        if game.remove_player(pid).is_none() {
+            game.leave_queue(pid);
+        }
         tracing::info!("Player {} disconnected", pid);
     }
 }
 
+/// The sending side of a session: numbers each message and counts it
+/// against the connection's bandwidth budget
+struct Outbox<S> {
+    sink: S,
+    budget: BandwidthBudget,
+    seq: u64, // of the last message sent
+    snapshot: SnapshotBuffer,
+}
+
+impl<S: MessageSink> Outbox<S> {
+    /// Send a message produced at `tick` in the connection's next envelope.
+    /// Returns false once the client is gone.
+    async fn send(&mut self, message: &ServerMessage, tick: u64, delivery: Delivery) -> bool {
+        self.seq += 1;
+        let envelope = Envelope {
+            server_tick: tick,
+            seq: self.seq,
+            message,
+        };
+        let sent = self.sink.send(&envelope, delivery).await;
+        self.record(sent)
+    }
+
+    /// Send an envelope already serialized with the connection's next `seq`
+    async fn send_json(&mut self, json: Bytes, delivery: Delivery) -> bool {
+        let sent = self.sink.send_json(json, delivery).await;
+        self.record(sent)
+    }
+
+    fn record(&mut self, sent: Option<usize>) -> bool {
+        match sent {
+            Some(bytes) => {
+                self.budget.record(bytes);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Tell the player the game turned down what they asked for. Anyone not
+/// in the game, like a player still in the join queue, is told that
+/// instead of the error's code.
+fn refuse(game: &mut GameState, player_id: Uuid, mut error: MessageError) {
+    if game.player(player_id).is_none() {
+        error.code = ErrorCode::NotInGame;
+    }
+    game.send_to(player_id, error.into());
+}
+
+/// Tell the client a message of theirs was refused before it reached the
+/// game. Until they've joined there's nobody to address it to, so it's
+/// only logged.
+async fn reject(
+    state: &SharedGameState,
+    player_id: &Arc<RwLock<Option<Uuid>>>,
+    error: MessageError,
+) {
+    tracing::debug!(
+        "Rejected client message ({:?}): {}",
+        error.code,
+        error.message
+    );
+    if let Some(pid) = *player_id.read().await {
+        state.write().await.send_to(pid, error.into());
+    }
+}
+
 async fn handle_client_message(
     msg: ClientMessage,
     state: &SharedGameState,
     player_id: &Arc<RwLock<Option<Uuid>>>,
+    ip: IpAddr,
+    handshake: Handshake,
 ) {
-    match msg {
-        ClientMessage::Join => {
-            let new_id = Uuid::new_v4();
-            let mut game = state.write().await;
-            let _player = game.add_player(new_id);
-
-            *player_id.write().await = Some(new_id);
-
-            tracing::info!("Player {} joined", new_id);
+    // Only the connection's first Join makes a player; the game turns
+    // down any after it along with the commands it refuses
+    let current = *player_id.read().await;
+    let (locale, protocol_version, password, account) = match (msg, current) {
+        (
+            ClientMessage::Join {
+                locale,
+                protocol_version,
+                password,
+                account,
+            },
+            None,
+        ) => (locale, protocol_version, password, account),
+        (msg, Some(pid)) => {
+            let mut game = diagnostics::write(state, LockSite::Message).await;
+            if let Err(e) = game.handle_command(pid, msg) {
+                refuse(&mut game, pid, e);
+            }
+            return;
+        }
+        (_, None) => return,
+    };
 
-            // Note: Welcome message could be sent here if we had access to sender
-            // For simplicity, client will receive game state updates immediately
+    let new_id = Uuid::new_v4();
+    let mut game = diagnostics::write(state, LockSite::Message).await;
+    let outcome = match protocol_version {
+        Some(version) if version != PROTOCOL_VERSION => {
+            let reason = format!(
+                "Server speaks protocol version {}, not {}",
+                PROTOCOL_VERSION, version
+            );
+            game.turn_away(new_id, ErrorCode::ProtocolMismatch, reason);
+            JoinOutcome::Rejected
         }
-        ClientMessage::Move { target } => {
-            if let Some(pid) = *player_id.read().await {
-                let mut game = state.write().await;
-                let delta_time = 1.0 / game.config.tick_rate as f32;
-                game.move_player(pid, target, delta_time);
-            }
+        _ if !handshake.password_checked
+            && !access::password_matches(&game, password.as_deref()) =>
+        {
+            let reason = "Wrong server password".to_string();
+            game.turn_away(new_id, ErrorCode::WrongPassword, reason);
+            JoinOutcome::Rejected
         }
-        ClientMessage::ChooseUpgrade { upgrade } => {
-            if let Some(pid) = *player_id.read().await {
-                let mut game = state.write().await;
-                if let Err(e) = game.apply_upgrade(pid, upgrade) {
-                    tracing::error!("Failed to apply upgrade for player {}: {}", pid, e);
-                }
-            }
+        _ if !handshake.account_checked && !access::account_allowed(&game, account.as_deref()) => {
+            let reason = "This server only takes accounts on its whitelist".to_string();
+            game.turn_away(new_id, ErrorCode::NotWhitelisted, reason);
+            JoinOutcome::Rejected
+        }
+        _ => game.request_join(new_id),
+    };
+    match outcome {
+        JoinOutcome::Joined => tracing::info!("Player {} joined", new_id),
+        JoinOutcome::Queued(position) => {
+            tracing::info!("Player {} queued at position {}", new_id, position)
         }
+        JoinOutcome::Rejected => tracing::info!("Turned away {}", new_id),
     }
+    if outcome != JoinOutcome::Rejected {
+        game.connect_client(new_id, ip, locale);
+    }
+
+    // Also set when rejected, so the connection gets its notice;
+    // Welcome follows once the player is actually in the game
+    *player_id.write().await = Some(new_id);
 }
@@ -0,0 +1,27 @@
+use uuid::Uuid;
+
+/// A group of players who share XP and are scored together
+#[derive(Debug, Clone)]
+pub struct Party {
+    pub id: Uuid,
+    pub members: Vec<Uuid>,
+    pub wiped: bool, // every member died; reset when the match restarts
+}
+
+impl Party {
+    pub fn new(leader: Uuid) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            members: vec![leader],
+            wiped: false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn remove_member(&mut self, player_id: Uuid) {
+        self.members.retain(|id| *id != player_id);
+    }
+}
@@ -0,0 +1,82 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use shared::{Enemy, Landmark, Pickup, Player, Projectile, ScoreEntry};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::GameConfig;
+use crate::daily::DailyBoard;
+use crate::game_state::SharedGameState;
+use crate::integrity::HeldScore;
+use crate::runs::RunLog;
+
+/// Everything needed to bring a world back after a restart, written as JSON.
+///
+/// Players are saved so a snapshot is useful in bug reports, but they aren't
+/// restored: their connections died with the old process and everyone
+/// rejoins as a new player.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    pub saved_at: chrono::DateTime<chrono::Utc>,
+    pub config: GameConfig, // informational; the running config wins on resume
+    pub players: Vec<Player>,
+    pub enemies: Vec<Enemy>,
+    pub projectiles: Vec<Projectile>,
+    pub pickups: Vec<Pickup>,
+    pub landmarks: Vec<Landmark>,
+    pub scores: Vec<ScoreEntry>,
+    #[serde(default)]
+    pub daily: Option<DailyBoard>,
+    #[serde(default)]
+    pub held_scores: Vec<HeldScore>,
+    #[serde(default)]
+    pub runs: RunLog,
+    pub game_time: f64,
+    pub tick: u64,
+    pub last_spawn_time: f64,
+    pub closed_rings: u32,
+    pub next_zone_shrink: f64,
+    pub match_start_time: f64,
+    pub match_seed: u64,
+    pub wave_number: u32,
+    pub next_wave_time: f64,
+    pub core_health: f32,
+}
+
+/// Write the current world to `path`. Goes through a temporary file so a
+/// crash mid-write never leaves a truncated snapshot behind.
+pub async fn save(state: &SharedGameState, path: &Path) -> anyhow::Result<()> {
+    let snapshot = state.read().await.capture_world();
+    let json = serde_json::to_vec(&snapshot)?;
+
+    let tmp_path = path.with_extension("tmp");
+    tokio::fs::write(&tmp_path, json)
+        .await
+        .with_context(|| format!("writing {}", tmp_path.display()))?;
+    tokio::fs::rename(&tmp_path, path)
+        .await
+        .with_context(|| format!("replacing {}", path.display()))?;
+
+    tracing::info!("Saved world snapshot to {}", path.display());
+    Ok(())
+}
+
+pub fn load(path: &Path) -> anyhow::Result<WorldSnapshot> {
+    let json = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    let snapshot =
+        serde_json::from_slice(&json).with_context(|| format!("parsing {}", path.display()))?;
+    Ok(snapshot)
+}
+
+/// Save the world every `interval` for as long as the server runs
+pub async fn run_autosave(state: SharedGameState, path: PathBuf, interval: Duration) {
+    let mut timer = tokio::time::interval(interval);
+    timer.tick().await; // the first tick fires immediately; nothing to save yet
+
+    loop {
+        timer.tick().await;
+        if let Err(e) = save(&state, &path).await {
+            tracing::error!("Failed to save world snapshot: {:#}", e);
+        }
+    }
+}
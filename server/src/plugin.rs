@@ -0,0 +1,75 @@
+//! Hook points for custom mechanics. Operators implement `GamePlugin` in
+//! Rust and list it in `installed`; the server registers those at startup
+//! and calls them from the simulation, so the game loop itself stays
+//! untouched.
+
+use shared::Enemy;
+use uuid::Uuid;
+
+use crate::events::GameEvent;
+use crate::game_state::GameState;
+
+/// Custom game logic called at fixed points of the simulation. Every hook
+/// has a default that does nothing, so a plugin only implements what it
+/// needs. Hooks get the whole game state and run inside the tick, under
+/// its lock: keep them quick.
+pub trait GamePlugin: Send + Sync {
+    /// Shown in logs and the state's `Debug` output
+    fn name(&self) -> &str;
+
+    /// A player was added to the game, after their starting stats are set
+    fn on_player_join(&mut self, _game: &mut GameState, _player_id: Uuid) {}
+
+    /// An enemy was killed by `killer`, after the kill's XP and drops
+    fn on_enemy_killed(&mut self, _game: &mut GameState, _enemy: &Enemy, _killer: Uuid) {}
+
+    /// Once per tick, after the built-in stages and before events go out
+    fn on_tick(&mut self, _game: &mut GameState, _delta_time: f32) {}
+
+    /// An enemy is about to enter the world; change its stats, type or
+    /// position here
+    fn modify_spawn(&mut self, _game: &GameState, _enemy: &mut Enemy) {}
+
+    /// Every event on the bus, after the game's own subscribers and the
+    /// specific hooks above
+    fn on_event(&mut self, _game: &mut GameState, _event: &GameEvent) {}
+}
+
+/// The plugins a game state calls, in registration order
+#[derive(Default)]
+pub struct Plugins(Vec<Box<dyn GamePlugin>>);
+
+impl Plugins {
+    pub fn register(&mut self, plugin: Box<dyn GamePlugin>) {
+        tracing::info!("Registered plugin {}", plugin.name());
+        self.0.push(plugin);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut Box<dyn GamePlugin>> {
+        self.0.iter_mut()
+    }
+
+    /// Put back plugins taken out for a hook call, keeping any registered
+    /// while they were out
+    pub(crate) fn restore(&mut self, mut taken: Plugins) {
+        taken.0.append(&mut self.0);
+        *self = taken;
+    }
+}
+
+impl std::fmt::Debug for Plugins {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|p| p.name()))
+            .finish()
+    }
+}
+
+/// Plugins the `server` binary registers at startup. Add yours here.
+pub fn installed() -> Vec<Box<dyn GamePlugin>> {
+    Vec::new()
+}
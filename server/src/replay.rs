@@ -0,0 +1,173 @@
+//! Recorded sessions played back headlessly, to catch changes in how the
+//! simulation behaves.
+//!
+//! A `CommandLog` holds a seed, the config overrides it ran with, and the
+//! client messages each player sent before each tick. `CommandLog::run`
+//! plays it through the same `GameState` calls a connection makes, one
+//! fixed step per tick, and `state_hash` reduces the result to a number
+//! that stays the same for as long as the simulation does. Everything the
+//! simulation rolls comes from the seed (see `GameState::run_rng`), so
+//! the same log always ends in the same state.
+//!
+//! Logs live under `server/tests/replays` with the hash they're expected
+//! to end in; `cargo test --test replay` checks them all. A change meant
+//! to alter the simulation updates the hashes with `UPDATE_REPLAYS=1`.
+
+use serde::{Deserialize, Serialize};
+use shared::ClientMessage;
+use uuid::Uuid;
+
+use crate::config::GameConfig;
+use crate::game_loop;
+use crate::game_state::GameState;
+
+/// One client message, applied before the step of `tick` (counting from 0)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Command {
+    pub tick: u64,
+    pub player: Uuid,
+    pub message: ClientMessage,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandLog {
+    pub seed: u64,
+    pub ticks: u64,
+    /// `GameConfig` fields that differ from the defaults
+    #[serde(default)]
+    pub config: serde_json::Map<String, serde_json::Value>,
+    pub commands: Vec<Command>,
+    /// What `state_hash` gave at the end when the log was recorded
+    #[serde(default)]
+    pub state_hash: Option<String>,
+}
+
+impl CommandLog {
+    pub fn load(path: &std::path::Path) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    pub fn config(&self) -> anyhow::Result<GameConfig> {
+        let mut config: GameConfig =
+            serde_json::from_value(serde_json::Value::Object(self.config.clone()))?;
+        config.seed = Some(self.seed);
+        Ok(config)
+    }
+
+    /// Play the log from a fresh game and return where it ended up
+    pub fn run(&self) -> anyhow::Result<GameState> {
+        let mut game = GameState::new(self.config()?);
+        let delta_time = 1.0 / game.config().tick_rate as f32;
+        let mut commands = self.commands.iter().peekable();
+        for tick in 0..self.ticks {
+            while let Some(command) = commands.next_if(|c| c.tick <= tick) {
+                apply(&mut game, command.player, command.message.clone());
+            }
+            game_loop::step(&mut game, delta_time);
+        }
+        Ok(game)
+    }
+}
+
+/// Carry out a message as `network` would for a connection playing as
+/// `player`. Refusals only matter to the client, so they're dropped.
+pub fn apply(game: &mut GameState, player: Uuid, message: ClientMessage) {
+    match message {
+        ClientMessage::Join { .. } => {
+            game.request_join(player);
+        }
+        command => {
+            let _ = game.handle_command(player, command);
+        }
+    }
+}
+
+/// A fingerprint of the simulated world: players, enemies, projectiles,
+/// pickups and summons, and the clock. Wall-clock times and the IDs of
+/// matches and parties are random by design and left out.
+pub fn state_hash(game: &GameState) -> u64 {
+    let mut hash = StateHash::default();
+    hash.u64(game.tick());
+    hash.u64(game.game_time().to_bits());
+
+    let mut players: Vec<_> = game.players().collect();
+    players.sort_by_key(|p| p.id);
+    hash.u64(players.len() as u64);
+    for p in players {
+        hash.u128(p.id.as_u128());
+        hash.position(p.position.x, p.position.y);
+        hash.f32(p.health);
+        hash.f32(p.max_health);
+        hash.f32(p.shield);
+        for n in [p.level, p.current_xp, p.gold, p.enemies_defeated] {
+            hash.u64(n as u64);
+        }
+        hash.u64(p.max_ring_reached as u64);
+    }
+
+    hash.u64(game.enemies().len() as u64);
+    for e in game.enemies().values() {
+        hash.str(e.enemy_type.name());
+        hash.position(e.position.x, e.position.y);
+        hash.f32(e.health);
+        hash.u64(e.spawn_ring as u64);
+    }
+    hash.u64(game.projectiles().len() as u64);
+    for p in game.projectiles().values() {
+        hash.position(p.position.x, p.position.y);
+        hash.f32(p.damage);
+        hash.u64(p.hostile as u64);
+    }
+    hash.u64(game.pickups().len() as u64);
+    for p in game.pickups().values() {
+        hash.u64(p.pickup_type as u64);
+        hash.position(p.position.x, p.position.y);
+        hash.u64(p.amount as u64);
+    }
+    hash.u64(game.summons().len() as u64);
+    for s in game.summons().values() {
+        hash.position(s.position.x, s.position.y);
+    }
+    hash.0
+}
+
+/// 64-bit FNV-1a over the values fed in, as little-endian bytes, so the
+/// result is the same on every platform and Rust version
+struct StateHash(u64);
+
+impl Default for StateHash {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl StateHash {
+    fn bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn u64(&mut self, n: u64) {
+        self.bytes(&n.to_le_bytes());
+    }
+
+    fn u128(&mut self, n: u128) {
+        self.bytes(&n.to_le_bytes());
+    }
+
+    fn f32(&mut self, x: f32) {
+        self.bytes(&x.to_bits().to_le_bytes());
+    }
+
+    fn position(&mut self, x: f32, y: f32) {
+        self.f32(x);
+        self.f32(y);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.u64(s.len() as u64);
+        self.bytes(s.as_bytes());
+    }
+}
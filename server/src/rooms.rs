@@ -0,0 +1,417 @@
+//! Rooms: more games on the same server, next to the main one. Each has
+//! its own `GameState` and game loop, built from the server's config with
+//! a few `RoomOverrides` on top, and clients join one with
+//! `/ws?room={token}` (no `room`, or `main`, is the main game).
+//!
+//! `GET /rooms` lists the main game and public rooms with their player
+//! counts and join tokens; `POST /rooms` opens a room, private unless asked
+//! otherwise, and answers with its token. At most `max_rooms` are open, and
+//! one left empty for `room_idle_timeout` seconds is closed. Rooms live in
+//! memory only: they aren't saved in world snapshots or match history.
+//!
+//! A private room also needs its invite code to join,
+//! `/ws?room={token}&code={code}`, which works for `invite_code_lifetime`
+//! seconds. Whoever opened the room gets an owner key and, behind
+//! `Authorization: Bearer <owner_key>`, may:
+//!
+//! - `POST /rooms/{token}/invite`: replace the code with a fresh one; the
+//!   old one stops working, so a kicked player can't come back with it
+//! - `POST /rooms/{token}/players/{id}/kick`: kick a player or queued joiner
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use rand::{distributions::Alphanumeric, seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+use shared::RoomInfo;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::config::GameConfig;
+use crate::game_loop;
+use crate::game_mode::GameMode;
+use crate::game_state::{GameState, SharedGameState};
+use crate::plugin;
+
+/// Join token of the server's main game
+pub const MAIN_ROOM: &str = "main";
+
+/// Characters in a room's join token, lowercase letters and digits so
+/// it's easy to pass on
+const TOKEN_LENGTH: usize = 10;
+
+/// Invite codes are this many characters from `CODE_ALPHABET`: lowercase
+/// letters and digits, leaving out ones easily read as another
+const CODE_LENGTH: usize = 6;
+const CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// How often empty rooms are looked for
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Settings a room may change from the server's config. Anything touching
+/// files, the network or admin access stays the server's.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoomOverrides {
+    pub mode: Option<GameMode>,
+    pub seed: Option<u64>,
+    pub max_players: Option<usize>,
+    pub pvp_enabled: Option<bool>,
+    pub timed_duration: Option<f32>,
+    pub max_rings: Option<u32>,
+    pub enemy_spawn_rate: Option<f32>,
+}
+
+impl RoomOverrides {
+    pub fn apply(&self, config: &mut GameConfig) {
+        if let Some(mode) = self.mode {
+            config.mode = mode;
+        }
+        if self.seed.is_some() {
+            config.seed = self.seed;
+        }
+        if let Some(max_players) = self.max_players {
+            config.max_players = max_players;
+        }
+        if let Some(pvp_enabled) = self.pvp_enabled {
+            config.pvp_enabled = pvp_enabled;
+        }
+        if let Some(duration) = self.timed_duration {
+            config.timed_duration = duration;
+        }
+        if let Some(max_rings) = self.max_rings {
+            config.max_rings = max_rings;
+        }
+        if let Some(rate) = self.enemy_spawn_rate {
+            config.enemy_spawn_rate = rate;
+        }
+    }
+}
+
+/// The code that lets players into a private room, until it expires
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Invite {
+    pub code: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Invite {
+    fn new(lifetime: Duration) -> Self {
+        let mut rng = rand::thread_rng();
+        let code = (0..CODE_LENGTH)
+            .map(|_| char::from(*CODE_ALPHABET.choose(&mut rng).unwrap()))
+            .collect();
+        let lifetime = chrono::Duration::from_std(lifetime).unwrap_or(chrono::Duration::MAX);
+        Self {
+            code,
+            expires_at: chrono::Utc::now() + lifetime,
+        }
+    }
+
+    fn admits(&self, code: Option<&str>) -> bool {
+        code == Some(self.code.as_str()) && chrono::Utc::now() < self.expires_at
+    }
+}
+
+/// What whoever opens a room gets back: how others join, and the key that
+/// lets them run it
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenedRoom {
+    pub token: String,
+    pub invite: Option<Invite>, // None for a public room
+    pub owner_key: String,
+}
+
+/// Why a connection can't join a room
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoomRejection {
+    NoSuchRoom,
+    BadInviteCode, // missing, wrong or expired
+}
+
+impl fmt::Display for RoomRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoomRejection::NoSuchRoom => write!(f, "no such room"),
+            RoomRejection::BadInviteCode => write!(f, "invite code missing, wrong or expired"),
+        }
+    }
+}
+
+/// One room and the task running its game loop, which stops with it
+struct Room {
+    public: bool,
+    state: SharedGameState,
+    invite: Option<Invite>,
+    owner_key: String,
+    empty_since: Option<Instant>,
+    game_loop: JoinHandle<()>,
+}
+
+impl Drop for Room {
+    fn drop(&mut self) {
+        self.game_loop.abort();
+    }
+}
+
+/// The open rooms by join token. Shared between the main game's state and
+/// the tasks sweeping and serving them.
+#[derive(Clone, Default)]
+pub struct Rooms(Arc<Mutex<HashMap<String, Room>>>);
+
+impl std::fmt::Debug for Rooms {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.0.lock().unwrap().keys())
+            .finish()
+    }
+}
+
+impl Rooms {
+    /// Start a game on `config`. A private one gets an invite code lasting
+    /// `code_lifetime`.
+    pub fn open(&self, config: GameConfig, public: bool, code_lifetime: Duration) -> OpenedRoom {
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LENGTH)
+            .map(|byte| char::from(byte).to_ascii_lowercase())
+            .collect();
+        let mut game = GameState::new(config);
+        for plugin in plugin::installed() {
+            game.register_plugin(plugin);
+        }
+        let state = Arc::new(RwLock::new(game));
+        let opened = OpenedRoom {
+            token,
+            invite: (!public).then(|| Invite::new(code_lifetime)),
+            owner_key: Uuid::new_v4().simple().to_string(),
+        };
+        let room = Room {
+            public,
+            state: state.clone(),
+            invite: opened.invite.clone(),
+            owner_key: opened.owner_key.clone(),
+            empty_since: Some(Instant::now()),
+            game_loop: tokio::spawn(game_loop::supervise(state)),
+        };
+        tracing::info!("Opened room {}", opened.token);
+        self.0.lock().unwrap().insert(opened.token.clone(), room);
+        opened
+    }
+
+    /// The room to play in, if `code` lets a new player into it
+    pub fn admit(&self, token: &str, code: Option<&str>) -> Result<SharedGameState, RoomRejection> {
+        let rooms = self.0.lock().unwrap();
+        let room = rooms.get(token).ok_or(RoomRejection::NoSuchRoom)?;
+        match &room.invite {
+            Some(invite) if !invite.admits(code) => Err(RoomRejection::BadInviteCode),
+            _ => Ok(room.state.clone()),
+        }
+    }
+
+    pub fn get(&self, token: &str) -> Option<SharedGameState> {
+        self.0
+            .lock()
+            .unwrap()
+            .get(token)
+            .map(|room| room.state.clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every room's token, listing and state, in no particular order
+    fn all(&self) -> Vec<(String, bool, SharedGameState)> {
+        let rooms = self.0.lock().unwrap();
+        rooms
+            .iter()
+            .map(|(token, room)| (token.clone(), room.public, room.state.clone()))
+            .collect()
+    }
+
+    /// Close rooms nobody has been in, or waiting for, for `timeout`
+    pub async fn close_idle(&self, timeout: Duration) {
+        for (token, _, state) in self.all() {
+            let empty = {
+                let game = state.read().await;
+                let counts = game.entity_counts();
+                counts.players == 0 && counts.queued == 0
+            };
+            let mut rooms = self.0.lock().unwrap();
+            let Some(room) = rooms.get_mut(&token) else {
+                continue;
+            };
+            match room.empty_since {
+                _ if !empty => room.empty_since = None,
+                None => room.empty_since = Some(Instant::now()),
+                Some(since) if since.elapsed() >= timeout => {
+                    rooms.remove(&token);
+                    tracing::info!("Closed idle room {}", token);
+                }
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+/// Close idle rooms every `SWEEP_INTERVAL` for as long as the server runs
+pub async fn run_sweeper(state: SharedGameState) {
+    let (rooms, timeout) = {
+        let game = state.read().await;
+        let timeout = Duration::from_secs_f32(game.config().room_idle_timeout);
+        (game.rooms().clone(), timeout)
+    };
+    let mut timer = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        timer.tick().await;
+        rooms.close_idle(timeout).await;
+    }
+}
+
+/// `/rooms` and the owner routes under it
+pub fn router() -> Router<SharedGameState> {
+    Router::new()
+        .route("/rooms", get(list_rooms).post(create_room))
+        .route("/rooms/{token}/invite", post(renew_invite))
+        .route("/rooms/{token}/players/{id}/kick", post(kick_player))
+}
+
+type RoomError = (StatusCode, String);
+
+async fn room_info(token: String, public: bool, state: &SharedGameState) -> RoomInfo {
+    let game = state.read().await;
+    RoomInfo {
+        token,
+        mode: game.config().mode.name().to_string(),
+        players: game.entity_counts().players,
+        max_players: (game.config().max_players > 0).then_some(game.config().max_players),
+        public,
+    }
+}
+
+async fn list_rooms(State(state): State<SharedGameState>) -> Json<Vec<RoomInfo>> {
+    let rooms = state.read().await.rooms().clone();
+    let mut listed = vec![room_info(MAIN_ROOM.to_string(), true, &state).await];
+    for (token, public, room) in rooms.all() {
+        if public {
+            listed.push(room_info(token, public, &room).await);
+        }
+    }
+    Json(listed)
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CreateRoom {
+    public: bool,
+    config: RoomOverrides,
+}
+
+/// `POST /rooms` answer: the listing plus how to join and run the room
+#[derive(Debug, Serialize)]
+struct CreatedRoom {
+    #[serde(flatten)]
+    room: RoomInfo,
+    invite: Option<Invite>,
+    owner_key: String,
+}
+
+async fn create_room(
+    State(state): State<SharedGameState>,
+    Json(request): Json<CreateRoom>,
+) -> Result<(StatusCode, Json<CreatedRoom>), RoomError> {
+    let (rooms, mut config) = {
+        let game = state.read().await;
+        (game.rooms().clone(), game.config().clone())
+    };
+    let code_lifetime = Duration::from_secs_f32(config.invite_code_lifetime);
+    if rooms.len() >= config.max_rooms {
+        let reason = format!("this server runs at most {} rooms", config.max_rooms);
+        return Err((StatusCode::SERVICE_UNAVAILABLE, reason));
+    }
+    request.config.apply(&mut config);
+    if let Some(problem) = config.problems().into_iter().next() {
+        return Err((StatusCode::BAD_REQUEST, problem));
+    }
+    let opened = rooms.open(config, request.public, code_lifetime);
+    let room = rooms.get(&opened.token).expect("room was just opened");
+    let created = CreatedRoom {
+        room: room_info(opened.token, request.public, &room).await,
+        invite: opened.invite,
+        owner_key: opened.owner_key,
+    };
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+/// The room named `token`, if the request carries its owner key
+async fn owned_room(
+    state: &SharedGameState,
+    token: &str,
+    headers: &HeaderMap,
+) -> Result<SharedGameState, RoomError> {
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let game = state.read().await;
+    let rooms = game.rooms().0.lock().unwrap();
+    let room = rooms
+        .get(token)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "no such room".to_string()))?;
+    if presented != Some(room.owner_key.as_str()) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            "not this room's owner".to_string(),
+        ));
+    }
+    Ok(room.state.clone())
+}
+
+async fn renew_invite(
+    State(state): State<SharedGameState>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<Invite>, RoomError> {
+    owned_room(&state, &token, &headers).await?;
+    let game = state.read().await;
+    let lifetime = Duration::from_secs_f32(game.config().invite_code_lifetime);
+    let mut rooms = game.rooms().0.lock().unwrap();
+    let Some(room) = rooms.get_mut(&token) else {
+        return Err((StatusCode::NOT_FOUND, "no such room".to_string()));
+    };
+    if room.public {
+        let reason = "public rooms don't use invite codes".to_string();
+        return Err((StatusCode::CONFLICT, reason));
+    }
+    let invite = Invite::new(lifetime);
+    room.invite = Some(invite.clone());
+    tracing::info!("Room {} has a new invite code", token);
+    Ok(Json(invite))
+}
+
+async fn kick_player(
+    State(state): State<SharedGameState>,
+    Path((token, id)): Path<(String, Uuid)>,
+    headers: HeaderMap,
+) -> Result<StatusCode, RoomError> {
+    let room = owned_room(&state, &token, &headers).await?;
+    let mut game = room.write().await;
+    if !game.knows_player(id) {
+        return Err((StatusCode::NOT_FOUND, format!("no player {}", id)));
+    }
+    game.kick(id, "Kicked by the room's owner".to_string());
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,151 @@
+//! Run summaries: one per death, so players can share a link to how a run
+//! went. `PlayerDied` carries the run's ID and `GET /runs/{id}` serves the
+//! summary, as JSON or, to a browser or link preview asking for HTML, as a
+//! small page with OpenGraph tags. The newest `max_run_summaries` are kept
+//! and saved with the world snapshot.
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use shared::{Player, RunStats};
+use std::collections::VecDeque;
+use uuid::Uuid;
+
+use crate::game_state::SharedGameState;
+
+/// How one player's run ended
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunSummary {
+    pub id: Uuid,
+    pub player_id: Uuid,
+    pub mode: String,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub ended_at: chrono::DateTime<chrono::Utc>,
+    pub survival_time: f32, // seconds
+    pub level: u32,
+    pub max_ring_reached: u32,
+    pub enemies_defeated: u32,
+    pub bosses_defeated: u32,
+    pub players_defeated: u32,
+    pub score_recorded: bool,
+    pub stats: RunStats,
+}
+
+impl RunSummary {
+    pub fn new(player: &Player, mode: &str, score_recorded: bool) -> Self {
+        let ended_at = chrono::Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            player_id: player.id,
+            mode: mode.to_string(),
+            started_at: player.spawn_time,
+            ended_at,
+            survival_time: (ended_at - player.spawn_time).num_milliseconds() as f32 / 1000.0,
+            level: player.level,
+            max_ring_reached: player.max_ring_reached,
+            enemies_defeated: player.enemies_defeated,
+            bosses_defeated: player.bosses_defeated,
+            players_defeated: player.players_defeated,
+            score_recorded,
+            stats: player.stats.clone(),
+        }
+    }
+
+    /// One line for link previews
+    fn description(&self) -> String {
+        format!(
+            "Survived {:.0}s, reached level {}, defeated {} enemies and dealt {:.0} damage",
+            self.survival_time, self.level, self.enemies_defeated, self.stats.damage_dealt
+        )
+    }
+
+    /// A page for people following a shared link. Every value is a number,
+    /// an ID or a mode name, so nothing needs escaping.
+    fn page(&self) -> String {
+        let title = format!("Ring {} in {}", self.max_ring_reached, self.mode);
+        let description = self.description();
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<meta property="og:type" content="website">
+<meta property="og:title" content="{title}">
+<meta property="og:description" content="{description}">
+</head>
+<body>
+<h1>{title}</h1>
+<p>{description}.</p>
+</body>
+</html>
+"#
+        )
+    }
+}
+
+/// The newest `capacity` run summaries, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RunLog {
+    runs: VecDeque<RunSummary>,
+    #[serde(skip)]
+    capacity: usize,
+}
+
+impl RunLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Self::default()
+        }
+    }
+
+    pub fn push(&mut self, run: RunSummary) {
+        self.runs.push_back(run);
+        while self.runs.len() > self.capacity {
+            self.runs.pop_front();
+        }
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<&RunSummary> {
+        self.runs.iter().find(|run| run.id == id)
+    }
+
+    /// Take in runs saved by an earlier process, keeping this log's capacity
+    pub fn restore(&mut self, saved: RunLog) {
+        for run in saved.runs {
+            self.push(run);
+        }
+    }
+}
+
+/// `GET /runs/{id}`
+pub fn router() -> Router<SharedGameState> {
+    Router::new().route("/runs/{id}", get(get_run))
+}
+
+async fn get_run(
+    State(state): State<SharedGameState>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Response {
+    let game = state.read().await;
+    let Some(run) = game.runs().get(id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let wants_html = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+    if wants_html {
+        Html(run.page()).into_response()
+    } else {
+        Json(run.clone()).into_response()
+    }
+}
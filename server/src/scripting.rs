@@ -0,0 +1,175 @@
+//! Rule scripts: operator-provided WebAssembly modules that run as
+//! plugins, so communities can change spawn tables, upgrade effects and
+//! rewards without building the server.
+//!
+//! Scripts are sandboxed. They get no imports, so all they can do is
+//! compute; each call runs on a fuel budget (`script_fuel`) and their
+//! memory is capped (`script_memory_limit`). A call that traps or runs
+//! out of fuel is logged and the game carries on as if the export were
+//! missing.
+//!
+//! Every export is optional and takes and returns plain numbers. Enemy
+//! types are indexes into `EnemyType::all()`, upgrades into
+//! `UpgradeType::ALL`:
+//! - `spawn_type(ring: i32, default: i32) -> i32`: the enemy to spawn
+//!   instead of `default`; out-of-range values keep it
+//! - `spawn_scale(enemy: i32, ring: i32) -> f32`: multiplier on a new
+//!   enemy's health and damage
+//! - `upgrade_per_level(upgrade: i32, default: f32) -> f32`: what one
+//!   level of an upgrade is worth; read once at startup
+//! - `on_player_join() -> i32`: gold the joining player starts with
+//! - `on_enemy_killed(enemy: i32, ring: i32) -> i32`: bonus gold for the
+//!   killer
+//!
+//! See `scripts/example.wat`.
+
+use anyhow::Context;
+use shared::{Enemy, EnemyType, UpgradeType};
+use std::path::Path;
+use uuid::Uuid;
+use wasmtime::{
+    Engine, Instance, Module, Store, StoreLimits, StoreLimitsBuilder, WasmParams, WasmResults,
+};
+
+use crate::config::GameConfig;
+use crate::game_state::GameState;
+use crate::plugin::GamePlugin;
+
+/// A loaded rule script and the store its instance lives in
+pub struct RuleScript {
+    name: String,
+    store: Store<StoreLimits>,
+    instance: Instance,
+    fuel: u64, // per call
+}
+
+impl RuleScript {
+    /// Compile and instantiate a `.wasm` or `.wat` file
+    pub fn load(path: &Path, config: &GameConfig) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        Self::new(name, &bytes, config)
+    }
+
+    pub fn new(name: String, bytes: &[u8], config: &GameConfig) -> anyhow::Result<Self> {
+        let mut engine_config = wasmtime::Config::new();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config)?;
+        let module = Module::new(&engine, bytes).with_context(|| format!("compiling {name}"))?;
+
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(config.script_memory_limit)
+            .instances(1)
+            .memories(1)
+            .tables(1)
+            .build();
+        let mut store = Store::new(&engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(config.script_fuel)?;
+        let instance = Instance::new(&mut store, &module, &[])
+            .with_context(|| format!("instantiating {name}; scripts can't import anything"))?;
+        Ok(Self {
+            name,
+            store,
+            instance,
+            fuel: config.script_fuel,
+        })
+    }
+
+    /// Let the script set upgrade magnitudes before the game starts
+    pub fn configure(&mut self, config: &mut GameConfig) {
+        for (index, upgrade) in UpgradeType::ALL.iter().enumerate() {
+            let Some(magnitude) = config
+                .upgrade_catalog
+                .upgrades
+                .iter_mut()
+                .find(|m| m.upgrade == *upgrade)
+            else {
+                continue;
+            };
+            let args = (index as i32, magnitude.per_level);
+            if let Some(per_level) = self.call::<_, f32>("upgrade_per_level", args) {
+                if per_level.is_finite() && per_level >= 0.0 {
+                    magnitude.per_level = per_level;
+                }
+            }
+        }
+    }
+
+    /// Run an export with a fresh fuel budget. `None` if the script
+    /// doesn't have it, or it failed.
+    fn call<P: WasmParams, R: WasmResults>(&mut self, export: &str, args: P) -> Option<R> {
+        let func = self
+            .instance
+            .get_typed_func::<P, R>(&mut self.store, export)
+            .ok()?;
+        if let Err(e) = self.store.set_fuel(self.fuel) {
+            tracing::warn!("Script {} can't be refuelled: {}", self.name, e);
+            return None;
+        }
+        match func.call(&mut self.store, args) {
+            Ok(result) => Some(result),
+            Err(e) => {
+                tracing::warn!("Script {} failed in {}: {:#}", self.name, export, e);
+                None
+            }
+        }
+    }
+}
+
+fn enemy_index(enemy_type: EnemyType) -> i32 {
+    EnemyType::all()
+        .iter()
+        .position(|t| *t == enemy_type)
+        .map_or(-1, |i| i as i32)
+}
+
+impl GamePlugin for RuleScript {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn on_player_join(&mut self, game: &mut GameState, player_id: Uuid) {
+        let Some(gold) = self.call::<(), i32>("on_player_join", ()) else {
+            return;
+        };
+        if let Some(player) = game.player_mut(player_id) {
+            player.gold = player.gold.saturating_add(gold.max(0) as u32);
+        }
+    }
+
+    fn on_enemy_killed(&mut self, game: &mut GameState, enemy: &Enemy, killer: Uuid) {
+        let args = (enemy_index(enemy.enemy_type), enemy.spawn_ring as i32);
+        let Some(gold) = self.call::<_, i32>("on_enemy_killed", args) else {
+            return;
+        };
+        if let Some(player) = game.player_mut(killer) {
+            player.gold = player.gold.saturating_add(gold.max(0) as u32);
+        }
+    }
+
+    fn modify_spawn(&mut self, game: &GameState, enemy: &mut Enemy) {
+        let ring = enemy.spawn_ring as i32;
+        let args = (ring, enemy_index(enemy.enemy_type));
+        if let Some(index) = self.call::<_, i32>("spawn_type", args) {
+            let replacement = usize::try_from(index)
+                .ok()
+                .and_then(|i| EnemyType::all().get(i).copied());
+            if let Some(enemy_type) = replacement.filter(|t| *t != enemy.enemy_type) {
+                let roster = &game.config().enemies;
+                *enemy = roster.spawn(enemy.id, enemy_type, enemy.position, enemy.spawn_ring);
+                enemy.scale_stats(game.difficulty_multiplier());
+            }
+        }
+
+        let args = (enemy_index(enemy.enemy_type), ring);
+        if let Some(scale) = self.call::<_, f32>("spawn_scale", args) {
+            if scale.is_finite() && scale > 0.0 {
+                enemy.scale_stats(scale);
+            }
+        }
+    }
+}
@@ -0,0 +1,26 @@
+//! Emotes and location pings: a few words' worth of co-op communication
+//! without chat. Each goes out to the players within `signal_range` of
+//! the sender and to their party wherever they are. Players get
+//! `signal_burst` of them at once, refilling at `signal_rate` a second;
+//! beyond that they're refused as `RateLimited`.
+
+/// One player's allowance of emotes and pings
+#[derive(Debug, Clone, Default)]
+pub struct SignalAllowance {
+    spent: f32,       // signals sent beyond what has refilled since
+    last_update: f64, // game time `spent` was last worked out
+}
+
+impl SignalAllowance {
+    /// Take one signal at game time `now`, if the allowance has one left
+    pub fn take(&mut self, now: f64, burst: u32, rate: f32) -> bool {
+        let refilled = (now - self.last_update) as f32 * rate;
+        self.spent = (self.spent - refilled).max(0.0);
+        self.last_update = now;
+        if self.spent + 1.0 > burst as f32 {
+            return false;
+        }
+        self.spent += 1.0;
+        true
+    }
+}
@@ -0,0 +1,232 @@
+//! Snapshots serialized once and shared across connections.
+//!
+//! At the end of every step `SnapshotCache::refresh` re-serializes only the
+//! entities the step changed (see `EntityMap::changed_since`), plus the
+//! player outlines everyone is sent, keeping each as shared `Bytes`. The
+//! outlines are also redone whenever someone joins or leaves. A
+//! connection's snapshot is then stitched together from those pieces in its
+//! own `SnapshotBuffer`, whose allocations carry over from one snapshot to
+//! the next. Nothing is serialized per connection but the receiver's own
+//! player.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use serde::Serialize;
+use shared::{Position, RemotePlayerView};
+use std::collections::HashMap;
+use std::io::Write;
+use uuid::Uuid;
+
+use crate::bandwidth;
+use crate::entity_map::{EntityId, EntityMap};
+use crate::game_state::GameState;
+
+/// One entity as it goes out in snapshots
+#[derive(Debug, Clone)]
+struct Fragment {
+    id: EntityId,
+    position: Position,
+    json: Bytes,
+}
+
+/// The serialized entities of one kind, kept in step with their map
+#[derive(Debug, Clone, Default)]
+struct Fragments {
+    list: Vec<Fragment>,
+    index: HashMap<EntityId, usize>, // entity -> place in `list`
+}
+
+impl Fragments {
+    /// Catch up with the changes to `map` from version `since` on, or
+    /// start over without a `since` or once it's beyond the map's history
+    fn refresh<T: Serialize>(
+        &mut self,
+        map: &EntityMap<T>,
+        since: Option<u64>,
+        position: impl Fn(&T) -> Position,
+    ) {
+        let changes = since.and_then(|v| Some((map.removed_since(v)?, map.changed_since(v)?)));
+        match changes {
+            Some((removed, changed)) => {
+                for id in removed {
+                    self.remove(id);
+                }
+                for id in changed {
+                    self.put(id, &map[id], &position);
+                }
+            }
+            None => {
+                self.list.clear();
+                self.index.clear();
+                for (id, value) in map.iter() {
+                    self.put(id, value, &position);
+                }
+            }
+        }
+    }
+
+    fn put<T: Serialize>(&mut self, id: EntityId, value: &T, position: impl Fn(&T) -> Position) {
+        let json = serde_json::to_vec(value).expect("entities always serialize");
+        let fragment = Fragment {
+            id,
+            position: position(value),
+            json: json.into(),
+        };
+        match self.index.get(&id) {
+            Some(&at) => self.list[at] = fragment,
+            None => {
+                self.index.insert(id, self.list.len());
+                self.list.push(fragment);
+            }
+        }
+    }
+
+    fn remove(&mut self, id: EntityId) {
+        let Some(at) = self.index.remove(&id) else {
+            return;
+        };
+        self.list.swap_remove(at);
+        if let Some(moved) = self.list.get(at) {
+            self.index.insert(moved.id, at);
+        }
+    }
+}
+
+/// Everything in a snapshot that's the same for every receiver, as of the
+/// end of the latest step
+#[derive(Debug, Clone)]
+pub struct SnapshotCache {
+    version: Option<u64>, // entity version it was last refreshed at
+    players: Bytes,       // everyone's outline
+    enemies: Fragments,
+    projectiles: Fragments,
+    pickups: Fragments,
+    summons: Fragments,
+}
+
+impl Default for SnapshotCache {
+    /// Empty until the first refresh
+    fn default() -> Self {
+        Self {
+            version: None,
+            players: Bytes::from_static(b"[]"),
+            enemies: Fragments::default(),
+            projectiles: Fragments::default(),
+            pickups: Fragments::default(),
+            summons: Fragments::default(),
+        }
+    }
+}
+
+impl SnapshotCache {
+    /// Re-serialize what changed since the last refresh
+    pub fn refresh(&mut self, game: &GameState) {
+        let since = self.version.map(|v| v + 1);
+        self.enemies.refresh(game.enemies(), since, |e| e.position);
+        self.projectiles
+            .refresh(game.projectiles(), since, |p| p.position);
+        self.pickups.refresh(game.pickups(), since, |p| p.position);
+        self.summons.refresh(game.summons(), since, |s| s.position);
+        self.refresh_players(game);
+        self.version = Some(game.entity_version());
+    }
+
+    /// Re-serialize everyone's outline
+    pub fn refresh_players(&mut self, game: &GameState) {
+        let now = game.game_time();
+        let players: Vec<_> = game
+            .players()
+            .map(|p| RemotePlayerView::of(p, now))
+            .collect();
+        self.players = serde_json::to_vec(&players)
+            .expect("players always serialize")
+            .into();
+    }
+}
+
+/// One connection's snapshot buffers, reused for each snapshot it's sent
+#[derive(Debug, Default)]
+pub struct SnapshotBuffer {
+    out: BytesMut,
+    picked: Vec<usize>, // fragments that made it through the view filter
+}
+
+impl SnapshotBuffer {
+    /// `player_id`'s snapshot, as the JSON of its `Envelope`. With a
+    /// `limit`, only that many enemies, projectiles, pickups and summons
+    /// nearest the player are included. The buffer gets its allocation
+    /// back once the previous snapshot has been sent and dropped.
+    pub fn write(
+        &mut self,
+        game: &GameState,
+        player_id: Uuid,
+        limit: Option<usize>,
+        seq: u64,
+    ) -> Bytes {
+        self.write_into(game, player_id, limit, seq)
+            .expect("writing to memory can't fail");
+        self.out.split().freeze()
+    }
+
+    fn write_into(
+        &mut self,
+        game: &GameState,
+        player_id: Uuid,
+        limit: Option<usize>,
+        seq: u64,
+    ) -> std::io::Result<()> {
+        let cache = game.snapshot_cache();
+        let own = game.player(player_id);
+        // Only what the client can see, plus a margin so nothing pops in at
+        // the screen edge
+        let view = own.map(|p| (p.position, game.aoi_radius(p)));
+        let center = view.map(|(center, _)| center).unwrap_or_default();
+
+        let mut out = (&mut self.out).writer();
+        write!(
+            out,
+            r#"{{"server_tick":{},"seq":{},"type":"GameState","own_player":"#,
+            game.tick(),
+            seq
+        )?;
+        serde_json::to_writer(&mut out, &own)?;
+        out.write_all(br#","players":"#)?;
+        out.write_all(&cache.players)?;
+
+        let kinds = [
+            ("enemies", &cache.enemies),
+            ("projectiles", &cache.projectiles),
+            ("pickups", &cache.pickups),
+            ("summons", &cache.summons),
+        ];
+        for (name, fragments) in kinds {
+            let list = &fragments.list;
+            self.picked.clear();
+            self.picked.extend((0..list.len()).filter(|&i| {
+                view.is_none_or(|(center, radius)| list[i].position.distance_to(&center) <= radius)
+            }));
+            if let Some(limit) = limit {
+                let picked = std::mem::take(&mut self.picked);
+                self.picked = bandwidth::nearest(picked, &center, limit, |&i| list[i].position);
+            }
+
+            write!(out, r#","{}":["#, name)?;
+            for (n, &i) in self.picked.iter().enumerate() {
+                if n > 0 {
+                    out.write_all(b",")?;
+                }
+                out.write_all(&list[i].json)?;
+            }
+            out.write_all(b"]")?;
+        }
+
+        out.write_all(br#","game_time":"#)?;
+        serde_json::to_writer(&mut out, &game.game_time())?;
+        write!(
+            out,
+            r#","difficulty_tier":{},"tick":{},"server_time_ms":{}}}"#,
+            game.difficulty_tier(),
+            game.tick(),
+            game.tick_wall_time_ms()
+        )
+    }
+}
@@ -0,0 +1,3442 @@
+use proptest::prelude::*;
+use shared::{
+    ClientMessage, CombatEventKind, DespawnReason, EmoteKind, Enemy, EnemyType, EntityEvent,
+    EntityKind, Envelope, Equipment, EquipmentSlot, ErrorCode, LandmarkType, Pickup, PickupType,
+    Player, Position, Projectile, RankChange, Rarity, ServerMessage, ShopItem, StatModifiers,
+    UpgradeChoice, UpgradeType, Vec2, Weapon,
+};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::access::{Ban, BanList, BanTarget, ConnectionCounts};
+use crate::bandwidth::{self, BandwidthBudget};
+use crate::config::GameConfig;
+use crate::daily::{self, DailyBoard};
+use crate::effects::{EffectKind, TimedEffect};
+use crate::entity_map::{self, EntityMap};
+use crate::events::GameEvent;
+use crate::game_loop;
+use crate::game_mode::GameMode;
+use crate::game_state::{GameState, JoinOutcome, OutboundMessage};
+use crate::input::InputKind;
+use crate::kill_credit::KillCredit;
+use crate::level_up::PendingLevelUp;
+use crate::match_history::{self, MatchHistory};
+use crate::network;
+use crate::persistence;
+use crate::rooms::{RoomOverrides, RoomRejection, Rooms};
+use crate::snapshot::SnapshotBuffer;
+use crate::validation;
+
+const DT: f32 = 0.05;
+
+/// Any f32, weighted toward the values that break naive float code
+fn any_coordinate() -> impl Strategy<Value = f32> {
+    prop_oneof![
+        Just(f32::NAN),
+        Just(f32::INFINITY),
+        Just(f32::NEG_INFINITY),
+        -3000.0f32..3000.0,
+        any::<f32>(),
+    ]
+}
+
+#[test]
+fn test_non_finite_move_is_ignored() {
+    let mut game = GameState::new(GameConfig::default());
+    let id = Uuid::new_v4();
+    let start = game.add_player(id).position;
+
+    game.move_player(id, Position::new(f32::NAN, 10.0), DT);
+    game.move_player(id, Position::new(f32::INFINITY, 0.0), DT);
+
+    assert_eq!(game.players[&id].position, start);
+}
+
+#[test]
+fn test_entity_map_reuses_slots_without_reviving_stale_ids() {
+    let mut map = EntityMap::new();
+    let a = map.insert("a");
+    let b = map.insert("b");
+
+    assert_eq!(map.remove(a), Some("a"));
+    assert_eq!(map.remove(a), None);
+    let c = map.insert("c");
+
+    // Same slot, new generation: the old handle must not see the new entity
+    assert_ne!(a, c);
+    assert_eq!(map.get(a), None);
+    assert_eq!(map[c], "c");
+    assert_eq!(map.len(), 2);
+
+    map.retain(|id, _| id != b);
+    assert!(!map.contains(b));
+    assert_eq!(map.values().collect::<Vec<_>>(), vec![&"c"]);
+}
+
+#[test]
+fn test_entity_map_slots_outlive_heavy_churn() {
+    let mut map = EntityMap::new();
+    let first = map.insert(0);
+    map.remove(first);
+    let mut last = first;
+    for n in 1..=10_000 {
+        last = map.insert(n);
+        map.remove(last);
+    }
+
+    // Still the one slot, and every generation a distinct ID
+    assert_eq!(last.to_string(), "0v10000");
+    assert_ne!(last.to_uuid(0), first.to_uuid(0));
+    let id = map.insert(-1);
+    assert_eq!(id.to_string(), "0v10001");
+    assert_eq!(map.get(first), None);
+    assert_eq!(map[id], -1);
+}
+
+#[test]
+fn test_projectiles_and_drops_take_their_ids_from_pooled_slots() {
+    let config = GameConfig {
+        seed: Some(7),
+        ..GameConfig::default()
+    };
+    let mut state = GameState::new(config.clone());
+    let shot = || {
+        Projectile::new(
+            Uuid::new_v4(),
+            Position::default(),
+            Vec2::new(1.0, 0.0),
+            300.0,
+            10.0,
+            1.0,
+        )
+    };
+    let first = state.insert_projectile(shot());
+    let second = state.insert_projectile(shot());
+    let first_id = state.projectiles[first].id;
+    assert!(!first_id.is_nil());
+    assert_ne!(first_id, state.projectiles[second].id);
+
+    // A freed slot is reused, under an ID its last occupant never had
+    state.projectiles.remove(first);
+    let third = state.insert_projectile(shot());
+    assert_ne!(state.projectiles[third].id, first_id);
+
+    // Drops in the same slot get IDs of their own
+    let drop = state.insert_pickup(Pickup::new(PickupType::Gold, Position::default(), 5, 10.0));
+    assert_ne!(state.pickups[drop].id, first_id);
+
+    // The same seed hands out the same IDs, for replays
+    let mut again = GameState::new(config);
+    let replayed = again.insert_projectile(shot());
+    assert_eq!(again.projectiles[replayed].id, first_id);
+}
+
+#[test]
+fn test_entity_map_lists_changes_since_a_version() {
+    let mut map = EntityMap::new();
+    let ids: Vec<_> = (0..4).map(|n| map.insert(n)).collect();
+    let changed = |map: &EntityMap<i32>, since| {
+        let mut ids: Vec<_> = map.changed_since(since).unwrap().collect();
+        ids.sort();
+        ids
+    };
+
+    map.set_version(1);
+    assert!(changed(&map, 1).is_empty());
+    *map.get_mut(ids[1]).unwrap() += 10;
+    map.update(|_, n| {
+        *n += 1;
+        *n == 1 // only the first one counts as a change
+    });
+    assert_eq!(changed(&map, 1), vec![ids[0], ids[1]]);
+
+    // Changes over several versions come back once each; removals separately
+    map.set_version(2);
+    *map.get_mut(ids[1]).unwrap() += 10;
+    map.remove(ids[3]);
+    assert_eq!(changed(&map, 1), vec![ids[0], ids[1]]);
+    assert_eq!(changed(&map, 2), vec![ids[1]]);
+    let removed: Vec<_> = map.removed_since(1).unwrap().collect();
+    assert_eq!(removed, vec![ids[3]]);
+
+    // Past the kept history, readers have to start over
+    map.set_version(2 + entity_map::CHANGE_HISTORY);
+    assert!(map.changed_since(1).is_none());
+    assert!(map.changed_since(2).is_some());
+}
+
+#[test]
+fn test_enemies_that_hold_still_are_not_marked_changed() {
+    let config = GameConfig {
+        enemy_spawn_rate: 0.0,
+        separation_strength: 0.0,
+        ..GameConfig::default()
+    };
+    let mut state = GameState::new(config);
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let orc = |x| Enemy::new(Uuid::new_v4(), EnemyType::Orc, Position::new(x, 600.0), 1);
+    state.enemies.insert(orc(0.0));
+    state.enemies.insert(orc(50.0));
+    game_loop::step(&mut state, DT);
+    assert_eq!(state.tick_stats.changed_entities, 2);
+
+    // Nobody to chase: after one tick of settling, nothing changes
+    state.remove_player(player);
+    game_loop::step(&mut state, DT);
+    game_loop::step(&mut state, DT);
+    assert_eq!(state.tick_stats.changed_entities, 0);
+}
+
+#[test]
+fn test_ageing_pickups_and_projectiles_count_as_changed_only_when_they_move() {
+    let config = GameConfig {
+        enemy_spawn_rate: 0.0,
+        ..GameConfig::default()
+    };
+    let mut state = GameState::new(config);
+    let at = Position::new(0.0, 900.0);
+    state.insert_pickup(Pickup::new(PickupType::Gold, at, 5, 60.0));
+    let owner = Uuid::new_v4();
+    let resting = Projectile::new(owner, at, Vec2::new(1.0, 0.0), 0.0, 10.0, 60.0);
+    let flying = Projectile::new(owner, at, Vec2::new(1.0, 0.0), 100.0, 10.0, 60.0);
+    state.insert_projectile(resting);
+    state.insert_projectile(flying);
+    game_loop::step(&mut state, DT);
+
+    game_loop::step(&mut state, DT);
+    assert_eq!(state.tick_stats.changed_entities, 1, "only the flying shot");
+    assert!(state.pickups.values().all(|p| p.lifetime < 60.0));
+}
+
+#[test]
+fn test_steps_over_the_slow_tick_threshold_are_counted_with_a_breakdown() {
+    let mut state = GameState::new(GameConfig::default());
+    state.add_player(Uuid::new_v4());
+    game_loop::step(&mut state, DT);
+    assert_eq!(state.tick_stats.slow_ticks, 0, "off by default");
+
+    // Every step takes longer than a nanosecond
+    state.config.slow_tick_threshold_ms = 1e-6;
+    game_loop::step(&mut state, DT);
+    game_loop::step(&mut state, DT);
+    let stats = &state.tick_stats;
+    assert_eq!(stats.slow_ticks, 2);
+    let b = stats.breakdown;
+    let areas = [
+        b.spawning_ms,
+        b.ai_ms,
+        b.projectiles_ms,
+        b.combat_ms,
+        b.broadcast_ms,
+        b.other_ms,
+    ];
+    assert!(areas.iter().all(|&ms| ms > 0.0), "{b:?}");
+    assert!(areas.iter().sum::<f64>() >= stats.slowest_stage_ms);
+}
+
+#[test]
+fn test_late_wakes_catch_up_in_fixed_steps_until_the_cap_drops_the_rest() {
+    let tick = Duration::from_millis(50);
+    let mut accumulator = game_loop::Accumulator::new(tick);
+
+    // On time: one step, and a partial tick carries over to the next wake
+    accumulator.add(Duration::from_millis(70));
+    assert_eq!(accumulator.take_steps(0, 4), (1, None));
+    accumulator.add(Duration::from_millis(30));
+    assert_eq!(accumulator.take_steps(0, 4), (1, None));
+
+    // A wake three ticks late makes them all up
+    accumulator.add(tick * 3);
+    assert_eq!(accumulator.take_steps(0, 4), (3, None));
+
+    // Steps already run this wake count toward the cap; what's left over
+    // the cap is dropped rather than owed forever
+    accumulator.add(Duration::from_millis(1000));
+    let (steps, dropped) = accumulator.take_steps(1, 4);
+    assert_eq!(steps, 3);
+    assert!((dropped.unwrap() - 0.85).abs() < 1e-9);
+    assert_eq!(accumulator.take_steps(0, 4), (0, None));
+
+    // Paused time is forgotten, not made up later
+    accumulator.add(tick * 2);
+    accumulator.clear();
+    assert_eq!(accumulator.take_steps(0, 4), (0, None));
+}
+
+#[test]
+fn test_snapshot_rate_halves_under_pressure_and_recovers() {
+    let config = GameConfig::default(); // 20 Hz down to 5 Hz
+    let start = Instant::now();
+    let mut budget = BandwidthBudget::new(&config, start);
+    let second = |n: u32| start + Duration::from_secs(n as u64);
+
+    // Two congested seconds step 20 -> 10 -> 5 Hz; a third can't go lower
+    for n in 1..=3 {
+        budget.record(config.bandwidth_budget + 1);
+        budget.snapshot_due(second(n));
+    }
+    assert_eq!(budget.rate_scale(), 0.25);
+    assert!(budget.under_pressure());
+    let due = (0..8).filter(|_| budget.snapshot_due(second(3))).count();
+    assert_eq!(due, 2);
+
+    // Quiet seconds bring it back up one step at a time
+    budget.snapshot_due(second(4));
+    assert_eq!(budget.rate_scale(), 0.5);
+    budget.snapshot_due(second(5));
+    assert!(!budget.under_pressure());
+}
+
+#[test]
+fn test_nearest_keeps_closest_entities() {
+    let positions: Vec<Position> = [300.0, 10.0, f32::NAN, 50.0, 200.0]
+        .into_iter()
+        .map(|x| Position::new(x, 0.0))
+        .collect();
+
+    let kept = bandwidth::nearest(positions, &Position::new(0.0, 0.0), 2, |p| *p);
+    assert_eq!(
+        kept,
+        vec![Position::new(10.0, 0.0), Position::new(50.0, 0.0)]
+    );
+}
+
+proptest! {
+    #[test]
+    fn prop_bad_input_and_corrupt_enemies_never_panic(
+        target in (any_coordinate(), any_coordinate()),
+        dash in (any_coordinate(), any_coordinate()),
+        enemy in (any_coordinate(), any_coordinate()),
+    ) {
+        let mut game = GameState::new(GameConfig::default());
+        let id = Uuid::new_v4();
+        game.add_player(id);
+        game.players.get_mut(&id).unwrap().position = Position::new(300.0, 0.0);
+
+        // One enemy next to the player, one somewhere arbitrary (possibly NaN)
+        for position in [Position::new(320.0, 0.0), Position::new(enemy.0, enemy.1)] {
+            let e = Enemy::new(Uuid::new_v4(), EnemyType::Goblin, position, 1);
+            game.enemies.insert(e);
+        }
+
+        game.move_player(id, Position::new(target.0, target.1), DT);
+        let _ = game.dash(id, Vec2::new(dash.0, dash.1));
+        for _ in 0..20 {
+            game_loop::step(&mut game, DT);
+        }
+
+        prop_assert!(game.players[&id].position.is_finite());
+    }
+}
+
+fn any_enemy_type() -> impl Strategy<Value = EnemyType> {
+    prop::sample::select(EnemyType::all())
+}
+
+/// A point anywhere on the map
+fn map_position() -> impl Strategy<Value = Position> {
+    (0.0f32..2500.0, 0.0f32..std::f32::consts::TAU)
+        .prop_map(|(r, angle)| Position::new(r * angle.cos(), r * angle.sin()))
+}
+
+/// A world with a few players and a crowd of enemies scattered around them
+fn any_world() -> impl Strategy<Value = GameState> {
+    (
+        prop::collection::vec(map_position(), 1..4),
+        prop::collection::vec((any_enemy_type(), map_position(), 1u32..=10), 0..30),
+    )
+        .prop_map(|(players, enemies)| {
+            let mut game = GameState::new(GameConfig::default());
+            for position in players {
+                let id = Uuid::new_v4();
+                game.add_player(id);
+                game.players.get_mut(&id).unwrap().position = position;
+            }
+            for (enemy_type, position, ring) in enemies {
+                let enemy = Enemy::new(Uuid::new_v4(), enemy_type, position, ring);
+                game.enemies.insert(enemy);
+            }
+            game
+        })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn prop_simulation_invariants_hold(mut game in any_world()) {
+        let map_size = game.config.map_size + 0.01;
+        for _ in 0..100 {
+            game_loop::step(&mut game, DT);
+
+            for p in game.players.values() {
+                prop_assert!(p.health >= 0.0 && p.health <= p.max_health);
+                prop_assert!(p.position.distance_from_center() <= map_size);
+            }
+            for e in game.enemies.values() {
+                prop_assert!(e.health >= 0.0 && e.health <= e.max_health);
+                prop_assert!(e.position.distance_from_center() <= map_size);
+            }
+        }
+    }
+
+    #[test]
+    fn prop_projectiles_always_expire(
+        shots in prop::collection::vec((map_position(), map_position(), 0.0f32..5.0), 1..20),
+    ) {
+        // No players, so nothing fires new shots and nothing spawns
+        let mut game = GameState::new(GameConfig::default());
+        for (position, direction, lifetime) in shots {
+            let shot = Projectile::new(Uuid::new_v4(), position, direction - Position::default(), 300.0, 10.0, lifetime);
+            game.projectiles.insert(shot);
+        }
+
+        for _ in 0..=(5.0 / DT) as usize + 1 {
+            game_loop::step(&mut game, DT);
+        }
+        prop_assert!(game.projectiles.is_empty());
+    }
+
+    #[test]
+    fn prop_xp_math_never_underflows(amounts in prop::collection::vec(any::<u32>(), 1..20)) {
+        let mut player = Player::new(Uuid::new_v4());
+        for amount in amounts {
+            let level_before = player.level;
+            let gained = player.grant_xp(amount);
+
+            prop_assert_eq!(player.level, level_before + gained);
+            prop_assert!(player.xp_to_next_level > 0);
+            prop_assert!(player.current_xp < player.xp_to_next_level);
+        }
+    }
+}
+
+#[test]
+fn test_client_ip_prefers_the_proxy_appended_forwarded_for() {
+    use axum::http::HeaderMap;
+
+    let peer = "10.0.0.2:40000".parse().unwrap();
+    let mut headers = HeaderMap::new();
+    headers.insert("x-forwarded-for", "1.2.3.4, 203.0.113.7".parse().unwrap());
+
+    assert_eq!(network::client_ip(peer, &headers, false), peer.ip());
+    assert_eq!(
+        network::client_ip(peer, &headers, true),
+        "203.0.113.7".parse::<std::net::IpAddr>().unwrap()
+    );
+
+    // Garbage from the proxy falls back to the socket address
+    headers.insert("x-forwarded-for", "unknown".parse().unwrap());
+    assert_eq!(network::client_ip(peer, &headers, true), peer.ip());
+    assert_eq!(network::client_ip(peer, &HeaderMap::new(), true), peer.ip());
+}
+
+#[test]
+fn test_normalize_base_path() {
+    assert_eq!(network::normalize_base_path(""), "");
+    assert_eq!(network::normalize_base_path("/"), "");
+    assert_eq!(network::normalize_base_path("game"), "/game");
+    assert_eq!(network::normalize_base_path("/game/"), "/game");
+    assert_eq!(network::normalize_base_path("/a/b"), "/a/b");
+}
+
+#[test]
+fn test_connection_counts_free_slots_on_drop() {
+    let counts = ConnectionCounts::default();
+    let ip = "203.0.113.7".parse().unwrap();
+
+    let first = counts.try_acquire(ip, 2).unwrap();
+    let second = counts.try_acquire(ip, 2).unwrap();
+    assert!(counts.try_acquire(ip, 2).is_none());
+    assert!(counts
+        .try_acquire("203.0.113.8".parse().unwrap(), 2)
+        .is_some());
+
+    drop(first);
+    assert_eq!(counts.get(ip), 1);
+    let _third = counts.try_acquire(ip, 2).unwrap();
+    drop(second);
+    assert_eq!(counts.get(ip), 1);
+    assert!(counts.try_acquire(ip, 0).is_some(), "0 means unlimited");
+}
+
+#[test]
+fn test_ip_ban_kicks_every_player_on_that_address() {
+    let mut state = GameState::new(GameConfig::default());
+    let ip = "203.0.113.7".parse().unwrap();
+    let (a, b, other) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    for id in [a, b, other] {
+        state.add_player(id);
+    }
+    state.player_ips.insert(a, ip);
+    state.player_ips.insert(b, ip);
+    state
+        .player_ips
+        .insert(other, "203.0.113.8".parse().unwrap());
+
+    let mut kicked = state.ban(Ban {
+        target: BanTarget::Ip(ip),
+        reason: None,
+        banned_at: chrono::Utc::now(),
+    });
+    kicked.sort();
+    let mut expected = vec![a, b];
+    expected.sort();
+
+    assert_eq!(kicked, expected);
+    assert!(state.players.contains_key(&other));
+    assert!(!state.players.contains_key(&a) && !state.players.contains_key(&b));
+    assert!(state.bans.get(&BanTarget::Ip(ip)).is_some());
+}
+
+#[tokio::test]
+async fn test_ban_list_round_trips_through_disk() {
+    let path = std::env::temp_dir().join(format!("bans-{}.json", Uuid::new_v4()));
+    assert!(BanList::load(&path).unwrap().to_vec().is_empty());
+
+    let bans: BanList = [
+        BanTarget::Ip("::1".parse().unwrap()),
+        BanTarget::Player(Uuid::new_v4()),
+    ]
+    .into_iter()
+    .map(|target| Ban {
+        target,
+        reason: Some("test".to_string()),
+        banned_at: chrono::Utc::now(),
+    })
+    .collect();
+    bans.save(&path).await.unwrap();
+
+    assert_eq!(BanList::load(&path).unwrap().to_vec(), bans.to_vec());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_joins_beyond_max_players_queue_then_get_admitted() {
+    let mut state = GameState::new(GameConfig {
+        max_players: 1,
+        max_queue_length: 1,
+        ..GameConfig::default()
+    });
+    let (first, second, third) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+    assert_eq!(state.request_join(first), JoinOutcome::Joined);
+    assert_eq!(state.request_join(second), JoinOutcome::Queued(1));
+    assert_eq!(state.request_join(third), JoinOutcome::Rejected);
+    assert!(!state.players.contains_key(&second));
+
+    state.remove_player(first);
+    assert!(state.players.contains_key(&second));
+    assert!(state.join_queue.is_empty());
+
+    // A queued player who leaves just drops out of the queue
+    assert_eq!(state.request_join(third), JoinOutcome::Queued(1));
+    assert!(state.leave_queue(third));
+    assert!(!state.leave_queue(third));
+}
+
+#[test]
+fn test_idle_players_are_warned_then_removed() {
+    let mut state = GameState::new(GameConfig {
+        afk_warning_after: 1.0,
+        afk_timeout: 2.0,
+        ..GameConfig::default()
+    });
+    let mut events = state.subscribe_events();
+    let (idle, active) = (Uuid::new_v4(), Uuid::new_v4());
+    state.add_player(idle);
+    state.add_player(active);
+
+    let mut warned = Vec::new();
+    for _ in 0..(2.5 / DT) as usize {
+        state.record_input(active);
+        game_loop::step(&mut state, DT);
+        while let Ok(event) = events.try_recv() {
+            if let ServerMessage::AfkWarning { seconds_left } = event.message {
+                assert_eq!(seconds_left, Some(1.0));
+                warned.extend(event.recipient);
+            }
+        }
+    }
+
+    assert_eq!(warned, vec![idle], "warned once, and only the idle player");
+    assert!(!state.players.contains_key(&idle));
+    assert!(state.players.contains_key(&active));
+}
+
+#[test]
+fn test_match_end_records_everyone_who_played() {
+    let mut state = GameState::new(GameConfig {
+        mode: GameMode::Timed,
+        timed_duration: 1.0,
+        ..GameConfig::default()
+    });
+    let mut events = state.subscribe_events();
+    let (stayed, left) = (Uuid::new_v4(), Uuid::new_v4());
+    state.add_player(stayed);
+    state.add_player(left);
+    state.players.get_mut(&stayed).unwrap().bosses_defeated = 2;
+    let regen = TimedEffect::periodic(stayed, EffectKind::Heal(1.0), 1.0, f32::INFINITY, 0.0);
+    state.add_effect(regen);
+    let match_id = state.match_id;
+
+    game_loop::step(&mut state, DT);
+    state.remove_player(left);
+    assert!(state.match_history.summaries().is_empty());
+    for _ in 0..(1.0 / DT) as usize {
+        game_loop::step(&mut state, DT);
+    }
+
+    let record = state.match_history.get(match_id).expect("match recorded");
+    assert_eq!(record.outcome, "Time's up");
+    assert_eq!(record.winners, vec![stayed]);
+    assert_eq!(record.bosses_killed, 2);
+    let left_early: Vec<(Uuid, bool)> = record
+        .participants
+        .iter()
+        .map(|p| (p.player_id, p.left_early))
+        .collect();
+    assert_eq!(left_early, vec![(left, true), (stayed, false)]);
+    assert_ne!(state.match_id, match_id, "the next match gets a new ID");
+    let heal = EffectKind::Heal(1.0);
+    assert!(
+        !state.effects.on(stayed).any(|e| e.kind == heal),
+        "buffs end with the match"
+    );
+    let endings: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+        .filter_map(|event| match event.message {
+            ServerMessage::MatchEnded { .. } => Some("ended"),
+            ServerMessage::WorldCleared => Some("cleared"),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(endings, ["ended", "cleared"]);
+}
+
+#[test]
+fn test_endless_runs_are_recorded_when_the_player_leaves() {
+    let mut state = GameState::new(GameConfig::default());
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    state.players.get_mut(&player).unwrap().health = 0.0;
+    state.remove_player(player);
+
+    let summaries = state.match_history.summaries();
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries[0].outcome, "Died");
+    let record = state.match_history.get(summaries[0].id).unwrap();
+    assert_eq!(record.participants[0].player_id, player);
+    assert!(!record.participants[0].survived);
+}
+
+#[tokio::test]
+async fn test_rooms_close_once_nobody_has_played_in_them_for_a_while() {
+    let rooms = Rooms::default();
+    let mut config = GameConfig::default();
+    RoomOverrides {
+        max_players: Some(2),
+        ..RoomOverrides::default()
+    }
+    .apply(&mut config);
+    let lifetime = Duration::from_secs(60);
+    let busy = rooms.open(config.clone(), true, lifetime).token;
+    let idle = rooms.open(config, true, lifetime).token;
+    let player = Uuid::new_v4();
+    let state = rooms.get(&busy).unwrap();
+    assert_eq!(state.read().await.config.max_players, 2);
+    state.write().await.add_player(player);
+
+    rooms.close_idle(Duration::from_secs(60)).await;
+    assert_eq!(rooms.len(), 2, "the idle one has time left");
+    rooms.close_idle(Duration::ZERO).await;
+    assert!(rooms.get(&idle).is_none());
+    assert!(rooms.get(&busy).is_some());
+
+    // Emptying a room starts its clock over
+    state.write().await.remove_player(player);
+    rooms.close_idle(Duration::ZERO).await;
+    assert!(rooms.get(&busy).is_some());
+    rooms.close_idle(Duration::ZERO).await;
+    assert!(rooms.is_empty());
+}
+
+#[tokio::test]
+async fn test_invite_codes_only_admit_until_they_expire() {
+    let rooms = Rooms::default();
+    let config = GameConfig::default();
+    let open = rooms.open(config.clone(), false, Duration::from_secs(60));
+    let code = open.invite.unwrap().code;
+    assert!(rooms.admit(&open.token, Some(&code)).is_ok());
+    assert_eq!(
+        rooms.admit(&open.token, None).unwrap_err(),
+        RoomRejection::BadInviteCode
+    );
+
+    let expired = rooms.open(config.clone(), false, Duration::ZERO);
+    let code = expired.invite.unwrap().code;
+    assert_eq!(
+        rooms.admit(&expired.token, Some(&code)).unwrap_err(),
+        RoomRejection::BadInviteCode
+    );
+
+    // Public rooms take anyone
+    let public = rooms.open(config, true, Duration::ZERO);
+    assert!(public.invite.is_none());
+    assert!(rooms.admit(&public.token, None).is_ok());
+    assert_eq!(
+        rooms.admit("nope", None).unwrap_err(),
+        RoomRejection::NoSuchRoom
+    );
+}
+
+#[tokio::test]
+async fn test_match_history_keeps_the_newest_records_on_disk_and_in_memory() {
+    let path = std::env::temp_dir().join(format!("matches-{}.jsonl", Uuid::new_v4()));
+    let mut state = GameState::new(GameConfig {
+        max_match_history: 2,
+        ..GameConfig::default()
+    });
+    for _ in 0..3 {
+        let player = Uuid::new_v4();
+        state.add_player(player);
+        state.remove_player(player);
+    }
+    let ids: Vec<Uuid> = state
+        .match_history
+        .summaries()
+        .iter()
+        .map(|s| s.id)
+        .collect();
+    assert_eq!(ids.len(), 2, "only the newest stay in memory");
+
+    let unsaved = state.match_history.take_unsaved();
+    assert_eq!(unsaved.len(), 3, "but all of them get written");
+    match_history::append(&path, &unsaved[..1]).await.unwrap();
+    match_history::append(&path, &unsaved[1..]).await.unwrap();
+    assert!(state.match_history.take_unsaved().is_empty());
+
+    let loaded = MatchHistory::load(&path, 2).unwrap();
+    let loaded_ids: Vec<Uuid> = loaded.summaries().iter().map(|s| s.id).collect();
+    assert_eq!(loaded_ids, ids);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_world_snapshots_resume_the_world_but_not_its_players() {
+    let path = std::env::temp_dir().join(format!("world-{}.json", Uuid::new_v4()));
+    let mut state = GameState::new(GameConfig::default());
+    state.add_player(Uuid::new_v4());
+    let at = Position::new(0.0, 900.0);
+    let troll = state.insert_enemy(Enemy::new(Uuid::new_v4(), EnemyType::Troll, at, 5));
+    state.insert_pickup(Pickup::new(PickupType::Gold, at, 5, 60.0));
+    state.insert_projectile(Projectile::new(
+        Uuid::new_v4(),
+        at,
+        Vec2::new(1.0, 0.0),
+        100.0,
+        10.0,
+        60.0,
+    ));
+    for _ in 0..10 {
+        game_loop::step(&mut state, DT);
+    }
+    state.enemies.get_mut(troll).unwrap().position = Position::new(40.0, 900.0);
+
+    let shared = Arc::new(RwLock::new(state));
+    persistence::save(&shared, &path).await.unwrap();
+    assert!(!path.with_extension("tmp").exists(), "written in place");
+    let saved = shared.read().await;
+
+    let snapshot = persistence::load(&path).unwrap();
+    assert_eq!(snapshot.players.len(), 1, "kept for bug reports");
+    let mut resumed = GameState::new(GameConfig::default());
+    resumed.restore_world(snapshot);
+
+    assert!(resumed.players.is_empty(), "everyone rejoins");
+    assert_eq!(
+        (resumed.tick, resumed.game_time, resumed.match_seed),
+        (saved.tick, saved.game_time, saved.match_seed)
+    );
+    let mut landmarks: Vec<_> = resumed.landmarks.keys().collect();
+    let mut saved_landmarks: Vec<_> = saved.landmarks.keys().collect();
+    landmarks.sort();
+    saved_landmarks.sort();
+    assert_eq!(landmarks, saved_landmarks);
+    assert_eq!(resumed.enemies.len(), saved.enemies.len());
+    let troll_id = saved.enemies[troll].id;
+    let troll = resumed.enemies.values().find(|e| e.id == troll_id).unwrap();
+    assert_eq!(troll.position, Position::new(40.0, 900.0));
+    assert_eq!(troll.home, troll.position, "leashed from where it stood");
+    assert_eq!(resumed.pickups.len(), saved.pickups.len());
+    assert_eq!(resumed.projectiles.len(), saved.projectiles.len());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_daily_runs_share_the_days_seed_and_spawns() {
+    let today = daily::today();
+    assert_eq!(daily::seed_for(today), daily::seed_for(today));
+    assert_ne!(
+        daily::seed_for(today),
+        daily::seed_for(today.succ_opt().unwrap())
+    );
+
+    let spawns = || {
+        let mut state = GameState::new(GameConfig {
+            mode: GameMode::Daily,
+            ..GameConfig::default()
+        });
+        assert_eq!(state.match_seed, daily::seed_for(today));
+        state.add_player(Uuid::new_v4());
+        while state.enemies.is_empty() {
+            game_loop::step(&mut state, DT);
+        }
+        let mut spawned: Vec<(EnemyType, i64, i64)> = state
+            .enemies
+            .values()
+            .map(|e| (e.enemy_type, e.home.x as i64, e.home.y as i64))
+            .collect();
+        spawned.sort_by_key(|&(_, x, y)| (x, y));
+        spawned
+    };
+    assert_eq!(spawns(), spawns());
+}
+
+#[test]
+fn test_daily_scores_go_to_the_daily_board_which_rolls_over() {
+    let mut state = GameState::new(GameConfig {
+        mode: GameMode::Daily,
+        ..GameConfig::default()
+    });
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    state.players.get_mut(&player).unwrap().enemies_defeated = 3;
+    state.remove_player(player);
+    assert!(state.scores.is_empty());
+    assert_eq!(state.daily.standings.len(), 1);
+
+    let yesterday = daily::today().pred_opt().unwrap();
+    state.daily = DailyBoard::new(yesterday);
+    state.match_seed = state.daily.seed;
+    let mut events = state.subscribe_events();
+    game_loop::step(&mut state, DT);
+
+    assert!(std::iter::from_fn(|| events.try_recv().ok())
+        .any(|event| matches!(event.message, ServerMessage::MatchEnded { .. })));
+    assert_eq!(state.daily.date, daily::today());
+    assert!(state.daily.standings.is_empty());
+    assert_eq!(state.match_seed, daily::seed_for(daily::today()));
+}
+
+#[test]
+fn test_runs_that_make_the_top_scores_are_broadcast_with_rank_changes() {
+    let mut state = GameState::new(GameConfig {
+        max_scoreboard_entries: 2,
+        ..GameConfig::default()
+    });
+    let finish_run = |state: &mut GameState, ring: u32| {
+        let id = Uuid::new_v4();
+        state.add_player(id);
+        state.players.get_mut(&id).unwrap().max_ring_reached = ring;
+        state.remove_player(id);
+        let mut events = state.subscribe_events();
+        game_loop::step(state, DT);
+        let boards: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+            .filter_map(|event| match event.message {
+                ServerMessage::Scoreboard { scores, changes } => Some((scores, changes)),
+                _ => None,
+            })
+            .collect();
+        (id, boards)
+    };
+    let min_ring = state.config.score_min_ring;
+
+    let (first, boards) = finish_run(&mut state, min_ring);
+    let new_entry = RankChange {
+        player_id: first,
+        from: None,
+        to: Some(1),
+    };
+    assert_eq!(boards.len(), 1);
+    assert_eq!(boards[0].1, [new_entry]);
+
+    // A better run takes first place and pushes the other down
+    let (second, boards) = finish_run(&mut state, min_ring + 2);
+    let (scores, changes) = &boards[0];
+    assert_eq!(scores[0].player_id, second);
+    assert!(changes.contains(&RankChange {
+        player_id: first,
+        from: Some(1),
+        to: Some(2),
+    }));
+
+    // One that only ties last place doesn't make the top two, so says nothing
+    let (_, boards) = finish_run(&mut state, min_ring);
+    assert!(boards.is_empty());
+
+    // One that does pushes the last entry off the end
+    let (_, boards) = finish_run(&mut state, min_ring + 1);
+    let (scores, changes) = &boards[0];
+    assert_eq!(scores.len(), 2);
+    assert!(changes.contains(&RankChange {
+        player_id: first,
+        from: Some(2),
+        to: None,
+    }));
+}
+
+#[test]
+fn test_boards_from_other_servers_merge_without_repeating_runs() {
+    let mut other = GameState::new(GameConfig::default());
+    let player = Uuid::new_v4();
+    other.add_player(player);
+    other.players.get_mut(&player).unwrap().max_ring_reached = other.config.score_min_ring;
+    other.remove_player(player);
+
+    let mut state = GameState::new(GameConfig::default());
+    let mut events = state.subscribe_events();
+    state.add_scores(other.scores.clone());
+    state.add_scores(other.scores.clone());
+    game_loop::step(&mut state, DT);
+
+    assert_eq!(state.scores.len(), 1);
+    assert!(state.scores[0].same_run(&other.scores[0]));
+    let boards = std::iter::from_fn(|| events.try_recv().ok())
+        .filter(|event| matches!(event.message, ServerMessage::Scoreboard { .. }))
+        .count();
+    assert_eq!(boards, 1);
+}
+
+#[test]
+fn test_announcements_reach_every_player() {
+    let mut state = GameState::new(GameConfig::default());
+    let mut events = state.subscribe_events();
+    state.announce("Restarting in five minutes".to_string());
+    game_loop::step(&mut state, DT);
+
+    assert!(
+        std::iter::from_fn(|| events.try_recv().ok()).any(|event| matches!(
+            event.message,
+            ServerMessage::Announcement { text } if text == "Restarting in five minutes"
+        ))
+    );
+}
+
+#[test]
+fn test_entity_events_tell_deaths_in_view_apart_from_culling() {
+    let config = GameConfig {
+        enemy_spawn_rate: 0.0,
+        ..GameConfig::default()
+    };
+    let mut state = GameState::new(config);
+    let mut events = state.subscribe_events();
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let spot = Position::new(600.0, 0.0);
+    state.players.get_mut(&player).unwrap().position = spot;
+
+    // A shot kills an enemy next to the player; another dies out of view
+    let near = Enemy::new(
+        Uuid::new_v4(),
+        EnemyType::Goblin,
+        Position::new(615.0, 0.0),
+        1,
+    );
+    let mut far = Enemy::new(
+        Uuid::new_v4(),
+        EnemyType::Goblin,
+        Position::new(-1800.0, 0.0),
+        1,
+    );
+    far.health = 0.0;
+    let (near_id, far_id) = (near.id, far.id);
+    state.enemies.insert(near);
+    state.enemies.insert(far);
+    let shot = Projectile::new(player, spot, Vec2::new(1.0, 0.0), 300.0, 1000.0, 1.0);
+    let shot = state.insert_projectile(shot);
+    let shot_id = state.projectiles[shot].id;
+    game_loop::step(&mut state, DT);
+
+    let seen: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+        .filter(|event| event.recipient == Some(player))
+        .filter_map(|event| match event.message {
+            ServerMessage::EntityEvents { events } => Some(events),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    let about = |id| {
+        seen.iter()
+            .filter(|e| e.id() == id)
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let shot_events = about(shot_id);
+    assert!(
+        matches!(
+            shot_events[..],
+            [
+                EntityEvent::Spawned {
+                    kind: EntityKind::Projectile,
+                    ..
+                },
+                EntityEvent::Despawned {
+                    reason: DespawnReason::Hit,
+                    ..
+                }
+            ]
+        ),
+        "{:?}",
+        shot_events
+    );
+    assert!(matches!(
+        about(near_id)[..],
+        [EntityEvent::Despawned {
+            kind: EntityKind::Enemy,
+            reason: DespawnReason::Killed,
+            ..
+        }]
+    ));
+    assert!(about(far_id).is_empty(), "out of view");
+}
+
+/// The `EntityDespawned` events `player` was sent for this tick
+fn despawns_seen(
+    events: &mut tokio::sync::broadcast::Receiver<OutboundMessage>,
+    player: Uuid,
+) -> Vec<(Uuid, DespawnReason)> {
+    std::iter::from_fn(|| events.try_recv().ok())
+        .filter(|event| event.recipient == Some(player))
+        .filter_map(|event| match event.message {
+            ServerMessage::EntityEvents { events } => Some(events),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|event| match event {
+            EntityEvent::Despawned { id, reason, .. } => Some((id, reason)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_wave_enemies_reaching_the_core_despawn_without_a_kill() {
+    let mut state = GameState::new(GameConfig {
+        mode: GameMode::WaveDefense,
+        ..GameConfig::default()
+    });
+    let mut events = state.subscribe_events();
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let orc = Enemy::new(Uuid::new_v4(), EnemyType::Orc, Position::new(5.0, 0.0), 1);
+    let (orc_id, damage) = (orc.id, orc.damage);
+    state.enemies.insert(orc);
+
+    state.update_enemies(DT);
+    state.flush_events();
+    assert!(state.enemies.is_empty());
+    assert_eq!(state.core_health, state.config().core_health - damage);
+    assert_eq!(
+        despawns_seen(&mut events, player),
+        vec![(orc_id, DespawnReason::ReachedCore)]
+    );
+}
+
+#[test]
+fn test_shots_leaving_the_map_are_despawned_out_of_bounds() {
+    let mut state = GameState::new(GameConfig::default());
+    let mut events = state.subscribe_events();
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let edge = Position::new(state.config().map_size - 1.0, 0.0);
+    state.players.get_mut(&player).unwrap().position = edge;
+
+    let shot = Projectile::new(player, edge, Vec2::new(1.0, 0.0), 300.0, 10.0, 5.0);
+    let shot = state.insert_projectile(shot);
+    let shot_id = state.projectiles[shot].id;
+    game_loop::step(&mut state, DT);
+
+    assert!(state.projectiles.is_empty());
+    assert_eq!(
+        despawns_seen(&mut events, player),
+        vec![(shot_id, DespawnReason::OutOfBounds)]
+    );
+}
+
+#[test]
+fn test_emotes_and_pings_reach_nearby_players_and_the_party_until_rate_limited() {
+    let mut state = GameState::new(GameConfig::default());
+    let mut events = state.subscribe_events();
+    let [sender, near, far, ally] = [(); 4].map(|_| Uuid::new_v4());
+    for (id, x) in [(sender, 0.0), (near, 300.0), (far, 5000.0), (ally, -5000.0)] {
+        state.add_player(id);
+        state.players.get_mut(&id).unwrap().position = Position::new(x, 0.0);
+    }
+    let party = state.create_party(sender).unwrap();
+    state.join_party(ally, party).unwrap();
+
+    let wave = ClientMessage::Emote {
+        kind: EmoteKind::Wave,
+    };
+    state.handle_command(sender, wave.clone()).unwrap();
+    let ping = ClientMessage::PingLocation {
+        position: Position::new(100.0, 50.0),
+    };
+    state.handle_command(sender, ping).unwrap();
+    game_loop::step(&mut state, DT);
+
+    let mut heard: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+        .filter(|event| {
+            matches!(
+                event.message,
+                ServerMessage::Emoted { .. } | ServerMessage::LocationPinged { .. }
+            )
+        })
+        .filter_map(|event| event.recipient)
+        .collect();
+    heard.sort();
+    let mut expected = vec![sender, sender, near, near, ally, ally];
+    expected.sort();
+    assert_eq!(heard, expected, "everyone in range or the party, twice");
+
+    // One more fits in the burst of three, then the sender has to wait
+    state.handle_command(sender, wave.clone()).unwrap();
+    let refused = state.handle_command(sender, wave.clone()).unwrap_err();
+    assert_eq!(refused.code, ErrorCode::RateLimited);
+    let off_map = ClientMessage::PingLocation {
+        position: Position::new(1.0e9, 0.0),
+    };
+    let refused = validation::check(&off_map, state.config()).unwrap_err();
+    assert_eq!(refused.code, ErrorCode::InvalidField);
+}
+
+#[test]
+fn test_death_summary_carries_the_runs_stats_once() {
+    let mut state = GameState::new(GameConfig::default());
+    let mut events = state.subscribe_events();
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let start = state.players[&player].position;
+    state.move_player(player, Position::new(start.x + 1000.0, start.y), 1.0);
+
+    // Out of the safe zone, kill an enemy and get shot dead in the same tick
+    let spot = Position::new(600.0, 0.0);
+    state.players.get_mut(&player).unwrap().position = spot;
+    let ahead = Position::new(spot.x + 15.0, spot.y);
+    let enemy = Enemy::new(Uuid::new_v4(), EnemyType::Goblin, ahead, 1);
+    let (enemy_health, xp_reward) = (enemy.health, enemy.xp_reward);
+    state.enemies.insert(enemy);
+    let direction = Vec2::new(1.0, 0.0);
+    let shot = Projectile::new(player, spot, direction, 300.0, 1000.0, 1.0);
+    state.projectiles.insert(shot);
+    let mut hostile = Projectile::new(Uuid::new_v4(), spot, direction, 300.0, 1000.0, 1.0);
+    hostile.hostile = true;
+    state.projectiles.insert(hostile);
+
+    game_loop::step(&mut state, DT);
+    game_loop::step(&mut state, DT);
+
+    let summaries: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+        .filter_map(|event| match event.message {
+            ServerMessage::PlayerDied {
+                player_id, stats, ..
+            } if player_id == player => Some(stats),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(summaries.len(), 1, "reported once, not every tick");
+    let stats = &summaries[0];
+    assert!((stats.distance_traveled - 120.0).abs() < 0.01);
+    assert_eq!(stats.damage_dealt, enemy_health, "overkill doesn't count");
+    assert_eq!(stats.damage_by_weapon[&Weapon::AutoAttack], enemy_health);
+    assert_eq!(stats.damage_taken, 100.0);
+    assert_eq!(stats.xp_collected, xp_reward);
+}
+
+#[test]
+fn test_merchant_sells_for_gold_only_in_the_safe_zone() {
+    let mut state = GameState::new(GameConfig::default());
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let p = state.players.get_mut(&player).unwrap();
+    p.position = Position::new(0.0, 0.0);
+    p.health = 10.0;
+    p.gold = 50;
+
+    assert!(state.buy_item(player, ShopItem::DamageBoost).is_ok());
+    assert!(
+        state.buy_item(player, ShopItem::Heal).is_err(),
+        "10 gold left"
+    );
+    assert!(state.buy_item(player, ShopItem::Reroll).is_err());
+
+    let p = state.players.get_mut(&player).unwrap();
+    assert_eq!(p.gold, 10);
+    assert!(p.damage_boost_until > state.game_time);
+    p.gold = 100;
+    p.position = Position::new(600.0, 0.0);
+    assert!(
+        state.buy_item(player, ShopItem::Heal).is_err(),
+        "out in the rings"
+    );
+
+    state.players.get_mut(&player).unwrap().position = Position::new(0.0, 0.0);
+    state.buy_item(player, ShopItem::Heal).unwrap();
+    state.buy_item(player, ShopItem::Reroll).unwrap();
+    let p = &state.players[&player];
+    assert_eq!(p.health, 60.0);
+    assert_eq!(p.rerolls_left, state.config.base_rerolls + 1);
+    assert_eq!(p.gold, 50);
+}
+
+#[test]
+fn test_equipping_swaps_gear_and_dropped_gear_goes_to_others() {
+    let mut state = GameState::new(GameConfig {
+        inventory_size: 1,
+        ..GameConfig::default()
+    });
+    let (owner, other) = (Uuid::new_v4(), Uuid::new_v4());
+    state.add_player(owner);
+    state.add_player(other);
+    let amulet = |max_health| Equipment {
+        id: Uuid::new_v4(),
+        slot: EquipmentSlot::Amulet,
+        level: 1,
+        modifiers: StatModifiers {
+            max_health,
+            ..StatModifiers::default()
+        },
+    };
+    let (first, second) = (amulet(20.0), amulet(30.0));
+    let p = state.players.get_mut(&owner).unwrap();
+    p.inventory = vec![first.clone(), second.clone()];
+
+    state.equip_item(owner, first.id).unwrap();
+    state.equip_item(owner, second.id).unwrap();
+    let p = &state.players[&owner];
+    assert_eq!(p.equipment, vec![second.clone()]);
+    assert_eq!(p.inventory, vec![first.clone()]);
+    assert_eq!((p.health, p.max_health), (100.0, 130.0), "no free healing");
+
+    // The dropper can't pick it straight back up; someone with room can
+    let spot = Position::new(600.0, 0.0);
+    for id in [owner, other] {
+        state.players.get_mut(&id).unwrap().position = spot;
+    }
+    state.players.get_mut(&other).unwrap().inventory = vec![amulet(5.0)];
+    state.drop_item(owner, second.id).unwrap();
+    assert_eq!(state.players[&owner].max_health, 100.0);
+    game_loop::step(&mut state, DT);
+    assert_eq!(state.pickups.len(), 1, "other's bag is full");
+
+    state.players.get_mut(&other).unwrap().inventory.clear();
+    game_loop::step(&mut state, DT);
+    assert!(state.pickups.is_empty());
+    assert_eq!(state.players[&other].inventory, vec![second]);
+}
+
+#[test]
+fn test_wolf_hunts_near_its_owner_and_leaves_with_them() {
+    let mut state = GameState::new(GameConfig::default());
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let spot = Position::new(600.0, 0.0);
+    let p = state.players.get_mut(&player).unwrap();
+    p.position = spot;
+    p.last_attack_time = 1e9; // leave the fighting to the wolf
+    for upgrade in [UpgradeType::SummonWolf, UpgradeType::SummonDamage] {
+        let choice = UpgradeChoice {
+            upgrade,
+            rarity: Rarity::Common,
+        };
+        let offer = PendingLevelUp::new(vec![choice], state.game_time);
+        state.pending_level_ups.insert(player, offer);
+        state.apply_upgrade(player, upgrade).unwrap();
+    }
+    assert_eq!(state.summons.len(), 1);
+
+    let goblin = Enemy::new(
+        Uuid::new_v4(),
+        EnemyType::Goblin,
+        Position::new(700.0, 0.0),
+        1,
+    );
+    let health = goblin.health;
+    let goblin = state.enemies.insert(goblin);
+    for _ in 0..(5.0 / DT) as usize {
+        game_loop::step(&mut state, DT);
+        if !state.enemies.contains(goblin) {
+            break;
+        }
+    }
+
+    assert!(!state.enemies.contains(goblin), "the wolf caught it");
+    let p = &state.players[&player];
+    assert!(p.enemies_defeated >= 1, "kills go to the owner");
+    assert!(p.stats.damage_by_weapon[&Weapon::WolfPet] >= health);
+
+    state.players.get_mut(&player).unwrap().health = 0.0;
+    game_loop::step(&mut state, DT);
+    assert!(state.summons.is_empty());
+}
+
+#[test]
+fn test_teleport_channels_between_attuned_waypoints() {
+    let mut state = GameState::new(GameConfig::default());
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let waypoint = |state: &GameState, ring| {
+        state
+            .landmarks
+            .values()
+            .find(|l| l.landmark_type == LandmarkType::Waypoint && l.ring == ring)
+            .map(|l| (l.id, l.position))
+            .unwrap()
+    };
+    let (home, home_position) = waypoint(&state, 0);
+    let (far, far_position) = waypoint(&state, 3);
+
+    assert!(
+        state.teleport_to(player, far).is_err(),
+        "not attuned to it yet"
+    );
+    state.players.get_mut(&player).unwrap().position = far_position;
+    state.interact(player, far).unwrap();
+    assert!(state.interact(player, far).is_err(), "already attuned");
+
+    // Walk away from any waypoint and the channel can't start
+    state.players.get_mut(&player).unwrap().position = Position::new(50.0, 50.0);
+    assert!(state.teleport_to(player, home).is_err());
+
+    // Moving cancels the channel
+    state.players.get_mut(&player).unwrap().position = home_position;
+    state.teleport_to(player, far).unwrap();
+    state.move_player(player, Position::new(30.0, 0.0), DT);
+    assert!(state.players[&player].teleport.is_none());
+
+    // A recent hit locks teleporting out for a while
+    state.players.get_mut(&player).unwrap().position = home_position;
+    state.players.get_mut(&player).unwrap().last_hurt_at = Some(state.game_time);
+    assert!(state.teleport_to(player, far).is_err(), "in combat");
+    state.players.get_mut(&player).unwrap().last_hurt_at = None;
+
+    state.teleport_to(player, far).unwrap();
+    let mut events = state.subscribe_events();
+    let ticks = (state.config.teleport_channel_time / DT).ceil() as usize + 1;
+    for _ in 0..ticks {
+        game_loop::step(&mut state, DT);
+    }
+    let p = &state.players[&player];
+    assert_eq!(p.position, far_position);
+    assert!(p.teleport.is_none());
+    let mut arrived = false;
+    while let Ok(event) = events.try_recv() {
+        if let ServerMessage::Teleported {
+            player_id,
+            waypoint,
+        } = event.message
+        {
+            arrived |= player_id == player && waypoint == far;
+        }
+    }
+    assert!(arrived);
+}
+
+#[test]
+fn test_minimap_covers_the_whole_world_once_a_second() {
+    let mut state = GameState::new(GameConfig::default());
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let far = Position::new(2000.0, 0.0);
+    let dragon = Enemy::new(Uuid::new_v4(), EnemyType::Dragon, far, 10);
+    let dragon_id = dragon.id;
+    state.enemies.insert(dragon);
+    state
+        .enemies
+        .insert(Enemy::new(Uuid::new_v4(), EnemyType::Goblin, far, 10));
+
+    let mut events = state.subscribe_events();
+    let ticks = (2.0 / DT).round() as usize;
+    for _ in 0..ticks {
+        game_loop::step(&mut state, DT);
+    }
+
+    let mut updates = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let ServerMessage::MinimapUpdate {
+            players,
+            bosses,
+            landmarks,
+            ..
+        } = event.message
+        {
+            updates.push((players, bosses, landmarks));
+        }
+    }
+    assert_eq!(updates.len(), 2, "one per second");
+    let (players, bosses, landmarks) = &updates[0];
+    assert!(players.iter().any(|m| m.id == player));
+    // Only the boss, and well outside the player's view
+    assert_eq!(bosses.len(), 1);
+    assert_eq!(bosses[0].id, dragon_id);
+    assert!(bosses[0].position().distance_to(&far) < 100.0);
+    assert_eq!(landmarks.len(), state.landmarks.len());
+}
+
+/// The snapshot `player` would be sent now, as the client reads it
+fn snapshot(state: &mut GameState, player: Uuid, limit: Option<usize>) -> ServerMessage {
+    state.refresh_snapshot_cache();
+    let json = SnapshotBuffer::default().write(state, player, limit, 1);
+    serde_json::from_slice::<Envelope>(&json).unwrap().message
+}
+
+#[test]
+fn test_fixed_steps_stamp_snapshots_with_the_tick_and_server_clock() {
+    let mut state = GameState::new(GameConfig::default());
+    let player = Uuid::new_v4();
+    state.add_player(player);
+
+    let before = chrono::Utc::now().timestamp_millis();
+    for _ in 0..3 {
+        game_loop::step(&mut state, DT);
+    }
+    let after = chrono::Utc::now().timestamp_millis();
+    assert_eq!(state.tick, 3);
+    assert!((state.game_time - 3.0 * DT as f64).abs() < 1e-9);
+
+    let ServerMessage::GameState {
+        tick,
+        game_time,
+        server_time_ms,
+        ..
+    } = snapshot(&mut state, player, None)
+    else {
+        panic!("expected a snapshot");
+    };
+    assert_eq!(tick, 3);
+    assert_eq!(game_time, state.game_time);
+    assert!((before..=after).contains(&server_time_ms));
+
+    // The stamp is when the tick ran, not when the snapshot was written
+    std::thread::sleep(Duration::from_millis(5));
+    let ServerMessage::GameState {
+        server_time_ms: again,
+        ..
+    } = snapshot(&mut state, player, None)
+    else {
+        panic!("expected a snapshot");
+    };
+    assert_eq!(again, server_time_ms);
+}
+
+#[test]
+fn test_snapshots_leave_out_entities_beyond_the_clients_viewport() {
+    let mut state = GameState::new(GameConfig::default());
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let near = Enemy::new(
+        Uuid::new_v4(),
+        EnemyType::Goblin,
+        Position::new(250.0, 0.0),
+        1,
+    );
+    let near_id = near.id;
+    state.enemies.insert(near);
+    let far = Enemy::new(
+        Uuid::new_v4(),
+        EnemyType::Goblin,
+        Position::new(900.0, 0.0),
+        4,
+    );
+    state.enemies.insert(far);
+
+    let enemies_seen = |state: &mut GameState| match snapshot(state, player, None) {
+        ServerMessage::GameState { enemies, .. } => enemies.len(),
+        _ => unreachable!(),
+    };
+    assert_eq!(enemies_seen(&mut state), 2, "no viewport reported yet");
+
+    // A phone-sized report, then one too small to be honest
+    let msg: ClientMessage = serde_json::from_str(r#"{"type":"Viewport","radius":400}"#).unwrap();
+    assert!(matches!(msg, ClientMessage::SetViewport { radius } if radius == 400.0));
+    state.set_view_radius(player, 400.0);
+    match snapshot(&mut state, player, None) {
+        ServerMessage::GameState { enemies, .. } => {
+            assert_eq!(enemies.len(), 1);
+            assert_eq!(enemies[0].id, near_id);
+        }
+        _ => unreachable!(),
+    }
+    state.set_view_radius(player, 1.0);
+    assert_eq!(
+        state.players[&player].view_radius,
+        state.config.min_view_radius
+    );
+    assert_eq!(enemies_seen(&mut state), 1);
+}
+
+#[test]
+fn test_snapshots_carry_velocity_and_facing() {
+    let mut state = GameState::new(GameConfig::default());
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    game_loop::step(&mut state, DT);
+    assert_eq!(state.players[&player].velocity, Vec2::ZERO);
+
+    state.move_player(player, Position::new(0.0, 50.0), DT);
+    game_loop::step(&mut state, DT);
+    let p = &state.players[&player];
+    let speed = p.effective_speed(state.game_time);
+    assert!((p.velocity.y - speed).abs() < 0.01, "{:?}", p.velocity);
+    assert!((p.facing - std::f32::consts::FRAC_PI_2).abs() < 0.001);
+
+    // Standing still stops the velocity but keeps the facing
+    game_loop::step(&mut state, DT);
+    let p = &state.players[&player];
+    assert_eq!(p.velocity, Vec2::ZERO);
+    assert!((p.facing - std::f32::consts::FRAC_PI_2).abs() < 0.001);
+
+    let json = serde_json::to_value(snapshot(&mut state, player, None)).unwrap();
+    assert!(json["players"][0]["velocity"].is_object());
+    assert!(json["players"][0]["facing"].is_number());
+}
+
+#[test]
+fn test_shooting_faces_the_shot_and_shots_face_their_way() {
+    let config = GameConfig {
+        enemy_spawn_rate: 0.0,
+        ..GameConfig::default()
+    };
+    let mut state = GameState::new(config);
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    // Out of the safe zone, standing still, ready to attack
+    let spot = Position::new(600.0, 0.0);
+    let p = state.players.get_mut(&player).unwrap();
+    p.position = spot;
+    p.last_attack_time = -10.0;
+    game_loop::step(&mut state, DT);
+    assert_eq!(state.players[&player].facing, 0.0);
+
+    let behind = Position::new(spot.x - 200.0, spot.y);
+    let enemy = Enemy::new(Uuid::new_v4(), EnemyType::Goblin, behind, 1);
+    state.enemies.insert(enemy);
+    game_loop::step(&mut state, DT);
+    let half_turn = std::f32::consts::PI;
+    assert!((state.players[&player].facing - half_turn).abs() < 0.001);
+    let shot = state.projectiles.values().next().expect("fired");
+    assert!((shot.facing - half_turn).abs() < 0.001);
+
+    let json = serde_json::to_value(snapshot(&mut state, player, None)).unwrap();
+    assert!(json["projectiles"][0]["facing"].is_number());
+}
+
+#[test]
+fn test_snapshots_show_other_players_only_in_outline() {
+    let mut state = GameState::new(GameConfig::default());
+    let (me, rival) = (Uuid::new_v4(), Uuid::new_v4());
+    state.add_player(me);
+    state.add_player(rival);
+    let r = state.players.get_mut(&rival).unwrap();
+    r.health = r.max_health / 4.0;
+    r.upgrades.damage_level = 3;
+    r.gold = 500;
+
+    let json = serde_json::to_value(snapshot(&mut state, me, None)).unwrap();
+    assert_eq!(json["own_player"]["id"], me.to_string());
+    assert!(json["own_player"]["upgrades"].is_object());
+    let players = json["players"].as_array().unwrap();
+    assert_eq!(players.len(), 2);
+    let seen = players
+        .iter()
+        .find(|p| p["id"] == rival.to_string())
+        .unwrap();
+    assert_eq!(seen["health_ratio"], 0.25);
+    for private in ["upgrades", "current_xp", "gold", "equipment", "inventory"] {
+        assert!(seen.get(private).is_none(), "{private} leaked");
+    }
+
+    // Nothing but the outlines for someone not in the game
+    match snapshot(&mut state, Uuid::new_v4(), None) {
+        ServerMessage::GameState { own_player, .. } => assert!(own_player.is_none()),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_snapshots_reserialize_only_what_changed_and_reuse_their_buffer() {
+    let mut state = GameState::new(GameConfig::default());
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    state.players.get_mut(&player).unwrap().position = Position::new(0.0, 0.0);
+    let place = |x| Enemy::new(Uuid::new_v4(), EnemyType::Goblin, Position::new(x, 0.0), 1);
+    let moved = state.enemies.insert(place(100.0));
+    let removed = state.enemies.insert(place(200.0));
+    let kept_id = state.enemies.insert(place(300.0));
+    let kept_uuid = state.enemies[kept_id].id;
+    state.refresh_snapshot_cache();
+
+    // Changes between refreshes, as the loop and the admin API make them
+    state.enemies.get_mut(moved).unwrap().position = Position::new(150.0, 0.0);
+    state.enemies.remove(removed);
+    let added = state.enemies.insert(place(-100.0));
+    state.refresh_snapshot_cache();
+
+    let mut buffer = SnapshotBuffer::default();
+    let enemies = |json: &[u8]| match serde_json::from_slice::<Envelope>(json).unwrap().message {
+        ServerMessage::GameState { enemies, .. } => enemies,
+        _ => unreachable!(),
+    };
+    let first = buffer.write(&state, player, None, 1);
+    let seen = enemies(&first);
+    assert_eq!(seen.len(), 3);
+    let at = |id| {
+        seen.iter()
+            .find(|e| e.id == state.enemies[id].id)
+            .unwrap()
+            .position
+    };
+    assert_eq!(at(moved), Position::new(150.0, 0.0));
+    assert_eq!(at(added), Position::new(-100.0, 0.0));
+    assert_eq!(at(kept_id), Position::new(300.0, 0.0));
+
+    // The next snapshot reuses the buffer without touching the one sent
+    drop(first);
+    let second = buffer.write(&state, player, Some(1), 2);
+    let envelope: Envelope = serde_json::from_slice(&second).unwrap();
+    assert_eq!((envelope.seq, envelope.server_tick), (2, state.tick));
+    let nearest = enemies(&second);
+    assert_eq!(nearest.len(), 1);
+    assert_ne!(nearest[0].id, kept_uuid);
+}
+
+#[test]
+fn test_inputs_apply_one_move_per_tick_and_are_acknowledged() {
+    let mut state = GameState::new(GameConfig {
+        max_queued_inputs: 3,
+        ..GameConfig::default()
+    });
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let east = Position::new(500.0, 0.0);
+
+    // Queued, not applied, until the tick
+    state.queue_input(player, 1, InputKind::Move { target: east });
+    state.queue_input(player, 2, InputKind::Move { target: east });
+    assert_eq!(state.players[&player].position, Position::new(0.0, 0.0));
+
+    game_loop::step(&mut state, DT);
+    let p = &state.players[&player];
+    assert_eq!(p.last_processed_input, 1);
+    let after_one = p.position;
+    assert!(after_one.x > 0.0);
+
+    game_loop::step(&mut state, DT);
+    let p = &state.players[&player];
+    assert_eq!(p.last_processed_input, 2);
+    assert!((p.position.x - 2.0 * after_one.x).abs() < 0.01);
+
+    // A dash rides along with the next move; a flood keeps only the newest
+    for seq in 3..=6 {
+        state.queue_input(player, seq, InputKind::Move { target: east });
+    }
+    state.queue_input(
+        player,
+        7,
+        InputKind::Dash {
+            direction: Vec2::new(1.0, 0.0),
+        },
+    );
+    state.queue_input(player, 8, InputKind::Move { target: east });
+    game_loop::step(&mut state, DT);
+    assert_eq!(state.players[&player].last_processed_input, 6);
+    game_loop::step(&mut state, DT);
+    let p = &state.players[&player];
+    assert_eq!(p.last_processed_input, 8);
+    assert!(p.is_dashing(state.game_time));
+}
+
+#[test]
+fn test_config_files_override_defaults_and_are_validated() {
+    let config: GameConfig =
+        serde_json::from_str(r#"{ "tick_rate": 30.0, "max_players": 4 }"#).unwrap();
+    assert_eq!(config.tick_rate, 30.0);
+    assert_eq!(config.max_players, 4);
+    assert_eq!(config.map_size, GameConfig::default().map_size);
+    assert!(config.problems().is_empty());
+
+    let config: GameConfig =
+        serde_json::from_str(r#"{ "snapshot_rate": 2.0, "tls_cert": "cert.pem" }"#).unwrap();
+    assert_eq!(config.problems().len(), 2);
+
+    // Durations the server turns into `Duration`s, which panic on these
+    let config: GameConfig = serde_json::from_str(r#"{ "udp_timeout": -1.0 }"#).unwrap();
+    assert_eq!(config.problems().len(), 1);
+    let config: GameConfig =
+        serde_json::from_str(r#"{ "room_idle_timeout": 0.0, "invite_code_lifetime": -5.0 }"#)
+            .unwrap();
+    assert_eq!(config.problems().len(), 2);
+
+    // Settings a room can override, so they're bounded for strangers too
+    let config: GameConfig = serde_json::from_str(
+        r#"{ "max_rings": 4000000000, "enemy_spawn_rate": -1.0, "timed_duration": 0.0 }"#,
+    )
+    .unwrap();
+    assert_eq!(config.problems().len(), 3);
+}
+
+#[test]
+fn test_enemy_data_file_replaces_the_built_in_roster() {
+    let mut config = GameConfig::default();
+    let bundled = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("data/enemies.toml");
+    config.load_enemies(&bundled).unwrap();
+    assert_eq!(config.enemies, shared::EnemyRoster::default());
+
+    config.enemies = toml::from_str(
+        r#"
+        scaling = { xp = 1 }
+
+        [[enemies]]
+        type = "Troll"
+        max_health = 7.0
+        damage = 1.0
+        movement_speed = 1.0
+        attack_speed = 1.0
+        xp = 3
+
+        [[rings]]
+        ring = 1
+        enemies = ["Troll"]
+        "#,
+    )
+    .unwrap();
+    assert!(config.problems().is_empty());
+    let mut state = GameState::new(config);
+    state.add_player(Uuid::new_v4());
+    state.game_time = 10.0;
+    state.spawn_enemies(0.05);
+
+    assert!(!state.enemies.is_empty());
+    assert!(state
+        .enemies
+        .values()
+        .all(|e| e.enemy_type == EnemyType::Troll && e.xp_reward == 3 * e.spawn_ring));
+
+    state.config.enemies.rings[0].enemies.push(EnemyType::Lich);
+    assert_eq!(
+        state.config.problems(),
+        ["ring 1 spawns Lich, which has no definition"]
+    );
+}
+
+#[test]
+fn test_log_tail_keeps_recent_lines_for_polling() {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let tail = crate::log_tail::LogTail::default();
+    let subscriber = tracing_subscriber::registry().with(tail.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(player = 7, "Kicking player");
+        tracing::warn!("Game loop fell behind");
+    });
+
+    let lines = tail.since(None);
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].message, "Kicking player player=7");
+    assert_eq!(lines[1].level, "WARN");
+    let newer = tail.since(Some(lines[0].seq));
+    assert_eq!(newer.len(), 1);
+    assert_eq!(newer[0].message, "Game loop fell behind");
+}
+
+#[test]
+fn test_rarer_upgrades_apply_more_levels() {
+    let mut state = GameState::new(GameConfig::default());
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let choices = vec![
+        UpgradeChoice {
+            upgrade: UpgradeType::IncreaseDamage,
+            rarity: Rarity::Epic,
+        },
+        UpgradeChoice {
+            upgrade: UpgradeType::Armor,
+            rarity: Rarity::Rare,
+        },
+    ];
+    let offer = PendingLevelUp::new(choices, state.game_time);
+    state.pending_level_ups.insert(player, offer);
+
+    state
+        .apply_upgrade(player, UpgradeType::IncreaseDamage)
+        .unwrap();
+    let p = &state.players[&player];
+    assert_eq!(p.upgrades.damage_level, 3);
+    assert!((p.damage - 16.0).abs() < 0.001);
+    assert_eq!(p.upgrades.armor_level, 0);
+}
+
+#[test]
+fn test_timed_effects_tick_on_schedule_and_refresh_instead_of_stacking() {
+    let mut state = GameState::new(GameConfig::default());
+    let (healer, burning) = (Uuid::new_v4(), Uuid::new_v4());
+    for (player, x) in [(healer, 300.0), (burning, 350.0)] {
+        state.add_player(player);
+        let p = state.players.get_mut(&player).unwrap();
+        p.position = Position::new(x, 0.0);
+        p.health = 50.0;
+    }
+
+    // Regeneration comes from the upgrade and lasts
+    let choice = UpgradeChoice {
+        upgrade: UpgradeType::HealthRegeneration,
+        rarity: Rarity::Common,
+    };
+    let offer = PendingLevelUp::new(vec![choice], state.game_time);
+    state.pending_level_ups.insert(healer, offer);
+    state
+        .apply_upgrade(healer, UpgradeType::HealthRegeneration)
+        .unwrap();
+
+    let demon = Uuid::new_v4();
+    let burn = |now| TimedEffect::periodic(burning, EffectKind::Burn(4.0), 1.0, 3.0, now);
+    state.add_effect(burn(state.game_time).from(demon));
+    state.add_effect(burn(state.game_time).from(demon));
+    assert_eq!(
+        state.effects.on(burning).count(),
+        1,
+        "a second burn refreshes the first"
+    );
+
+    for _ in 0..5 {
+        state.game_time += 1.0;
+        state.update_effects();
+    }
+    assert_eq!(state.players[&healer].health, 55.0);
+    assert_eq!(
+        state.players[&burning].health, 38.0,
+        "three ticks, then out"
+    );
+    assert_eq!(state.effects.on(burning).count(), 0);
+    assert_eq!(state.effects.on(healer).count(), 1);
+
+    // A blessing's aura heals everyone near the blessed player
+    let aura = EffectKind::HealingAura {
+        radius: 100.0,
+        heal: 3.0,
+    };
+    state.add_effect(TimedEffect::periodic(
+        burning,
+        aura,
+        1.0,
+        20.0,
+        state.game_time,
+    ));
+    state.game_time += 1.0;
+    state.update_effects();
+    assert_eq!(state.players[&healer].health, 59.0);
+    assert_eq!(state.players[&burning].health, 41.0);
+
+    // Buffs apply as soon as they're added
+    let now = state.game_time;
+    state.add_effect(TimedEffect::buff(healer, EffectKind::SpeedBoost, 20.0, now));
+    assert_eq!(state.players[&healer].speed_boost_until, now + 20.0);
+}
+
+#[test]
+fn test_safe_zone_regen_and_power_ups_go_through_the_effect_scheduler() {
+    let config = GameConfig {
+        safe_zone_max_stay: 2.5,
+        ..GameConfig::default()
+    };
+    let (regen, duration) = (config.safe_zone_regen, config.power_up_duration as f64);
+    let mut state = GameState::new(config);
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    state.players.get_mut(&player).unwrap().health = 50.0;
+    let choice = UpgradeChoice {
+        upgrade: UpgradeType::HealthRegeneration,
+        rarity: Rarity::Common,
+    };
+    state.set_upgrade_offer(player, vec![choice]);
+    state
+        .apply_upgrade(player, UpgradeType::HealthRegeneration)
+        .unwrap();
+    let upgrade_regen = match state.effects.on(player).next().unwrap().kind {
+        EffectKind::Heal(amount) => amount,
+        other => panic!("{other:?}"),
+    };
+    let step = |state: &mut GameState, seconds: f32| {
+        for _ in 0..(seconds / DT).round() as usize {
+            state.game_time += DT as f64;
+            state.update_safe_zone(DT);
+            state.update_effects();
+        }
+    };
+
+    // Both heal once a second without replacing each other
+    step(&mut state, 2.1);
+    assert_eq!(state.effects.on(player).count(), 2);
+    assert_eq!(
+        state.players[&player].health,
+        50.0 + 2.0 * (regen + upgrade_regen)
+    );
+    // Overstaying stops the zone's share
+    step(&mut state, 2.0);
+    assert_eq!(
+        state.players[&player].health,
+        50.0 + 4.0 * upgrade_regen + 2.0 * regen
+    );
+
+    // A picked-up boost and a bought one refresh the same timer
+    let spot = state.players[&player].position;
+    let boost = Pickup::new(PickupType::DoubleDamage, spot, 0, 60.0);
+    state.insert_pickup(boost);
+    state.update_pickups(DT);
+    let picked_at = state.game_time;
+    assert_eq!(
+        state.players[&player].damage_boost_until,
+        picked_at + duration
+    );
+    let p = state.players.get_mut(&player).unwrap();
+    (p.gold, p.position) = (1000, Position::new(0.0, 0.0));
+    state.game_time += 1.0;
+    state.buy_item(player, ShopItem::DamageBoost).unwrap();
+    assert_eq!(
+        state.players[&player].damage_boost_until,
+        picked_at + 1.0 + duration
+    );
+}
+
+#[test]
+fn test_damage_aura_hits_every_enemy_inside_and_grows_with_radius_upgrades() {
+    let mut state = GameState::new(GameConfig::default());
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    state.players.get_mut(&player).unwrap().position = Position::new(300.0, 0.0);
+    let pick = |state: &mut GameState, upgrade| {
+        let choice = UpgradeChoice {
+            upgrade,
+            rarity: Rarity::Common,
+        };
+        let offer = PendingLevelUp::new(vec![choice], state.game_time);
+        state.pending_level_ups.insert(player, offer);
+        state.apply_upgrade(player, upgrade).unwrap();
+    };
+    pick(&mut state, UpgradeType::DamageAura);
+
+    let orc = |x| Enemy::new(Uuid::new_v4(), EnemyType::Orc, Position::new(x, 0.0), 1);
+    let near = state.enemies.insert(orc(340.0));
+    let close = state.enemies.insert(orc(250.0));
+    let far = state.enemies.insert(orc(370.0));
+    let full = state.enemies.get(near).unwrap().max_health;
+
+    state.game_time += 0.5;
+    state.update_effects();
+    let health = |state: &GameState, id| state.enemies.get(id).unwrap().health;
+    assert_eq!(health(&state, near), full - 2.5, "5 damage a second");
+    assert_eq!(health(&state, close), full - 2.5);
+    assert_eq!(health(&state, far), full, "outside the 60 unit aura");
+    let stats = &state.players[&player].stats;
+    assert_eq!(stats.damage_by_weapon[&Weapon::Aura], 5.0);
+
+    // +25% reach takes in the Orc at 70 units
+    pick(&mut state, UpgradeType::AuraRadius);
+    state.game_time += 0.5;
+    state.update_effects();
+    assert_eq!(health(&state, far), full - 2.5);
+}
+
+#[test]
+fn test_overshield_absorbs_damage_first_then_wears_off_and_recharges() {
+    let mut state = GameState::new(GameConfig::default());
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let choice = UpgradeChoice {
+        upgrade: UpgradeType::Overshield,
+        rarity: Rarity::Common,
+    };
+    let offer = PendingLevelUp::new(vec![choice], state.game_time);
+    state.pending_level_ups.insert(player, offer);
+    state
+        .apply_upgrade(player, UpgradeType::Overshield)
+        .unwrap();
+    state.update_effects();
+    assert_eq!(state.players[&player].shield, 20.0, "charged right away");
+
+    let p = state.players.get_mut(&player).unwrap();
+    p.take_damage(15.0);
+    assert_eq!((p.shield, p.health), (5.0, 100.0));
+    p.take_damage(15.0);
+    assert_eq!((p.shield, p.health), (0.0, 90.0));
+    assert_eq!(p.stats.damage_taken, 10.0, "absorbed damage isn't taken");
+    assert_eq!(p.max_shield, 0.0, "the bar goes once the shield does");
+
+    // A shrine's warding outlasts the upgrade's, then both decay
+    state.add_effect(TimedEffect::buff(
+        player,
+        EffectKind::Shield(50.0),
+        20.0,
+        state.game_time,
+    ));
+    state.update_shields(5.0);
+    assert_eq!(state.players[&player].shield, 40.0, "2 points a second");
+    assert_eq!(state.players[&player].max_shield, 50.0);
+
+    state.game_time += 10.0;
+    state.update_effects();
+    assert_eq!(
+        state.players[&player].shield, 40.0,
+        "a recharge never weakens it"
+    );
+    state.update_shields(15.0);
+    state.game_time += 10.0;
+    state.update_effects();
+    assert_eq!(state.players[&player].shield, 20.0);
+}
+
+#[test]
+fn test_second_wind_survives_one_lethal_hit_per_run() {
+    let mut state = GameState::new(GameConfig::default());
+    let mut events = state.subscribe_events();
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let take_second_wind = |state: &mut GameState| {
+        let choice = UpgradeChoice {
+            upgrade: UpgradeType::SecondWind,
+            rarity: Rarity::Common,
+        };
+        let offer = PendingLevelUp::new(vec![choice], state.game_time);
+        state.pending_level_ups.insert(player, offer);
+        state
+            .apply_upgrade(player, UpgradeType::SecondWind)
+            .unwrap();
+    };
+    take_second_wind(&mut state);
+    let spot = Position::new(600.0, 0.0);
+    state.players.get_mut(&player).unwrap().position = spot;
+
+    let shoot = |state: &mut GameState| {
+        let still = Vec2::ZERO;
+        let mut shot = Projectile::new(Uuid::new_v4(), spot, still, 0.0, 1000.0, 5.0);
+        shot.hostile = true;
+        state.projectiles.insert(shot);
+    };
+    shoot(&mut state);
+    game_loop::step(&mut state, DT);
+    let p = &state.players[&player];
+    assert_eq!(p.health, 50.0);
+    assert!(p.second_wind_used);
+    assert_eq!(p.invulnerable_until, state.game_time + 2.0);
+    let announced = std::iter::from_fn(|| events.try_recv().ok()).any(|event| {
+        matches!(event.message, ServerMessage::CombatEvents { events }
+            if events.iter().any(|e| e.kind == CombatEventKind::SecondWind && e.target == player))
+    });
+    assert!(announced);
+
+    // Shots pass harmlessly while it lasts, and taking it again doesn't
+    // bring it back
+    shoot(&mut state);
+    game_loop::step(&mut state, DT);
+    assert_eq!(state.players[&player].health, 50.0);
+    take_second_wind(&mut state);
+    state.game_time += 2.0;
+    game_loop::step(&mut state, DT);
+    assert!(!state.players[&player].is_alive());
+}
+
+#[test]
+fn test_enemies_decide_at_the_ai_rate_but_move_every_tick() {
+    let config = GameConfig {
+        enemy_spawn_rate: 0.0,
+        ..GameConfig::default()
+    };
+    let rate = config.ai_decision_rate;
+    let mut state = GameState::new(config);
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    state.players.get_mut(&player).unwrap().position = Position::new(600.0, 0.0);
+    let orc = Enemy::new(Uuid::new_v4(), EnemyType::Orc, Position::new(300.0, 0.0), 1);
+    let orc_id = state.enemies.insert(orc);
+    let tick = |state: &mut GameState| {
+        state.game_time += DT as f64;
+        state.decide_enemies(DT);
+        state.update_enemies(DT);
+    };
+
+    let (mut decisions, mut moves) = (Vec::new(), 0);
+    for _ in 0..20 {
+        let before = state.enemies[orc_id].clone();
+        tick(&mut state);
+        let orc = &state.enemies[orc_id];
+        if orc.last_decision != before.last_decision {
+            decisions.push(state.game_time);
+        }
+        if orc.position.x > before.position.x {
+            moves += 1;
+        }
+    }
+    assert_eq!(decisions.len(), rate as usize, "{decisions:?}");
+    assert_eq!(moves, 20);
+    assert_eq!(state.enemies[orc_id].target_player_id, Some(player));
+
+    // Chasing follows the target between decisions
+    tick(&mut state); // a decision is due on this one
+    state.players.get_mut(&player).unwrap().position = Position::new(0.0, 600.0);
+    let before = state.enemies[orc_id].clone();
+    tick(&mut state);
+    let orc = &state.enemies[orc_id];
+    assert_eq!(orc.last_decision, before.last_decision);
+    assert!(orc.position.y > before.position.y);
+}
+
+#[test]
+fn test_contact_damage_hurts_per_second_instead_of_per_swing() {
+    let config = GameConfig {
+        contact_damage: true,
+        ..GameConfig::default()
+    };
+    let mut state = GameState::new(config);
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    state.players.get_mut(&player).unwrap().position = Position::new(600.0, 0.0);
+    let orc = |x| {
+        let mut orc = Enemy::new(Uuid::new_v4(), EnemyType::Orc, Position::new(x, 0.0), 1);
+        orc.target_player_id = Some(player);
+        orc
+    };
+    let touching = orc(615.0);
+    let per_second = touching.damage * touching.attack_speed;
+    state.enemies.insert(touching);
+    state.enemies.insert(orc(640.0)); // in melee range, but not touching
+
+    for _ in 0..20 {
+        state.game_time += DT as f64;
+        state.process_combat();
+        state.update_contact_damage(DT);
+    }
+    let health = state.players[&player].health;
+    assert!((health - (100.0 - per_second)).abs() < 0.01, "{health}");
+
+    // Only the end gets a combat event, not every tick of contact
+    state.flush_events();
+    let mut events = state.subscribe_events();
+    state.update_contact_damage(DT);
+    state.flush_events();
+    assert!(events.try_recv().is_err());
+    state.players.get_mut(&player).unwrap().health = 0.01;
+    state.update_contact_damage(DT);
+    state.flush_events();
+    let ServerMessage::CombatEvents { events } = events.try_recv().unwrap().message else {
+        panic!("expected combat events");
+    };
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, CombatEventKind::Kill);
+}
+
+#[test]
+fn test_party_xp_goes_to_members_who_fought_nearby() {
+    let mut state = GameState::new(GameConfig::default());
+    let [killer, helper, idle, distant] = [(); 4].map(|_| Uuid::new_v4());
+    for (id, y) in [
+        (killer, 0.0),
+        (helper, 200.0),
+        (idle, -200.0),
+        (distant, 450.0),
+    ] {
+        state.add_player(id);
+        state.players.get_mut(&id).unwrap().position = Position::new(600.0, y);
+    }
+    let party = state.create_party(killer).unwrap();
+    for member in [helper, idle, distant] {
+        state.join_party(member, party).unwrap();
+    }
+    let xp = |state: &GameState, id| state.players[&id].stats.xp_collected;
+    let still = Vec2::ZERO;
+    let shoot = |state: &mut GameState, owner, at: Position, damage| {
+        let shot = Projectile::new(owner, at, still, 0.0, damage, 1.0);
+        state.projectiles.insert(shot);
+        state.update_projectiles(DT);
+    };
+    let kill = |state: &mut GameState| {
+        let spot = Position::new(600.0, 0.0);
+        let mut goblin = Enemy::new(Uuid::new_v4(), EnemyType::Goblin, spot, 1);
+        goblin.xp_reward = 100;
+        state.enemies.insert(goblin);
+        shoot(state, killer, spot, 1000.0);
+        state.dispatch_events();
+    };
+
+    // The helper and the distant member chip at an orc off to the side
+    for (i, member) in [helper, distant].into_iter().enumerate() {
+        let at = Position::new(1500.0, 100.0 * i as f32);
+        state
+            .enemies
+            .insert(Enemy::new(Uuid::new_v4(), EnemyType::Orc, at, 1));
+        shoot(&mut state, member, at, 1.0);
+    }
+    kill(&mut state);
+    assert_eq!(xp(&state, killer), 100);
+    assert_eq!(xp(&state, helper), 50, "a full share");
+    assert_eq!(xp(&state, idle), 0, "standing by doesn't count");
+    assert_eq!(xp(&state, distant), 25, "halfway to the edge of the range");
+
+    // Long after their last hit the helper is idle too
+    state.game_time += state.config().party_active_window as f64 + 1.0;
+    kill(&mut state);
+    assert_eq!(xp(&state, helper), 50);
+}
+
+#[test]
+fn test_kill_xp_is_split_by_damage_dealt_unless_the_last_hit_takes_it_all() {
+    for credit in [KillCredit::Proportional, KillCredit::LastHit] {
+        let config = GameConfig {
+            kill_credit: credit,
+            damage_variance: 0.0,
+            ..GameConfig::default()
+        };
+        let mut state = GameState::new(config);
+        let [tank, finisher] = [(); 2].map(|_| Uuid::new_v4());
+        for (id, y) in [(tank, 100.0), (finisher, -100.0)] {
+            state.add_player(id);
+            let p = state.players.get_mut(&id).unwrap();
+            p.position = Position::new(600.0, y);
+            p.crit_chance = 0.0;
+        }
+        let spot = Position::new(600.0, 0.0);
+        let mut orc = Enemy::new(Uuid::new_v4(), EnemyType::Orc, spot, 1);
+        (orc.health, orc.max_health, orc.xp_reward) = (100.0, 100.0, 40);
+        state.enemies.insert(orc);
+
+        // Three quarters from one player, then the rest and more from another
+        for (owner, damage) in [(tank, 75.0), (finisher, 500.0)] {
+            let shot = Projectile::new(owner, spot, Vec2::ZERO, 0.0, damage, 1.0);
+            state.projectiles.insert(shot);
+            state.update_projectiles(DT);
+        }
+        state.dispatch_events();
+
+        let got = |id| {
+            let p = &state.players[&id];
+            (p.stats.xp_collected, p.enemies_defeated)
+        };
+        if credit == KillCredit::Proportional {
+            assert_eq!(got(tank), (30, 1), "overkill doesn't count");
+            assert_eq!(got(finisher), (10, 0));
+        } else {
+            assert_eq!(got(tank), (0, 0));
+            assert_eq!(got(finisher), (40, 1));
+        }
+    }
+}
+
+#[test]
+fn test_explosive_shots_splash_enemies_and_rivals_but_spare_the_party() {
+    let config = GameConfig {
+        pvp_enabled: true,
+        damage_variance: 0.0,
+        ..GameConfig::default()
+    };
+    let mut state = GameState::new(config);
+    let mut events = state.subscribe_events();
+    let [shooter, ally, rival] = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+    for (id, y) in [(shooter, 300.0), (ally, -30.0), (rival, 30.0)] {
+        state.add_player(id);
+        let p = state.players.get_mut(&id).unwrap();
+        p.position = Position::new(400.0, y);
+        p.crit_chance = 0.0;
+        p.invulnerable_until = 0.0;
+    }
+    let party = state.create_party(shooter).unwrap();
+    state.join_party(ally, party).unwrap();
+
+    let orc = |x, y| Enemy::new(Uuid::new_v4(), EnemyType::Orc, Position::new(x, y), 1);
+    let struck = state.enemies.insert(orc(400.0, 0.0));
+    let near = state.enemies.insert(orc(430.0, 0.0));
+    let far = state.enemies.insert(orc(470.0, 0.0));
+    let full = state.enemies.get(near).unwrap().max_health;
+    let still = Vec2::ZERO;
+    let mut shot = Projectile::new(shooter, Position::new(400.0, 0.0), still, 0.0, 20.0, 1.0);
+    shot.explosion = 0.5;
+    state.projectiles.insert(shot);
+    state.update_projectiles(DT);
+
+    let health = |state: &GameState, id| state.enemies.get(id).unwrap().health;
+    assert_eq!(
+        health(&state, struck),
+        full - 20.0,
+        "no splash on top of the hit"
+    );
+    assert_eq!(health(&state, near), full - 10.0);
+    assert_eq!(health(&state, far), full, "outside the 50 unit blast");
+    assert_eq!(
+        state.players[&rival].health, 95.0,
+        "PvP damage scale applies"
+    );
+    assert_eq!(
+        state.players[&ally].health, 100.0,
+        "party members are spared"
+    );
+    assert_eq!(state.players[&shooter].health, 100.0);
+
+    // A shot that runs out of time goes off where it stopped
+    let mut shot = Projectile::new(
+        shooter,
+        Position::new(440.0, 40.0),
+        still,
+        0.0,
+        20.0,
+        DT / 2.0,
+    );
+    shot.explosion = 0.5;
+    state.projectiles.insert(shot);
+    state.update_projectiles(DT);
+    assert!(state.projectiles.is_empty());
+    assert_eq!(health(&state, near), full - 20.0);
+    assert_eq!(health(&state, far), full - 10.0);
+
+    state.flush_events();
+    let blasts = std::iter::from_fn(|| events.try_recv().ok())
+        .filter(|event| matches!(event.message, ServerMessage::Explosion { radius, .. } if radius == 50.0))
+        .count();
+    assert_eq!(blasts, 2);
+}
+
+#[test]
+fn test_plugins_see_joins_kills_ticks_and_spawns() {
+    use crate::plugin::GamePlugin;
+    use std::sync::Mutex;
+
+    /// Starts players with gold, toughens spawns and notes what it saw
+    struct Bounty(Arc<Mutex<Vec<String>>>);
+
+    impl GamePlugin for Bounty {
+        fn name(&self) -> &str {
+            "bounty"
+        }
+
+        fn on_player_join(&mut self, game: &mut GameState, player_id: Uuid) {
+            game.players.get_mut(&player_id).unwrap().gold = 100;
+        }
+
+        fn on_enemy_killed(&mut self, game: &mut GameState, enemy: &Enemy, killer: Uuid) {
+            game.players.get_mut(&killer).unwrap().gold += 10;
+            let kill = format!("killed {:?}", enemy.enemy_type);
+            self.0.lock().unwrap().push(kill);
+        }
+
+        fn on_tick(&mut self, game: &mut GameState, _delta_time: f32) {
+            self.0.lock().unwrap().push(format!("tick {}", game.tick));
+        }
+
+        fn modify_spawn(&mut self, _game: &GameState, enemy: &mut Enemy) {
+            enemy.max_health = 1000.0;
+            enemy.health = 1000.0;
+        }
+    }
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let mut state = GameState::new(GameConfig::default());
+    state.plugins.register(Box::new(Bounty(seen.clone())));
+    let player = Uuid::new_v4();
+    assert_eq!(state.add_player(player).gold, 100);
+
+    state.players.get_mut(&player).unwrap().position = Position::new(300.0, 0.0);
+    let mut goblin = Enemy::new(
+        Uuid::new_v4(),
+        EnemyType::Goblin,
+        Position::new(330.0, 0.0),
+        1,
+    );
+    goblin.health = 1.0;
+    let goblin = state.enemies.insert(goblin);
+    for _ in 0..(5.0 / DT) as usize {
+        game_loop::step(&mut state, DT);
+        if !state.enemies.contains(goblin) {
+            break;
+        }
+    }
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen[0], "tick 1");
+    assert!(seen.contains(&"killed Goblin".to_string()), "{seen:?}");
+    assert!(state.players[&player].gold >= 110);
+    assert!(state.enemies.values().all(|e| e.max_health == 1000.0));
+    assert_eq!(format!("{:?}", state.plugins), r#"["bounty"]"#);
+}
+
+#[test]
+fn test_json_logs_name_the_tick_and_stage_they_came_from() {
+    use crate::plugin::GamePlugin;
+    use std::sync::Mutex;
+
+    /// Logs from inside the plugin stage
+    struct Chatty;
+
+    impl GamePlugin for Chatty {
+        fn name(&self) -> &str {
+            "chatty"
+        }
+
+        fn on_tick(&mut self, _game: &mut GameState, _delta_time: f32) {
+            tracing::info!("plugin ran");
+        }
+    }
+
+    /// Collects what the subscriber writes
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .with_current_span(true)
+        .with_span_list(true)
+        .with_max_level(tracing::Level::DEBUG)
+        .with_writer(move || writer.clone())
+        .finish();
+
+    let mut state = GameState::new(GameConfig::default());
+    state.register_plugin(Box::new(Chatty));
+    state.config.slow_tick_threshold_ms = 1e-6;
+    tracing::subscriber::with_default(subscriber, || {
+        game_loop::step(&mut state, DT);
+        game_loop::step(&mut state, DT);
+    });
+
+    let output = captured.0.lock().unwrap();
+    let lines: Vec<serde_json::Value> = output
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).unwrap())
+        .collect();
+    let logged = |message: &str| -> Vec<&serde_json::Value> {
+        lines
+            .iter()
+            .filter(|line| {
+                line["fields"]["message"]
+                    .as_str()
+                    .unwrap_or("")
+                    .starts_with(message)
+            })
+            .collect()
+    };
+
+    let plugin = logged("plugin ran");
+    assert_eq!(plugin.len(), 2);
+    assert_eq!(plugin[1]["spans"][0]["name"], "tick");
+    assert_eq!(plugin[1]["spans"][0]["tick"], 2);
+    assert_eq!(plugin[1]["span"]["name"], "stage");
+    assert_eq!(plugin[1]["span"]["stage"], "update_plugins");
+
+    // Slow ticks are logged after the stages, with the breakdown as fields
+    let slow = logged("Slow tick");
+    assert_eq!(slow.len(), 2);
+    assert_eq!(slow[0]["span"]["tick"], 1);
+    assert!(slow[0]["fields"]["slowest_stage"].is_string());
+    assert!(slow[0]["fields"]["ai_ms"].is_number());
+}
+
+#[tokio::test]
+async fn test_a_panicking_game_loop_is_restarted_after_saving_a_crash_snapshot() {
+    use crate::plugin::GamePlugin;
+
+    /// Panics on the first tick it sees
+    struct Faulty;
+
+    impl GamePlugin for Faulty {
+        fn name(&self) -> &str {
+            "faulty"
+        }
+
+        fn on_tick(&mut self, _game: &mut GameState, _delta_time: f32) {
+            panic!("faulty plugin");
+        }
+    }
+
+    let dir = std::env::temp_dir().join(format!("crashes-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut state = GameState::new(GameConfig {
+        snapshot_path: dir.join("world.json").to_string_lossy().into_owned(),
+        ..GameConfig::default()
+    });
+    state.register_plugin(Box::new(Faulty));
+    let state = Arc::new(RwLock::new(state));
+    let supervisor = tokio::spawn(game_loop::supervise(state.clone()));
+
+    // The plugin is lost with the crashed step, so the restarted loop runs on
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let game = state.read().await;
+        if game.tick_stats.crashes == 1 && game.tick > 5 {
+            break;
+        }
+        assert!(Instant::now() < deadline, "loop never came back");
+    }
+    supervisor.abort();
+
+    let crash_files: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert_eq!(crash_files.len(), 1, "{crash_files:?}");
+    assert!(crash_files[0].starts_with("world.crash-"));
+    let crashed = persistence::load(&dir.join(&crash_files[0])).unwrap();
+    assert_eq!(crashed.tick, 1, "saved as the panic left it");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_game_events_are_handled_then_passed_to_subscribers_in_order() {
+    let mut state = GameState::new(GameConfig::default());
+    let mut events = state.game_events.subscribe();
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let p = state.players.get_mut(&player).unwrap();
+    p.position = Position::new(300.0, 0.0);
+    p.current_xp = p.xp_to_next_level - 1;
+
+    let mut goblin = Enemy::new(
+        Uuid::new_v4(),
+        EnemyType::Goblin,
+        Position::new(330.0, 0.0),
+        1,
+    );
+    goblin.health = 1.0;
+    let goblin = state.enemies.insert(goblin);
+    while state.enemies.contains(goblin) {
+        game_loop::step(&mut state, DT);
+    }
+    // The kill's subscribers credited it and opened the level-up offer
+    assert_eq!(state.players[&player].enemies_defeated, 1);
+    assert!(state.pending_level_ups.contains_key(&player));
+
+    state.players.get_mut(&player).unwrap().health = 0.0;
+    game_loop::step(&mut state, DT);
+
+    let seen: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+        .filter_map(|event| match event {
+            GameEvent::PlayerJoined { .. } => Some("joined".to_string()),
+            GameEvent::EnemyKilled { enemy, killer, .. } if killer == player => {
+                Some(format!("killed {:?}", enemy.enemy_type))
+            }
+            GameEvent::PlayerLevelUp { level, .. } => Some(format!("level {level}")),
+            GameEvent::PlayerDied { .. } => Some("died".to_string()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(seen, ["joined", "killed Goblin", "level 2", "died"]);
+}
+
+#[cfg(feature = "scripting")]
+#[test]
+fn test_rule_scripts_change_rules_inside_the_sandbox() {
+    use crate::plugin::GamePlugin;
+    use crate::scripting::RuleScript;
+
+    let mut config = GameConfig::default();
+    let example = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("scripts/example.wat");
+    let mut script = RuleScript::load(&example, &config).unwrap();
+    script.configure(&mut config);
+    assert_eq!(config.upgrade_catalog.upgrades[0].per_level, 0.25);
+
+    let mut state = GameState::new(config.clone());
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let mut wolf = Enemy::new(
+        Uuid::new_v4(),
+        EnemyType::Wolf,
+        Position::new(700.0, 0.0),
+        3,
+    );
+    script.modify_spawn(&state, &mut wolf);
+    assert_eq!(wolf.enemy_type, EnemyType::Troll);
+    script.on_enemy_killed(&mut state, &wolf, player);
+    assert_eq!(state.players[&player].gold, 6);
+
+    // A runaway loop runs out of fuel, and the game carries on
+    let spin = r#"(module (func (export "on_player_join") (result i32) (loop br 0) i32.const 1))"#;
+    let mut script = RuleScript::new("spin".into(), spin.as_bytes(), &config).unwrap();
+    script.on_player_join(&mut state, player);
+    assert_eq!(state.players[&player].gold, 6);
+
+    // Nothing to import, and memory is capped
+    let imports = r#"(module (import "env" "exit" (func)))"#;
+    assert!(RuleScript::new("imports".into(), imports.as_bytes(), &config).is_err());
+    let greedy = r#"(module (memory 1024))"#;
+    assert!(RuleScript::new("greedy".into(), greedy.as_bytes(), &config).is_err());
+}
+
+#[cfg(feature = "lua")]
+#[test]
+fn test_boss_scripts_drive_phases_dialogue_and_attacks() {
+    use crate::boss_script::BossDirector;
+
+    let scripts = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("scripts");
+    let mut state = GameState::new(GameConfig::default());
+    state.plugins.register(Box::new(BossDirector::new(scripts)));
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let dragon = Enemy::new(
+        Uuid::new_v4(),
+        EnemyType::Dragon,
+        Position::new(150.0, 0.0),
+        0,
+    );
+    let dragon_id = dragon.id;
+    let slot = state.enemies.insert(dragon);
+    let mut events = state.subscribe_events();
+
+    game_loop::step(&mut state, DT);
+    let dragon = state.enemies.get_mut(slot).unwrap();
+    dragon.health = dragon.max_health * 0.5;
+    game_loop::step(&mut state, DT);
+
+    let lines: Vec<String> = std::iter::from_fn(|| events.try_recv().ok())
+        .filter_map(|event| match event.message {
+            ServerMessage::BossDialogue { enemy_id, text, .. } if enemy_id == dragon_id => {
+                Some(text)
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(lines, ["Who dares wake me?", "Burn!"]);
+    let volley = state
+        .projectiles
+        .values()
+        .filter(|p| p.hostile && p.owner_id == dragon_id)
+        .count();
+    assert!(volley >= 16, "the airborne phase opens with a ring of fire");
+
+    // A runaway script is cut off, and the game carries on
+    let dir = std::env::temp_dir().join(format!("bosses-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let spin = "return { on_tick = function() while true do end end }";
+    std::fs::write(dir.join("lich.lua"), spin).unwrap();
+    state.plugins.register(Box::new(BossDirector::new(&dir)));
+    let lich = Position::new(0.0, 150.0);
+    let lich = state
+        .enemies
+        .insert(Enemy::new(Uuid::new_v4(), EnemyType::Lich, lich, 0));
+    game_loop::step(&mut state, DT);
+    assert!(state.enemies.get(lich).is_some());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_implausible_runs_are_held_for_review_instead_of_scored() {
+    let mut state = GameState::new(GameConfig::default());
+    let (honest, flooder) = (Uuid::new_v4(), Uuid::new_v4());
+    state.add_player(honest);
+    state.add_player(flooder);
+    for id in [honest, flooder] {
+        let player = state.players.get_mut(&id).unwrap();
+        player.max_ring_reached = state.config.score_min_ring;
+        player.enemies_defeated = 10;
+    }
+
+    // Twenty moves a second is what the client sends; a hundred is not
+    let target = Position::new(300.0, 0.0);
+    for seq in 0..20 {
+        state.queue_input(honest, seq, InputKind::Move { target });
+    }
+    for seq in 0..100 {
+        state.queue_input(flooder, seq, InputKind::Move { target });
+    }
+    state.players.get_mut(&flooder).unwrap().stats.xp_collected = 1_000_000;
+    state.remove_player(honest);
+    state.remove_player(flooder);
+
+    assert_eq!(state.scores.len(), 1);
+    assert_eq!(state.scores[0].player_id, honest);
+    assert_eq!(state.held_scores.len(), 1);
+    let held = state.held_scores[0].clone();
+    assert_eq!(held.score.player_id, flooder);
+    assert_eq!(held.records[0].peak_inputs_per_second, 100);
+    assert_eq!(held.records[0].flags.len(), 2);
+
+    // Each run's record also goes into its match history entry
+    let records = state.match_history.summaries();
+    assert_eq!(records.len(), 2);
+    for summary in records {
+        let record = state.match_history.get(summary.id).unwrap();
+        let integrity = record.participants[0].integrity.as_ref().unwrap();
+        let expected = integrity.player_id == flooder;
+        assert_eq!(integrity.is_suspicious(), expected);
+    }
+
+    assert!(state.review_score(Uuid::new_v4(), true).is_none());
+    assert!(state.review_score(held.id, true).is_some());
+    assert!(state.held_scores.is_empty());
+    assert_eq!(state.scores.len(), 2);
+}
+
+#[test]
+fn test_battle_royale_closes_rings_on_a_timer_until_one_player_stands() {
+    let config = GameConfig {
+        mode: GameMode::BattleRoyale,
+        zone_shrink_interval: 10.0,
+        max_rings: 3,
+        ..GameConfig::default()
+    };
+    let damage = config.zone_damage;
+    let mut state = GameState::new(config);
+    let mut events = state.subscribe_events();
+    let (inner, outer) = (Uuid::new_v4(), Uuid::new_v4());
+    for (id, x) in [(inner, 150.0), (outer, 450.0)] {
+        state.add_player(id);
+        state.players.get_mut(&id).unwrap().position = Position::new(x, 0.0);
+    }
+    let health = |state: &GameState, id| state.players[&id].health;
+    let full = health(&state, inner);
+    assert!(state.is_safe(&Position::new(50.0, 0.0)));
+
+    // The first ring closes over the inner player and takes the safe zone with it
+    state.game_time = 10.0;
+    state.update_zone(1.0);
+    assert_eq!(state.closed_rings, 1);
+    assert_eq!(health(&state, inner), full - damage);
+    assert_eq!(health(&state, outer), full);
+    assert!(!state.is_safe(&Position::new(50.0, 0.0)));
+
+    state.game_time = 20.0;
+    state.update_zone(1.0);
+    assert_eq!(health(&state, outer), full - damage);
+    // The outermost ring never closes
+    state.game_time = 100.0;
+    state.update_zone(0.0);
+    assert_eq!(state.closed_rings, 2);
+    state.flush_events();
+    let closed: Vec<f32> = std::iter::from_fn(|| events.try_recv().ok())
+        .filter(|event| event.recipient.is_none())
+        .filter_map(|event| match event.message {
+            ServerMessage::ZoneUpdate { closed_radius, .. } => Some(closed_radius),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(closed, [300.0, 500.0]);
+
+    state.players.get_mut(&inner).unwrap().health = 0.0;
+    state.update_match();
+    let record = &state.match_history.summaries()[0];
+    assert_eq!(record.outcome, "Last player standing");
+    assert_eq!(state.closed_rings, 0, "the next match starts open");
+}
+
+#[test]
+fn test_wave_defense_sends_growing_waves_until_the_core_falls() {
+    let config = GameConfig {
+        mode: GameMode::WaveDefense,
+        ..GameConfig::default()
+    };
+    let (interval, first, radius) = (
+        config.wave_interval as f64,
+        config.wave_base_size as usize,
+        config.wave_spawn_radius,
+    );
+    let mut state = GameState::new(config);
+    let player = Uuid::new_v4();
+    state.add_player(player);
+
+    state.spawn_enemies(DT);
+    assert_eq!(state.enemies.len(), first);
+    assert!(state
+        .enemies
+        .values()
+        .all(|e| (e.position.distance_from_center() - radius).abs() < 0.1));
+    state.spawn_enemies(DT);
+    assert_eq!(state.enemies.len(), first, "nothing until the next wave");
+    state.game_time += interval;
+    state.spawn_enemies(DT);
+    assert_eq!(state.enemies.len(), first * 2 + 3);
+
+    state.core_health = 0.0;
+    state.update_match();
+    let match_id = state.match_history.summaries()[0].id;
+    let record = state.match_history.get(match_id).unwrap();
+    assert_eq!(record.outcome, "The core has fallen");
+    assert!(record.winners.is_empty());
+    assert_eq!(state.core_health, state.config.core_health);
+    assert_eq!(state.wave_number, 0);
+}
+
+#[test]
+fn test_pvp_shots_hurt_rivals_outside_the_safe_zone_and_kills_are_scored() {
+    let config = GameConfig {
+        pvp_enabled: true,
+        damage_variance: 0.0,
+        base_crit_chance: 0.0,
+        ..GameConfig::default()
+    };
+    let scale = config.pvp_damage_scale;
+    let mut state = GameState::new(config);
+    let [shooter, rival, camper] = [(); 3].map(|_| Uuid::new_v4());
+    for (id, at) in [(shooter, 600.0), (rival, -600.0), (camper, 50.0)] {
+        state.add_player(id);
+        state.players.get_mut(&id).unwrap().position = Position::new(at, 0.0);
+    }
+    let shoot = |state: &mut GameState, at: Position| {
+        let shot = Projectile::new(shooter, at, Vec2::ZERO, 0.0, 40.0, 1.0);
+        state.projectiles.insert(shot);
+        state.update_projectiles(DT);
+    };
+    let health = |state: &GameState, id| state.players[&id].health;
+    let full = health(&state, rival);
+
+    shoot(&mut state, Position::new(-600.0, 0.0));
+    assert_eq!(health(&state, rival), full - 40.0 * scale);
+    shoot(&mut state, Position::new(50.0, 0.0));
+    assert_eq!(health(&state, camper), full, "the safe zone protects");
+
+    state.players.get_mut(&rival).unwrap().health = 1.0;
+    shoot(&mut state, Position::new(-600.0, 0.0));
+    assert!(!state.players[&rival].is_alive());
+    assert_eq!(state.players[&shooter].players_defeated, 1);
+
+    state.players.get_mut(&shooter).unwrap().max_ring_reached = state.config.score_min_ring;
+    state.remove_player(shooter);
+    assert_eq!(state.scores[0].player_id, shooter);
+    assert_eq!(state.scores[0].players_defeated, 1);
+}
+
+#[test]
+fn test_a_wiped_party_scores_once_with_its_combined_kills() {
+    let mut state = GameState::new(GameConfig::default());
+    let [leader, member] = [(); 2].map(|_| Uuid::new_v4());
+    for (id, kills) in [(leader, 3), (member, 4)] {
+        state.add_player(id);
+        let p = state.players.get_mut(&id).unwrap();
+        p.enemies_defeated = kills;
+        p.max_ring_reached = state.config.score_min_ring;
+    }
+    let party = state.create_party(leader).unwrap();
+    state.join_party(member, party).unwrap();
+
+    state.players.get_mut(&leader).unwrap().health = 0.0;
+    state.update_parties();
+    assert!(state.scores.is_empty(), "one member still stands");
+    state.players.get_mut(&member).unwrap().health = 0.0;
+    state.update_parties();
+    state.update_parties();
+
+    assert_eq!(state.scores.len(), 1);
+    let entry = &state.scores[0];
+    assert_eq!(entry.player_id, party);
+    assert_eq!(entry.enemies_defeated, 7);
+    assert_eq!(entry.party_members, vec![leader, member]);
+}
+
+#[test]
+fn test_kills_drop_pickups_that_players_collect_by_walking_over_them() {
+    let config = GameConfig {
+        pickup_drop_chance: 1.0,
+        equipment_drop_chance: 0.0,
+        ..GameConfig::default()
+    };
+    let (lifetime, boost) = (config.pickup_lifetime, config.power_up_duration as f64);
+    let mut state = GameState::new(config);
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let spot = Position::new(600.0, 0.0);
+    state.players.get_mut(&player).unwrap().position = spot;
+
+    let far = Position::new(900.0, 0.0);
+    let mut goblin = Enemy::new(Uuid::new_v4(), EnemyType::Goblin, far, 1);
+    goblin.health = 1.0;
+    state.enemies.insert(goblin);
+    let shot = Projectile::new(player, far, Vec2::ZERO, 0.0, 100.0, 1.0);
+    state.projectiles.insert(shot);
+    state.update_projectiles(DT);
+    state.dispatch_events();
+    assert_eq!(state.pickups.len(), 1);
+    assert_eq!(state.pickups.values().next().unwrap().position, far);
+
+    state.players.get_mut(&player).unwrap().health = 50.0;
+    for (pickup_type, amount) in [
+        (PickupType::HealthPotion, 25),
+        (PickupType::DoubleDamage, 0),
+        (PickupType::Gold, 15),
+    ] {
+        state.insert_pickup(Pickup::new(pickup_type, spot, amount, lifetime));
+    }
+    state.update_pickups(DT);
+    let p = &state.players[&player];
+    assert_eq!(p.health, 75.0);
+    assert_eq!(p.gold, 15);
+    assert_eq!(p.damage_boost_until, state.game_time + boost);
+    assert_eq!(state.pickups.len(), 1, "the drop out of reach stays");
+    state.update_pickups(lifetime);
+    assert!(state.pickups.is_empty(), "until it ages out");
+}
+
+#[test]
+fn test_landmarks_follow_the_seed_and_are_looted_once_or_on_cooldown() {
+    let config = GameConfig {
+        seed: Some(42),
+        chest_rare_chance: 0.0,
+        ..GameConfig::default()
+    };
+    let cooldown = config.shrine_cooldown as f64;
+    let mut state = GameState::new(config.clone());
+    let mut events = state.subscribe_events();
+    let layout = |state: &GameState| {
+        let mut layout: Vec<_> = state
+            .landmarks
+            .values()
+            .map(|l| {
+                (
+                    format!("{:?}", l.landmark_type),
+                    l.ring,
+                    l.position.x.to_bits(),
+                )
+            })
+            .collect();
+        layout.sort();
+        layout
+    };
+    assert_eq!(layout(&state), layout(&GameState::new(config)));
+    assert_eq!(
+        state.landmarks.len(),
+        1 + 4 * state.config.max_rings as usize
+    );
+
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let find = |state: &GameState, kind| {
+        let landmark = state
+            .landmarks
+            .values()
+            .find(|l| l.landmark_type == kind && l.ring == 1)
+            .unwrap();
+        (landmark.id, landmark.position)
+    };
+    let visit = |state: &mut GameState, kind| {
+        let (id, position) = find(state, kind);
+        state.players.get_mut(&player).unwrap().position = position;
+        id
+    };
+
+    let shrine = find(&state, LandmarkType::Shrine).0;
+    assert_eq!(state.interact(player, shrine).unwrap_err(), "Too far away");
+    let chest = visit(&mut state, LandmarkType::Chest);
+    state.interact(player, chest).unwrap();
+    assert!(state.upgrade_offer(player).is_some());
+    assert!(state.interact(player, chest).is_err(), "chests open once");
+    let obelisk = visit(&mut state, LandmarkType::Obelisk);
+    assert!(state.interact(player, obelisk).is_err());
+
+    let shrine = visit(&mut state, LandmarkType::Shrine);
+    state.interact(player, shrine).unwrap();
+    state.flush_events();
+    let blessed = std::iter::from_fn(|| events.try_recv().ok()).any(|event| {
+        matches!(event.message, ServerMessage::LandmarkUsed { reward, .. } if reward.starts_with("was blessed"))
+    });
+    assert!(blessed);
+    assert!(state.interact(player, shrine).is_err());
+    state.game_time += cooldown;
+    state.interact(player, shrine).unwrap();
+}
+
+#[test]
+fn test_skeletons_keep_their_distance_trolls_wind_up_and_wraiths_phase() {
+    use crate::enemy_ai::{self, AiContext};
+    use shared::{EnemyBehavior, Steering};
+
+    let target = Player::new(Uuid::new_v4());
+    let at = |x| Position::new(x, 0.0);
+    let decide = |enemy: &mut Enemy, game_time| {
+        let ctx = AiContext {
+            game_time,
+            wolves: &[],
+        };
+        enemy_ai::decide(enemy, &target, &ctx)
+    };
+
+    // Skeletons back off when crowded, hold their band and shoot from it
+    let mut skeleton = Enemy::new(Uuid::new_v4(), EnemyType::Skeleton, at(100.0), 1);
+    let arrow = decide(&mut skeleton, 10.0).expect("in range, off cooldown");
+    assert!(arrow.hostile);
+    assert!(matches!(skeleton.steering, Steering::Toward(p) if p.x > 100.0));
+    assert!(decide(&mut skeleton, 10.0).is_none(), "one arrow per swing");
+    skeleton.position = at(220.0);
+    decide(&mut skeleton, 10.0);
+    assert_eq!(skeleton.steering, Steering::Hold);
+    skeleton.position = at(400.0);
+    decide(&mut skeleton, 10.0);
+    assert_eq!(skeleton.steering, Steering::Chase);
+
+    // Trolls telegraph, charge, then stand winded
+    let mut troll = Enemy::new(Uuid::new_v4(), EnemyType::Troll, at(200.0), 1);
+    let mut phases = Vec::new();
+    for time in [0.0, 0.5, 1.0, 1.8, 3.0] {
+        decide(&mut troll, time);
+        phases.push((troll.behavior, troll.steering));
+    }
+    let toward = target.position - at(200.0);
+    assert_eq!(
+        phases,
+        [
+            (EnemyBehavior::WindUp, Steering::Hold),
+            (EnemyBehavior::WindUp, Steering::Hold),
+            (EnemyBehavior::Charge, Steering::Heading(toward, 3.0)),
+            (EnemyBehavior::Recover, Steering::Hold),
+            (EnemyBehavior::Chase, Steering::Chase),
+        ]
+    );
+
+    // Wraiths far from their target fade out, then reappear beside it
+    let mut wraith = Enemy::new(Uuid::new_v4(), EnemyType::Wraith, at(400.0), 1);
+    decide(&mut wraith, 5.0);
+    assert!(!wraith.is_targetable());
+    decide(&mut wraith, 6.0);
+    assert!(wraith.is_targetable());
+    assert!((wraith.position.distance_to(&target.position) - 60.0).abs() < 0.01);
+}
+
+#[test]
+fn test_threat_holds_an_enemy_on_its_target_until_someone_clearly_outdoes_them() {
+    use crate::threat;
+
+    let config = GameConfig::default();
+    let margin = config.threat_switch_margin;
+    let mut enemy = Enemy::new(Uuid::new_v4(), EnemyType::Orc, Position::new(0.0, 0.0), 1);
+    let [mut tank, mut striker, mut bystander] = [(); 3].map(|_| Player::new(Uuid::new_v4()));
+    tank.position = Position::new(300.0, 0.0);
+    striker.position = Position::new(100.0, 0.0);
+    bystander.position = Position::new(2000.0, 0.0);
+    let players = [tank.clone(), striker.clone(), bystander.clone()];
+    let target = |enemy: &Enemy| threat::select_target(enemy, &players, &config).unwrap().id;
+
+    assert_eq!(target(&enemy), striker.id, "nearest with nothing to go on");
+    threat::add_damage_threat(&mut enemy, tank.id, 50.0, &config);
+    enemy.target_player_id = Some(target(&enemy));
+    assert_eq!(enemy.target_player_id, Some(tank.id));
+    threat::add_damage_threat(&mut enemy, striker.id, 50.0 * margin - 1.0, &config);
+    assert_eq!(target(&enemy), tank.id, "not enough to pull it away");
+    threat::add_damage_threat(&mut enemy, striker.id, 2.0, &config);
+    assert_eq!(target(&enemy), striker.id);
+
+    // Far away threat bleeds off until the player drops off the table
+    threat::add_damage_threat(&mut enemy, bystander.id, 5.0, &config);
+    threat::update_threat(&mut enemy, &players, &config, 1.0);
+    assert!(!enemy.threat.contains_key(&bystander.id));
+    assert!(enemy.threat.contains_key(&tank.id));
+}
+
+#[test]
+fn test_a_ticks_hits_go_out_as_one_batch_of_combat_events() {
+    let config = GameConfig {
+        damage_variance: 0.0,
+        base_crit_chance: 0.0,
+        ..GameConfig::default()
+    };
+    let mut state = GameState::new(config);
+    let mut events = state.subscribe_events();
+    let (shooter, camper) = (Uuid::new_v4(), Uuid::new_v4());
+    for (id, x) in [(shooter, 600.0), (camper, 0.0)] {
+        state.add_player(id);
+        state.players.get_mut(&id).unwrap().position = Position::new(x, 0.0);
+    }
+    let (tough, weak) = (Position::new(700.0, 0.0), Position::new(800.0, 0.0));
+    for (at, health) in [(tough, 100.0), (weak, 5.0)] {
+        let mut orc = Enemy::new(Uuid::new_v4(), EnemyType::Orc, at, 1);
+        orc.health = health;
+        state.enemies.insert(orc);
+        let shot = Projectile::new(shooter, at, Vec2::ZERO, 0.0, 10.0, 1.0);
+        state.projectiles.insert(shot);
+    }
+    let at_camper = Position::new(0.0, 0.0);
+    let mut arrow = Projectile::new(Uuid::new_v4(), at_camper, Vec2::ZERO, 0.0, 10.0, 1.0);
+    arrow.hostile = true;
+    state.projectiles.insert(arrow);
+    state.flush_events();
+    while events.try_recv().is_ok() {}
+
+    state.update_projectiles(DT);
+    state.flush_events();
+    let batches: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+        .filter_map(|event| match event.message {
+            ServerMessage::CombatEvents { events } => Some(events),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(batches.len(), 1);
+    let mut kinds: Vec<_> = batches[0]
+        .iter()
+        .map(|e| (format!("{:?}", e.kind), e.amount))
+        .collect();
+    kinds.sort_by(|a, b| a.0.cmp(&b.0));
+    let expected = [("Blocked", 0.0), ("Hit", 10.0), ("Kill", 10.0)];
+    assert_eq!(
+        kinds,
+        expected.map(|(kind, amount)| (kind.to_string(), amount))
+    );
+}
+
+#[test]
+fn test_crits_multiply_damage_and_variance_stays_within_its_band() {
+    let config = GameConfig {
+        damage_variance: 0.2,
+        base_crit_chance: 0.0,
+        ..GameConfig::default()
+    };
+    let mut state = GameState::new(config);
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let dealt = |state: &mut GameState| {
+        let at = Position::new(600.0, 0.0);
+        let mut orc = Enemy::new(Uuid::new_v4(), EnemyType::Orc, at, 1);
+        orc.health = 1000.0;
+        let orc = state.enemies.insert(orc);
+        let shot = Projectile::new(player, at, Vec2::ZERO, 0.0, 10.0, 1.0);
+        state.projectiles.insert(shot);
+        state.update_projectiles(DT);
+        state.tick += 1;
+        1000.0 - state.enemies.remove(orc).unwrap().health
+    };
+
+    let rolls: Vec<f32> = (0..50).map(|_| dealt(&mut state)).collect();
+    assert!(rolls.iter().all(|d| (8.0..=12.0).contains(d)), "{rolls:?}");
+    assert!(rolls.iter().any(|d| *d != rolls[0]), "{rolls:?}");
+
+    state.config.damage_variance = 0.0;
+    let p = state.players.get_mut(&player).unwrap();
+    (p.crit_chance, p.crit_multiplier) = (1.0, 2.5);
+    assert_eq!(dealt(&mut state), 25.0);
+
+    // The upgrade raises the chance from the configured base
+    state.players.get_mut(&player).unwrap().crit_chance = 0.0;
+    let choice = UpgradeChoice {
+        upgrade: UpgradeType::CritChance,
+        rarity: Rarity::Common,
+    };
+    state.set_upgrade_offer(player, vec![choice]);
+    state
+        .apply_upgrade(player, UpgradeType::CritChance)
+        .unwrap();
+    assert!(state.players[&player].crit_chance > 0.0);
+}
+
+#[test]
+fn test_dash_bursts_forward_with_iframes_then_waits_out_its_cooldown() {
+    let config = GameConfig::default();
+    let (distance, cooldown) = (config.dash_distance, config.dash_cooldown as f64);
+    let iframes = config.dash_iframes as f64;
+    let ticks = (config.dash_duration / DT).round() as usize;
+    let mut state = GameState::new(config);
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let start = Position::new(600.0, 0.0);
+    state.players.get_mut(&player).unwrap().position = start;
+    let arrow_at = |state: &mut GameState, at: Position| {
+        let mut arrow = Projectile::new(Uuid::new_v4(), at, Vec2::ZERO, 0.0, 30.0, 1.0);
+        arrow.hostile = true;
+        state.projectiles.insert(arrow);
+        state.update_projectiles(DT);
+    };
+    let full = state.players[&player].health;
+
+    assert!(state.dash(player, Vec2::ZERO).is_err());
+    state.dash(player, Vec2::new(0.0, 2.0)).unwrap();
+    arrow_at(&mut state, start);
+    assert_eq!(state.players[&player].health, full, "i-frames");
+    for _ in 0..ticks {
+        state.update_dashes(DT);
+        state.game_time += DT as f64;
+    }
+    let end = state.players[&player].position;
+    assert!((end.distance_to(&start) - distance).abs() < 0.1, "{end:?}");
+    assert!(end.y > start.y);
+    assert_eq!(
+        state.dash(player, Vec2::new(1.0, 0.0)).unwrap_err(),
+        "Dash is on cooldown"
+    );
+
+    state.projectiles.clear();
+    state.game_time = iframes;
+    arrow_at(&mut state, end);
+    assert_eq!(state.players[&player].health, full - 30.0);
+    state.game_time = cooldown;
+    state.dash(player, Vec2::new(1.0, 0.0)).unwrap();
+}
+
+#[test]
+fn test_rerolls_and_banishes_are_limited_and_banished_upgrades_stay_gone() {
+    let mut state = GameState::new(GameConfig::default());
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let (rerolls, banishes) = {
+        let p = &state.players[&player];
+        (p.rerolls_left, p.banishes_left)
+    };
+    let reroll =
+        |state: &mut GameState| state.handle_command(player, ClientMessage::RerollUpgrades);
+    let offered = |state: &GameState| -> Vec<UpgradeType> {
+        let offer = state.upgrade_offer(player).unwrap();
+        offer.choices.iter().map(|c| c.upgrade).collect()
+    };
+
+    let refused = reroll(&mut state).unwrap_err();
+    assert_eq!(refused.code, ErrorCode::InvalidUpgradeChoice);
+    let choice = UpgradeChoice {
+        upgrade: UpgradeType::Armor,
+        rarity: Rarity::Common,
+    };
+    state.set_upgrade_offer(player, vec![choice]);
+    for _ in 0..rerolls {
+        reroll(&mut state).unwrap();
+    }
+    assert_eq!(offered(&state).len(), 3, "a fresh offer");
+    assert!(reroll(&mut state).is_err());
+    assert_eq!(state.players[&player].rerolls_left, 0);
+
+    let banished = offered(&state)[0];
+    let not_offered = UpgradeType::ALL
+        .into_iter()
+        .find(|u| !offered(&state).contains(u))
+        .unwrap();
+    let banish = |upgrade| ClientMessage::BanishUpgrade { upgrade };
+    assert!(state.handle_command(player, banish(not_offered)).is_err());
+    for _ in 0..banishes {
+        let upgrade = offered(&state)[0];
+        state.handle_command(player, banish(upgrade)).unwrap();
+    }
+    assert!(!offered(&state).contains(&banished));
+    assert!(state.players[&player].banished.contains(&banished));
+    let upgrade = offered(&state)[0];
+    assert!(state.handle_command(player, banish(upgrade)).is_err());
+}
+
+#[test]
+fn test_level_ups_queue_behind_an_open_offer_and_resolve_on_timeout() {
+    let config = GameConfig::default();
+    let (resend, timeout) = (
+        config.level_up_resend_interval as f64,
+        config.level_up_timeout as f64,
+    );
+    let mut state = GameState::new(config);
+    let mut events = state.subscribe_events();
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    for level in 2..=4 {
+        let event = GameEvent::PlayerLevelUp {
+            player_id: player,
+            level,
+        };
+        state.game_events.publish(event);
+    }
+    state.dispatch_events();
+    assert_eq!(state.upgrade_offer(player).unwrap().queued, 2);
+
+    let mut sent = |state: &mut GameState| {
+        state.flush_events();
+        std::iter::from_fn(|| events.try_recv().ok())
+            .filter_map(|event| match event.message {
+                ServerMessage::LevelUp { .. } => Some("offer"),
+                ServerMessage::LevelUpExpired {
+                    auto_picked: Some(_),
+                    ..
+                } => Some("auto-picked"),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(sent(&mut state), ["offer"], "one offer at a time");
+    state.game_time += resend;
+    state.update_level_ups();
+    assert_eq!(sent(&mut state), ["offer"], "a reminder");
+    state.game_time += timeout;
+    state.update_level_ups();
+    assert_eq!(sent(&mut state), ["auto-picked", "offer"]);
+    assert_eq!(state.upgrade_offer(player).unwrap().queued, 1);
+
+    for _ in 0..2 {
+        let upgrade = state.upgrade_offer(player).unwrap().choices[0].upgrade;
+        state.apply_upgrade(player, upgrade).unwrap();
+    }
+    assert!(state.upgrade_offer(player).is_none());
+}
+
+#[test]
+fn test_difficulty_climbs_with_match_time_and_toughens_spawns() {
+    let config = GameConfig {
+        max_difficulty_tier: 3,
+        ..GameConfig::default()
+    };
+    let (interval, growth) = (
+        config.difficulty_tier_interval as f64,
+        config.difficulty_stat_growth,
+    );
+    let mut state = GameState::new(config);
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    state.players.get_mut(&player).unwrap().position = Position::new(700.0, 0.0);
+
+    assert_eq!(state.difficulty_tier(), 0);
+    state.game_time = interval * 2.5;
+    assert_eq!(state.difficulty_tier(), 2);
+    assert_eq!(state.difficulty_multiplier(), 1.0 + 2.0 * growth);
+    state.game_time = interval * 100.0;
+    assert_eq!(state.difficulty_tier(), 3, "capped");
+
+    state.spawn_enemies(DT);
+    assert!(!state.enemies.is_empty());
+    let multiplier = 1.0 + 3.0 * growth;
+    for enemy in state.enemies.values() {
+        let roster = &state.config.enemies;
+        let base = roster.spawn(enemy.id, enemy.enemy_type, enemy.position, enemy.spawn_ring);
+        assert!((enemy.max_health - base.max_health * multiplier).abs() < 0.01);
+        assert!((enemy.damage - base.damage * multiplier).abs() < 0.01);
+    }
+}
+
+#[test]
+fn test_spawns_near_newcomers_stay_low_level_and_far_chasers_are_leashed_home() {
+    let config = GameConfig {
+        enemy_spawn_rate: 1000.0,
+        ..GameConfig::default()
+    };
+    let leash = config.leash_range;
+    let mut state = GameState::new(config);
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let at = Position::new(1300.0, 0.0);
+    state.players.get_mut(&player).unwrap().position = at;
+    let bubble = state.config.spawn_bubble_radius;
+    let spawned_rings = |state: &mut GameState, level| {
+        state.players.get_mut(&player).unwrap().level = level;
+        let mut rings = Vec::new();
+        // Spawns land anywhere in the active rings; sample those nearby
+        for _ in 0..500 {
+            state.enemies.clear();
+            state.game_time += 1.0;
+            state.spawn_enemies(DT);
+            rings.extend(
+                state
+                    .enemies
+                    .values()
+                    .filter(|e| e.position.distance_to(&at) <= bubble)
+                    .map(|e| e.spawn_ring),
+            );
+            if rings.len() >= 20 {
+                break;
+            }
+        }
+        rings.sort();
+        rings.dedup();
+        rings
+    };
+
+    // Rings 6 and 7 are active; nearby enemies' level follows the player's
+    assert_eq!(spawned_rings(&mut state, 1), [1]);
+    assert_eq!(spawned_rings(&mut state, 9), [5]);
+    let veteran = spawned_rings(&mut state, 99);
+    assert!(!veteran.is_empty() && veteran.iter().all(|ring| [6, 7].contains(ring)));
+
+    // Pulled past its leash, an enemy drops the chase and heads home
+    state.enemies.clear();
+    let home = Position::new(1300.0 + leash + 10.0, 0.0);
+    let mut orc = Enemy::new(Uuid::new_v4(), EnemyType::Orc, home, 1);
+    orc.position = Position::new(1300.0, 50.0);
+    orc.health = 1.0;
+    orc.target_player_id = Some(player);
+    let orc = state.enemies.insert(orc);
+    state.decide_enemies(DT);
+    assert_eq!(state.enemies[orc].behavior, shared::EnemyBehavior::Return);
+    assert_eq!(state.enemies[orc].target_player_id, None);
+    state.enemies.get_mut(orc).unwrap().position = Position::new(home.x - 0.5, 0.0);
+    state.update_enemies(DT);
+    let orc = &state.enemies[orc];
+    assert_eq!(orc.health, orc.max_health, "back home, healed");
+    assert_eq!(orc.behavior, shared::EnemyBehavior::Chase);
+}
+
+#[test]
+fn test_enemies_never_spawn_on_top_of_players_or_inside_their_view() {
+    let config = GameConfig {
+        enemy_spawn_rate: 1000.0,
+        ..GameConfig::default()
+    };
+    let min_distance = config.min_spawn_distance;
+    let mut state = GameState::new(config);
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let at = Position::new(700.0, 0.0);
+    state.players.get_mut(&player).unwrap().position = at;
+    let closest_spawn = |state: &mut GameState| {
+        state.enemies.clear();
+        for _ in 0..50 {
+            state.game_time += 1.0;
+            state.spawn_enemies(DT);
+        }
+        assert!(!state.enemies.is_empty());
+        state
+            .enemies
+            .values()
+            .map(|e| e.position.distance_to(&at))
+            .fold(f32::INFINITY, f32::min)
+    };
+
+    assert!(closest_spawn(&mut state) >= min_distance);
+    state.config.offscreen_spawns = true;
+    state.set_view_radius(player, 450.0);
+    assert!(closest_spawn(&mut state) >= 450.0);
+}
+
+#[test]
+fn test_crowds_spread_around_a_player_instead_of_stacking() {
+    let mut state = GameState::new(GameConfig::default());
+    let player = Uuid::new_v4();
+    state.add_player(player);
+    let spot = Position::new(600.0, 0.0);
+    state.players.get_mut(&player).unwrap().position = spot;
+    let orcs: Vec<_> = (0..6)
+        .map(|_| {
+            let orc = Enemy::new(Uuid::new_v4(), EnemyType::Orc, spot, 1);
+            state.enemies.insert(orc)
+        })
+        .collect();
+    let mut wraith = Enemy::new(Uuid::new_v4(), EnemyType::Wraith, spot, 1);
+    wraith.behavior = shared::EnemyBehavior::Phased;
+    let wraith = state.enemies.insert(wraith);
+
+    for _ in 0..50 {
+        state.update_enemies(DT);
+    }
+    let radius = EnemyType::Orc.radius();
+    let positions: Vec<Position> = orcs.iter().map(|id| state.enemies[*id].position).collect();
+    for (i, a) in positions.iter().enumerate() {
+        assert!(
+            a.distance_to(&spot) >= radius + Player::RADIUS - 0.5,
+            "{a:?}"
+        );
+        for b in &positions[i + 1..] {
+            assert!(a.distance_to(b) >= radius * 2.0 - 0.5, "{a:?} {b:?}");
+        }
+    }
+    assert_eq!(
+        state.players[&player].position, spot,
+        "players aren't pushed"
+    );
+    assert_eq!(
+        state.enemies[wraith].position, spot,
+        "phased wraiths pass through"
+    );
+}
@@ -0,0 +1,61 @@
+use shared::{cmp_distance, Enemy, Player};
+use uuid::Uuid;
+
+use crate::config::GameConfig;
+
+/// Build and decay an enemy's threat table for this tick.
+///
+/// Players close to the enemy accrue threat over time; everyone's threat
+/// bleeds off faster the further away they are. Damage threat is added
+/// separately when a hit lands (see `add_damage_threat`).
+pub fn update_threat(enemy: &mut Enemy, players: &[Player], config: &GameConfig, delta_time: f32) {
+    for player in players.iter().filter(|p| p.is_alive()) {
+        let distance = enemy.position.distance_to(&player.position);
+        let proximity = (1.0 - distance / config.threat_range).max(0.0);
+        let gain = config.threat_proximity_rate * proximity;
+        let decay = config.threat_decay_per_unit * distance;
+
+        let threat = enemy.threat.entry(player.id).or_insert(0.0);
+        *threat = (*threat + (gain - decay) * delta_time).max(0.0);
+    }
+
+    // Forget players who left, died, or fell off entirely
+    enemy
+        .threat
+        .retain(|id, threat| *threat > 0.0 && players.iter().any(|p| p.id == *id && p.is_alive()));
+}
+
+pub fn add_damage_threat(enemy: &mut Enemy, attacker: Uuid, damage: f32, config: &GameConfig) {
+    *enemy.threat.entry(attacker).or_insert(0.0) += damage * config.threat_per_damage;
+}
+
+/// Pick the enemy's target: highest threat, but the current target is kept
+/// until someone beats it by `threat_switch_margin` so aggro doesn't flicker.
+/// With an empty table the enemy falls back to the nearest player.
+pub fn select_target<'a>(
+    enemy: &Enemy,
+    players: &'a [Player],
+    config: &GameConfig,
+) -> Option<&'a Player> {
+    let threat_of = |p: &Player| enemy.threat.get(&p.id).copied().unwrap_or(0.0);
+    let alive = || players.iter().filter(|p| p.is_alive());
+
+    let top = alive().max_by(|a, b| threat_of(a).total_cmp(&threat_of(b)))?;
+    if threat_of(top) <= 0.0 {
+        return alive().min_by(|a, b| {
+            let dist_a = enemy.position.distance_to(&a.position);
+            let dist_b = enemy.position.distance_to(&b.position);
+            cmp_distance(&dist_a, &dist_b)
+        });
+    }
+
+    let current = enemy
+        .target_player_id
+        .and_then(|id| alive().find(|p| p.id == id));
+    match current {
+        Some(current) if threat_of(current) * config.threat_switch_margin >= threat_of(top) => {
+            Some(current)
+        }
+        _ => Some(top),
+    }
+}
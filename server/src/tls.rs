@@ -0,0 +1,22 @@
+//! TLS termination for the HTTP/WebSocket listener, so a small deployment
+//! can serve `wss://` without a reverse proxy in front.
+
+use anyhow::Context;
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+/// Load a PEM certificate chain and private key
+pub async fn load(cert_path: &str, key_path: &str) -> anyhow::Result<RustlsConfig> {
+    RustlsConfig::from_pem_file(cert_path, key_path)
+        .await
+        .with_context(|| format!("loading TLS certificate {} and key {}", cert_path, key_path))
+}
+
+/// Like `axum::serve`, but every connection goes through a TLS handshake
+pub async fn serve(listener: TcpListener, tls: RustlsConfig, app: Router) -> std::io::Result<()> {
+    axum_server::from_tcp_rustls(listener.into_std()?, tls)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+}
@@ -0,0 +1,69 @@
+//! Ways a client can connect. Each transport turns a connection into a
+//! [`MessageSink`] and a [`MessageSource`], and `network::run_session`
+//! plays the game over them without knowing which transport it has.
+
+use bytes::Bytes;
+use flate2::{write::DeflateEncoder, Compression};
+use shared::{ClientMessage, Envelope, ServerMessage};
+use std::future::Future;
+use std::io::Write;
+
+use crate::validation::MessageError;
+
+#[cfg(feature = "quic")]
+pub mod quic;
+pub mod udp;
+pub mod websocket;
+
+/// What a message needs from the transport
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// Must arrive, in order (welcome, events, errors)
+    Reliable,
+    /// Only the newest matters; may be dropped (state snapshots)
+    Latest,
+}
+
+/// Outgoing half of a client connection
+pub trait MessageSink: Send + 'static {
+    /// Send an already serialized envelope, returning the bytes put on the
+    /// wire, or `None` once the client is gone
+    fn send_json(
+        &mut self,
+        json: Bytes,
+        delivery: Delivery,
+    ) -> impl Future<Output = Option<usize>> + Send;
+
+    /// Serialize and send a message
+    fn send(
+        &mut self,
+        msg: &Envelope<&ServerMessage>,
+        delivery: Delivery,
+    ) -> impl Future<Output = Option<usize>> + Send {
+        let json = serde_json::to_vec(msg);
+        async move {
+            match json {
+                Ok(json) => self.send_json(json.into(), delivery).await,
+                Err(e) => {
+                    tracing::error!("Failed to serialize message: {}", e);
+                    Some(0)
+                }
+            }
+        }
+    }
+}
+
+/// Incoming half of a client connection
+pub trait MessageSource: Send + 'static {
+    /// The next message from the client, or why it couldn't be read (see
+    /// `validation::parse`); `None` once the client disconnects
+    fn recv(&mut self) -> impl Future<Output = Option<Result<ClientMessage, MessageError>>> + Send;
+}
+
+/// Raw-deflate a serialized message
+pub(crate) fn deflate(json: &[u8]) -> Vec<u8> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+    // Writing into a Vec can't fail
+    encoder.write_all(json).expect("in-memory deflate");
+    encoder.finish().expect("in-memory deflate")
+}
@@ -0,0 +1,179 @@
+//! Raw QUIC transport, for native clients on lossy links.
+//!
+//! After connecting, the client opens one bidirectional stream and sends
+//! its `Join` on it. That stream carries newline-delimited JSON both ways:
+//! client messages up, and welcomes, events and errors down. Snapshots go
+//! out as unreliable datagrams of raw-deflated JSON, so a lost packet just
+//! means waiting for the next snapshot instead of stalling everything
+//! behind a retransmit. A snapshot too large for one datagram falls back
+//! to the stream.
+
+use anyhow::Context;
+use bytes::Bytes;
+use quinn::rustls::pki_types::pem::PemObject;
+use quinn::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use quinn::{Connection, Endpoint, RecvStream, SendStream, ServerConfig};
+use shared::{ClientMessage, ErrorCode};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use super::{Delivery, MessageSink, MessageSource};
+use crate::access::{self, Handshake};
+use crate::game_state::SharedGameState;
+use crate::network;
+use crate::validation::{self, MessageError};
+
+/// ALPN protocol clients must offer
+pub const ALPN: &[u8] = b"game-json";
+
+pub type Certificate = (Vec<CertificateDer<'static>>, PrivateKeyDer<'static>);
+
+/// Load a PEM certificate chain and private key
+pub fn load_certificate(cert_path: &Path, key_path: &Path) -> anyhow::Result<Certificate> {
+    let chain = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("reading {}", cert_path.display()))?;
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .with_context(|| format!("reading {}", key_path.display()))?;
+    Ok((chain, key))
+}
+
+/// A throwaway certificate for `localhost`. Clients have to be told to
+/// trust it, so it's only good for development.
+pub fn self_signed() -> anyhow::Result<Certificate> {
+    let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+    let key = PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der());
+    Ok((vec![certified.cert.der().clone()], key.into()))
+}
+
+pub fn bind(addr: SocketAddr, (chain, key): Certificate) -> anyhow::Result<Endpoint> {
+    let mut crypto = quinn::rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(chain, key)?;
+    crypto.alpn_protocols = vec![ALPN.to_vec()];
+    let crypto = quinn::crypto::rustls::QuicServerConfig::try_from(crypto)?;
+    let endpoint = Endpoint::server(ServerConfig::with_crypto(Arc::new(crypto)), addr)?;
+    Ok(endpoint)
+}
+
+/// Accept QUIC clients until the endpoint closes
+pub async fn serve(endpoint: Endpoint, state: SharedGameState) {
+    while let Some(incoming) = endpoint.accept().await {
+        let state = state.clone();
+        tokio::spawn(async move {
+            let ip = incoming.remote_address().ip();
+            let guard = match access::admit(&*state.read().await, ip) {
+                Ok(guard) => guard,
+                Err(rejection) => {
+                    tracing::info!("Refused QUIC connection from {}: {}", ip, rejection);
+                    incoming.refuse();
+                    return;
+                }
+            };
+            let connection = match incoming.await {
+                Ok(c) => c,
+                Err(e) => {
+                    tracing::warn!("QUIC handshake failed: {}", e);
+                    return;
+                }
+            };
+            let (send, recv) = match connection.accept_bi().await {
+                Ok(streams) => streams,
+                Err(e) => {
+                    tracing::debug!("QUIC client left before opening a stream: {}", e);
+                    return;
+                }
+            };
+            let sink = QuicSink {
+                connection,
+                stream: send,
+            };
+            let source = QuicSource {
+                reader: BufReader::new(recv),
+                max_len: state.read().await.config().max_message_size,
+            };
+            // QUIC clients give a server password and account in Join
+            let handshake = Handshake::default();
+            network::run_session(sink, source, state, ip, handshake).await;
+            drop(guard);
+        });
+    }
+}
+
+pub struct QuicSink {
+    connection: Connection,
+    stream: SendStream,
+}
+
+impl QuicSink {
+    async fn send_reliable(&mut self, json: &[u8]) -> Option<usize> {
+        self.stream.write_all(json).await.ok()?;
+        self.stream.write_all(b"\n").await.ok()?;
+        Some(json.len() + 1)
+    }
+}
+
+impl MessageSink for QuicSink {
+    async fn send_json(&mut self, json: Bytes, delivery: Delivery) -> Option<usize> {
+        if delivery == Delivery::Latest {
+            let datagram = super::deflate(&json);
+            let fits = self
+                .connection
+                .max_datagram_size()
+                .is_some_and(|max| datagram.len() <= max);
+            if fits {
+                let bytes = datagram.len();
+                self.connection.send_datagram(datagram.into()).ok()?;
+                return Some(bytes);
+            }
+        }
+        self.send_reliable(&json).await
+    }
+}
+
+pub struct QuicSource {
+    reader: BufReader<RecvStream>,
+    max_len: usize, // longest line read; the rest of a longer one is skipped
+}
+
+impl QuicSource {
+    /// The next line, without its newline, or `None` at the end of the
+    /// stream (an unfinished last line is dropped)
+    async fn next_line(&mut self) -> Option<Result<String, MessageError>> {
+        let mut line = Vec::new();
+        let mut len = 0;
+        loop {
+            let buf = self.reader.fill_buf().await.ok()?;
+            if buf.is_empty() {
+                return None;
+            }
+            let newline = buf.iter().position(|&b| b == b'\n');
+            let chunk = &buf[..newline.unwrap_or(buf.len())];
+            len += chunk.len();
+            if len <= self.max_len {
+                line.extend_from_slice(chunk);
+            }
+            let used = newline.map_or(buf.len(), |i| i + 1);
+            self.reader.consume(used);
+            if newline.is_some() {
+                break;
+            }
+        }
+
+        if len > self.max_len {
+            return Some(Err(MessageError::too_large(len, self.max_len)));
+        }
+        let line = String::from_utf8(line)
+            .map_err(|_| MessageError::new(ErrorCode::Malformed, "message isn't UTF-8"));
+        Some(line)
+    }
+}
+
+impl MessageSource for QuicSource {
+    async fn recv(&mut self) -> Option<Result<ClientMessage, MessageError>> {
+        let line = self.next_line().await?;
+        Some(line.and_then(|line| validation::parse(&line)))
+    }
+}
@@ -0,0 +1,259 @@
+//! Optional UDP endpoint carrying snapshots and inputs; see `shared::udp`
+//! for the packet format.
+//!
+//! A client joins over WebSocket as usual and gets a `UdpOffer`. Once it
+//! sends a `Hello` here with the offered token, its snapshots come from
+//! this endpoint and its `Move`/`Dash` inputs are taken from here too.
+//! Everything else stays on the WebSocket. A client silent for
+//! `udp_timeout` seconds goes back to WebSocket snapshots.
+
+use shared::udp::{self, AckState, PacketHeader, PacketKind, HEADER_LEN, MAX_PACKET_LEN};
+use shared::ClientMessage;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::hash::BuildHasher;
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use uuid::Uuid;
+
+use crate::game_state::{GameState, SharedGameState};
+use crate::snapshot::SnapshotBuffer;
+use crate::validation;
+
+/// Sent packets remembered for matching acks; older ones count as lost
+const ACK_WINDOW: usize = 33;
+
+/// The token a player must present to bind a UDP address. Keyed with a
+/// per-process random key, so tokens can't be derived from the player ID.
+pub fn session_token(player_id: Uuid) -> u64 {
+    static KEY: OnceLock<RandomState> = OnceLock::new();
+    KEY.get_or_init(RandomState::new).hash_one(player_id)
+}
+
+struct Peer {
+    player_id: Uuid,
+    received: AckState,
+    next_sequence: u16,
+    in_flight: VecDeque<(u16, Instant)>, // sent, not yet acked
+    last_heard: Instant,
+    rtt: Option<Duration>, // smoothed round trip from acked snapshots
+    lost: u64,
+    snapshot: SnapshotBuffer,
+}
+
+impl Peer {
+    fn new(player_id: Uuid, now: Instant) -> Self {
+        Self {
+            player_id,
+            received: AckState::default(),
+            next_sequence: 0,
+            in_flight: VecDeque::new(),
+            last_heard: now,
+            rtt: None,
+            lost: 0,
+            snapshot: SnapshotBuffer::default(),
+        }
+    }
+
+    /// Take in a packet's header; true if it's the newest from this peer
+    fn receive(&mut self, header: &PacketHeader, now: Instant) -> bool {
+        self.last_heard = now;
+        self.in_flight.retain(|&(sequence, sent_at)| {
+            if !header.acks(sequence) {
+                return true;
+            }
+            let sample = now - sent_at;
+            self.rtt = Some(match self.rtt {
+                Some(rtt) => rtt.mul_f32(0.9) + sample.mul_f32(0.1),
+                None => sample,
+            });
+            false
+        });
+        self.received.receive(header.sequence)
+    }
+
+    fn packet(&mut self, payload: &[u8], now: Instant) -> Vec<u8> {
+        let header = PacketHeader {
+            kind: PacketKind::Snapshot,
+            sequence: self.next_sequence,
+            ack: self.received.ack,
+            ack_bits: self.received.ack_bits,
+        };
+        self.in_flight.push_back((self.next_sequence, now));
+        if self.in_flight.len() > ACK_WINDOW {
+            self.in_flight.pop_front();
+            self.lost += 1;
+        }
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        header.encode(payload)
+    }
+}
+
+/// Serve UDP clients until the socket fails
+pub async fn serve(socket: UdpSocket, state: SharedGameState) {
+    let (period, timeout) = {
+        let game = state.read().await;
+        (
+            Duration::from_secs_f32(1.0 / game.config().snapshot_rate),
+            Duration::from_secs_f32(game.config().udp_timeout),
+        )
+    };
+    let mut interval = tokio::time::interval(period);
+    let mut peers: HashMap<SocketAddr, Peer> = HashMap::new();
+    let mut buf = [0u8; MAX_PACKET_LEN];
+
+    loop {
+        tokio::select! {
+            received = socket.recv_from(&mut buf) => match received {
+                Ok((len, addr)) => receive(&buf[..len], addr, &mut peers, &state).await,
+                // e.g. ICMP port unreachable from a client that went away
+                Err(e) => tracing::debug!("UDP receive failed: {}", e),
+            },
+            _ = interval.tick() => {
+                expire(&mut peers, &state, timeout).await;
+                let packets = snapshots(&mut peers, &*state.read().await);
+                for (addr, packet) in packets {
+                    if let Err(e) = socket.send_to(&packet, addr).await {
+                        tracing::debug!("UDP send to {} failed: {}", addr, e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn receive(
+    packet: &[u8],
+    addr: SocketAddr,
+    peers: &mut HashMap<SocketAddr, Peer>,
+    state: &SharedGameState,
+) {
+    let Some((header, payload)) = PacketHeader::decode(packet) else {
+        return;
+    };
+    let now = Instant::now();
+
+    if header.kind == PacketKind::Hello {
+        let Some((player_id, token)) = udp::decode_hello(payload) else {
+            return;
+        };
+        if token != session_token(player_id) {
+            tracing::warn!("UDP hello from {} with a bad token", addr);
+            return;
+        }
+        let mut game = state.write().await;
+        if game.player(player_id).is_none() {
+            return;
+        }
+        if game.bind_udp(player_id, addr) {
+            tracing::info!("Player {} switched to UDP from {}", player_id, addr);
+            peers.retain(|_, peer| peer.player_id != player_id);
+        }
+        let peer = peers
+            .entry(addr)
+            .or_insert_with(|| Peer::new(player_id, now));
+        if peer.player_id != player_id {
+            *peer = Peer::new(player_id, now);
+        }
+        peer.receive(&header, now);
+        return;
+    }
+
+    let Some(peer) = peers.get_mut(&addr) else {
+        return;
+    };
+    let newest = peer.receive(&header, now);
+    // Inputs that arrive out of order are already stale
+    if header.kind != PacketKind::Input || !newest {
+        return;
+    }
+
+    let player_id = peer.player_id;
+    let Ok(text) = std::str::from_utf8(payload) else {
+        return;
+    };
+    let mut game = state.write().await;
+    let checked = validation::parse(text)
+        .and_then(|msg| validation::check(&msg, game.config()).map(|()| msg));
+    // There's no reply channel over UDP, so refused inputs are just dropped
+    let msg = match checked {
+        Ok(msg) => msg,
+        Err(e) => {
+            tracing::debug!("Dropped UDP input from {}: {}", player_id, e.message);
+            return;
+        }
+    };
+    match msg {
+        ClientMessage::Move { .. } | ClientMessage::Dash { .. } => {
+            // Queued for the next tick, never refused
+            let _ = game.handle_command(player_id, msg);
+        }
+        other => {
+            tracing::debug!("Ignoring {:?} over UDP; use the WebSocket", other);
+        }
+    }
+}
+
+/// Drop peers that went quiet, handing their snapshots back to WebSocket
+async fn expire(peers: &mut HashMap<SocketAddr, Peer>, state: &SharedGameState, timeout: Duration) {
+    let now = Instant::now();
+    let expired: Vec<SocketAddr> = peers
+        .iter()
+        .filter(|(_, peer)| now - peer.last_heard > timeout)
+        .map(|(addr, _)| *addr)
+        .collect();
+    if expired.is_empty() {
+        return;
+    }
+
+    let mut game = state.write().await;
+    for addr in expired {
+        let Some(peer) = peers.remove(&addr) else {
+            continue;
+        };
+        game.unbind_udp(peer.player_id, addr);
+        tracing::info!(
+            "UDP client {} for player {} timed out (rtt {:?}, {} lost)",
+            addr,
+            peer.player_id,
+            peer.rtt,
+            peer.lost
+        );
+    }
+}
+
+/// One snapshot packet per peer. Snapshots that won't fit in a packet are
+/// cut down to the nearest entities until they do.
+fn snapshots(
+    peers: &mut HashMap<SocketAddr, Peer>,
+    game: &GameState,
+) -> Vec<(SocketAddr, Vec<u8>)> {
+    let now = Instant::now();
+    peers.retain(|_, peer| game.player(peer.player_id).is_some());
+
+    let mut packets = Vec::new();
+    for (addr, peer) in peers.iter_mut() {
+        let mut limit = None;
+        let payload = loop {
+            let seq = peer.next_sequence.into();
+            let json = game.snapshot(&mut peer.snapshot, peer.player_id, limit, seq);
+            let payload = super::deflate(&json);
+            if HEADER_LEN + payload.len() <= MAX_PACKET_LEN {
+                break Some(payload);
+            }
+            limit = match limit {
+                None => Some(game.config().congested_entity_limit),
+                Some(0) => break None,
+                Some(n) => Some(n / 2),
+            };
+        };
+
+        match payload {
+            Some(payload) => packets.push((*addr, peer.packet(&payload, now))),
+            None => tracing::warn!("Snapshot for {} doesn't fit in a UDP packet", addr),
+        }
+    }
+    packets
+}
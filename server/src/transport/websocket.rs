@@ -0,0 +1,68 @@
+use axum::extract::ws::{Message, Utf8Bytes, WebSocket};
+use bytes::Bytes;
+use futures_util::stream::{SplitSink, SplitStream, StreamExt};
+use futures_util::SinkExt;
+use shared::{ClientMessage, DEFLATE_PROTOCOL};
+
+use super::{Delivery, MessageSink, MessageSource};
+use crate::validation::{self, MessageError};
+
+/// Split an upgraded socket. Clients that negotiated [`DEFLATE_PROTOCOL`]
+/// get messages of at least `compression_threshold` bytes as deflated
+/// binary frames.
+pub fn split(socket: WebSocket, compression_threshold: usize) -> (WsSink, WsSource) {
+    let deflate = socket.protocol().is_some_and(|p| p == DEFLATE_PROTOCOL);
+    let (sender, receiver) = socket.split();
+    let sink = WsSink {
+        sender,
+        compress_above: deflate.then_some(compression_threshold),
+    };
+    (sink, WsSource { receiver })
+}
+
+pub struct WsSink {
+    sender: SplitSink<WebSocket, Message>,
+    compress_above: Option<usize>,
+}
+
+impl MessageSink for WsSink {
+    // Everything goes over the one TCP stream, so delivery is always reliable
+    async fn send_json(&mut self, json: Bytes, _delivery: Delivery) -> Option<usize> {
+        let (frame, bytes) = match self.compress_above {
+            Some(threshold) if json.len() >= threshold => {
+                let deflated = super::deflate(&json);
+                let bytes = deflated.len();
+                (Message::Binary(deflated.into()), bytes)
+            }
+            _ => {
+                let bytes = json.len();
+                // serde_json only ever writes UTF-8
+                let text = Utf8Bytes::try_from(json).ok()?;
+                (Message::Text(text), bytes)
+            }
+        };
+        self.sender.send(frame).await.ok().map(|()| bytes)
+    }
+}
+
+pub struct WsSource {
+    receiver: SplitStream<WebSocket>,
+}
+
+impl MessageSource for WsSource {
+    // Messages over `max_message_size` never get here: the upgrade sets
+    // it as the socket's limit, and going over it closes the connection
+    async fn recv(&mut self) -> Option<Result<ClientMessage, MessageError>> {
+        while let Some(received) = self.receiver.next().await {
+            match received {
+                Ok(Message::Text(text)) => return Some(validation::parse(&text)),
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::debug!("WebSocket read failed: {}", e);
+                    break;
+                }
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,112 @@
+//! Checks on what clients send, before any of it reaches the game.
+//!
+//! A message has to be small (`max_message_size` bytes; see the
+//! transports for what happens to larger ones), shallow (`MAX_NESTING`
+//! levels of objects and arrays), parse as a `ClientMessage`, and carry
+//! plausible values: finite numbers, and move targets no further out than
+//! the map edge plus the widest view. Anything else gets a
+//! `ServerMessage::Error` with a code saying which check failed.
+
+use shared::{ClientMessage, ErrorCode, ServerMessage};
+
+use crate::config::GameConfig;
+
+/// Deepest nesting of objects and arrays a message may have. The deepest
+/// real message, a `Move`, has two.
+pub const MAX_NESTING: usize = 4;
+
+/// Why a client message was refused
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl MessageError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn too_large(len: usize, max: usize) -> Self {
+        let message = format!("message of {len} bytes is over the limit of {max}");
+        Self::new(ErrorCode::TooLarge, message)
+    }
+}
+
+impl From<MessageError> for ServerMessage {
+    fn from(error: MessageError) -> Self {
+        ServerMessage::Error {
+            code: error.code,
+            message: error.message,
+        }
+    }
+}
+
+/// Parse one client message, after checking how deeply it nests
+pub fn parse(text: &str) -> Result<ClientMessage, MessageError> {
+    if nesting(text) > MAX_NESTING {
+        let message = format!("message nests deeper than {MAX_NESTING} levels");
+        return Err(MessageError::new(ErrorCode::TooLarge, message));
+    }
+    serde_json::from_str(text).map_err(|e| MessageError::new(ErrorCode::Malformed, e.to_string()))
+}
+
+/// How deeply objects and arrays nest in a JSON text, without parsing it
+fn nesting(text: &str) -> usize {
+    let (mut depth, mut deepest) = (0usize, 0);
+    let (mut in_string, mut escaped) = (false, false);
+    for byte in text.bytes() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                deepest = deepest.max(depth);
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    deepest
+}
+
+/// Check the values in a parsed message. Unknown variants and malformed
+/// IDs never get this far: they fail to parse.
+pub fn check(msg: &ClientMessage, config: &GameConfig) -> Result<(), MessageError> {
+    let invalid = |message: &str| Err(MessageError::new(ErrorCode::InvalidField, message));
+    let reach = config.map_size + config.max_view_radius;
+    match msg {
+        ClientMessage::Move { target, .. } if !target.is_finite() => {
+            invalid("Move target isn't a finite position")
+        }
+        ClientMessage::Move { target, .. } if target.distance_from_center() > reach => {
+            invalid("Move target is too far off the map")
+        }
+        ClientMessage::Dash { direction, .. } if !direction.is_finite() => {
+            invalid("Dash direction isn't a finite vector")
+        }
+        ClientMessage::PingLocation { position } if !position.is_finite() => {
+            invalid("ping position isn't a finite position")
+        }
+        ClientMessage::PingLocation { position }
+            if position.distance_from_center() > config.map_size =>
+        {
+            invalid("ping position is off the map")
+        }
+        ClientMessage::SetViewport { radius } if !(radius.is_finite() && *radius > 0.0) => {
+            invalid("viewport radius isn't a positive number")
+        }
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,103 @@
+//! `GameState` driven the way sessions, transports and HTTP routes drive
+//! it: through its queries, `handle_command` and the event subscriptions,
+//! never its fields.
+
+use server::config::GameConfig;
+use server::events::GameEvent;
+use server::game_loop;
+use server::game_state::{GameState, JoinOutcome};
+use server::snapshot::SnapshotBuffer;
+use shared::{
+    ClientMessage, EmoteKind, Envelope, ErrorCode, Locale, Position, ServerMessage, UpgradeType,
+};
+use std::net::IpAddr;
+use uuid::Uuid;
+
+const DT: f32 = 0.05;
+
+fn quiet_game() -> GameState {
+    GameState::new(GameConfig {
+        enemy_spawn_rate: 0.0,
+        ..GameConfig::default()
+    })
+}
+
+#[test]
+fn joined_players_show_up_in_queries_snapshots_and_game_events() {
+    let mut game = quiet_game();
+    let mut game_events = game.subscribe_game_events();
+    let id = Uuid::new_v4();
+    let ip: IpAddr = "203.0.113.7".parse().unwrap();
+    assert!(!game.knows_player(id));
+
+    game.connect_client(id, ip, Locale::default());
+    assert!(matches!(game.request_join(id), JoinOutcome::Joined));
+    assert!(game.knows_player(id));
+    assert_eq!(game.player_ip(id), Some(ip));
+    assert_eq!(game.entity_counts().players, 1);
+    assert_eq!(game.players().map(|p| p.id).collect::<Vec<_>>(), vec![id]);
+
+    game_loop::step(&mut game, DT);
+    assert_eq!(game.tick(), 1);
+    assert!(matches!(
+        game_events.try_recv(),
+        Ok(GameEvent::PlayerJoined { player_id }) if player_id == id
+    ));
+
+    let json = game.snapshot(&mut SnapshotBuffer::default(), id, None, 7);
+    let envelope: Envelope = serde_json::from_slice(&json).unwrap();
+    assert_eq!((envelope.seq, envelope.server_tick), (7, 1));
+    let ServerMessage::GameState { own_player, .. } = envelope.message else {
+        panic!("expected a snapshot");
+    };
+    assert_eq!(own_player.map(|p| p.id), Some(id));
+}
+
+#[test]
+fn commands_queue_inputs_for_the_next_tick_and_refuse_what_they_cant_do() {
+    let mut game = quiet_game();
+    let id = Uuid::new_v4();
+    game.request_join(id);
+    let start = game.player(id).unwrap().position;
+
+    let target = Position::new(start.x + 500.0, start.y);
+    let command = ClientMessage::Move { target, seq: 1 };
+    game.handle_command(id, command).unwrap();
+    assert_eq!(game.player(id).unwrap().position, start, "waits for a tick");
+    game_loop::step(&mut game, DT);
+    assert!(game.player(id).unwrap().position.x > start.x);
+
+    let join = ClientMessage::join(Locale::default());
+    let error = game.handle_command(id, join).unwrap_err();
+    assert_eq!(error.code, ErrorCode::Refused);
+    let choose = ClientMessage::ChooseUpgrade {
+        upgrade: UpgradeType::Armor,
+    };
+    let error = game.handle_command(id, choose).unwrap_err();
+    assert_eq!(error.code, ErrorCode::InvalidUpgradeChoice);
+}
+
+#[test]
+fn messages_for_connections_arrive_once_events_are_flushed() {
+    let mut game = quiet_game();
+    let mut events = game.subscribe_events();
+    assert_eq!(game.session_count(), 1);
+    let id = Uuid::new_v4();
+    game.request_join(id);
+    game.flush_events();
+    while events.try_recv().is_ok() {}
+
+    let emote = ClientMessage::Emote {
+        kind: EmoteKind::Wave,
+    };
+    game.handle_command(id, emote).unwrap();
+    assert!(events.try_recv().is_err(), "held until the flush");
+
+    game.flush_events();
+    let sent = events.try_recv().unwrap();
+    assert_eq!(sent.recipient, Some(id));
+    assert!(matches!(
+        sent.message,
+        ServerMessage::Emoted { player_id, kind: EmoteKind::Wave } if player_id == id
+    ));
+}
@@ -0,0 +1,83 @@
+//! QUIC transport end to end: join over the stream, then read snapshots
+//! from datagrams. Run with `cargo test -p server --features quic`.
+#![cfg(feature = "quic")]
+
+use flate2::read::DeflateDecoder;
+use quinn::rustls::RootCertStore;
+use quinn::{ClientConfig, Endpoint};
+use server::config::GameConfig;
+use server::game_loop;
+use server::game_state::{GameState, SharedGameState};
+use server::transport::quic;
+use shared::{ClientMessage, Locale, ServerMessage};
+use std::io::Read;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::RwLock;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn join_over_stream_and_receive_datagram_snapshots() {
+    let state: SharedGameState = Arc::new(RwLock::new(GameState::new(GameConfig::default())));
+    tokio::spawn(game_loop::supervise(state.clone()));
+
+    let certificate = quic::self_signed().unwrap();
+    let mut roots = RootCertStore::empty();
+    roots.add(certificate.0[0].clone()).unwrap();
+    let server = quic::bind("127.0.0.1:0".parse().unwrap(), certificate).unwrap();
+    let addr = server.local_addr().unwrap();
+    tokio::spawn(quic::serve(server, state.clone()));
+
+    let mut crypto = quinn::rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    crypto.alpn_protocols = vec![quic::ALPN.to_vec()];
+    let crypto = quinn::crypto::rustls::QuicClientConfig::try_from(crypto).unwrap();
+    let mut client = Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+    client.set_default_client_config(ClientConfig::new(Arc::new(crypto)));
+
+    let connection = client.connect(addr, "localhost").unwrap().await.unwrap();
+    let (mut send, recv) = connection.open_bi().await.unwrap();
+    let mut join = serde_json::to_vec(&ClientMessage::join(Locale::En)).unwrap();
+    join.push(b'\n');
+    send.write_all(&join).await.unwrap();
+
+    // Welcome arrives on the reliable stream
+    let mut lines = BufReader::new(recv).lines();
+    let line = tokio::time::timeout(TIMEOUT, lines.next_line())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let player_id = match serde_json::from_str(&line).unwrap() {
+        ServerMessage::Welcome { player_id } => player_id,
+        other => panic!("expected Welcome, got {:?}", other),
+    };
+
+    // A near-empty world's snapshot fits in one datagram
+    let datagram = tokio::time::timeout(TIMEOUT, connection.read_datagram())
+        .await
+        .unwrap()
+        .unwrap();
+    let mut json = Vec::new();
+    DeflateDecoder::new(&datagram[..])
+        .read_to_end(&mut json)
+        .unwrap();
+    match serde_json::from_slice(&json).unwrap() {
+        ServerMessage::GameState { players, .. } => {
+            assert!(players.iter().any(|p| p.id == player_id));
+        }
+        other => panic!("expected GameState, got {:?}", other),
+    }
+
+    // Closing the connection ends the session
+    connection.close(0u32.into(), b"bye");
+    let removed = async {
+        while state.read().await.player(player_id).is_some() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    };
+    tokio::time::timeout(TIMEOUT, removed).await.unwrap();
+}
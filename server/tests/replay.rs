@@ -0,0 +1,49 @@
+//! Golden replays: every command log in `tests/replays` must play out to
+//! the state hash recorded with it. See `server::replay`.
+
+use server::replay::{self, CommandLog};
+use std::path::PathBuf;
+
+fn replay_hash(log: &CommandLog) -> String {
+    let game = log.run().expect("log plays");
+    format!("{:016x}", replay::state_hash(&game))
+}
+
+#[test]
+fn recorded_sessions_end_in_their_recorded_state() {
+    let update = std::env::var_os("UPDATE_REPLAYS").is_some();
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/replays");
+    let mut paths: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no logs in {}", dir.display());
+
+    for path in paths {
+        let mut log = CommandLog::load(&path).unwrap();
+        let hash = replay_hash(&log);
+        // Two runs have to agree before either can be checked against the
+        // record
+        assert_eq!(
+            hash,
+            replay_hash(&log),
+            "{} doesn't replay the same way twice",
+            path.display()
+        );
+
+        if update {
+            log.state_hash = Some(hash);
+            let json = serde_json::to_string_pretty(&log).unwrap();
+            std::fs::write(&path, json + "\n").unwrap();
+            continue;
+        }
+        assert_eq!(
+            log.state_hash.as_deref(),
+            Some(hash.as_str()),
+            "{} plays out differently; if the change is intended, rerun with UPDATE_REPLAYS=1",
+            path.display()
+        );
+    }
+}
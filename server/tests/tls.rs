@@ -0,0 +1,67 @@
+//! `wss://` end to end against a throwaway certificate. Run with
+//! `cargo test -p server --features tls`.
+#![cfg(feature = "tls")]
+
+use futures_util::{SinkExt, StreamExt};
+use server::config::GameConfig;
+use server::game_loop;
+use server::game_state::{GameState, SharedGameState};
+use server::{network, tls};
+use shared::{ClientMessage, Locale, ServerMessage};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::Connector;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn join_over_wss() {
+    let dir = std::env::temp_dir().join(format!("tls-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem"));
+    std::fs::write(&cert_path, certified.cert.pem()).unwrap();
+    std::fs::write(&key_path, certified.key_pair.serialize_pem()).unwrap();
+    let config = tls::load(cert_path.to_str().unwrap(), key_path.to_str().unwrap())
+        .await
+        .unwrap();
+
+    let state: SharedGameState = Arc::new(RwLock::new(GameState::new(GameConfig::default())));
+    tokio::spawn(game_loop::supervise(state.clone()));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let app = network::create_router(state.clone()).await;
+    tokio::spawn(tls::serve(listener, config, app));
+
+    // Trust only the throwaway certificate
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(certified.cert.der().clone()).unwrap();
+    let client = rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let url = format!("wss://localhost:{}/ws", port);
+    let connector = Connector::Rustls(Arc::new(client));
+    let (mut ws, _) =
+        tokio_tungstenite::connect_async_tls_with_config(url, None, false, Some(connector))
+            .await
+            .unwrap();
+
+    let join = serde_json::to_string(&ClientMessage::join(Locale::En)).unwrap();
+    ws.send(Message::Text(join.into())).await.unwrap();
+    let welcome = async {
+        while let Some(frame) = ws.next().await {
+            if let Message::Text(text) = frame.unwrap() {
+                if let ServerMessage::Welcome { player_id } = serde_json::from_str(&text).unwrap() {
+                    return player_id;
+                }
+            }
+        }
+        panic!("connection closed");
+    };
+    let player_id = tokio::time::timeout(TIMEOUT, welcome).await.unwrap();
+    assert!(state.read().await.player(player_id).is_some());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,167 @@
+//! UDP channel end to end: join over WebSocket, bind a UDP address with the
+//! offered token, then exchange snapshots and inputs over UDP.
+
+use flate2::read::DeflateDecoder;
+use futures_util::{SinkExt, StreamExt};
+use server::config::GameConfig;
+use server::game_state::{GameState, SharedGameState};
+use server::transport::udp as udp_transport;
+use server::{game_loop, network};
+use shared::udp::{self, PacketHeader, PacketKind, MAX_PACKET_LEN};
+use shared::{ClientMessage, Locale, Position, ServerMessage};
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::RwLock;
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Wait for the next snapshot packet, re-sending `hello` until one arrives
+async fn next_snapshot(socket: &UdpSocket, hello: &[u8]) -> (PacketHeader, ServerMessage) {
+    let mut buf = [0u8; MAX_PACKET_LEN];
+    let wait = async {
+        loop {
+            socket.send(hello).await.unwrap();
+            let received = tokio::time::timeout(Duration::from_millis(200), socket.recv(&mut buf));
+            let Ok(Ok(len)) = received.await else {
+                continue;
+            };
+            let Some((header, payload)) = PacketHeader::decode(&buf[..len]) else {
+                continue;
+            };
+            assert_eq!(header.kind, PacketKind::Snapshot);
+            let mut json = Vec::new();
+            DeflateDecoder::new(payload).read_to_end(&mut json).unwrap();
+            return (header, serde_json::from_slice(&json).unwrap());
+        }
+    };
+    tokio::time::timeout(TIMEOUT, wait)
+        .await
+        .expect("timed out waiting for a UDP snapshot")
+}
+
+fn own_position(msg: &ServerMessage, id: Uuid) -> Position {
+    match msg {
+        ServerMessage::GameState { own_player, .. } => {
+            own_player.as_ref().filter(|p| p.id == id).unwrap().position
+        }
+        other => panic!("expected GameState, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn snapshots_and_inputs_over_udp() {
+    let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let udp_addr = server_socket.local_addr().unwrap();
+    let config = GameConfig {
+        udp_port: Some(udp_addr.port()),
+        ..GameConfig::default()
+    };
+    let state: SharedGameState = Arc::new(RwLock::new(GameState::new(config)));
+    tokio::spawn(game_loop::supervise(state.clone()));
+    tokio::spawn(udp_transport::serve(server_socket, state.clone()));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let ws_url = format!("ws://{}/ws", listener.local_addr().unwrap());
+    let app = network::create_router(state.clone()).await;
+    tokio::spawn(async move {
+        let app = app.into_make_service_with_connect_info::<SocketAddr>();
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    // Join over WebSocket and pick up the offer
+    let (mut ws, _) = tokio_tungstenite::connect_async(ws_url).await.unwrap();
+    let join = serde_json::to_string(&ClientMessage::join(Locale::En)).unwrap();
+    ws.send(Message::Text(join.into())).await.unwrap();
+    let wait = async {
+        let (mut player_id, mut offer) = (None, None);
+        while let Some(frame) = ws.next().await {
+            let Message::Text(text) = frame.unwrap() else {
+                continue;
+            };
+            match serde_json::from_str(&text).unwrap() {
+                ServerMessage::Welcome { player_id: id } => player_id = Some(id),
+                ServerMessage::UdpOffer { port, token } => offer = Some((port, token)),
+                _ => {}
+            }
+            if let (Some(id), Some(offer)) = (player_id, &offer) {
+                return (id, offer.clone());
+            }
+        }
+        panic!("connection closed");
+    };
+    let (player_id, (port, token)) = tokio::time::timeout(TIMEOUT, wait).await.unwrap();
+    assert_eq!(port, udp_addr.port());
+    let token = u64::from_str_radix(&token, 16).unwrap();
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect(udp_addr).await.unwrap();
+    let hello = PacketHeader {
+        kind: PacketKind::Hello,
+        sequence: 0,
+        ack: 0,
+        ack_bits: 0,
+    }
+    .encode(&udp::encode_hello(player_id, token));
+
+    let (_, snapshot) = next_snapshot(&socket, &hello).await;
+    let start = own_position(&snapshot, player_id);
+    assert!(state.read().await.uses_udp(player_id));
+
+    // A move sent over UDP shows up in later UDP snapshots
+    let target = Position::new(start.x + 500.0, start.y);
+    let input = serde_json::to_vec(&ClientMessage::Move { target, seq: 1 }).unwrap();
+    let input = PacketHeader {
+        kind: PacketKind::Input,
+        sequence: 1,
+        ack: 0,
+        ack_bits: 0,
+    }
+    .encode(&input);
+    socket.send(&input).await.unwrap();
+
+    let moved = async {
+        loop {
+            let (_, snapshot) = next_snapshot(&socket, &hello).await;
+            if own_position(&snapshot, player_id).x > start.x {
+                break;
+            }
+        }
+    };
+    tokio::time::timeout(TIMEOUT, moved).await.unwrap();
+}
+
+#[tokio::test]
+async fn hello_with_wrong_token_is_ignored() {
+    let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let udp_addr = server_socket.local_addr().unwrap();
+    let config = GameConfig {
+        udp_port: Some(udp_addr.port()),
+        ..GameConfig::default()
+    };
+    let state: SharedGameState = Arc::new(RwLock::new(GameState::new(config)));
+    let player_id = Uuid::new_v4();
+    state.write().await.add_player(player_id);
+    tokio::spawn(udp_transport::serve(server_socket, state.clone()));
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect(udp_addr).await.unwrap();
+    let token = udp_transport::session_token(player_id).wrapping_add(1);
+    let hello = PacketHeader {
+        kind: PacketKind::Hello,
+        sequence: 0,
+        ack: 0,
+        ack_bits: 0,
+    }
+    .encode(&udp::encode_hello(player_id, token));
+    socket.send(&hello).await.unwrap();
+
+    let mut buf = [0u8; MAX_PACKET_LEN];
+    let received = tokio::time::timeout(Duration::from_millis(300), socket.recv(&mut buf)).await;
+    assert!(received.is_err(), "no snapshot for an unverified peer");
+    assert!(!state.read().await.uses_udp(player_id));
+}
@@ -0,0 +1,1055 @@
+//! End-to-end tests: a real router on an ephemeral port, driven by
+//! WebSocket clients speaking the JSON protocol.
+
+use flate2::read::DeflateDecoder;
+use futures_util::{SinkExt, StreamExt};
+use server::config::GameConfig;
+use server::daily;
+use server::game_loop;
+use server::game_mode::GameMode;
+use server::game_state::{GameState, SharedGameState};
+use server::network;
+use shared::{
+    ClientMessage, Enemy, EnemyType, Envelope, ErrorCode, Locale, Player, Position, Rarity,
+    ServerInfo, ServerMessage, UpgradeCatalog, UpgradeChoice, UpgradeType, ACCOUNT_HEADER,
+    DEFLATE_PROTOCOL, PASSWORD_HEADER, PROTOCOL_VERSION,
+};
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::RwLock;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use uuid::Uuid;
+
+/// Longest a test waits for any single expected message
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Boot the router and game loop in-process; returns the WebSocket URL
+async fn start_server() -> (String, SharedGameState) {
+    start_server_with(GameConfig::default()).await
+}
+
+async fn start_server_with(config: GameConfig) -> (String, SharedGameState) {
+    let base_path = config.base_path.clone();
+    let state: SharedGameState = Arc::new(RwLock::new(GameState::new(config)));
+    tokio::spawn(game_loop::supervise(state.clone()));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = network::create_router(state.clone()).await;
+    tokio::spawn(async move {
+        let app = app.into_make_service_with_connect_info::<SocketAddr>();
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    (format!("ws://{}{}/ws", addr, base_path), state)
+}
+
+/// Plain HTTP/1.1 GET against the server behind `ws_url`; the response
+/// comes back lowercased
+async fn http_get(ws_url: &str, path: &str) -> String {
+    http_request(
+        ws_url,
+        "GET",
+        path,
+        "Origin: https://play.example.com\r\n",
+        "",
+    )
+    .await
+}
+
+/// `headers` are full lines, each ending in CRLF
+async fn http_request(ws_url: &str, method: &str, path: &str, headers: &str, body: &str) -> String {
+    let addr = ws_url
+        .trim_start_matches("ws://")
+        .split('/')
+        .next()
+        .unwrap();
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        addr,
+        headers,
+        body.len(),
+        body
+    );
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response.to_ascii_lowercase()
+}
+
+struct TestClient {
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    deflated_frames: usize,
+}
+
+impl TestClient {
+    async fn connect(url: &str) -> Self {
+        let (ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        Self {
+            ws,
+            deflated_frames: 0,
+        }
+    }
+
+    /// Connect offering the compression subprotocol, which must be accepted
+    async fn connect_deflate(url: &str) -> Self {
+        let mut request = url.into_client_request().unwrap();
+        let protocol = HeaderValue::from_static(DEFLATE_PROTOCOL);
+        request
+            .headers_mut()
+            .insert("Sec-WebSocket-Protocol", protocol.clone());
+        let (ws, response) = tokio_tungstenite::connect_async(request).await.unwrap();
+        assert_eq!(
+            response.headers().get("Sec-WebSocket-Protocol"),
+            Some(&protocol)
+        );
+        Self {
+            ws,
+            deflated_frames: 0,
+        }
+    }
+
+    async fn send(&mut self, msg: &ClientMessage) {
+        let json = serde_json::to_string(msg).unwrap();
+        self.send_text(&json).await;
+    }
+
+    async fn send_text(&mut self, text: &str) {
+        self.ws.send(Message::Text(text.into())).await.unwrap();
+    }
+
+    /// Read messages until `pick` accepts one, failing the test on timeout
+    async fn expect<T>(&mut self, mut pick: impl FnMut(ServerMessage) -> Option<T>) -> T {
+        let wait = async {
+            while let Some(frame) = self.ws.next().await {
+                let msg: ServerMessage = match frame.unwrap() {
+                    Message::Text(text) => serde_json::from_str(&text).unwrap(),
+                    Message::Binary(bytes) => {
+                        self.deflated_frames += 1;
+                        let mut json = Vec::new();
+                        DeflateDecoder::new(&bytes[..])
+                            .read_to_end(&mut json)
+                            .unwrap();
+                        serde_json::from_slice(&json).unwrap()
+                    }
+                    _ => continue,
+                };
+                if let Some(found) = pick(msg) {
+                    return found;
+                }
+            }
+            panic!("connection closed");
+        };
+        tokio::time::timeout(TIMEOUT, wait)
+            .await
+            .expect("timed out waiting for message")
+    }
+
+    /// The code of the next `Error`
+    async fn error_code(&mut self) -> ErrorCode {
+        self.expect(|msg| match msg {
+            ServerMessage::Error { code, .. } => Some(code),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Join and return the assigned player id
+    async fn join(&mut self) -> Uuid {
+        self.send(&ClientMessage::join(Locale::En)).await;
+        self.expect(|msg| match msg {
+            ServerMessage::Welcome { player_id } => Some(player_id),
+            _ => None,
+        })
+        .await
+    }
+
+    /// The next snapshot's view of this player
+    async fn own_player(&mut self, id: Uuid) -> Player {
+        self.expect(|msg| match msg {
+            ServerMessage::GameState { own_player, .. } => {
+                own_player.filter(|p| p.id == id).map(|p| *p)
+            }
+            _ => None,
+        })
+        .await
+    }
+}
+
+#[tokio::test]
+async fn join_gets_welcome_and_snapshots() {
+    let (url, _) = start_server().await;
+    let mut client = TestClient::connect(&url).await;
+
+    let id = client.join().await;
+    let player = client.own_player(id).await;
+
+    assert_eq!(player.level, 1);
+    assert!(player.is_alive());
+}
+
+#[tokio::test]
+async fn joining_again_on_the_same_connection_is_refused() {
+    let (url, state) = start_server().await;
+    let mut client = TestClient::connect(&url).await;
+    client.join().await;
+
+    client.send(&ClientMessage::join(Locale::En)).await;
+    assert_eq!(client.error_code().await, ErrorCode::Refused);
+    let counts = state.read().await.entity_counts();
+    assert_eq!((counts.players, counts.queued), (1, 0));
+}
+
+#[tokio::test]
+async fn move_walks_the_player_toward_the_target() {
+    let (url, _) = start_server().await;
+    let mut client = TestClient::connect(&url).await;
+    let id = client.join().await;
+    let start = client.own_player(id).await.position;
+
+    let target = Position::new(start.x + 500.0, start.y);
+    for seq in 1..=5 {
+        client.send(&ClientMessage::Move { target, seq }).await;
+    }
+
+    let moved = client.own_player(id).await.position;
+    assert!(moved.x > start.x);
+    assert!(moved.distance_to(&target) < start.distance_to(&target));
+}
+
+#[tokio::test]
+async fn messages_arrive_in_numbered_envelopes() {
+    let (url, _) = start_server().await;
+    let mut client = TestClient::connect(&url).await;
+    client.send(&ClientMessage::join(Locale::En)).await;
+
+    let mut envelopes = Vec::new();
+    while envelopes.len() < 10 {
+        let frame = tokio::time::timeout(TIMEOUT, client.ws.next())
+            .await
+            .expect("timed out waiting for message");
+        if let Message::Text(text) = frame.unwrap().unwrap() {
+            envelopes.push(serde_json::from_str::<Envelope>(&text).unwrap());
+        }
+    }
+
+    let seqs: Vec<u64> = envelopes.iter().map(|e| e.seq).collect();
+    assert_eq!(seqs, (1..=10).collect::<Vec<_>>());
+    // Events can trail the snapshot after them, but snapshots stay in order
+    let mut snapshot_ticks = Vec::new();
+    for envelope in &envelopes {
+        if let ServerMessage::GameState { tick, .. } = envelope.message {
+            assert_eq!(envelope.server_tick, tick);
+            snapshot_ticks.push(tick);
+        }
+    }
+    assert!(!snapshot_ticks.is_empty());
+    assert!(snapshot_ticks.windows(2).all(|w| w[0] <= w[1]));
+}
+
+#[tokio::test]
+async fn choose_upgrade_applies_an_offered_choice() {
+    let (url, state) = start_server().await;
+    let mut client = TestClient::connect(&url).await;
+    let id = client.join().await;
+
+    // Nothing on offer yet
+    client
+        .send(&ClientMessage::ChooseUpgrade {
+            upgrade: UpgradeType::IncreaseDamage,
+        })
+        .await;
+    assert_eq!(client.error_code().await, ErrorCode::InvalidUpgradeChoice);
+
+    let choice = UpgradeChoice {
+        upgrade: UpgradeType::IncreaseDamage,
+        rarity: Rarity::Common,
+    };
+    state.write().await.set_upgrade_offer(id, vec![choice]);
+    client
+        .send(&ClientMessage::ChooseUpgrade {
+            upgrade: UpgradeType::IncreaseDamage,
+        })
+        .await;
+
+    let upgraded = client
+        .expect(|msg| match msg {
+            ServerMessage::GameState { own_player, .. } => {
+                own_player.filter(|p| p.id == id && p.upgrades.damage_level > 0)
+            }
+            _ => None,
+        })
+        .await;
+    assert!(upgraded.damage > Player::new(id).damage);
+    assert!(state.read().await.upgrade_offer(id).is_none());
+}
+
+#[tokio::test]
+async fn bad_messages_get_coded_errors_and_oversized_ones_close_the_connection() {
+    let (url, state) = start_server().await;
+    let mut client = TestClient::connect(&url).await;
+    let id = client.join().await;
+
+    let far = r#"{"type":"Move","target":{"x":1e9,"y":0},"seq":1}"#;
+    let nested = r#"{"type":"CreateParty","extra":[[[[[[]]]]]]}"#;
+    let cases = [
+        ("not json", ErrorCode::Malformed),
+        (r#"{"type":"Fly"}"#, ErrorCode::Malformed),
+        (far, ErrorCode::InvalidField),
+        (nested, ErrorCode::TooLarge),
+    ];
+    for (text, code) in cases {
+        client.send_text(text).await;
+        assert_eq!(client.error_code().await, code, "for {text}");
+    }
+    // The far target never reached the game
+    assert_eq!(
+        state.read().await.player(id).unwrap().last_processed_input,
+        0
+    );
+
+    let limit = state.read().await.config().max_message_size;
+    let huge = format!(r#"{{"type":"CreateParty","pad":"{}"}}"#, "x".repeat(limit));
+    let _ = client.ws.send(Message::Text(huge.into())).await;
+    let closed = async {
+        while let Some(Ok(frame)) = client.ws.next().await {
+            if frame.is_close() {
+                break;
+            }
+        }
+    };
+    tokio::time::timeout(TIMEOUT, closed)
+        .await
+        .expect("oversized message didn't close the connection");
+}
+
+#[tokio::test]
+async fn joins_speaking_another_protocol_version_are_turned_away() {
+    let (url, state) = start_server().await;
+    let mut client = TestClient::connect(&url).await;
+    let join = ClientMessage::Join {
+        locale: Locale::En,
+        protocol_version: Some(PROTOCOL_VERSION + 1),
+        password: None,
+        account: None,
+    };
+    client.send(&join).await;
+
+    assert_eq!(client.error_code().await, ErrorCode::ProtocolMismatch);
+    client
+        .expect(|msg| matches!(msg, ServerMessage::Kicked { .. }).then_some(()))
+        .await;
+    assert_eq!(state.read().await.players().len(), 0);
+}
+
+#[tokio::test]
+async fn password_protected_servers_take_the_password_in_join_or_the_handshake() {
+    let config = GameConfig {
+        server_password: Some("hunter2".to_string()),
+        ..GameConfig::default()
+    };
+    let (url, state) = start_server_with(config).await;
+
+    let mut stranger = TestClient::connect(&url).await;
+    stranger.send(&ClientMessage::join(Locale::En)).await;
+    assert_eq!(stranger.error_code().await, ErrorCode::WrongPassword);
+    stranger
+        .expect(|msg| matches!(msg, ServerMessage::Kicked { .. }).then_some(()))
+        .await;
+    assert_eq!(state.read().await.players().len(), 0);
+
+    let mut friend = TestClient::connect(&url).await;
+    let join = ClientMessage::join(Locale::En).with_password(Some("hunter2".to_string()));
+    friend.send(&join).await;
+    friend
+        .expect(|msg| matches!(msg, ServerMessage::Welcome { .. }).then_some(()))
+        .await;
+
+    let with_header = |password: &'static str| {
+        let mut request = url.as_str().into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert(PASSWORD_HEADER, HeaderValue::from_static(password));
+        request
+    };
+    let refused = tokio_tungstenite::connect_async(with_header("guess"))
+        .await
+        .unwrap_err();
+    assert!(refused.to_string().contains("401"), "{}", refused);
+    let (ws, _) = tokio_tungstenite::connect_async(with_header("hunter2"))
+        .await
+        .unwrap();
+    let mut native = TestClient {
+        ws,
+        deflated_frames: 0,
+    };
+    native.join().await;
+    assert_eq!(state.read().await.players().len(), 2);
+}
+
+#[tokio::test]
+async fn whitelisted_servers_only_take_listed_accounts() {
+    let config = GameConfig {
+        allowed_accounts: vec!["ana:k3y".to_string(), "bo:s3cret".to_string()],
+        ..GameConfig::default()
+    };
+    let (url, state) = start_server_with(config).await;
+
+    for account in [None, Some("ana:wrong"), Some("eve:k3y")] {
+        let mut stranger = TestClient::connect(&url).await;
+        let join = ClientMessage::join(Locale::En).with_account(account.map(String::from));
+        stranger.send(&join).await;
+        assert_eq!(stranger.error_code().await, ErrorCode::NotWhitelisted);
+    }
+    assert_eq!(state.read().await.players().len(), 0);
+
+    let mut ana = TestClient::connect(&url).await;
+    let join = ClientMessage::join(Locale::En).with_account(Some("ana:k3y".to_string()));
+    ana.send(&join).await;
+    ana.expect(|msg| matches!(msg, ServerMessage::Welcome { .. }).then_some(()))
+        .await;
+
+    let with_header = |account: &'static str| {
+        let mut request = url.as_str().into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert(ACCOUNT_HEADER, HeaderValue::from_static(account));
+        request
+    };
+    let refused = tokio_tungstenite::connect_async(with_header("bo:guess"))
+        .await
+        .unwrap_err();
+    assert!(refused.to_string().contains("403"), "{}", refused);
+    let (ws, _) = tokio_tungstenite::connect_async(with_header("bo:s3cret"))
+        .await
+        .unwrap();
+    let mut bo = TestClient {
+        ws,
+        deflated_frames: 0,
+    };
+    bo.join().await;
+    assert_eq!(state.read().await.players().len(), 2);
+}
+
+#[tokio::test]
+async fn upgrade_offers_arrive_in_the_joining_locale() {
+    let (url, state) = start_server().await;
+    let mut client = TestClient::connect(&url).await;
+    let join = r#"{"type": "Join", "locale": "pt-BR"}"#;
+    client.ws.send(Message::Text(join.into())).await.unwrap();
+    let id = client
+        .expect(|msg| match msg {
+            ServerMessage::Welcome { player_id } => Some(player_id),
+            _ => None,
+        })
+        .await;
+
+    {
+        let mut game = state.write().await;
+        let choice = UpgradeChoice {
+            upgrade: UpgradeType::Armor,
+            rarity: Rarity::Common,
+        };
+        game.set_upgrade_offer(id, vec![choice]);
+        game.player_mut(id).unwrap().rerolls_left = 1;
+    }
+    client.send(&ClientMessage::RerollUpgrades).await;
+
+    let (choices, text) = client
+        .expect(|msg| match msg {
+            ServerMessage::LevelUp {
+                upgrade_choices,
+                choice_text,
+                ..
+            } => Some((upgrade_choices, choice_text)),
+            _ => None,
+        })
+        .await;
+    assert_eq!(text.len(), choices.len());
+    for (upgrade, text) in choices.iter().zip(&text) {
+        assert_eq!(
+            text.name,
+            Locale::Pt
+                .upgrade_text(*upgrade, &UpgradeCatalog::default())
+                .name
+        );
+    }
+}
+
+#[tokio::test]
+async fn disconnect_removes_the_player() {
+    let (url, state) = start_server().await;
+    let mut client = TestClient::connect(&url).await;
+    let id = client.join().await;
+    assert!(state.read().await.player(id).is_some());
+
+    client.ws.close(None).await.unwrap();
+    drop(client);
+
+    let removed = async {
+        while state.read().await.player(id).is_some() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    };
+    tokio::time::timeout(TIMEOUT, removed)
+        .await
+        .expect("player was never removed");
+}
+
+#[tokio::test]
+async fn deflate_protocol_compresses_large_snapshots() {
+    let (url, state) = start_server().await;
+    {
+        // Enough enemies to push every snapshot past the threshold
+        let mut game = state.write().await;
+        for i in 0..50 {
+            let position = Position::new(1000.0 + i as f32 * 20.0, 0.0);
+            game.insert_enemy(Enemy::new(Uuid::new_v4(), EnemyType::Goblin, position, 1));
+        }
+    }
+
+    let mut client = TestClient::connect_deflate(&url).await;
+    let id = client.join().await;
+    client.own_player(id).await;
+
+    assert!(client.deflated_frames > 0);
+}
+
+#[tokio::test]
+async fn plain_clients_never_get_binary_frames() {
+    let (url, _) = start_server().await;
+    let mut client = TestClient::connect(&url).await;
+    let id = client.join().await;
+    for _ in 0..3 {
+        client.own_player(id).await;
+    }
+
+    assert_eq!(client.deflated_frames, 0);
+}
+
+#[tokio::test]
+async fn info_describes_the_server_without_connecting() {
+    let config = GameConfig {
+        server_name: "test server".to_string(),
+        motd: "welcome".to_string(),
+        max_players: 10,
+        ..GameConfig::default()
+    };
+    let (url, _state) = start_server_with(config).await;
+    let mut client = TestClient::connect(&url).await;
+    client.join().await;
+
+    let response = http_get(&url, "/info").await;
+    assert!(response.starts_with("http/1.1 200"), "{}", response);
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    let info: ServerInfo = serde_json::from_str(body).unwrap();
+    assert_eq!(info.name, "test server");
+    assert_eq!(info.motd, "welcome");
+    assert_eq!(info.players, 1);
+    assert_eq!(info.max_players, Some(10));
+    assert_eq!(info.mode, "endless");
+    assert_eq!(info.protocol_version, PROTOCOL_VERSION);
+}
+
+#[tokio::test]
+async fn finished_matches_are_listed_and_fetched_by_id() {
+    let config = GameConfig {
+        mode: GameMode::Timed,
+        timed_duration: 0.2,
+        ..GameConfig::default()
+    };
+    let (url, _state) = start_server_with(config).await;
+    let mut client = TestClient::connect(&url).await;
+    let player_id = client.join().await;
+    client
+        .expect(|msg| matches!(msg, ServerMessage::MatchEnded { .. }).then_some(()))
+        .await;
+
+    let list = http_get(&url, "/matches").await;
+    assert!(list.starts_with("http/1.1 200"), "{}", list);
+    let (_, body) = list.split_once("\r\n\r\n").unwrap();
+    let matches: Vec<serde_json::Value> = serde_json::from_str(body).unwrap();
+    let id = matches[0]["id"].as_str().unwrap().to_string();
+    assert_eq!(matches[0]["participants"], 1);
+
+    let record = http_get(&url, &format!("/matches/{}", id)).await;
+    assert!(record.starts_with("http/1.1 200"), "{}", record);
+    assert!(record.contains(&player_id.to_string()), "{}", record);
+    let missing = http_get(&url, &format!("/matches/{}", Uuid::new_v4())).await;
+    assert!(missing.starts_with("http/1.1 404"), "{}", missing);
+}
+
+#[tokio::test]
+async fn deaths_are_summarized_at_a_shareable_run_link() {
+    let (url, state) = start_server().await;
+    let mut client = TestClient::connect(&url).await;
+    let id = client.join().await;
+    state.write().await.player_mut(id).unwrap().health = 0.0;
+    let run_id = client
+        .expect(|msg| match msg {
+            ServerMessage::PlayerDied {
+                player_id, run_id, ..
+            } if player_id == id => run_id,
+            _ => None,
+        })
+        .await;
+
+    let response = http_get(&url, &format!("/runs/{}", run_id)).await;
+    assert!(response.starts_with("http/1.1 200"), "{}", response);
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    let summary: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(summary["player_id"], id.to_string());
+    assert_eq!(summary["mode"], "endless");
+
+    // Link previews get a page to unfurl instead
+    let path = format!("/runs/{}", run_id);
+    let page = http_request(&url, "GET", &path, "Accept: text/html\r\n", "").await;
+    assert!(page.contains("text/html"), "{}", page);
+    assert!(page.contains(r#"<meta property="og:title""#), "{}", page);
+
+    let missing = http_get(&url, &format!("/runs/{}", Uuid::new_v4())).await;
+    assert!(missing.starts_with("http/1.1 404"), "{}", missing);
+}
+
+#[tokio::test]
+async fn daily_exposes_todays_seed_and_standings() {
+    let (url, _state) = start_server().await;
+    let response = http_get(&url, "/daily").await;
+    assert!(response.starts_with("http/1.1 200"), "{}", response);
+
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    let board: serde_json::Value = serde_json::from_str(body).unwrap();
+    let today = daily::today();
+    assert_eq!(board["date"], today.to_string());
+    assert_eq!(board["seed"], daily::seed_for(today));
+    assert_eq!(board["standings"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn routes_live_under_the_base_path_with_cors() {
+    let config = GameConfig {
+        base_path: "/game".to_string(),
+        cors_origins: vec!["https://play.example.com".to_string()],
+        ..GameConfig::default()
+    };
+    let (url, _state) = start_server_with(config).await;
+    assert!(url.ends_with("/game/ws"));
+    let mut client = TestClient::connect(&url).await;
+    client.join().await;
+
+    let health = http_get(&url, "/game/health").await;
+    assert!(health.starts_with("http/1.1 200"), "{}", health);
+    assert!(health.contains("access-control-allow-origin: https://play.example.com"));
+    assert!(http_get(&url, "/health").await.starts_with("http/1.1 404"));
+}
+
+#[tokio::test]
+async fn serves_the_web_client_under_play() {
+    let dir = std::env::temp_dir().join(format!("client-dir-test-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("pkg")).unwrap();
+    std::fs::write(dir.join("index.html"), "<canvas id=\"canvas\"></canvas>").unwrap();
+    std::fs::write(dir.join("pkg/client_web.js"), "export default init;").unwrap();
+    let config = GameConfig {
+        base_path: "/game".to_string(),
+        client_dir: Some(dir.to_string_lossy().into_owned()),
+        ..GameConfig::default()
+    };
+    let (url, _state) = start_server_with(config).await;
+
+    let redirect = http_get(&url, "/game/play").await;
+    assert!(redirect.starts_with("http/1.1 308"), "{}", redirect);
+    assert!(redirect.contains("location: /game/play/"));
+    let index = http_get(&url, "/game/play/").await;
+    assert!(index.starts_with("http/1.1 200"), "{}", index);
+    assert!(index.contains("<canvas id=\"canvas\">"));
+    let script = http_get(&url, "/game/play/pkg/client_web.js").await;
+    assert!(script.contains("export default init;"));
+    assert!(http_get(&url, "/game/play/missing.js")
+        .await
+        .starts_with("http/1.1 404"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn connections_per_ip_are_capped() {
+    let config = GameConfig {
+        max_connections_per_ip: 1,
+        ..GameConfig::default()
+    };
+    let (url, _state) = start_server_with(config).await;
+
+    let mut first = TestClient::connect(&url).await;
+    first.join().await;
+    let refused = tokio_tungstenite::connect_async(&url).await.unwrap_err();
+    assert!(refused.to_string().contains("429"), "{}", refused);
+
+    // Closing the first connection frees the slot
+    drop(first);
+    let reconnect = async {
+        loop {
+            if tokio_tungstenite::connect_async(&url).await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    };
+    tokio::time::timeout(TIMEOUT, reconnect).await.unwrap();
+}
+
+#[tokio::test]
+async fn admin_ban_kicks_and_refuses_the_address() {
+    let dir = std::env::temp_dir().join(format!("ban-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let ban_list_path = dir.join("bans.json");
+    let config = GameConfig {
+        admin_token: Some("secret".to_string()),
+        ban_list_path: ban_list_path.to_string_lossy().into_owned(),
+        ..GameConfig::default()
+    };
+    let (url, _state) = start_server_with(config).await;
+    let mut client = TestClient::connect(&url).await;
+    client.join().await;
+
+    let auth = "Authorization: Bearer secret\r\nContent-Type: application/json\r\n";
+    let body = r#"{"target": {"ip": "127.0.0.1"}, "reason": "griefing"}"#;
+    let unauthorized = http_request(&url, "POST", "/admin/bans", "", body).await;
+    assert!(unauthorized.starts_with("http/1.1 401"), "{}", unauthorized);
+    let created = http_request(&url, "POST", "/admin/bans", auth, body).await;
+    assert!(created.starts_with("http/1.1 201"), "{}", created);
+
+    let reason = client
+        .expect(|msg| match msg {
+            ServerMessage::Kicked { reason } => Some(reason),
+            _ => None,
+        })
+        .await;
+    assert_eq!(reason, "Banned: griefing");
+    let refused = tokio_tungstenite::connect_async(&url).await.unwrap_err();
+    assert!(refused.to_string().contains("403"), "{}", refused);
+    assert!(std::fs::read_to_string(&ban_list_path)
+        .unwrap()
+        .contains("griefing"));
+
+    let lifted = http_request(&url, "DELETE", "/admin/bans/ip/127.0.0.1", auth, "").await;
+    assert!(lifted.starts_with("http/1.1 204"), "{}", lifted);
+    TestClient::connect(&url).await.join().await;
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn debug_tasks_reports_sessions_and_lock_waits() {
+    let config = GameConfig {
+        admin_token: Some("secret".to_string()),
+        ..GameConfig::default()
+    };
+    let (url, _state) = start_server_with(config).await;
+    let mut client = TestClient::connect(&url).await;
+    client.join().await;
+
+    let unauthorized = http_request(&url, "GET", "/debug/tasks", "", "").await;
+    assert!(unauthorized.starts_with("http/1.1 401"), "{}", unauthorized);
+    let auth = "Authorization: Bearer secret\r\n";
+    let response = http_request(&url, "GET", "/debug/tasks", auth, "").await;
+    assert!(response.starts_with("http/1.1 200"), "{}", response);
+
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    let report: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(report["sessions"], 1);
+    assert_eq!(report["connections"], 1);
+    assert_eq!(report["players"], 1);
+    assert!(report["runtime"]["alive_tasks"].as_u64().unwrap() > 0);
+    assert!(report["lock_waits"]["game_loop"]["count"].as_u64().unwrap() > 0);
+    assert!(report["lock_waits"]["message"]["count"].as_u64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn admin_dashboard_lists_and_kicks_players() {
+    let config = GameConfig {
+        admin_token: Some("secret".to_string()),
+        ..GameConfig::default()
+    };
+    let (url, _state) = start_server_with(config).await;
+    let mut client = TestClient::connect(&url).await;
+    let player_id = client.join().await;
+
+    // The page is public, what it shows isn't
+    let page = http_request(&url, "GET", "/admin/ui", "", "").await;
+    assert!(page.starts_with("http/1.1 200"), "{}", page);
+    let unauthorized = http_request(&url, "GET", "/admin/status", "", "").await;
+    assert!(unauthorized.starts_with("http/1.1 401"), "{}", unauthorized);
+
+    let auth = "Authorization: Bearer secret\r\n";
+    let response = http_request(&url, "GET", "/admin/status", auth, "").await;
+    assert!(response.starts_with("http/1.1 200"), "{}", response);
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    let status: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(status["counts"]["players"], 1);
+    assert_eq!(status["players"][0]["id"], player_id.to_string());
+    assert_eq!(status["players"][0]["ip"], "127.0.0.1");
+    assert!(status["timings"]["ticks"].as_u64().is_some());
+
+    let logs = http_request(&url, "GET", "/admin/logs?after=0", auth, "").await;
+    assert!(logs.starts_with("http/1.1 200"), "{}", logs);
+
+    let path = format!("/admin/players/{}/kick", player_id);
+    let kicked = http_request(&url, "POST", &path, auth, "").await;
+    assert!(kicked.starts_with("http/1.1 204"), "{}", kicked);
+    let reason = client
+        .expect(|msg| match msg {
+            ServerMessage::Kicked { reason } => Some(reason),
+            _ => None,
+        })
+        .await;
+    assert_eq!(reason, "Kicked by an admin");
+    let again = http_request(&url, "POST", &path, auth, "").await;
+    assert!(again.starts_with("http/1.1 404"), "{}", again);
+}
+
+#[tokio::test]
+async fn admin_announcements_reach_connected_players() {
+    let config = GameConfig {
+        admin_token: Some("secret".to_string()),
+        ..GameConfig::default()
+    };
+    let (url, _state) = start_server_with(config).await;
+    let mut client = TestClient::connect(&url).await;
+    client.join().await;
+
+    let auth = "Authorization: Bearer secret\r\nContent-Type: application/json\r\n";
+    let empty = http_request(&url, "POST", "/admin/announce", auth, r#"{"text": " "}"#).await;
+    assert!(empty.starts_with("http/1.1 400"), "{}", empty);
+    let body = r#"{"text": "Restarting in five minutes"}"#;
+    let response = http_request(&url, "POST", "/admin/announce", auth, body).await;
+    assert!(response.starts_with("http/1.1 204"), "{}", response);
+    let text = client
+        .expect(|msg| match msg {
+            ServerMessage::Announcement { text } => Some(text),
+            _ => None,
+        })
+        .await;
+    assert_eq!(text, "Restarting in five minutes");
+}
+
+#[tokio::test]
+async fn admin_can_pause_single_step_and_speed_up_the_simulation() {
+    let config = GameConfig {
+        admin_token: Some("secret".to_string()),
+        ..GameConfig::default()
+    };
+    let (url, state) = start_server_with(config).await;
+    let auth = "Authorization: Bearer secret\r\nContent-Type: application/json\r\n";
+    let sim = "/admin/rooms/main/sim";
+
+    let paused = http_request(&url, "POST", sim, auth, r#"{"paused": true}"#).await;
+    assert!(paused.starts_with("http/1.1 200"), "{}", paused);
+    // A step already under way when the pause landed may still finish
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let tick = state.read().await.tick();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(state.read().await.tick(), tick, "paused");
+
+    let step = "/admin/rooms/main/sim/step";
+    let stepped = http_request(&url, "POST", step, auth, r#"{"ticks": 3}"#).await;
+    assert!(stepped.starts_with("http/1.1 200"), "{}", stepped);
+    let three_steps = async {
+        while state.read().await.tick() < tick + 3 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(TIMEOUT, three_steps).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(state.read().await.tick(), tick + 3);
+
+    let too_fast = http_request(&url, "POST", sim, auth, r#"{"speed": 8}"#).await;
+    assert!(too_fast.starts_with("http/1.1 400"), "{}", too_fast);
+    let resumed = http_request(&url, "POST", sim, auth, r#"{"paused": false, "speed": 4}"#).await;
+    assert!(resumed.starts_with("http/1.1 200"), "{}", resumed);
+    assert!(resumed.contains(r#""speed":4.0"#), "{}", resumed);
+    // The body is optional, one step by default
+    let token_only = "Authorization: Bearer secret\r\n";
+    let running = http_request(&url, "POST", step, token_only, "").await;
+    assert!(running.starts_with("http/1.1 409"), "{}", running);
+
+    let missing = http_request(&url, "GET", "/admin/rooms/nowhere/sim", auth, "").await;
+    assert!(missing.starts_with("http/1.1 404"), "{}", missing);
+}
+
+#[tokio::test]
+async fn rooms_can_be_opened_listed_and_joined() {
+    let config = GameConfig {
+        max_rooms: 1,
+        ..GameConfig::default()
+    };
+    let (url, state) = start_server_with(config).await;
+    let json = "Content-Type: application/json\r\n";
+    let body_of = |response: &str| -> serde_json::Value {
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        serde_json::from_str(body).unwrap()
+    };
+
+    let listed = http_get(&url, "/rooms").await;
+    assert!(listed.starts_with("http/1.1 200"), "{}", listed);
+    assert_eq!(body_of(&listed)[0]["token"], "main");
+
+    let bad = r#"{"config": {"admin_token": "mine"}}"#;
+    let refused = http_request(&url, "POST", "/rooms", json, bad).await;
+    assert!(refused.starts_with("http/1.1 422"), "{}", refused);
+    for bad in [
+        r#"{"config": {"max_rings": 4000000000}}"#,
+        r#"{"config": {"enemy_spawn_rate": -1.0}}"#,
+        r#"{"config": {"timed_duration": 0.0}}"#,
+    ] {
+        let refused = http_request(&url, "POST", "/rooms", json, bad).await;
+        assert!(refused.starts_with("http/1.1 400"), "{}", refused);
+    }
+    assert_eq!(state.read().await.rooms().len(), 0);
+
+    let body = r#"{"config": {"mode": "Timed", "max_players": 2}}"#;
+    let created = http_request(&url, "POST", "/rooms", json, body).await;
+    assert!(created.starts_with("http/1.1 201"), "{}", created);
+    let room = body_of(&created);
+    assert_eq!(room["mode"], "timed");
+    assert_eq!(room["max_players"], 2);
+    let token = room["token"].as_str().unwrap().to_string();
+    let code = room["invite"]["code"].as_str().unwrap().to_string();
+
+    // Private rooms aren't listed
+    let listed = body_of(&http_get(&url, "/rooms").await);
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    let full = http_request(&url, "POST", "/rooms", json, "{}").await;
+    assert!(full.starts_with("http/1.1 503"), "{}", full);
+
+    let room_url = format!("{}?room={}&code={}", url, token, code);
+    let mut client = TestClient::connect(&room_url).await;
+    let player_id = client.join().await;
+    let room = state.read().await.rooms().get(&token).unwrap();
+    assert!(room.read().await.player(player_id).is_some());
+    assert_eq!(state.read().await.players().len(), 0);
+
+    let missing = tokio_tungstenite::connect_async(format!("{}?room=nope", url))
+        .await
+        .unwrap_err();
+    assert!(missing.to_string().contains("404"), "{}", missing);
+}
+
+#[tokio::test]
+async fn private_rooms_need_their_invite_code_and_answer_to_their_owner() {
+    let (url, _state) = start_server().await;
+    let json = "Content-Type: application/json\r\n";
+    let created = http_request(&url, "POST", "/rooms", json, "{}").await;
+    let (_, body) = created.split_once("\r\n\r\n").unwrap();
+    let room: serde_json::Value = serde_json::from_str(body).unwrap();
+    let token = room["token"].as_str().unwrap().to_string();
+    let code = room["invite"]["code"].as_str().unwrap().to_string();
+    let owner = format!(
+        "Authorization: Bearer {}\r\n",
+        room["owner_key"].as_str().unwrap()
+    );
+
+    for query in [
+        format!("room={}", token),
+        format!("room={}&code=nope", token),
+    ] {
+        let refused = tokio_tungstenite::connect_async(format!("{}?{}", url, query))
+            .await
+            .unwrap_err();
+        assert!(refused.to_string().contains("403"), "{}", refused);
+    }
+    let mut guest = TestClient::connect(&format!("{}?room={}&code={}", url, token, code)).await;
+    let guest_id = guest.join().await;
+
+    // Only the owner may kick, and a new code keeps the kicked out
+    let kick = format!("/rooms/{}/players/{}/kick", token, guest_id);
+    let stranger = http_request(&url, "POST", &kick, "", "").await;
+    assert!(stranger.starts_with("http/1.1 401"), "{}", stranger);
+    let kicked = http_request(&url, "POST", &kick, &owner, "").await;
+    assert!(kicked.starts_with("http/1.1 204"), "{}", kicked);
+    let reason = guest
+        .expect(|msg| match msg {
+            ServerMessage::Kicked { reason } => Some(reason),
+            _ => None,
+        })
+        .await;
+    assert_eq!(reason, "Kicked by the room's owner");
+
+    let renewed = http_request(
+        &url,
+        "POST",
+        &format!("/rooms/{}/invite", token),
+        &owner,
+        "",
+    )
+    .await;
+    assert!(renewed.starts_with("http/1.1 200"), "{}", renewed);
+    let (_, body) = renewed.split_once("\r\n\r\n").unwrap();
+    let invite: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_ne!(invite["code"], code.as_str());
+    let old_code = format!("{}?room={}&code={}", url, token, code);
+    let refused = tokio_tungstenite::connect_async(old_code)
+        .await
+        .unwrap_err();
+    assert!(refused.to_string().contains("403"), "{}", refused);
+}
+
+#[tokio::test]
+async fn full_server_queues_joins_until_a_slot_frees() {
+    let config = GameConfig {
+        max_players: 1,
+        max_queue_length: 1,
+        ..GameConfig::default()
+    };
+    let (url, _state) = start_server_with(config).await;
+    let mut playing = TestClient::connect(&url).await;
+    playing.join().await;
+
+    let mut waiting = TestClient::connect(&url).await;
+    waiting.send(&ClientMessage::join(Locale::En)).await;
+    let position = waiting
+        .expect(|msg| match msg {
+            ServerMessage::QueuePosition {
+                position,
+                queue_length,
+            } => Some((position, queue_length)),
+            ServerMessage::Welcome { .. } => panic!("admitted past max_players"),
+            _ => None,
+        })
+        .await;
+    assert_eq!(position, (1, 1));
+    let choose = ClientMessage::ChooseUpgrade {
+        upgrade: UpgradeType::IncreaseDamage,
+    };
+    waiting.send(&choose).await;
+    assert_eq!(waiting.error_code().await, ErrorCode::NotInGame);
+
+    // Queue full as well: turned away
+    let mut turned_away = TestClient::connect(&url).await;
+    turned_away.send(&ClientMessage::join(Locale::En)).await;
+    assert_eq!(turned_away.error_code().await, ErrorCode::RoomFull);
+    let reason = turned_away
+        .expect(|msg| match msg {
+            ServerMessage::Kicked { reason } => Some(reason),
+            _ => None,
+        })
+        .await;
+    assert_eq!(reason, "Server is full");
+
+    drop(playing);
+    waiting
+        .expect(|msg| match msg {
+            ServerMessage::Welcome { player_id } => Some(player_id),
+            _ => None,
+        })
+        .await;
+}
@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::types::{Enemy, EnemyStats, EnemyType, Position};
+
+/// One enemy type's ring 1 stats and kill reward
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnemyDefinition {
+    #[serde(rename = "type")]
+    pub enemy_type: EnemyType,
+    #[serde(flatten)]
+    pub stats: EnemyStats,
+    pub xp: u32, // before ring scaling
+    #[serde(default)]
+    pub boss: bool, // counts as a boss kill and shows on the minimap
+    #[serde(default)]
+    pub burns: bool, // melee hits set players on fire
+}
+
+/// Enemy types that spawn from `ring` outwards, until the next entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RingSpawns {
+    pub ring: u32,
+    pub enemies: Vec<EnemyType>,
+}
+
+/// How much stronger enemies get per ring past the first, as a fraction of
+/// their ring 1 value
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RingScaling {
+    pub health: f32,
+    pub damage: f32,
+    pub movement_speed: f32,
+    pub xp: u32, // XP is multiplied by `xp * ring`
+}
+
+impl Default for RingScaling {
+    fn default() -> Self {
+        Self {
+            health: 1.0,
+            damage: 0.3,
+            movement_speed: 0.1,
+            xp: 5,
+        }
+    }
+}
+
+impl RingScaling {
+    pub fn stats(&self, base: EnemyStats, ring: u32) -> EnemyStats {
+        let rings_out = (ring.max(1) - 1) as f32;
+        EnemyStats {
+            max_health: base.max_health * (1.0 + rings_out * self.health),
+            damage: base.damage * (1.0 + rings_out * self.damage),
+            movement_speed: base.movement_speed * (1.0 + rings_out * self.movement_speed),
+            attack_speed: base.attack_speed,
+        }
+    }
+
+    pub fn xp(&self, base_xp: u32, ring: u32) -> u32 {
+        base_xp * ring.max(1) * self.xp
+    }
+}
+
+/// Every enemy the server can spawn and where. The default is the built-in
+/// balance from `EnemyType`; a data file can replace it without a rebuild.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnemyRoster {
+    pub enemies: Vec<EnemyDefinition>,
+    pub rings: Vec<RingSpawns>, // sorted by ring
+    #[serde(default)]
+    pub scaling: RingScaling,
+}
+
+impl Default for EnemyRoster {
+    fn default() -> Self {
+        let enemies = EnemyType::all()
+            .into_iter()
+            .map(|enemy_type| EnemyDefinition {
+                enemy_type,
+                stats: enemy_type.base_stats(),
+                xp: enemy_type.base_xp(),
+                boss: enemy_type.is_boss(),
+                burns: enemy_type.burns(),
+            })
+            .collect();
+        let rings = (1..=10)
+            .map(|ring| RingSpawns {
+                ring,
+                enemies: EnemyType::for_ring(ring),
+            })
+            .collect();
+        Self {
+            enemies,
+            rings,
+            scaling: RingScaling::default(),
+        }
+    }
+}
+
+impl EnemyRoster {
+    pub fn definition(&self, enemy_type: EnemyType) -> Option<&EnemyDefinition> {
+        self.enemies.iter().find(|d| d.enemy_type == enemy_type)
+    }
+
+    /// Enemy types that can spawn at `ring`: the last entry starting at or
+    /// inside it, or the first entry for rings before any
+    pub fn for_ring(&self, ring: u32) -> &[EnemyType] {
+        self.rings
+            .iter()
+            .rev()
+            .find(|r| r.ring <= ring)
+            .or(self.rings.first())
+            .map_or(&[], |r| &r.enemies)
+    }
+
+    pub fn is_boss(&self, enemy_type: EnemyType) -> bool {
+        self.definition(enemy_type).is_some_and(|d| d.boss)
+    }
+
+    pub fn burns(&self, enemy_type: EnemyType) -> bool {
+        self.definition(enemy_type).is_some_and(|d| d.burns)
+    }
+
+    /// A new enemy of `enemy_type` with its stats for `ring`. Types missing
+    /// from the roster fall back to the built-in values.
+    pub fn spawn(&self, id: Uuid, enemy_type: EnemyType, position: Position, ring: u32) -> Enemy {
+        let Some(definition) = self.definition(enemy_type) else {
+            return Enemy::new(id, enemy_type, position, ring);
+        };
+        let stats = self.scaling.stats(definition.stats, ring);
+        let xp_reward = self.scaling.xp(definition.xp, ring);
+        Enemy::with_stats(id, enemy_type, position, ring, stats, xp_reward)
+    }
+
+    /// Everything in the roster the server can't spawn from, one line each
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.rings.is_empty() {
+            problems.push("enemy roster has no rings".to_string());
+        }
+        if !self.rings.windows(2).all(|w| w[0].ring < w[1].ring) {
+            problems.push("enemy roster rings must be in increasing order".to_string());
+        }
+        for (i, definition) in self.enemies.iter().enumerate() {
+            let name = definition.enemy_type.name();
+            if self.enemies[..i]
+                .iter()
+                .any(|d| d.enemy_type == definition.enemy_type)
+            {
+                problems.push(format!("{name} is defined more than once"));
+            }
+            let stats = definition.stats;
+            if stats.max_health <= 0.0 || stats.damage < 0.0 || stats.movement_speed < 0.0 {
+                problems.push(format!(
+                    "{name} needs positive health and no negative stats"
+                ));
+            }
+        }
+        for spawns in &self.rings {
+            if spawns.enemies.is_empty() {
+                problems.push(format!("ring {} spawns no enemies", spawns.ring));
+            }
+            for enemy_type in &spawns.enemies {
+                if self.definition(*enemy_type).is_none() {
+                    problems.push(format!(
+                        "ring {} spawns {}, which has no definition",
+                        spawns.ring,
+                        enemy_type.name()
+                    ));
+                }
+            }
+        }
+        problems
+    }
+}
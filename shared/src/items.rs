@@ -0,0 +1,83 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Where a piece of equipment is worn; a player wears one item per slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    Ring,   // damage and crit chance
+    Amulet, // max health
+    Boots,  // movement speed
+}
+
+impl EquipmentSlot {
+    pub const ALL: [EquipmentSlot; 3] = [
+        EquipmentSlot::Ring,
+        EquipmentSlot::Amulet,
+        EquipmentSlot::Boots,
+    ];
+
+    pub fn name(&self) -> &str {
+        match self {
+            EquipmentSlot::Ring => "Ring",
+            EquipmentSlot::Amulet => "Amulet",
+            EquipmentSlot::Boots => "Boots",
+        }
+    }
+}
+
+/// Flat stat bonuses, added on top of the player's own stats while worn
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatModifiers {
+    pub damage: f32,
+    pub max_health: f32,
+    pub movement_speed: f32,
+    pub crit_chance: f32, // 0.0-1.0, added to the player's chance
+}
+
+impl StatModifiers {
+    pub fn add(&mut self, other: &StatModifiers) {
+        self.damage += other.damage;
+        self.max_health += other.max_health;
+        self.movement_speed += other.movement_speed;
+        self.crit_chance += other.crit_chance;
+    }
+}
+
+/// A piece of gear, carried in the inventory or worn in its slot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Equipment {
+    pub id: Uuid,
+    pub slot: EquipmentSlot,
+    pub level: u32, // ring it dropped in; deeper rings drop stronger gear
+    pub modifiers: StatModifiers,
+}
+
+impl Equipment {
+    /// Random gear for an enemy killed in `ring`
+    pub fn roll(ring: u32, rng: &mut impl Rng) -> Self {
+        let slot = EquipmentSlot::ALL[rng.gen_range(0..EquipmentSlot::ALL.len())];
+        let power = ring.max(1) as f32 * rng.gen_range(0.75..=1.25);
+        let modifiers = match slot {
+            EquipmentSlot::Ring => StatModifiers {
+                damage: power,
+                crit_chance: 0.01 * power,
+                ..StatModifiers::default()
+            },
+            EquipmentSlot::Amulet => StatModifiers {
+                max_health: 10.0 * power,
+                ..StatModifiers::default()
+            },
+            EquipmentSlot::Boots => StatModifiers {
+                movement_speed: 6.0 * power,
+                ..StatModifiers::default()
+            },
+        };
+        Self {
+            id: Uuid::from_u128(rng.gen()),
+            slot,
+            level: ring,
+            modifiers,
+        }
+    }
+}
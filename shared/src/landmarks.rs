@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::types::Position;
+
+/// Static points of interest placed in each ring at match start
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LandmarkType {
+    Chest,    // one-time: upgrade choice or a rare item
+    Shrine,   // reusable after a cooldown: temporary buff
+    Obelisk,  // ring marker for navigation; can't be used
+    Waypoint, // attune by using it, then teleport between attuned ones
+}
+
+impl LandmarkType {
+    pub fn name(&self) -> &str {
+        match self {
+            LandmarkType::Chest => "Chest",
+            LandmarkType::Shrine => "Shrine",
+            LandmarkType::Obelisk => "Obelisk",
+            LandmarkType::Waypoint => "Waypoint",
+        }
+    }
+}
+
+/// A landmark in the world. Sent on join and whenever one changes state,
+/// not with every snapshot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Landmark {
+    pub id: Uuid,
+    pub landmark_type: LandmarkType,
+    pub position: Position,
+    pub ring: u32,
+    pub opened: bool,  // chests: already looted
+    pub ready_at: f64, // shrines: game time the cooldown ends
+}
+
+impl Landmark {
+    pub fn new(id: Uuid, landmark_type: LandmarkType, position: Position, ring: u32) -> Self {
+        Self {
+            id,
+            landmark_type,
+            position,
+            ring,
+            opened: false,
+            ready_at: 0.0,
+        }
+    }
+
+    /// Whether a player could use this landmark right now
+    pub fn is_usable(&self, current_time: f64) -> bool {
+        match self.landmark_type {
+            LandmarkType::Chest => !self.opened,
+            LandmarkType::Shrine => current_time >= self.ready_at,
+            LandmarkType::Obelisk => false,
+            LandmarkType::Waypoint => true,
+        }
+    }
+}
+
+/// A teleport being channeled; moving or getting hit cancels it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Teleport {
+    pub waypoint: Uuid,
+    pub arrives_at: f64, // game time the channel completes
+}
@@ -1,10 +1,39 @@
+pub mod enemies;
+pub mod items;
+pub mod landmarks;
+pub mod locale;
 pub mod messages;
+pub mod minimap;
+pub mod shop;
+pub mod summons;
+pub mod timestamp;
 pub mod types;
+pub mod udp;
 pub mod upgrades;
+pub mod vec2;
 
 #[cfg(test)]
 mod tests;
 
-pub use messages::{ClientMessage, ServerMessage};
-pub use types::{Enemy, EnemyStats, EnemyType, Player, Position, Projectile, ScoreEntry};
-pub use upgrades::{PlayerUpgrades, UpgradeType};
+pub use enemies::{EnemyDefinition, EnemyRoster, RingScaling, RingSpawns};
+pub use items::{Equipment, EquipmentSlot, StatModifiers};
+pub use landmarks::{Landmark, LandmarkType, Teleport};
+pub use locale::{Locale, UpgradeText};
+pub use messages::{
+    ClientMessage, EmoteKind, Envelope, ErrorCode, RoomInfo, ServerInfo, ServerMessage,
+    ACCOUNT_HEADER, DEFLATE_PROTOCOL, PASSWORD_HEADER, PROTOCOL_VERSION,
+};
+pub use minimap::MinimapMarker;
+pub use shop::{ShopItem, ShopListing};
+pub use summons::{Summon, SummonStats, SummonType};
+pub use timestamp::Timestamp;
+pub use types::{
+    cmp_distance, CombatEvent, CombatEventKind, DespawnReason, Enemy, EnemyBehavior, EnemyStats,
+    EnemyType, EntityEvent, EntityKind, OwnPlayerState, Pickup, PickupType, Player, Position,
+    Projectile, RankChange, RemotePlayerView, RunStats, ScoreEntry, Steering, Weapon,
+};
+pub use upgrades::{
+    PlayerUpgrades, Rarity, RarityTier, UpgradeCatalog, UpgradeChoice, UpgradeMagnitude,
+    UpgradeTable, UpgradeType, UpgradeWeight,
+};
+pub use vec2::Vec2;
@@ -0,0 +1,390 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::EnemyType;
+use crate::upgrades::{Rarity, UpgradeCatalog, UpgradeChoice, UpgradeType};
+
+/// Language for player-facing text the server sends, such as upgrade
+/// offers. Clients name theirs in `Join`; on the wire it's the language
+/// part of a BCP 47 tag, and anything unsupported falls back to English.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Locale {
+    #[default]
+    En,
+    Es,
+    Pt,
+    De,
+}
+
+/// An upgrade's name and description in the receiving player's locale
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpgradeText {
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub rarity: String,
+}
+
+impl UpgradeText {
+    /// What to show for a `LevelUp`'s choices: the text the server sent,
+    /// or English from a server that predates localization
+    pub fn for_choices(choices: &[UpgradeType], sent: Vec<UpgradeText>) -> Vec<UpgradeText> {
+        if sent.len() == choices.len() {
+            return sent;
+        }
+        let catalog = UpgradeCatalog::default();
+        choices
+            .iter()
+            .map(|u| Locale::En.upgrade_text(*u, &catalog))
+            .collect()
+    }
+}
+
+impl Locale {
+    /// The user's language from `LC_ALL`, `LC_MESSAGES` or `LANG`, for
+    /// native clients
+    pub fn from_env() -> Locale {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|tag| !tag.is_empty())
+            .map_or(Locale::En, |tag| Locale::from_tag(&tag))
+    }
+
+    /// From a tag like `pt-BR`, or a POSIX locale like `de_DE.UTF-8`
+    pub fn from_tag(tag: &str) -> Locale {
+        let language = tag.split(['-', '_', '.']).next().unwrap_or_default();
+        match language.to_ascii_lowercase().as_str() {
+            "es" => Locale::Es,
+            "pt" => Locale::Pt,
+            "de" => Locale::De,
+            _ => Locale::En,
+        }
+    }
+
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Es => "es",
+            Locale::Pt => "pt",
+            Locale::De => "de",
+        }
+    }
+
+    /// An upgrade's name, and its description with the catalog's numbers
+    pub fn upgrade_text(&self, upgrade: UpgradeType, catalog: &UpgradeCatalog) -> UpgradeText {
+        let (name, template) = match self {
+            Locale::En => (upgrade.name(), upgrade.description_template()),
+            Locale::Es => upgrade_es(upgrade),
+            Locale::Pt => upgrade_pt(upgrade),
+            Locale::De => upgrade_de(upgrade),
+        };
+        UpgradeText {
+            name: name.to_string(),
+            description: catalog.describe(upgrade, template),
+            rarity: self.rarity_name(Rarity::Common).to_string(),
+        }
+    }
+
+    /// An offered upgrade's text, noting its rarity and, when it applies
+    /// more than one, how many levels it's worth
+    pub fn choice_text(
+        &self,
+        choice: UpgradeChoice,
+        levels: u32,
+        catalog: &UpgradeCatalog,
+    ) -> UpgradeText {
+        let mut text = self.upgrade_text(choice.upgrade, catalog);
+        text.rarity = self.rarity_name(choice.rarity).to_string();
+        if levels > 1 {
+            text.description = format!("{} (x{})", text.description, levels);
+        }
+        text
+    }
+
+    pub fn rarity_name(&self, rarity: Rarity) -> &'static str {
+        match (self, rarity) {
+            (Locale::En, _) => rarity.name(),
+            (Locale::Es, Rarity::Common) => "Común",
+            (Locale::Es, Rarity::Rare) => "Raro",
+            (Locale::Es, Rarity::Epic) => "Épico",
+            (Locale::Pt, Rarity::Common) => "Comum",
+            (Locale::Pt, Rarity::Rare) => "Raro",
+            (Locale::Pt, Rarity::Epic) => "Épico",
+            (Locale::De, Rarity::Common) => "Gewöhnlich",
+            (Locale::De, Rarity::Rare) => "Selten",
+            (Locale::De, Rarity::Epic) => "Episch",
+        }
+    }
+
+    pub fn enemy_name(&self, enemy: EnemyType) -> &'static str {
+        match self {
+            Locale::En => enemy.name(),
+            Locale::Es => enemy_es(enemy),
+            Locale::Pt => enemy_pt(enemy),
+            Locale::De => enemy_de(enemy),
+        }
+    }
+}
+
+impl From<String> for Locale {
+    fn from(tag: String) -> Self {
+        Locale::from_tag(&tag)
+    }
+}
+
+impl From<Locale> for String {
+    fn from(locale: Locale) -> Self {
+        locale.tag().to_string()
+    }
+}
+
+fn upgrade_es(upgrade: UpgradeType) -> (&'static str, &'static str) {
+    match upgrade {
+        UpgradeType::IncreaseDamage => ("Daño+", "Aumenta el daño un {value}%"),
+        UpgradeType::IncreaseAttackSpeed => (
+            "Velocidad de ataque+",
+            "Aumenta la velocidad de ataque un {value}%",
+        ),
+        UpgradeType::IncreaseProjectileSpeed => (
+            "Velocidad de proyectil+",
+            "Aumenta la velocidad de los proyectiles un {value}%",
+        ),
+        UpgradeType::MultiShot => (
+            "Disparo múltiple",
+            "Dispara {value} proyectiles adicionales",
+        ),
+        UpgradeType::PiercingShots => (
+            "Disparos perforantes",
+            "Los proyectiles atraviesan {value} enemigo",
+        ),
+        UpgradeType::CritChance => (
+            "Prob. de crítico+",
+            "Aumenta la probabilidad de golpe crítico un {value}%",
+        ),
+        UpgradeType::IncreaseMaxHealth => ("Salud máxima+", "Aumenta la salud máxima un {value}%"),
+        UpgradeType::IncreaseMovementSpeed => (
+            "Velocidad+",
+            "Aumenta la velocidad de movimiento un {value}%",
+        ),
+        UpgradeType::HealthRegeneration => ("Regeneración", "Regenera {value} PS por segundo"),
+        UpgradeType::Overshield => (
+            "Sobreescudo",
+            "Recarga un escudo que absorbe {value} de daño",
+        ),
+        UpgradeType::PickupRadius => (
+            "Radio de recogida+",
+            "Aumenta el radio de recogida un {value}%",
+        ),
+        UpgradeType::Magnet => ("Imán", "Recoge automáticamente la experiencia cercana"),
+        UpgradeType::Armor => (
+            "Armadura",
+            "Reduce el daño recibido un {value}% (máx. {cap}%)",
+        ),
+        UpgradeType::Luck => ("Suerte", "Aumenta la suerte un {value}%"),
+        UpgradeType::SecondWind => (
+            "Segundo aliento",
+            "Una vez por partida, sobrevive a un golpe letal con un {value}% de vida",
+        ),
+        UpgradeType::SummonDrone => (
+            "Dron de ataque",
+            "Invoca un dron que dispara a los enemigos cercanos",
+        ),
+        UpgradeType::SummonWolf => (
+            "Lobo mascota",
+            "Invoca un lobo que caza a los enemigos cercanos",
+        ),
+        UpgradeType::SummonDamage => (
+            "Daño de invocaciones+",
+            "Los compañeros infligen un {value}% más de daño",
+        ),
+        UpgradeType::DamageAura => (
+            "Aura de daño",
+            "Un campo a tu alrededor inflige {value} de daño por segundo",
+        ),
+        UpgradeType::AuraRadius => ("Radio del aura+", "Aumenta el radio del aura un {value}%"),
+        UpgradeType::HomingShots => (
+            "Disparos teledirigidos",
+            "Los proyectiles giran hacia el enemigo más cercano a {value}° por segundo",
+        ),
+        UpgradeType::ExplosiveShots => (
+            "Disparos explosivos",
+            "Los proyectiles explotan con un {value}% de su daño",
+        ),
+    }
+}
+
+fn upgrade_pt(upgrade: UpgradeType) -> (&'static str, &'static str) {
+    match upgrade {
+        UpgradeType::IncreaseDamage => ("Dano+", "Aumenta o dano em {value}%"),
+        UpgradeType::IncreaseAttackSpeed => (
+            "Velocidade de ataque+",
+            "Aumenta a velocidade de ataque em {value}%",
+        ),
+        UpgradeType::IncreaseProjectileSpeed => (
+            "Velocidade do projétil+",
+            "Aumenta a velocidade dos projéteis em {value}%",
+        ),
+        UpgradeType::MultiShot => ("Tiro múltiplo", "Dispara {value} projéteis adicionais"),
+        UpgradeType::PiercingShots => (
+            "Tiros perfurantes",
+            "Os projéteis atravessam {value} inimigo",
+        ),
+        UpgradeType::CritChance => (
+            "Chance de crítico+",
+            "Aumenta a chance de acerto crítico em {value}%",
+        ),
+        UpgradeType::IncreaseMaxHealth => ("Vida máxima+", "Aumenta a vida máxima em {value}%"),
+        UpgradeType::IncreaseMovementSpeed => (
+            "Velocidade+",
+            "Aumenta a velocidade de movimento em {value}%",
+        ),
+        UpgradeType::HealthRegeneration => ("Regeneração", "Regenera {value} PV por segundo"),
+        UpgradeType::Overshield => (
+            "Sobrescudo",
+            "Recarrega um escudo que absorve {value} de dano",
+        ),
+        UpgradeType::PickupRadius => ("Raio de coleta+", "Aumenta o raio de coleta em {value}%"),
+        UpgradeType::Magnet => ("Ímã", "Coleta automaticamente a XP próxima"),
+        UpgradeType::Armor => (
+            "Armadura",
+            "Reduz o dano recebido em {value}% (máx. {cap}%)",
+        ),
+        UpgradeType::Luck => ("Sorte", "Aumenta a sorte em {value}%"),
+        UpgradeType::SecondWind => (
+            "Segundo fôlego",
+            "Uma vez por partida, sobreviva a um golpe letal com {value}% de vida",
+        ),
+        UpgradeType::SummonDrone => (
+            "Drone de ataque",
+            "Invoca um drone que atira nos inimigos próximos",
+        ),
+        UpgradeType::SummonWolf => (
+            "Lobo de estimação",
+            "Invoca um lobo que caça os inimigos próximos",
+        ),
+        UpgradeType::SummonDamage => (
+            "Dano de invocações+",
+            "Os companheiros causam {value}% mais dano",
+        ),
+        UpgradeType::DamageAura => (
+            "Aura de dano",
+            "Um campo ao seu redor causa {value} de dano por segundo",
+        ),
+        UpgradeType::AuraRadius => ("Raio da aura+", "Aumenta o raio da aura em {value}%"),
+        UpgradeType::HomingShots => (
+            "Tiros teleguiados",
+            "Os projéteis viram em direção ao inimigo mais próximo a {value}° por segundo",
+        ),
+        UpgradeType::ExplosiveShots => (
+            "Tiros explosivos",
+            "Os projéteis explodem com {value}% do seu dano",
+        ),
+    }
+}
+
+fn upgrade_de(upgrade: UpgradeType) -> (&'static str, &'static str) {
+    match upgrade {
+        UpgradeType::IncreaseDamage => ("Schaden+", "Erhöht den Schaden um {value} %"),
+        UpgradeType::IncreaseAttackSpeed => {
+            ("Angriffstempo+", "Erhöht das Angriffstempo um {value} %")
+        }
+        UpgradeType::IncreaseProjectileSpeed => {
+            ("Projektiltempo+", "Erhöht das Projektiltempo um {value} %")
+        }
+        UpgradeType::MultiShot => ("Mehrfachschuss", "Feuert {value} zusätzliche Projektile ab"),
+        UpgradeType::PiercingShots => ("Durchschlag", "Projektile durchdringen {value} Gegner"),
+        UpgradeType::CritChance => (
+            "Krit-Chance+",
+            "Erhöht die Chance auf kritische Treffer um {value} %",
+        ),
+        UpgradeType::IncreaseMaxHealth => ("Max. Leben+", "Erhöht das maximale Leben um {value} %"),
+        UpgradeType::IncreaseMovementSpeed => ("Lauftempo+", "Erhöht das Lauftempo um {value} %"),
+        UpgradeType::HealthRegeneration => ("Regeneration", "Regeneriert {value} LP pro Sekunde"),
+        UpgradeType::Overshield => (
+            "Überschild",
+            "Lädt einen Schild auf, der {value} Schaden abfängt",
+        ),
+        UpgradeType::PickupRadius => ("Sammelradius+", "Erhöht den Sammelradius um {value} %"),
+        UpgradeType::Magnet => ("Magnet", "Sammelt nahe EP automatisch ein"),
+        UpgradeType::Armor => (
+            "Rüstung",
+            "Verringert erlittenen Schaden um {value} % (max. {cap} %)",
+        ),
+        UpgradeType::Luck => ("Glück", "Erhöht das Glück um {value} %"),
+        UpgradeType::SecondWind => (
+            "Zweiter Atem",
+            "Überlebe einmal pro Lauf einen tödlichen Treffer mit {value} % Leben",
+        ),
+        UpgradeType::SummonDrone => (
+            "Angriffsdrohne",
+            "Beschwört eine Drohne, die auf nahe Gegner schießt",
+        ),
+        UpgradeType::SummonWolf => (
+            "Wolf-Begleiter",
+            "Beschwört einen Wolf, der nahe Gegner jagt",
+        ),
+        UpgradeType::SummonDamage => (
+            "Begleiterschaden+",
+            "Begleiter verursachen {value} % mehr Schaden",
+        ),
+        UpgradeType::DamageAura => (
+            "Schadensaura",
+            "Ein Feld um dich verursacht {value} Schaden pro Sekunde",
+        ),
+        UpgradeType::AuraRadius => ("Auraradius+", "Erhöht den Auraradius um {value} %"),
+        UpgradeType::HomingShots => (
+            "Zielsuchende Schüsse",
+            "Geschosse lenken mit {value}° pro Sekunde zum nächsten Gegner",
+        ),
+        UpgradeType::ExplosiveShots => (
+            "Explosive Schüsse",
+            "Geschosse explodieren mit {value} % ihres Schadens",
+        ),
+    }
+}
+
+fn enemy_es(enemy: EnemyType) -> &'static str {
+    match enemy {
+        EnemyType::Goblin => "Trasgo",
+        EnemyType::Orc => "Orco",
+        EnemyType::Wolf => "Lobo",
+        EnemyType::Skeleton => "Esqueleto",
+        EnemyType::Zombie => "Zombi",
+        EnemyType::Demon => "Demonio",
+        EnemyType::Wraith => "Espectro",
+        EnemyType::Troll => "Trol",
+        EnemyType::Dragon => "Dragón",
+        EnemyType::Lich => "Liche",
+    }
+}
+
+fn enemy_pt(enemy: EnemyType) -> &'static str {
+    match enemy {
+        EnemyType::Goblin => "Goblin",
+        EnemyType::Orc => "Orc",
+        EnemyType::Wolf => "Lobo",
+        EnemyType::Skeleton => "Esqueleto",
+        EnemyType::Zombie => "Zumbi",
+        EnemyType::Demon => "Demônio",
+        EnemyType::Wraith => "Espectro",
+        EnemyType::Troll => "Troll",
+        EnemyType::Dragon => "Dragão",
+        EnemyType::Lich => "Lich",
+    }
+}
+
+fn enemy_de(enemy: EnemyType) -> &'static str {
+    match enemy {
+        EnemyType::Goblin => "Goblin",
+        EnemyType::Orc => "Ork",
+        EnemyType::Wolf => "Wolf",
+        EnemyType::Skeleton => "Skelett",
+        EnemyType::Zombie => "Zombie",
+        EnemyType::Demon => "Dämon",
+        EnemyType::Wraith => "Geist",
+        EnemyType::Troll => "Troll",
+        EnemyType::Dragon => "Drache",
+        EnemyType::Lich => "Lich",
+    }
+}
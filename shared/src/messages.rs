@@ -1,19 +1,178 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::types::{Enemy, Player, Position, Projectile, ScoreEntry};
-use crate::upgrades::UpgradeType;
+use crate::landmarks::Landmark;
+use crate::locale::{Locale, UpgradeText};
+use crate::minimap::MinimapMarker;
+use crate::shop::{ShopItem, ShopListing};
+use crate::summons::Summon;
+use crate::types::{
+    CombatEvent, Enemy, EnemyType, EntityEvent, OwnPlayerState, Pickup, PickupType, Position,
+    Projectile, RankChange, RemotePlayerView, RunStats, ScoreEntry,
+};
+use crate::upgrades::{Rarity, UpgradeType};
+use crate::vec2::Vec2;
+
+/// WebSocket subprotocol a client offers to receive large server messages
+/// as binary frames of raw-deflated JSON. Small messages stay plain text.
+pub const DEFLATE_PROTOCOL: &str = "deflate-json";
+
+/// Handshake header carrying a password-protected server's password, for
+/// clients that can set one; browsers send it in `Join` instead
+pub const PASSWORD_HEADER: &str = "x-server-password";
+
+/// Handshake header carrying a whitelisted account as `name:key`, like
+/// `PASSWORD_HEADER`; browsers send it in `Join` instead
+pub const ACCOUNT_HEADER: &str = "x-server-account";
+
+/// Bumped whenever a change to these messages would break existing
+/// clients; reported by `GET /info`
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// `GET /info`: what a launcher or server list shows before connecting
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub name: String,
+    pub motd: String,
+    pub players: usize,
+    pub max_players: Option<usize>, // None when there's no cap
+    pub mode: String,
+    pub uptime_secs: u64,
+    pub protocol_version: u32,
+}
+
+/// `GET /rooms`: a game on the server, joined with `/ws?room={token}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoomInfo {
+    pub token: String,
+    pub mode: String,
+    pub players: usize,
+    pub max_players: Option<usize>, // None when there's no cap
+    pub public: bool,               // listed by GET /rooms
+}
 
 /// Client → Server messages
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ClientMessage {
-    /// Join the game
-    Join,
-    /// Move player to a target position
-    Move { target: Position },
+    /// Join the game. `locale` picks the language of text the server
+    /// sends, such as upgrade offers; English when left out. A client
+    /// naming a `protocol_version` other than the server's is turned away
+    /// with `ErrorCode::ProtocolMismatch`, and one without the `password`
+    /// of a password-protected server (unless the handshake carried it in
+    /// `PASSWORD_HEADER`) with `ErrorCode::WrongPassword`. On a server with
+    /// a whitelist, `account` (`name:key`) has to be on it, or have come in
+    /// `ACCOUNT_HEADER`; otherwise it's `ErrorCode::NotWhitelisted`.
+    Join {
+        #[serde(default)]
+        locale: Locale,
+        #[serde(default)]
+        protocol_version: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        password: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        account: Option<String>,
+    },
+    /// Move player to a target position. `seq` numbers the client's
+    /// Move/Dash inputs; snapshots echo the last one applied as
+    /// `Player::last_processed_input`.
+    Move {
+        target: Position,
+        #[serde(default)]
+        seq: u32,
+    },
     /// Choose an upgrade after leveling up
     ChooseUpgrade { upgrade: UpgradeType },
+    /// Replace the current upgrade offer with a new one (limited per run)
+    RerollUpgrades,
+    /// Remove an upgrade from the rest of this run's offers (limited per run)
+    BanishUpgrade { upgrade: UpgradeType },
+    /// Start a new party (leaving any current one)
+    CreateParty,
+    /// Join an existing party by ID
+    JoinParty { party_id: Uuid },
+    /// Open a chest or pray at a shrine (must be standing next to it)
+    Interact { landmark_id: Uuid },
+    /// Dash a short distance in a direction, briefly invulnerable
+    Dash {
+        direction: Vec2,
+        #[serde(default)]
+        seq: u32,
+    },
+    /// How far from the player the client draws. Snapshots leave out
+    /// entities beyond it, and off-screen spawning keeps clear of it.
+    #[serde(alias = "Viewport")]
+    SetViewport { radius: f32 },
+    /// Spend gold at the merchant (must be in the safe zone)
+    BuyItem { item: ShopItem },
+    /// Wear an item from the inventory, swapping out whatever is in its slot
+    EquipItem { item_id: Uuid },
+    /// Drop a carried or worn item on the ground
+    DropItem { item_id: Uuid },
+    /// Channel a teleport to an attuned waypoint (must be standing at one)
+    TeleportTo { waypoint: Uuid },
+    /// Show an emote to nearby players and the party
+    Emote { kind: EmoteKind },
+    /// Mark a spot on the map for nearby players and the party
+    PingLocation { position: Position },
+}
+
+impl ClientMessage {
+    /// A `Join` from a client speaking this version of the protocol
+    pub fn join(locale: Locale) -> Self {
+        ClientMessage::Join {
+            locale,
+            protocol_version: Some(PROTOCOL_VERSION),
+            password: None,
+            account: None,
+        }
+    }
+
+    /// The same `Join`, carrying a server password
+    pub fn with_password(mut self, given: Option<String>) -> Self {
+        if let ClientMessage::Join { password, .. } = &mut self {
+            *password = given;
+        }
+        self
+    }
+
+    /// The same `Join`, carrying a whitelisted account as `name:key`
+    pub fn with_account(mut self, given: Option<String>) -> Self {
+        if let ClientMessage::Join { account, .. } = &mut self {
+            *account = given;
+        }
+        self
+    }
+
+    /// The message as the JSON text it's sent as
+    #[cfg(feature = "serde_json")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("client messages always serialize")
+    }
+}
+
+/// How every server message goes over the wire: the message's own fields,
+/// flattened, next to where it sits in the stream. `server_tick` is the
+/// simulation step it was produced in (a snapshot's own `tick`, for a
+/// snapshot), so events can be placed between the snapshots around them.
+/// `seq` numbers
+/// the messages on a connection from 1; a gap means some were lost, which
+/// only happens to snapshots sent unreliably. Snapshots over UDP carry
+/// their packet's sequence number instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope<M = ServerMessage> {
+    pub server_tick: u64,
+    pub seq: u64,
+    #[serde(flatten)]
+    pub message: M,
+}
+
+#[cfg(feature = "serde_json")]
+impl Envelope {
+    /// A server message as received, from its JSON text
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
 }
 
 /// Server → Client messages
@@ -22,29 +181,212 @@ pub enum ClientMessage {
 pub enum ServerMessage {
     /// Welcome message with assigned player ID
     Welcome { player_id: Uuid },
-    /// Full game state update
+    /// Full game state update. `game_time` advances in fixed steps of
+    /// `1 / tick_rate`; `server_time_ms` is the server's wall clock (Unix
+    /// millis) when step `tick` ran, for interpolation and skew detection.
+    /// Only the receiver's own player comes in full, as `own_player`;
+    /// `players` has everyone in the room, the receiver too, as others see
+    /// them.
     GameState {
-        players: Vec<Player>,
+        #[serde(default)]
+        own_player: Option<Box<OwnPlayerState>>,
+        players: Vec<RemotePlayerView>,
         enemies: Vec<Enemy>,
         projectiles: Vec<Projectile>,
+        pickups: Vec<Pickup>,
+        #[serde(default)]
+        summons: Vec<Summon>,
         game_time: f64,
+        #[serde(default)]
+        difficulty_tier: u32,
+        #[serde(default)]
+        tick: u64,
+        #[serde(default)]
+        server_time_ms: i64,
     },
-    /// Player death notification
+    /// Player death notification, with the run's stats for the end screen
     PlayerDied {
         player_id: Uuid,
         max_ring: u32,
         survival_time: f32,
         enemies_defeated: u32,
         score_recorded: bool,
+        #[serde(default)]
+        stats: RunStats,
+        #[serde(default)]
+        run_id: Option<Uuid>, // shareable as GET /runs/{id}
+    },
+    /// Top scores, sent to everyone whenever a run changes them, with
+    /// `changes` saying how
+    Scoreboard {
+        scores: Vec<ScoreEntry>,
+        #[serde(default)]
+        changes: Vec<RankChange>,
     },
-    /// Top scores
-    Scoreboard { scores: Vec<ScoreEntry> },
     /// Player leveled up - present upgrade choices
     LevelUp {
         player_id: Uuid,
         new_level: u32,
         upgrade_choices: Vec<UpgradeType>,
+        /// `upgrade_choices`' rarities, in the same order
+        #[serde(default)]
+        choice_rarities: Vec<Rarity>,
+        /// `upgrade_choices`' names and descriptions in the player's locale
+        #[serde(default)]
+        choice_text: Vec<UpgradeText>,
+    },
+    /// An unanswered level-up timed out; `auto_picked` is the upgrade applied
+    /// for the player, if the server is set to pick one
+    LevelUpExpired {
+        player_id: Uuid,
+        auto_picked: Option<UpgradeType>,
+    },
+    /// Player walked into the safe zone
+    SafeZoneEntered { player_id: Uuid },
+    /// Player left the safe zone (on foot or pushed out after camping)
+    SafeZoneExited { player_id: Uuid },
+    /// Battle royale: the closed area grew, or a newcomer needs the current zone.
+    /// Everything within `closed_radius` of the center damages players.
+    ZoneUpdate {
+        closed_radius: f32,
+        next_closed_radius: f32,
+        next_shrink_time: f64, // game time of the next closure
+    },
+    /// The match ended (timer, core destroyed, last player standing);
+    /// everyone respawns into a fresh match
+    MatchEnded { reason: String, winners: Vec<Uuid> },
+    /// The player got close to the edge of the world, which lies
+    /// `map_radius` from the center
+    BoundaryWarning { player_id: Uuid, map_radius: f32 },
+    /// Every damage instance from the last tick
+    CombatEvents { events: Vec<CombatEvent> },
+    /// Enemies, projectiles, pickups and summons that entered or left the
+    /// world within the receiver's view during the last tick
+    EntityEvents { events: Vec<EntityEvent> },
+    /// Every enemy, projectile, pickup and summon left the world at once,
+    /// as a new match starts; no `EntityDespawned` follows for them
+    WorldCleared,
+    /// An explosive shot went off, damaging everything within `radius`
+    Explosion {
+        owner_id: Uuid,
+        position: Position,
+        radius: f32,
+    },
+    /// A player walked over a pickup
+    PickupCollected {
+        player_id: Uuid,
+        pickup_type: PickupType,
+    },
+    /// Current landmark layout and state (on join, new match, or a change)
+    Landmarks { landmarks: Vec<Landmark> },
+    /// A player used a landmark; `reward` is a short description for the log
+    LandmarkUsed {
+        player_id: Uuid,
+        landmark_id: Uuid,
+        reward: String,
     },
-    /// Error message
-    Error { message: String },
+    /// What the merchant sells and for how much (on join)
+    Shop { listings: Vec<ShopListing> },
+    /// The player's purchase went through
+    ItemBought {
+        player_id: Uuid,
+        item: ShopItem,
+        gold_left: u32,
+    },
+    /// A player's teleport went through; they're now at `waypoint`
+    Teleported { player_id: Uuid, waypoint: Uuid },
+    /// The player moved or was hit while channeling a teleport
+    TeleportInterrupted { player_id: Uuid },
+    /// Coarse positions of everything worth a minimap dot, across the
+    /// whole world; sent every `minimap_interval` seconds
+    MinimapUpdate {
+        map_radius: f32,
+        players: Vec<MinimapMarker>, // alive only
+        bosses: Vec<MinimapMarker>,
+        landmarks: Vec<MinimapMarker>,
+    },
+    /// Party membership changed; sent to every member
+    PartyUpdate { party_id: Uuid, members: Vec<Uuid> },
+    /// The server takes snapshots and inputs over UDP on `port`; send a
+    /// `Hello` there with this token (16 hex digits) to switch over. See
+    /// `shared::udp`.
+    UdpOffer { port: u16, token: String },
+    /// No input for a while; without some, the player is removed in
+    /// `seconds_left` (`None` if idle players are only warned)
+    AfkWarning { seconds_left: Option<f32> },
+    /// Sent while a join waits for a free slot, whenever the queue moves.
+    /// `Welcome` follows once the player is in the game.
+    QueuePosition {
+        position: usize,
+        queue_length: usize,
+    },
+    /// The server removed this player (ban, admin action) or turned the
+    /// join away (server full); the connection closes after this message
+    Kicked { reason: String },
+    /// A message from the server's operators to everyone playing on it
+    /// (or, in a cluster, on any of its servers)
+    Announcement { text: String },
+    /// A line spoken by a boss during its scripted encounter
+    BossDialogue {
+        enemy_id: Uuid,
+        enemy_type: EnemyType,
+        text: String,
+    },
+    /// A player nearby (or in the receiver's party) emoted; the sender
+    /// gets their own too
+    Emoted { player_id: Uuid, kind: EmoteKind },
+    /// A player nearby (or in the receiver's party) pinged a spot
+    LocationPinged { player_id: Uuid, position: Position },
+    /// Something the client sent was refused; `code` says why without
+    /// having to parse `message`
+    Error {
+        #[serde(default)]
+        code: ErrorCode,
+        message: String,
+    },
+}
+
+/// The emotes a player can show, for talking without chat
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EmoteKind {
+    Wave,
+    Thanks,
+    Help,
+    FollowMe,
+    Attack,
+    Retreat,
+}
+
+/// What kind of refusal a `ServerMessage::Error` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ErrorCode {
+    /// The message was fine but the game said no: no offer open, not
+    /// enough gold, too far from a landmark
+    #[default]
+    Refused,
+    /// Not JSON, or not a message with that type and those fields
+    Malformed,
+    /// Over the server's size or nesting limit, so not read at all
+    TooLarge,
+    /// A field held a value no honest client sends, like NaN coordinates
+    InvalidField,
+    /// No upgrade offer is open, or the upgrade named isn't on it
+    InvalidUpgradeChoice,
+    /// The message needs a player in the game, and this connection hasn't
+    /// joined or is still waiting in the join queue
+    NotInGame,
+    /// Inputs arrived faster than the server applies them; the oldest
+    /// waiting ones were dropped. Also emotes and pings beyond the
+    /// server's allowance, which are dropped.
+    RateLimited,
+    /// The server and its join queue are full; `Kicked` follows
+    RoomFull,
+    /// The client speaks another protocol version; `Kicked` follows
+    ProtocolMismatch,
+    /// The server takes a password and the client didn't give the right
+    /// one; `Kicked` follows
+    WrongPassword,
+    /// The server only takes whitelisted accounts and the client didn't
+    /// give one; `Kicked` follows
+    NotWhitelisted,
 }
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::types::Position;
+
+/// Something to draw on the minimap, at whole-unit precision. Minimap
+/// updates cover the whole world, so they carry only ids and rounded
+/// positions rather than full entities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MinimapMarker {
+    pub id: Uuid,
+    pub x: i16,
+    pub y: i16,
+}
+
+impl MinimapMarker {
+    pub fn new(id: Uuid, position: Position) -> Self {
+        // Saturating casts; the map is far smaller than i16's range
+        Self {
+            id,
+            x: position.x.round() as i16,
+            y: position.y.round() as i16,
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        Position::new(self.x as f32, self.y as f32)
+    }
+}
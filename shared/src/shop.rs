@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+/// Something the merchant in the safe zone sells for gold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ShopItem {
+    Heal,        // restores some health
+    Reroll,      // one more upgrade reroll this run
+    DamageBoost, // double damage for a while, like the power-up
+    SpeedBoost,  // faster movement for a while, like the power-up
+}
+
+impl ShopItem {
+    pub fn name(&self) -> &str {
+        match self {
+            ShopItem::Heal => "Healing Draught",
+            ShopItem::Reroll => "Reroll Token",
+            ShopItem::DamageBoost => "Strength Tonic",
+            ShopItem::SpeedBoost => "Swiftness Tonic",
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        match self {
+            ShopItem::Heal => "Restore health",
+            ShopItem::Reroll => "One more upgrade reroll",
+            ShopItem::DamageBoost => "Double damage for a while",
+            ShopItem::SpeedBoost => "Move faster for a while",
+        }
+    }
+}
+
+/// An item the merchant stocks and its price
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShopListing {
+    pub item: ShopItem,
+    pub price: u32, // gold
+}
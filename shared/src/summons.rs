@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::types::{Position, Weapon};
+
+/// Companions a player can summon through upgrades
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SummonType {
+    AttackDrone, // hovers by its owner and shoots
+    WolfPet,     // runs down enemies near its owner and bites
+}
+
+/// Base combat stats of a companion, before `SummonDamage` upgrades
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SummonStats {
+    pub damage: f32,
+    pub attack_cooldown: f32, // seconds between attacks
+    pub attack_range: f32,
+    pub aggro_range: f32, // only enemies this close to the owner are engaged
+    pub speed: f32,
+}
+
+impl SummonType {
+    pub fn name(&self) -> &str {
+        match self {
+            SummonType::AttackDrone => "Attack Drone",
+            SummonType::WolfPet => "Wolf",
+        }
+    }
+
+    pub fn stats(&self) -> SummonStats {
+        match self {
+            SummonType::AttackDrone => SummonStats {
+                damage: 6.0,
+                attack_cooldown: 1.0,
+                attack_range: 300.0,
+                aggro_range: 350.0,
+                speed: 220.0,
+            },
+            SummonType::WolfPet => SummonStats {
+                damage: 12.0,
+                attack_cooldown: 0.8,
+                attack_range: 30.0,
+                aggro_range: 250.0,
+                speed: 170.0,
+            },
+        }
+    }
+
+    /// Drones shoot projectiles; wolves have to reach their target
+    pub fn is_melee(&self) -> bool {
+        matches!(self, SummonType::WolfPet)
+    }
+
+    /// The weapon their damage is credited to in run stats
+    pub fn weapon(&self) -> Weapon {
+        match self {
+            SummonType::AttackDrone => Weapon::AttackDrone,
+            SummonType::WolfPet => Weapon::WolfPet,
+        }
+    }
+}
+
+/// A server-controlled companion that follows its owner and fights for them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Summon {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub summon_type: SummonType,
+    pub position: Position,
+    pub last_attack_time: f64, // game time
+}
+
+impl Summon {
+    pub fn new(owner_id: Uuid, summon_type: SummonType, position: Position) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            owner_id,
+            summon_type,
+            position,
+            last_attack_time: 0.0,
+        }
+    }
+
+    pub fn can_attack(&self, current_time: f64) -> bool {
+        current_time - self.last_attack_time >= self.summon_type.stats().attack_cooldown as f64
+    }
+
+    /// Where it waits when there's nothing to fight: a spot beside the
+    /// owner, spread by ID so companions don't stack
+    pub fn follow_point(&self, owner: &Position) -> Position {
+        const FOLLOW_DISTANCE: f32 = 40.0;
+        let angle = (self.id.as_u128() % 360) as f32 * std::f32::consts::PI / 180.0;
+        Position::new(
+            owner.x + angle.cos() * FOLLOW_DISTANCE,
+            owner.y + angle.sin() * FOLLOW_DISTANCE,
+        )
+    }
+}
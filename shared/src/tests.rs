@@ -1,4 +1,11 @@
-use crate::types::{EnemyType, Position};
+use crate::locale::{Locale, UpgradeText};
+use crate::messages::{ClientMessage, Envelope, ServerMessage};
+use crate::types::{cmp_distance, EnemyType, Player, Position, Projectile};
+use crate::udp::{self, AckState, PacketHeader, PacketKind};
+use crate::upgrades::{PlayerUpgrades, Rarity, UpgradeCatalog, UpgradeTable, UpgradeType};
+use crate::vec2::Vec2;
+use proptest::prelude::*;
+use uuid::Uuid;
 
 #[test]
 fn test_position_distance() {
@@ -47,6 +54,18 @@ fn test_position_move_towards() {
     assert!(pos.y.abs() < 0.001);
 }
 
+#[test]
+fn test_position_clamp_to_radius() {
+    let mut outside = Position::new(300.0, 400.0);
+    outside.clamp_to_radius(100.0);
+    assert!((outside.distance_from_center() - 100.0).abs() < 0.001);
+    assert!((outside.x - 60.0).abs() < 0.001);
+
+    let mut inside = Position::new(30.0, 40.0);
+    inside.clamp_to_radius(100.0);
+    assert_eq!(inside, Position::new(30.0, 40.0));
+}
+
 #[test]
 fn test_enemy_stats_scaling() {
     let goblin = EnemyType::Goblin;
@@ -73,3 +92,321 @@ fn test_all_enemy_types_exist() {
         assert!(stats.movement_speed > 0.0);
     }
 }
+
+#[test]
+fn test_player_heal_caps_at_max() {
+    let mut player = Player::new(Uuid::new_v4());
+    player.take_damage(30.0);
+    player.heal(10.0);
+    assert!((player.health - 80.0).abs() < 0.001);
+
+    player.heal(1000.0);
+    assert!((player.health - player.max_health).abs() < 0.001);
+}
+
+#[test]
+fn test_grant_xp_crosses_several_levels() {
+    let mut player = Player::new(Uuid::new_v4());
+    assert_eq!(player.grant_xp(1), 0);
+
+    let start_level = player.level;
+    let first = player.xp_to_next_level;
+    let second = (first as f32 * 1.2) as u32;
+    let levels = player.grant_xp(first + second + 5);
+
+    assert_eq!(levels, 2);
+    assert_eq!(player.level, start_level + 2);
+    assert_eq!(player.current_xp, 6);
+}
+
+#[test]
+fn test_homing_shots_turn_toward_the_target_at_their_turn_rate() {
+    let right = Vec2::new(1.0, 0.0);
+    let mut shot = Projectile::new(Uuid::new_v4(), Position::default(), right, 100.0, 5.0, 3.0);
+    let above = Position::new(0.0, 1000.0);
+    shot.update(0.1, |_| Some(above));
+    assert_eq!(shot.velocity, Vec2::new(100.0, 0.0), "no turn rate");
+
+    shot.turn_rate = std::f32::consts::PI; // 18 degrees a tenth of a second
+    shot.update(0.1, |_| Some(above));
+    let heading = shot.velocity.angle().to_degrees();
+    assert!((heading - 18.0).abs() < 0.01, "{heading}");
+    assert!((shot.velocity.length() - 100.0).abs() < 0.01);
+
+    for _ in 0..20 {
+        shot.update(0.1, |from| Some(Position::new(from.x, from.y + 10.0)));
+    }
+    let heading = shot.velocity.angle().to_degrees();
+    assert!((heading - 90.0).abs() < 0.01, "{heading}");
+
+    let velocity = shot.velocity;
+    shot.update(0.1, |_| None);
+    assert_eq!(shot.velocity, velocity, "flies straight without a target");
+}
+
+#[test]
+fn test_cmp_distance_sorts_nan_last() {
+    let distances = [f32::NAN, 3.0, f32::INFINITY, -f32::NAN, 1.0];
+    let closest = distances.iter().min_by(|a, b| cmp_distance(a, b));
+    assert_eq!(closest, Some(&1.0));
+}
+
+/// Any f32, weighted toward the values that break naive float code
+fn any_coordinate() -> impl Strategy<Value = f32> {
+    prop_oneof![
+        Just(f32::NAN),
+        Just(f32::INFINITY),
+        Just(f32::NEG_INFINITY),
+        -5000.0f32..5000.0,
+        any::<f32>(),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn prop_move_towards_keeps_position_finite(
+        x in any_coordinate(),
+        y in any_coordinate(),
+        speed in 0.0f32..1000.0,
+    ) {
+        let mut pos = Position::new(10.0, -20.0);
+        pos.move_towards(&Position::new(x, y), speed, 0.05);
+        prop_assert!(pos.is_finite());
+    }
+
+    #[test]
+    fn prop_cmp_distance_never_picks_nan_over_a_real_distance(
+        distances in prop::collection::vec(any_coordinate(), 1..20),
+    ) {
+        let closest = *distances.iter().min_by(|a, b| cmp_distance(a, b)).unwrap();
+        if distances.iter().any(|d| !d.is_nan()) {
+            prop_assert!(!closest.is_nan());
+        }
+    }
+}
+
+#[test]
+fn test_udp_header_round_trip() {
+    let header = PacketHeader {
+        kind: PacketKind::Input,
+        sequence: 65535,
+        ack: 7,
+        ack_bits: 0b101,
+    };
+    let packet = header.encode(b"payload");
+
+    assert_eq!(
+        PacketHeader::decode(&packet),
+        Some((header, &b"payload"[..]))
+    );
+    assert_eq!(PacketHeader::decode(&packet[..5]), None);
+    assert_eq!(PacketHeader::decode(b"not one of ours"), None);
+
+    let id = Uuid::new_v4();
+    assert_eq!(
+        udp::decode_hello(&udp::encode_hello(id, 42)),
+        Some((id, 42))
+    );
+}
+
+#[test]
+fn test_udp_acks_across_wraparound() {
+    let mut acks = AckState::default();
+    for sequence in [65534, 65535, 1] {
+        assert!(acks.receive(sequence));
+    }
+    // Late arrivals are acked but not newest; duplicates change nothing
+    assert!(!acks.receive(0));
+    assert!(!acks.receive(1));
+
+    let header = PacketHeader {
+        kind: PacketKind::Snapshot,
+        sequence: 0,
+        ack: acks.ack,
+        ack_bits: acks.ack_bits,
+    };
+    for sequence in [65534, 65535, 0, 1] {
+        assert!(header.acks(sequence), "{} not acked", sequence);
+    }
+    assert!(!header.acks(65533));
+    assert!(udp::sequence_greater_than(1, 65535));
+}
+
+#[test]
+fn test_locale_tags_fall_back_to_english() {
+    assert_eq!(Locale::from_tag("pt-BR"), Locale::Pt);
+    assert_eq!(Locale::from_tag("de_DE.UTF-8"), Locale::De);
+    assert_eq!(Locale::from_tag("ES"), Locale::Es);
+    assert_eq!(Locale::from_tag("ja-JP"), Locale::En);
+
+    let join: ClientMessage = serde_json::from_str(r#"{"type": "Join"}"#).unwrap();
+    assert!(matches!(
+        join,
+        ClientMessage::Join {
+            locale: Locale::En,
+            ..
+        }
+    ));
+    let join: ClientMessage =
+        serde_json::from_str(r#"{"type": "Join", "locale": "es-MX"}"#).unwrap();
+    assert!(matches!(
+        join,
+        ClientMessage::Join {
+            locale: Locale::Es,
+            ..
+        }
+    ));
+    assert_eq!(serde_json::to_string(&Locale::Pt).unwrap(), r#""pt""#);
+
+    let text = Locale::De.upgrade_text(UpgradeType::Armor, &UpgradeCatalog::default());
+    assert_eq!(text.name, "Rüstung");
+    let shown = UpgradeText::for_choices(&[UpgradeType::Armor], Vec::new());
+    assert_eq!(shown[0].name, UpgradeType::Armor.name());
+}
+
+#[test]
+fn test_upgrade_descriptions_quote_the_catalog() {
+    let mut catalog = UpgradeCatalog::default();
+    let upgrades = PlayerUpgrades {
+        damage_level: 2,
+        armor_level: 10,
+        ..Default::default()
+    };
+    assert!((upgrades.damage_multiplier(&catalog) - 1.4).abs() < 0.001);
+    assert!((upgrades.damage_reduction(&catalog) - 0.75).abs() < 0.001);
+    let armor = Locale::En.upgrade_text(UpgradeType::Armor, &catalog);
+    assert_eq!(armor.description, "Reduce damage taken by 10% (max 75%)");
+    let multi_shot = Locale::Es.upgrade_text(UpgradeType::MultiShot, &catalog);
+    assert_eq!(multi_shot.description, "Dispara 2 proyectiles adicionales");
+    assert!(catalog.problems().is_empty());
+
+    // Every percentage in the text moves with the number behind it
+    catalog.upgrades[0].per_level = 0.35;
+    assert_eq!(catalog.upgrades[0].upgrade, UpgradeType::IncreaseDamage);
+    assert!((upgrades.damage_multiplier(&catalog) - 1.7).abs() < 0.001);
+    for locale in [Locale::En, Locale::Es, Locale::Pt, Locale::De] {
+        let text = locale.upgrade_text(UpgradeType::IncreaseDamage, &catalog);
+        assert!(text.description.contains("35"), "{}", text.description);
+        assert!(!text.description.contains('{'), "{}", text.description);
+    }
+}
+
+#[test]
+fn test_upgrade_table_offers_distinct_choices_and_luck_favours_rarity() {
+    use rand::SeedableRng;
+
+    let table = UpgradeTable::default();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+    let exclude = [UpgradeType::Armor, UpgradeType::Luck];
+    for _ in 0..200 {
+        let choices = table.roll(&exclude, 0, 3, &mut rng);
+        assert_eq!(choices.len(), 3);
+        assert!(choices.iter().all(|c| !exclude.contains(&c.upgrade)));
+        assert_ne!(choices[0].upgrade, choices[1].upgrade);
+        assert_ne!(choices[1].upgrade, choices[2].upgrade);
+        assert_ne!(choices[0].upgrade, choices[2].upgrade);
+        for c in &choices {
+            if c.upgrade == UpgradeType::Magnet {
+                assert_eq!(c.rarity, Rarity::Common);
+            }
+        }
+    }
+
+    // Only Magnet left: it's the one offer, and never rarer than allowed
+    let all_but_magnet: Vec<UpgradeType> = UpgradeType::ALL
+        .into_iter()
+        .filter(|u| *u != UpgradeType::Magnet)
+        .collect();
+    let choices = table.roll(&all_but_magnet, 50, 3, &mut rng);
+    assert_eq!(choices.len(), 1);
+    assert_eq!(choices[0].rarity, Rarity::Common);
+
+    let rare_share = |luck| {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+        let rolls = (0..2000).flat_map(|_| table.roll(&[], luck, 1, &mut rng));
+        rolls.filter(|c| c.rarity != Rarity::Common).count()
+    };
+    assert!(rare_share(10) > rare_share(0));
+    assert_eq!(table.levels(Rarity::Epic), 3);
+}
+
+#[test]
+fn test_envelopes_flatten_around_the_message() {
+    let message = ServerMessage::Kicked {
+        reason: "Server is full".to_string(),
+    };
+    let envelope = Envelope {
+        server_tick: 7,
+        seq: 3,
+        message: &message,
+    };
+    let json = serde_json::to_string(&envelope).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["type"], "Kicked");
+    assert_eq!(value["server_tick"], 7);
+
+    let back: Envelope = serde_json::from_str(&json).unwrap();
+    assert_eq!((back.server_tick, back.seq), (7, 3));
+    assert!(matches!(back.message, ServerMessage::Kicked { reason } if reason == "Server is full"));
+    // Clients that predate envelopes still read the message itself
+    let bare: ServerMessage = serde_json::from_str(&json).unwrap();
+    assert!(matches!(bare, ServerMessage::Kicked { .. }));
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn rfc3339_timestamps_read_and_write_like_chrono() {
+    use crate::timestamp::rfc3339;
+    use chrono::{DateTime, Utc};
+
+    for millis in [
+        0,
+        951_782_400_123,
+        1_709_296_205_250,
+        -86_400_001,
+        4_102_444_799_999,
+    ] {
+        let time = DateTime::<Utc>::from_timestamp_millis(millis).unwrap();
+        let seconds = millis as f64 / 1000.0;
+        // What a chrono build sends, the fallback reads, and vice versa
+        let from_chrono = serde_json::to_string(&time).unwrap();
+        let read = rfc3339::parse(from_chrono.trim_matches('"')).unwrap();
+        assert!(
+            (read - seconds).abs() < 1e-6,
+            "{} read as {}",
+            from_chrono,
+            read
+        );
+        let written = rfc3339::format(seconds);
+        assert_eq!(written.parse::<DateTime<Utc>>().unwrap(), time);
+    }
+    assert_eq!(rfc3339::parse("1970-01-01T02:00:00+02:00"), Some(0.0));
+    assert_eq!(rfc3339::parse("2024-13-01T00:00:00Z"), None);
+    assert_eq!(rfc3339::parse("yesterday"), None);
+}
+
+#[test]
+fn vec2_arithmetic_between_points_and_vectors() {
+    let (from, to) = (Position::new(1.0, 2.0), Position::new(4.0, 6.0));
+    let offset = to - from;
+    assert_eq!(offset, Vec2::new(3.0, 4.0));
+    assert_eq!(offset.length(), 5.0);
+    assert_eq!(from + offset, to);
+    assert_eq!(to - offset, from);
+    assert_eq!(offset * 2.0 - offset, offset);
+    assert_eq!(-offset / 2.0, Vec2::new(-1.5, -2.0));
+
+    assert!((offset.normalize().length() - 1.0).abs() < 1e-6);
+    assert_eq!(Vec2::ZERO.normalize(), Vec2::ZERO, "no direction to keep");
+    let turned = Vec2::new(2.0, 0.0).rotate(std::f32::consts::FRAC_PI_2);
+    assert!((turned - Vec2::new(0.0, 2.0)).length() < 1e-6, "{turned:?}");
+    assert!((Vec2::from_angle(turned.angle()) - Vec2::new(0.0, 1.0)).length() < 1e-6);
+    assert_eq!(Vec2::ZERO.lerp(offset, 0.5), Vec2::new(1.5, 2.0));
+
+    // Same shape on the wire as a position
+    let json = serde_json::to_string(&offset).unwrap();
+    assert_eq!(
+        json,
+        serde_json::to_string(&Position::new(3.0, 4.0)).unwrap()
+    );
+}
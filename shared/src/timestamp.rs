@@ -0,0 +1,155 @@
+//! Wall-clock timestamps, as used for when a run started or a score was
+//! set. With the `chrono` feature (on by default) a `Timestamp` is
+//! chrono's `DateTime<Utc>`; without it, plain f64 Unix seconds, for
+//! builds like the web client that would rather not carry chrono. Both go
+//! over the wire as RFC 3339 strings, so either side can be built either
+//! way.
+
+#[cfg(feature = "chrono")]
+pub type Timestamp = chrono::DateTime<chrono::Utc>;
+
+#[cfg(not(feature = "chrono"))]
+pub type Timestamp = f64;
+
+/// The current time
+#[cfg(feature = "chrono")]
+pub fn now() -> Timestamp {
+    chrono::Utc::now()
+}
+
+/// The current time. In the browser that takes the `js` feature, since
+/// wasm32 has no clock of its own.
+#[cfg(not(feature = "chrono"))]
+pub fn now() -> Timestamp {
+    #[cfg(all(target_arch = "wasm32", feature = "js"))]
+    return js_sys::Date::now() / 1000.0;
+    #[cfg(not(all(target_arch = "wasm32", feature = "js")))]
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0.0, |since| since.as_secs_f64())
+}
+
+/// Serde for f64 Unix seconds in chrono's RFC 3339 form, e.g.
+/// `"2024-03-01T12:30:05.250Z"`; the fallback `Timestamp` goes through
+/// this. Offsets other than `Z` are accepted when reading.
+pub mod rfc3339 {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    const SECONDS_PER_DAY: i64 = 86_400;
+
+    pub fn serialize<S: Serializer>(seconds: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format(*seconds))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        let text = <&str>::deserialize(deserializer)?;
+        parse(text).ok_or_else(|| de::Error::custom(format!("not an RFC 3339 time: {}", text)))
+    }
+
+    /// Unix seconds as UTC, to the millisecond
+    pub fn format(seconds: f64) -> String {
+        let millis = (seconds * 1000.0).round() as i64;
+        let secs = millis.div_euclid(1000);
+        let (year, month, day) = civil_from_days(secs.div_euclid(SECONDS_PER_DAY));
+        let time = secs.rem_euclid(SECONDS_PER_DAY);
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            year,
+            month,
+            day,
+            time / 3600,
+            time / 60 % 60,
+            time % 60,
+            millis.rem_euclid(1000)
+        )
+    }
+
+    /// `YYYY-MM-DDTHH:MM:SS[.fraction](Z|±HH:MM)` as Unix seconds
+    pub fn parse(text: &str) -> Option<f64> {
+        let field = |range: std::ops::Range<usize>| -> Option<i64> {
+            let digits = text.get(range)?;
+            digits
+                .bytes()
+                .all(|b| b.is_ascii_digit())
+                .then(|| digits.parse().ok())?
+        };
+        let bytes = text.as_bytes();
+        let separators = [(4, b'-'), (7, b'-'), (13, b':'), (16, b':')];
+        if separators.iter().any(|&(at, c)| bytes.get(at) != Some(&c))
+            || !matches!(bytes.get(10), Some(b'T' | b't' | b' '))
+        {
+            return None;
+        }
+        let (year, month, day) = (field(0..4)?, field(5..7)?, field(8..10)?);
+        let (hour, minute, second) = (field(11..13)?, field(14..16)?, field(17..19)?);
+        if !(1..=12).contains(&month)
+            || !(1..=31).contains(&day)
+            || hour > 23
+            || minute > 59
+            || second > 60
+        {
+            return None;
+        }
+
+        let mut rest = &text[19..];
+        let mut fraction = 0.0;
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            let len = after_dot.bytes().take_while(u8::is_ascii_digit).count();
+            if len == 0 {
+                return None;
+            }
+            fraction = format!("0.{}", &after_dot[..len]).parse().ok()?;
+            rest = &after_dot[len..];
+        }
+        let offset = match rest {
+            "Z" | "z" => 0,
+            _ => {
+                let sign = match rest.as_bytes().first()? {
+                    b'+' => 1,
+                    b'-' => -1,
+                    _ => return None,
+                };
+                if rest.len() != 6 || rest.as_bytes()[3] != b':' {
+                    return None;
+                }
+                let hours: i64 = rest[1..3].parse().ok()?;
+                let minutes: i64 = rest[4..6].parse().ok()?;
+                sign * (hours * 3600 + minutes * 60)
+            }
+        };
+
+        let days = days_from_civil(year, month, day);
+        let secs = days * SECONDS_PER_DAY + hour * 3600 + minute * 60 + second - offset;
+        Some(secs as f64 + fraction)
+    }
+
+    /// Days since 1970-01-01 in the proleptic Gregorian calendar, after
+    /// Howard Hinnant's `days_from_civil`
+    fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    /// The inverse of `days_from_civil`
+    fn civil_from_days(days: i64) -> (i64, i64, i64) {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153; // March is 0
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        (year, month, day)
+    }
+}
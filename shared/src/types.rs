@@ -1,9 +1,18 @@
+use crate::enemies::RingScaling;
+use crate::items::{Equipment, EquipmentSlot, StatModifiers};
+use crate::landmarks::{Landmark, LandmarkType, Teleport};
+use crate::timestamp::{self, Timestamp};
+use crate::upgrades::{PlayerUpgrades, UpgradeType};
+use crate::vec2::Vec2;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use uuid::Uuid;
-use crate::upgrades::PlayerUpgrades;
 
-/// 2D position in game world
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// 2D position in game world: a point. Offsets, directions and velocities
+/// are `Vec2`s (see `vec2`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
 pub struct Position {
     pub x: f32,
     pub y: f32,
@@ -15,13 +24,25 @@ impl Position {
     }
 
     pub fn distance_to(&self, other: &Position) -> f32 {
-        let dx = self.x - other.x;
-        let dy = self.y - other.y;
-        (dx * dx + dy * dy).sqrt()
+        (*self - *other).length()
+    }
+
+    /// False if either coordinate is NaN or infinite
+    pub fn is_finite(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite()
     }
 
     pub fn distance_from_center(&self) -> f32 {
-        (self.x * self.x + self.y * self.y).sqrt()
+        self.distance_to(&Position::default())
+    }
+
+    /// Pull the position back inside a circle of `radius` around the center
+    pub fn clamp_to_radius(&mut self, radius: f32) {
+        let distance = self.distance_from_center();
+        if distance > radius {
+            self.x *= radius / distance;
+            self.y *= radius / distance;
+        }
     }
 
     /// Returns which ring (1-10+) this position is in
@@ -31,15 +52,29 @@ impl Position {
     }
 
     pub fn move_towards(&mut self, target: &Position, speed: f32, delta_time: f32) {
-        let distance = self.distance_to(target);
+        // A non-finite target would poison this position with NaN
+        if !target.is_finite() {
+            return;
+        }
+        let offset = *target - *self;
+        let distance = offset.length();
         if distance > 0.01 {
-            let ratio = (speed * delta_time / distance).min(1.0);
-            self.x += (target.x - self.x) * ratio;
-            self.y += (target.y - self.y) * ratio;
+            *self += offset * (speed * delta_time / distance).min(1.0);
         }
     }
 }
 
+/// Ordering for distances in `min_by`/`max_by` that can't panic: NaN
+/// (from a corrupted position) sorts after every real distance.
+pub fn cmp_distance(a: &f32, b: &f32) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => a.total_cmp(b),
+    }
+}
+
 /// Player entity
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Player {
@@ -47,39 +82,135 @@ pub struct Player {
     pub position: Position,
     pub health: f32,
     pub max_health: f32,
+    #[serde(default)]
+    pub shield: f32, // absorbs damage before health, wearing off over time
+    #[serde(default)]
+    pub max_shield: f32, // the most `shield` has been since it last ran out, for the bar
+    #[serde(default)]
+    pub second_wind: f32, // share of max health a lethal hit leaves instead; 0 if none
+    #[serde(default)]
+    pub second_wind_used: bool, // once per run
+    #[serde(skip)]
+    pub revived: bool, // server-only: a second wind just went off, invulnerable until timed
     pub damage: f32,
     pub attack_speed: f32, // attacks per second
     pub movement_speed: f32,
+    pub crit_chance: f32,      // 0.0-1.0
+    pub crit_multiplier: f32,  // damage multiplier on a crit
     pub last_attack_time: f64, // game time
     pub max_ring_reached: u32,
     pub enemies_defeated: u32,
-    pub spawn_time: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub bosses_defeated: u32, // of enemies_defeated, those that count as bosses
+    pub players_defeated: u32, // PvP kills
+    #[cfg_attr(not(feature = "chrono"), serde(with = "crate::timestamp::rfc3339"))]
+    pub spawn_time: Timestamp,
     // XP and Leveling
     pub level: u32,
     pub current_xp: u32,
     pub xp_to_next_level: u32,
     pub upgrades: PlayerUpgrades,
+    pub rerolls_left: u32,
+    pub banishes_left: u32,
+    pub banished: Vec<UpgradeType>, // never offered again this run
+    // Safe zone
+    pub in_safe_zone: bool,
+    pub safe_zone_time: f32, // seconds of camping accrued, decays outside
+    #[serde(skip)]
+    pub near_boundary: bool, // server-only: inside the map edge warning band
+    pub party_id: Option<Uuid>,
+    // Pickups
+    pub gold: u32,
+    pub damage_boost_until: f64, // game time the double damage power-up wears off
+    pub speed_boost_until: f64,  // game time the speed power-up wears off
+    // Dash
+    pub dash_ready_at: f64,   // game time the dash is off cooldown
+    pub dashing_until: f64,   // game time the current dash ends
+    pub dash_direction: Vec2, // unit vector of the current dash
+    pub invulnerable_until: f64,
+    #[serde(skip)]
+    pub view_radius: f32, // server-only: reported by the client, for culling and off-screen spawns  // i-frames granted by the dash
+    #[serde(default)]
+    pub stats: RunStats,
+    #[serde(skip)]
+    pub death_reported: bool, // server-only: `PlayerDied` already sent for this run
+    #[serde(default)]
+    pub equipment: Vec<Equipment>, // worn, at most one per slot
+    #[serde(default)]
+    pub inventory: Vec<Equipment>, // carried, up to the configured inventory size
+    #[serde(default)]
+    pub waypoints: Vec<Uuid>, // attuned this run, besides the safe zone's
+    #[serde(default)]
+    pub teleport: Option<Teleport>,
+    #[serde(skip)]
+    pub last_hurt_at: Option<f64>, // server-only: game time of the latest hit taken
+    #[serde(default)]
+    pub velocity: Vec2, // units/sec over the last tick, for client extrapolation
+    #[serde(default)]
+    pub facing: f32, // radians; the direction of the latest movement
+    #[serde(skip)]
+    pub last_position: Option<Position>, // server-only: where the last tick left the player
+    #[serde(default)]
+    pub last_processed_input: u32, // `seq` of the latest Move/Dash applied; `position` is its result
 }
 
 impl Player {
+    /// Collision radius in world units
+    pub const RADIUS: f32 = 8.0;
+
     pub fn new(id: Uuid) -> Self {
         Self {
             id,
             position: Position::new(0.0, 0.0), // spawn at center
             health: 100.0,
             max_health: 100.0,
+            shield: 0.0,
+            max_shield: 0.0,
+            second_wind: 0.0,
+            second_wind_used: false,
+            revived: false,
             damage: 10.0,
             attack_speed: 1.0,
             // Faster base speed to reduce sluggish feel; server-authoritative.
             movement_speed: 120.0,
+            crit_chance: 0.05,
+            crit_multiplier: 1.5,
             last_attack_time: 0.0,
             max_ring_reached: 1,
             enemies_defeated: 0,
-            spawn_time: chrono::Utc::now(),
+            bosses_defeated: 0,
+            players_defeated: 0,
+            spawn_time: timestamp::now(),
             level: 1,
             current_xp: 0,
             xp_to_next_level: 100, // First level requires 100 XP
             upgrades: PlayerUpgrades::default(),
+            rerolls_left: 0,
+            banishes_left: 0,
+            banished: Vec::new(),
+            in_safe_zone: true,
+            near_boundary: false,
+            safe_zone_time: 0.0,
+            party_id: None,
+            gold: 0,
+            damage_boost_until: 0.0,
+            speed_boost_until: 0.0,
+            dash_ready_at: 0.0,
+            dashing_until: 0.0,
+            dash_direction: Vec2::ZERO,
+            invulnerable_until: 0.0,
+            view_radius: 0.0,
+            stats: RunStats::default(),
+            death_reported: false,
+            equipment: Vec::new(),
+            inventory: Vec::new(),
+            waypoints: Vec::new(),
+            teleport: None,
+            last_hurt_at: None,
+            velocity: Vec2::ZERO,
+            facing: 0.0,
+            last_position: None,
+            last_processed_input: 0,
         }
     }
 
@@ -91,23 +222,121 @@ impl Player {
         self.position.distance_from_center() <= safe_zone_radius
     }
 
+    /// Lose HP, with the shield absorbing what it can first. A lethal hit
+    /// spends an unused second wind instead of killing.
     pub fn take_damage(&mut self, amount: f32) {
+        let absorbed = amount.min(self.shield);
+        self.drain_shield(absorbed);
+        let amount = amount - absorbed;
+        self.stats.damage_taken += amount.min(self.health);
         self.health = (self.health - amount).max(0.0);
+        if !self.is_alive() && self.second_wind > 0.0 && !self.second_wind_used {
+            self.health = self.max_health * self.second_wind;
+            self.second_wind_used = true;
+            self.revived = true;
+        }
+    }
+
+    /// Top the shield up to `amount`; a stronger shield isn't weakened
+    pub fn raise_shield(&mut self, amount: f32) {
+        self.shield = self.shield.max(amount);
+        self.max_shield = self.max_shield.max(self.shield);
+    }
+
+    /// Wear the shield down by `amount`; once it's gone the bar resets
+    pub fn drain_shield(&mut self, amount: f32) {
+        self.shield = (self.shield - amount).max(0.0);
+        if self.shield <= 0.0 {
+            self.max_shield = 0.0;
+        }
+    }
+
+    /// Whether the player can teleport to this waypoint. Everyone starts
+    /// attuned to the one in the safe zone.
+    pub fn is_attuned(&self, waypoint: &Landmark) -> bool {
+        waypoint.landmark_type == LandmarkType::Waypoint
+            && (waypoint.ring == 0 || self.waypoints.contains(&waypoint.id))
+    }
+
+    pub fn heal(&mut self, amount: f32) {
+        self.health = (self.health + amount).min(self.max_health);
     }
 
     pub fn can_attack(&self, current_time: f64) -> bool {
         current_time - self.last_attack_time >= 1.0 / self.attack_speed as f64
     }
 
-    /// Grant XP to player and check for level up. Returns true if leveled up.
-    pub fn grant_xp(&mut self, amount: u32) -> bool {
-        self.current_xp += amount;
-        if self.current_xp >= self.xp_to_next_level {
-            self.level_up();
-            true
+    pub fn is_dashing(&self, current_time: f64) -> bool {
+        current_time < self.dashing_until
+    }
+
+    pub fn is_invulnerable(&self, current_time: f64) -> bool {
+        self.revived || current_time < self.invulnerable_until
+    }
+
+    /// Damage including any active power-up
+    pub fn effective_damage(&self, current_time: f64) -> f32 {
+        let damage = self.damage + self.gear().damage;
+        if current_time < self.damage_boost_until {
+            damage * 2.0
         } else {
-            false
+            damage
+        }
+    }
+
+    /// Movement speed including any active power-up
+    pub fn effective_speed(&self, current_time: f64) -> f32 {
+        let speed = self.movement_speed + self.gear().movement_speed;
+        if current_time < self.speed_boost_until {
+            speed * 1.5
+        } else {
+            speed
+        }
+    }
+
+    /// Crit chance including worn gear
+    pub fn effective_crit_chance(&self) -> f32 {
+        self.crit_chance + self.gear().crit_chance
+    }
+
+    /// Combined bonuses of everything worn
+    pub fn gear(&self) -> StatModifiers {
+        let mut total = StatModifiers::default();
+        for item in &self.equipment {
+            total.add(&item.modifiers);
+        }
+        total
+    }
+
+    /// Wear `item`, returning whatever was in its slot. Max health bonuses
+    /// apply directly, without healing.
+    pub fn equip(&mut self, item: Equipment) -> Option<Equipment> {
+        let replaced = self.unequip(item.slot);
+        self.max_health += item.modifiers.max_health;
+        self.equipment.push(item);
+        replaced
+    }
+
+    /// Take off whatever is in `slot`
+    pub fn unequip(&mut self, slot: EquipmentSlot) -> Option<Equipment> {
+        let index = self.equipment.iter().position(|i| i.slot == slot)?;
+        let item = self.equipment.remove(index);
+        self.max_health -= item.modifiers.max_health;
+        self.health = self.health.min(self.max_health);
+        Some(item)
+    }
+
+    /// Grant XP to player, leveling up as many times as it covers.
+    /// Returns the number of levels gained.
+    pub fn grant_xp(&mut self, amount: u32) -> u32 {
+        self.current_xp = self.current_xp.saturating_add(amount);
+        self.stats.xp_collected = self.stats.xp_collected.saturating_add(amount);
+        let mut levels = 0;
+        while self.current_xp >= self.xp_to_next_level {
+            self.level_up();
+            levels += 1;
         }
+        levels
     }
 
     /// Level up the player
@@ -119,6 +348,90 @@ impl Player {
     }
 }
 
+/// A player as its own client sees it: everything
+pub type OwnPlayerState = Player;
+
+/// A player as every other client sees it: enough to draw, nothing of the
+/// build behind it (upgrades, gear, XP, gold)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemotePlayerView {
+    pub id: Uuid,
+    pub position: Position,
+    #[serde(default)]
+    pub velocity: Vec2,
+    #[serde(default)]
+    pub facing: f32,
+    pub health_ratio: f32, // 0.0-1.0 of max health
+    #[serde(default)]
+    pub shield_ratio: f32, // 0.0-1.0 of the shield bar; 0 without a shield
+    pub level: u32,
+    pub party_id: Option<Uuid>,
+    #[serde(default)]
+    pub dashing: bool,
+}
+
+impl RemotePlayerView {
+    /// What others see of `player` at game time `now`
+    pub fn of(player: &Player, now: f64) -> Self {
+        let ratio = |value: f32, max: f32| {
+            if max > 0.0 {
+                (value / max).clamp(0.0, 1.0)
+            } else {
+                0.0
+            }
+        };
+        Self {
+            id: player.id,
+            position: player.position,
+            velocity: player.velocity,
+            facing: player.facing,
+            health_ratio: ratio(player.health, player.max_health),
+            shield_ratio: ratio(player.shield, player.max_shield),
+            level: player.level,
+            party_id: player.party_id,
+            dashing: player.is_dashing(now),
+        }
+    }
+}
+
+/// What a player's damage came from, for the per-weapon stats breakdown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Weapon {
+    #[default]
+    AutoAttack, // the auto-aimed projectile every player fires
+    AttackDrone,
+    WolfPet,
+    Aura, // the damage field from the DamageAura upgrade
+}
+
+impl Weapon {
+    pub fn name(&self) -> &str {
+        match self {
+            Weapon::AutoAttack => "Auto Attack",
+            Weapon::AttackDrone => "Attack Drone",
+            Weapon::WolfPet => "Wolf",
+            Weapon::Aura => "Aura",
+        }
+    }
+}
+
+/// Totals for one run, sent with the death summary for the end screen
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunStats {
+    pub damage_dealt: f32, // to enemies and, in PvP, players
+    pub damage_taken: f32,
+    pub xp_collected: u32,
+    pub distance_traveled: f32,
+    pub damage_by_weapon: HashMap<Weapon, f32>,
+}
+
+impl RunStats {
+    pub fn record_damage(&mut self, weapon: Weapon, amount: f32) {
+        self.damage_dealt += amount;
+        *self.damage_by_weapon.entry(weapon).or_default() += amount;
+    }
+}
+
 /// Enemy type enumeration
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum EnemyType {
@@ -135,6 +448,21 @@ pub enum EnemyType {
 }
 
 impl EnemyType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EnemyType::Goblin => "Goblin",
+            EnemyType::Orc => "Orc",
+            EnemyType::Wolf => "Wolf",
+            EnemyType::Skeleton => "Skeleton",
+            EnemyType::Zombie => "Zombie",
+            EnemyType::Demon => "Demon",
+            EnemyType::Wraith => "Wraith",
+            EnemyType::Troll => "Troll",
+            EnemyType::Dragon => "Dragon",
+            EnemyType::Lich => "Lich",
+        }
+    }
+
     pub fn all() -> Vec<EnemyType> {
         vec![
             EnemyType::Goblin,
@@ -232,22 +560,20 @@ impl EnemyType {
         }
     }
 
-    /// Get scaled stats for a given ring
-    pub fn stats_for_ring(&self, ring: u32) -> EnemyStats {
-        let base = self.base_stats();
-        // HP scales x10 per ring, Damage +30% per ring, Speed +10% per ring
-        let ring_level = ring.max(1);
-        EnemyStats {
-            max_health: base.max_health * (ring_level as f32),
-            damage: base.damage * (1.0 + (ring_level as f32 - 1.0) * 0.3),
-            movement_speed: base.movement_speed * (1.0 + (ring_level as f32 - 1.0) * 0.1),
-            attack_speed: base.attack_speed,
-        }
+    /// The outer-ring heavyweights; killing one counts as a boss kill in
+    /// match history
+    pub fn is_boss(&self) -> bool {
+        matches!(self, EnemyType::Dragon | EnemyType::Lich)
     }
 
-    /// Calculate XP reward for killing this enemy at a given ring (5x per ring level)
-    pub fn xp_for_ring(&self, ring: u32) -> u32 {
-        let base_xp = match self {
+    /// Whether its melee hits leave players burning
+    pub fn burns(&self) -> bool {
+        matches!(self, EnemyType::Demon | EnemyType::Dragon)
+    }
+
+    /// XP for a kill at ring 1
+    pub fn base_xp(&self) -> u32 {
+        match self {
             EnemyType::Goblin | EnemyType::Wolf => 10,
             EnemyType::Orc | EnemyType::Skeleton => 15,
             EnemyType::Zombie | EnemyType::Wraith => 20,
@@ -255,12 +581,22 @@ impl EnemyType {
             EnemyType::Troll => 30,
             EnemyType::Dragon => 50,
             EnemyType::Lich => 40,
-        };
-        base_xp * (ring.max(1) * 5)
+        }
+    }
+
+    /// Get scaled stats for a given ring
+    pub fn stats_for_ring(&self, ring: u32) -> EnemyStats {
+        // HP +100% per ring, Damage +30% per ring, Speed +10% per ring
+        RingScaling::default().stats(self.base_stats(), ring)
+    }
+
+    /// Calculate XP reward for killing this enemy at a given ring (5x per ring level)
+    pub fn xp_for_ring(&self, ring: u32) -> u32 {
+        RingScaling::default().xp(self.base_xp(), ring)
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct EnemyStats {
     pub max_health: f32,
     pub damage: f32,
@@ -268,6 +604,56 @@ pub struct EnemyStats {
     pub attack_speed: f32,
 }
 
+/// Current step of an enemy's behavior state machine. Sent to clients so
+/// they can telegraph attacks (e.g. a troll winding up).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EnemyBehavior {
+    Chase,  // beeline at the target
+    Circle, // wolves: orbit the target until the pack gathers
+    Phased, // wraiths: untargetable, about to teleport
+    Kite,   // skeletons: hold range and shoot
+    WindUp, // trolls: telegraphing a charge
+    Charge, // trolls: running in a locked direction
+    Recover,
+    Return, // pulled past its leash: walking home, ignoring players
+}
+
+/// Server-only: where an enemy's AI last told it to go. Decisions run less
+/// often than the tick; movement follows the steering every tick.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Steering {
+    #[default]
+    Hold,
+    Chase,              // toward the target player, wherever they are now
+    Toward(Position),   // toward a fixed point
+    Heading(Vec2, f32), // along a direction, at a multiple of movement speed
+}
+
+impl EnemyType {
+    /// Collision radius in world units; also the size clients draw
+    pub fn radius(&self) -> f32 {
+        match self {
+            EnemyType::Goblin | EnemyType::Wraith => 5.0,
+            EnemyType::Wolf | EnemyType::Skeleton => 6.0,
+            EnemyType::Orc => 7.0,
+            EnemyType::Zombie => 8.0,
+            EnemyType::Demon => 9.0,
+            EnemyType::Lich => 10.0,
+            EnemyType::Troll => 12.0,
+            EnemyType::Dragon => 15.0,
+        }
+    }
+
+    /// Behavior an enemy of this type starts in
+    pub fn initial_behavior(&self) -> EnemyBehavior {
+        match self {
+            EnemyType::Wolf => EnemyBehavior::Circle,
+            EnemyType::Skeleton => EnemyBehavior::Kite,
+            _ => EnemyBehavior::Chase,
+        }
+    }
+}
+
 /// Enemy entity
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Enemy {
@@ -283,12 +669,43 @@ pub struct Enemy {
     pub xp_reward: u32,
     pub last_attack_time: f64,
     pub target_player_id: Option<Uuid>,
+    pub behavior: EnemyBehavior,
+    pub behavior_started: f64,  // game time the current behavior began
+    pub charge_direction: Vec2, // trolls: heading locked in at the end of the windup
+    #[serde(skip)]
+    pub threat: HashMap<Uuid, f32>, // server-only: player ID -> accumulated threat
+    #[serde(skip)]
+    pub damage_by: HashMap<Uuid, f32>, // server-only: player ID -> damage dealt, for kill credit
+    #[serde(skip)]
+    pub home: Position, // server-only: spawn point the leash is measured from
+    #[serde(default)]
+    pub velocity: Vec2, // units/sec over the last tick, for client extrapolation
+    #[serde(default)]
+    pub facing: f32, // radians; the direction of the latest movement
+    #[serde(skip)]
+    pub last_position: Option<Position>, // server-only: where the last tick left the enemy
+    #[serde(skip)]
+    pub steering: Steering, // server-only: what movement follows between decisions
+    #[serde(skip)]
+    pub last_decision: Option<f64>, // server-only: game time the AI last decided
 }
 
 impl Enemy {
+    /// An enemy with the built-in stats for `ring`
     pub fn new(id: Uuid, enemy_type: EnemyType, position: Position, ring: u32) -> Self {
         let stats = enemy_type.stats_for_ring(ring);
         let xp_reward = enemy_type.xp_for_ring(ring);
+        Self::with_stats(id, enemy_type, position, ring, stats, xp_reward)
+    }
+
+    pub fn with_stats(
+        id: Uuid,
+        enemy_type: EnemyType,
+        position: Position,
+        ring: u32,
+        stats: EnemyStats,
+        xp_reward: u32,
+    ) -> Self {
         Self {
             id,
             enemy_type,
@@ -302,9 +719,40 @@ impl Enemy {
             xp_reward,
             last_attack_time: 0.0,
             target_player_id: None,
+            behavior: enemy_type.initial_behavior(),
+            behavior_started: 0.0,
+            charge_direction: Vec2::ZERO,
+            threat: HashMap::new(),
+            damage_by: HashMap::new(),
+            home: position,
+            velocity: Vec2::ZERO,
+            facing: 0.0,
+            last_position: None,
+            steering: Steering::Hold,
+            last_decision: None,
         }
     }
 
+    /// Scale health and damage on top of the ring stats (e.g. difficulty tiers)
+    pub fn scale_stats(&mut self, multiplier: f32) {
+        self.max_health *= multiplier;
+        self.health = self.max_health;
+        self.damage *= multiplier;
+    }
+
+    /// Phased wraiths can't be hit or auto-targeted
+    pub fn is_targetable(&self) -> bool {
+        self.is_alive() && self.behavior != EnemyBehavior::Phased
+    }
+
+    /// Whether the current behavior allows a melee hit
+    pub fn can_melee(&self) -> bool {
+        matches!(
+            self.behavior,
+            EnemyBehavior::Chase | EnemyBehavior::Circle | EnemyBehavior::Charge
+        )
+    }
+
     pub fn is_alive(&self) -> bool {
         self.health > 0.0
     }
@@ -322,47 +770,228 @@ impl Enemy {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Projectile {
     pub id: Uuid,
-    pub owner_id: Uuid,      // player who fired it
+    pub owner_id: Uuid, // player (or enemy, if hostile) who fired it
     pub position: Position,
-    pub velocity: Position,  // direction and speed (units per second)
+    pub velocity: Vec2, // direction and speed (units per second)
+    #[serde(default)]
+    pub facing: f32, // radians; the direction it flies in, for rotating its sprite
     pub damage: f32,
-    pub lifetime: f32,       // remaining seconds before despawn
-    pub max_lifetime: f32,   // total lifetime for age calculation
+    pub lifetime: f32,     // remaining seconds before despawn
+    pub max_lifetime: f32, // total lifetime for age calculation
+    pub hostile: bool,     // fired by an enemy; hits players instead of enemies
+    #[serde(default)]
+    pub weapon: Weapon,
+    #[serde(default)]
+    pub turn_rate: f32, // radians per second it steers toward enemies; 0 flies straight
+    #[serde(default)]
+    pub explosion: f32, // fraction of its damage dealt around where it lands; 0 doesn't explode
 }
 
 impl Projectile {
-    pub fn new(owner_id: Uuid, position: Position, direction: Position, speed: f32, damage: f32, lifetime: f32) -> Self {
-        // Normalize direction and apply speed
-        let magnitude = (direction.x * direction.x + direction.y * direction.y).sqrt();
-        let velocity = if magnitude > 0.0 {
-            Position::new(
-                direction.x / magnitude * speed,
-                direction.y / magnitude * speed
-            )
-        } else {
-            Position::new(0.0, 0.0)
-        };
-        
+    /// A shot with a nil ID; the server gives it one from its pool when it
+    /// enters the world
+    pub fn new(
+        owner_id: Uuid,
+        position: Position,
+        direction: Vec2,
+        speed: f32,
+        damage: f32,
+        lifetime: f32,
+    ) -> Self {
         Self {
-            id: Uuid::new_v4(),
+            id: Uuid::nil(),
             owner_id,
             position,
-            velocity,
+            velocity: direction.normalize() * speed,
+            facing: direction.angle(),
             damage,
             lifetime,
             max_lifetime: lifetime,
+            hostile: false,
+            weapon: Weapon::AutoAttack,
+            turn_rate: 0.0,
+            explosion: 0.0,
         }
     }
 
-    pub fn update(&mut self, delta_time: f32) {
-        self.position.x += self.velocity.x * delta_time;
-        self.position.y += self.velocity.y * delta_time;
+    /// Move on by `delta_time`, first steering toward the enemy
+    /// `nearest_enemy` finds from here if the shot homes
+    pub fn update(
+        &mut self,
+        delta_time: f32,
+        nearest_enemy: impl FnOnce(&Position) -> Option<Position>,
+    ) {
+        if self.turn_rate > 0.0 {
+            if let Some(target) = nearest_enemy(&self.position) {
+                self.steer_towards(&target, self.turn_rate * delta_time);
+            }
+        }
+        self.position += self.velocity * delta_time;
         self.lifetime -= delta_time;
     }
 
     pub fn is_alive(&self) -> bool {
         self.lifetime > 0.0
     }
+
+    /// Turn the velocity toward `target` by at most `max_turn` radians,
+    /// keeping its speed
+    fn steer_towards(&mut self, target: &Position, max_turn: f32) {
+        use std::f32::consts::{PI, TAU};
+        let wanted = (*target - self.position).angle();
+        let difference = (wanted - self.velocity.angle() + PI).rem_euclid(TAU) - PI;
+        self.velocity = self.velocity.rotate(difference.clamp(-max_turn, max_turn));
+        self.facing = self.velocity.angle();
+    }
+}
+
+/// How a combat hit resolved, for client feedback
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CombatEventKind {
+    Hit,
+    Crit,
+    Blocked,    // absorbed (e.g. by the safe zone); amount is 0
+    Kill,       // the killing blow
+    SecondWind, // a lethal hit was survived; amount is the health restored
+}
+
+/// A single damage instance, sent in per-tick batches so clients can draw
+/// floating numbers and death effects
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CombatEvent {
+    pub attacker: Uuid,
+    pub target: Uuid,
+    pub amount: f32,
+    pub kind: CombatEventKind,
+    pub position: Position, // where the target was hit
+}
+
+/// The kinds of entity snapshots carry besides players
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntityKind {
+    Enemy,
+    Projectile,
+    Pickup,
+    Summon,
+}
+
+/// Why an entity left the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DespawnReason {
+    Killed,      // an enemy died
+    Hit,         // a projectile struck something
+    Expired,     // a projectile or pickup ran out its lifetime
+    Collected,   // a player picked the pickup up
+    OutOfBounds, // a projectile flew off the map
+    ReachedCore, // wave defense: an enemy got to the core and spent itself on it
+    Removed,     // the server took it away: its owner died or left, a corrupt position
+}
+
+/// An entity entering or leaving the world, sent in per-tick batches.
+/// Entities also drop out of snapshots when they leave the receiver's view
+/// or the bandwidth limit; a despawned one is gone for good, so clients
+/// can play its death or impact rather than just stop drawing it. A
+/// spawned one has no earlier position to interpolate from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum EntityEvent {
+    #[serde(rename = "EntitySpawned")]
+    Spawned {
+        id: Uuid,
+        kind: EntityKind,
+        position: Position,
+    },
+    #[serde(rename = "EntityDespawned")]
+    Despawned {
+        id: Uuid,
+        kind: EntityKind,
+        position: Position, // where it was last
+        reason: DespawnReason,
+    },
+}
+
+impl EntityEvent {
+    pub fn id(&self) -> Uuid {
+        match self {
+            EntityEvent::Spawned { id, .. } | EntityEvent::Despawned { id, .. } => *id,
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        match self {
+            EntityEvent::Spawned { position, .. } | EntityEvent::Despawned { position, .. } => {
+                *position
+            }
+        }
+    }
+}
+
+/// Kinds of item an enemy can drop
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PickupType {
+    HealthPotion,
+    DoubleDamage, // 2x damage for a while
+    SpeedBoost,   // 1.5x movement speed for a while
+    Gold,
+    Equipment, // a piece of gear for the inventory; see `Pickup::item`
+}
+
+impl PickupType {
+    /// Roll the drop table; `None` means the enemy dropped nothing
+    pub fn roll_drop(drop_chance: f32, rng: &mut impl Rng) -> Option<PickupType> {
+        if !rng.gen_bool(drop_chance.clamp(0.0, 1.0) as f64) {
+            return None;
+        }
+
+        // Weighted: consumables common, power-ups rare
+        let pickup = match rng.gen_range(0..100) {
+            0..40 => PickupType::Gold,
+            40..80 => PickupType::HealthPotion,
+            80..90 => PickupType::DoubleDamage,
+            _ => PickupType::SpeedBoost,
+        };
+        Some(pickup)
+    }
+}
+
+/// Item lying in the world, collected by walking over it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pickup {
+    pub id: Uuid,
+    pub pickup_type: PickupType,
+    pub position: Position,
+    pub amount: u32,   // gold coins or HP restored; unused for power-ups
+    pub lifetime: f32, // remaining seconds before despawn
+    #[serde(default)]
+    pub item: Option<Equipment>, // the gear, for `PickupType::Equipment`
+    #[serde(skip)]
+    pub dropped_by: Option<Uuid>, // server-only: can't take it back until they step away
+}
+
+impl Pickup {
+    /// A drop with a nil ID, like `Projectile::new`
+    pub fn new(pickup_type: PickupType, position: Position, amount: u32, lifetime: f32) -> Self {
+        Self {
+            id: Uuid::nil(),
+            pickup_type,
+            position,
+            amount,
+            lifetime,
+            item: None,
+            dropped_by: None,
+        }
+    }
+
+    pub fn equipment(item: Equipment, position: Position, lifetime: f32) -> Self {
+        Self {
+            item: Some(item),
+            ..Self::new(PickupType::Equipment, position, 0, lifetime)
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.lifetime > 0.0
+    }
 }
 
 /// Score entry for the leaderboard
@@ -372,10 +1001,20 @@ pub struct ScoreEntry {
     pub max_ring_reached: u32,
     pub survival_time_seconds: f32,
     pub enemies_defeated: u32,
-    pub timestamp: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub players_defeated: u32,
+    #[serde(default)]
+    pub party_members: Vec<Uuid>, // empty for solo runs; player_id is then the party ID
+    #[cfg_attr(not(feature = "chrono"), serde(with = "crate::timestamp::rfc3339"))]
+    pub timestamp: Timestamp,
 }
 
 impl ScoreEntry {
+    /// Runs are told apart by who and when; `player_id` alone repeats
+    pub fn same_run(&self, other: &ScoreEntry) -> bool {
+        self.player_id == other.player_id && self.timestamp == other.timestamp
+    }
+
     /// Calculate a composite score for sorting
     pub fn total_score(&self) -> u32 {
         // Primary: max ring, Secondary: survival time, Tertiary: enemies defeated
@@ -384,3 +1023,36 @@ impl ScoreEntry {
             + self.enemies_defeated
     }
 }
+
+/// How one entry moved when the scoreboard changed. Ranks count from 1;
+/// `from` is `None` for a new entry and `to` for one pushed off the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RankChange {
+    pub player_id: Uuid,
+    pub from: Option<u32>,
+    pub to: Option<u32>,
+}
+
+impl RankChange {
+    /// Every entry ranked differently in `after` than in `before`
+    pub fn between(before: &[ScoreEntry], after: &[ScoreEntry]) -> Vec<RankChange> {
+        let rank = |board: &[ScoreEntry], entry: &ScoreEntry| {
+            let index = board.iter().position(|e| e.same_run(entry))?;
+            Some(index as u32 + 1)
+        };
+        let moved = after.iter().map(|entry| RankChange {
+            player_id: entry.player_id,
+            from: rank(before, entry),
+            to: rank(after, entry),
+        });
+        let dropped = before
+            .iter()
+            .filter(|entry| rank(after, entry).is_none())
+            .map(|entry| RankChange {
+                player_id: entry.player_id,
+                from: rank(before, entry),
+                to: None,
+            });
+        moved.chain(dropped).filter(|c| c.from != c.to).collect()
+    }
+}
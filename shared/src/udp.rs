@@ -0,0 +1,156 @@
+//! Framing for the optional UDP channel native clients use for snapshots
+//! and inputs. Control traffic (joining, upgrades, parties) stays on the
+//! WebSocket.
+//!
+//! Every packet starts with an 11-byte header, all integers little-endian:
+//!
+//! | bytes | field                                                  |
+//! |-------|--------------------------------------------------------|
+//! | 0..2  | [`PROTOCOL_ID`]                                        |
+//! | 2     | [`PacketKind`]                                         |
+//! | 3..5  | sequence number of this packet                         |
+//! | 5..7  | newest sequence number received from the other side    |
+//! | 7..11 | ack bits: bit `n` set means `ack - 1 - n` arrived too  |
+//!
+//! Sequence numbers wrap; compare them with [`sequence_greater_than`].
+
+use uuid::Uuid;
+
+/// Packets not starting with this are ignored
+pub const PROTOCOL_ID: u16 = 0x4753;
+
+pub const HEADER_LEN: usize = 11;
+
+/// Largest packet either side sends, to stay clear of IP fragmentation
+pub const MAX_PACKET_LEN: usize = 1200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketKind {
+    /// Client → server: bind this address to a player. Payload is the
+    /// player ID (16 bytes) and the token from `UdpOffer` (8 bytes).
+    /// Repeated until snapshots start arriving.
+    Hello = 0,
+    /// Client → server: a JSON `ClientMessage`; only `Move` and `Dash`
+    /// are accepted here
+    Input = 1,
+    /// Server → client: a raw-deflated JSON `GameState` message
+    Snapshot = 2,
+}
+
+impl PacketKind {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(PacketKind::Hello),
+            1 => Some(PacketKind::Input),
+            2 => Some(PacketKind::Snapshot),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketHeader {
+    pub kind: PacketKind,
+    pub sequence: u16,
+    pub ack: u16,
+    pub ack_bits: u32,
+}
+
+impl PacketHeader {
+    /// Header followed by `payload`
+    pub fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(HEADER_LEN + payload.len());
+        packet.extend_from_slice(&PROTOCOL_ID.to_le_bytes());
+        packet.push(self.kind as u8);
+        packet.extend_from_slice(&self.sequence.to_le_bytes());
+        packet.extend_from_slice(&self.ack.to_le_bytes());
+        packet.extend_from_slice(&self.ack_bits.to_le_bytes());
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    /// Split a packet into header and payload; `None` for anything that
+    /// isn't ours
+    pub fn decode(packet: &[u8]) -> Option<(Self, &[u8])> {
+        if packet.len() < HEADER_LEN {
+            return None;
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([packet[i], packet[i + 1]]);
+        if u16_at(0) != PROTOCOL_ID {
+            return None;
+        }
+        let header = Self {
+            kind: PacketKind::from_u8(packet[2])?,
+            sequence: u16_at(3),
+            ack: u16_at(5),
+            ack_bits: u32::from_le_bytes([packet[7], packet[8], packet[9], packet[10]]),
+        };
+        Some((header, &packet[HEADER_LEN..]))
+    }
+
+    /// Whether this header acknowledges `sequence`
+    pub fn acks(&self, sequence: u16) -> bool {
+        if sequence == self.ack {
+            return true;
+        }
+        let behind = self.ack.wrapping_sub(sequence);
+        (1..=32).contains(&behind) && self.ack_bits & (1 << (behind - 1)) != 0
+    }
+}
+
+/// Payload of a `Hello` packet
+pub fn encode_hello(player_id: Uuid, token: u64) -> Vec<u8> {
+    let mut payload = player_id.as_bytes().to_vec();
+    payload.extend_from_slice(&token.to_le_bytes());
+    payload
+}
+
+pub fn decode_hello(payload: &[u8]) -> Option<(Uuid, u64)> {
+    let player_id = Uuid::from_slice(payload.get(..16)?).ok()?;
+    let token = u64::from_le_bytes(payload.get(16..24)?.try_into().ok()?);
+    Some((player_id, token))
+}
+
+/// Whether `a` is newer than `b`, allowing for wraparound
+pub fn sequence_greater_than(a: u16, b: u16) -> bool {
+    a != b && a.wrapping_sub(b) < u16::MAX / 2
+}
+
+/// One side's record of what it has received, echoed back in every header
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AckState {
+    pub ack: u16,
+    pub ack_bits: u32,
+    received_any: bool,
+}
+
+impl AckState {
+    /// Note an arriving packet. Returns true if it's the newest so far;
+    /// older ones are still acked but their contents are stale.
+    pub fn receive(&mut self, sequence: u16) -> bool {
+        if !self.received_any {
+            self.received_any = true;
+            self.ack = sequence;
+            self.ack_bits = 0;
+            return true;
+        }
+
+        if sequence_greater_than(sequence, self.ack) {
+            let shift = sequence.wrapping_sub(self.ack) as u32;
+            // The previous newest becomes bit `shift - 1`
+            self.ack_bits = match shift {
+                1..=31 => (self.ack_bits << shift) | (1 << (shift - 1)),
+                32 => 1 << 31,
+                _ => 0,
+            };
+            self.ack = sequence;
+            return true;
+        }
+
+        let behind = self.ack.wrapping_sub(sequence);
+        if (1..=32).contains(&behind) {
+            self.ack_bits |= 1 << (behind - 1);
+        }
+        false
+    }
+}
@@ -7,93 +7,358 @@ pub enum UpgradeType {
     IncreaseDamage,
     IncreaseAttackSpeed,
     IncreaseProjectileSpeed,
-    MultiShot,          // Fire multiple projectiles
-    PiercingShots,      // Projectiles pierce through enemies
-    
+    MultiShot,      // Fire multiple projectiles
+    PiercingShots,  // Projectiles pierce through enemies
+    HomingShots,    // Projectiles steer toward enemies
+    ExplosiveShots, // Projectiles explode where they land
+    CritChance,     // More frequent critical strikes
+
     // Stat upgrades
     IncreaseMaxHealth,
     IncreaseMovementSpeed,
     HealthRegeneration,
-    
+    Overshield, // A recharging shield that absorbs damage
+
     // Passive abilities
-    PickupRadius,       // Increase XP pickup radius
-    Magnet,             // Auto-collect XP
-    Armor,              // Reduce damage taken
-    Luck,               // Better drops/bonuses
+    PickupRadius, // Increase XP pickup radius
+    Magnet,       // Auto-collect XP
+    Armor,        // Reduce damage taken
+    Luck,         // Better drops/bonuses
+    SecondWind,   // Survive one lethal hit per run
+
+    // Companions
+    SummonDrone,  // One more attack drone
+    SummonWolf,   // One more wolf pet
+    SummonDamage, // Companions hit harder
+
+    // Aura weapon
+    DamageAura, // A damage field around the player
+    AuraRadius, // A wider damage field
 }
 
 impl UpgradeType {
-    pub fn name(&self) -> &str {
+    pub fn name(&self) -> &'static str {
         match self {
             UpgradeType::IncreaseDamage => "Damage+",
             UpgradeType::IncreaseAttackSpeed => "Attack Speed+",
             UpgradeType::IncreaseProjectileSpeed => "Projectile Speed+",
             UpgradeType::MultiShot => "Multi Shot",
             UpgradeType::PiercingShots => "Piercing Shots",
+            UpgradeType::HomingShots => "Homing Shots",
+            UpgradeType::ExplosiveShots => "Explosive Shots",
+            UpgradeType::CritChance => "Crit Chance+",
             UpgradeType::IncreaseMaxHealth => "Max Health+",
             UpgradeType::IncreaseMovementSpeed => "Move Speed+",
             UpgradeType::HealthRegeneration => "HP Regeneration",
+            UpgradeType::Overshield => "Overshield",
             UpgradeType::PickupRadius => "Pickup Radius+",
             UpgradeType::Magnet => "Magnet",
             UpgradeType::Armor => "Armor",
             UpgradeType::Luck => "Luck",
+            UpgradeType::SecondWind => "Second Wind",
+            UpgradeType::SummonDrone => "Attack Drone",
+            UpgradeType::SummonWolf => "Wolf Pet",
+            UpgradeType::SummonDamage => "Summon Damage+",
+            UpgradeType::DamageAura => "Damage Aura",
+            UpgradeType::AuraRadius => "Aura Radius+",
         }
     }
 
-    pub fn description(&self) -> &str {
+    /// English description, with `{value}` and `{cap}` standing for the
+    /// magnitudes in the `UpgradeCatalog`
+    pub fn description_template(&self) -> &'static str {
         match self {
-            UpgradeType::IncreaseDamage => "Increase damage by 20%",
-            UpgradeType::IncreaseAttackSpeed => "Increase attack speed by 15%",
-            UpgradeType::IncreaseProjectileSpeed => "Increase projectile speed by 25%",
-            UpgradeType::MultiShot => "Fire 2 additional projectiles",
-            UpgradeType::PiercingShots => "Projectiles pierce through 1 enemy",
-            UpgradeType::IncreaseMaxHealth => "Increase max health by 25%",
-            UpgradeType::IncreaseMovementSpeed => "Increase movement speed by 10%",
-            UpgradeType::HealthRegeneration => "Regenerate 1 HP per second",
-            UpgradeType::PickupRadius => "Increase pickup radius by 50%",
+            UpgradeType::IncreaseDamage => "Increase damage by {value}%",
+            UpgradeType::IncreaseAttackSpeed => "Increase attack speed by {value}%",
+            UpgradeType::IncreaseProjectileSpeed => "Increase projectile speed by {value}%",
+            UpgradeType::MultiShot => "Fire {value} additional projectiles",
+            UpgradeType::PiercingShots => "Projectiles pierce through {value} enemy",
+            UpgradeType::HomingShots => "Shots turn {value}° per second toward enemies",
+            UpgradeType::ExplosiveShots => "Shots explode for {value}% of their damage",
+            UpgradeType::CritChance => "Increase critical strike chance by {value}%",
+            UpgradeType::IncreaseMaxHealth => "Increase max health by {value}%",
+            UpgradeType::IncreaseMovementSpeed => "Increase movement speed by {value}%",
+            UpgradeType::HealthRegeneration => "Regenerate {value} HP per second",
+            UpgradeType::Overshield => "Recharge a shield that absorbs {value} damage",
+            UpgradeType::PickupRadius => "Increase pickup radius by {value}%",
             UpgradeType::Magnet => "Automatically collect nearby XP",
-            UpgradeType::Armor => "Reduce damage taken by 10%",
-            UpgradeType::Luck => "Increase luck by 10%",
+            UpgradeType::Armor => "Reduce damage taken by {value}% (max {cap}%)",
+            UpgradeType::Luck => "Increase luck by {value}%",
+            UpgradeType::SecondWind => "Once per run, survive a lethal hit with {value}% health",
+            UpgradeType::SummonDrone => "Summon a drone that shoots nearby enemies",
+            UpgradeType::SummonWolf => "Summon a wolf that hunts nearby enemies",
+            UpgradeType::SummonDamage => "Companions deal {value}% more damage",
+            UpgradeType::DamageAura => "A field around you deals {value} damage per second",
+            UpgradeType::AuraRadius => "Increase aura radius by {value}%",
         }
     }
 
-    /// Get a random selection of upgrades (3 choices)
-    pub fn random_choices(exclude: &[UpgradeType]) -> Vec<UpgradeType> {
-        use rand::seq::SliceRandom;
-        let all: Vec<UpgradeType> = vec![
-            UpgradeType::IncreaseDamage,
-            UpgradeType::IncreaseAttackSpeed,
-            UpgradeType::IncreaseProjectileSpeed,
-            UpgradeType::MultiShot,
-            UpgradeType::PiercingShots,
-            UpgradeType::IncreaseMaxHealth,
-            UpgradeType::IncreaseMovementSpeed,
-            UpgradeType::HealthRegeneration,
-            UpgradeType::PickupRadius,
-            UpgradeType::Magnet,
-            UpgradeType::Armor,
-            UpgradeType::Luck,
-        ];
-        
-        let mut available: Vec<UpgradeType> = all
-            .into_iter()
-            .filter(|u| !exclude.contains(u))
+    /// Whether catalog magnitudes are fractions, shown as percentages
+    pub fn is_percentage(&self) -> bool {
+        !matches!(
+            self,
+            UpgradeType::MultiShot
+                | UpgradeType::PiercingShots
+                | UpgradeType::HomingShots
+                | UpgradeType::HealthRegeneration
+                | UpgradeType::Overshield
+                | UpgradeType::Magnet
+                | UpgradeType::SummonDrone
+                | UpgradeType::SummonWolf
+                | UpgradeType::DamageAura
+        )
+    }
+
+    pub const ALL: [UpgradeType; 22] = [
+        UpgradeType::IncreaseDamage,
+        UpgradeType::IncreaseAttackSpeed,
+        UpgradeType::IncreaseProjectileSpeed,
+        UpgradeType::MultiShot,
+        UpgradeType::PiercingShots,
+        UpgradeType::HomingShots,
+        UpgradeType::ExplosiveShots,
+        UpgradeType::CritChance,
+        UpgradeType::IncreaseMaxHealth,
+        UpgradeType::IncreaseMovementSpeed,
+        UpgradeType::HealthRegeneration,
+        UpgradeType::Overshield,
+        UpgradeType::PickupRadius,
+        UpgradeType::Magnet,
+        UpgradeType::Armor,
+        UpgradeType::Luck,
+        UpgradeType::SecondWind,
+        UpgradeType::SummonDrone,
+        UpgradeType::SummonWolf,
+        UpgradeType::SummonDamage,
+        UpgradeType::DamageAura,
+        UpgradeType::AuraRadius,
+    ];
+}
+
+/// What one level of an upgrade is worth
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpgradeMagnitude {
+    pub upgrade: UpgradeType,
+    pub per_level: f32,
+    #[serde(default)]
+    pub cap: Option<f32>, // limit on the total over all levels
+}
+
+/// Upgrade magnitudes, built in from `data/upgrades.toml`. Stat math and
+/// description text both read them, so the two can't disagree.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpgradeCatalog {
+    pub upgrades: Vec<UpgradeMagnitude>,
+}
+
+impl Default for UpgradeCatalog {
+    fn default() -> Self {
+        toml::from_str(include_str!("../data/upgrades.toml"))
+            .expect("built-in upgrade catalog should parse")
+    }
+}
+
+impl UpgradeCatalog {
+    pub fn magnitude(&self, upgrade: UpgradeType) -> Option<&UpgradeMagnitude> {
+        self.upgrades.iter().find(|m| m.upgrade == upgrade)
+    }
+
+    /// What `levels` levels of `upgrade` add up to, after its cap
+    pub fn bonus(&self, upgrade: UpgradeType, levels: u32) -> f32 {
+        let Some(magnitude) = self.magnitude(upgrade) else {
+            return 0.0;
+        };
+        let total = magnitude.per_level * levels as f32;
+        magnitude.cap.map_or(total, |cap| total.min(cap))
+    }
+
+    /// `template` with `{value}` (one level) and `{cap}` filled in
+    pub fn describe(&self, upgrade: UpgradeType, template: &str) -> String {
+        let show = |amount: f32| {
+            let amount = if upgrade.is_percentage() {
+                amount * 100.0
+            } else {
+                amount
+            };
+            ((amount * 100.0).round() / 100.0).to_string()
+        };
+        let magnitude = self.magnitude(upgrade);
+        template
+            .replace("{value}", &show(magnitude.map_or(0.0, |m| m.per_level)))
+            .replace("{cap}", &show(magnitude.and_then(|m| m.cap).unwrap_or(0.0)))
+    }
+
+    /// Every entry the game can't use, one line each
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (i, magnitude) in self.upgrades.iter().enumerate() {
+            let name = magnitude.upgrade.name();
+            if self.upgrades[..i]
+                .iter()
+                .any(|m| m.upgrade == magnitude.upgrade)
+            {
+                problems.push(format!("{name} has more than one magnitude"));
+            }
+            if magnitude.per_level < 0.0 || magnitude.cap.is_some_and(|cap| cap < 0.0) {
+                problems.push(format!("{name} magnitudes can't be negative"));
+            }
+        }
+        problems
+    }
+}
+
+/// How strong an offered upgrade is. Rarer ones apply several levels at once.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Rarity {
+    #[default]
+    Common,
+    Rare,
+    Epic,
+}
+
+impl Rarity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rarity::Common => "Common",
+            Rarity::Rare => "Rare",
+            Rarity::Epic => "Epic",
+        }
+    }
+}
+
+/// One entry of a level-up offer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpgradeChoice {
+    pub upgrade: UpgradeType,
+    pub rarity: Rarity,
+}
+
+/// How often an upgrade is offered, relative to the others in the table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpgradeWeight {
+    pub upgrade: UpgradeType,
+    pub weight: f32,
+    #[serde(default = "UpgradeWeight::default_max_rarity")]
+    pub max_rarity: Rarity, // e.g. Common for upgrades that don't stack
+}
+
+impl UpgradeWeight {
+    fn default_max_rarity() -> Rarity {
+        Rarity::Epic
+    }
+}
+
+/// How often a rarity comes up, and what it's worth
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RarityTier {
+    pub rarity: Rarity,
+    pub weight: f32,
+    pub luck_weight: f32, // added to `weight` per level of Luck
+    pub levels: u32,      // upgrade levels applied when picked
+}
+
+/// What level-up offers are drawn from: each choice is a different upgrade
+/// picked by weight, then given a rarity by the tier weights
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpgradeTable {
+    pub upgrades: Vec<UpgradeWeight>,
+    pub rarities: Vec<RarityTier>,
+}
+
+impl Default for UpgradeTable {
+    fn default() -> Self {
+        let upgrades = UpgradeType::ALL
+            .iter()
+            .map(|&upgrade| UpgradeWeight {
+                upgrade,
+                weight: 1.0,
+                max_rarity: match upgrade {
+                    UpgradeType::Magnet | UpgradeType::SecondWind => Rarity::Common,
+                    _ => Rarity::Epic,
+                },
+            })
             .collect();
-        
-        let mut rng = rand::thread_rng();
-        available.shuffle(&mut rng);
-        available.into_iter().take(3).collect()
+        let tier = |rarity, weight, luck_weight, levels| RarityTier {
+            rarity,
+            weight,
+            luck_weight,
+            levels,
+        };
+        Self {
+            upgrades,
+            rarities: vec![
+                tier(Rarity::Common, 70.0, 0.0, 1),
+                tier(Rarity::Rare, 25.0, 2.0, 2),
+                tier(Rarity::Epic, 5.0, 1.0, 3),
+            ],
+        }
+    }
+}
+
+impl UpgradeTable {
+    /// Up to `count` choices, none of them in `exclude`
+    pub fn roll(
+        &self,
+        exclude: &[UpgradeType],
+        luck_level: u32,
+        count: usize,
+        rng: &mut impl rand::Rng,
+    ) -> Vec<UpgradeChoice> {
+        use rand::distributions::{Distribution, WeightedIndex};
+
+        let mut pool: Vec<&UpgradeWeight> = self
+            .upgrades
+            .iter()
+            .filter(|u| u.weight > 0.0 && !exclude.contains(&u.upgrade))
+            .collect();
+        let mut choices = Vec::new();
+        while choices.len() < count {
+            let Ok(index) = WeightedIndex::new(pool.iter().map(|u| u.weight)) else {
+                break;
+            };
+            let entry = pool.swap_remove(index.sample(rng));
+            pool.retain(|u| u.upgrade != entry.upgrade);
+            choices.push(UpgradeChoice {
+                upgrade: entry.upgrade,
+                rarity: self.roll_rarity(entry.max_rarity, luck_level, rng),
+            });
+        }
+        choices
+    }
+
+    fn roll_rarity(&self, max: Rarity, luck_level: u32, rng: &mut impl rand::Rng) -> Rarity {
+        use rand::distributions::{Distribution, WeightedIndex};
+
+        let tiers: Vec<&RarityTier> = self.rarities.iter().filter(|t| t.rarity <= max).collect();
+        let weights = tiers
+            .iter()
+            .map(|t| (t.weight + t.luck_weight * luck_level as f32).max(0.0));
+        match WeightedIndex::new(weights) {
+            Ok(index) => tiers[index.sample(rng)].rarity,
+            Err(_) => Rarity::Common,
+        }
+    }
+
+    /// Upgrade levels a choice of `rarity` applies; 1 if it has no tier
+    pub fn levels(&self, rarity: Rarity) -> u32 {
+        self.rarities
+            .iter()
+            .find(|t| t.rarity == rarity)
+            .map_or(1, |t| t.levels.max(1))
     }
 }
 
 /// Player upgrade state
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PlayerUpgrades {
     pub damage_level: u32,
     pub attack_speed_level: u32,
     pub projectile_speed_level: u32,
     pub multi_shot_level: u32,
     pub piercing_level: u32,
+    pub crit_level: u32,
     pub max_health_level: u32,
     pub movement_speed_level: u32,
     pub regen_level: u32,
@@ -101,25 +366,24 @@ pub struct PlayerUpgrades {
     pub has_magnet: bool,
     pub armor_level: u32,
     pub luck_level: u32,
-}
-
-impl Default for PlayerUpgrades {
-    fn default() -> Self {
-        Self {
-            damage_level: 0,
-            attack_speed_level: 0,
-            projectile_speed_level: 0,
-            multi_shot_level: 0,
-            piercing_level: 0,
-            max_health_level: 0,
-            movement_speed_level: 0,
-            regen_level: 0,
-            pickup_radius_level: 0,
-            has_magnet: false,
-            armor_level: 0,
-            luck_level: 0,
-        }
-    }
+    #[serde(default)]
+    pub drones: u32,
+    #[serde(default)]
+    pub wolves: u32,
+    #[serde(default)]
+    pub summon_damage_level: u32,
+    #[serde(default)]
+    pub aura_level: u32,
+    #[serde(default)]
+    pub aura_radius_level: u32,
+    #[serde(default)]
+    pub homing_level: u32,
+    #[serde(default)]
+    pub explosive_level: u32,
+    #[serde(default)]
+    pub shield_level: u32,
+    #[serde(default)]
+    pub has_second_wind: bool,
 }
 
 impl PlayerUpgrades {
@@ -130,43 +394,118 @@ impl PlayerUpgrades {
             UpgradeType::IncreaseProjectileSpeed => self.projectile_speed_level += 1,
             UpgradeType::MultiShot => self.multi_shot_level += 1,
             UpgradeType::PiercingShots => self.piercing_level += 1,
+            UpgradeType::HomingShots => self.homing_level += 1,
+            UpgradeType::ExplosiveShots => self.explosive_level += 1,
+            UpgradeType::CritChance => self.crit_level += 1,
             UpgradeType::IncreaseMaxHealth => self.max_health_level += 1,
             UpgradeType::IncreaseMovementSpeed => self.movement_speed_level += 1,
             UpgradeType::HealthRegeneration => self.regen_level += 1,
+            UpgradeType::Overshield => self.shield_level += 1,
             UpgradeType::PickupRadius => self.pickup_radius_level += 1,
             UpgradeType::Magnet => self.has_magnet = true,
             UpgradeType::Armor => self.armor_level += 1,
             UpgradeType::Luck => self.luck_level += 1,
+            UpgradeType::SecondWind => self.has_second_wind = true,
+            UpgradeType::SummonDrone => self.drones += 1,
+            UpgradeType::SummonWolf => self.wolves += 1,
+            UpgradeType::SummonDamage => self.summon_damage_level += 1,
+            UpgradeType::DamageAura => self.aura_level += 1,
+            UpgradeType::AuraRadius => self.aura_radius_level += 1,
         }
     }
 
     /// Calculate effective damage multiplier
-    pub fn damage_multiplier(&self) -> f32 {
-        1.0 + (self.damage_level as f32 * 0.2)
+    pub fn damage_multiplier(&self, catalog: &UpgradeCatalog) -> f32 {
+        1.0 + catalog.bonus(UpgradeType::IncreaseDamage, self.damage_level)
     }
 
     /// Calculate effective attack speed multiplier
-    pub fn attack_speed_multiplier(&self) -> f32 {
-        1.0 + (self.attack_speed_level as f32 * 0.15)
+    pub fn attack_speed_multiplier(&self, catalog: &UpgradeCatalog) -> f32 {
+        1.0 + catalog.bonus(UpgradeType::IncreaseAttackSpeed, self.attack_speed_level)
     }
 
     /// Calculate effective movement speed multiplier
-    pub fn movement_speed_multiplier(&self) -> f32 {
-        1.0 + (self.movement_speed_level as f32 * 0.1)
+    pub fn movement_speed_multiplier(&self, catalog: &UpgradeCatalog) -> f32 {
+        1.0 + catalog.bonus(
+            UpgradeType::IncreaseMovementSpeed,
+            self.movement_speed_level,
+        )
     }
 
     /// Calculate effective projectile speed multiplier
-    pub fn projectile_speed_multiplier(&self) -> f32 {
-        1.0 + (self.projectile_speed_level as f32 * 0.25)
+    pub fn projectile_speed_multiplier(&self, catalog: &UpgradeCatalog) -> f32 {
+        1.0 + catalog.bonus(
+            UpgradeType::IncreaseProjectileSpeed,
+            self.projectile_speed_level,
+        )
+    }
+
+    /// Calculate effective max health multiplier
+    pub fn max_health_multiplier(&self, catalog: &UpgradeCatalog) -> f32 {
+        1.0 + catalog.bonus(UpgradeType::IncreaseMaxHealth, self.max_health_level)
+    }
+
+    /// Calculate crit chance added on top of the base
+    pub fn crit_chance_bonus(&self, catalog: &UpgradeCatalog) -> f32 {
+        catalog.bonus(UpgradeType::CritChance, self.crit_level)
+    }
+
+    /// Calculate damage reduction from armor, up to its cap
+    pub fn damage_reduction(&self, catalog: &UpgradeCatalog) -> f32 {
+        catalog.bonus(UpgradeType::Armor, self.armor_level)
+    }
+
+    /// Calculate effective pickup radius multiplier
+    pub fn pickup_radius_multiplier(&self, catalog: &UpgradeCatalog) -> f32 {
+        1.0 + catalog.bonus(UpgradeType::PickupRadius, self.pickup_radius_level)
+    }
+
+    /// Calculate drop chance multiplier from luck
+    pub fn luck_multiplier(&self, catalog: &UpgradeCatalog) -> f32 {
+        1.0 + catalog.bonus(UpgradeType::Luck, self.luck_level)
+    }
+
+    /// Share of max health a second wind leaves the player with; 0 without one
+    pub fn second_wind_health(&self, catalog: &UpgradeCatalog) -> f32 {
+        catalog.bonus(UpgradeType::SecondWind, self.has_second_wind as u32)
+    }
+
+    /// Calculate the damage multiplier for companions
+    pub fn summon_damage_multiplier(&self, catalog: &UpgradeCatalog) -> f32 {
+        1.0 + catalog.bonus(UpgradeType::SummonDamage, self.summon_damage_level)
+    }
+
+    /// How fast shots steer toward enemies, in radians per second; 0 flies
+    /// straight
+    pub fn homing_turn_rate(&self, catalog: &UpgradeCatalog) -> f32 {
+        catalog
+            .bonus(UpgradeType::HomingShots, self.homing_level)
+            .to_radians()
+    }
+
+    /// Fraction of a shot's damage its explosion deals around where it
+    /// lands; 0 if shots don't explode
+    pub fn explosion_damage(&self, catalog: &UpgradeCatalog) -> f32 {
+        catalog.bonus(UpgradeType::ExplosiveShots, self.explosive_level)
+    }
+
+    /// Shield points each Overshield recharge restores; 0 without one
+    pub fn shield_capacity(&self, catalog: &UpgradeCatalog) -> f32 {
+        catalog.bonus(UpgradeType::Overshield, self.shield_level)
+    }
+
+    /// Damage per second of the aura weapon; 0 without one
+    pub fn aura_damage(&self, catalog: &UpgradeCatalog) -> f32 {
+        catalog.bonus(UpgradeType::DamageAura, self.aura_level)
     }
 
-    /// Calculate damage reduction from armor
-    pub fn damage_reduction(&self) -> f32 {
-        (self.armor_level as f32 * 0.1).min(0.75) // Max 75% reduction
+    /// Calculate effective aura radius multiplier
+    pub fn aura_radius_multiplier(&self, catalog: &UpgradeCatalog) -> f32 {
+        1.0 + catalog.bonus(UpgradeType::AuraRadius, self.aura_radius_level)
     }
 
     /// Calculate number of additional projectiles
-    pub fn extra_projectiles(&self) -> u32 {
-        self.multi_shot_level * 2
+    pub fn extra_projectiles(&self, catalog: &UpgradeCatalog) -> u32 {
+        catalog.bonus(UpgradeType::MultiShot, self.multi_shot_level) as u32
     }
 }
@@ -0,0 +1,141 @@
+//! `Vec2`: a direction or rate in the plane, such as a velocity, as
+//! opposed to a `Position`, a point in the world. Points and vectors
+//! combine the usual way: `to - from` is the `Vec2` between two positions,
+//! and a position plus a vector is another position. Both go over the wire
+//! as `{"x": .., "y": ..}`.
+
+use crate::types::Position;
+use serde::{Deserialize, Serialize};
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2 { x: 0.0, y: 0.0 };
+
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    /// The unit vector pointing `angle` radians counterclockwise from +x
+    pub fn from_angle(angle: f32) -> Self {
+        Self::new(angle.cos(), angle.sin())
+    }
+
+    pub fn length(self) -> f32 {
+        (self.x * self.x + self.y * self.y).sqrt()
+    }
+
+    /// The same direction at length 1, or zero for the zero vector (or one
+    /// too short to have a direction)
+    pub fn normalize(self) -> Self {
+        let length = self.length();
+        if length > 0.0 {
+            Self::new(self.x / length, self.y / length)
+        } else {
+            Self::ZERO
+        }
+    }
+
+    /// Radians counterclockwise from +x, in -π..=π
+    pub fn angle(self) -> f32 {
+        self.y.atan2(self.x)
+    }
+
+    /// Turned `angle` radians counterclockwise
+    pub fn rotate(self, angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+    }
+
+    /// `t` of the way from `self` to `other`; `t` isn't clamped
+    pub fn lerp(self, other: Vec2, t: f32) -> Self {
+        self + (other - self) * t
+    }
+
+    /// False if either component is NaN or infinite
+    pub fn is_finite(self) -> bool {
+        self.x.is_finite() && self.y.is_finite()
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, scale: f32) -> Vec2 {
+        Vec2::new(self.x * scale, self.y * scale)
+    }
+}
+
+impl Div<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn div(self, scale: f32) -> Vec2 {
+        Vec2::new(self.x / scale, self.y / scale)
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Vec2;
+
+    fn neg(self) -> Vec2 {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+impl Sub for Position {
+    type Output = Vec2;
+
+    /// The vector from `other` to `self`
+    fn sub(self, other: Position) -> Vec2 {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Add<Vec2> for Position {
+    type Output = Position;
+
+    fn add(self, offset: Vec2) -> Position {
+        Position::new(self.x + offset.x, self.y + offset.y)
+    }
+}
+
+impl Sub<Vec2> for Position {
+    type Output = Position;
+
+    fn sub(self, offset: Vec2) -> Position {
+        Position::new(self.x - offset.x, self.y - offset.y)
+    }
+}
+
+impl AddAssign<Vec2> for Position {
+    fn add_assign(&mut self, offset: Vec2) {
+        *self = *self + offset;
+    }
+}
+
+impl SubAssign<Vec2> for Position {
+    fn sub_assign(&mut self, offset: Vec2) {
+        *self = *self - offset;
+    }
+}
@@ -0,0 +1,170 @@
+use shared::{
+    Enemy, OwnPlayerState, Pickup, Player, Projectile, RemotePlayerView, ServerMessage,
+    UpgradeText, UpgradeType,
+};
+use std::collections::VecDeque;
+use uuid::Uuid;
+
+/// Number of lines kept in the message log
+const LOG_CAPACITY: usize = 200;
+
+/// World units per map cell at zoom level 1
+const BASE_CELL_SIZE: f32 = 20.0;
+
+/// Everything the TUI knows about the server
+pub struct App {
+    pub player_id: Option<Uuid>,
+    pub own: Option<OwnPlayerState>, // ours in full; `players` has everyone in outline
+    pub players: Vec<RemotePlayerView>,
+    pub enemies: Vec<Enemy>,
+    pub projectiles: Vec<Projectile>,
+    pub pickups: Vec<Pickup>,
+    pub game_time: f64,
+    pub difficulty_tier: u32,
+    pub upgrade_choices: Vec<UpgradeType>,
+    pub upgrade_text: Vec<UpgradeText>, // `upgrade_choices` in our locale
+    pub log: VecDeque<String>,
+    pub zoom: f32,
+    pub connected: bool,
+    pub should_quit: bool,
+    pub input_seq: u32, // numbers Move inputs for the server to acknowledge
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            player_id: None,
+            own: None,
+            players: Vec::new(),
+            enemies: Vec::new(),
+            projectiles: Vec::new(),
+            pickups: Vec::new(),
+            game_time: 0.0,
+            difficulty_tier: 0,
+            upgrade_choices: Vec::new(),
+            upgrade_text: Vec::new(),
+            log: VecDeque::new(),
+            zoom: 1.0,
+            connected: false,
+            should_quit: false,
+            input_seq: 0,
+        }
+    }
+}
+
+impl App {
+    pub fn log(&mut self, line: impl Into<String>) {
+        self.log
+            .push_back(format!("[{:>7.1}] {}", self.game_time, line.into()));
+        while self.log.len() > LOG_CAPACITY {
+            self.log.pop_front();
+        }
+    }
+
+    /// Apply a message received from the server
+    pub fn apply(&mut self, msg: ServerMessage) {
+        match msg {
+            ServerMessage::Welcome { player_id } => {
+                self.player_id = Some(player_id);
+                self.log(format!("Welcome, player {}", player_id));
+            }
+            ServerMessage::GameState {
+                own_player,
+                players,
+                enemies,
+                projectiles,
+                pickups,
+                game_time,
+                difficulty_tier,
+                ..
+            } => {
+                self.own = own_player.map(|p| *p);
+                self.players = players;
+                self.enemies = enemies;
+                self.projectiles = projectiles;
+                self.pickups = pickups;
+                self.game_time = game_time;
+                self.difficulty_tier = difficulty_tier;
+            }
+            ServerMessage::PlayerDied {
+                player_id,
+                max_ring,
+                survival_time,
+                enemies_defeated,
+                score_recorded,
+                stats,
+                run_id,
+            } => {
+                self.log(format!(
+                    "Player {} died: ring {}, {:.1}s, {} kills, {:.0} damage dealt, {:.0} taken{}",
+                    player_id,
+                    max_ring,
+                    survival_time,
+                    enemies_defeated,
+                    stats.damage_dealt,
+                    stats.damage_taken,
+                    if score_recorded {
+                        " (score recorded)"
+                    } else {
+                        ""
+                    }
+                ));
+                if let Some(id) = run_id.filter(|_| Some(player_id) == self.player_id) {
+                    self.log(format!("Share your run: /runs/{}", id));
+                }
+            }
+            ServerMessage::Scoreboard { scores, changes } => {
+                self.log(format!("Scoreboard updated ({} entries)", scores.len()));
+                let ours = changes.iter().find(|c| Some(c.player_id) == self.player_id);
+                if let Some(rank) = ours.and_then(|c| c.to) {
+                    self.log(format!("Your run is now ranked #{}", rank));
+                }
+            }
+            ServerMessage::LevelUp {
+                player_id,
+                new_level,
+                upgrade_choices,
+                choice_text,
+                ..
+            } => {
+                // The server repeats open offers; only log new ones
+                if Some(player_id) == self.player_id && self.upgrade_choices != upgrade_choices {
+                    self.log(format!("Player {} reached level {}", player_id, new_level));
+                    self.upgrade_text = UpgradeText::for_choices(&upgrade_choices, choice_text);
+                    self.upgrade_choices = upgrade_choices;
+                }
+            }
+            ServerMessage::LevelUpExpired {
+                player_id,
+                auto_picked,
+            } if Some(player_id) == self.player_id => {
+                if let Some(upgrade) = auto_picked {
+                    self.log(format!("Upgrade offer timed out, took {}", upgrade.name()));
+                }
+                self.upgrade_choices.clear();
+            }
+            ServerMessage::Error { message, .. } => {
+                self.log(format!("Server error: {}", message));
+            }
+            ServerMessage::Announcement { text } => self.log(format!("Announcement: {}", text)),
+            other => self.log(format!("{:?}", other)),
+        }
+    }
+
+    pub fn own_player(&self) -> Option<&Player> {
+        self.own.as_ref()
+    }
+
+    /// World units covered by one map cell
+    pub fn cell_size(&self) -> f32 {
+        BASE_CELL_SIZE / self.zoom
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.zoom = (self.zoom * 2.0).min(8.0);
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.zoom = (self.zoom / 2.0).max(0.125);
+    }
+}
@@ -0,0 +1,179 @@
+//! Terminal debug client.
+//!
+//! Renders a top-down ASCII map around the player, their stats, and a log of
+//! server messages. Useful for poking at the server without a graphical
+//! client.
+
+mod app;
+mod ui;
+
+#[cfg(test)]
+mod tests;
+
+use app::App;
+use flate2::read::DeflateDecoder;
+use futures_util::{SinkExt, StreamExt};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use shared::{
+    ClientMessage, Locale, Position, ServerMessage, ACCOUNT_HEADER, DEFLATE_PROTOCOL,
+    PASSWORD_HEADER,
+};
+use std::io::Read;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+
+/// How far ahead of the player a keyboard move targets
+const MOVE_DISTANCE: f32 = 100.0;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let url = std::env::args()
+        .nth(1)
+        .or_else(|| std::env::var("SERVER_URL").ok())
+        .unwrap_or_else(|| "ws://localhost:3000/ws".to_string());
+
+    // Connect before taking over the terminal so errors print normally.
+    // Offer compression; big snapshots then arrive as deflated binary frames.
+    let mut request = url.as_str().into_client_request()?;
+    request.headers_mut().insert(
+        "Sec-WebSocket-Protocol",
+        HeaderValue::from_static(DEFLATE_PROTOCOL),
+    );
+    if let Ok(password) = std::env::var("SERVER_PASSWORD") {
+        request
+            .headers_mut()
+            .insert(PASSWORD_HEADER, HeaderValue::from_str(&password)?);
+    }
+    if let Ok(account) = std::env::var("SERVER_ACCOUNT") {
+        request
+            .headers_mut()
+            .insert(ACCOUNT_HEADER, HeaderValue::from_str(&account)?);
+    }
+    let (socket, _) = tokio_tungstenite::connect_async(request).await?;
+    let (mut sink, mut stream) = socket.split();
+    let join = ClientMessage::join(Locale::from_env());
+    sink.send(encode(&join)).await?;
+
+    // crossterm's reader blocks, so keep it off the runtime
+    let (key_tx, mut key_rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || loop {
+        if event::poll(Duration::from_millis(100)).unwrap_or(false) {
+            if let Ok(Event::Key(key)) = event::read() {
+                if key_tx.send(key).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut app = App {
+        connected: true,
+        ..App::default()
+    };
+    app.log(format!("Connected to {}", url));
+
+    let mut terminal = ratatui::init();
+    let mut redraw = tokio::time::interval(Duration::from_millis(100));
+
+    let result: anyhow::Result<()> = async {
+        while !app.should_quit {
+            tokio::select! {
+                msg = stream.next(), if app.connected => match msg {
+                    Some(Ok(frame @ (Message::Text(_) | Message::Binary(_)))) => match decode(frame) {
+                        Ok(msg) => app.apply(msg),
+                        Err(e) => app.log(format!("Unparseable message: {}", e)),
+                    },
+                    Some(Ok(Message::Close(_))) | None => {
+                        app.connected = false;
+                        app.log("Disconnected from server");
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => {
+                        app.connected = false;
+                        app.log(format!("Connection error: {}", e));
+                    }
+                },
+                Some(key) = key_rx.recv() => {
+                    if let Some(out) = handle_key(&mut app, key) {
+                        if app.connected {
+                            sink.send(encode(&out)).await?;
+                        }
+                    }
+                }
+                _ = redraw.tick() => {
+                    terminal.draw(|frame| ui::draw(frame, &app))?;
+                }
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    ratatui::restore();
+    result
+}
+
+/// Update local state for a key press, returning a message to send if any
+fn handle_key(app: &mut App, key: KeyEvent) -> Option<ClientMessage> {
+    if key.kind != KeyEventKind::Press {
+        return None;
+    }
+
+    let (dx, dy) = match key.code {
+        KeyCode::Char('q') | KeyCode::Esc => {
+            app.should_quit = true;
+            return None;
+        }
+        KeyCode::Char('+') | KeyCode::Char('=') => {
+            app.zoom_in();
+            return None;
+        }
+        KeyCode::Char('-') => {
+            app.zoom_out();
+            return None;
+        }
+        KeyCode::Char(c @ '1'..='3') => {
+            let index = c as usize - '1' as usize;
+            let upgrade = *app.upgrade_choices.get(index)?;
+            app.upgrade_choices.clear();
+            app.log(format!("Chose upgrade {}", upgrade.name()));
+            return Some(ClientMessage::ChooseUpgrade { upgrade });
+        }
+        KeyCode::Char('w') | KeyCode::Up => (0.0, -1.0),
+        KeyCode::Char('s') | KeyCode::Down => (0.0, 1.0),
+        KeyCode::Char('a') | KeyCode::Left => (-1.0, 0.0),
+        KeyCode::Char('d') | KeyCode::Right => (1.0, 0.0),
+        _ => return None,
+    };
+
+    let me = app.own_player()?;
+    let target = Position::new(
+        me.position.x + dx * MOVE_DISTANCE,
+        me.position.y + dy * MOVE_DISTANCE,
+    );
+    app.input_seq += 1;
+    Some(ClientMessage::Move {
+        target,
+        seq: app.input_seq,
+    })
+}
+
+/// Parse a server frame; binary frames hold deflated JSON
+fn decode(frame: Message) -> anyhow::Result<ServerMessage> {
+    let msg = match frame {
+        Message::Binary(bytes) => {
+            let mut json = Vec::new();
+            DeflateDecoder::new(&bytes[..]).read_to_end(&mut json)?;
+            serde_json::from_slice(&json)?
+        }
+        other => serde_json::from_str(other.to_text()?)?,
+    };
+    Ok(msg)
+}
+
+fn encode(msg: &ClientMessage) -> Message {
+    Message::text(serde_json::to_string(msg).expect("client messages always serialize"))
+}
@@ -0,0 +1,154 @@
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use shared::{ClientMessage, Player, Position, RemotePlayerView, ServerMessage, UpgradeType};
+use std::io::Write;
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+
+use crate::app::App;
+use crate::{decode, encode, handle_key};
+
+fn press(code: KeyCode) -> KeyEvent {
+    KeyEvent::new(code, KeyModifiers::NONE)
+}
+
+fn game_state(me: &Player) -> ServerMessage {
+    ServerMessage::GameState {
+        players: vec![RemotePlayerView::of(me, 2.0)],
+        own_player: Some(Box::new(me.clone())),
+        enemies: vec![],
+        projectiles: vec![],
+        pickups: vec![],
+        summons: vec![],
+        game_time: 2.0,
+        difficulty_tier: 1,
+        tick: 40,
+        server_time_ms: 1_700_000_000_000,
+    }
+}
+
+#[test]
+fn test_movement_keys_target_ahead_of_the_player_with_rising_seqs() {
+    let mut app = App::default();
+    let id = Uuid::new_v4();
+    app.apply(ServerMessage::Welcome { player_id: id });
+    assert!(handle_key(&mut app, press(KeyCode::Char('d'))).is_none());
+
+    let mut me = Player::new(id);
+    me.position = Position::new(50.0, 50.0);
+    app.apply(game_state(&me));
+    assert_eq!(app.own_player().map(|p| p.id), Some(id));
+
+    let Some(ClientMessage::Move { target, seq }) = handle_key(&mut app, press(KeyCode::Right))
+    else {
+        panic!("arrow keys move");
+    };
+    assert_eq!((target.x, target.y, seq), (150.0, 50.0, 1));
+    let Some(ClientMessage::Move { target, seq }) = handle_key(&mut app, press(KeyCode::Char('w')))
+    else {
+        panic!("WASD moves");
+    };
+    assert_eq!((target.x, target.y, seq), (50.0, -50.0, 2));
+
+    let mut release = press(KeyCode::Char('w'));
+    release.kind = KeyEventKind::Release;
+    assert!(handle_key(&mut app, release).is_none());
+    assert_eq!(app.input_seq, 2);
+}
+
+#[test]
+fn test_number_keys_pick_from_our_own_offer_once() {
+    let mut app = App::default();
+    let id = Uuid::new_v4();
+    app.apply(ServerMessage::Welcome { player_id: id });
+    let offer = |player_id| ServerMessage::LevelUp {
+        player_id,
+        new_level: 2,
+        upgrade_choices: vec![UpgradeType::Armor, UpgradeType::Luck],
+        choice_rarities: Vec::new(),
+        choice_text: Vec::new(),
+    };
+
+    app.apply(offer(Uuid::new_v4()));
+    assert!(handle_key(&mut app, press(KeyCode::Char('1'))).is_none());
+
+    app.apply(offer(id));
+    let logged = app.log.len();
+    app.apply(offer(id)); // the server repeats open offers
+    assert_eq!(app.log.len(), logged);
+
+    assert!(handle_key(&mut app, press(KeyCode::Char('3'))).is_none());
+    assert!(matches!(
+        handle_key(&mut app, press(KeyCode::Char('2'))),
+        Some(ClientMessage::ChooseUpgrade {
+            upgrade: UpgradeType::Luck
+        })
+    ));
+    assert!(app.upgrade_choices.is_empty());
+
+    app.apply(offer(id));
+    app.apply(ServerMessage::LevelUpExpired {
+        player_id: id,
+        auto_picked: Some(UpgradeType::Armor),
+    });
+    assert!(app.upgrade_choices.is_empty());
+}
+
+#[test]
+fn test_zoom_is_clamped_and_quit_keys_stop_the_client() {
+    let mut app = App::default();
+    for _ in 0..10 {
+        handle_key(&mut app, press(KeyCode::Char('+')));
+    }
+    assert_eq!(app.zoom, 8.0);
+    for _ in 0..10 {
+        handle_key(&mut app, press(KeyCode::Char('-')));
+    }
+    assert_eq!(app.zoom, 0.125);
+    assert_eq!(app.cell_size(), 160.0);
+
+    assert!(!app.should_quit);
+    handle_key(&mut app, press(KeyCode::Esc));
+    assert!(app.should_quit);
+}
+
+#[test]
+fn test_log_keeps_only_the_latest_lines() {
+    let mut app = App::default();
+    for i in 0..250 {
+        app.log(format!("line {}", i));
+    }
+    assert_eq!(app.log.len(), 200);
+    assert!(app.log.front().unwrap().ends_with("line 50"));
+    assert!(app.log.back().unwrap().ends_with("line 249"));
+}
+
+#[test]
+fn test_frames_decode_from_text_and_deflated_binary() {
+    let id = Uuid::new_v4();
+    let json = serde_json::to_vec(&ServerMessage::Welcome { player_id: id }).unwrap();
+    let mut deflated = DeflateEncoder::new(Vec::new(), Compression::default());
+    deflated.write_all(&json).unwrap();
+    let binary = Message::binary(deflated.finish().unwrap());
+    let text = Message::text(String::from_utf8(json).unwrap());
+
+    for frame in [text, binary] {
+        assert!(matches!(
+            decode(frame).unwrap(),
+            ServerMessage::Welcome { player_id } if player_id == id
+        ));
+    }
+    assert!(decode(Message::text("not json")).is_err());
+
+    let sent = encode(&ClientMessage::ChooseUpgrade {
+        upgrade: UpgradeType::Armor,
+    });
+    let back: ClientMessage = serde_json::from_str(sent.to_text().unwrap()).unwrap();
+    assert!(matches!(
+        back,
+        ClientMessage::ChooseUpgrade {
+            upgrade: UpgradeType::Armor
+        }
+    ));
+}
@@ -0,0 +1,219 @@
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
+use ratatui::Frame;
+use shared::{EnemyType, Position};
+
+use crate::app::App;
+
+/// World layout mirrored from the server's default `GameConfig`
+const SAFE_ZONE_RADIUS: f32 = 100.0;
+const RING_RADIUS: f32 = 200.0;
+
+/// Terminal cells are roughly twice as tall as they are wide
+const CELL_ASPECT: f32 = 2.0;
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [top, log_area] =
+        Layout::vertical([Constraint::Min(10), Constraint::Length(10)]).areas(frame.area());
+    let [map_area, stats_area] =
+        Layout::horizontal([Constraint::Min(20), Constraint::Length(36)]).areas(top);
+
+    draw_map(frame, app, map_area);
+    draw_stats(frame, app, stats_area);
+    draw_log(frame, app, log_area);
+}
+
+fn draw_map(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Map (1 cell = {:.0}u) ", app.cell_size()));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let Some(me) = app.own_player() else {
+        let status = if app.connected {
+            "Joining..."
+        } else {
+            "Connecting..."
+        };
+        frame.render_widget(Paragraph::new(status), inner);
+        return;
+    };
+
+    let width = inner.width as usize;
+    let height = inner.height as usize;
+    let cell_w = app.cell_size();
+    let cell_h = cell_w * CELL_ASPECT;
+    let center = me.position;
+
+    let to_world = |col: usize, row: usize| {
+        Position::new(
+            center.x + (col as f32 - width as f32 / 2.0) * cell_w,
+            center.y + (row as f32 - height as f32 / 2.0) * cell_h,
+        )
+    };
+    let to_cell = |pos: &Position| -> Option<(usize, usize)> {
+        let col = ((pos.x - center.x) / cell_w + width as f32 / 2.0).floor();
+        let row = ((pos.y - center.y) / cell_h + height as f32 / 2.0).floor();
+        if col >= 0.0 && row >= 0.0 && (col as usize) < width && (row as usize) < height {
+            Some((col as usize, row as usize))
+        } else {
+            None
+        }
+    };
+
+    let mut grid = vec![vec![(' ', Color::Reset); width]; height];
+
+    // Terrain: safe zone fill and ring boundaries
+    for (row, cells) in grid.iter_mut().enumerate() {
+        for (col, cell) in cells.iter_mut().enumerate() {
+            let distance = to_world(col, row).distance_from_center();
+            if distance <= SAFE_ZONE_RADIUS {
+                *cell = ('.', Color::Green);
+            } else {
+                let from_boundary = (distance / RING_RADIUS).fract() * RING_RADIUS;
+                if from_boundary < cell_w || RING_RADIUS - from_boundary < cell_w {
+                    *cell = (':', Color::DarkGray);
+                }
+            }
+        }
+    }
+
+    for pickup in &app.pickups {
+        if let Some((col, row)) = to_cell(&pickup.position) {
+            grid[row][col] = ('+', Color::LightGreen);
+        }
+    }
+    for projectile in &app.projectiles {
+        if let Some((col, row)) = to_cell(&projectile.position) {
+            grid[row][col] = ('*', Color::Yellow);
+        }
+    }
+    for enemy in &app.enemies {
+        if let Some((col, row)) = to_cell(&enemy.position) {
+            grid[row][col] = enemy_glyph(enemy.enemy_type);
+        }
+    }
+    for player in &app.players {
+        if let Some((col, row)) = to_cell(&player.position) {
+            grid[row][col] = if player.id == me.id {
+                ('@', Color::LightGreen)
+            } else {
+                ('P', Color::LightBlue)
+            };
+        }
+    }
+
+    let lines: Vec<Line> = grid
+        .into_iter()
+        .map(|row| {
+            Line::from(
+                row.into_iter()
+                    .map(|(c, color)| Span::styled(c.to_string(), Style::default().fg(color)))
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+fn draw_stats(frame: &mut Frame, app: &App, area: Rect) {
+    let mut lines = Vec::new();
+
+    match app.own_player() {
+        Some(p) => {
+            lines.push(Line::from(format!("ID:      {}", &p.id.to_string()[..8])));
+            lines.push(Line::from(format!(
+                "Pos:     ({:.0}, {:.0})",
+                p.position.x, p.position.y
+            )));
+            lines.push(Line::from(format!(
+                "Ring:    {} (max {})",
+                p.position.ring(RING_RADIUS),
+                p.max_ring_reached
+            )));
+            lines.push(Line::from(format!(
+                "HP:      {:.0}/{:.0}",
+                p.health, p.max_health
+            )));
+            lines.push(Line::from(format!(
+                "Level:   {} ({}/{} XP)",
+                p.level, p.current_xp, p.xp_to_next_level
+            )));
+            lines.push(Line::from(format!("Damage:  {:.1}", p.damage)));
+            lines.push(Line::from(format!("Atk/s:   {:.2}", p.attack_speed)));
+            lines.push(Line::from(format!("Speed:   {:.0}", p.movement_speed)));
+            lines.push(Line::from(format!("Kills:   {}", p.enemies_defeated)));
+        }
+        None => lines.push(Line::from("Not in game")),
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(format!("Time:    {:.1}s", app.game_time)));
+    lines.push(Line::from(format!("Tier:    {}", app.difficulty_tier)));
+    lines.push(Line::from(format!("Players: {}", app.players.len())));
+    lines.push(Line::from(format!("Enemies: {}", app.enemies.len())));
+    lines.push(Line::from(format!("Shots:   {}", app.projectiles.len())));
+
+    if !app.upgrade_choices.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::styled(
+            "LEVEL UP!",
+            Style::default().fg(Color::Yellow),
+        ));
+        for (i, text) in app.upgrade_text.iter().enumerate() {
+            lines.push(Line::from(format!(
+                "[{}] {} ({})",
+                i + 1,
+                text.name,
+                text.rarity
+            )));
+        }
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::styled(
+        "WASD/arrows move, 1-3 upgrade",
+        Style::default().fg(Color::DarkGray),
+    ));
+    lines.push(Line::styled(
+        "+/- zoom, q quit",
+        Style::default().fg(Color::DarkGray),
+    ));
+
+    let block = Block::default().borders(Borders::ALL).title(" Stats ");
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn draw_log(frame: &mut Frame, app: &App, area: Rect) {
+    let visible = area.height.saturating_sub(2) as usize;
+    let lines: Vec<Line> = app
+        .log
+        .iter()
+        .skip(app.log.len().saturating_sub(visible))
+        .map(|l| Line::from(l.as_str()))
+        .collect();
+
+    let block = Block::default().borders(Borders::ALL).title(" Log ");
+    frame.render_widget(
+        Paragraph::new(lines).block(block).wrap(Wrap { trim: true }),
+        area,
+    );
+}
+
+fn enemy_glyph(enemy_type: EnemyType) -> (char, Color) {
+    match enemy_type {
+        EnemyType::Goblin => ('g', Color::Yellow),
+        EnemyType::Orc => ('o', Color::LightRed),
+        EnemyType::Wolf => ('w', Color::Red),
+        EnemyType::Skeleton => ('s', Color::Gray),
+        EnemyType::Zombie => ('z', Color::Green),
+        EnemyType::Demon => ('D', Color::Red),
+        EnemyType::Wraith => ('W', Color::Magenta),
+        EnemyType::Troll => ('T', Color::Cyan),
+        EnemyType::Dragon => ('R', Color::LightMagenta),
+        EnemyType::Lich => ('L', Color::LightCyan),
+    }
+}